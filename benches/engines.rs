@@ -0,0 +1,220 @@
+//! Regression benchmarks of parsing, the engines and the transaction stores
+//!
+//! `parsing_strategies` compares whole runs of the strategies on the fixture
+//! files. This suite measures the layers separately, on generated workloads,
+//! with criterion so a run can be compared against a saved baseline:
+//!
+//! - `parse`: reading CSV records, without processing them
+//! - `sync_engine` and `async_engine`: processing parsed records, with the
+//!   workload spread over 1 to 4,096 clients; the async engine processes
+//!   batches of 1,000 records partitioned by client, as the async strategy does
+//! - `transaction_store`: storing and looking up deposits in the synchronous
+//!   and async stores, with and without the duplicate filter
+//!
+//! # Detecting Regressions
+//!
+//! ```bash
+//! # Record a baseline on the main branch
+//! git checkout main && cargo bench --bench engines -- --save-baseline main
+//!
+//! # Compare a change against it; criterion reports changes beyond the noise
+//! git checkout my-change && cargo bench --bench engines -- --baseline main
+//!
+//! # Only the async engine
+//! cargo bench --bench engines -- async_engine
+//! ```
+//!
+//! Features that change the layers measured here (`compact-store`, `fxhash`,
+//! `ahash`, `fast-csv`) are compared the same way, saving the baseline without
+//! the feature and comparing a run with it.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rust_decimal::Decimal;
+use rust_payments_engine::core::{
+    AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore, DuplicateFilter,
+    TransactionEngine, TransactionStore,
+};
+#[cfg(feature = "fast-csv")]
+use rust_payments_engine::io::FastCsvReader;
+use rust_payments_engine::io::SyncReader;
+use rust_payments_engine::types::{
+    ClientId, DisputeState, StoredTransaction, TransactionRecord, TransactionType,
+};
+use std::fmt::Write as _;
+use std::hint::black_box;
+use std::sync::Arc;
+
+/// Transactions per workload
+const TRANSACTIONS: u64 = 100_000;
+
+/// Client cardinalities of the engine benchmarks
+const CLIENTS: [u64; 4] = [1, 16, 256, 4096];
+
+/// Records per batch of the async engine
+const BATCH_SIZE: usize = 1000;
+
+/// CSV of `TRANSACTIONS` transactions spread round-robin over `clients`
+/// clients
+///
+/// Each client's transactions repeat a cycle of ten: seven deposits, a
+/// withdrawal, a dispute of the cycle's first deposit and its resolve.
+fn workload_csv(clients: u64) -> String {
+    let mut csv = String::from("type,client,tx,amount\n");
+    for tx in 1..=TRANSACTIONS {
+        let client = tx % clients + 1;
+        let _ = match (tx / clients) % 10 {
+            7 => writeln!(csv, "withdrawal,{},{},2.5", client, tx),
+            8 => writeln!(csv, "dispute,{},{},", client, tx - 8 * clients),
+            9 => writeln!(csv, "resolve,{},{},", client, tx - 9 * clients),
+            _ => writeln!(csv, "deposit,{},{},10.0", client, tx),
+        };
+    }
+    csv
+}
+
+/// The records of a workload, parsed
+fn workload(clients: u64) -> Vec<TransactionRecord> {
+    SyncReader::from_reader(workload_csv(clients).as_bytes())
+        .expect("Invalid workload header")
+        .collect::<Result<_, _>>()
+        .expect("Invalid workload record")
+}
+
+fn parse(c: &mut Criterion) {
+    let csv = workload_csv(256);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(TRANSACTIONS));
+    group.bench_function("sync_reader", |b| {
+        b.iter(|| {
+            SyncReader::from_reader(black_box(csv.as_bytes()))
+                .expect("Invalid workload header")
+                .filter(Result::is_ok)
+                .count()
+        })
+    });
+    #[cfg(feature = "fast-csv")]
+    group.bench_function("fast_csv_reader", |b| {
+        b.iter(|| {
+            FastCsvReader::from_reader(black_box(csv.as_bytes()))
+                .expect("Invalid workload header")
+                .filter(Result::is_ok)
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn sync_engine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sync_engine");
+    group.throughput(Throughput::Elements(TRANSACTIONS));
+    for clients in CLIENTS {
+        let records = workload(clients);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(clients),
+            &records,
+            |b, records| {
+                b.iter_batched(
+                    || records.clone(),
+                    |records| {
+                        let mut engine = TransactionEngine::new();
+                        for record in records {
+                            let _ = engine.process(record);
+                        }
+                        engine
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn async_engine(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start the runtime");
+    let mut group = c.benchmark_group("async_engine");
+    group.throughput(Throughput::Elements(TRANSACTIONS));
+    for clients in CLIENTS {
+        let records = workload(clients);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(clients),
+            &records,
+            |b, records| {
+                b.iter_batched(
+                    || records.clone(),
+                    |records| {
+                        let engine = AsyncTransactionEngine::new(
+                            Arc::new(AsyncAccountManager::new()),
+                            Arc::new(AsyncTransactionStore::new()),
+                        );
+                        runtime.block_on(async {
+                            for batch in records.chunks(BATCH_SIZE) {
+                                engine.process_batch(batch.to_vec()).await;
+                            }
+                        });
+                        engine
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+/// A stored deposit of one of 256 clients
+fn stored_deposit(tx_id: u64) -> StoredTransaction {
+    StoredTransaction {
+        client: (tx_id % 256) as ClientId,
+        amount: Decimal::new(tx_id as i64 % 10_000, 2),
+        tx_type: TransactionType::Deposit,
+        dispute_state: DisputeState::None,
+        disputes: 0,
+    }
+}
+
+fn transaction_store(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transaction_store");
+    group.throughput(Throughput::Elements(TRANSACTIONS));
+    group.bench_function("sync", |b| {
+        b.iter(|| {
+            let mut store = TransactionStore::new();
+            for tx_id in 0..TRANSACTIONS {
+                if !store.contains(tx_id) {
+                    store.store(tx_id, stored_deposit(tx_id));
+                }
+            }
+            (0..TRANSACTIONS)
+                .filter_map(|tx_id| store.get(black_box(tx_id)))
+                .count()
+        })
+    });
+    for filtered in [false, true] {
+        let name = if filtered { "async_filtered" } else { "async" };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut store = AsyncTransactionStore::new();
+                if filtered {
+                    store = store
+                        .with_duplicate_filter(DuplicateFilter::new(TRANSACTIONS as usize, 0.01));
+                }
+                for tx_id in 0..TRANSACTIONS {
+                    if !store.contains(tx_id) {
+                        store.store(tx_id, stored_deposit(tx_id));
+                    }
+                }
+                (0..TRANSACTIONS)
+                    .filter_map(|tx_id| store.get(black_box(tx_id)))
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = parse, sync_engine, async_engine, transaction_store
+}
+criterion_main!(benches);
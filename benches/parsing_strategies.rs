@@ -8,6 +8,18 @@
 //! ```bash
 //! # Run all benchmarks
 //! cargo bench
+//!
+//! # Include the fast CSV reader
+//! cargo bench --features fast-csv
+//!
+//! # Only the duplicate check, with and without the bloom filter
+//! cargo bench -- duplicate
+//!
+//! # Map lookups with SipHash and with the hasher selected by features
+//! cargo bench --features fxhash -- map_lookup
+//!
+//! # The async strategy under injected delays and transient errors
+//! cargo bench --features fault-injection -- faults
 //! ```
 //!
 //! # Benchmark Fixtures
@@ -22,9 +34,20 @@
 //! - Multiple clients
 //! - Dispute resolution flows
 
-use rust_payments_engine::cli::StrategyType;
+use rust_decimal::Decimal;
+use rust_payments_engine::cli::{InputFormat, StrategyType};
+use rust_payments_engine::core::hash::KEY_HASHER_NAME;
+use rust_payments_engine::core::{AsyncTransactionStore, DuplicateFilter, EngineConfig, KeyHasher};
+#[cfg(feature = "fault-injection")]
+use rust_payments_engine::core::{FaultInjection, RetryPolicy};
 use rust_payments_engine::strategy::create_strategy;
-use rust_payments_engine::strategy::BatchConfig;
+#[cfg(feature = "fast-csv")]
+use rust_payments_engine::strategy::InputOptions;
+use rust_payments_engine::strategy::{BatchConfig, DuplicateFilterOptions};
+use rust_payments_engine::types::{DisputeState, StoredTransaction, TransactionType};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::path::Path;
 
 fn main() {
@@ -34,7 +57,13 @@ fn main() {
 /// Benchmark synchronous processing strategy with small dataset (100 transactions)
 #[divan::bench]
 fn sync_strategy_small() {
-    let strategy = create_strategy(StrategyType::Sync, None);
+    let strategy = create_strategy(
+        StrategyType::Sync,
+        None,
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
     let path = Path::new("benches/fixtures/benchmark_small.csv");
     let mut output = Vec::new();
 
@@ -46,7 +75,13 @@ fn sync_strategy_small() {
 /// Benchmark asynchronous processing strategy with small dataset (100 transactions)
 #[divan::bench]
 fn async_strategy_small() {
-    let strategy = create_strategy(StrategyType::Async, Some(BatchConfig::default()));
+    let strategy = create_strategy(
+        StrategyType::Async,
+        Some(BatchConfig::default()),
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
     let path = Path::new("benches/fixtures/benchmark_small.csv");
     let mut output = Vec::new();
 
@@ -58,7 +93,13 @@ fn async_strategy_small() {
 /// Benchmark synchronous processing strategy with medium dataset (1,000 transactions)
 #[divan::bench]
 fn sync_strategy_medium() {
-    let strategy = create_strategy(StrategyType::Sync, None);
+    let strategy = create_strategy(
+        StrategyType::Sync,
+        None,
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
     let path = Path::new("benches/fixtures/benchmark_medium.csv");
     let mut output = Vec::new();
 
@@ -70,7 +111,13 @@ fn sync_strategy_medium() {
 /// Benchmark asynchronous processing strategy with medium dataset (1,000 transactions)
 #[divan::bench]
 fn async_strategy_medium() {
-    let strategy = create_strategy(StrategyType::Async, Some(BatchConfig::default()));
+    let strategy = create_strategy(
+        StrategyType::Async,
+        Some(BatchConfig::default()),
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
     let path = Path::new("benches/fixtures/benchmark_medium.csv");
     let mut output = Vec::new();
 
@@ -82,7 +129,13 @@ fn async_strategy_medium() {
 /// Benchmark synchronous processing strategy with large dataset (1,000,000 transactions)
 #[divan::bench]
 fn sync_strategy_large() {
-    let strategy = create_strategy(StrategyType::Sync, None);
+    let strategy = create_strategy(
+        StrategyType::Sync,
+        None,
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
     let path = Path::new("benches/fixtures/benchmark_large.csv");
     let mut output = Vec::new();
 
@@ -94,7 +147,33 @@ fn sync_strategy_large() {
 /// Benchmark asynchronous processing strategy with large dataset (1,000,000 transactions)
 #[divan::bench]
 fn async_strategy_large() {
-    let strategy = create_strategy(StrategyType::Async, Some(BatchConfig::default()));
+    let strategy = create_strategy(
+        StrategyType::Async,
+        Some(BatchConfig::default()),
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
+    let path = Path::new("benches/fixtures/benchmark_large.csv");
+    let mut output = Vec::new();
+
+    strategy
+        .process(path, &mut output)
+        .expect("Processing failed");
+}
+
+/// Benchmark synchronous processing with the fast CSV reader with large dataset
+/// (1,000,000 transactions)
+#[cfg(feature = "fast-csv")]
+#[divan::bench]
+fn sync_strategy_fast_csv_large() {
+    let strategy = create_strategy(
+        StrategyType::Sync,
+        None,
+        InputOptions::new(InputFormat::Csv).with_fast_csv(true),
+        EngineConfig::default(),
+        None,
+    );
     let path = Path::new("benches/fixtures/benchmark_large.csv");
     let mut output = Vec::new();
 
@@ -102,3 +181,108 @@ fn async_strategy_large() {
         .process(path, &mut output)
         .expect("Processing failed");
 }
+
+/// Benchmark asynchronous processing with the duplicate filter with large
+/// dataset (1,000,000 transactions)
+#[divan::bench]
+fn async_strategy_duplicate_filter_large() {
+    let config = BatchConfig::builder()
+        .duplicate_filter(DuplicateFilterOptions::new(0.01))
+        .build()
+        .expect("Invalid batch config");
+    let strategy = create_strategy(
+        StrategyType::Async,
+        Some(config),
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
+    let path = Path::new("benches/fixtures/benchmark_large.csv");
+    let mut output = Vec::new();
+
+    strategy
+        .process(path, &mut output)
+        .expect("Processing failed");
+}
+
+/// Benchmark asynchronous processing with 5% of the processing attempts
+/// failing with a transient error and retried, and 10% of the transactions
+/// delayed by up to 1ms, with medium dataset (1,000 transactions)
+#[cfg(feature = "fault-injection")]
+#[divan::bench]
+fn async_strategy_faults_medium() {
+    let config = BatchConfig::builder()
+        .retry(RetryPolicy::new(10, std::time::Duration::ZERO))
+        .faults(
+            FaultInjection::new(0)
+                .with_errors(0.05)
+                .with_delays(0.1, std::time::Duration::from_millis(1)),
+        )
+        .build()
+        .expect("Invalid batch config");
+    let strategy = create_strategy(
+        StrategyType::Async,
+        Some(config),
+        InputFormat::Csv,
+        EngineConfig::default(),
+        None,
+    );
+    let path = Path::new("benches/fixtures/benchmark_medium.csv");
+    let mut output = Vec::new();
+
+    strategy
+        .process(path, &mut output)
+        .expect("Processing failed");
+}
+
+/// Benchmark the duplicate check of a new transaction ID against a store of
+/// 1,000,000 transactions, without and with the duplicate filter
+#[divan::bench(args = [false, true])]
+fn duplicate_check_new_id(bencher: divan::Bencher, filtered: bool) {
+    let mut store = AsyncTransactionStore::new();
+    if filtered {
+        store = store.with_duplicate_filter(DuplicateFilter::new(1_000_000, 0.01));
+    }
+    for tx_id in 0..1_000_000 {
+        store.store(
+            tx_id,
+            StoredTransaction {
+                client: 1,
+                amount: Decimal::ONE,
+                tx_type: TransactionType::Deposit,
+                dispute_state: DisputeState::None,
+                disputes: 0,
+            },
+        );
+    }
+
+    let mut tx_id = 1_000_000;
+    bencher.bench_local(|| {
+        tx_id += 1;
+        store.contains(divan::black_box(tx_id))
+    });
+}
+
+/// Look up 1,024 of 65,536 accounts by client ID in a map using hasher `H`
+fn lookup_accounts<H: BuildHasher + Default>(bencher: divan::Bencher) {
+    let accounts: HashMap<u64, Decimal, H> =
+        (0..65_536).map(|client| (client, Decimal::ONE)).collect();
+
+    bencher.bench_local(|| {
+        (0..1_024u64)
+            .map(|i| i * 7919 % 65_536)
+            .filter_map(|client| accounts.get(&divan::black_box(client)))
+            .count()
+    });
+}
+
+/// Benchmark account lookups with SipHash and with the hasher selected by
+/// the `fxhash` and `ahash` features
+#[divan::bench(args = ["siphash", KEY_HASHER_NAME])]
+fn map_lookup(bencher: divan::Bencher, hasher: &str) {
+    if hasher == "siphash" {
+        lookup_accounts::<RandomState>(bencher);
+    } else {
+        lookup_accounts::<KeyHasher>(bencher);
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * C bindings for the rust-payments-engine sync engine.
+ *
+ * Built from the library with the `ffi` feature, e.g.
+ *   cargo rustc --release --lib --no-default-features --features ffi --crate-type cdylib
+ *
+ * Amounts are signed 64-bit fixed-point integers in units of 1/10,000
+ * (12345 is 1.2345). Functions returning int32_t return a PE_* status code;
+ * after a failure, pe_engine_last_error() describes it. Messages of rejected
+ * transactions start with a stable error code, e.g. "[E301] Insufficient funds".
+ *
+ * An engine must not be used by several threads at the same time.
+ */
+
+#ifndef PAYMENTS_ENGINE_H
+#define PAYMENTS_ENGINE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes */
+#define PE_OK (0)
+#define PE_NULL_ARGUMENT (-1)
+#define PE_INVALID_ARGUMENT (-2)
+#define PE_REJECTED (-3)
+#define PE_NOT_FOUND (-4)
+
+/* Transaction types accepted by pe_engine_process() */
+#define PE_DEPOSIT (0)
+#define PE_WITHDRAWAL (1)
+#define PE_DISPUTE (2)
+#define PE_RESOLVE (3)
+#define PE_CHARGEBACK (4)
+#define PE_APPROVE (5)
+#define PE_REJECT (6)
+
+/* Opaque engine handle */
+typedef struct PeEngine PeEngine;
+
+/* Balances of one account, amounts in 1/10,000 units */
+typedef struct PeAccount {
+    uint64_t client;
+    int64_t available;
+    int64_t held;
+    int64_t total;
+    bool locked;
+} PeAccount;
+
+/* Create an engine; release it with pe_engine_free() */
+PeEngine *pe_engine_new(void);
+
+/* Release an engine; null is ignored */
+void pe_engine_free(PeEngine *engine);
+
+/* Apply one transaction; amount is only used for deposits and withdrawals */
+int32_t pe_engine_process(PeEngine *engine, uint8_t tx_type, uint64_t client, uint64_t tx,
+                          int64_t amount);
+
+/* Read the balances of one account; PE_NOT_FOUND if it does not exist */
+int32_t pe_engine_account(PeEngine *engine, uint64_t client, PeAccount *out);
+
+/* Copy up to capacity accounts, sorted by client ID; returns the total count */
+size_t pe_engine_accounts(const PeEngine *engine, PeAccount *out, size_t capacity);
+
+/* Message of the last failed call, or null; valid until the next call */
+const char *pe_engine_last_error(const PeEngine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PAYMENTS_ENGINE_H */
@@ -0,0 +1,187 @@
+//! `apply` subcommand
+//!
+//! Applies a delta file (the records added since an earlier run) on top of
+//! the state that run saved with `--save-state`, instead of reprocessing every
+//! record from the start:
+//!
+//! ```bash
+//! payments-engine --save-state state.bin day1.csv > accounts.csv
+//! payments-engine apply --state state.bin day2-delta.csv > accounts.csv
+//! ```
+//!
+//! The delta is processed by the sync strategy, with default options, against
+//! the saved accounts and stored transactions, so it may dispute deposits of
+//! the earlier run, and records already applied are rejected as duplicates.
+//! The final accounts are written to the output and the updated state replaces
+//! the state file, or is saved to `--save-state` if given.
+
+use super::args::expand_input_paths;
+use crate::core::load_state;
+use crate::io::create_sink;
+use crate::strategy::{ProcessingStrategy, RunSummary, SyncProcessingStrategy};
+use clap::Args;
+use std::path::PathBuf;
+
+/// Arguments of the `apply` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct ApplyArgs {
+    /// State file to continue from
+    #[arg(
+        long = "state",
+        value_name = "FILE",
+        help = "State file written with --save-state to apply the delta on; replaced with the updated state"
+    )]
+    pub state: PathBuf,
+
+    /// Delta files holding the records added since the state was saved
+    #[arg(
+        value_name = "INPUT",
+        required = true,
+        help = "Delta files to process (sync strategy, default options), in order"
+    )]
+    pub input_files: Vec<PathBuf>,
+
+    /// Where to write the final account states
+    #[arg(
+        long = "output",
+        short = 'o',
+        value_name = "TARGET",
+        default_value = "-",
+        help = "Write the final accounts to TARGET: '-' for stdout, or a file path"
+    )]
+    pub output: String,
+
+    /// Where to save the updated state instead of replacing `--state`
+    #[arg(
+        long = "save-state",
+        value_name = "FILE",
+        help = "Save the updated state to FILE instead of replacing the --state file"
+    )]
+    pub save_state: Option<PathBuf>,
+}
+
+impl ApplyArgs {
+    /// Apply the delta files, writing the final accounts and updated state
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RunSummary)` - The summary of the delta records
+    /// * `Err(String)` - If the state or a delta file cannot be read, or the
+    ///   output or updated state cannot be written
+    pub fn run(&self) -> Result<RunSummary, String> {
+        let prior = load_state(&self.state)?;
+        let inputs = expand_input_paths(&self.input_files)?;
+        let strategy = SyncProcessingStrategy::new()
+            .with_prior_state(prior)
+            .with_save_state(self.save_state.as_ref().unwrap_or(&self.state));
+        let mut output = create_sink(&self.output)?;
+        strategy
+            .process_files(&inputs, output.as_mut())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{save_state, EngineSnapshot};
+    use crate::types::DisputeState;
+    use tempfile::TempDir;
+
+    fn apply_args(dir: &TempDir, delta: &str, save_state: Option<&str>) -> ApplyArgs {
+        std::fs::write(dir.path().join("delta.csv"), delta).unwrap();
+        ApplyArgs {
+            state: dir.path().join("state.bin"),
+            input_files: vec![dir.path().join("delta.csv")],
+            output: dir
+                .path()
+                .join("accounts.csv")
+                .to_string_lossy()
+                .into_owned(),
+            save_state: save_state.map(|path| dir.path().join(path)),
+        }
+    }
+
+    fn save_prior(dir: &TempDir) {
+        let mut output = Vec::new();
+        std::fs::write(
+            dir.path().join("day1.csv"),
+            "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,2,2,5.0\n",
+        )
+        .unwrap();
+        SyncProcessingStrategy::new()
+            .with_save_state(dir.path().join("state.bin"))
+            .process(&dir.path().join("day1.csv"), &mut output)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_apply_delta_onto_state() {
+        let dir = TempDir::new().unwrap();
+        save_prior(&dir);
+
+        let summary = apply_args(
+            &dir,
+            "type,client,tx,amount\n\
+             dispute,1,1,\n\
+             withdrawal,2,3,2.0\n\
+             deposit,1,1,99.0\n",
+            None,
+        )
+        .run()
+        .unwrap();
+
+        // The repeated deposit was applied in the earlier run
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.transaction_errors, 1);
+        assert_eq!(summary.conservation, None);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("accounts.csv")).unwrap(),
+            "client,available,held,total,locked\n\
+             1,0.0000,10.0000,10.0000,false\n\
+             2,3.0000,0.0000,3.0000,false\n"
+        );
+        let state = load_state(&dir.path().join("state.bin")).unwrap();
+        assert_eq!(
+            state.transaction(1).unwrap().dispute_state,
+            DisputeState::Disputed
+        );
+        assert!(state.transaction(3).is_some());
+    }
+
+    #[test]
+    fn test_apply_saves_state_elsewhere() {
+        let dir = TempDir::new().unwrap();
+        save_prior(&dir);
+        let prior = load_state(&dir.path().join("state.bin")).unwrap();
+
+        apply_args(
+            &dir,
+            "type,client,tx,amount\ndeposit,3,4,1.0\n",
+            Some("next.bin"),
+        )
+        .run()
+        .unwrap();
+
+        assert_eq!(load_state(&dir.path().join("state.bin")).unwrap(), prior);
+        assert_eq!(
+            load_state(&dir.path().join("next.bin"))
+                .unwrap()
+                .accounts
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_apply_missing_state() {
+        let dir = TempDir::new().unwrap();
+        save_state(&dir.path().join("other.bin"), &EngineSnapshot::default()).unwrap();
+
+        let err = apply_args(&dir, "type,client,tx,amount\n", None)
+            .run()
+            .unwrap_err();
+        assert!(err.contains("Failed to read state file"), "{err}");
+        assert!(!dir.path().join("accounts.csv").exists());
+    }
+}
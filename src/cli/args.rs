@@ -1,93 +1,2402 @@
-use crate::strategy::BatchConfig;
-use clap::{Parser, ValueEnum};
+use super::apply::ApplyArgs;
+use super::bench::BenchArgs;
+use super::exit_policy::{parse_error_rate, ExitPolicy};
+use super::generate::GenerateArgs;
+#[cfg(feature = "graphql")]
+use super::graphql::GraphqlArgs;
+use super::merge::MergeArgs;
+use super::query::QueryArgs;
+use super::reconcile::ReconcileArgs;
+use super::statement::StatementArgs;
+use crate::core::r#async::{BatchDeadline, DeadlinePolicy};
+use crate::core::{
+    AmountLimits, DisputableTypes, DuplicateTxPolicy, EngineConfig, Fee, FeeSchedule,
+    MetadataRequirement, NegativeBalancePolicy, RedisputePolicy, RetryPolicy, VelocityLimit,
+};
+use crate::io::{
+    is_object_url, read_account_metadata, read_client_map, read_pseudonym_key, read_risk_rules,
+    read_standing_orders, AccountColumns, AccountLayout, AmountFormat, ClientPseudonymizer,
+    ClientRegistry, CsvDialect, DeadLetterOptions, DecimalSeparator, HeaderAlias, LogFormat,
+};
+use crate::strategy::{
+    registered_strategies, BalanceHistoryOptions, BatchConfig, ClientIdOffset, CutoffOptions,
+    DuplicateFilterOptions, FollowOptions, InputOptions, QuarantineOptions, QuarantineRule,
+    RegisteredStrategy, RuntimeOptions, SanityCheck, SanityOptions, StandingOrder,
+    BUILTIN_STRATEGIES,
+};
+use crate::types::{ClientId, ClientSet};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use rust_decimal::Decimal;
+use std::ffi::OsString;
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Process payment transactions with dispute resolution
+///
+/// Every mode is a subcommand with its own options. A command line starting
+/// with an option or an input file runs `process`, so `payments-engine
+/// transactions.csv` still processes the file (see `with_default_command`).
 #[derive(Parser, Debug)]
 #[command(name = "payments-engine")]
 #[command(about = "Process payment transactions with dispute resolution", long_about = None)]
+#[command(subcommand_required = true, arg_required_else_help = true)]
 pub struct CliArgs {
-    /// Input CSV file path containing transaction records
-    #[arg(value_name = "INPUT", help = "Path to the input CSV file")]
-    pub input_file: PathBuf,
+    /// Mode to run
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Subcommand run when a command line names none
+pub const DEFAULT_COMMAND: &str = "process";
+
+/// Insert the default subcommand into a command line that names none
+///
+/// The first argument after the program name selects the subcommand; when it
+/// is neither a subcommand nor a request for help, `process` is inserted
+/// before it. A file named like a subcommand is processed with an explicit
+/// `process`, or as `./query`.
+pub fn with_default_command<I, T>(command_line: I) -> Vec<OsString>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut command_line: Vec<OsString> = command_line.into_iter().map(Into::into).collect();
+    let Some(first) = command_line.get(1) else {
+        return command_line;
+    };
+    let names_command = first.to_str().is_some_and(|first| {
+        ["help", "-h", "--help"].contains(&first)
+            || CliArgs::command()
+                .get_subcommands()
+                .any(|command| command.get_name() == first)
+    });
+    if !names_command {
+        command_line.insert(1, OsString::from(DEFAULT_COMMAND));
+    }
+    command_line
+}
+
+/// Arguments of the `process` subcommand
+#[derive(Args, Debug, Clone)]
+#[command(group(
+    ArgGroup::new("quarantine_rule")
+        .multiple(true)
+        .args(["quarantine_above", "quarantine_velocity"])
+))]
+#[command(group(
+    ArgGroup::new("sanity_check")
+        .multiple(true)
+        .args(["warn_precision", "warn_above_percentile", "warn_new_client_withdrawals"])
+))]
+#[command(group(
+    ArgGroup::new("velocity_max")
+        .multiple(true)
+        .args(["velocity_max_withdrawals", "velocity_max_amount"])
+))]
+#[command(group(ArgGroup::new("live").args(["follow", "replica"])))]
+pub struct ProcessArgs {
+    /// Input file paths or glob patterns containing transaction records
+    #[arg(
+        value_name = "INPUT",
+        required = true,
+        help = "Input files, processed in order; quoted glob patterns (e.g. '2024-*.csv') are expanded in sorted order"
+    )]
+    pub input_files: Vec<PathBuf>,
+
+    /// Parsing strategy to use for processing transactions
+    #[arg(
+        long = "strategy",
+        value_name = "STRATEGY",
+        default_value = "async",
+        help = "Parsing strategy: 'sync' for synchronous, 'async' for asynchronous, or the name of a registered strategy"
+    )]
+    pub strategy: StrategyType,
+
+    /// Format of the input file
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        default_value = "csv",
+        help = "Input format: 'csv', or 'avro' (requires the 'avro' feature)"
+    )]
+    pub format: InputFormat,
+
+    /// Read well-formed CSV input with the fast reader
+    #[arg(
+        long = "fast-csv",
+        conflicts_with = "follow",
+        help = "Split well-formed CSV input with SIMD byte searches, falling back to the flexible reader on quotes (requires the 'fast-csv' feature)"
+    )]
+    pub fast_csv: bool,
+
+    /// Field delimiter of CSV input
+    #[arg(
+        long = "delimiter",
+        value_name = "CHAR",
+        default_value = ",",
+        value_parser = parse_delimiter,
+        help = "Field delimiter of CSV input, e.g. ';' ('tab' for tab-separated files)"
+    )]
+    pub delimiter: u8,
+
+    /// Decimal separator of amounts in CSV input
+    #[arg(
+        long = "decimal-separator",
+        value_name = "SEP",
+        default_value = ".",
+        help = "Decimal separator of amounts in CSV input: '.' or ','"
+    )]
+    pub decimal_separator: DecimalSeparator,
+
+    /// Header names of CSV input to read as standard columns
+    #[arg(
+        long = "header-alias",
+        value_name = "ALIAS=COLUMN",
+        help = "Read the CSV header ALIAS as COLUMN (type, client, tx or amount), e.g. 'transaction_id=tx' (repeatable)"
+    )]
+    pub header_aliases: Vec<HeaderAlias>,
+
+    /// Reject transaction IDs that do not fit in 32 bits
+    #[arg(
+        long = "legacy-tx-ids",
+        help = "Reject transaction IDs above 4294967295 as parse errors, for legacy 32-bit files"
+    )]
+    pub legacy_tx_ids: bool,
+
+    /// Number of preceding records to check for identical duplicate rows
+    #[arg(
+        long = "dedup-window",
+        value_name = "RECORDS",
+        default_value_t = 0,
+        help = "Skip deposits and withdrawals identical to one of the previous RECORDS records (0 disables)"
+    )]
+    pub dedup_window: usize,
+
+    /// Offset added to every client ID before processing
+    #[arg(
+        long = "client-id-offset",
+        value_name = "N",
+        help = "Add N to every client ID before processing, e.g. to merge tenants with overlapping IDs"
+    )]
+    pub client_id_offset: Option<ClientId>,
+
+    /// CSV file mapping external client identifiers to client IDs
+    #[arg(
+        long = "client-map",
+        value_name = "FILE",
+        conflicts_with_all = ["follow", "client_id_offset"],
+        help = "CSV file with 'external,client' columns; CSV input names clients by their external identifiers, which the output shows too"
+    )]
+    pub client_map: Option<PathBuf>,
+
+    /// Read CSV client fields as arbitrary strings, interning them as client IDs
+    #[arg(
+        long = "string-clients",
+        conflicts_with_all = ["follow", "client_id_offset", "clients", "ledger", "wal", "save_state"],
+        help = "Read CSV client fields as arbitrary strings, assigning each new one the next free client ID; the output shows the strings"
+    )]
+    pub string_clients: bool,
+
+    /// File holding the key of the client pseudonyms
+    #[arg(
+        long = "pseudonymize-key",
+        value_name = "FILE",
+        conflicts_with = "follow",
+        help = "Replace clients in the account output and logged errors with their HMAC-SHA256 pseudonyms under the key in FILE"
+    )]
+    pub pseudonymize_key: Option<PathBuf>,
+
+    /// File to divert suspicious transactions to instead of applying them
+    #[arg(
+        long = "quarantine",
+        value_name = "FILE",
+        requires = "quarantine_rule",
+        help = "Write transactions matching a quarantine rule to FILE instead of applying them"
+    )]
+    pub quarantine: Option<PathBuf>,
+
+    /// Quarantine deposits and withdrawals above this amount
+    #[arg(
+        long = "quarantine-above",
+        value_name = "AMOUNT",
+        requires = "quarantine",
+        help = "Quarantine deposits and withdrawals with an amount above AMOUNT"
+    )]
+    pub quarantine_above: Option<Decimal>,
+
+    /// Quarantine withdrawals over the velocity limit instead of rejecting them
+    #[arg(
+        long = "quarantine-velocity",
+        requires_all = ["quarantine", "velocity_window"],
+        help = "Quarantine withdrawals over the --velocity-window limit instead of rejecting them"
+    )]
+    pub quarantine_velocity: bool,
+
+    /// File to write warnings about suspicious records to
+    #[arg(
+        long = "warnings",
+        value_name = "FILE",
+        requires = "sanity_check",
+        help = "Write warnings about records failing a sanity check to FILE, processing them as usual"
+    )]
+    pub warnings: Option<PathBuf>,
+
+    /// Warn about amounts with more than four decimal places
+    #[arg(
+        long = "warn-precision",
+        requires = "warnings",
+        help = "Warn about amounts with more than four decimal places, which the output truncates"
+    )]
+    pub warn_precision: bool,
+
+    /// Warn about amounts above this percentile of the earlier amounts
+    #[arg(
+        long = "warn-above-percentile",
+        value_name = "PERCENTILE",
+        value_parser = parse_percentile,
+        requires = "warnings",
+        help = "Warn about deposits and withdrawals above PERCENTILE (e.g. 99.9) of the earlier amounts of their type"
+    )]
+    pub warn_above_percentile: Option<Decimal>,
+
+    /// Warn about withdrawals by clients with no earlier transaction
+    #[arg(
+        long = "warn-new-client-withdrawals",
+        requires = "warnings",
+        help = "Warn about withdrawals by clients with no earlier transaction"
+    )]
+    pub warn_new_client_withdrawals: bool,
+
+    /// Where to send the records the engine rejects
+    #[arg(
+        long = "dead-letter",
+        value_name = "TARGET",
+        help = "Send records the engine rejects, with their errors, to TARGET: a CSV file, an http(s):// endpoint or kafka+http(s)://PROXY/topics/TOPIC"
+    )]
+    pub dead_letter: Option<String>,
+
+    /// Attempts to deliver dead letters to a remote target
+    #[arg(
+        long = "dead-letter-attempts",
+        value_name = "N",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "dead_letter",
+        help = "Attempts to deliver a batch of dead letters to a remote --dead-letter target before aborting"
+    )]
+    pub dead_letter_attempts: u32,
+
+    /// Delay before the first retry of a failed dead letter delivery
+    #[arg(
+        long = "dead-letter-backoff-ms",
+        value_name = "MS",
+        default_value_t = 100,
+        requires = "dead_letter",
+        help = "Wait MS milliseconds before retrying a failed dead letter delivery, doubling the wait for each further retry"
+    )]
+    pub dead_letter_backoff_ms: u64,
+
+    /// Clients whose accounts are written to the output
+    #[arg(
+        long = "clients",
+        value_name = "LIST",
+        help = "Only write the accounts of these clients, e.g. '1,2,7-20'"
+    )]
+    pub clients: Option<ClientSet>,
+
+    /// Skip the records of clients not selected with `--clients`
+    #[arg(
+        long = "filter-input",
+        requires = "clients",
+        help = "Also skip input records of clients not in --clients instead of processing them"
+    )]
+    pub filter_input: bool,
+
+    /// Add why each account was locked to the output
+    #[arg(
+        long = "lock-reasons",
+        help = "Add a lock_reason column to the output: chargeback:TX, admin or risk_rule:NAME"
+    )]
+    pub lock_reasons: bool,
+
+    /// Add the activity counters of each client to the output
+    #[arg(
+        long = "client-stats",
+        conflicts_with = "ledger",
+        help = "Add transactions, errors, disputes and last_tx columns with each client's activity to the output"
+    )]
+    pub client_stats: bool,
+
+    /// Columns of the output, in order
+    #[arg(
+        long = "columns",
+        value_name = "LIST",
+        conflicts_with_all = ["lock_reasons", "client_stats"],
+        help = "Write these output columns, in this order, e.g. 'client,available,held,total,locked,last_tx,dispute_count'; other names are metadata columns"
+    )]
+    pub columns: Option<AccountColumns>,
+
+    /// Format of the amounts in the output
+    #[arg(
+        long = "amount-format",
+        value_name = "FORMAT",
+        default_value_t = AmountFormat::Decimal,
+        help = "Write output amounts as 'decimal' strings (1.2345) or as integer 'minor-units' of 1/10,000 (12345)"
+    )]
+    pub amount_format: AmountFormat,
+
+    /// Number of transactions per batch (async mode only)
+    #[arg(
+        long = "batch-size",
+        value_name = "SIZE",
+        help = "Number of transactions per batch (default: 1000, range: 100-10000)"
+    )]
+    pub batch_size: Option<usize>,
+
+    /// Maximum number of concurrent batches (async mode only)
+    #[arg(
+        long = "max-concurrent",
+        value_name = "COUNT",
+        help = "Maximum number of batches processing concurrently (default: CPU cores)"
+    )]
+    pub max_concurrent_batches: Option<usize>,
+
+    /// Maximum number of clients processed concurrently (async strategy only)
+    #[arg(
+        long = "max-inflight-clients",
+        value_name = "N",
+        help = "Maximum number of clients processed concurrently (async only, default: unlimited)"
+    )]
+    pub max_inflight_clients: Option<usize>,
+
+    /// Tokio runtime flavor (async strategy only)
+    #[arg(
+        long = "runtime",
+        value_enum,
+        default_value_t = RuntimeFlavor::MultiThread,
+        help = "Tokio runtime: multi-thread, current-thread, or auto (current-thread for inputs up to 1 MiB) (async only)"
+    )]
+    pub runtime: RuntimeFlavor,
+
+    /// Pin the runtime's worker threads to CPU cores (async strategy only)
+    #[arg(
+        long = "pin-threads",
+        help = "Pin worker threads to CPU cores, round-robin (async only, requires the 'core-pinning' feature)"
+    )]
+    pub pin_threads: bool,
+
+    /// Name of the runtime's worker threads (async strategy only)
+    #[arg(
+        long = "thread-name",
+        value_name = "NAME",
+        help = "Name of the worker threads, e.g. for profilers and top -H (async only)"
+    )]
+    pub thread_name: Option<String>,
+
+    /// False positive rate of the duplicate transaction filter, in percent
+    #[arg(
+        long = "duplicate-filter",
+        value_name = "PERCENT",
+        value_parser = parse_false_positive_rate,
+        help = "Check transaction IDs against a bloom filter with a PERCENT false positive rate before the store, e.g. 1 (async only)"
+    )]
+    pub duplicate_filter: Option<f64>,
+
+    /// Number of transactions to size the duplicate filter for
+    #[arg(
+        long = "duplicate-filter-capacity",
+        value_name = "N",
+        requires = "duplicate_filter",
+        help = "Size the --duplicate-filter for N deposits and withdrawals (default: estimated from the input size)"
+    )]
+    pub duplicate_filter_capacity: Option<usize>,
+
+    /// Attempts at processing a transaction failing with a transient error
+    #[arg(
+        long = "retry-attempts",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Process a transaction failing with a transient error up to N times in total (async only, default: 1)"
+    )]
+    pub retry_attempts: Option<u32>,
+
+    /// Delay before the first retry of a transaction
+    #[arg(
+        long = "retry-backoff-ms",
+        value_name = "MS",
+        default_value_t = 10,
+        requires = "retry_attempts",
+        help = "Wait MS milliseconds before retrying a transaction, doubling the wait for each further retry"
+    )]
+    pub retry_backoff_ms: u64,
+
+    /// Time a batch may take before it is reported (async strategy only)
+    #[arg(
+        long = "batch-deadline-ms",
+        value_name = "MS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Report a batch still running MS milliseconds after it was submitted, naming its slow client partitions (async only)"
+    )]
+    pub batch_deadline_ms: Option<u64>,
+
+    /// What to do with a batch that missed its deadline
+    #[arg(
+        long = "on-batch-deadline",
+        value_name = "POLICY",
+        default_value_t = DeadlinePolicy::Continue,
+        requires = "batch_deadline_ms",
+        help = "After reporting a batch that missed --batch-deadline-ms: 'continue' waiting for it, or 'abort' the run"
+    )]
+    pub on_batch_deadline: DeadlinePolicy,
+
+    /// Faults injected into the async strategy, for testing
+    #[cfg(feature = "fault-injection")]
+    #[arg(
+        long = "inject-faults",
+        value_name = "SPEC",
+        help = "Inject faults for testing: errors=PERCENT of processing attempts fail with a transient error, delays=PERCENT of transactions wait up to max-delay-ms=MS, drawn from seed=N (async only)"
+    )]
+    pub inject_faults: Option<crate::core::FaultInjection>,
+
+    /// Expected number of distinct clients
+    #[arg(
+        long = "expected-clients",
+        value_name = "N",
+        help = "Pre-size the account maps for N clients, avoiding rehashing on huge runs"
+    )]
+    pub expected_clients: Option<usize>,
+
+    /// Expected number of deposits and withdrawals
+    #[arg(
+        long = "expected-transactions",
+        value_name = "N",
+        help = "Pre-size the transaction stores for N deposits and withdrawals, avoiding rehashing on huge runs"
+    )]
+    pub expected_transactions: Option<usize>,
+
+    /// SQLite ledger database to load transactions into
+    #[arg(
+        long = "ledger",
+        value_name = "DB",
+        help = "Load into a persistent SQLite ledger (requires the 'sqlite' feature; processes sequentially)"
+    )]
+    pub ledger: Option<PathBuf>,
+
+    /// Write-ahead log to record transactions in and recover state from
+    #[arg(
+        long = "wal",
+        value_name = "FILE",
+        conflicts_with = "ledger",
+        help = "Log every transaction to FILE before applying it, replaying FILE on startup (processes sequentially)"
+    )]
+    pub wal: Option<PathBuf>,
+
+    /// Follow the input file as it grows, like `tail -f`
+    #[arg(
+        long = "follow",
+        conflicts_with_all = ["ledger", "wal"],
+        help = "Keep reading the input file as records are appended, writing account snapshots periodically (single CSV input only)"
+    )]
+    pub follow: bool,
+
+    /// Replicate the state of another process's write-ahead log
+    #[arg(
+        long = "replica",
+        conflicts_with_all = [
+            "ledger",
+            "wal",
+            "client_map",
+            "string_clients",
+            "dead_letter",
+            "quarantine",
+            "warnings",
+            "journal",
+            "trial_balance",
+            "changes",
+            "balance_history",
+            "analytics",
+        ],
+        help = "Treat the input as the write-ahead log of a process running with --wal, following it read-only and writing account snapshots (and --save-state) periodically"
+    )]
+    pub replica: bool,
+
+    /// Minimum number of seconds between account snapshots in follow or
+    /// replica mode
+    #[arg(
+        long = "snapshot-interval",
+        value_name = "SECS",
+        requires = "live",
+        help = "Write an account snapshot at most every SECS seconds while following or replicating (default: 5)"
+    )]
+    pub snapshot_interval: Option<u64>,
+
+    /// Stop following after this many seconds without new records
+    #[arg(
+        long = "idle-timeout",
+        value_name = "SECS",
+        requires = "live",
+        help = "Stop following once nothing has been appended for SECS seconds (default: follow forever)"
+    )]
+    pub idle_timeout: Option<u64>,
+
+    /// Number of per-client shards to process on separate threads
+    #[arg(
+        long = "shards",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..=1024),
+        conflicts_with_all = [
+            "strategy",
+            "ledger",
+            "wal",
+            "follow",
+            "replica",
+            "save_state",
+            "dispute_expiry",
+            "quarantine",
+            "warnings",
+            "dead_letter",
+            "snapshot_every",
+            "journal",
+            "trial_balance",
+            "changes",
+            "balance_history",
+            "analytics",
+        ],
+        help = "Split the input by client into N shards, each processed by its own engine on its own thread (requires --duplicate-tx per-client)"
+    )]
+    pub shards: Option<u64>,
+
+    /// CSV file with metadata (labels, owner, KYC status, ...) for each client
+    #[arg(
+        long = "accounts-metadata",
+        value_name = "FILE",
+        conflicts_with = "ledger",
+        help = "CSV file with a 'client' column and one column per metadata key; keys are added to the output"
+    )]
+    pub accounts_metadata: Option<PathBuf>,
+
+    /// Metadata values a client must have for its withdrawals to be accepted
+    #[arg(
+        long = "require-for-withdrawal",
+        value_name = "KEY=VALUE",
+        requires = "accounts_metadata",
+        help = "Reject withdrawals from clients whose metadata KEY is not VALUE (repeatable)"
+    )]
+    pub withdrawal_requirements: Vec<MetadataRequirement>,
+
+    /// What to do with deposits and withdrawals reusing a transaction ID
+    #[arg(
+        long = "duplicate-tx",
+        value_name = "POLICY",
+        default_value = "reject",
+        help = "Deposits and withdrawals reusing a transaction ID: 'reject' them, 'ignore' them, or 'per-client' to only require IDs to be unique per client (sync strategy only)"
+    )]
+    pub duplicate_tx_policy: DuplicateTxPolicy,
+
+    /// Transaction types that can be disputed
+    #[arg(
+        long = "disputable",
+        value_name = "TYPES",
+        default_value = "all",
+        help = "Transaction types that can be disputed: 'deposits', 'deposits-withdrawals' or 'all'"
+    )]
+    pub disputable_types: DisputableTypes,
+
+    /// Whether a resolved transaction can be disputed again
+    #[arg(
+        long = "redispute",
+        value_name = "POLICY",
+        default_value = "unlimited",
+        help = "Re-disputes allowed after a dispute is resolved: 'unlimited', 'never', 'once' or a number"
+    )]
+    pub redispute_policy: RedisputePolicy,
+
+    /// Whether a chargeback may arrive without a preceding dispute
+    #[arg(
+        long = "allow-direct-chargeback",
+        help = "Accept chargebacks on undisputed transactions by disputing them implicitly"
+    )]
+    pub allow_direct_chargeback: bool,
+
+    /// Whether a dispute may drive available funds negative
+    #[arg(
+        long = "negative-balance",
+        value_name = "POLICY",
+        default_value = "reject",
+        help = "Disputes of funds no longer available: 'reject' them or 'allow' a negative balance"
+    )]
+    pub negative_balance_policy: NegativeBalancePolicy,
+
+    /// Number of records after which an unanswered dispute is resolved
+    #[arg(
+        long = "dispute-expiry",
+        value_name = "RECORDS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "ledger",
+        help = "Resolve disputes neither resolved nor charged back within RECORDS further records, releasing the held funds (sync strategy only)"
+    )]
+    pub dispute_expiry: Option<u32>,
+
+    /// Largest amount accepted for a single deposit
+    #[arg(
+        long = "max-deposit",
+        value_name = "AMOUNT",
+        help = "Reject deposits with an amount above AMOUNT"
+    )]
+    pub max_deposit: Option<Decimal>,
+
+    /// Largest amount accepted for a single withdrawal
+    #[arg(
+        long = "max-withdrawal",
+        value_name = "AMOUNT",
+        help = "Reject withdrawals with an amount above AMOUNT"
+    )]
+    pub max_withdrawal: Option<Decimal>,
+
+    /// Largest total balance a deposit may leave a client with
+    #[arg(
+        long = "max-total",
+        value_name = "AMOUNT",
+        help = "Reject deposits that would raise a client's total above AMOUNT"
+    )]
+    pub max_total: Option<Decimal>,
+
+    /// Withdrawal amount above which withdrawals wait for approval
+    #[arg(
+        long = "approve-withdrawals-above",
+        value_name = "AMOUNT",
+        help = "Hold withdrawals above AMOUNT as pending until an 'approve' or 'reject' record for them"
+    )]
+    pub approve_withdrawals_above: Option<Decimal>,
+
+    /// Interest rate credited on held funds released by a resolve
+    #[arg(
+        long = "held-interest",
+        value_name = "RATE",
+        value_parser = parse_interest_rate,
+        help = "Credit interest at RATE (e.g. 0.005) on the held funds of every dispute resolved in the client's favor"
+    )]
+    pub held_interest: Option<Decimal>,
+
+    /// Fee charged on every deposit
+    #[arg(
+        long = "deposit-fee",
+        value_name = "FEE",
+        requires = "fee_account",
+        help = "Charge FEE (AMOUNT, PERCENT% or AMOUNT+PERCENT%) on every deposit, credited to --fee-account"
+    )]
+    pub deposit_fee: Option<Fee>,
+
+    /// Fee charged on every withdrawal
+    #[arg(
+        long = "withdrawal-fee",
+        value_name = "FEE",
+        requires = "fee_account",
+        help = "Charge FEE (AMOUNT, PERCENT% or AMOUNT+PERCENT%) on every withdrawal, credited to --fee-account"
+    )]
+    pub withdrawal_fee: Option<Fee>,
+
+    /// Client account collecting the fees
+    #[arg(
+        long = "fee-account",
+        value_name = "CLIENT",
+        conflicts_with = "ledger",
+        help = "Client account the --deposit-fee and --withdrawal-fee fees are credited to"
+    )]
+    pub fee_account: Option<ClientId>,
+
+    /// Number of a client's recent transactions the velocity limit applies to
+    #[arg(
+        long = "velocity-window",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "velocity_max",
+        conflicts_with = "ledger",
+        help = "Limit withdrawals within each client's last N transactions (see --velocity-max-*)"
+    )]
+    pub velocity_window: Option<u32>,
+
+    /// Maximum number of withdrawals within the velocity window
+    #[arg(
+        long = "velocity-max-withdrawals",
+        value_name = "COUNT",
+        requires = "velocity_window",
+        help = "Reject withdrawals beyond COUNT within the velocity window"
+    )]
+    pub velocity_max_withdrawals: Option<u32>,
+
+    /// Maximum sum of withdrawals within the velocity window
+    #[arg(
+        long = "velocity-max-amount",
+        value_name = "AMOUNT",
+        requires = "velocity_window",
+        help = "Reject withdrawals that would take more than AMOUNT within the velocity window"
+    )]
+    pub velocity_max_amount: Option<Decimal>,
+
+    /// JSON file of amount limits, velocity limit and withdrawal requirements
+    #[arg(
+        long = "risk-rules",
+        value_name = "FILE",
+        conflicts_with_all = [
+            "max_deposit",
+            "max_withdrawal",
+            "max_total",
+            "velocity_window",
+            "withdrawal_requirements",
+            "held_interest",
+        ],
+        help = "Read the amount limits, velocity limit, withdrawal requirements and held funds interest from a JSON file; with --follow it is reloaded whenever it changes"
+    )]
+    pub risk_rules: Option<PathBuf>,
+
+    /// Destination for the final account states
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "TARGET",
+        default_value = "-",
+        help = "Output target: '-' for stdout, a CSV file path, or a postgres:// URL (requires the 'postgres' feature)"
+    )]
+    pub output: String,
+
+    /// Exit non-zero if any record failed to parse or process
+    #[arg(
+        long = "fail-on-error",
+        help = "Exit with status 2 if any record failed to parse or process"
+    )]
+    pub fail_on_error: bool,
+
+    /// Maximum percentage of failed records before the run is considered failed
+    #[arg(
+        long = "max-error-rate",
+        value_name = "PERCENT",
+        value_parser = parse_error_rate,
+        help = "Exit with status 2 if more than PERCENT (0-100) of records failed"
+    )]
+    pub max_error_rate: Option<f64>,
+
+    /// Check that the final balances match the money moved by the run
+    #[arg(
+        long = "check-conservation",
+        conflicts_with = "ledger",
+        help = "Exit with status 2 if account totals differ from deposits - withdrawals - chargebacks"
+    )]
+    pub check_conservation: bool,
+
+    /// Where to write a machine-readable summary of the run
+    #[arg(
+        long = "summary",
+        value_name = "FILE",
+        help = "Write a JSON run summary (counts per type and error kind, account totals) to FILE, or '-' for stderr"
+    )]
+    pub summary: Option<String>,
+
+    /// Format of the warning and error lines on stderr
+    #[arg(
+        long = "log-format",
+        value_name = "FORMAT",
+        default_value_t = LogFormat::Text,
+        help = "Write warnings and errors to stderr as 'text' lines or as 'json' objects with level, code, tx, client, line and message"
+    )]
+    pub log_format: LogFormat,
+
+    /// Number of lines logged per class of error
+    #[arg(
+        long = "max-errors-per-class",
+        value_name = "N",
+        help = "Log only the first N rejected records of each error kind (InsufficientFunds, ParseError, ...), then how many more were suppressed"
+    )]
+    pub max_errors_per_class: Option<u64>,
+
+    /// Where to write the manifest of the run
+    #[arg(
+        long = "manifest",
+        value_name = "FILE",
+        conflicts_with = "follow",
+        help = "Write a JSON run manifest (version, input digests, options, timings, output digest) to FILE"
+    )]
+    pub manifest: Option<PathBuf>,
+
+    /// Manifest of an earlier run that this run must reproduce
+    #[arg(
+        long = "verify-manifest",
+        value_name = "FILE",
+        conflicts_with = "follow",
+        help = "Check that this run reproduces the run of the manifest in FILE: same version, inputs, options and output"
+    )]
+    pub verify_manifest: Option<PathBuf>,
+
+    /// Where to save the final engine state for later queries
+    #[arg(
+        long = "save-state",
+        value_name = "FILE",
+        conflicts_with = "ledger",
+        help = "Save the final accounts and stored transactions to FILE, for the 'query' subcommand"
+    )]
+    pub save_state: Option<PathBuf>,
+
+    /// Number of input records between cutoff snapshots
+    #[arg(
+        long = "snapshot-every",
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "snapshot_dir",
+        conflicts_with = "ledger",
+        help = "Also write the account states after every N input records to numbered files in --snapshot-dir (sync strategy only)"
+    )]
+    pub snapshot_every: Option<u64>,
+
+    /// Directory for the cutoff snapshots
+    #[arg(
+        long = "snapshot-dir",
+        value_name = "DIR",
+        requires = "snapshot_every",
+        help = "Directory for the snapshot-NNNNNN.csv files written by --snapshot-every"
+    )]
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// File of recurring deposits and withdrawals
+    #[arg(
+        long = "standing-orders",
+        value_name = "FILE",
+        help = "Also apply the recurring deposits and withdrawals in FILE (client,type,amount,every), each after every 'every' input records (sync strategy only)"
+    )]
+    pub standing_orders: Option<PathBuf>,
+
+    /// Where to write the double-entry journal
+    #[arg(
+        long = "journal",
+        value_name = "FILE",
+        conflicts_with = "ledger",
+        help = "Also write a balanced debit/credit journal line pair for every applied transaction to FILE"
+    )]
+    pub journal: Option<PathBuf>,
+
+    /// Where to write the trial balance
+    #[arg(
+        long = "trial-balance",
+        value_name = "FILE",
+        conflicts_with = "ledger",
+        help = "Also write the debit, credit and balance of every journal account, settlement and fee accounts included, to FILE"
+    )]
+    pub trial_balance: Option<PathBuf>,
+
+    /// Where to send balance change events
+    #[arg(
+        long = "changes",
+        value_name = "TARGET",
+        conflicts_with = "ledger",
+        help = "Also send an event with the before and after balances of every balance change to TARGET: a JSON Lines file or kafka+http://host:port/topics/TOPIC"
+    )]
+    pub changes: Option<String>,
+
+    /// Where to write each client's balance history
+    #[arg(
+        long = "balance-history",
+        value_name = "FILE",
+        requires = "history_every",
+        conflicts_with = "ledger",
+        help = "Also write each client's balances after every --history-every of its transactions to FILE"
+    )]
+    pub balance_history: Option<PathBuf>,
+
+    /// Number of a client's transactions between balance history samples
+    #[arg(
+        long = "history-every",
+        value_name = "K",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "balance_history",
+        help = "Sample a client's balances after every K of its applied transactions for --balance-history"
+    )]
+    pub history_every: Option<u32>,
+
+    /// Where to write aggregate analytics of the run
+    #[arg(
+        long = "analytics",
+        value_name = "FILE",
+        conflicts_with = "ledger",
+        help = "Write JSON analytics (top clients by volume and dispute rate, chargeback totals, amount distribution) to FILE, or '-' for stderr"
+    )]
+    pub analytics: Option<String>,
+
+    /// Number of clients listed per analytics ranking
+    #[arg(
+        long = "analytics-top",
+        value_name = "N",
+        default_value_t = 10,
+        requires = "analytics",
+        help = "Number of clients listed in each --analytics ranking"
+    )]
+    pub analytics_top: usize,
+}
+
+/// Resolve input arguments into the list of files to process
+///
+/// Arguments that name an existing file or an object URL are used as-is.
+/// Other arguments containing glob metacharacters (`*`, `?`, `[`) are
+/// expanded, with the matches sorted so that date-stamped files are
+/// processed in order.
+///
+/// # Returns
+///
+/// * `Ok(Vec<PathBuf>)` - The input files, in processing order
+/// * `Err(String)` - If a pattern is invalid or matches no files
+pub(crate) fn expand_input_paths(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        let pattern = input.to_string_lossy();
+        if input.exists() || is_object_url(&pattern) || !pattern.contains(['*', '?', '[']) {
+            paths.push(input.clone());
+            continue;
+        }
+
+        let mut matches = glob::glob(&pattern)
+            .map_err(|e| format!("Invalid input pattern '{}': {}", pattern, e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to expand input pattern '{}': {}", pattern, e))?;
+        if matches.is_empty() {
+            return Err(format!("No input files match '{}'", pattern));
+        }
+        matches.sort();
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
+/// Subcommands of the payments engine
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Process input files and write the final account states (the default)
+    Process(Box<ProcessArgs>),
+    /// Print an account or stored transaction from a state file saved with --save-state
+    Query(QueryArgs),
+    /// List a client's stored transactions from a state file saved with --save-state
+    Statement(StatementArgs),
+    /// Compare the final balances against a file of expected balances
+    Reconcile(ReconcileArgs),
+    /// Merge account CSVs or state files of runs over disjoint clients
+    Merge(MergeArgs),
+    /// Apply a delta file onto a state file saved with --save-state
+    Apply(ApplyArgs),
+    /// Time the processing strategies on input files
+    Bench(BenchArgs),
+    /// Write a synthetic transaction file
+    Generate(GenerateArgs),
+    /// Serve GraphQL queries over a state file saved with --save-state
+    #[cfg(feature = "graphql")]
+    Graphql(GraphqlArgs),
+}
+
+/// Available parsing strategies for CSV processing
+///
+/// Besides the built-in `sync` and `async`, any strategy registered with
+/// `strategy::register_strategy` can be selected by its name.
+#[derive(Clone, Debug)]
+pub enum StrategyType {
+    Sync,
+    Async,
+    /// A strategy registered by a library user
+    Custom(RegisteredStrategy),
+}
+
+impl FromStr for StrategyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sync" => Ok(StrategyType::Sync),
+            "async" => Ok(StrategyType::Async),
+            _ => RegisteredStrategy::lookup(s)
+                .map(StrategyType::Custom)
+                .ok_or_else(|| {
+                    let names: Vec<String> = BUILTIN_STRATEGIES
+                        .iter()
+                        .map(ToString::to_string)
+                        .chain(registered_strategies())
+                        .collect();
+                    format!(
+                        "Unknown strategy '{}': expected one of {}",
+                        s,
+                        names.join(", ")
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for StrategyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategyType::Sync => write!(f, "sync"),
+            StrategyType::Async => write!(f, "async"),
+            StrategyType::Custom(strategy) => write!(f, "{}", strategy.name()),
+        }
+    }
+}
+
+/// Tokio runtime of the async strategy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RuntimeFlavor {
+    /// One worker thread per concurrent batch
+    #[default]
+    MultiThread,
+    /// Everything on the calling thread, with no worker startup cost
+    CurrentThread,
+    /// Current-thread for small local inputs, multi-thread otherwise
+    Auto,
+}
+
+/// Input file format
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// Comma-separated values with a `type,client,tx,amount` header
+    #[default]
+    Csv,
+    /// Avro object container file (see `io::avro_reader` for the schema)
+    Avro,
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputFormat::Csv => write!(f, "CSV"),
+            InputFormat::Avro => write!(f, "Avro"),
+        }
+    }
+}
+
+impl ProcessArgs {
+    /// Create a BatchConfig from CLI arguments
+    ///
+    /// Unlike `BatchConfigBuilder::build`, invalid tuning values do not fail
+    /// the run: zero values fall back to their defaults and values above
+    /// their maximum are capped, with a warning on stderr.
+    ///
+    /// # Returns
+    ///
+    /// A `BatchConfig` with values from CLI arguments or defaults.
+    pub fn to_batch_config(&self) -> BatchConfig {
+        let mut builder = BatchConfig::builder();
+        if let Some(batch_size) = self.batch_size {
+            builder = builder.batch_size(batch_size);
+        }
+        if let Some(max_concurrent_batches) = self.max_concurrent_batches {
+            builder = builder.max_concurrent_batches(max_concurrent_batches);
+        }
+        if let Some(max_inflight_clients) = self.max_inflight_clients {
+            builder = builder.max_inflight_clients(max_inflight_clients);
+        }
+        if let Some(percent) = self.duplicate_filter {
+            let mut options = DuplicateFilterOptions::new(percent / 100.0);
+            if let Some(capacity) = self.duplicate_filter_capacity {
+                options = options.with_capacity(capacity);
+            }
+            builder = builder.duplicate_filter(options);
+        }
+        if let Some(attempts) = self.retry_attempts {
+            builder = builder.retry(RetryPolicy::new(
+                attempts,
+                Duration::from_millis(self.retry_backoff_ms),
+            ));
+        }
+        if let Some(ms) = self.batch_deadline_ms {
+            builder = builder.batch_deadline(
+                BatchDeadline::new(Duration::from_millis(ms)).with_policy(self.on_batch_deadline),
+            );
+        }
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = self.inject_faults {
+            builder = builder.faults(faults);
+        }
+        builder
+            .runtime(RuntimeOptions {
+                flavor: self.runtime,
+                pin_threads: self.pin_threads,
+                thread_name: self.thread_name.clone(),
+            })
+            .build_lenient()
+    }
+
+    /// Resolve the input arguments into the list of files to process
+    ///
+    /// See `expand_input_paths`.
+    pub fn input_paths(&self) -> Result<Vec<PathBuf>, String> {
+        expand_input_paths(&self.input_files)
+    }
+
+    /// Create the InputOptions described by the CLI arguments
+    pub fn input_options(&self) -> InputOptions {
+        let mut input = InputOptions::new(self.format)
+            .with_legacy_tx_ids(self.legacy_tx_ids)
+            .with_dedup_window(self.dedup_window)
+            .with_csv_dialect(self.csv_dialect())
+            .with_fast_csv(self.fast_csv);
+        if let Some(offset) = self.client_id_offset {
+            input = input.with_middleware(ClientIdOffset::new(offset));
+        }
+        if let (Some(clients), true) = (&self.clients, self.filter_input) {
+            input = input.with_clients(clients.clone());
+        }
+        if let (Some(dir), Some(every)) = (&self.snapshot_dir, self.snapshot_every) {
+            input = input.with_cutoffs(CutoffOptions::new(dir, every));
+        }
+        if let Some(path) = &self.journal {
+            input = input.with_journal(path);
+        }
+        if let Some(path) = &self.trial_balance {
+            input = input.with_trial_balance(path);
+        }
+        if let Some(target) = &self.changes {
+            input = input.with_changes(target);
+        }
+        if let (Some(path), Some(every)) = (&self.balance_history, self.history_every) {
+            input = input.with_balance_history(BalanceHistoryOptions::new(path, every));
+        }
+        if self.analytics.is_some() {
+            input = input.with_analytics(self.analytics_top);
+        }
+        if let Some(target) = &self.dead_letter {
+            input = input.with_dead_letter(DeadLetterOptions::new(target).with_retry(
+                RetryPolicy::new(
+                    self.dead_letter_attempts,
+                    Duration::from_millis(self.dead_letter_backoff_ms),
+                ),
+            ));
+        }
+        if let Some(path) = &self.warnings {
+            let mut sanity = SanityOptions::new(path);
+            if self.warn_precision {
+                sanity = sanity.with_check(SanityCheck::ExcessPrecision);
+            }
+            if let Some(percentile) = self.warn_above_percentile {
+                sanity = sanity.with_check(SanityCheck::AbovePercentile(percentile));
+            }
+            if self.warn_new_client_withdrawals {
+                sanity = sanity.with_check(SanityCheck::NewClientWithdrawal);
+            }
+            input = input.with_sanity(sanity);
+        }
+        let Some(path) = &self.quarantine else {
+            return input;
+        };
+        let mut quarantine = QuarantineOptions::new(path);
+        if let Some(threshold) = self.quarantine_above {
+            quarantine = quarantine.with_rule(QuarantineRule::AmountAbove(threshold));
+        }
+        if let (Some(limit), true) = (self.velocity_limit(), self.quarantine_velocity) {
+            quarantine = quarantine.with_rule(QuarantineRule::Velocity(limit));
+        }
+        input.with_quarantine(quarantine)
+    }
+
+    /// Create the VelocityLimit described by the CLI arguments, if any
+    pub fn velocity_limit(&self) -> Option<VelocityLimit> {
+        let mut limit = VelocityLimit::new(self.velocity_window?);
+        if let Some(max) = self.velocity_max_withdrawals {
+            limit = limit.with_max_withdrawals(max);
+        }
+        if let Some(max) = self.velocity_max_amount {
+            limit = limit.with_max_withdrawn(max);
+        }
+        Some(limit)
+    }
+
+    /// Create the CsvDialect described by the CLI arguments
+    pub fn csv_dialect(&self) -> CsvDialect {
+        self.header_aliases.iter().cloned().fold(
+            CsvDialect::default()
+                .with_delimiter(self.delimiter)
+                .with_decimal_separator(self.decimal_separator),
+            CsvDialect::with_header_alias,
+        )
+    }
+
+    /// Create the layout of the account output described by the CLI arguments
+    pub fn output_layout(&self) -> AccountLayout {
+        let layout = AccountLayout::default().with_amounts(self.amount_format);
+        match &self.columns {
+            Some(columns) => layout.with_columns(columns.clone()),
+            None => layout,
+        }
+    }
+
+    /// Create the client registry described by the CLI arguments, if any
+    ///
+    /// With `--string-clients` the registry interns new external identifiers,
+    /// starting from the `--client-map` file if one is given; otherwise it
+    /// holds just the map file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Option<Arc<ClientRegistry>>)` - The registry, or `None` if CSV
+    ///   client fields are numeric client IDs
+    /// * `Err(String)` - If the map file cannot be read
+    pub fn client_registry(&self) -> Result<Option<Arc<ClientRegistry>>, String> {
+        let map = match &self.client_map {
+            Some(path) => Some(read_client_map(path)?),
+            None => None,
+        };
+        Ok(match (map, self.string_clients) {
+            (map, true) => Some(ClientRegistry::interning(map.unwrap_or_default())),
+            (Some(map), false) => Some(ClientRegistry::fixed(map)),
+            (None, false) => None,
+        }
+        .map(Arc::new))
+    }
+
+    /// Load the pseudonymizer keyed with `--pseudonymize-key`, if any
+    ///
+    /// # Arguments
+    ///
+    /// * `client_map` - The client registry, whose external identifiers are
+    ///   pseudonymized in place of client IDs
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Option<Arc<ClientPseudonymizer>>)` - The pseudonymizer, or
+    ///   `None` without `--pseudonymize-key`
+    /// * `Err(String)` - If the key file cannot be read or the key is too short
+    pub fn pseudonymizer(
+        &self,
+        client_map: Option<Arc<ClientRegistry>>,
+    ) -> Result<Option<Arc<ClientPseudonymizer>>, String> {
+        let Some(path) = &self.pseudonymize_key else {
+            return Ok(None);
+        };
+        let mut pseudonymizer = read_pseudonym_key(path)?;
+        if let Some(client_map) = client_map {
+            pseudonymizer = pseudonymizer.with_client_map(client_map);
+        }
+        Ok(Some(Arc::new(pseudonymizer)))
+    }
+
+    /// Load the standing orders in the `--standing-orders` file, if any
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<StandingOrder>)` - The orders, or none without
+    ///   `--standing-orders`
+    /// * `Err(String)` - If the file cannot be read or is malformed
+    pub fn standing_orders(&self) -> Result<Vec<StandingOrder>, String> {
+        match &self.standing_orders {
+            Some(path) => read_standing_orders(path),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Create the FollowOptions described by the CLI arguments
+    pub fn follow_options(&self) -> FollowOptions {
+        let mut follow =
+            FollowOptions::default().with_idle_timeout(self.idle_timeout.map(Duration::from_secs));
+        if let Some(secs) = self.snapshot_interval {
+            follow = follow.with_snapshot_interval(Duration::from_secs(secs));
+        }
+        follow
+    }
+
+    /// Create the EngineConfig described by the CLI arguments
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(EngineConfig)` - With the metadata file loaded, if one was given
+    /// * `Err(String)` - If the metadata file cannot be read
+    pub fn engine_config(&self) -> Result<EngineConfig, String> {
+        let mut config = EngineConfig::new()
+            .with_duplicate_tx_policy(self.duplicate_tx_policy)
+            .with_disputable_types(self.disputable_types)
+            .with_redispute_policy(self.redispute_policy)
+            .with_direct_chargeback(self.allow_direct_chargeback)
+            .with_negative_balance_policy(self.negative_balance_policy)
+            .with_limits(AmountLimits {
+                max_deposit: self.max_deposit,
+                max_withdrawal: self.max_withdrawal,
+                max_total: self.max_total,
+            });
+        if let Some(records) = self.dispute_expiry {
+            config = config.with_dispute_expiry(records);
+        }
+        if let Some(amount) = self.approve_withdrawals_above {
+            config = config.with_withdrawal_approval_above(amount);
+        }
+        if let Some(rate) = self.held_interest {
+            config = config.with_held_interest(rate);
+        }
+        if let Some(account) = self.fee_account {
+            let mut fees = FeeSchedule::new(account);
+            if let Some(fee) = self.deposit_fee {
+                fees = fees.with_deposit_fee(fee);
+            }
+            if let Some(fee) = self.withdrawal_fee {
+                fees = fees.with_withdrawal_fee(fee);
+            }
+            config = config.with_fees(fees);
+        }
+        if let Some(path) = &self.accounts_metadata {
+            config = config.with_account_metadata(read_account_metadata(path)?);
+        }
+        for requirement in &self.withdrawal_requirements {
+            config = config.with_withdrawal_requirement(requirement.clone());
+        }
+        if let Some(clients) = self.expected_clients {
+            config = config.with_expected_clients(clients);
+        }
+        if let Some(transactions) = self.expected_transactions {
+            config = config.with_expected_transactions(transactions);
+        }
+        // Quarantined withdrawals never reach the engine, so it needn't check them again
+        if let (Some(limit), false) = (self.velocity_limit(), self.quarantine_velocity) {
+            config = config.with_velocity_limit(limit);
+        }
+        if let Some(path) = &self.risk_rules {
+            let rules = read_risk_rules(path)?;
+            if rules.velocity_limit.is_some() && self.ledger.is_some() {
+                return Err(format!(
+                    "The velocity limit in '{}' is not supported with --ledger",
+                    path.display()
+                ));
+            }
+            config = config.with_risk_rules(rules);
+        }
+        Ok(config)
+    }
+
+    /// Create the ExitPolicy described by the CLI arguments
+    pub fn exit_policy(&self) -> ExitPolicy {
+        ExitPolicy {
+            fail_on_error: self.fail_on_error,
+            max_error_rate: self.max_error_rate,
+            check_conservation: self.check_conservation,
+        }
+    }
+}
+
+/// Parse a `--held-interest` value as a non-negative rate
+fn parse_interest_rate(value: &str) -> Result<Decimal, String> {
+    let rate: Decimal = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid rate", value))?;
+    if rate.is_sign_negative() {
+        return Err(format!("{} is negative", rate));
+    }
+    Ok(rate)
+}
+
+/// Parse a `--warn-above-percentile` value as a percentile strictly between
+/// 0 and 100
+fn parse_percentile(value: &str) -> Result<Decimal, String> {
+    let percentile: Decimal = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid percentile", value))?;
+    if percentile <= Decimal::ZERO || percentile >= Decimal::ONE_HUNDRED {
+        return Err(format!("{} is not strictly between 0 and 100", percentile));
+    }
+    Ok(percentile)
+}
+
+/// Parse a `--delimiter` value as a single ASCII character
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(format!(
+            "'{}' is not a single ASCII character (use 'tab' for tabs)",
+            value
+        )),
+    }
+}
+
+/// Parse a `--duplicate-filter` value as a percentage strictly between 0 and 100
+fn parse_false_positive_rate(value: &str) -> Result<f64, String> {
+    let rate: f64 = value
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid percentage", value))?;
+
+    if !(rate > 0.0 && rate < 100.0) {
+        return Err(format!("{} is not strictly between 0 and 100", rate));
+    }
+
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AccountColumn, ClientMap};
+    use crate::strategy::MAX_BATCH_SIZE;
+    use crate::types::{TransactionRecord, TransactionType};
+    use rstest::rstest;
+
+    /// Parse a command line of the `process` subcommand, which it may omit
+    fn parse<I, T>(command_line: I) -> Result<ProcessArgs, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let parsed = CliArgs::try_parse_from(with_default_command(command_line))?;
+        match parsed.command {
+            Command::Process(args) => Ok(*args),
+            command => panic!("expected the process subcommand, got {:?}", command),
+        }
+    }
+
+    // Strategy parsing tests
+    #[rstest]
+    #[case::default_strategy(&["program", "input.csv"], StrategyType::Async)]
+    #[case::explicit_sync(&["program", "--strategy", "sync", "input.csv"], StrategyType::Sync)]
+    #[case::explicit_async(&["program", "--strategy", "async", "input.csv"], StrategyType::Async)]
+    fn test_strategy_parsing(#[case] args: &[&str], #[case] expected: StrategyType) {
+        let parsed = parse(args).unwrap();
+        match (&parsed.strategy, &expected) {
+            (StrategyType::Sync, StrategyType::Sync) => (),
+            (StrategyType::Async, StrategyType::Async) => (),
+            _ => panic!("Expected {:?}, got {:?}", expected, parsed.strategy),
+        }
+    }
+
+    #[test]
+    fn test_registered_strategy_parsing() {
+        use crate::strategy::{register_strategy, SyncProcessingStrategy};
+
+        register_strategy("args-test", |_| Box::new(SyncProcessingStrategy::new())).unwrap();
+        let parsed = parse(["program", "--strategy", "args-test", "input.csv"]).unwrap();
+        match &parsed.strategy {
+            StrategyType::Custom(strategy) => assert_eq!(strategy.name(), "args-test"),
+            other => panic!("Expected the registered strategy, got {:?}", other),
+        }
+        assert_eq!(parsed.strategy.to_string(), "args-test");
+
+        let err = "gpu".parse::<StrategyType>().unwrap_err();
+        assert!(
+            err.starts_with("Unknown strategy 'gpu': expected one of sync, async, "),
+            "{}",
+            err
+        );
+        assert!(err.contains("args-test"), "{}", err);
+    }
+
+    // Input format tests
+    #[rstest]
+    #[case::default_format(&["program", "input.csv"], InputFormat::Csv)]
+    #[case::explicit_csv(&["program", "--format", "csv", "input.csv"], InputFormat::Csv)]
+    #[case::avro(&["program", "--format", "avro", "input.avro"], InputFormat::Avro)]
+    fn test_format_parsing(#[case] args: &[&str], #[case] expected: InputFormat) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.format, expected);
+    }
+
+    #[test]
+    fn test_csv_dialect_options() {
+        let parsed = parse([
+            "program",
+            "--delimiter",
+            ";",
+            "--decimal-separator",
+            ",",
+            "--header-alias",
+            "transaction_id=tx",
+            "--header-alias",
+            "kind=type",
+            "input.csv",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed.input_options().csv_dialect,
+            CsvDialect::default()
+                .with_delimiter(b';')
+                .with_decimal_separator(DecimalSeparator::Comma)
+                .with_header_alias("transaction_id=tx".parse().unwrap())
+                .with_header_alias("kind=type".parse().unwrap())
+        );
+        assert_eq!(
+            parse(["program", "input.csv"]).unwrap().csv_dialect(),
+            CsvDialect::default()
+        );
+    }
+
+    #[rstest]
+    #[case::tab("tab", Ok(b'\t'))]
+    #[case::escaped_tab("\\t", Ok(b'\t'))]
+    #[case::pipe("|", Ok(b'|'))]
+    #[case::empty("", Err(()))]
+    #[case::several(";;", Err(()))]
+    #[case::non_ascii("§", Err(()))]
+    fn test_parse_delimiter(#[case] value: &str, #[case] expected: Result<u8, ()>) {
+        assert_eq!(parse_delimiter(value).map_err(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case::decimal_separator(&["program", "--decimal-separator", ";", "input.csv"])]
+    #[case::header_alias_column(&["program", "--header-alias", "id=transaction", "input.csv"])]
+    #[case::header_alias_format(&["program", "--header-alias", "transaction_id", "input.csv"])]
+    fn test_csv_dialect_options_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], false)]
+    #[case::legacy(&["program", "--legacy-tx-ids", "input.csv"], true)]
+    fn test_legacy_tx_ids_option(#[case] args: &[&str], #[case] expected: bool) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.input_options().legacy_tx_ids, expected);
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], 0)]
+    #[case::window(&["program", "--dedup-window", "1000", "input.csv"], 1000)]
+    fn test_dedup_window_option(#[case] args: &[&str], #[case] expected: usize) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.input_options().dedup_window, expected);
+    }
+
+    #[test]
+    fn test_client_id_offset_option() {
+        let parsed = parse(["program", "--client-id-offset", "1000", "input.csv"]).unwrap();
+        let input = parsed.input_options();
+        assert!(input.checks_records());
+        assert_eq!(
+            input.check(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 7,
+                tx: 1,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            }),
+            Ok(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1007,
+                tx: 1,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+        );
+
+        let parsed = parse(["program", "input.csv"]).unwrap();
+        assert!(parsed.input_options().middleware.is_empty());
+        assert!(parse(["program", "--client-id-offset", "-1", "input.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_client_map_option() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("clients.csv");
+        std::fs::write(&path, "external,client\nCUST-0042,2\n").unwrap();
+
+        let parsed = parse([
+            "program".as_ref(),
+            "--client-map".as_ref(),
+            path.as_os_str(),
+            "input.csv".as_ref(),
+        ])
+        .unwrap();
+        let registry = parsed.client_registry().unwrap().unwrap();
+        assert!(!registry.is_interning());
+        assert_eq!(registry.map().client("CUST-0042"), Some(2));
+
+        let parsed = parse([
+            "program".as_ref(),
+            "--client-map".as_ref(),
+            path.as_os_str(),
+            "--string-clients".as_ref(),
+            "input.csv".as_ref(),
+        ])
+        .unwrap();
+        let registry = parsed.client_registry().unwrap().unwrap();
+        assert!(registry.is_interning());
+        assert_eq!(registry.parse_client(b"CUST-0043"), Ok(3));
+
+        let parsed = parse(["program", "--string-clients", "input.csv"]).unwrap();
+        let registry = parsed.client_registry().unwrap().unwrap();
+        assert_eq!(*registry.map(), ClientMap::new());
+
+        let parsed = parse(["program", "input.csv"]).unwrap();
+        assert_eq!(parsed.client_registry(), Ok(None));
+        let parsed = parse(["program", "--client-map", "missing.csv", "input.csv"]).unwrap();
+        assert!(parsed.client_registry().is_err());
+        assert!(parse([
+            "program",
+            "--client-map",
+            "clients.csv",
+            "--client-id-offset",
+            "10",
+            "input.csv"
+        ])
+        .is_err());
+        assert!(parse([
+            "program",
+            "--string-clients",
+            "--save-state",
+            "state.bin",
+            "input.csv"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_pseudonymize_key_option() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, "0123456789abcdef\n").unwrap();
+        let args = |key: &std::ffi::OsStr| {
+            parse([
+                "program".as_ref(),
+                "--pseudonymize-key".as_ref(),
+                key,
+                "--string-clients".as_ref(),
+                "input.csv".as_ref(),
+            ])
+            .unwrap()
+        };
+
+        let parsed = args(path.as_os_str());
+        let registry = parsed.client_registry().unwrap().unwrap();
+        registry.parse_client(b"alice").unwrap();
+        let pseudonymizer = parsed.pseudonymizer(Some(registry)).unwrap().unwrap();
+        assert_eq!(pseudonymizer.client(1), pseudonymizer.pseudonym("alice"));
+
+        assert!(args("missing".as_ref()).pseudonymizer(None).is_err());
+        let parsed = parse(["program", "input.csv"]).unwrap();
+        assert_eq!(parsed.pseudonymizer(None), Ok(None));
+    }
+
+    #[test]
+    fn test_standing_orders_option() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("orders.csv");
+        std::fs::write(&path, "client,type,amount,every\n1,withdrawal,9.99,1000\n").unwrap();
+        let args = |orders: &std::ffi::OsStr| {
+            parse([
+                "program".as_ref(),
+                "--standing-orders".as_ref(),
+                orders,
+                "input.csv".as_ref(),
+            ])
+            .unwrap()
+        };
+
+        assert_eq!(
+            args(path.as_os_str()).standing_orders(),
+            Ok(vec![StandingOrder::new(
+                1,
+                crate::types::TransactionType::Withdrawal,
+                Decimal::new(999, 2),
+                1000
+            )])
+        );
+        assert!(args("missing".as_ref()).standing_orders().is_err());
+        let parsed = parse(["program", "input.csv"]).unwrap();
+        assert_eq!(parsed.standing_orders(), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_dead_letter_options() {
+        let parsed = parse([
+            "program",
+            "--dead-letter",
+            "kafka+http://proxy:8082/topics/dead-letters",
+            "--dead-letter-attempts",
+            "5",
+            "input.csv",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed.input_options().dead_letter,
+            Some(
+                DeadLetterOptions::new("kafka+http://proxy:8082/topics/dead-letters")
+                    .with_retry(RetryPolicy::new(5, Duration::from_millis(100)))
+            )
+        );
+        let parsed = parse(["program", "input.csv"]).unwrap();
+        assert_eq!(parsed.input_options().dead_letter, None);
+        assert!(parse(["program", "--dead-letter-attempts", "2", "input.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_quarantine_options() {
+        let parsed = parse([
+            "program",
+            "--quarantine",
+            "quarantine.csv",
+            "--quarantine-above",
+            "1000",
+            "input.csv",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed.input_options().quarantine,
+            Some(
+                QuarantineOptions::new("quarantine.csv")
+                    .with_rule(QuarantineRule::AmountAbove(Decimal::from(1000)))
+            )
+        );
+        assert_eq!(
+            parse(["program", "input.csv"])
+                .unwrap()
+                .input_options()
+                .quarantine,
+            None
+        );
+    }
+
+    #[test]
+    fn test_sanity_options() {
+        let parsed = parse([
+            "program",
+            "--warnings",
+            "warnings.csv",
+            "--warn-precision",
+            "--warn-above-percentile",
+            "99.9",
+            "--warn-new-client-withdrawals",
+            "input.csv",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed.input_options().sanity,
+            Some(
+                SanityOptions::new("warnings.csv")
+                    .with_check(SanityCheck::ExcessPrecision)
+                    .with_check(SanityCheck::AbovePercentile(Decimal::new(999, 1)))
+                    .with_check(SanityCheck::NewClientWithdrawal)
+            )
+        );
+        assert_eq!(
+            parse(["program", "input.csv"])
+                .unwrap()
+                .input_options()
+                .sanity,
+            None
+        );
+    }
+
+    #[rstest]
+    #[case::file_without_check(&["program", "--warnings", "warnings.csv", "input.csv"])]
+    #[case::check_without_file(&["program", "--warn-precision", "input.csv"])]
+    #[case::percentile_of_100(&["program", "--warnings", "w.csv", "--warn-above-percentile", "100", "input.csv"])]
+    #[case::zero_percentile(&["program", "--warnings", "w.csv", "--warn-above-percentile", "0", "input.csv"])]
+    #[case::sharded(&["program", "--warnings", "w.csv", "--warn-precision", "--shards", "2", "input.csv"])]
+    fn test_sanity_options_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[rstest]
+    #[case::file_without_rule(&["program", "--quarantine", "quarantine.csv", "input.csv"])]
+    #[case::rule_without_file(&["program", "--quarantine-above", "1000", "input.csv"])]
+    #[case::invalid_amount(&["program", "--quarantine", "q.csv", "--quarantine-above", "lots", "input.csv"])]
+    fn test_quarantine_options_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
 
-    /// Parsing strategy to use for processing transactions
-    #[arg(
-        long = "strategy",
-        value_name = "STRATEGY",
-        default_value = "async",
-        help = "Parsing strategy: 'sync' for synchronous or 'async' for asynchronous"
-    )]
-    pub strategy: StrategyType,
+    #[test]
+    fn test_velocity_limit() {
+        let args = [
+            "program",
+            "--velocity-window",
+            "10",
+            "--velocity-max-withdrawals",
+            "3",
+            "--velocity-max-amount",
+            "500",
+            "input.csv",
+        ];
+        let limit = VelocityLimit::new(10)
+            .with_max_withdrawals(3)
+            .with_max_withdrawn(Decimal::from(500));
 
-    /// Number of transactions per batch (async mode only)
-    #[arg(
-        long = "batch-size",
-        value_name = "SIZE",
-        help = "Number of transactions per batch (default: 1000, range: 100-10000)"
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.engine_config().unwrap().velocity_limit, Some(limit));
+        assert_eq!(parsed.input_options().quarantine, None);
+
+        let quarantined =
+            parse(
+                args.iter()
+                    .copied()
+                    .chain(["--quarantine", "q.csv", "--quarantine-velocity"]),
+            )
+            .unwrap();
+        assert_eq!(quarantined.engine_config().unwrap().velocity_limit, None);
+        assert_eq!(
+            quarantined.input_options().quarantine,
+            Some(QuarantineOptions::new("q.csv").with_rule(QuarantineRule::Velocity(limit)))
+        );
+    }
+
+    #[rstest]
+    #[case::window_without_max(&["program", "--velocity-window", "10", "input.csv"])]
+    #[case::max_without_window(&["program", "--velocity-max-withdrawals", "3", "input.csv"])]
+    #[case::zero_window(&["program", "--velocity-window", "0", "--velocity-max-amount", "5", "input.csv"])]
+    #[case::quarantine_without_window(&["program", "--quarantine", "q.csv", "--quarantine-velocity", "input.csv"])]
+    #[case::ledger(&["program", "--velocity-window", "5", "--velocity-max-amount", "5", "--ledger", "l.db", "input.csv"])]
+    fn test_velocity_limit_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[test]
+    fn test_cutoff_options() {
+        let parsed = parse([
+            "program",
+            "--snapshot-every",
+            "1000000",
+            "--snapshot-dir",
+            "cutoffs",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.input_options().cutoffs,
+            Some(CutoffOptions::new("cutoffs", 1_000_000))
+        );
+    }
+
+    #[rstest]
+    #[case::every_without_dir(&["program", "--snapshot-every", "10", "input.csv"])]
+    #[case::dir_without_every(&["program", "--snapshot-dir", "cutoffs", "input.csv"])]
+    #[case::zero(&["program", "--snapshot-every", "0", "--snapshot-dir", "cutoffs", "input.csv"])]
+    fn test_cutoff_options_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[rstest]
+    #[case::none(&["program", "input.csv"], None)]
+    #[case::file(&["program", "--journal", "journal.csv", "input.csv"], Some("journal.csv"))]
+    fn test_journal_option(#[case] args: &[&str], #[case] expected: Option<&str>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.input_options().journal, expected.map(PathBuf::from));
+    }
+
+    #[test]
+    fn test_trial_balance_option() {
+        let parsed = parse(["program", "--trial-balance", "tb.csv", "input.csv"]).unwrap();
+        assert_eq!(
+            parsed.input_options().trial_balance,
+            Some(PathBuf::from("tb.csv"))
+        );
+        assert!(parse([
+            "program",
+            "--trial-balance",
+            "tb.csv",
+            "--ledger",
+            "ledger.db",
+            "input.csv"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_changes_option() {
+        let parsed = parse([
+            "program",
+            "--changes",
+            "kafka+http://proxy:8082/topics/balance-changes",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.input_options().changes.as_deref(),
+            Some("kafka+http://proxy:8082/topics/balance-changes")
+        );
+        assert!(parse([
+            "program",
+            "--changes",
+            "changes.jsonl",
+            "--shards",
+            "2",
+            "input.csv"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_balance_history_options() {
+        let parsed = parse([
+            "program",
+            "--balance-history",
+            "history.csv",
+            "--history-every",
+            "100",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.input_options().balance_history,
+            Some(BalanceHistoryOptions::new("history.csv", 100))
+        );
+    }
+
+    #[rstest]
+    #[case::none(&["program", "input.csv"], None)]
+    #[case::default_top(&["program", "--analytics", "-", "input.csv"], Some(10))]
+    #[case::top(&["program", "--analytics", "a.json", "--analytics-top", "3", "input.csv"], Some(3))]
+    fn test_analytics_options(#[case] args: &[&str], #[case] expected: Option<usize>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.input_options().analytics, expected);
+    }
+
+    #[rstest]
+    #[case::file_without_every(&["program", "--balance-history", "h.csv", "input.csv"])]
+    #[case::every_without_file(&["program", "--history-every", "10", "input.csv"])]
+    #[case::zero(&["program", "--balance-history", "h.csv", "--history-every", "0", "input.csv"])]
+    fn test_balance_history_options_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[rstest]
+    #[case::none(&["program", "input.csv"], None)]
+    #[case::file(&["program", "--summary", "summary.json", "input.csv"], Some("summary.json"))]
+    #[case::stderr(&["program", "--summary", "-", "input.csv"], Some("-"))]
+    fn test_summary_option(#[case] args: &[&str], #[case] expected: Option<&str>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.summary.as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], Some(LogFormat::Text))]
+    #[case::json(&["program", "--log-format", "json", "input.csv"], Some(LogFormat::Json))]
+    #[case::invalid(&["program", "--log-format", "xml", "input.csv"], None)]
+    fn test_log_format_option(#[case] args: &[&str], #[case] expected: Option<LogFormat>) {
+        assert_eq!(parse(args).ok().map(|parsed| parsed.log_format), expected);
+    }
+
+    #[test]
+    fn test_max_errors_per_class_option() {
+        let parsed = parse(["program", "--max-errors-per-class", "100", "input.csv"]).unwrap();
+        assert_eq!(parsed.max_errors_per_class, Some(100));
+        assert_eq!(
+            parse(["program", "input.csv"])
+                .unwrap()
+                .max_errors_per_class,
+            None
+        );
+        assert!(parse(["program", "--max-errors-per-class", "-1", "input.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_follow_options() {
+        let parsed = parse([
+            "program",
+            "--follow",
+            "--snapshot-interval",
+            "2",
+            "--idle-timeout",
+            "60",
+            "input.csv",
+        ])
+        .unwrap();
+
+        assert!(parsed.follow);
+        let follow = parsed.follow_options();
+        assert_eq!(follow.snapshot_interval, Duration::from_secs(2));
+        assert_eq!(follow.idle_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(
+            parse(["program", "--follow", "input.csv"])
+                .unwrap()
+                .follow_options(),
+            FollowOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_replica_options() {
+        let parsed = parse([
+            "program",
+            "--replica",
+            "--snapshot-interval",
+            "1",
+            "--save-state",
+            "replica.bin",
+            "engine.wal",
+        ])
+        .unwrap();
+
+        assert!(parsed.replica);
+        assert!(!parsed.follow);
+        assert_eq!(
+            parsed.follow_options().snapshot_interval,
+            Duration::from_secs(1)
+        );
+    }
+
+    #[rstest]
+    #[case::interval_without_follow(&["program", "--snapshot-interval", "2", "input.csv"])]
+    #[case::timeout_without_follow(&["program", "--idle-timeout", "2", "input.csv"])]
+    #[case::with_wal(&["program", "--follow", "--wal", "engine.wal", "input.csv"])]
+    #[case::replica_with_follow(&["program", "--replica", "--follow", "engine.wal"])]
+    #[case::replica_with_wal(&["program", "--replica", "--wal", "other.wal", "engine.wal"])]
+    fn test_follow_options_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[test]
+    fn test_object_urls_are_not_globbed() {
+        let parsed = parse(["program", "s3://bucket/2024-[01].csv"]).unwrap();
+        assert_eq!(
+            parsed.input_paths().unwrap(),
+            vec![PathBuf::from("s3://bucket/2024-[01].csv")]
+        );
+    }
+
+    #[test]
+    fn test_multiple_inputs_keep_order() {
+        let parsed = parse(["program", "b.csv", "a.csv"]).unwrap();
+        assert_eq!(
+            parsed.input_paths().unwrap(),
+            vec![PathBuf::from("b.csv"), PathBuf::from("a.csv")]
+        );
+    }
+
+    #[test]
+    fn test_missing_input_is_rejected() {
+        assert!(parse(["program"]).is_err());
+    }
+
+    #[test]
+    fn test_glob_inputs_are_expanded_in_sorted_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["2024-02.csv", "2024-01.csv", "other.csv"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let pattern = dir.path().join("2024-*.csv");
+
+        let parsed = parse(["program".as_ref(), pattern.as_os_str()]).unwrap();
+        assert_eq!(
+            parsed.input_paths().unwrap(),
+            vec![
+                dir.path().join("2024-01.csv"),
+                dir.path().join("2024-02.csv")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_glob_without_matches_fails() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pattern = dir.path().join("*.csv");
+
+        let parsed = parse(["program".as_ref(), pattern.as_os_str()]).unwrap();
+        assert!(parsed
+            .input_paths()
+            .unwrap_err()
+            .contains("No input files match"));
+    }
+
+    #[rstest]
+    #[case::no_ledger(&["program", "input.csv"], None)]
+    #[case::ledger(&["program", "--ledger", "ledger.db", "input.csv"], Some("ledger.db"))]
+    fn test_ledger_option(#[case] args: &[&str], #[case] expected: Option<&str>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.ledger, expected.map(PathBuf::from));
+    }
+
+    #[rstest]
+    #[case::default_stdout(&["program", "input.csv"], "-")]
+    #[case::file(&["program", "-o", "accounts.csv", "input.csv"], "accounts.csv")]
+    #[case::postgres(
+        &["program", "--output", "postgres://localhost/payments", "input.csv"],
+        "postgres://localhost/payments"
     )]
-    pub batch_size: Option<usize>,
+    fn test_output_option(#[case] args: &[&str], #[case] expected: &str) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.output, expected);
+    }
 
-    /// Maximum number of concurrent batches (async mode only)
-    #[arg(
-        long = "max-concurrent",
-        value_name = "COUNT",
-        help = "Maximum number of batches processing concurrently (default: CPU cores)"
+    #[rstest]
+    #[case::no_wal(&["program", "input.csv"], None)]
+    #[case::wal(&["program", "--wal", "engine.wal", "input.csv"], Some("engine.wal"))]
+    fn test_wal_option(#[case] args: &[&str], #[case] expected: Option<&str>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.wal, expected.map(PathBuf::from));
+    }
+
+    #[test]
+    fn test_wal_conflicts_with_ledger() {
+        let result = parse([
+            "program",
+            "--wal",
+            "engine.wal",
+            "--ledger",
+            "ledger.db",
+            "input.csv",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_withdrawal_requirements() {
+        let parsed = parse([
+            "program",
+            "--accounts-metadata",
+            "accounts.csv",
+            "--require-for-withdrawal",
+            "kyc_status=verified",
+            "--require-for-withdrawal",
+            "region=eu",
+            "input.csv",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed.accounts_metadata,
+            Some(PathBuf::from("accounts.csv"))
+        );
+        let requirements: Vec<String> = parsed
+            .withdrawal_requirements
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(requirements, ["kyc_status=verified", "region=eu"]);
+    }
+
+    #[rstest]
+    #[case::requirement_without_metadata(&["program", "--require-for-withdrawal", "kyc_status=verified", "input.csv"])]
+    #[case::invalid_requirement(&["program", "--accounts-metadata", "accounts.csv", "--require-for-withdrawal", "kyc_status", "input.csv"])]
+    #[case::metadata_with_ledger(&["program", "--accounts-metadata", "accounts.csv", "--ledger", "ledger.db", "input.csv"])]
+    fn test_account_metadata_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], RedisputePolicy::Unlimited)]
+    #[case::never(&["program", "--redispute", "never", "input.csv"], RedisputePolicy::Limit(0))]
+    #[case::once(&["program", "--redispute", "once", "input.csv"], RedisputePolicy::Limit(1))]
+    #[case::limit(&["program", "--redispute", "3", "input.csv"], RedisputePolicy::Limit(3))]
+    fn test_redispute_policy(#[case] args: &[&str], #[case] expected: RedisputePolicy) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.redispute_policy, expected);
+        assert_eq!(parsed.engine_config().unwrap().redispute_policy, expected);
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], false)]
+    #[case::enabled(&["program", "--allow-direct-chargeback", "input.csv"], true)]
+    fn test_allow_direct_chargeback(#[case] args: &[&str], #[case] expected: bool) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.allow_direct_chargeback, expected);
+        assert_eq!(
+            parsed.engine_config().unwrap().allow_direct_chargeback,
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], DisputableTypes::All)]
+    #[case::deposits(&["program", "--disputable", "deposits", "input.csv"], DisputableTypes::Deposits)]
+    fn test_disputable_types(#[case] args: &[&str], #[case] expected: DisputableTypes) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.disputable_types, expected);
+        assert_eq!(parsed.engine_config().unwrap().disputable_types, expected);
+        assert!(parse(["program", "--disputable", "refunds", "input.csv"]).is_err());
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], DuplicateTxPolicy::Reject)]
+    #[case::ignore(&["program", "--duplicate-tx", "ignore", "input.csv"], DuplicateTxPolicy::Ignore)]
+    #[case::per_client(&["program", "--duplicate-tx", "per-client", "input.csv"], DuplicateTxPolicy::PerClient)]
+    fn test_duplicate_tx_policy(#[case] args: &[&str], #[case] expected: DuplicateTxPolicy) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.duplicate_tx_policy, expected);
+        assert_eq!(
+            parsed.engine_config().unwrap().duplicate_tx_policy,
+            expected
+        );
+        assert!(parse(["program", "--duplicate-tx", "allow", "input.csv"]).is_err());
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], NegativeBalancePolicy::Reject)]
+    #[case::allow(&["program", "--negative-balance", "allow", "input.csv"], NegativeBalancePolicy::AllowDebt)]
+    fn test_negative_balance_policy(
+        #[case] args: &[&str],
+        #[case] expected: NegativeBalancePolicy,
+    ) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.negative_balance_policy, expected);
+        assert_eq!(
+            parsed.engine_config().unwrap().negative_balance_policy,
+            expected
+        );
+        assert!(parse(["program", "--negative-balance", "debt", "input.csv"]).is_err());
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], None)]
+    #[case::enabled(&["program", "--dispute-expiry", "100", "input.csv"], Some(100))]
+    fn test_dispute_expiry(#[case] args: &[&str], #[case] expected: Option<u32>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.engine_config().unwrap().dispute_expiry, expected);
+        assert!(parse(["program", "--dispute-expiry", "0", "input.csv"]).is_err());
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], None)]
+    #[case::enabled(
+        &["program", "--approve-withdrawals-above", "1000", "input.csv"],
+        Some(Decimal::from(1000))
     )]
-    pub max_concurrent_batches: Option<usize>,
-}
+    fn test_approve_withdrawals_above(#[case] args: &[&str], #[case] expected: Option<Decimal>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(
+            parsed.engine_config().unwrap().withdrawal_approval_above,
+            expected
+        );
+    }
 
-/// Available parsing strategies for CSV processing
-#[derive(Clone, Debug, ValueEnum)]
-pub enum StrategyType {
-    Sync,
-    Async,
-}
+    #[test]
+    fn test_amount_limits() {
+        let parsed = parse([
+            "program",
+            "--max-deposit",
+            "10000",
+            "--max-total",
+            "50000.5",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.engine_config().unwrap().limits,
+            AmountLimits {
+                max_deposit: Some(Decimal::from(10000)),
+                max_withdrawal: None,
+                max_total: Some(Decimal::new(500005, 1)),
+            }
+        );
+        assert!(parse(["program", "--max-withdrawal", "lots", "input.csv"]).is_err());
+    }
 
-impl CliArgs {
-    /// Create a BatchConfig from CLI arguments
-    ///
-    /// This method constructs a BatchConfig using the CLI arguments if provided,
-    /// or falls back to default values. It also validates the configuration and
-    /// prints warnings to stderr if any issues are detected.
-    ///
-    /// # Returns
-    ///
-    /// A `BatchConfig` with values from CLI arguments or defaults.
-    pub fn to_batch_config(&self) -> BatchConfig {
-        // Use provided values or defaults
-        if self.batch_size.is_some() || self.max_concurrent_batches.is_some() {
-            // At least one custom value provided, create custom config
-            let default = BatchConfig::default();
-            BatchConfig::new(
-                self.batch_size.unwrap_or(default.batch_size),
-                self.max_concurrent_batches
-                    .unwrap_or(default.max_concurrent_batches),
-            )
-        } else {
-            // No custom values, use all defaults
-            BatchConfig::default()
+    #[test]
+    fn test_shards_option() {
+        let args = parse(["program", "--shards", "4", "input.csv"]).unwrap();
+        assert_eq!(args.shards, Some(4));
+        assert_eq!(parse(["program", "input.csv"]).unwrap().shards, None);
+
+        for conflicting in [
+            &["--strategy", "async"][..],
+            &["--journal", "journal.csv"],
+            &["--dispute-expiry", "10"],
+        ] {
+            let mut command_line = vec!["program", "--shards", "4"];
+            command_line.extend_from_slice(conflicting);
+            command_line.push("input.csv");
+            assert!(parse(command_line).is_err(), "{:?}", conflicting);
         }
+        assert!(parse(["program", "--shards", "0", "input.csv"]).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rstest::rstest;
+    #[rstest]
+    #[case::default(&["program", "input.csv"], None)]
+    #[case::rate(&["program", "--held-interest", "0.005", "input.csv"], Some(Decimal::new(5, 3)))]
+    fn test_held_interest_option(#[case] args: &[&str], #[case] expected: Option<Decimal>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.engine_config().unwrap().held_interest, expected);
+    }
 
-    // Strategy parsing tests
     #[rstest]
-    #[case::default_strategy(&["program", "input.csv"], StrategyType::Async)]
-    #[case::explicit_sync(&["program", "--strategy", "sync", "input.csv"], StrategyType::Sync)]
-    #[case::explicit_async(&["program", "--strategy", "async", "input.csv"], StrategyType::Async)]
-    fn test_strategy_parsing(#[case] args: &[&str], #[case] expected: StrategyType) {
-        let parsed = CliArgs::try_parse_from(args).unwrap();
-        match (&parsed.strategy, &expected) {
-            (StrategyType::Sync, StrategyType::Sync) => (),
-            (StrategyType::Async, StrategyType::Async) => (),
-            _ => panic!("Expected {:?}, got {:?}", expected, parsed.strategy),
-        }
+    #[case::negative("-0.01")]
+    #[case::invalid("half")]
+    fn test_held_interest_option_invalid(#[case] rate: &str) {
+        assert!(parse(["program", "--held-interest", rate, "input.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_fee_options() {
+        let parsed = parse([
+            "program",
+            "--deposit-fee",
+            "0.25+1%",
+            "--fee-account",
+            "9999",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.engine_config().unwrap().fees,
+            Some(FeeSchedule::new(9999).with_deposit_fee("0.25+1%".parse().unwrap()))
+        );
+        assert_eq!(
+            parse(["program", "input.csv"])
+                .unwrap()
+                .engine_config()
+                .unwrap()
+                .fees,
+            None
+        );
+
+        // A fee needs a fee account, and must be valid
+        assert!(parse(["program", "--withdrawal-fee", "1", "input.csv"]).is_err());
+        assert!(parse([
+            "program",
+            "--withdrawal-fee",
+            "one",
+            "--fee-account",
+            "9999",
+            "input.csv"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_risk_rules_option() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let rules = dir.path().join("rules.json");
+        std::fs::write(
+            &rules,
+            r#"{"max_withdrawal": "500", "velocity": {"window": 5, "max_amount": "900"}}"#,
+        )
+        .unwrap();
+        let rules = rules.to_str().unwrap();
+
+        let config = parse(["program", "--risk-rules", rules, "input.csv"])
+            .unwrap()
+            .engine_config()
+            .unwrap();
+        assert_eq!(config.limits.max_withdrawal, Some(Decimal::from(500)));
+        assert_eq!(
+            config.velocity_limit,
+            Some(VelocityLimit::new(5).with_max_withdrawn(Decimal::from(900)))
+        );
+
+        let ledger = parse([
+            "program",
+            "--risk-rules",
+            rules,
+            "--ledger",
+            "l.db",
+            "input.csv",
+        ]);
+        assert!(ledger.unwrap().engine_config().is_err());
+        assert!(parse([
+            "program",
+            "--risk-rules",
+            rules,
+            "--max-deposit",
+            "1",
+            "input.csv"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_redispute_policy_invalid() {
+        assert!(parse(["program", "--redispute", "twice", "input.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_engine_config_missing_metadata_file() {
+        let parsed = parse(["program", "--accounts-metadata", "missing.csv", "input.csv"]).unwrap();
+        assert!(parsed
+            .engine_config()
+            .unwrap_err()
+            .contains("Failed to open metadata file"));
     }
 
     // Individual config option tests
@@ -105,7 +2414,7 @@ mod tests {
         #[case] batch_size: Option<usize>,
         #[case] max_concurrent: Option<usize>,
     ) {
-        let parsed = CliArgs::try_parse_from(args).unwrap();
+        let parsed = parse(args).unwrap();
         assert_eq!(parsed.batch_size, batch_size);
         assert_eq!(parsed.max_concurrent_batches, max_concurrent);
     }
@@ -125,23 +2434,24 @@ mod tests {
         #[case] expected_batch_size: usize,
         #[case] expected_max_concurrent: usize,
     ) {
-        let parsed = CliArgs::try_parse_from(args).unwrap();
+        let parsed = parse(args).unwrap();
         let config = parsed.to_batch_config();
 
         assert_eq!(config.batch_size, expected_batch_size);
         assert_eq!(config.max_concurrent_batches, expected_max_concurrent);
     }
 
-    // BatchConfig edge cases - zero values should fall back to defaults
+    // BatchConfig edge cases - zero values fall back to defaults, large ones are capped
     #[rstest]
     #[case::zero_batch_size(&["program", "--batch-size", "0", "input.csv"], "batch_size", 1000)]
     #[case::zero_max_concurrent(&["program", "--max-concurrent", "0", "input.csv"], "max_concurrent", num_cpus::get())]
+    #[case::capped_batch_size(&["program", "--batch-size", "99999999", "input.csv"], "batch_size", MAX_BATCH_SIZE)]
     fn test_batch_config_zero_values_fallback(
         #[case] args: &[&str],
         #[case] field: &str,
         #[case] expected_default: usize,
     ) {
-        let parsed = CliArgs::try_parse_from(args).unwrap();
+        let parsed = parse(args).unwrap();
         let config = parsed.to_batch_config();
 
         match field {
@@ -151,12 +2461,489 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case::unlimited(&["program", "input.csv"], None)]
+    #[case::limited(&["program", "--max-inflight-clients", "4", "input.csv"], Some(4))]
+    #[case::zero_falls_back(&["program", "--max-inflight-clients", "0", "input.csv"], None)]
+    fn test_max_inflight_clients(#[case] args: &[&str], #[case] expected: Option<usize>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.to_batch_config().max_inflight_clients, expected);
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], RetryPolicy::none())]
+    #[case::attempts(
+        &["program", "--retry-attempts", "4", "input.csv"],
+        RetryPolicy::new(4, Duration::from_millis(10))
+    )]
+    #[case::backoff(
+        &["program", "--retry-attempts", "2", "--retry-backoff-ms", "50", "input.csv"],
+        RetryPolicy::new(2, Duration::from_millis(50))
+    )]
+    fn test_retry_policy(#[case] args: &[&str], #[case] expected: RetryPolicy) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.to_batch_config().retry, expected);
+    }
+
+    #[rstest]
+    #[case::none(&["program", "input.csv"], None)]
+    #[case::report(
+        &["program", "--batch-deadline-ms", "500", "input.csv"],
+        Some(BatchDeadline::new(Duration::from_millis(500)))
+    )]
+    #[case::abort(
+        &["program", "--batch-deadline-ms", "20", "--on-batch-deadline", "abort", "input.csv"],
+        Some(BatchDeadline::new(Duration::from_millis(20)).with_policy(DeadlinePolicy::Abort))
+    )]
+    fn test_batch_deadline(#[case] args: &[&str], #[case] expected: Option<BatchDeadline>) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.to_batch_config().batch_deadline, expected);
+    }
+
+    #[rstest]
+    #[case::zero(&["program", "--batch-deadline-ms", "0", "input.csv"])]
+    #[case::unknown_policy(&["program", "--batch-deadline-ms", "5", "--on-batch-deadline", "stop", "input.csv"])]
+    #[case::policy_without_deadline(&["program", "--on-batch-deadline", "abort", "input.csv"])]
+    fn test_batch_deadline_invalid(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn test_inject_faults() {
+        use crate::core::FaultInjection;
+
+        let parsed = parse([
+            "program",
+            "--inject-faults",
+            "errors=10,seed=3",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.to_batch_config().faults,
+            Some(FaultInjection::new(3).with_errors(0.1))
+        );
+        assert!(parse(["program", "--inject-faults", "errors=x", "input.csv"]).is_err());
+    }
+
+    #[rstest]
+    #[case::defaults(&["program", "input.csv"], RuntimeOptions::default())]
+    #[case::tuned(
+        &["program", "--runtime", "current-thread", "--pin-threads", "--thread-name", "payments", "input.csv"],
+        RuntimeOptions {
+            flavor: RuntimeFlavor::CurrentThread,
+            pin_threads: true,
+            thread_name: Some("payments".to_string()),
+        }
+    )]
+    #[case::auto(
+        &["program", "--runtime", "auto", "input.csv"],
+        RuntimeOptions { flavor: RuntimeFlavor::Auto, ..RuntimeOptions::default() }
+    )]
+    fn test_runtime_options(#[case] args: &[&str], #[case] expected: RuntimeOptions) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.to_batch_config().runtime, expected);
+    }
+
+    #[rstest]
+    #[case::disabled(&["program", "input.csv"], None)]
+    #[case::rate(
+        &["program", "--duplicate-filter", "1", "input.csv"],
+        Some(DuplicateFilterOptions::new(0.01))
+    )]
+    #[case::capacity(
+        &["program", "--duplicate-filter", "0.1%", "--duplicate-filter-capacity", "5000", "input.csv"],
+        Some(DuplicateFilterOptions::new(0.001).with_capacity(5000))
+    )]
+    fn test_duplicate_filter_options(
+        #[case] args: &[&str],
+        #[case] expected: Option<DuplicateFilterOptions>,
+    ) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.to_batch_config().duplicate_filter, expected);
+    }
+
+    #[test]
+    fn test_capacity_hints() {
+        let parsed = parse([
+            "program",
+            "--expected-clients",
+            "5000",
+            "--expected-transactions",
+            "2000000",
+            "input.csv",
+        ])
+        .unwrap();
+        let config = parsed.engine_config().unwrap();
+        assert_eq!(config.expected_clients, Some(5000));
+        assert_eq!(config.expected_transactions, Some(2_000_000));
+
+        let config = parse(["program", "input.csv"])
+            .unwrap()
+            .engine_config()
+            .unwrap();
+        assert_eq!(config.expected_clients, None);
+        assert_eq!(config.expected_transactions, None);
+    }
+
+    #[rstest]
+    #[case::zero(&["program", "--duplicate-filter", "0", "input.csv"])]
+    #[case::hundred(&["program", "--duplicate-filter", "100", "input.csv"])]
+    #[case::capacity_without_filter(&["program", "--duplicate-filter-capacity", "10", "input.csv"])]
+    fn test_duplicate_filter_rejected(#[case] args: &[&str]) {
+        assert!(parse(args).is_err());
+    }
+
+    // Exit policy tests
+    #[rstest]
+    #[case::defaults(&["program", "input.csv"], false, None)]
+    #[case::fail_on_error(&["program", "--fail-on-error", "input.csv"], true, None)]
+    #[case::max_error_rate(&["program", "--max-error-rate", "5", "input.csv"], false, Some(5.0))]
+    #[case::both(
+        &["program", "--fail-on-error", "--max-error-rate", "2.5%", "input.csv"],
+        true,
+        Some(2.5)
+    )]
+    fn test_exit_policy_options(
+        #[case] args: &[&str],
+        #[case] fail_on_error: bool,
+        #[case] max_error_rate: Option<f64>,
+    ) {
+        let parsed = parse(args).unwrap();
+        let policy = parsed.exit_policy();
+        assert_eq!(policy.fail_on_error, fail_on_error);
+        assert_eq!(policy.max_error_rate, max_error_rate);
+        assert!(!policy.check_conservation);
+    }
+
+    #[rstest]
+    #[case::none(&["program", "input.csv"], false, false)]
+    #[case::output_only(&["program", "--clients", "1,7-20", "input.csv"], true, false)]
+    #[case::filter_input(
+        &["program", "--clients", "1,7-20", "--filter-input", "input.csv"],
+        true,
+        true
+    )]
+    fn test_clients_options(
+        #[case] args: &[&str],
+        #[case] has_clients: bool,
+        #[case] filters_input: bool,
+    ) {
+        let parsed = parse(args).unwrap();
+        assert_eq!(parsed.clients.is_some(), has_clients);
+        if let Some(clients) = &parsed.clients {
+            assert!(clients.contains(12));
+            assert!(!clients.contains(2));
+        }
+        assert_eq!(parsed.input_options().clients.is_some(), filters_input);
+    }
+
+    #[test]
+    fn test_save_state_option() {
+        let parsed = parse(["program", "--save-state", "state.bin", "input.csv"]).unwrap();
+        assert_eq!(parsed.save_state, Some(PathBuf::from("state.bin")));
+    }
+
+    #[rstest]
+    #[case::implicit(&["program", "--strategy", "sync", "input.csv"])]
+    #[case::explicit(&["program", "process", "--strategy", "sync", "input.csv"])]
+    #[case::input_first(&["program", "input.csv", "--strategy", "sync"])]
+    fn test_default_command(#[case] args: &[&str]) {
+        let parsed = parse(args).unwrap();
+        assert!(matches!(parsed.strategy, StrategyType::Sync));
+        assert_eq!(parsed.input_files, vec![PathBuf::from("input.csv")]);
+    }
+
+    #[rstest]
+    #[case::no_arguments(&["program"], &["program"])]
+    #[case::subcommand(&["program", "query", "--client", "1"], &["program", "query", "--client", "1"])]
+    #[case::help(&["program", "--help"], &["program", "--help"])]
+    #[case::help_command(&["program", "help", "bench"], &["program", "help", "bench"])]
+    #[case::input(&["program", "query.csv"], &["program", "process", "query.csv"])]
+    #[case::option(&["program", "--fast-csv", "a.csv"], &["program", "process", "--fast-csv", "a.csv"])]
+    fn test_with_default_command(#[case] args: &[&str], #[case] expected: &[&str]) {
+        let expected: Vec<OsString> = expected.iter().map(OsString::from).collect();
+        assert_eq!(with_default_command(args.iter().copied()), expected);
+    }
+
+    #[test]
+    fn test_query_subcommand() {
+        let parsed = CliArgs::try_parse_from([
+            "program",
+            "query",
+            "--state",
+            "state.bin",
+            "--client",
+            "42",
+            "--tx",
+            "1234",
+        ])
+        .unwrap();
+        let Command::Query(query) = parsed.command else {
+            panic!("expected the query subcommand");
+        };
+        assert_eq!(query.state, PathBuf::from("state.bin"));
+        assert_eq!(query.client, 42);
+        assert_eq!(query.tx, Some(1234));
+    }
+
+    #[test]
+    fn test_statement_subcommand() {
+        let parsed = CliArgs::try_parse_from([
+            "program",
+            "statement",
+            "--state",
+            "state.bin",
+            "--client",
+            "42",
+        ])
+        .unwrap();
+        let Command::Statement(statement) = parsed.command else {
+            panic!("expected the statement subcommand");
+        };
+        assert_eq!(statement.state, PathBuf::from("state.bin"));
+        assert_eq!(statement.client, 42);
+    }
+
+    #[test]
+    fn test_reconcile_subcommand() {
+        let parsed = CliArgs::try_parse_from([
+            "program",
+            "reconcile",
+            "--expected",
+            "expected.csv",
+            "day1.csv",
+            "day2.csv",
+        ])
+        .unwrap();
+        let Command::Reconcile(reconcile) = parsed.command else {
+            panic!("expected the reconcile subcommand");
+        };
+        assert_eq!(reconcile.expected, PathBuf::from("expected.csv"));
+        assert_eq!(reconcile.state, None);
+        assert_eq!(
+            reconcile.input_files,
+            vec![PathBuf::from("day1.csv"), PathBuf::from("day2.csv")]
+        );
+    }
+
+    #[test]
+    fn test_bench_subcommand() {
+        let parsed = CliArgs::try_parse_from(["program", "bench", "input.csv"]).unwrap();
+        let Command::Bench(bench) = parsed.command else {
+            panic!("expected the bench subcommand");
+        };
+        assert!(matches!(
+            bench.strategies.as_slice(),
+            [StrategyType::Sync, StrategyType::Async]
+        ));
+        assert_eq!(bench.iterations, 5);
+
+        let parsed = CliArgs::try_parse_from([
+            "program",
+            "bench",
+            "--strategy",
+            "async",
+            "--iterations",
+            "2",
+            "input.csv",
+        ])
+        .unwrap();
+        let Command::Bench(bench) = parsed.command else {
+            panic!("expected the bench subcommand");
+        };
+        assert!(matches!(bench.strategies.as_slice(), [StrategyType::Async]));
+        assert_eq!(bench.iterations, 2);
+        assert_eq!(bench.input_files, vec![PathBuf::from("input.csv")]);
+    }
+
+    #[test]
+    fn test_generate_subcommand() {
+        let parsed = CliArgs::try_parse_from([
+            "program",
+            "generate",
+            "--transactions",
+            "500",
+            "--clients",
+            "3",
+            "--seed",
+            "7",
+        ])
+        .unwrap();
+        let Command::Generate(generate) = parsed.command else {
+            panic!("expected the generate subcommand");
+        };
+        assert_eq!(generate.transactions, 500);
+        assert_eq!(generate.clients, 3);
+        assert_eq!(generate.seed, 7);
+    }
+
+    #[test]
+    fn test_merge_subcommand() {
+        let parsed =
+            CliArgs::try_parse_from(with_default_command(["program", "merge", "a.csv", "b.csv"]))
+                .unwrap();
+        let Command::Merge(merge) = parsed.command else {
+            panic!("expected the merge subcommand");
+        };
+        assert_eq!(
+            merge.inputs,
+            vec![PathBuf::from("a.csv"), PathBuf::from("b.csv")]
+        );
+        assert_eq!(merge.output, "-");
+        assert!(CliArgs::try_parse_from(["program", "merge"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_subcommand() {
+        let parsed = CliArgs::try_parse_from(with_default_command([
+            "program",
+            "apply",
+            "--state",
+            "state.bin",
+            "delta.csv",
+        ]))
+        .unwrap();
+        let Command::Apply(apply) = parsed.command else {
+            panic!("expected the apply subcommand");
+        };
+        assert_eq!(apply.state, PathBuf::from("state.bin"));
+        assert_eq!(apply.input_files, vec![PathBuf::from("delta.csv")]);
+        assert_eq!(apply.save_state, None);
+        assert!(CliArgs::try_parse_from(["program", "apply", "delta.csv"]).is_err());
+        assert!(CliArgs::try_parse_from(["program", "apply", "--state", "state.bin"]).is_err());
+    }
+
+    #[test]
+    fn test_check_conservation_option() {
+        let parsed = parse(["program", "--check-conservation", "input.csv"]).unwrap();
+        assert!(parsed.exit_policy().check_conservation);
+        assert!(parsed.exit_policy().is_enabled());
+
+        let result = parse([
+            "program",
+            "--check-conservation",
+            "--ledger",
+            "ledger.db",
+            "input.csv",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_lock_reasons_option() {
+        assert!(!parse(["program", "input.csv"]).unwrap().lock_reasons);
+        assert!(
+            parse(["program", "--lock-reasons", "input.csv"])
+                .unwrap()
+                .lock_reasons
+        );
+    }
+
+    #[test]
+    fn test_columns_option() {
+        assert_eq!(parse(["program", "input.csv"]).unwrap().columns, None);
+        let args = parse([
+            "program",
+            "--columns",
+            "client,total,last_tx,owner",
+            "input.csv",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.columns.unwrap().columns(),
+            [
+                AccountColumn::Client,
+                AccountColumn::Total,
+                AccountColumn::LastTx,
+                AccountColumn::Metadata("owner".to_string()),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::default(&["program", "input.csv"], Some(AmountFormat::Decimal))]
+    #[case::minor_units(
+        &["program", "--amount-format", "minor-units", "input.csv"],
+        Some(AmountFormat::MinorUnits)
+    )]
+    #[case::invalid(&["program", "--amount-format", "cents", "input.csv"], None)]
+    fn test_amount_format_option(#[case] args: &[&str], #[case] expected: Option<AmountFormat>) {
+        assert_eq!(
+            parse(args).ok().map(|parsed| parsed.amount_format),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_output_layout() {
+        assert!(parse(["program", "input.csv"])
+            .unwrap()
+            .output_layout()
+            .is_standard());
+        assert_eq!(
+            parse([
+                "program",
+                "--columns",
+                "client,total",
+                "--amount-format",
+                "minor-units",
+                "input.csv"
+            ])
+            .unwrap()
+            .output_layout(),
+            AccountLayout::default()
+                .with_columns("client,total".parse().unwrap())
+                .with_amounts(AmountFormat::MinorUnits)
+        );
+    }
+
+    #[test]
+    fn test_client_stats_option() {
+        assert!(!parse(["program", "input.csv"]).unwrap().client_stats);
+        assert!(
+            parse(["program", "--client-stats", "input.csv"])
+                .unwrap()
+                .client_stats
+        );
+    }
+
     // Error handling tests
     #[rstest]
     #[case::missing_input(&["program"])]
     #[case::invalid_strategy(&["program", "--strategy", "invalid", "input.csv"])]
+    #[case::invalid_format(&["program", "--format", "parquet", "input.csv"])]
+    #[case::max_error_rate_out_of_range(&["program", "--max-error-rate", "150", "input.csv"])]
+    #[case::invalid_clients(&["program", "--clients", "1,x", "input.csv"])]
+    #[case::filter_input_without_clients(&["program", "--filter-input", "input.csv"])]
+    #[case::query_without_state(&["program", "query", "--client", "42"])]
+    #[case::analytics_top_without_analytics(&["program", "--analytics-top", "3", "input.csv"])]
+    #[case::reconcile_without_expected(&["program", "reconcile", "input.csv"])]
+    #[case::reconcile_without_balances(&["program", "reconcile", "--expected", "expected.csv"])]
+    #[case::reconcile_state_and_inputs(
+        &["program", "reconcile", "--expected", "e.csv", "--state", "state.bin", "input.csv"]
+    )]
+    #[case::save_state_with_ledger(
+        &["program", "--save-state", "state.bin", "--ledger", "ledger.db", "input.csv"]
+    )]
+    #[case::client_stats_with_ledger(
+        &["program", "--client-stats", "--ledger", "ledger.db", "input.csv"]
+    )]
+    #[case::duplicate_column(&["program", "--columns", "client,total,client", "input.csv"])]
+    #[case::empty_column(&["program", "--columns", "client,,total", "input.csv"])]
+    #[case::columns_with_lock_reasons(
+        &["program", "--columns", "client,lock_reason", "--lock-reasons", "input.csv"]
+    )]
+    #[case::process_without_input(&["program", "process"])]
+    #[case::query_with_process_options(
+        &["program", "query", "--state", "state.bin", "--client", "42", "--strategy", "sync"]
+    )]
+    #[case::bench_without_input(&["program", "bench"])]
+    #[case::bench_zero_iterations(&["program", "bench", "--iterations", "0", "input.csv"])]
+    #[case::generate_zero_clients(&["program", "generate", "--clients", "0"])]
     fn test_parsing_errors(#[case] args: &[&str]) {
-        let result = CliArgs::try_parse_from(args);
+        let result = CliArgs::try_parse_from(with_default_command(args.iter().copied()));
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,174 @@
+//! `bench` subcommand
+//!
+//! Times whole runs of the processing strategies on the given input files,
+//! discarding their account output, and writes a report as CSV with one row
+//! per strategy:
+//!
+//! ```text
+//! strategy,iterations,records,mean_ms,min_ms,max_ms,records_per_sec
+//! sync,5,100000,182.113,179.402,187.958,549109
+//! async,5,100000,71.508,69.871,74.225,1398445
+//! ```
+//!
+//! Unlike `cargo bench`, this measures the built binary on real inputs, with
+//! the strategies' default options.
+
+use super::args::{expand_input_paths, StrategyType};
+use crate::core::EngineConfig;
+use crate::strategy::{create_strategy, InputOptions};
+use clap::Args;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Arguments of the `bench` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct BenchArgs {
+    /// Strategies to time, in order
+    #[arg(
+        long = "strategy",
+        value_name = "STRATEGY",
+        value_delimiter = ',',
+        default_value = "sync,async",
+        help = "Strategies to time, comma-separated"
+    )]
+    pub strategies: Vec<StrategyType>,
+
+    /// Runs of each strategy
+    #[arg(
+        long = "iterations",
+        value_name = "N",
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of runs of each strategy"
+    )]
+    pub iterations: u32,
+
+    /// Input files processed by every run
+    #[arg(
+        value_name = "INPUT",
+        required = true,
+        help = "Input files processed by every run, in order; quoted glob patterns are expanded"
+    )]
+    pub input_files: Vec<PathBuf>,
+}
+
+/// Timings of one strategy as written to the report
+#[derive(Serialize)]
+struct BenchRow {
+    strategy: String,
+    iterations: u32,
+    records: u64,
+    mean_ms: String,
+    min_ms: String,
+    max_ms: String,
+    records_per_sec: String,
+}
+
+impl BenchRow {
+    fn new(strategy: &StrategyType, records: u64, timings: &[Duration]) -> Self {
+        let ms = |duration: Duration| format!("{:.3}", duration.as_secs_f64() * 1000.0);
+        let total: Duration = timings.iter().sum();
+        let mean = total / timings.len() as u32;
+        let records_per_sec = if mean.is_zero() {
+            0.0
+        } else {
+            records as f64 / mean.as_secs_f64()
+        };
+        Self {
+            strategy: strategy.to_string(),
+            iterations: timings.len() as u32,
+            records,
+            mean_ms: ms(mean),
+            min_ms: ms(timings.iter().copied().min().unwrap_or_default()),
+            max_ms: ms(timings.iter().copied().max().unwrap_or_default()),
+            records_per_sec: format!("{:.0}", records_per_sec),
+        }
+    }
+}
+
+impl BenchArgs {
+    /// Run the benchmark, writing the report as CSV
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every run succeeded and the report was written
+    /// * `Err(String)` - If the inputs cannot be resolved, a run fails, or the
+    ///   report cannot be written
+    pub fn run(&self, output: &mut dyn Write) -> Result<(), String> {
+        let inputs = expand_input_paths(&self.input_files)?;
+
+        let mut writer = csv::Writer::from_writer(output);
+        for strategy in &self.strategies {
+            let mut records = 0;
+            let mut timings = Vec::with_capacity(self.iterations as usize);
+            for _ in 0..self.iterations {
+                let processor = create_strategy(
+                    strategy.clone(),
+                    None,
+                    InputOptions::default(),
+                    EngineConfig::default(),
+                    None,
+                );
+                let started = Instant::now();
+                let summary = processor
+                    .process_files(&inputs, &mut std::io::sink())
+                    .map_err(|e| e.to_string())?;
+                timings.push(started.elapsed());
+                records = summary.records_read;
+            }
+            writer
+                .serialize(BenchRow::new(strategy, records, &timings))
+                .map_err(|e| format!("Failed to write report: {}", e))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write report: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bench_reports_each_strategy() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("input.csv");
+        std::fs::write(
+            &input,
+            "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,4.0\n",
+        )
+        .unwrap();
+        let args = BenchArgs {
+            strategies: vec![StrategyType::Sync, StrategyType::Async],
+            iterations: 2,
+            input_files: vec![input],
+        };
+
+        let mut output = Vec::new();
+        args.run(&mut output).unwrap();
+
+        let report = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(
+            lines[0],
+            "strategy,iterations,records,mean_ms,min_ms,max_ms,records_per_sec"
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("sync,2,2,"), "{}", lines[1]);
+        assert!(lines[2].starts_with("async,2,2,"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_bench_missing_input() {
+        let args = BenchArgs {
+            strategies: vec![StrategyType::Sync],
+            iterations: 1,
+            input_files: vec![PathBuf::from("does-not-exist.csv")],
+        };
+        assert!(args.run(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use crate::strategy::RunSummary;
+
+/// Exit-code policy applied after a processing run
+///
+/// By default, recoverable errors (malformed rows, rejected transactions) never
+/// change the exit code. The policy lets callers such as CI jobs fail a run
+/// when any error occurred, when the share of failed records exceeds a
+/// threshold, or when the final balances do not conserve money.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExitPolicy {
+    /// Fail the run if any record failed to parse or process
+    pub fail_on_error: bool,
+
+    /// Fail the run if more than this percentage (0-100) of records failed
+    pub max_error_rate: Option<f64>,
+
+    /// Fail the run if the final balances do not match the money moved
+    pub check_conservation: bool,
+}
+
+impl ExitPolicy {
+    /// Whether the policy can fail a run at all
+    pub fn is_enabled(&self) -> bool {
+        self.fail_on_error || self.max_error_rate.is_some() || self.check_conservation
+    }
+
+    /// Check a run summary against the policy
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the run satisfies the policy
+    /// * `Err(String)` describing the violated threshold otherwise
+    pub fn check(&self, summary: &RunSummary) -> Result<(), String> {
+        if self.fail_on_error && summary.error_count() > 0 {
+            return Err(format!(
+                "{} of {} records failed (--fail-on-error)",
+                summary.error_count(),
+                summary.records_read
+            ));
+        }
+
+        if let Some(max_rate) = self.max_error_rate {
+            let rate = summary.error_rate();
+            if rate > max_rate {
+                return Err(format!(
+                    "error rate {:.2}% exceeds maximum of {:.2}% (--max-error-rate)",
+                    rate, max_rate
+                ));
+            }
+        }
+
+        if self.check_conservation {
+            match &summary.conservation {
+                Some(conservation) if !conservation.is_conserved() => {
+                    return Err(format!(
+                        "money is not conserved: {} (--check-conservation)",
+                        conservation
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    return Err(
+                        "this strategy does not track money flows (--check-conservation)"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a `--max-error-rate` value as a percentage between 0 and 100
+pub(crate) fn parse_error_rate(value: &str) -> Result<f64, String> {
+    let rate: f64 = value
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid percentage", value))?;
+
+    if !(0.0..=100.0).contains(&rate) {
+        return Err(format!("{} is not between 0 and 100", rate));
+    }
+
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::Conservation;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    fn summary(records_read: u64, parse_errors: u64, transaction_errors: u64) -> RunSummary {
+        RunSummary {
+            records_read,
+            parse_errors,
+            transaction_errors,
+            duplicates: 0,
+            quarantined: 0,
+            ..RunSummary::default()
+        }
+    }
+
+    #[rstest]
+    #[case::disabled(ExitPolicy::default(), summary(10, 9, 0), true)]
+    #[case::fail_on_error_clean(
+        ExitPolicy { fail_on_error: true, max_error_rate: None, ..ExitPolicy::default() },
+        summary(10, 0, 0),
+        true
+    )]
+    #[case::fail_on_error_parse_error(
+        ExitPolicy { fail_on_error: true, max_error_rate: None, ..ExitPolicy::default() },
+        summary(10, 1, 0),
+        false
+    )]
+    #[case::fail_on_error_transaction_error(
+        ExitPolicy { fail_on_error: true, max_error_rate: None, ..ExitPolicy::default() },
+        summary(10, 0, 1),
+        false
+    )]
+    #[case::rate_below_threshold(
+        ExitPolicy { fail_on_error: false, max_error_rate: Some(10.0), ..ExitPolicy::default() },
+        summary(100, 5, 4),
+        true
+    )]
+    #[case::rate_at_threshold(
+        ExitPolicy { fail_on_error: false, max_error_rate: Some(10.0), ..ExitPolicy::default() },
+        summary(100, 5, 5),
+        true
+    )]
+    #[case::rate_above_threshold(
+        ExitPolicy { fail_on_error: false, max_error_rate: Some(10.0), ..ExitPolicy::default() },
+        summary(100, 90, 0),
+        false
+    )]
+    #[case::zero_rate_empty_input(
+        ExitPolicy { fail_on_error: false, max_error_rate: Some(0.0), ..ExitPolicy::default() },
+        summary(0, 0, 0),
+        true
+    )]
+    fn test_check(#[case] policy: ExitPolicy, #[case] summary: RunSummary, #[case] passes: bool) {
+        assert_eq!(policy.check(&summary).is_ok(), passes);
+    }
+
+    #[rstest]
+    #[case::conserved(Some(Decimal::ZERO), true)]
+    #[case::drift(Some(Decimal::ONE), false)]
+    #[case::untracked(None, false)]
+    fn test_check_conservation(#[case] drift: Option<Decimal>, #[case] passes: bool) {
+        let policy = ExitPolicy {
+            check_conservation: true,
+            ..ExitPolicy::default()
+        };
+        let summary = RunSummary {
+            conservation: drift.map(|drift| Conservation {
+                drift,
+                ..Conservation::default()
+            }),
+            ..summary(10, 0, 0)
+        };
+        assert_eq!(policy.check(&summary).is_ok(), passes);
+    }
+
+    #[rstest]
+    #[case("5", Some(5.0))]
+    #[case("12.5%", Some(12.5))]
+    #[case("0", Some(0.0))]
+    #[case("100", Some(100.0))]
+    #[case("101", None)]
+    #[case("-1", None)]
+    #[case("abc", None)]
+    fn test_parse_error_rate(#[case] value: &str, #[case] expected: Option<f64>) {
+        assert_eq!(parse_error_rate(value).ok(), expected);
+    }
+}
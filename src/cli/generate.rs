@@ -0,0 +1,223 @@
+//! `generate` subcommand
+//!
+//! Writes a synthetic transaction file, for trying out the engine and for
+//! `bench`, without hand-writing inputs:
+//!
+//! ```bash
+//! payments-engine generate --transactions 1000000 --clients 5000 > transactions.csv
+//! payments-engine bench transactions.csv
+//! ```
+//!
+//! Each record goes to a random client. About 60% are deposits, 20%
+//! withdrawals and 10% disputes of one of the client's earlier deposits;
+//! disputed deposits are later resolved (9%) or, rarely (0.2%), charged back,
+//! locking the account. Some withdrawals exceed the balance and some records
+//! hit locked accounts, so processing the file also exercises the engine's
+//! rejections. The same seed always generates the same file.
+
+use crate::types::{ClientId, TransactionId, TransactionType};
+use clap::Args;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Arguments of the `generate` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct GenerateArgs {
+    /// Records to write
+    #[arg(
+        long = "transactions",
+        value_name = "N",
+        default_value_t = 10_000,
+        help = "Number of records to write"
+    )]
+    pub transactions: u64,
+
+    /// Clients the records are spread over
+    #[arg(
+        long = "clients",
+        value_name = "N",
+        default_value_t = 100,
+        value_parser = clap::value_parser!(ClientId).range(1..),
+        help = "Number of clients, with IDs from 1 to N"
+    )]
+    pub clients: ClientId,
+
+    /// Seed of the pseudo-random draws
+    #[arg(
+        long = "seed",
+        value_name = "N",
+        default_value_t = 0,
+        help = "Seed; the same seed generates the same file"
+    )]
+    pub seed: u64,
+}
+
+/// A generated record as written to the file
+#[derive(Serialize)]
+struct GeneratedRow {
+    #[serde(rename = "type")]
+    tx_type: TransactionType,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<Decimal>,
+}
+
+/// Deposits of a client that records can refer to
+#[derive(Default)]
+struct ClientDeposits {
+    /// Deposits that are not disputed
+    settled: Vec<TransactionId>,
+    /// Deposits under dispute
+    disputed: Vec<TransactionId>,
+}
+
+/// Pseudo-random number generator (SplitMix64)
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform draw in `0..bound`
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    /// Remove a random element of a non-empty list
+    fn take(&mut self, list: &mut Vec<TransactionId>) -> TransactionId {
+        let index = self.below(list.len() as u64) as usize;
+        list.swap_remove(index)
+    }
+}
+
+impl GenerateArgs {
+    /// Generate the records, writing them as CSV
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the file was written
+    /// * `Err(String)` - If the output cannot be written
+    // `ClientId` is already `u64` with the `client-id-u64` feature
+    #[allow(clippy::useless_conversion)]
+    pub fn run(&self, output: &mut dyn Write) -> Result<(), String> {
+        let mut rng = SplitMix(self.seed);
+        let mut deposits: HashMap<ClientId, ClientDeposits> = HashMap::new();
+        let mut next_tx: TransactionId = 1;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(output);
+        writer
+            .write_record(["type", "client", "tx", "amount"])
+            .map_err(|e| format!("Failed to write transactions: {}", e))?;
+        for _ in 0..self.transactions {
+            let client = rng.below(u64::from(self.clients)) as ClientId + 1;
+            let client_deposits = deposits.entry(client).or_default();
+            let roll = rng.below(1000);
+            let (tx_type, tx, amount) = match roll {
+                800..=899 if !client_deposits.settled.is_empty() => {
+                    let tx = rng.take(&mut client_deposits.settled);
+                    client_deposits.disputed.push(tx);
+                    (TransactionType::Dispute, tx, None)
+                }
+                900..=991 if !client_deposits.disputed.is_empty() => {
+                    // Resolved deposits are not disputed again, as re-disputes
+                    // are rejected by default
+                    let tx = rng.take(&mut client_deposits.disputed);
+                    let tx_type = if roll < 990 {
+                        TransactionType::Resolve
+                    } else {
+                        TransactionType::Chargeback
+                    };
+                    (tx_type, tx, None)
+                }
+                600..=799 => {
+                    let amount = Decimal::new(rng.below(50_000) as i64 + 100, 2);
+                    next_tx += 1;
+                    (TransactionType::Withdrawal, next_tx - 1, Some(amount))
+                }
+                _ => {
+                    let amount = Decimal::new(rng.below(100_000) as i64 + 100, 2);
+                    client_deposits.settled.push(next_tx);
+                    next_tx += 1;
+                    (TransactionType::Deposit, next_tx - 1, Some(amount))
+                }
+            };
+            writer
+                .serialize(GeneratedRow {
+                    tx_type,
+                    client,
+                    tx,
+                    amount,
+                })
+                .map_err(|e| format!("Failed to write transactions: {}", e))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write transactions: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TransactionEngine;
+    use crate::io::SyncReader;
+
+    fn generate(transactions: u64, clients: ClientId, seed: u64) -> Vec<u8> {
+        let mut output = Vec::new();
+        GenerateArgs {
+            transactions,
+            clients,
+            seed,
+        }
+        .run(&mut output)
+        .unwrap();
+        output
+    }
+
+    #[test]
+    fn test_generated_records_are_valid() {
+        let output = generate(20_000, 200, 1);
+
+        let records: Vec<_> = SyncReader::from_reader(output.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 20_000);
+        assert!(records
+            .iter()
+            .all(|record| (1..=200).contains(&record.client)));
+        for tx_type in [
+            TransactionType::Deposit,
+            TransactionType::Withdrawal,
+            TransactionType::Dispute,
+            TransactionType::Resolve,
+            TransactionType::Chargeback,
+        ] {
+            assert!(records.iter().any(|record| record.tx_type == tx_type));
+        }
+
+        // Disputes only refer to the client's own deposits, so any rejection
+        // is for funds or a locked account
+        let mut engine = TransactionEngine::new();
+        let processed = records
+            .into_iter()
+            .filter(|record| engine.process(record.clone()).is_ok())
+            .count();
+        assert!(processed > 16_000, "{}", processed);
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        assert_eq!(generate(1_000, 10, 7), generate(1_000, 10, 7));
+        assert_ne!(generate(1_000, 10, 7), generate(1_000, 10, 8));
+    }
+}
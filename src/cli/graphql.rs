@@ -0,0 +1,137 @@
+//! `graphql` subcommand (feature `graphql`)
+//!
+//! Serves GraphQL queries over the accounts and stored transactions of a
+//! state file written with `--save-state` (see `crate::graphql` for the
+//! schema), or runs a single query and prints its JSON response:
+//!
+//! ```bash
+//! payments-engine graphql --state state.bin --listen 127.0.0.1:8080
+//! payments-engine graphql --state state.bin --query '{ accounts(locked: true) { client total } }'
+//! ```
+
+use crate::core::load_state;
+use crate::graphql::{schema, serve};
+use crate::io::{log, LogLine};
+use clap::Args;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Arguments of the `graphql` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct GraphqlArgs {
+    /// State file written with `--save-state`
+    #[arg(
+        long = "state",
+        value_name = "FILE",
+        help = "State file written with --save-state to query"
+    )]
+    pub state: PathBuf,
+
+    /// Address the server listens on
+    #[arg(
+        long = "listen",
+        value_name = "ADDR",
+        default_value = "127.0.0.1:8080",
+        conflicts_with = "query",
+        help = "Serve GraphQL requests POSTed to http://ADDR/graphql"
+    )]
+    pub listen: SocketAddr,
+
+    /// Query to run instead of serving
+    #[arg(
+        long = "query",
+        value_name = "QUERY",
+        help = "Run QUERY, print its JSON response and exit instead of serving"
+    )]
+    pub query: Option<String>,
+}
+
+impl GraphqlArgs {
+    /// Run the query, writing its response as JSON, or serve queries until
+    /// the server fails
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the query ran without errors
+    /// * `Err(String)` - If the state file cannot be read, the query has
+    ///   errors (its response is still written), or the server cannot start
+    pub fn run(&self, output: &mut dyn Write) -> Result<(), String> {
+        let schema = schema(load_state(&self.state)?);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("Failed to create runtime: {}", e))?;
+
+        let Some(query) = &self.query else {
+            return runtime.block_on(async {
+                let listener = tokio::net::TcpListener::bind(self.listen)
+                    .await
+                    .map_err(|e| format!("Failed to listen on {}: {}", self.listen, e))?;
+                log(LogLine::info(format!(
+                    "Serving GraphQL at http://{}/graphql",
+                    self.listen
+                )));
+                serve(schema, listener).await
+            });
+        };
+
+        let response = runtime.block_on(schema.execute(query.as_str()));
+        serde_json::to_writer(&mut *output, &response)
+            .map_err(|e| format!("Failed to write response: {}", e))?;
+        writeln!(output).map_err(|e| format!("Failed to write response: {}", e))?;
+        match response.errors.first() {
+            Some(error) => Err(format!("Query failed: {}", error.message)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{save_state, Engine, TransactionEngine};
+    use crate::types::{TransactionRecord, TransactionType};
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    fn run(query: &str) -> (Result<(), String>, String) {
+        let mut engine = TransactionEngine::new();
+        engine
+            .process_transaction(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 42,
+                tx: 1,
+                amount: Some(Decimal::new(15, 1)),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+        let dir = TempDir::new().unwrap();
+        let state = dir.path().join("state.bin");
+        save_state(&state, &engine.snapshot()).unwrap();
+
+        let args = GraphqlArgs {
+            state,
+            listen: "127.0.0.1:0".parse().unwrap(),
+            query: Some(query.to_string()),
+        };
+        let mut output = Vec::new();
+        let result = args.run(&mut output);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_run_query() {
+        let (result, output) = run("{ account(client: 42) { total } }");
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "{\"data\":{\"account\":{\"total\":\"1.5000\"}}}\n");
+    }
+
+    #[test]
+    fn test_run_invalid_query() {
+        let (result, output) = run("{ account(client: 42) { balance } }");
+        assert!(result.unwrap_err().starts_with("Query failed: "));
+        assert!(output.contains("\"errors\""), "{}", output);
+    }
+}
@@ -0,0 +1,378 @@
+//! Run manifests
+//!
+//! `--manifest FILE` writes a JSON manifest of a processing run, recording
+//! what it was given and what it produced:
+//!
+//! - the crate version, and the build settings that can change results
+//! - the SHA-256 digest of every input file, in order
+//! - the configuration: every option given on the command line, except the
+//!   inputs and the destinations of the output, summary, manifest, dead
+//!   letters and warnings
+//! - when the run started and how long it took
+//! - the SHA-256 digest of the account output, in the standard CSV format
+//!
+//! `--verify-manifest FILE` reproduces a run: after processing, the manifest
+//! of the run is compared with the given one, and every difference other
+//! than the timings is reported. Processing is deterministic, so the same
+//! version, inputs and configuration always give the same output.
+
+use super::args::{with_default_command, CliArgs, DEFAULT_COMMAND};
+use crate::core::hash::KEY_HASHER_NAME;
+use crate::io::digest_input;
+use crate::types::ClientId;
+use clap::parser::ValueSource;
+use clap::CommandFactory;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Options left out of the configuration: the inputs, which are recorded by
+/// digest, and destinations that do not change the results
+const UNRECORDED_OPTIONS: [&str; 9] = [
+    "input_files",
+    "output",
+    "summary",
+    "manifest",
+    "verify_manifest",
+    "dead_letter",
+    "dead_letter_attempts",
+    "dead_letter_backoff_ms",
+    "warnings",
+];
+
+/// Build settings of the binary that can change results
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: String,
+    /// Width of client IDs in bits
+    pub client_id_bits: u32,
+    /// Hasher of the engines' maps
+    pub hasher: String,
+}
+
+impl BuildInfo {
+    /// The settings of this binary
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            client_id_bits: ClientId::BITS,
+            hasher: KEY_HASHER_NAME.to_string(),
+        }
+    }
+}
+
+/// An input file and the SHA-256 digest of its contents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputDigest {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Manifest of a processing run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub build: BuildInfo,
+    pub inputs: Vec<InputDigest>,
+    /// Options given on the command line, with their values as given
+    pub config: BTreeMap<String, Vec<String>>,
+    /// Start of the run, in seconds since the Unix epoch
+    pub started_at: u64,
+    /// Duration of the run in milliseconds
+    pub duration_ms: u64,
+    /// SHA-256 digest of the account output
+    pub output_sha256: String,
+}
+
+impl RunManifest {
+    /// Write the manifest as JSON to a file
+    pub fn write_to(&self, path: &Path) -> Result<(), String> {
+        let mut file = File::create(path)
+            .map_err(|e| format!("Failed to create manifest '{}': {}", path.display(), e))?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .map_err(|e| format!("Failed to write manifest: {}", e))?;
+        writeln!(file).map_err(|e| format!("Failed to write manifest: {}", e))
+    }
+
+    /// Read a manifest written with `write_to`
+    pub fn read_from(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open manifest '{}': {}", path.display(), e))?;
+        serde_json::from_reader(file)
+            .map_err(|e| format!("Invalid manifest '{}': {}", path.display(), e))
+    }
+
+    /// Differences between this manifest and an expected one
+    ///
+    /// The timings and the input paths are not compared, so a run can be
+    /// reproduced later and from copies of the inputs.
+    ///
+    /// # Returns
+    ///
+    /// One message per difference; empty if the runs match
+    pub fn differences(&self, expected: &RunManifest) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.build != expected.build {
+            differences.push(format!(
+                "build differs: expected {:?}, got {:?}",
+                expected.build, self.build
+            ));
+        }
+        if self.inputs.len() != expected.inputs.len() {
+            differences.push(format!(
+                "expected {} input files, got {}",
+                expected.inputs.len(),
+                self.inputs.len()
+            ));
+        }
+        for (index, (input, expected)) in self.inputs.iter().zip(&expected.inputs).enumerate() {
+            if input.sha256 != expected.sha256 {
+                differences.push(format!(
+                    "input {} ('{}') differs from '{}': expected {}, got {}",
+                    index + 1,
+                    input.path.display(),
+                    expected.path.display(),
+                    expected.sha256,
+                    input.sha256
+                ));
+            }
+        }
+        let options: BTreeSet<&String> = self.config.keys().chain(expected.config.keys()).collect();
+        for option in options {
+            let (actual, wanted) = (self.config.get(option), expected.config.get(option));
+            if actual != wanted {
+                differences.push(format!(
+                    "option '{}' differs: expected {:?}, got {:?}",
+                    option, wanted, actual
+                ));
+            }
+        }
+        if self.output_sha256 != expected.output_sha256 {
+            differences.push(format!(
+                "output differs: expected {}, got {}",
+                expected.output_sha256, self.output_sha256
+            ));
+        }
+        differences
+    }
+}
+
+/// Recorder of a run's manifest
+#[derive(Debug, Clone)]
+pub struct ManifestRecorder {
+    build: BuildInfo,
+    inputs: Vec<InputDigest>,
+    config: BTreeMap<String, Vec<String>>,
+    started: SystemTime,
+}
+
+impl ManifestRecorder {
+    /// Start recording a run, digesting its inputs
+    ///
+    /// # Arguments
+    ///
+    /// * `command_line` - The arguments the program was started with,
+    ///   including the program name
+    /// * `input_paths` - The input files, as expanded from the arguments
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ManifestRecorder)` - With the inputs digested
+    /// * `Err(String)` - If an input cannot be read
+    pub fn start(
+        command_line: impl IntoIterator<Item = OsString>,
+        input_paths: &[PathBuf],
+    ) -> Result<Self, String> {
+        let started = SystemTime::now();
+        let inputs = input_paths
+            .iter()
+            .map(|path| {
+                Ok(InputDigest {
+                    path: path.clone(),
+                    sha256: digest_input(path)?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            build: BuildInfo::current(),
+            inputs,
+            config: recorded_config(command_line)?,
+            started,
+        })
+    }
+
+    /// Finish recording once the output has been written
+    pub fn finish(self, output_sha256: String) -> RunManifest {
+        let duration = self.started.elapsed().unwrap_or(Duration::ZERO);
+        RunManifest {
+            build: self.build,
+            inputs: self.inputs,
+            config: self.config,
+            started_at: self
+                .started
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or(Duration::ZERO)
+                .as_secs(),
+            duration_ms: duration.as_millis() as u64,
+            output_sha256,
+        }
+    }
+}
+
+/// The options given on a command line, by argument ID, with their values
+fn recorded_config(
+    command_line: impl IntoIterator<Item = OsString>,
+) -> Result<BTreeMap<String, Vec<String>>, String> {
+    let command = CliArgs::command();
+    let matches = command
+        .clone()
+        .try_get_matches_from(with_default_command(command_line))
+        .map_err(|e| format!("Failed to record the configuration: {}", e))?;
+    // Only runs of `process` write manifests
+    let (Some(command), Some(matches)) = (
+        command.find_subcommand(DEFAULT_COMMAND),
+        matches.subcommand_matches(DEFAULT_COMMAND),
+    ) else {
+        return Err(format!(
+            "Failed to record the configuration: not a '{}' command line",
+            DEFAULT_COMMAND
+        ));
+    };
+    let mut config = BTreeMap::new();
+    // Argument groups have IDs in the matches too, so go by the arguments
+    for id in command.get_arguments().map(|arg| arg.get_id().as_str()) {
+        if UNRECORDED_OPTIONS.contains(&id)
+            || matches.value_source(id) != Some(ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let values = matches
+            .get_raw(id)
+            .into_iter()
+            .flatten()
+            .map(|value| value.to_string_lossy().into_owned())
+            .collect();
+        config.insert(id.to_string(), values);
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command_line(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn manifest(input: &Path, args: &[&str], output: &str) -> RunManifest {
+        ManifestRecorder::start(command_line(args), &[input.to_path_buf()])
+            .unwrap()
+            .finish(output.to_string())
+    }
+
+    #[test]
+    fn test_recorded_config() {
+        let config = recorded_config(command_line(&[
+            "program",
+            "--strategy",
+            "sync",
+            "--max-deposit",
+            "100",
+            "--output",
+            "accounts.csv",
+            "--manifest",
+            "manifest.json",
+            "input.csv",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            config,
+            BTreeMap::from([
+                ("max_deposit".to_string(), vec!["100".to_string()]),
+                ("strategy".to_string(), vec!["sync".to_string()]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_recorded_config_explicit_process() {
+        // Naming the default subcommand records the same configuration
+        assert_eq!(
+            recorded_config(command_line(&[
+                "program",
+                "process",
+                "--dedup-window",
+                "5",
+                "a.csv"
+            ])),
+            recorded_config(command_line(&["program", "--dedup-window", "5", "a.csv"]))
+        );
+        assert!(recorded_config(command_line(&["program", "generate"])).is_err());
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("input.csv");
+        std::fs::write(&input, "type,client,tx,amount\n").unwrap();
+        let path = dir.path().join("manifest.json");
+
+        let written = manifest(&input, &["program", "input.csv"], "abc");
+        written.write_to(&path).unwrap();
+
+        let read = RunManifest::read_from(&path).unwrap();
+        assert_eq!(read, written);
+        assert_eq!(read.build, BuildInfo::current());
+        assert_eq!(read.inputs[0].sha256.len(), 64);
+        assert!(read.differences(&written).is_empty());
+    }
+
+    #[test]
+    fn test_differences() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("input.csv");
+        std::fs::write(&input, "type,client,tx,amount\n").unwrap();
+        let copy = dir.path().join("copy.csv");
+        std::fs::write(&copy, "type,client,tx,amount\n").unwrap();
+        let changed = dir.path().join("changed.csv");
+        std::fs::write(&changed, "type,client,tx,amount\ndeposit,1,1,1\n").unwrap();
+
+        let expected = manifest(&input, &["program", "input.csv"], "abc");
+
+        // A copy of the input under another name reproduces the run
+        assert!(manifest(&copy, &["program", "copy.csv"], "abc")
+            .differences(&expected)
+            .is_empty());
+
+        let differences = manifest(
+            &changed,
+            &["program", "--max-deposit", "5", "changed.csv"],
+            "def",
+        )
+        .differences(&expected);
+        assert_eq!(differences.len(), 3, "{:?}", differences);
+        assert!(differences[0].starts_with("input 1 ("));
+        assert!(differences[1].starts_with("option 'max_deposit' differs"));
+        assert!(differences[2].starts_with("output differs"));
+    }
+
+    #[test]
+    fn test_read_invalid_manifest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("manifest.json");
+        std::fs::write(&path, "{}").unwrap();
+
+        assert!(RunManifest::read_from(&path)
+            .unwrap_err()
+            .starts_with("Invalid manifest"));
+        assert!(RunManifest::read_from(&dir.path().join("missing.json"))
+            .unwrap_err()
+            .starts_with("Failed to open manifest"));
+    }
+}
@@ -0,0 +1,264 @@
+//! `merge` subcommand
+//!
+//! Combines the account outputs of runs over disjoint sets of clients, such
+//! as per-region runs or runs over a pre-split input, into one output:
+//!
+//! ```bash
+//! payments-engine merge eu/accounts.csv us/accounts.csv > accounts.csv
+//! payments-engine merge --save-state all.bin eu/state.bin us/state.bin
+//! ```
+//!
+//! Every input is an account CSV (the output of `process`, or a cutoff
+//! snapshot) or a state file written with `--save-state`, recognized by its
+//! contents. A client in more than one input is a conflict and fails the
+//! merge, as the runs were not over disjoint clients. The merged accounts are
+//! written in client order, and their totals reported on stderr.
+//!
+//! With `--save-state`, the inputs must all be state files; their stored
+//! transactions are merged too, and a transaction ID stored by more than one
+//! of them is also a conflict.
+
+use crate::core::{is_state_file, load_state, save_state, EngineSnapshot};
+use crate::io::{create_sink, read_accounts_csv};
+use crate::strategy::AccountTotals;
+use crate::types::{Account, ClientId, StoredTransaction, TransactionId};
+use clap::Args;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Arguments of the `merge` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct MergeArgs {
+    /// Account CSVs or state files to merge
+    #[arg(
+        value_name = "INPUT",
+        required = true,
+        help = "Account CSVs or state files (--save-state) over disjoint clients"
+    )]
+    pub inputs: Vec<PathBuf>,
+
+    /// Where to write the merged accounts
+    #[arg(
+        long = "output",
+        short = 'o',
+        value_name = "TARGET",
+        default_value = "-",
+        help = "Write the merged accounts to TARGET: '-' for stdout, or a file path"
+    )]
+    pub output: String,
+
+    /// Where to save the merged engine state
+    #[arg(
+        long = "save-state",
+        value_name = "FILE",
+        help = "Also save the merged accounts and stored transactions to FILE (state file inputs only)"
+    )]
+    pub save_state: Option<PathBuf>,
+}
+
+/// Accounts and stored transactions merged so far, by the input they came from
+#[derive(Default)]
+struct Merged {
+    accounts: BTreeMap<ClientId, (Account, usize)>,
+    transactions: BTreeMap<TransactionId, (StoredTransaction, usize)>,
+}
+
+impl MergeArgs {
+    /// Merge the inputs, writing the merged accounts to the output target
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AccountTotals)` - The totals of the merged accounts
+    /// * `Err(String)` - If an input cannot be read, two inputs hold the same
+    ///   client or transaction, or the output cannot be written
+    pub fn run(&self) -> Result<AccountTotals, String> {
+        let mut merged = Merged::default();
+        for (index, path) in self.inputs.iter().enumerate() {
+            let (accounts, transactions) = if is_state_file(path)? {
+                let snapshot = load_state(path)?;
+                (snapshot.accounts, Some(snapshot.transactions))
+            } else {
+                if self.save_state.is_some() {
+                    return Err(format!(
+                        "'{}' is not a state file; --save-state needs state file inputs",
+                        path.display()
+                    ));
+                }
+                (read_accounts_file(path)?, None)
+            };
+
+            for account in accounts {
+                match merged.accounts.entry(account.client) {
+                    Entry::Vacant(entry) => {
+                        entry.insert((account, index));
+                    }
+                    Entry::Occupied(entry) => {
+                        return Err(self.conflict("client", account.client, entry.get().1, index))
+                    }
+                }
+            }
+            for (tx_id, transaction) in transactions.into_iter().flatten() {
+                match merged.transactions.entry(tx_id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert((transaction, index));
+                    }
+                    Entry::Occupied(entry) => {
+                        return Err(self.conflict("transaction", tx_id, entry.get().1, index))
+                    }
+                }
+            }
+        }
+
+        let accounts: Vec<Account> = merged
+            .accounts
+            .into_values()
+            .map(|(account, _)| account)
+            .collect();
+        if let Some(path) = &self.save_state {
+            let transactions = merged
+                .transactions
+                .into_iter()
+                .map(|(tx_id, (transaction, _))| (tx_id, transaction))
+                .collect();
+            save_state(path, &EngineSnapshot::new(accounts.clone(), transactions))?;
+        }
+        create_sink(&self.output)?.write_accounts(&accounts)?;
+        Ok(AccountTotals::of(&accounts))
+    }
+
+    /// Error for an ID found in two inputs
+    fn conflict(
+        &self,
+        kind: &str,
+        id: impl std::fmt::Display,
+        first: usize,
+        second: usize,
+    ) -> String {
+        format!(
+            "Conflict: {} {} is in both '{}' and '{}'",
+            kind,
+            id,
+            self.inputs[first].display(),
+            self.inputs[second].display()
+        )
+    }
+}
+
+/// Read an account CSV input
+fn read_accounts_file(path: &Path) -> Result<Vec<Account>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open accounts file '{}': {}", path.display(), e))?;
+    read_accounts_csv(file)
+        .map_err(|e| format!("Invalid accounts file '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Engine, TransactionEngine};
+    use crate::types::{TransactionRecord, TransactionType};
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    fn merge_args(dir: &TempDir, inputs: &[&str], save_state: Option<&str>) -> MergeArgs {
+        MergeArgs {
+            inputs: inputs.iter().map(|input| dir.path().join(input)).collect(),
+            output: dir.path().join("merged.csv").to_string_lossy().into_owned(),
+            save_state: save_state.map(|path| dir.path().join(path)),
+        }
+    }
+
+    fn save_deposits(dir: &TempDir, name: &str, deposits: &[(ClientId, TransactionId)]) {
+        let mut engine = TransactionEngine::new();
+        for &(client, tx) in deposits {
+            engine
+                .process(TransactionRecord {
+                    tx_type: TransactionType::Deposit,
+                    client,
+                    tx,
+                    amount: Some(Decimal::TEN),
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+        save_state(&dir.path().join(name), &engine.snapshot()).unwrap();
+    }
+
+    #[test]
+    fn test_merge_account_files_and_states() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("a.csv"),
+            "client,available,held,total,locked\n\
+             3,1.5000,0.0000,1.5000,false\n\
+             1,2.0000,1.0000,3.0000,true\n",
+        )
+        .unwrap();
+        save_deposits(&dir, "b.bin", &[(2, 1)]);
+
+        let totals = merge_args(&dir, &["a.csv", "b.bin"], None).run().unwrap();
+
+        assert_eq!(totals.accounts, 3);
+        assert_eq!(totals.locked, 1);
+        assert_eq!(totals.total, Decimal::new(145, 1));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("merged.csv")).unwrap(),
+            "client,available,held,total,locked\n\
+             1,2.0000,1.0000,3.0000,true\n\
+             2,10.0000,0.0000,10.0000,false\n\
+             3,1.5000,0.0000,1.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_states_into_state() {
+        let dir = TempDir::new().unwrap();
+        save_deposits(&dir, "a.bin", &[(1, 1), (1, 2)]);
+        save_deposits(&dir, "b.bin", &[(2, 3)]);
+
+        merge_args(&dir, &["a.bin", "b.bin"], Some("all.bin"))
+            .run()
+            .unwrap();
+
+        let merged = load_state(&dir.path().join("all.bin")).unwrap();
+        assert_eq!(merged.accounts.len(), 2);
+        assert_eq!(merged.transactions.len(), 3);
+        assert_eq!(merged.account(1).unwrap().total, Decimal::from(20));
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let dir = TempDir::new().unwrap();
+        save_deposits(&dir, "a.bin", &[(1, 1)]);
+        save_deposits(&dir, "b.bin", &[(1, 2)]);
+        save_deposits(&dir, "c.bin", &[(2, 1)]);
+        std::fs::write(
+            dir.path().join("d.csv"),
+            "client,available,held,total,locked\n",
+        )
+        .unwrap();
+
+        let err = merge_args(&dir, &["a.bin", "b.bin"], None)
+            .run()
+            .unwrap_err();
+        assert!(err.starts_with("Conflict: client 1 is in both '"), "{err}");
+        let err = merge_args(&dir, &["a.bin", "c.bin"], None)
+            .run()
+            .unwrap_err();
+        assert!(
+            err.starts_with("Conflict: transaction 1 is in both"),
+            "{err}"
+        );
+        let err = merge_args(&dir, &["a.bin", "d.csv"], Some("all.bin"))
+            .run()
+            .unwrap_err();
+        assert!(
+            err.contains("--save-state needs state file inputs"),
+            "{err}"
+        );
+        assert!(!dir.path().join("merged.csv").exists());
+    }
+}
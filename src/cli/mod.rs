@@ -1,9 +1,34 @@
 // CLI module
 // Command-line interface and argument parsing
 
+mod apply;
 mod args;
+mod bench;
+mod exit_policy;
+mod generate;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod manifest;
+mod merge;
+mod query;
+mod reconcile;
+mod statement;
 
-pub use args::{CliArgs, StrategyType};
+pub use apply::ApplyArgs;
+pub use args::{
+    with_default_command, CliArgs, Command, InputFormat, ProcessArgs, RuntimeFlavor, StrategyType,
+    DEFAULT_COMMAND,
+};
+pub use bench::BenchArgs;
+pub use exit_policy::ExitPolicy;
+pub use generate::GenerateArgs;
+#[cfg(feature = "graphql")]
+pub use graphql::GraphqlArgs;
+pub use manifest::{BuildInfo, InputDigest, ManifestRecorder, RunManifest};
+pub use merge::MergeArgs;
+pub use query::QueryArgs;
+pub use reconcile::ReconcileArgs;
+pub use statement::StatementArgs;
 
 use clap::Parser;
 
@@ -12,12 +37,13 @@ use clap::Parser;
 /// This function parses the command-line arguments and returns a `CliArgs` struct
 /// containing the parsed values. If parsing fails (e.g., invalid arguments, missing
 /// required arguments, or --help flag), clap will automatically display an error
-/// message or help text and exit the process.
+/// message or help text and exit the process. A command line that names no
+/// subcommand runs `process` (see `with_default_command`).
 ///
 /// # Returns
 ///
 /// Returns a `CliArgs` struct with the parsed command-line arguments.
 /// ```
 pub fn parse_args() -> CliArgs {
-    CliArgs::parse()
+    CliArgs::parse_from(with_default_command(std::env::args_os()))
 }
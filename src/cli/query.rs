@@ -0,0 +1,180 @@
+//! `query` subcommand
+//!
+//! Looks up an account, or one of its stored transactions, in a state file
+//! written by a run with `--save-state`, without reprocessing the input.
+
+use crate::core::load_state;
+use crate::io::write_accounts_csv;
+use crate::types::{ClientId, DisputeState, StoredTransaction, TransactionId, TransactionType};
+use clap::Args;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Arguments of the `query` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct QueryArgs {
+    /// State file written with `--save-state`
+    #[arg(
+        long = "state",
+        value_name = "FILE",
+        help = "State file written with --save-state"
+    )]
+    pub state: PathBuf,
+
+    /// Client whose account is printed
+    #[arg(
+        long = "client",
+        value_name = "ID",
+        help = "Client whose account to print"
+    )]
+    pub client: ClientId,
+
+    /// Stored transaction of the client to print instead of the account
+    #[arg(
+        long = "tx",
+        value_name = "ID",
+        help = "Print this stored transaction of the client instead of the account"
+    )]
+    pub tx: Option<TransactionId>,
+}
+
+/// A stored transaction as printed by `query --tx` and `statement`
+#[derive(Serialize)]
+pub(super) struct TransactionRow {
+    tx: TransactionId,
+    client: ClientId,
+    #[serde(rename = "type")]
+    tx_type: TransactionType,
+    amount: String,
+    dispute_state: DisputeState,
+    disputes: u32,
+}
+
+impl TransactionRow {
+    pub(super) fn new(tx_id: TransactionId, tx: &StoredTransaction) -> Self {
+        Self {
+            tx: tx_id,
+            client: tx.client,
+            tx_type: tx.tx_type,
+            amount: format!("{:.4}", tx.amount),
+            dispute_state: tx.dispute_state,
+            disputes: tx.disputes,
+        }
+    }
+}
+
+impl QueryArgs {
+    /// Run the query, writing the result as CSV
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the account or transaction was found and written
+    /// * `Err(String)` - If the state file cannot be read, or the client or
+    ///   transaction is not in it
+    pub fn run(&self, output: &mut dyn Write) -> Result<(), String> {
+        let snapshot = load_state(&self.state)?;
+
+        let Some(tx_id) = self.tx else {
+            let account = snapshot
+                .account(self.client)
+                .ok_or_else(|| format!("Client {} not found in state file", self.client))?;
+            return write_accounts_csv(std::slice::from_ref(account), output);
+        };
+
+        // Only deposits and withdrawals are stored, and only for their own client
+        let tx = snapshot
+            .transaction(tx_id)
+            .filter(|tx| tx.client == self.client)
+            .ok_or_else(|| {
+                format!(
+                    "Transaction {} of client {} not found in state file",
+                    tx_id, self.client
+                )
+            })?;
+        let mut writer = csv::Writer::from_writer(output);
+        writer
+            .serialize(TransactionRow::new(tx_id, tx))
+            .map_err(|e| format!("Failed to write transaction: {}", e))?;
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write transaction: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{save_state, Engine, TransactionEngine};
+    use crate::types::TransactionRecord;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    /// Save the state of a small run and return the directory holding it
+    fn saved_state() -> TempDir {
+        let mut engine = TransactionEngine::new();
+        for (tx_type, tx, amount) in [
+            (TransactionType::Deposit, 1, Some(Decimal::new(15, 1))),
+            (TransactionType::Deposit, 2, Some(Decimal::ONE)),
+            (TransactionType::Dispute, 2, None),
+        ] {
+            engine
+                .process(TransactionRecord {
+                    tx_type,
+                    client: 42,
+                    tx,
+                    amount,
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+        let dir = TempDir::new().unwrap();
+        save_state(&dir.path().join("state.bin"), &engine.snapshot()).unwrap();
+        dir
+    }
+
+    fn query(dir: &TempDir, client: ClientId, tx: Option<TransactionId>) -> Result<String, String> {
+        let args = QueryArgs {
+            state: dir.path().join("state.bin"),
+            client,
+            tx,
+        };
+        let mut output = Vec::new();
+        args.run(&mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_query_account() {
+        let dir = saved_state();
+        assert_eq!(
+            query(&dir, 42, None).unwrap(),
+            "client,available,held,total,locked\n42,1.5000,1.0000,2.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_query_transaction() {
+        let dir = saved_state();
+        assert_eq!(
+            query(&dir, 42, Some(2)).unwrap(),
+            "tx,client,type,amount,dispute_state,disputes\n2,42,deposit,1.0000,disputed,1\n"
+        );
+    }
+
+    #[rstest]
+    #[case::unknown_client(7, None, "Client 7 not found")]
+    #[case::unknown_transaction(42, Some(3), "Transaction 3 of client 42 not found")]
+    #[case::other_client(7, Some(1), "Transaction 1 of client 7 not found")]
+    fn test_query_not_found(
+        #[case] client: ClientId,
+        #[case] tx: Option<TransactionId>,
+        #[case] expected: &str,
+    ) {
+        let dir = saved_state();
+        let err = query(&dir, client, tx).unwrap_err();
+        assert!(err.contains(expected), "{err}");
+    }
+}
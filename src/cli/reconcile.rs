@@ -0,0 +1,258 @@
+//! `reconcile` subcommand
+//!
+//! Compares the final balances computed from the input files, or saved with
+//! `--save-state`, against a file of expected balances and writes a diff
+//! report as CSV:
+//!
+//! ```text
+//! client,issue,field,expected,actual,delta
+//! 2,mismatch,available,7.0000,6.5000,-0.5000
+//! 2,mismatch,locked,false,true,
+//! 3,missing,,,,
+//! 4,unexpected,,,,
+//! ```
+
+use super::args::expand_input_paths;
+use crate::core::{load_state, reconcile, Discrepancy};
+use crate::io::read_accounts_csv;
+use crate::strategy::{ProcessingStrategy, SyncProcessingStrategy};
+use crate::types::{Account, ClientId};
+use clap::Args;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Arguments of the `reconcile` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct ReconcileArgs {
+    /// Expected balances, in the account output format
+    #[arg(
+        long = "expected",
+        value_name = "FILE",
+        help = "Expected balances (client,available,held,total,locked)"
+    )]
+    pub expected: PathBuf,
+
+    /// State file written with `--save-state` to take the computed balances from
+    #[arg(
+        long = "state",
+        value_name = "FILE",
+        conflicts_with = "input_files",
+        help = "Reconcile the balances in a state file written with --save-state instead of processing input files"
+    )]
+    pub state: Option<PathBuf>,
+
+    /// Input files to compute the balances from
+    #[arg(
+        value_name = "INPUT",
+        required_unless_present = "state",
+        help = "Input files to process (sync strategy, default options) for the computed balances"
+    )]
+    pub input_files: Vec<PathBuf>,
+}
+
+/// A discrepancy as written to the report
+#[derive(Serialize)]
+struct ReportRow {
+    client: ClientId,
+    issue: &'static str,
+    field: String,
+    expected: String,
+    actual: String,
+    delta: String,
+}
+
+impl From<&Discrepancy> for ReportRow {
+    fn from(discrepancy: &Discrepancy) -> Self {
+        let mut row = ReportRow {
+            client: discrepancy.client(),
+            issue: "mismatch",
+            field: String::new(),
+            expected: String::new(),
+            actual: String::new(),
+            delta: String::new(),
+        };
+        match discrepancy {
+            Discrepancy::Missing { .. } => row.issue = "missing",
+            Discrepancy::Unexpected { .. } => row.issue = "unexpected",
+            Discrepancy::Balance {
+                field,
+                expected,
+                actual,
+                ..
+            } => {
+                row.field = field.to_string();
+                row.expected = format!("{:.4}", expected);
+                row.actual = format!("{:.4}", actual);
+                row.delta = format!("{:.4}", actual - expected);
+            }
+            Discrepancy::Locked {
+                expected, actual, ..
+            } => {
+                row.field = "locked".to_string();
+                row.expected = expected.to_string();
+                row.actual = actual.to_string();
+            }
+        }
+        row
+    }
+}
+
+impl ReconcileArgs {
+    /// Run the reconciliation, writing the diff report as CSV
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(discrepancies)` - The number of discrepancies found (and written)
+    /// * `Err(String)` - If a file cannot be read or the inputs cannot be processed
+    pub fn run(&self, output: &mut dyn Write) -> Result<usize, String> {
+        let expected = read_accounts_file(&self.expected)?;
+        let actual = self.computed_accounts()?;
+        let discrepancies = reconcile(&expected, &actual);
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(output);
+        writer
+            .write_record(["client", "issue", "field", "expected", "actual", "delta"])
+            .map_err(|e| format!("Failed to write report: {}", e))?;
+        for discrepancy in &discrepancies {
+            writer
+                .serialize(ReportRow::from(discrepancy))
+                .map_err(|e| format!("Failed to write report: {}", e))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write report: {}", e))?;
+        Ok(discrepancies.len())
+    }
+
+    /// Load the saved accounts, or compute them by processing the inputs
+    fn computed_accounts(&self) -> Result<Vec<Account>, String> {
+        if let Some(state) = &self.state {
+            return Ok(load_state(state)?.accounts);
+        }
+        let inputs = expand_input_paths(&self.input_files)?;
+        let mut output = Vec::new();
+        SyncProcessingStrategy::new()
+            .process_files(&inputs, &mut output)
+            .map_err(|e| e.to_string())?;
+        read_accounts_csv(output.as_slice())
+    }
+}
+
+/// Read the expected balances file
+fn read_accounts_file(path: &Path) -> Result<Vec<Account>, String> {
+    let file = File::open(path).map_err(|e| {
+        format!(
+            "Failed to open expected balances file '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    read_accounts_csv(file)
+        .map_err(|e| format!("Invalid expected balances file '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{save_state, Engine, TransactionEngine};
+    use crate::types::{TransactionRecord, TransactionType};
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    const INPUT: &str = "type,client,tx,amount\n\
+                         deposit,1,1,10.0\n\
+                         deposit,2,2,7.0\n\
+                         dispute,2,2,\n\
+                         chargeback,2,2,\n";
+
+    fn reconcile_with(dir: &TempDir, expected: &str, args: ReconcileArgs) -> (usize, String) {
+        std::fs::write(dir.path().join("expected.csv"), expected).unwrap();
+        let mut output = Vec::new();
+        let discrepancies = args.run(&mut output).unwrap();
+        (discrepancies, String::from_utf8(output).unwrap())
+    }
+
+    fn input_args(dir: &TempDir) -> ReconcileArgs {
+        std::fs::write(dir.path().join("input.csv"), INPUT).unwrap();
+        ReconcileArgs {
+            expected: dir.path().join("expected.csv"),
+            state: None,
+            input_files: vec![dir.path().join("input.csv")],
+        }
+    }
+
+    #[test]
+    fn test_reconcile_matching_balances() {
+        let dir = TempDir::new().unwrap();
+        let expected = "client,available,held,total,locked\n\
+                        2,0,0,0,true\n\
+                        1,10.0000,0.0000,10.0000,false\n";
+
+        let (discrepancies, report) = reconcile_with(&dir, expected, input_args(&dir));
+        assert_eq!(discrepancies, 0);
+        assert_eq!(report, "client,issue,field,expected,actual,delta\n");
+    }
+
+    #[test]
+    fn test_reconcile_reports_discrepancies() {
+        let dir = TempDir::new().unwrap();
+        let expected = "client,available,held,total,locked\n\
+                        1,10.5,0,10.5,false\n\
+                        2,0,0,0,false\n\
+                        3,1,0,1,false\n";
+
+        let (discrepancies, report) = reconcile_with(&dir, expected, input_args(&dir));
+        assert_eq!(discrepancies, 4);
+        assert_eq!(
+            report,
+            "client,issue,field,expected,actual,delta\n\
+             1,mismatch,available,10.5000,10.0000,-0.5000\n\
+             1,mismatch,total,10.5000,10.0000,-0.5000\n\
+             2,mismatch,locked,false,true,\n\
+             3,missing,,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_reconcile_saved_state() {
+        let dir = TempDir::new().unwrap();
+        let mut engine = TransactionEngine::new();
+        engine
+            .process(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 4,
+                tx: 1,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+        save_state(&dir.path().join("state.bin"), &engine.snapshot()).unwrap();
+        let args = ReconcileArgs {
+            expected: dir.path().join("expected.csv"),
+            state: Some(dir.path().join("state.bin")),
+            input_files: Vec::new(),
+        };
+
+        let (discrepancies, report) =
+            reconcile_with(&dir, "client,available,held,total,locked\n", args);
+        assert_eq!(discrepancies, 1);
+        assert_eq!(
+            report,
+            "client,issue,field,expected,actual,delta\n4,unexpected,,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_reconcile_invalid_expected_file() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("expected.csv"), "client,available\n1,x\n").unwrap();
+
+        let err = input_args(&dir).run(&mut Vec::new()).unwrap_err();
+        assert!(err.contains("Invalid expected balances file"), "{err}");
+    }
+}
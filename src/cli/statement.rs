@@ -0,0 +1,122 @@
+//! `statement` subcommand
+//!
+//! Lists the stored transactions of one client, with their dispute states,
+//! from a state file written by a run with `--save-state`.
+
+use super::query::TransactionRow;
+use crate::core::load_state;
+use crate::types::ClientId;
+use clap::Args;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Arguments of the `statement` subcommand
+#[derive(Args, Debug, Clone)]
+pub struct StatementArgs {
+    /// State file written with `--save-state`
+    #[arg(
+        long = "state",
+        value_name = "FILE",
+        help = "State file written with --save-state"
+    )]
+    pub state: PathBuf,
+
+    /// Client whose transactions are listed
+    #[arg(
+        long = "client",
+        value_name = "ID",
+        help = "Client whose transactions to list"
+    )]
+    pub client: ClientId,
+}
+
+impl StatementArgs {
+    /// Write the client's stored transactions as CSV, in transaction ID order
+    ///
+    /// Only deposits and withdrawals are stored, so disputes, resolves and
+    /// chargebacks show as the dispute state of the transaction they refer to.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the statement was written
+    /// * `Err(String)` - If the state file cannot be read, or the client has
+    ///   no account in it
+    pub fn run(&self, output: &mut dyn Write) -> Result<(), String> {
+        let snapshot = load_state(&self.state)?;
+        if snapshot.account(self.client).is_none() {
+            return Err(format!("Client {} not found in state file", self.client));
+        }
+
+        let mut writer = csv::Writer::from_writer(output);
+        for (tx_id, tx) in snapshot
+            .transactions
+            .iter()
+            .filter(|(_, tx)| tx.client == self.client)
+        {
+            writer
+                .serialize(TransactionRow::new(*tx_id, tx))
+                .map_err(|e| format!("Failed to write statement: {}", e))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write statement: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{save_state, Engine, TransactionEngine};
+    use crate::types::{TransactionRecord, TransactionType};
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    fn statement(client: ClientId) -> Result<String, String> {
+        let mut engine = TransactionEngine::new();
+        for (tx_type, client, tx, amount) in [
+            (TransactionType::Deposit, 42, 3, Some(Decimal::from(5))),
+            (TransactionType::Deposit, 7, 2, Some(Decimal::ONE)),
+            (TransactionType::Deposit, 42, 4, Some(Decimal::from(2))),
+            (TransactionType::Withdrawal, 42, 1, Some(Decimal::from(2))),
+            (TransactionType::Dispute, 42, 3, None),
+        ] {
+            engine
+                .process(TransactionRecord {
+                    tx_type,
+                    client,
+                    tx,
+                    amount,
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+        let dir = TempDir::new().unwrap();
+        save_state(&dir.path().join("state.bin"), &engine.snapshot()).unwrap();
+
+        let args = StatementArgs {
+            state: dir.path().join("state.bin"),
+            client,
+        };
+        let mut output = Vec::new();
+        args.run(&mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_statement_lists_client_transactions() {
+        assert_eq!(
+            statement(42).unwrap(),
+            "tx,client,type,amount,dispute_state,disputes\n\
+             1,42,withdrawal,2.0000,none,0\n\
+             3,42,deposit,5.0000,disputed,1\n\
+             4,42,deposit,2.0000,none,0\n"
+        );
+    }
+
+    #[test]
+    fn test_statement_unknown_client() {
+        let err = statement(9).unwrap_err();
+        assert!(err.contains("Client 9 not found"), "{err}");
+    }
+}
@@ -9,9 +9,11 @@
 //! - Managing account locked status
 //! - Providing sorted account listings for output
 
-use crate::types::{Account, ClientId, PaymentError};
+use crate::core::config::MetadataMap;
+use crate::core::hash::{key_map, KeyMap};
+use crate::types::{Account, ClientId, LockReason, PaymentError, TransactionId, TransactionType};
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Manages all client accounts and their states
 ///
@@ -20,7 +22,9 @@ use std::collections::HashMap;
 /// all accounts for output generation.
 pub struct AccountManager {
     /// Map of client IDs to account states
-    accounts: HashMap<ClientId, Account>,
+    accounts: KeyMap<ClientId, Account>,
+    /// Metadata attached to accounts when they are created
+    metadata: Arc<MetadataMap>,
 }
 
 impl AccountManager {
@@ -31,15 +35,42 @@ impl AccountManager {
     /// A new AccountManager with an empty account map
     pub fn new() -> Self {
         AccountManager {
-            accounts: HashMap::new(),
+            accounts: KeyMap::default(),
+            metadata: Arc::default(),
         }
     }
 
+    /// Create a new AccountManager with room for `clients` accounts
+    ///
+    /// Avoids rehashing the account map while it grows to the expected number
+    /// of clients.
+    pub fn with_capacity(clients: usize) -> Self {
+        AccountManager {
+            accounts: key_map(clients),
+            metadata: Arc::default(),
+        }
+    }
+
+    /// Number of accounts the manager can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.accounts.capacity()
+    }
+
+    /// Attach metadata to accounts as they are created
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Account metadata keyed by client ID
+    pub fn with_metadata(mut self, metadata: Arc<MetadataMap>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     /// Get or create an account for the specified client
     ///
     /// If an account already exists for the client, returns a mutable reference
-    /// to it. If no account exists, creates a new account with zero balances
-    /// and unlocked status.
+    /// to it. If no account exists, creates a new account with zero balances,
+    /// unlocked status and the client's metadata (if any).
     ///
     /// # Arguments
     ///
@@ -49,9 +80,136 @@ impl AccountManager {
     ///
     /// A mutable reference to the account for the specified client
     pub fn get_or_create_account(&mut self, client: ClientId) -> &mut Account {
-        self.accounts
-            .entry(client)
-            .or_insert_with(|| Account::new(client))
+        let metadata = &self.metadata;
+        self.accounts.entry(client).or_insert_with(|| Account {
+            metadata: metadata.get(&client).cloned(),
+            ..Account::new(client)
+        })
+    }
+
+    /// Get an existing account without creating it
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID to look up
+    ///
+    /// # Returns
+    ///
+    /// * `Some(&Account)` - If an account exists for the client
+    /// * `None` - If no account exists
+    pub fn get_account(&self, client: ClientId) -> Option<&Account> {
+        self.accounts.get(&client)
+    }
+
+    /// Insert an account, replacing any existing account for the same client
+    ///
+    /// Used to seed the manager with account state loaded from elsewhere
+    /// (e.g. a persistent ledger).
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The account state to insert
+    pub fn insert_account(&mut self, account: Account) {
+        self.accounts.insert(account.client, account);
+    }
+
+    /// Update two accounts together using a closure
+    ///
+    /// The counterpart of `AsyncAccountManager::update_pair` for operations
+    /// that touch two accounts (transfers, admin corrections). Accounts that
+    /// don't exist are created first.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The client ID of the account passed first to `f`
+    /// * `second` - The client ID of the account passed second to `f`
+    /// * `f` - A closure that receives mutable references to both accounts and
+    ///   returns a Result indicating success or failure
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the closure executed successfully
+    /// * `Err(PaymentError)` if the closure returned an error; changes it made
+    ///   before failing are kept
+    ///
+    /// # Panics
+    ///
+    /// If `first` and `second` are the same client.
+    pub fn update_pair<F>(
+        &mut self,
+        first: ClientId,
+        second: ClientId,
+        f: F,
+    ) -> Result<(), PaymentError>
+    where
+        F: FnOnce(&mut Account, &mut Account) -> Result<(), PaymentError>,
+    {
+        assert_ne!(first, second, "update_pair needs two different clients");
+        self.get_or_create_account(first);
+        self.get_or_create_account(second);
+        match self.accounts.get_disjoint_mut([&first, &second]) {
+            [Some(first), Some(second)] => f(first, second),
+            _ => unreachable!("both accounts were just created"),
+        }
+    }
+
+    /// Move a fee from a client's available funds to the fee account
+    ///
+    /// Uses `update_pair`, computing every new balance before any is updated,
+    /// so a failed charge leaves both accounts unchanged. The fee account is
+    /// credited even if it is locked.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID charged the fee
+    /// * `fee_account` - The client ID of the account collecting the fee
+    /// * `fee` - The fee (must be non-negative)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The fee exceeds the client's available funds
+    /// - Adding the fee to the fee account would cause overflow
+    ///
+    /// # Panics
+    ///
+    /// If `client` and `fee_account` are the same client.
+    pub fn charge_fee(
+        &mut self,
+        client: ClientId,
+        fee_account: ClientId,
+        fee: Decimal,
+    ) -> Result<(), PaymentError> {
+        self.update_pair(client, fee_account, |account, collector| {
+            if account.available < fee {
+                return Err(PaymentError::insufficient_funds(
+                    client,
+                    account.available,
+                    fee,
+                ));
+            }
+            let available = account
+                .available
+                .checked_sub(fee)
+                .ok_or_else(|| PaymentError::arithmetic_underflow("fee", client))?;
+            let total = account
+                .total
+                .checked_sub(fee)
+                .ok_or_else(|| PaymentError::arithmetic_underflow("fee", client))?;
+            let collected_available = collector
+                .available
+                .checked_add(fee)
+                .ok_or_else(|| PaymentError::arithmetic_overflow("fee", fee_account))?;
+            let collected_total = collector
+                .total
+                .checked_add(fee)
+                .ok_or_else(|| PaymentError::arithmetic_overflow("fee", fee_account))?;
+            account.available = available;
+            account.total = total;
+            collector.available = collected_available;
+            collector.total = collected_total;
+            Ok(())
+        })
     }
 
     /// Check if an account is locked
@@ -73,6 +231,28 @@ impl AccountManager {
             .is_some_and(|account| account.locked)
     }
 
+    /// Count a processed record in the activity of its client's account
+    ///
+    /// Records of clients without an account are not counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID of the record
+    /// * `tx_type` - The type of the record
+    /// * `tx` - The transaction ID of the record
+    /// * `applied` - Whether the record was accepted
+    pub fn record_activity(
+        &mut self,
+        client: ClientId,
+        tx_type: TransactionType,
+        tx: TransactionId,
+        applied: bool,
+    ) {
+        if let Some(account) = self.accounts.get_mut(&client) {
+            account.activity.record(tx_type, tx, applied);
+        }
+    }
+
     /// Get all accounts sorted by client ID
     ///
     /// Returns a vector of references to all accounts, sorted by client ID
@@ -178,6 +358,87 @@ impl AccountManager {
         Ok(())
     }
 
+    /// Move funds from available to held for a withdrawal pending approval
+    ///
+    /// Like `withdraw`, the withdrawal must be covered by available funds, but
+    /// the funds are held until the withdrawal is approved (`complete_withdrawal`)
+    /// or rejected (`release_funds`). The total balance remains unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID to withdraw from
+    /// * `amount` - The amount to move from available to held (must be non-negative)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the hold was successful
+    /// * `Err(PaymentError)` - If insufficient funds or overflow would occur
+    pub fn hold_withdrawal(
+        &mut self,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
+        let account = self.get_or_create_account(client);
+
+        // Check if sufficient available funds exist
+        if account.available < amount {
+            return Err(PaymentError::insufficient_funds(
+                client,
+                account.available,
+                amount,
+            ));
+        }
+
+        self.hold_funds_allowing_debt(client, amount)
+    }
+
+    /// Remove the held funds of an approved withdrawal
+    ///
+    /// Decreases both held funds and total funds by the specified amount, like
+    /// a chargeback but without locking the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID the withdrawal was held for
+    /// * `amount` - The amount to remove from held and total (must be non-negative)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the withdrawal was completed
+    /// * `Err(PaymentError)` - If insufficient held funds or underflow would occur
+    pub fn complete_withdrawal(
+        &mut self,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
+        let account = self.get_or_create_account(client);
+
+        // Check if sufficient held funds exist
+        if account.held < amount {
+            return Err(PaymentError::insufficient_held_funds(
+                client,
+                account.held,
+                amount,
+                "approve",
+            ));
+        }
+
+        let new_held = account
+            .held
+            .checked_sub(amount)
+            .ok_or_else(|| PaymentError::arithmetic_underflow("approve", client))?;
+
+        let new_total = account
+            .total
+            .checked_sub(amount)
+            .ok_or_else(|| PaymentError::arithmetic_underflow("approve", client))?;
+
+        account.held = new_held;
+        account.total = new_total;
+
+        Ok(())
+    }
+
     /// Move funds from available to held (dispute)
     ///
     /// Decreases available funds and increases held funds by the specified amount.
@@ -213,6 +474,31 @@ impl AccountManager {
             ));
         }
 
+        self.hold_funds_allowing_debt(client, amount)
+    }
+
+    /// Move funds from available to held, even if available goes negative
+    ///
+    /// Like `hold_funds`, but without the available funds check: disputing a
+    /// deposit that was already withdrawn leaves the client with a negative
+    /// available balance (a debt) instead of failing.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID to hold funds for
+    /// * `amount` - The amount to move from available to held (must be non-negative)
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the hold was successful
+    /// * `Err(PaymentError)` - If underflow or overflow would occur
+    pub fn hold_funds_allowing_debt(
+        &mut self,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
+        let account = self.get_or_create_account(client);
+
         let new_available = account
             .available
             .checked_sub(amount)
@@ -253,6 +539,33 @@ impl AccountManager {
     /// - Subtracting the amount from held funds would cause underflow
     /// - Adding the amount to available funds would cause overflow
     pub fn release_funds(&mut self, client: ClientId, amount: Decimal) -> Result<(), PaymentError> {
+        self.release_funds_with_interest(client, amount, Decimal::ZERO)
+    }
+
+    /// Move funds from held to available and credit interest on them (resolve)
+    ///
+    /// Like `release_funds`, but also adds `interest` to the available and
+    /// total balances. Every new balance is computed before any is updated, so
+    /// a failed release leaves the account unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client ID to release funds for
+    /// * `amount` - The amount to move from held to available (must be non-negative)
+    /// * `interest` - The interest credited on top (must be non-negative)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The amount exceeds held funds
+    /// - Subtracting the amount from held funds would cause underflow
+    /// - Adding the amount and interest to available or total funds would cause overflow
+    pub fn release_funds_with_interest(
+        &mut self,
+        client: ClientId,
+        amount: Decimal,
+        interest: Decimal,
+    ) -> Result<(), PaymentError> {
         let account = self.get_or_create_account(client);
 
         // Check if sufficient held funds exist
@@ -273,11 +586,18 @@ impl AccountManager {
         let new_available = account
             .available
             .checked_add(amount)
+            .and_then(|available| available.checked_add(interest))
             .ok_or_else(|| PaymentError::arithmetic_overflow("release_funds", client))?;
 
-        // Update account balances (total remains unchanged)
+        let new_total = account
+            .total
+            .checked_add(interest)
+            .ok_or_else(|| PaymentError::arithmetic_overflow("release_funds", client))?;
+
+        // Update account balances (total only changes by the interest)
         account.held = new_held;
         account.available = new_available;
+        account.total = new_total;
 
         Ok(())
     }
@@ -285,12 +605,14 @@ impl AccountManager {
     /// Remove held funds and lock account (chargeback)
     ///
     /// Decreases both held funds and total funds by the specified amount, then
-    /// locks the account to prevent further transactions. Uses checked arithmetic
-    /// to prevent underflow and maintain account integrity.
+    /// locks the account to prevent further transactions, with the charged back
+    /// transaction as the lock reason. Uses checked arithmetic to prevent
+    /// underflow and maintain account integrity.
     ///
     /// # Arguments
     ///
     /// * `client` - The client ID to chargeback funds from
+    /// * `tx` - The transaction charged back
     /// * `amount` - The amount to remove from held and total (must be non-negative)
     ///
     /// # Returns
@@ -304,7 +626,12 @@ impl AccountManager {
     /// - The amount exceeds held funds
     /// - Subtracting the amount from held funds would cause underflow
     /// - Subtracting the amount from total funds would cause underflow
-    pub fn chargeback(&mut self, client: ClientId, amount: Decimal) -> Result<(), PaymentError> {
+    pub fn chargeback(
+        &mut self,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
         let account = self.get_or_create_account(client);
 
         // Check if sufficient held funds exist
@@ -330,7 +657,7 @@ impl AccountManager {
         // Update account balances and lock the account
         account.held = new_held;
         account.total = new_total;
-        account.locked = true;
+        account.lock(LockReason::ChargebackTx(tx));
 
         Ok(())
     }
@@ -347,6 +674,32 @@ mod tests {
     use super::*;
     use rust_decimal::Decimal;
 
+    #[test]
+    fn test_update_pair_transfers_between_accounts() {
+        let mut manager = AccountManager::new();
+        manager.deposit(2, Decimal::from(10)).unwrap();
+
+        manager
+            .update_pair(2, 1, |source, target| {
+                assert_eq!((source.client, target.client), (2, 1));
+                source.available -= Decimal::from(4);
+                target.available += Decimal::from(4);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(manager.get_account(2).unwrap().available, Decimal::from(6));
+        assert_eq!(manager.get_account(1).unwrap().available, Decimal::from(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "two different clients")]
+    fn test_update_pair_same_client_panics() {
+        AccountManager::new()
+            .update_pair(1, 1, |_, _| Ok(()))
+            .unwrap();
+    }
+
     #[test]
     fn test_new_creates_empty_manager() {
         let manager = AccountManager::new();
@@ -354,6 +707,13 @@ mod tests {
         assert_eq!(manager.get_all_accounts().len(), 0);
     }
 
+    #[test]
+    fn test_with_capacity_creates_empty_manager() {
+        let manager = AccountManager::with_capacity(1000);
+        assert!(manager.accounts.is_empty());
+        assert!(manager.capacity() >= 1000);
+    }
+
     #[test]
     fn test_get_or_create_account_creates_new_account() {
         let mut manager = AccountManager::new();
@@ -367,6 +727,19 @@ mod tests {
         assert!(!account.locked);
     }
 
+    #[test]
+    fn test_get_or_create_account_attaches_metadata() {
+        let metadata = Arc::new(crate::types::AccountMetadata::from([(
+            "kyc_status".to_string(),
+            "verified".to_string(),
+        )]));
+        let mut manager = AccountManager::new()
+            .with_metadata(Arc::new(MetadataMap::from([(1, metadata.clone())])));
+
+        assert_eq!(manager.get_or_create_account(1).metadata, Some(metadata));
+        assert_eq!(manager.get_or_create_account(2).metadata, None);
+    }
+
     #[test]
     fn test_get_or_create_account_returns_existing_account() {
         let mut manager = AccountManager::new();
@@ -502,11 +875,8 @@ mod tests {
 
         // If overflow detection works, this should be an error
         // Note: Decimal::checked_add returns None on overflow
-        if result.is_err() {
-            assert!(matches!(
-                result.unwrap_err(),
-                PaymentError::ArithmeticOverflow { .. }
-            ));
+        if let Err(err) = result {
+            assert!(matches!(err, PaymentError::ArithmeticOverflow { .. }));
 
             // Account should remain unchanged
             let account = manager.get_or_create_account(1);
@@ -708,6 +1078,22 @@ mod tests {
         assert_eq!(account.total, Decimal::new(50000, 4));
     }
 
+    #[test]
+    fn test_hold_funds_allowing_debt_goes_negative() {
+        let mut manager = AccountManager::new();
+
+        // Deposit 5.0000, then hold 10.0000
+        manager.deposit(1, Decimal::new(50000, 4)).unwrap();
+        manager
+            .hold_funds_allowing_debt(1, Decimal::new(100000, 4))
+            .unwrap();
+
+        let account = manager.get_or_create_account(1);
+        assert_eq!(account.available, Decimal::new(-50000, 4));
+        assert_eq!(account.held, Decimal::new(100000, 4));
+        assert_eq!(account.total, Decimal::new(50000, 4));
+    }
+
     #[test]
     fn test_hold_funds_multiple_times() {
         let mut manager = AccountManager::new();
@@ -744,6 +1130,51 @@ mod tests {
         assert_eq!(account.total, Decimal::new(100000, 4));
     }
 
+    #[test]
+    fn test_release_funds_with_interest() {
+        let mut manager = AccountManager::new();
+        manager.deposit(1, Decimal::new(100000, 4)).unwrap();
+        manager.hold_funds(1, Decimal::new(30000, 4)).unwrap();
+
+        manager
+            .release_funds_with_interest(1, Decimal::new(30000, 4), Decimal::new(150, 4))
+            .unwrap();
+
+        let account = manager.get_or_create_account(1);
+        assert_eq!(account.available, Decimal::new(100150, 4));
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::new(100150, 4));
+
+        // An overflowing interest leaves the account unchanged
+        manager.hold_funds(1, Decimal::ONE).unwrap();
+        assert!(matches!(
+            manager.release_funds_with_interest(1, Decimal::ONE, Decimal::MAX),
+            Err(PaymentError::ArithmeticOverflow { .. })
+        ));
+        let account = manager.get_or_create_account(1);
+        assert_eq!(account.held, Decimal::ONE);
+        assert_eq!(account.total, Decimal::new(100150, 4));
+    }
+
+    #[test]
+    fn test_charge_fee() {
+        let mut manager = AccountManager::new();
+        manager.deposit(1, Decimal::from(10)).unwrap();
+
+        manager.charge_fee(1, 99, Decimal::new(25, 2)).unwrap();
+        assert_eq!(manager.get_account(1).unwrap().total, Decimal::new(975, 2));
+        assert_eq!(
+            manager.get_account(99).unwrap().available,
+            Decimal::new(25, 2)
+        );
+
+        assert!(matches!(
+            manager.charge_fee(1, 99, Decimal::from(20)),
+            Err(PaymentError::InsufficientFunds { .. })
+        ));
+        assert_eq!(manager.get_account(99).unwrap().total, Decimal::new(25, 2));
+    }
+
     #[test]
     fn test_release_funds_with_insufficient_held() {
         let mut manager = AccountManager::new();
@@ -777,7 +1208,7 @@ mod tests {
         manager.hold_funds(1, Decimal::new(30000, 4)).unwrap();
 
         // Chargeback 3.0000
-        let result = manager.chargeback(1, Decimal::new(30000, 4));
+        let result = manager.chargeback(1, 5, Decimal::new(30000, 4));
         assert!(result.is_ok());
 
         let account = manager.get_or_create_account(1);
@@ -785,6 +1216,7 @@ mod tests {
         assert_eq!(account.held, Decimal::ZERO);
         assert_eq!(account.total, Decimal::new(70000, 4));
         assert!(account.locked);
+        assert_eq!(account.lock_reason, Some(LockReason::ChargebackTx(5)));
     }
 
     #[test]
@@ -796,7 +1228,7 @@ mod tests {
         manager.hold_funds(1, Decimal::new(30000, 4)).unwrap();
 
         // Try to chargeback 5.0000 (more than held)
-        let result = manager.chargeback(1, Decimal::new(50000, 4));
+        let result = manager.chargeback(1, 5, Decimal::new(50000, 4));
 
         assert!(result.is_err());
         assert!(matches!(
@@ -810,6 +1242,7 @@ mod tests {
         assert_eq!(account.held, Decimal::new(30000, 4));
         assert_eq!(account.total, Decimal::new(100000, 4));
         assert!(!account.locked); // Should not be locked on failed chargeback
+        assert_eq!(account.lock_reason, None);
     }
 
     #[test]
@@ -843,7 +1276,7 @@ mod tests {
         manager.hold_funds(1, Decimal::new(30000, 4)).unwrap();
 
         // Chargeback: remove 3.0000 and lock
-        manager.chargeback(1, Decimal::new(30000, 4)).unwrap();
+        manager.chargeback(1, 5, Decimal::new(30000, 4)).unwrap();
 
         let account = manager.get_or_create_account(1);
         assert_eq!(account.available, Decimal::new(70000, 4));
@@ -16,8 +16,15 @@
 //! synchronization. The Rust type system ensures that shared references cannot be
 //! used to mutate state, and mutable operations are properly synchronized.
 
-use crate::types::{Account, ClientId, PaymentError};
-use dashmap::DashMap;
+use super::loom::AccessPoint;
+use crate::core::config::MetadataMap;
+use crate::core::hash::KeyHasher;
+use crate::types::{Account, ClientId, PaymentError, TransactionId, TransactionType};
+use dashmap::mapref::one::Ref;
+use dashmap::{DashMap, Entry};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
 
 /// Thread-safe account state manager for async batch processing
 ///
@@ -45,7 +52,44 @@ pub struct AsyncAccountManager {
     ///
     /// DashMap provides fine-grained locking through internal sharding,
     /// allowing concurrent access to different accounts without global locks.
-    accounts: DashMap<ClientId, Account>,
+    accounts: DashMap<ClientId, Account, KeyHasher>,
+
+    /// Metadata attached to accounts when they are created
+    metadata: Arc<MetadataMap>,
+
+    /// Reached before every access to `accounts` (see the `loom` module)
+    access: AccessPoint,
+}
+
+/// Guarded read access to one account of an `AsyncAccountManager`
+///
+/// Dereferences to the account as it is while the guard is alive: updates
+/// to it wait until the guard is dropped, so fields read through the same
+/// guard (e.g. `available` and `held`) are always consistent with each other
+/// and with `total`.
+///
+/// # Thread Safety
+///
+/// The guard holds a read lock on the `DashMap` shard containing the account,
+/// which blocks updates to every account in that shard. Drop it promptly,
+/// and don't update accounts of the same manager while holding it, as that
+/// can deadlock.
+pub struct AccountRef<'a> {
+    entry: Ref<'a, ClientId, Account>,
+}
+
+impl Deref for AccountRef<'_> {
+    type Target = Account;
+
+    fn deref(&self) -> &Account {
+        self.entry.value()
+    }
+}
+
+impl fmt::Debug for AccountRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AccountRef").field(&**self).finish()
+    }
 }
 
 impl AsyncAccountManager {
@@ -57,14 +101,52 @@ impl AsyncAccountManager {
     /// on-demand as transactions are processed.
     pub fn new() -> Self {
         Self {
-            accounts: DashMap::new(),
+            accounts: DashMap::default(),
+            metadata: Arc::default(),
+            access: AccessPoint::new(),
+        }
+    }
+
+    /// Create a new empty AsyncAccountManager with room for `clients` accounts
+    ///
+    /// Avoids resizing the DashMap's shards while they grow to the expected
+    /// number of clients.
+    pub fn with_capacity(clients: usize) -> Self {
+        Self {
+            accounts: DashMap::with_capacity_and_hasher(clients, KeyHasher::default()),
+            metadata: Arc::default(),
+            access: AccessPoint::new(),
+        }
+    }
+
+    /// Number of accounts the manager can hold without resizing
+    pub fn capacity(&self) -> usize {
+        self.accounts.capacity()
+    }
+
+    /// Attach metadata to accounts as they are created
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Account metadata keyed by client ID
+    pub fn with_metadata(mut self, metadata: Arc<MetadataMap>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Create a new account for a client, with its metadata attached
+    fn new_account(&self, client_id: ClientId) -> Account {
+        Account {
+            metadata: self.metadata.get(&client_id).cloned(),
+            ..Account::new(client_id)
         }
     }
 
     /// Get an existing account or create a new one if it doesn't exist
     ///
     /// This method is thread-safe and can be called concurrently from multiple threads.
-    /// If the account doesn't exist, it will be created with zero balances and unlocked status.
+    /// If the account doesn't exist, it will be created with zero balances, unlocked status
+    /// and the client's metadata (if any).
     ///
     /// # Arguments
     ///
@@ -81,12 +163,34 @@ impl AsyncAccountManager {
     /// attempt to create the same account simultaneously, only one will succeed in
     /// creating it, and all threads will receive the same account.
     pub fn get_or_create(&self, client_id: ClientId) -> Account {
+        self.access.reach();
         self.accounts
             .entry(client_id)
-            .or_insert_with(|| Account::new(client_id))
+            .or_insert_with(|| self.new_account(client_id))
             .clone()
     }
 
+    /// Get a guarded view of an existing account without creating it
+    ///
+    /// Unlike `get_or_create`, which returns a clone, the returned guard reads
+    /// the live account and keeps concurrent updates out until it is dropped
+    /// (see `AccountRef`).
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The client ID to look up
+    ///
+    /// # Returns
+    ///
+    /// * `Some(AccountRef)` - If an account exists for the client
+    /// * `None` - If no account exists
+    pub fn get(&self, client_id: ClientId) -> Option<AccountRef<'_>> {
+        self.access.reach();
+        self.accounts
+            .get(&client_id)
+            .map(|entry| AccountRef { entry })
+    }
+
     /// Update an account using a closure
     ///
     /// This method provides atomic access to an account for modification. The closure
@@ -116,13 +220,171 @@ impl AsyncAccountManager {
     where
         F: FnOnce(&mut Account) -> Result<(), PaymentError>,
     {
+        self.access.reach();
         let mut entry = self
             .accounts
             .entry(client_id)
-            .or_insert_with(|| Account::new(client_id));
+            .or_insert_with(|| self.new_account(client_id));
         f(entry.value_mut())
     }
 
+    /// Update an unlocked account, checking the lock with the same map access
+    ///
+    /// Looks the account up once: if it is locked, fails without calling
+    /// either closure. Otherwise `check` runs first, still holding the
+    /// account's entry, and `f` receives the account and its result. An
+    /// account that doesn't exist yet is only created once `check` succeeds,
+    /// so a record rejected by it leaves no empty account behind.
+    ///
+    /// Whenever the account exists, `count` then receives it and whether the
+    /// update went through, before the entry is released: a record is counted
+    /// in the account's activity in the same access that applies or rejects
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The client ID of the account to update
+    /// * `check` - Validation before the account is touched, returning what `f`
+    ///   needs from it
+    /// * `f` - A closure that receives a mutable reference to the account and
+    ///   the result of `check`
+    /// * `count` - A closure that receives a mutable reference to the account
+    ///   and whether the update succeeded; not called if there is no account
+    ///   and `check` fails
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(T)` with the result of `check` if both closures executed successfully
+    /// * `Err(PaymentError::AccountLocked)` if the account is locked
+    /// * `Err(PaymentError)` if either closure returned an error
+    ///
+    /// # Thread Safety
+    ///
+    /// All closures are executed while holding a lock on the account's entry,
+    /// so they must not access this account manager.
+    pub fn update_unlocked<T, C, F, K>(
+        &self,
+        client_id: ClientId,
+        check: C,
+        f: F,
+        count: K,
+    ) -> Result<T, PaymentError>
+    where
+        C: FnOnce() -> Result<T, PaymentError>,
+        F: FnOnce(&mut Account, &T) -> Result<(), PaymentError>,
+        K: FnOnce(&mut Account, bool),
+    {
+        self.access.reach();
+        match self.accounts.entry(client_id) {
+            Entry::Occupied(mut entry) => {
+                let account = entry.get_mut();
+                let result = if account.locked {
+                    Err(PaymentError::account_locked(client_id))
+                } else {
+                    check().and_then(|checked| f(account, &checked).map(|()| checked))
+                };
+                count(account, result.is_ok());
+                result
+            }
+            Entry::Vacant(entry) => {
+                let checked = check()?;
+                let mut account = entry.insert(self.new_account(client_id));
+                let result = f(account.value_mut(), &checked);
+                count(account.value_mut(), result.is_ok());
+                result.map(|()| checked)
+            }
+        }
+    }
+
+    /// Update two accounts together using a closure
+    ///
+    /// Like `update`, but for operations that touch two accounts (transfers,
+    /// admin corrections): the closure runs while holding both accounts'
+    /// entries, so no other thread can observe or modify either account
+    /// until it returns. Accounts that don't exist are created first.
+    ///
+    /// # Arguments
+    ///
+    /// * `first` - The client ID of the account passed first to `f`
+    /// * `second` - The client ID of the account passed second to `f`
+    /// * `f` - A closure that receives mutable references to both accounts and
+    ///   returns a Result indicating success or failure
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the closure executed successfully
+    /// * `Err(PaymentError)` if the closure returned an error; changes it made
+    ///   before failing are kept, as with `update`
+    ///
+    /// # Panics
+    ///
+    /// If `first` and `second` are the same client.
+    ///
+    /// # Thread Safety
+    ///
+    /// A `DashMap` entry locks the whole shard holding it, and two entries may
+    /// share a shard, so taking them one after the other can deadlock. This
+    /// method waits for the lower client's entry only, then merely tries the
+    /// higher one. If that shard is locked (by another thread, or by this one
+    /// because both entries share it), it takes both entries in a single pass
+    /// over the map, which locks shards in ascending order. It never waits for
+    /// a shard while holding another out of that order, so it cannot deadlock
+    /// with other calls or with single-account updates. The fallback visits
+    /// every account, so it is much slower than the usual path.
+    pub fn update_pair<F>(
+        &self,
+        first: ClientId,
+        second: ClientId,
+        f: F,
+    ) -> Result<(), PaymentError>
+    where
+        F: FnOnce(&mut Account, &mut Account) -> Result<(), PaymentError>,
+    {
+        assert_ne!(first, second, "update_pair needs two different clients");
+        let (low, high) = (first.min(second), first.max(second));
+
+        self.access.reach();
+        {
+            let mut low_entry = self
+                .accounts
+                .entry(low)
+                .or_insert_with(|| self.new_account(low));
+            if let Some(high_entry) = self.accounts.try_entry(high) {
+                let mut high_entry = high_entry.or_insert_with(|| self.new_account(high));
+                return if first == low {
+                    f(low_entry.value_mut(), high_entry.value_mut())
+                } else {
+                    f(high_entry.value_mut(), low_entry.value_mut())
+                };
+            }
+        }
+
+        // Create the higher account, then lock both in shard order
+        self.access.reach();
+        drop(
+            self.accounts
+                .entry(high)
+                .or_insert_with(|| self.new_account(high)),
+        );
+        let (mut first_entry, mut second_entry) = (None, None);
+        for entry in self.accounts.iter_mut() {
+            if *entry.key() == first {
+                first_entry = Some(entry);
+            } else if *entry.key() == second {
+                second_entry = Some(entry);
+            }
+            if first_entry.is_some() && second_entry.is_some() {
+                break;
+            }
+        }
+        match (first_entry, second_entry) {
+            (Some(mut first_entry), Some(mut second_entry)) => {
+                f(first_entry.value_mut(), second_entry.value_mut())
+            }
+            _ => unreachable!("accounts are never removed"),
+        }
+    }
+
     /// Check if an account is locked
     ///
     /// This is a read-only operation that checks the locked status of an account.
@@ -143,12 +405,40 @@ impl AsyncAccountManager {
     /// returned value is a snapshot at the time of the call; the account's locked
     /// status may change immediately after this method returns.
     pub fn is_locked(&self, client_id: ClientId) -> bool {
+        self.access.reach();
         self.accounts
             .get(&client_id)
             .map(|acc| acc.locked)
             .unwrap_or(false)
     }
 
+    /// Count a processed record in the activity of its client's account
+    ///
+    /// Records of clients without an account are not counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The client ID of the record
+    /// * `tx_type` - The type of the record
+    /// * `tx` - The transaction ID of the record
+    /// * `applied` - Whether the record was accepted
+    ///
+    /// # Thread Safety
+    ///
+    /// The account is locked while the counters are updated, like in `update`.
+    pub fn record_activity(
+        &self,
+        client_id: ClientId,
+        tx_type: TransactionType,
+        tx: TransactionId,
+        applied: bool,
+    ) {
+        self.access.reach();
+        if let Some(mut account) = self.accounts.get_mut(&client_id) {
+            account.activity.record(tx_type, tx, applied);
+        }
+    }
+
     /// Get all accounts for final output
     ///
     /// This method returns a vector containing clones of all accounts currently
@@ -167,6 +457,7 @@ impl AsyncAccountManager {
     /// created or modified by other threads after this method returns.
     ///
     pub fn get_all_accounts(&self) -> Vec<Account> {
+        self.access.reach();
         self.accounts
             .iter()
             .map(|entry| entry.value().clone())
@@ -183,6 +474,7 @@ impl Default for AsyncAccountManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
     use rust_decimal::Decimal;
 
     #[test]
@@ -198,6 +490,13 @@ mod tests {
         assert!(!account.locked);
     }
 
+    #[test]
+    fn test_with_capacity() {
+        let manager = AsyncAccountManager::with_capacity(1000);
+        assert!(manager.capacity() >= 1000);
+        assert!(manager.get_all_accounts().is_empty());
+    }
+
     #[test]
     fn test_get_or_create_returns_existing_account() {
         let manager = AsyncAccountManager::new();
@@ -219,6 +518,21 @@ mod tests {
         assert_eq!(account.total, Decimal::new(10000, 4));
     }
 
+    #[test]
+    fn test_new_accounts_get_metadata() {
+        let metadata = Arc::new(crate::types::AccountMetadata::from([(
+            "kyc_status".to_string(),
+            "verified".to_string(),
+        )]));
+        let manager = AsyncAccountManager::new()
+            .with_metadata(Arc::new(MetadataMap::from([(1, metadata.clone())])));
+
+        manager.update(1, |_| Ok(())).unwrap();
+
+        assert_eq!(manager.get_or_create(1).metadata, Some(metadata));
+        assert_eq!(manager.get_or_create(2).metadata, None);
+    }
+
     #[test]
     fn test_update_creates_account_if_not_exists() {
         let manager = AsyncAccountManager::new();
@@ -266,6 +580,215 @@ mod tests {
         assert_eq!(result.unwrap_err(), PaymentError::account_locked(1));
     }
 
+    #[test]
+    fn test_update_unlocked_rejects_locked_account() {
+        let manager = AsyncAccountManager::new();
+        manager
+            .update(1, |account| {
+                account.total = Decimal::from(10);
+                account.locked = true;
+                Ok(())
+            })
+            .unwrap();
+
+        let result = manager.update_unlocked(
+            1,
+            || -> Result<(), PaymentError> { panic!("check called on a locked account") },
+            |_, _| panic!("update called on a locked account"),
+            |account, applied| {
+                account
+                    .activity
+                    .record(TransactionType::Deposit, 5, applied)
+            },
+        );
+        assert_eq!(result, Err(PaymentError::account_locked(1)));
+        let account = manager.get_or_create(1);
+        assert_eq!(account.total, Decimal::from(10));
+        assert_eq!(account.activity.errors, 1);
+    }
+
+    #[rstest]
+    #[case::check_fails(Err(PaymentError::duplicate_transaction(5, 1)), false)]
+    #[case::check_passes(Ok(Decimal::from(3)), true)]
+    fn test_update_unlocked_creates_account_after_check(
+        #[case] checked: Result<Decimal, PaymentError>,
+        #[case] created: bool,
+    ) {
+        let manager = AsyncAccountManager::new();
+        let result = manager.update_unlocked(
+            1,
+            || checked.clone(),
+            |account, amount| {
+                account.total = *amount;
+                Ok(())
+            },
+            |_, _| {},
+        );
+
+        assert_eq!(result, checked);
+        assert_eq!(manager.get_all_accounts().len(), usize::from(created));
+        if created {
+            assert_eq!(manager.get_or_create(1).total, Decimal::from(3));
+        }
+    }
+
+    #[rstest]
+    #[case::ascending(1, 2)]
+    #[case::descending(2, 1)]
+    fn test_update_pair_transfers_between_accounts(#[case] from: ClientId, #[case] to: ClientId) {
+        let manager = AsyncAccountManager::new();
+        manager
+            .update(from, |account| {
+                account.available = Decimal::from(10);
+                Ok(())
+            })
+            .unwrap();
+
+        manager
+            .update_pair(from, to, |source, target| {
+                assert_eq!((source.client, target.client), (from, to));
+                source.available -= Decimal::from(4);
+                target.available += Decimal::from(4);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(manager.get_or_create(from).available, Decimal::from(6));
+        assert_eq!(manager.get_or_create(to).available, Decimal::from(4));
+    }
+
+    #[test]
+    fn test_update_pair_returns_error_from_closure() {
+        let manager = AsyncAccountManager::new();
+        let result = manager.update_pair(1, 2, |_, _| Err(PaymentError::account_locked(1)));
+        assert_eq!(result, Err(PaymentError::account_locked(1)));
+        assert_eq!(manager.get_all_accounts().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "two different clients")]
+    fn test_update_pair_same_client_panics() {
+        AsyncAccountManager::new()
+            .update_pair(1, 1, |_, _| Ok(()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_update_pairs() {
+        use std::sync::Arc;
+        use std::thread;
+
+        // Enough clients that some pairs share a shard and take the fallback
+        let clients: ClientId = 64;
+        let manager = Arc::new(AsyncAccountManager::new());
+        for client in 0..clients {
+            manager
+                .update(client, |account| {
+                    account.available = Decimal::from(100);
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        let handles: Vec<_> = (0..8)
+            .map(|thread_id: ClientId| {
+                let manager = Arc::clone(&manager);
+                thread::spawn(move || {
+                    for i in 0..2_000 {
+                        let from = (i * 7 + thread_id) % clients;
+                        let to = (i * 13 + thread_id * 3 + 1) % clients;
+                        if from == to {
+                            continue;
+                        }
+                        manager
+                            .update_pair(from, to, |source, target| {
+                                source.available -= Decimal::ONE;
+                                target.available += Decimal::ONE;
+                                Ok(())
+                            })
+                            .unwrap();
+                        // Single-account updates interleave with the pairs
+                        manager.update(to, |_| Ok(())).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let total: Decimal = manager
+            .get_all_accounts()
+            .iter()
+            .map(|account| account.available)
+            .sum();
+        assert_eq!(total, Decimal::from(100) * Decimal::from(clients));
+    }
+
+    #[test]
+    fn test_get_returns_guarded_view() {
+        let manager = AsyncAccountManager::new();
+        assert!(manager.get(1).is_none());
+
+        manager
+            .update(1, |account| {
+                account.available = Decimal::from(3);
+                account.held = Decimal::from(2);
+                account.total = Decimal::from(5);
+                Ok(())
+            })
+            .unwrap();
+
+        let account = manager.get(1).unwrap();
+        assert_eq!(account.client, 1);
+        assert_eq!(account.available + account.held, account.total);
+        // Other accounts can still be read while the guard is held
+        assert!(manager.get(2).is_none());
+    }
+
+    #[test]
+    fn test_get_is_consistent_with_concurrent_updates() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let manager = Arc::new(AsyncAccountManager::new());
+        manager
+            .update(1, |account| {
+                account.available = Decimal::from(1_000);
+                account.total = Decimal::from(1_000);
+                Ok(())
+            })
+            .unwrap();
+
+        let writer = {
+            let manager = Arc::clone(&manager);
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    manager
+                        .update(1, |account| {
+                            account.available -= Decimal::ONE;
+                            account.held += Decimal::ONE;
+                            Ok(())
+                        })
+                        .unwrap();
+                    manager
+                        .update(1, |account| {
+                            account.held -= Decimal::ONE;
+                            account.available += Decimal::ONE;
+                            Ok(())
+                        })
+                        .unwrap();
+                }
+            })
+        };
+
+        for _ in 0..10_000 {
+            let account = manager.get(1).unwrap();
+            assert_eq!(account.available + account.held, account.total);
+        }
+        writer.join().unwrap();
+    }
+
     #[test]
     fn test_is_locked_returns_false_for_nonexistent_account() {
         let manager = AsyncAccountManager::new();
@@ -310,7 +833,7 @@ mod tests {
         assert_eq!(accounts.len(), 3);
 
         // Verify all client IDs are present
-        let client_ids: Vec<u16> = accounts.iter().map(|a| a.client).collect();
+        let client_ids: Vec<ClientId> = accounts.iter().map(|a| a.client).collect();
         assert!(client_ids.contains(&1));
         assert!(client_ids.contains(&2));
         assert!(client_ids.contains(&3));
@@ -413,7 +936,7 @@ mod tests {
         let mut handles = vec![];
 
         // Spawn 10 threads, each updating a different account
-        for i in 0u16..10 {
+        for i in 0..10 {
             let manager_clone = Arc::clone(&manager);
             let handle = thread::spawn(move || {
                 let amount = Decimal::new(((i + 1) * 1000) as i64, 4);
@@ -434,7 +957,7 @@ mod tests {
         }
 
         // Verify all accounts have correct balances
-        for i in 0u16..10 {
+        for i in 0..10 {
             let account = manager.get_or_create(i);
             let expected = Decimal::new(((i + 1) * 1000) as i64, 4);
             assert_eq!(account.available, expected);
@@ -503,7 +1026,7 @@ mod tests {
         for i in 0..20 {
             let manager_clone = Arc::clone(&manager);
             let handle = thread::spawn(move || {
-                let client_id = (i % 5) as u16;
+                let client_id = (i % 5) as ClientId;
 
                 match i % 3 {
                     0 => {
@@ -559,7 +1082,7 @@ mod tests {
         for i in 0..10 {
             let manager_clone = Arc::clone(&manager);
             let handle = thread::spawn(move || {
-                let client_id = (i % 3) as u16;
+                let client_id = (i % 3) as ClientId;
 
                 // Lock the account
                 manager_clone
@@ -619,7 +1142,7 @@ mod tests {
                 } else {
                     // Update an account
                     manager_clone
-                        .update((i % 5) as u16, |account| {
+                        .update((i % 5) as ClientId, |account| {
                             account.available =
                                 account.available.checked_add(Decimal::new(100, 4)).unwrap();
                             account.total =
@@ -15,32 +15,65 @@
 //! ```text
 //! BatchProcessor
 //!     ├── Arc<AsyncTransactionEngine>  (shared transaction processor)
-//!     └── BatchConfig                  (configuration parameters)
+//!     ├── max_inflight_clients         (optional per-batch concurrency limit)
+//!     ├── retry                        (retries of transient errors)
+//!     ├── cancel                       (optional cancellation token)
+//!     └── faults                       (injected faults, feature `fault-injection`)
 //! ```
 //!
+//! # Scheduling
+//!
+//! A client's transactions must be applied in order, so a single client's
+//! partition can never be split across tasks. When one client dominates a
+//! batch, its partition determines how long the batch takes. To keep the other
+//! workers busy alongside it, partitions are dispatched largest-first: the
+//! longest partition starts immediately and the smaller ones fill the remaining
+//! workers (longest-processing-time-first scheduling). The optional
+//! `max_inflight_clients` limit bounds how many client partitions run at once.
+//!
+//! # Retries
+//!
+//! A transaction failing with a transient error (see
+//! `PaymentError::is_transient`) is processed again following the retry
+//! policy, before the client's next transaction, so retries never reorder a
+//! client's transactions. Waiting between attempts yields the worker to other
+//! clients' partitions. By default every transaction is attempted once.
+//!
+//! # Cancellation
+//!
+//! With a `CancellationToken`, cancelling the token stops the processor
+//! between transactions: no further partitions are dispatched, and every task
+//! returns the results of the transactions it applied so far. Transactions
+//! that were not applied have no result, so a cancelled batch returns fewer
+//! results than it had records (`process_batch_ordered` leaves their slots
+//! `None` instead); the engine's state reflects exactly the transactions with
+//! results. A transaction being retried is not interrupted.
+//!
+//! # Fault Injection
+//!
+//! With the `fault-injection` feature, a `FaultInjection` can delay
+//! transactions before they are processed and fail processing attempts with
+//! transient errors, to test the retries and the ordering guarantees under
+//! stress. A failed attempt never reaches the engine.
+//!
 //! # Thread Safety
 //!
 //! The processor is cloneable and can be safely shared across async tasks.
 //! All internal state is protected by Arc, and the underlying engine uses
 //! thread-safe components.
 
-use std::collections::HashMap;
+use crate::core::hash::KeyMap;
+use crate::core::RetryPolicy;
 use std::sync::Arc;
 
-use super::AsyncTransactionEngine;
-use crate::types::{ClientId, PaymentError, TransactionRecord};
-
-/// Result of processing a single transaction
-///
-/// Contains the original transaction record and the result of processing it.
-#[derive(Debug, Clone)]
-pub struct ProcessingResult {
-    /// The transaction record that was processed
-    pub record: TransactionRecord,
+use tokio::sync::Semaphore;
 
-    /// The result of processing (success or error)
-    pub result: Result<(), PaymentError>,
-}
+#[cfg(feature = "fault-injection")]
+use super::FaultInjection;
+use super::{AsyncTransactionEngine, CancellationToken};
+pub use crate::core::report::ProcessingResult;
+use crate::io::{log, LogLine};
+use crate::types::{ClientId, PaymentError, TransactionRecord};
 
 /// Batch processor with client-based partitioning
 ///
@@ -53,6 +86,21 @@ pub struct BatchProcessor {
     ///
     /// Wrapped in Arc to enable sharing across async tasks.
     engine: Arc<AsyncTransactionEngine>,
+
+    /// Maximum number of client partitions processed concurrently
+    ///
+    /// `None` means every client in a batch may be in flight at once.
+    max_inflight_clients: Option<usize>,
+
+    /// Retries of transactions failing with a transient error
+    retry: RetryPolicy,
+
+    /// Token that stops processing between transactions once cancelled
+    cancel: Option<CancellationToken>,
+
+    /// Faults injected into processing, for testing
+    #[cfg(feature = "fault-injection")]
+    faults: Option<FaultInjection>,
 }
 
 impl BatchProcessor {
@@ -66,7 +114,108 @@ impl BatchProcessor {
     ///
     /// A new `BatchProcessor` that can be cloned and shared across async tasks.
     pub fn new(engine: Arc<AsyncTransactionEngine>) -> Self {
-        Self { engine }
+        Self {
+            engine,
+            max_inflight_clients: None,
+            retry: RetryPolicy::none(),
+            cancel: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        }
+    }
+
+    /// Limit the number of client partitions processed concurrently
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of in-flight clients, or `None` for no limit.
+    ///   A limit of zero is treated as one.
+    ///
+    /// # Returns
+    ///
+    /// The `BatchProcessor` with the limit applied
+    pub fn with_max_inflight_clients(mut self, limit: Option<usize>) -> Self {
+        self.max_inflight_clients = limit.map(|limit| limit.max(1));
+        self
+    }
+
+    /// Maximum number of client partitions processed concurrently, if limited
+    pub fn max_inflight_clients(&self) -> Option<usize> {
+        self.max_inflight_clients
+    }
+
+    /// Retry transactions failing with a transient error following `retry`
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Retry policy of transactions failing with a transient error
+    pub fn retry(&self) -> RetryPolicy {
+        self.retry
+    }
+
+    /// Stop processing between transactions once `cancel` is cancelled
+    ///
+    /// Clones of the processor share the token, so cancelling it stops every
+    /// task started from them.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Token that stops processing once cancelled, if any
+    pub fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancel.as_ref()
+    }
+
+    /// Whether processing was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Inject `faults` into processing, or none
+    #[cfg(feature = "fault-injection")]
+    pub fn with_faults(mut self, faults: Option<FaultInjection>) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Faults injected into processing, if any
+    #[cfg(feature = "fault-injection")]
+    pub fn faults(&self) -> Option<FaultInjection> {
+        self.faults
+    }
+
+    /// Make the attempt number `attempt` (counting from 1) at processing a
+    /// transaction, unless a fault is injected instead
+    fn process_attempt(
+        &self,
+        record: &TransactionRecord,
+        attempt: u32,
+    ) -> Result<(), PaymentError> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(error) = self.faults.and_then(|faults| faults.error(record, attempt)) {
+            return Err(error);
+        }
+        #[cfg(not(feature = "fault-injection"))]
+        let _ = attempt;
+        self.engine.process_transaction(record.clone())
+    }
+
+    /// Order client partitions for dispatch, largest first
+    ///
+    /// Ties are broken by client ID so the dispatch order is deterministic.
+    pub(crate) fn schedule(
+        client_batches: KeyMap<ClientId, Vec<TransactionRecord>>,
+    ) -> Vec<(ClientId, Vec<TransactionRecord>)> {
+        let mut partitions: Vec<_> = client_batches.into_iter().collect();
+        partitions.sort_unstable_by(|(client_a, txs_a), (client_b, txs_b)| {
+            txs_b.len().cmp(&txs_a.len()).then(client_a.cmp(client_b))
+        });
+        partitions
     }
 
     /// Partition a batch of transactions by client ID
@@ -96,8 +245,8 @@ impl BatchProcessor {
     pub fn partition_by_client(
         &self,
         batch: Vec<TransactionRecord>,
-    ) -> HashMap<ClientId, Vec<TransactionRecord>> {
-        let mut client_batches: HashMap<ClientId, Vec<TransactionRecord>> = HashMap::new();
+    ) -> KeyMap<ClientId, Vec<TransactionRecord>> {
+        let mut client_batches: KeyMap<ClientId, Vec<TransactionRecord>> = KeyMap::default();
 
         for record in batch {
             client_batches
@@ -129,8 +278,12 @@ impl BatchProcessor {
     ///
     /// - Transactions are processed in the order they appear in the input vector
     /// - All transactions are processed, even if some fail
+    /// - Transactions failing with a transient error are retried, following
+    ///   the retry policy, before the next one is processed
     /// - Errors are captured in the result and don't stop processing
     /// - Results maintain the same order as input transactions
+    /// - Once the processor is cancelled, the remaining transactions are
+    ///   skipped and have no result
     pub async fn process_client_transactions(
         &self,
         transactions: Vec<TransactionRecord>,
@@ -138,8 +291,29 @@ impl BatchProcessor {
         let mut results = Vec::with_capacity(transactions.len());
 
         for record in transactions {
-            let result = self.engine.process_transaction(record.clone());
-            results.push(ProcessingResult { record, result });
+            if self.is_cancelled() {
+                break;
+            }
+            #[cfg(feature = "fault-injection")]
+            if let Some(delay) = self.faults.and_then(|faults| faults.delay(&record)) {
+                tokio::time::sleep(delay).await;
+            }
+            let mut attempt = 0;
+            let (result, attempts) = self
+                .retry
+                .run_async(
+                    || {
+                        attempt += 1;
+                        self.process_attempt(&record, attempt)
+                    },
+                    PaymentError::is_transient,
+                )
+                .await;
+            results.push(ProcessingResult {
+                record,
+                result,
+                attempts,
+            });
         }
 
         results
@@ -149,9 +323,11 @@ impl BatchProcessor {
     ///
     /// This method processes a batch of transactions by:
     /// 1. Partitioning the batch by client ID
-    /// 2. Spawning tokio tasks to process each client's transactions concurrently
-    /// 3. Waiting for all tasks to complete
-    /// 4. Collecting and returning all results
+    /// 2. Ordering the partitions largest-first
+    /// 3. Spawning tokio tasks to process each client's transactions concurrently,
+    ///    with at most `max_inflight_clients` partitions in flight
+    /// 4. Waiting for all tasks to complete
+    /// 5. Collecting and returning all results
     ///
     /// # Arguments
     ///
@@ -160,49 +336,126 @@ impl BatchProcessor {
     /// # Returns
     ///
     /// A vector of `ProcessingResult` containing the outcome of each transaction.
-    /// Results may be in a different order than the input due to concurrent processing.
+    /// Results may be in a different order than the input due to concurrent processing;
+    /// `process_batch_ordered` returns them in input order.
     ///
     /// # Guarantees
     ///
     /// - Transactions for different clients are processed concurrently
     /// - Transactions for the same client are processed sequentially in order
-    /// - All transactions are processed, even if some fail
+    /// - Larger client partitions are dispatched before smaller ones
+    /// - All transactions are processed, even if some fail, unless the
+    ///   processor is cancelled
     /// - Errors are captured in results and don't stop processing
     pub async fn process_batch(&self, batch: Vec<TransactionRecord>) -> Vec<ProcessingResult> {
-        // Partition batch by client ID
-        let client_batches = self.partition_by_client(batch);
+        self.process_indexed(batch)
+            .await
+            .into_iter()
+            .map(|(_, result)| result)
+            .collect()
+    }
+
+    /// Process a batch like `process_batch`, pairing each result with the
+    /// index of its record in `batch`
+    async fn process_indexed(
+        &self,
+        batch: Vec<TransactionRecord>,
+    ) -> Vec<(usize, ProcessingResult)> {
+        // Each client's results are in the order of its records, so the
+        // input indices of a client's records tell where its results go
+        let mut indices: KeyMap<ClientId, Vec<usize>> = KeyMap::default();
+        for (index, record) in batch.iter().enumerate() {
+            indices.entry(record.client).or_default().push(index);
+        }
+
+        // Partition batch by client ID and dispatch the largest partitions first
+        let partitions = Self::schedule(self.partition_by_client(batch));
+
+        let limiter = self
+            .max_inflight_clients
+            .map(|limit| Arc::new(Semaphore::new(limit)));
 
         // Spawn tokio tasks for each client's transactions
-        let mut tasks = Vec::new();
-        for (_client_id, transactions) in client_batches {
+        let mut tasks = Vec::with_capacity(partitions.len());
+        for (client_id, transactions) in partitions {
+            if self.is_cancelled() {
+                break;
+            }
+
+            // Wait for a free slot before dispatching the next client
+            let permit = match &limiter {
+                Some(limiter) => Some(
+                    Arc::clone(limiter)
+                        .acquire_owned()
+                        .await
+                        .expect("semaphore is never closed"),
+                ),
+                None => None,
+            };
+
             let processor = self.clone();
             let task = tokio::spawn(async move {
-                processor
-                    .process_client_transactions(transactions)
-                    .await
+                let results = processor.process_client_transactions(transactions).await;
+                drop(permit);
+                results
             });
-            tasks.push(task);
+            tasks.push((client_id, task));
         }
 
         // Wait for all tasks to complete and collect results
         let mut results = Vec::new();
-        for task in tasks {
+        for (client_id, task) in tasks {
             match task.await {
-                Ok(client_results) => results.extend(client_results),
+                Ok(client_results) => results.extend(
+                    indices
+                        .remove(&client_id)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .zip(client_results),
+                ),
                 Err(e) => {
-                    eprintln!("Task panicked: {:?}", e);
+                    log(LogLine::error(format!("Task panicked: {:?}", e)));
                 }
             }
         }
 
         results
     }
+
+    /// Process a batch like `process_batch`, returning results in input order
+    ///
+    /// The batch is processed exactly as by `process_batch`; each result is
+    /// then put in the slot of its record, tracked by input index, so the
+    /// slot at index `i` holds the outcome of the record at index `i` of
+    /// `batch`, for callers correlating results with input rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch` - A vector of transaction records to process
+    ///
+    /// # Returns
+    ///
+    /// One slot per record of `batch`, in its order. A slot is `None` if its
+    /// record has no result: it was skipped because the processor was
+    /// cancelled, or its client's task panicked (the panic is logged). The
+    /// other slots keep their positions.
+    pub async fn process_batch_ordered(
+        &self,
+        batch: Vec<TransactionRecord>,
+    ) -> Vec<Option<ProcessingResult>> {
+        let mut slots = vec![None; batch.len()];
+        for (index, result) in self.process_indexed(batch).await {
+            slots[index] = Some(result);
+        }
+        slots
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::r#async::{AsyncAccountManager, AsyncTransactionStore};
+    use crate::types::TransactionId;
 
     #[test]
     fn test_new_creates_processor() {
@@ -308,18 +561,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Withdrawal,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(5000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -358,30 +617,40 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(5000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 3,
                 tx: 4,
                 amount: Some(Decimal::new(15000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 5,
                 amount: Some(Decimal::new(8000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -429,30 +698,40 @@ mod tests {
                 client: 1,
                 tx: 10,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 20,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 11,
                 amount: Some(Decimal::new(5000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 12,
                 amount: Some(Decimal::new(3000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 21,
                 amount: Some(Decimal::new(8000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -492,18 +771,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 3,
                 tx: 3,
                 amount: Some(Decimal::new(30000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -538,18 +823,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(30000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -591,8 +882,10 @@ mod tests {
             batch.push(TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: i,
-                tx: i as u32,
+                tx: i as TransactionId,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             });
         }
 
@@ -629,18 +922,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Dispute,
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -695,6 +994,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         }];
 
         let results = processor.process_client_transactions(transactions).await;
@@ -728,18 +1029,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(5000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -776,12 +1083,16 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Withdrawal,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(3000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -817,12 +1128,16 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Withdrawal,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)), // More than available
+                line: None,
+                source: None,
             },
         ];
 
@@ -858,18 +1173,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Withdrawal,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)), // Will fail
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(5000, 4)), // Should still process
+                line: None,
+                source: None,
             },
         ];
 
@@ -906,12 +1227,16 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Dispute,
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             },
         ];
 
@@ -948,18 +1273,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(30000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -1011,12 +1342,16 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -1051,18 +1386,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 3,
                 tx: 3,
                 amount: Some(Decimal::new(30000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -1102,24 +1443,32 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 1,
                 tx: 3,
                 amount: Some(Decimal::new(5000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 4,
                 amount: Some(Decimal::new(8000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -1156,18 +1505,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Withdrawal,
                 client: 1,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)), // Will fail - insufficient funds
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 3,
                 amount: Some(Decimal::new(30000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -1211,12 +1566,16 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
@@ -1246,14 +1605,18 @@ mod tests {
             batch.push(TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: i,
-                tx: i as u32 * 2,
+                tx: i as TransactionId * 2,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             });
             batch.push(TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: i,
-                tx: i as u32 * 2 + 1,
+                tx: i as TransactionId * 2 + 1,
                 amount: Some(Decimal::new(5000, 4)),
+                line: None,
+                source: None,
             });
         }
 
@@ -1289,18 +1652,24 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Dispute,
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Resolve,
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             },
         ];
 
@@ -1337,26 +1706,434 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             },
             TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: 3,
                 tx: 3,
                 amount: Some(Decimal::new(30000, 4)),
+                line: None,
+                source: None,
             },
         ];
 
-        let original_tx_ids: HashSet<u32> = batch.iter().map(|r| r.tx).collect();
+        let original_tx_ids: HashSet<TransactionId> = batch.iter().map(|r| r.tx).collect();
         let results = processor.process_batch(batch).await;
 
         // Verify all transactions were processed
-        let result_tx_ids: HashSet<u32> = results.iter().map(|r| r.record.tx).collect();
+        let result_tx_ids: HashSet<TransactionId> = results.iter().map(|r| r.record.tx).collect();
         assert_eq!(original_tx_ids, result_tx_ids);
     }
+
+    #[tokio::test]
+    async fn test_process_batch_dispatches_largest_client_first() {
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            account_manager,
+            transaction_store,
+        ));
+
+        let processor = BatchProcessor::new(engine);
+
+        // Client 3 has the most transactions, then client 2, then client 1
+        let clients = [1, 2, 2, 3, 3, 3];
+        let batch = clients
+            .iter()
+            .enumerate()
+            .map(|(i, &client)| TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client,
+                tx: i as TransactionId,
+                amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
+            })
+            .collect();
+
+        let results = processor.process_batch(batch).await;
+
+        let order: Vec<ClientId> = results.iter().map(|r| r.record.client).collect();
+        assert_eq!(order, vec![3, 3, 3, 2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_stops_when_cancelled() {
+        use crate::core::r#async::CancellationToken;
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        ));
+        let cancel = CancellationToken::new();
+        let processor = BatchProcessor::new(engine).with_cancellation(cancel.clone());
+        let deposit = |client, tx| TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client,
+            tx,
+            amount: Some(Decimal::ONE),
+            line: None,
+            source: None,
+        };
+
+        let results = processor.process_batch(vec![deposit(1, 1)]).await;
+        assert_eq!(results.len(), 1);
+        assert!(!processor.is_cancelled());
+
+        cancel.cancel();
+        assert!(processor.clone().is_cancelled());
+        let results = processor
+            .process_batch(vec![deposit(1, 2), deposit(2, 3)])
+            .await;
+        assert!(results.is_empty());
+        assert!(processor
+            .process_client_transactions(vec![deposit(1, 4)])
+            .await
+            .is_empty());
+        assert_eq!(account_manager.get_or_create(1).available, Decimal::ONE);
+        assert_eq!(account_manager.get_or_create(2).available, Decimal::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_process_batch_ordered_reports_cancelled_records() {
+        use crate::core::r#async::CancellationToken;
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        ));
+        let cancel = CancellationToken::new();
+        let processor = BatchProcessor::new(engine).with_cancellation(cancel.clone());
+        let batch: Vec<TransactionRecord> = [1, 2, 1]
+            .iter()
+            .zip(1..)
+            .map(|(&client, tx)| TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client,
+                tx,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+            .collect();
+
+        cancel.cancel();
+        let slots = processor.process_batch_ordered(batch).await;
+
+        // Every skipped record keeps its slot, with no result
+        assert_eq!(slots.len(), 3);
+        assert!(slots.iter().all(Option::is_none));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_process_batch_ordered_keeps_input_order() {
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            account_manager,
+            transaction_store,
+        ));
+
+        let processor = BatchProcessor::new(engine);
+
+        // Interleaved clients, the largest last; every third record is a
+        // withdrawal, of which those of tx 2 and 5 exceed client 3's balance
+        let batch: Vec<TransactionRecord> = [1, 2, 3, 3, 2, 3, 1, 3, 3]
+            .iter()
+            .enumerate()
+            .map(|(i, &client)| TransactionRecord {
+                tx_type: if i % 3 == 2 {
+                    TransactionType::Withdrawal
+                } else {
+                    TransactionType::Deposit
+                },
+                client,
+                tx: i as TransactionId,
+                amount: Some(Decimal::new(10000 * (1 + i as i64), 4)),
+                line: None,
+                source: None,
+            })
+            .collect();
+
+        let results: Vec<ProcessingResult> = processor
+            .process_batch_ordered(batch)
+            .await
+            .into_iter()
+            .map(Option::unwrap)
+            .collect();
+
+        let order: Vec<TransactionId> = results.iter().map(|r| r.record.tx).collect();
+        assert_eq!(order, (0..9).collect::<Vec<_>>());
+        let rejected: Vec<TransactionId> = results
+            .iter()
+            .filter(|r| r.result.is_err())
+            .map(|r| r.record.tx)
+            .collect();
+        assert_eq!(rejected, vec![2, 5]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_process_batch_with_max_inflight_clients() {
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            transaction_store,
+        ));
+
+        let processor = BatchProcessor::new(engine).with_max_inflight_clients(Some(2));
+
+        // One dominant client followed by many small ones
+        let mut batch = Vec::new();
+        for i in 0..90 {
+            batch.push(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: i,
+                amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
+            });
+        }
+        for client in 2..12 {
+            batch.push(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client,
+                tx: 100 + client as TransactionId,
+                amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
+            });
+        }
+
+        let results = processor.process_batch(batch).await;
+
+        assert_eq!(results.len(), 100);
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        assert_eq!(
+            account_manager.get_or_create(1).available,
+            Decimal::new(900000, 4)
+        );
+        for client in 2..12 {
+            assert_eq!(
+                account_manager.get_or_create(client).available,
+                Decimal::new(10000, 4)
+            );
+        }
+    }
+
+    #[test]
+    fn test_with_max_inflight_clients_zero_is_one() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            account_manager,
+            transaction_store,
+        ));
+
+        let processor = BatchProcessor::new(engine).with_max_inflight_clients(Some(0));
+        assert_eq!(processor.max_inflight_clients, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            account_manager,
+            transaction_store,
+        ));
+        let retry = RetryPolicy::new(3, std::time::Duration::ZERO);
+        let processor = BatchProcessor::new(engine).with_retry(retry);
+        assert_eq!(processor.retry(), retry);
+
+        let results = processor
+            .process_client_transactions(vec![
+                TransactionRecord {
+                    tx_type: TransactionType::Deposit,
+                    client: 1,
+                    tx: 1,
+                    amount: Some(Decimal::ONE),
+                    line: None,
+                    source: None,
+                },
+                TransactionRecord {
+                    tx_type: TransactionType::Withdrawal,
+                    client: 1,
+                    tx: 2,
+                    amount: Some(Decimal::TEN),
+                    line: None,
+                    source: None,
+                },
+            ])
+            .await;
+
+        assert!(results[1].result.is_err());
+        assert!(results.iter().all(|r| r.attempts == 1));
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[tokio::test]
+    async fn test_injected_faults_are_retried_in_order() {
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+        use std::time::Duration;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            account_manager.clone(),
+            transaction_store,
+        ));
+        let faults = FaultInjection::new(1)
+            .with_errors(0.5)
+            .with_delays(0.5, Duration::from_millis(1));
+        let processor = BatchProcessor::new(engine)
+            .with_retry(RetryPolicy::new(64, Duration::ZERO))
+            .with_faults(Some(faults));
+        assert_eq!(processor.faults(), Some(faults));
+
+        // Each withdrawal only succeeds if the deposits before it were applied
+        let transactions: Vec<_> = (1..=50)
+            .map(|tx| TransactionRecord {
+                tx_type: if tx % 2 == 1 {
+                    TransactionType::Deposit
+                } else {
+                    TransactionType::Withdrawal
+                },
+                client: 1,
+                tx,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+            .collect();
+        let results = processor.process_client_transactions(transactions).await;
+
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        assert!(results.iter().any(|r| r.attempts > 1));
+        assert_eq!(
+            results.iter().map(|r| r.record.tx).collect::<Vec<_>>(),
+            (1..=50).collect::<Vec<_>>()
+        );
+        assert_eq!(account_manager.get(1).unwrap().total, Decimal::ZERO);
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[tokio::test]
+    async fn test_injected_fault_leaves_state_unchanged() {
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            account_manager.clone(),
+            transaction_store,
+        ));
+        let processor =
+            BatchProcessor::new(engine).with_faults(Some(FaultInjection::new(1).with_errors(1.0)));
+
+        let results = processor
+            .process_client_transactions(vec![TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            }])
+            .await;
+
+        assert!(matches!(
+            results[0].result,
+            Err(PaymentError::IoError { .. })
+        ));
+        assert!(account_manager.get(1).is_none());
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[tokio::test(start_paused = true)]
+    async fn test_process_batch_ordered_keeps_slots_of_cancelled_partition() {
+        use crate::core::r#async::CancellationToken;
+        use crate::types::TransactionType;
+        use rust_decimal::Decimal;
+        use std::time::Duration;
+
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        ));
+        let cancel = CancellationToken::new();
+        let processor = BatchProcessor::new(engine)
+            .with_faults(Some(
+                FaultInjection::new(1).with_delays(1.0, Duration::from_millis(10)),
+            ))
+            .with_cancellation(cancel.clone());
+
+        // Client 1 is still being processed when the batch is cancelled
+        let batch: Vec<TransactionRecord> = [1, 1, 2, 1, 2, 1, 1, 1]
+            .iter()
+            .zip(1..)
+            .map(|(&client, tx)| TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client,
+                tx,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+            .collect();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(12)).await;
+            cancel.cancel();
+        });
+        let slots = processor.process_batch_ordered(batch.clone()).await;
+
+        // Every slot holds the result of its own record, or none; a client's
+        // results are those of its first records
+        assert_eq!(slots.len(), batch.len());
+        for client in [1, 2] {
+            let processed: Vec<bool> = batch
+                .iter()
+                .zip(&slots)
+                .filter(|(record, _)| record.client == client)
+                .map(|(record, slot)| {
+                    slot.as_ref()
+                        .inspect(|result| assert_eq!(result.record.tx, record.tx))
+                        .is_some()
+                })
+                .collect();
+            assert!(processed.is_sorted_by(|a, b| a >= b), "{processed:?}");
+        }
+        assert!(slots.iter().any(Option::is_some));
+        assert!(slots.iter().any(Option::is_none));
+    }
 }
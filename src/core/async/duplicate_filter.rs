@@ -0,0 +1,204 @@
+//! Bloom filter in front of the duplicate transaction check
+//!
+//! Every deposit and withdrawal is checked against the transaction store
+//! before it is applied, and almost all of them are new. `DuplicateFilter`
+//! answers "definitely new" for those without locking a shard of the store's
+//! `DashMap`; only IDs the filter may have seen are looked up in the map.
+//!
+//! # Design
+//!
+//! The filter is blocked: each ID maps to one block of eight 64-bit words (a
+//! cache line), and all of its bits are set or tested within that block with
+//! atomic operations on the words. Concurrent inserts and lookups never
+//! contend on a lock, and each costs one cache miss at most. Blocking raises
+//! the false positive rate slightly above that of a classic bloom filter of
+//! the same size.
+//!
+//! A false positive only costs the map lookup the filter would have saved, and
+//! there are no false negatives, so the filter never changes results. Once
+//! more IDs are inserted than it was sized for, its false positive rate
+//! climbs towards 1 and the store falls back to map lookups.
+
+use crate::types::TransactionId;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of words per block
+const BLOCK_WORDS: usize = 8;
+
+/// Number of bits per block
+const BLOCK_BITS: f64 = (BLOCK_WORDS * 64) as f64;
+
+/// Largest number of bits set per ID
+const MAX_HASHES: u64 = 16;
+
+/// Lock-free bloom filter of transaction IDs
+#[derive(Debug)]
+pub struct DuplicateFilter {
+    words: Box<[AtomicU64]>,
+    /// Number of bits set per ID
+    hashes: u64,
+}
+
+/// Mix the bits of a 64-bit value (the splitmix64 finalizer)
+pub(super) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// False positive rate of a blocked filter holding `ids_per_block` IDs per
+/// block on average, with `hashes` bits set per ID
+///
+/// The number of IDs in a block is Poisson distributed; a block holding `j`
+/// IDs has the false positive rate of a classic filter of `BLOCK_BITS` bits.
+fn blocked_rate(ids_per_block: f64, hashes: u64) -> f64 {
+    let bit_clear = 1.0 - 1.0 / BLOCK_BITS;
+    let terms = (ids_per_block + 10.0 * ids_per_block.sqrt() + 20.0) as i32;
+    let mut probability = (-ids_per_block).exp();
+    let mut rate = 0.0;
+    for j in 0..terms {
+        rate += probability * (1.0 - bit_clear.powi(j * hashes as i32)).powi(hashes as i32);
+        probability *= ids_per_block / f64::from(j + 1);
+    }
+    rate
+}
+
+impl DuplicateFilter {
+    /// Create a filter sized for `capacity` IDs at a target false positive rate
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Number of IDs the filter is expected to hold
+    /// * `false_positive_rate` - Target rate of new IDs reported as possibly
+    ///   seen, clamped to between 0.000001 and 0.5; the rate reached is close
+    ///   to it while at most `capacity` IDs are inserted
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let rate = false_positive_rate.clamp(1e-6, 0.5);
+        let capacity = capacity.max(1) as f64;
+        // Start from the size of a classic bloom filter, then grow it until
+        // the blocked filter reaches the target rate too
+        let bits_per_id = -rate.ln() / std::f64::consts::LN_2.powi(2);
+        let hashes = ((bits_per_id * std::f64::consts::LN_2).round() as u64).clamp(1, MAX_HASHES);
+        let mut blocks = (capacity * bits_per_id / BLOCK_BITS).ceil();
+        while blocked_rate(capacity / blocks, hashes) > rate {
+            blocks = (blocks * 1.05).ceil();
+        }
+        Self {
+            words: (0..blocks as usize * BLOCK_WORDS)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            hashes,
+        }
+    }
+
+    /// Size of the filter's bit array in bytes
+    pub fn size_bytes(&self) -> usize {
+        self.words.len() * 8
+    }
+
+    /// The block of `tx_id` and the masks of its bits in the block's words
+    fn locate(&self, tx_id: TransactionId) -> (&[AtomicU64], [u64; BLOCK_WORDS]) {
+        let hash = mix(tx_id);
+        let blocks = self.words.len() / BLOCK_WORDS;
+        // Multiply-shift maps the hash onto the blocks without a division
+        let block = ((u128::from(hash) * blocks as u128) >> 64) as usize;
+        // Each bit position takes 9 bits of a hash, so one hash covers 7 bits
+        let mut positions = hash;
+        let mut masks = [0; BLOCK_WORDS];
+        for i in 0..self.hashes {
+            if i % 7 == 0 {
+                positions = mix(positions ^ 0x9e37_79b9_7f4a_7c15);
+            }
+            let position = positions & 511;
+            masks[(position >> 6) as usize] |= 1 << (position & 63);
+            positions >>= 9;
+        }
+        let start = block * BLOCK_WORDS;
+        (&self.words[start..start + BLOCK_WORDS], masks)
+    }
+
+    /// Record that `tx_id` has been seen
+    pub fn insert(&self, tx_id: TransactionId) {
+        let (block, masks) = self.locate(tx_id);
+        for (word, mask) in block.iter().zip(masks) {
+            if mask != 0 {
+                word.fetch_or(mask, Ordering::Release);
+            }
+        }
+    }
+
+    /// Returns false if `tx_id` has definitely not been inserted
+    pub fn may_contain(&self, tx_id: TransactionId) -> bool {
+        let (block, masks) = self.locate(tx_id);
+        block
+            .iter()
+            .zip(masks)
+            .all(|(word, mask)| word.load(Ordering::Acquire) & mask == mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_inserted_ids_are_found() {
+        let filter = DuplicateFilter::new(10_000, 0.01);
+        for tx_id in (0..10_000).map(|i| i * 7919) {
+            filter.insert(tx_id);
+        }
+        assert!((0..10_000).all(|i| filter.may_contain(i * 7919)));
+    }
+
+    #[rstest]
+    #[case::one_percent(0.01)]
+    #[case::one_in_a_thousand(0.001)]
+    fn test_false_positive_rate(#[case] rate: f64) {
+        let filter = DuplicateFilter::new(100_000, rate);
+        for tx_id in 0..100_000 {
+            filter.insert(tx_id);
+        }
+
+        let false_positives = (100_000..1_100_000)
+            .filter(|&tx_id| filter.may_contain(tx_id))
+            .count();
+        // The sizing model is approximate
+        assert!(
+            (false_positives as f64) < 1_000_000.0 * rate * 1.5,
+            "{} false positives",
+            false_positives
+        );
+    }
+
+    #[rstest]
+    #[case::zero_capacity(0, 0.01, 64)]
+    #[case::zero_rate(1000, 0.0, 5056)]
+    #[case::one_percent(1000, 0.01, 1280)]
+    fn test_size(#[case] capacity: usize, #[case] rate: f64, #[case] bytes: usize) {
+        assert_eq!(DuplicateFilter::new(capacity, rate).size_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_concurrent_inserts() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let filter = Arc::new(DuplicateFilter::new(40_000, 0.01));
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread_id| {
+                let filter = Arc::clone(&filter);
+                thread::spawn(move || {
+                    for tx_id in (0..10_000).map(|i| i * 4 + thread_id) {
+                        filter.insert(tx_id);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!((0..40_000).all(|tx_id| filter.may_contain(tx_id)));
+    }
+}
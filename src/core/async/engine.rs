@@ -24,11 +24,23 @@
 //! The engine itself is cloneable (via Clone trait) and can be safely shared across
 //! multiple async tasks. All internal state is protected by Arc, and the underlying
 //! components use DashMap for thread-safe concurrent access.
-use std::sync::Arc;
-
-use crate::types::{PaymentError, StoredTransaction};
-
-use super::{AsyncAccountManager, AsyncTransactionStore};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::core::config::{DuplicateTxPolicy, EngineConfig};
+use crate::core::flows::MoneyFlows;
+use crate::core::history::{BalanceHistory, BalancePoint};
+use crate::core::journal::Posting;
+use crate::core::report::ProcessingResult;
+use crate::core::traits::{Engine, EngineSnapshot};
+use crate::core::velocity::VelocityTracker;
+use crate::types::{
+    Account, ClientId, DisputeState, LockReason, PaymentError, StoredTransaction, TransactionId,
+    TransactionRecord, TransactionType,
+};
+use rust_decimal::Decimal;
+
+use super::{AsyncAccountManager, AsyncTransactionStore, BatchProcessor};
 
 /// Transaction processing orchestrator for async batch processing
 ///
@@ -54,6 +66,34 @@ pub struct AsyncTransactionEngine {
     /// Wrapped in Arc to enable sharing across async tasks. The AsyncTransactionStore
     /// uses DashMap internally for fine-grained locking per transaction.
     transaction_store: Arc<AsyncTransactionStore>,
+
+    /// Engine configuration (risk rules)
+    ///
+    /// Account metadata is attached by the AsyncAccountManager, which should be
+    /// built from the same configuration via `with_metadata`.
+    config: Arc<EngineConfig>,
+
+    /// Money moved by the transactions applied by this engine and its clones
+    ///
+    /// Only updated after a transaction succeeds, so the lock is held briefly.
+    flows: Arc<Mutex<MoneyFlows>>,
+
+    /// Recent transactions of every client, if a velocity limit is configured
+    ///
+    /// A client's transactions are processed in order by one task at a time,
+    /// so checking and recording under separate locks is consistent.
+    velocity: Option<Arc<Mutex<VelocityTracker>>>,
+
+    /// Postings since the last `take_postings`, if the journal is enabled
+    ///
+    /// Postings of different clients interleave in processing order; those of
+    /// one client keep its input order.
+    postings: Arc<Mutex<Vec<Posting>>>,
+
+    /// Sampled balances of every client, if a balance history is configured
+    ///
+    /// Samples of one client keep its input order, like postings.
+    history: Option<Arc<Mutex<BalanceHistory>>>,
 }
 
 impl AsyncTransactionEngine {
@@ -74,14 +114,42 @@ impl AsyncTransactionEngine {
         Self {
             account_manager,
             transaction_store,
+            config: Arc::default(),
+            flows: Arc::default(),
+            velocity: None,
+            postings: Arc::default(),
+            history: None,
         }
     }
 
+    /// Set the engine configuration
+    ///
+    /// The dispute expiry is not applied: it counts records in input order,
+    /// which concurrent processing does not follow. Transaction IDs are
+    /// always unique across clients: the per-client duplicate transaction
+    /// policy rejects reused IDs like the default one.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Engine configuration whose risk rules will be enforced
+    pub fn with_config(mut self, config: EngineConfig) -> Self {
+        self.velocity = config
+            .velocity_limit
+            .map(|limit| Arc::new(Mutex::new(VelocityTracker::new(limit))));
+        self.history = config
+            .balance_history
+            .map(|every| Arc::new(Mutex::new(BalanceHistory::new(every))));
+        self.config = Arc::new(config);
+        self
+    }
+
     /// Process a deposit transaction
     ///
     /// This method processes a deposit by:
-    /// 1. Storing the transaction for potential future disputes
+    /// 1. Checking the account is not locked, the transaction ID is new and
+    ///    the amount limits, within a single access to the account
     /// 2. Updating the account balance with checked arithmetic
+    /// 3. Storing the transaction for potential future disputes
     ///
     /// # Arguments
     ///
@@ -90,56 +158,123 @@ impl AsyncTransactionEngine {
     /// # Returns
     ///
     /// * `Ok(())` - If the deposit was processed successfully
+    /// * `Err(PaymentError::AccountLocked)` - If the account is locked
     /// * `Err(PaymentError::MissingAmount)` - If the amount field is missing
+    /// * `Err(PaymentError::DuplicateTransaction)` - If the transaction ID was already used
+    /// * `Err(PaymentError::AmountLimitExceeded)` - If the amount is above the maximum deposit
+    /// * `Err(PaymentError::BalanceLimitExceeded)` - If the new total would be above the maximum
+    /// * `Err(PaymentError::InsufficientFunds)` - If the balance after the deposit does not
+    ///   cover its fee
     /// * `Err(PaymentError::ArithmeticOverflow)` - If the deposit would cause overflow
     pub fn process_deposit(
         &self,
         record: crate::types::TransactionRecord,
     ) -> Result<(), crate::types::PaymentError> {
-        // Extract amount or return error if missing
-        let amount = record
-            .amount
-            .ok_or_else(|| PaymentError::missing_amount("deposit", record.tx, record.client))?;
-
-        // Check for duplicate transaction ID
-        if self.transaction_store.get(record.tx).is_some() {
-            return Err(PaymentError::duplicate_transaction(
-                record.tx,
+        self.deposit(record, None)
+    }
+
+    /// Process a deposit like `process_deposit`, counting it in `activity`
+    /// under the account access that applies or rejects it
+    fn deposit(
+        &self,
+        record: TransactionRecord,
+        activity: Option<&Activity>,
+    ) -> Result<(), PaymentError> {
+        // Check the lock and update the account balance in one map access
+        let (amount, fee) =
+            self.account_manager.update_unlocked(
                 record.client,
-            ));
+                || {
+                    // Extract amount or return error if missing
+                    let amount = record.amount.ok_or_else(|| {
+                        PaymentError::missing_amount("deposit", record.tx, record.client)
+                    })?;
+
+                    // Check for duplicate transaction ID
+                    if self.transaction_store.contains(record.tx) {
+                        return Err(PaymentError::duplicate_transaction(
+                            record.tx,
+                            record.client,
+                        ));
+                    }
+                    let fee = self.config.fee(record.tx_type, record.client, amount)?;
+                    Ok((amount, fee))
+                },
+                |account, &(amount, fee)| {
+                    self.config.limits.check_deposit(
+                        record.tx,
+                        record.client,
+                        amount,
+                        account.total,
+                    )?;
+                    let available = account.available.checked_add(amount).ok_or_else(|| {
+                        PaymentError::arithmetic_overflow("deposit", record.client)
+                    })?;
+                    let total = account.total.checked_add(amount).ok_or_else(|| {
+                        PaymentError::arithmetic_overflow("deposit", record.client)
+                    })?;
+                    // The fee, if any, must be covered by the balance after the deposit
+                    let fee = fee.map_or(Decimal::ZERO, |(_, fee)| fee);
+                    if available < fee {
+                        return Err(PaymentError::insufficient_funds(
+                            record.client,
+                            available,
+                            fee,
+                        ));
+                    }
+                    account.available = available.checked_sub(fee).ok_or_else(|| {
+                        PaymentError::arithmetic_underflow("deposit", record.client)
+                    })?;
+                    account.total = total.checked_sub(fee).ok_or_else(|| {
+                        PaymentError::arithmetic_underflow("deposit", record.client)
+                    })?;
+                    Ok(())
+                },
+                |account, applied| Activity::count(activity, account, applied),
+            )?;
+
+        // If the fee account cannot take the fee, take the deposit back off
+        // the client's account, so the failed deposit moves no money
+        if let Err(e) = self.collect_fee(record.client, fee) {
+            let credited = amount - fee.map_or(Decimal::ZERO, |(_, fee)| fee);
+            self.take_back(record.client, activity, credited, Decimal::ZERO, credited)?;
+            return Err(e);
         }
 
-        // Store transaction for potential disputes
+        // Store transaction for potential disputes (only after a successful deposit)
         self.transaction_store.store(
             record.tx,
             StoredTransaction {
                 client: record.client,
                 amount,
                 tx_type: record.tx_type,
-                under_dispute: false,
+                dispute_state: DisputeState::None,
+                disputes: 0,
             },
         );
 
-        // Update account balance
-        self.account_manager.update(record.client, |account| {
-            account.available = account
-                .available
-                .checked_add(amount)
-                .ok_or_else(|| PaymentError::arithmetic_overflow("deposit", record.client))?;
-            account.total = account
-                .total
-                .checked_add(amount)
-                .ok_or_else(|| PaymentError::arithmetic_overflow("deposit", record.client))?;
-            Ok(())
-        })
+        self.post(Posting::new(
+            record.tx_type,
+            record.client,
+            record.tx,
+            amount,
+        ));
+        self.post_fee(record.tx_type, record.client, record.tx, fee);
+        Ok(())
     }
 
     /// Process a withdrawal transaction
     ///
     /// This method processes a withdrawal by:
-    /// 1. Validating sufficient available funds
-    /// 2. Storing the transaction for potential future disputes
-    /// 3. Updating the account balance with checked arithmetic
+    /// 1. Checking the account is not locked, the transaction ID is new and
+    ///    the risk rules and limits, within a single access to the account
+    /// 2. Validating sufficient available funds and updating the account
+    ///    balance with checked arithmetic
+    /// 3. Storing the transaction for potential future disputes
+    ///
+    /// A withdrawal above `EngineConfig::withdrawal_approval_above` moves its
+    /// funds from available to held instead, and is stored as `Pending` until
+    /// an approve or reject record completes it.
     ///
     /// # Arguments
     ///
@@ -148,69 +283,145 @@ impl AsyncTransactionEngine {
     /// # Returns
     ///
     /// * `Ok(())` - If the withdrawal was processed successfully
+    /// * `Err(PaymentError::AccountLocked)` - If the account is locked
     /// * `Err(PaymentError::MissingAmount)` - If the amount field is missing
-    /// * `Err(PaymentError::InsufficientFunds)` - If available funds are insufficient
+    /// * `Err(PaymentError::DuplicateTransaction)` - If the transaction ID was already used
+    /// * `Err(PaymentError::WithdrawalBlocked)` - If the client fails the withdrawal requirements
+    /// * `Err(PaymentError::AmountLimitExceeded)` - If the amount is above the maximum withdrawal
+    /// * `Err(PaymentError::VelocityLimitExceeded)` - If the withdrawal exceeds the velocity limit
+    /// * `Err(PaymentError::InsufficientFunds)` - If available funds are insufficient,
+    ///   including for the withdrawal's fee
     /// * `Err(PaymentError::ArithmeticUnderflow)` - If the withdrawal would cause underflow
     pub fn process_withdrawal(
         &self,
         record: crate::types::TransactionRecord,
     ) -> Result<(), crate::types::PaymentError> {
-        // Extract amount or return error if missing
-        let amount = record
-            .amount
-            .ok_or_else(|| PaymentError::missing_amount("withdrawal", record.tx, record.client))?;
-
-        // Check for duplicate transaction ID
-        if self.transaction_store.get(record.tx).is_some() {
-            return Err(PaymentError::duplicate_transaction(
-                record.tx,
-                record.client,
-            ));
-        }
+        self.withdrawal(record, None)
+    }
 
-        // Capture values before the closure to avoid any potential issues
+    /// Process a withdrawal like `process_withdrawal`, counting it in `activity`
+    /// under the account access that applies or rejects it
+    fn withdrawal(
+        &self,
+        record: TransactionRecord,
+        activity: Option<&Activity>,
+    ) -> Result<(), PaymentError> {
+        // Capture values before the closures to avoid any potential issues
         let client = record.client;
         let tx = record.tx;
         let tx_type = record.tx_type;
 
-        // Update account balance with checked arithmetic and insufficient funds check
-        let update_result = self.account_manager.update(client, |account| {
-            // Check for insufficient funds before processing
-            if account.available < amount {
-                return Err(PaymentError::insufficient_funds(
-                    client,
-                    account.available,
-                    amount,
-                ));
+        // Check the lock and update the account balance in one map access
+        let (amount, fee) =
+            self.account_manager.update_unlocked(
+                client,
+                || {
+                    // Extract amount or return error if missing
+                    let amount = record
+                        .amount
+                        .ok_or_else(|| PaymentError::missing_amount("withdrawal", tx, client))?;
+
+                    // Check for duplicate transaction ID
+                    if self.transaction_store.contains(tx) {
+                        return Err(PaymentError::duplicate_transaction(tx, client));
+                    }
+
+                    // Apply risk rules and amount limits
+                    self.config.check_withdrawal(client)?;
+                    self.config.limits.check_withdrawal(tx, client, amount)?;
+                    if let Some(velocity) = &self.velocity {
+                        velocity
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .check_withdrawal(tx, client, amount)?;
+                    }
+                    let fee = self.config.fee(tx_type, client, amount)?;
+                    Ok((amount, fee))
+                },
+                |account, &(amount, fee)| {
+                    // Check for insufficient funds, including the fee, before processing
+                    let fee = fee.map_or(Decimal::ZERO, |(_, fee)| fee);
+                    let needed = amount
+                        .checked_add(fee)
+                        .ok_or_else(|| PaymentError::arithmetic_overflow("withdrawal", client))?;
+                    if account.available < needed {
+                        return Err(PaymentError::insufficient_funds(
+                            client,
+                            account.available,
+                            needed,
+                        ));
+                    }
+
+                    // Hold the funds of a withdrawal that needs approval
+                    if self.config.needs_approval(amount) {
+                        let available = account.available.checked_sub(needed).ok_or_else(|| {
+                            PaymentError::arithmetic_underflow("withdrawal", client)
+                        })?;
+                        let held = account.held.checked_add(amount).ok_or_else(|| {
+                            PaymentError::arithmetic_overflow("withdrawal", client)
+                        })?;
+                        let total = account.total.checked_sub(fee).ok_or_else(|| {
+                            PaymentError::arithmetic_underflow("withdrawal", client)
+                        })?;
+                        account.available = available;
+                        account.held = held;
+                        account.total = total;
+                        return Ok(());
+                    }
+
+                    let available = account
+                        .available
+                        .checked_sub(needed)
+                        .ok_or_else(|| PaymentError::arithmetic_underflow("withdrawal", client))?;
+                    let total = account
+                        .total
+                        .checked_sub(needed)
+                        .ok_or_else(|| PaymentError::arithmetic_underflow("withdrawal", client))?;
+                    account.available = available;
+                    account.total = total;
+
+                    Ok(())
+                },
+                |account, applied| Activity::count(activity, account, applied),
+            )?;
+
+        // If the fee account cannot take the fee, give the withdrawal back to
+        // the client's account, so the failed withdrawal moves no money
+        let pending = self.config.needs_approval(amount);
+        if let Err(e) = self.collect_fee(client, fee) {
+            let fee = fee.map_or(Decimal::ZERO, |(_, fee)| fee);
+            let debited = amount + fee;
+            if pending {
+                self.take_back(client, activity, -debited, amount, -fee)?;
+            } else {
+                self.take_back(client, activity, -debited, Decimal::ZERO, -debited)?;
             }
+            return Err(e);
+        }
 
-            account.available = account
-                .available
-                .checked_sub(amount)
-                .ok_or_else(|| PaymentError::arithmetic_underflow("withdrawal", client))?;
-
-            account.total = account
-                .total
-                .checked_sub(amount)
-                .ok_or_else(|| PaymentError::arithmetic_underflow("withdrawal", client))?;
-
-            Ok(())
-        });
-
-        // Only store transaction if update succeeded
-        update_result?;
-
-        // Store transaction for potential disputes (only after successful withdrawal)
+        // Store transaction for potential disputes (only after successful
+        // withdrawal), or for its approval
         self.transaction_store.store(
             tx,
             StoredTransaction {
                 client,
                 amount,
                 tx_type,
-                under_dispute: false,
+                dispute_state: if pending {
+                    DisputeState::Pending
+                } else {
+                    DisputeState::None
+                },
+                disputes: 0,
             },
         );
 
+        self.post(if pending {
+            Posting::pending_withdrawal(client, tx, amount)
+        } else {
+            Posting::new(tx_type, client, tx, amount)
+        });
+        self.post_fee(tx_type, client, tx, fee);
         Ok(())
     }
 
@@ -233,12 +444,29 @@ impl AsyncTransactionEngine {
     /// * `Err(PaymentError::TransactionNotFound)` - If the referenced transaction doesn't exist
     /// * `Err(PaymentError::ClientMismatch)` - If the client ID doesn't match
     /// * `Err(PaymentError::TransactionAlreadyDisputed)` - If the transaction is already disputed
+    /// * `Err(PaymentError::TransactionChargedBack)` - If the transaction has been charged back
+    /// * `Err(PaymentError::TransactionNotDisputable)` - If the transaction's type is not one
+    ///   of the configured disputable types
+    /// * `Err(PaymentError::RedisputeNotAllowed)` - If re-disputes are disabled
+    /// * `Err(PaymentError::RedisputeLimitReached)` - If the re-dispute limit has been used up
+    /// * `Err(PaymentError::InsufficientAvailableFunds)` - If available funds don't cover the
+    ///   disputed amount and the negative balance policy rejects debt
     /// * `Err(PaymentError::ArithmeticUnderflow)` - If moving funds would cause underflow
     /// * `Err(PaymentError::ArithmeticOverflow)` - If moving funds would cause overflow
     pub fn process_dispute(
         &self,
         record: crate::types::TransactionRecord,
     ) -> Result<(), crate::types::PaymentError> {
+        self.dispute(record, None)
+    }
+
+    /// Process a dispute like `process_dispute`, counting it in `activity`
+    /// under the account access that applies or rejects it
+    fn dispute(
+        &self,
+        record: TransactionRecord,
+        activity: Option<&Activity>,
+    ) -> Result<(), PaymentError> {
         // Get the referenced transaction
         let stored_tx = self
             .transaction_store
@@ -255,29 +483,50 @@ impl AsyncTransactionEngine {
             ));
         }
 
-        // Mark transaction as disputed (this will fail if already disputed)
-        self.transaction_store.update(record.tx, |tx| {
-            if tx.under_dispute {
-                return Err(PaymentError::transaction_already_disputed(
-                    record.tx, tx.client,
-                ));
-            }
-            tx.under_dispute = true;
-            Ok(())
-        })?;
+        // Verify the scheme allows disputing this type of transaction
+        self.config.disputable_types.check(record.tx, &stored_tx)?;
 
-        // Move funds from available to held
-        self.account_manager.update(record.client, |account| {
-            account.available = account
-                .available
-                .checked_sub(stored_tx.amount)
-                .ok_or_else(|| PaymentError::arithmetic_underflow("dispute", record.client))?;
-            account.held = account
-                .held
-                .checked_add(stored_tx.amount)
-                .ok_or_else(|| PaymentError::arithmetic_overflow("dispute", record.client))?;
-            Ok(())
-        })
+        self.update_counted(
+            record.tx,
+            record.client,
+            activity,
+            // Mark transaction as disputed (this will fail if already disputed or charged
+            // back, or if the re-dispute policy forbids disputing it again)
+            |tx| {
+                let disputed = tx
+                    .dispute_state
+                    .transition(record.tx_type, record.tx, tx.client)?;
+                self.config.redispute_policy.check(record.tx, tx)?;
+                tx.dispute_state = disputed;
+                tx.disputes = tx.disputes.saturating_add(1);
+                Ok(())
+            },
+            // Move funds from available to held, unless the negative balance policy forbids it
+            |account| {
+                self.config.negative_balance_policy.check_hold(
+                    record.client,
+                    account.available,
+                    stored_tx.amount,
+                )?;
+                account.available = account
+                    .available
+                    .checked_sub(stored_tx.amount)
+                    .ok_or_else(|| PaymentError::arithmetic_underflow("dispute", record.client))?;
+                account.held = account
+                    .held
+                    .checked_add(stored_tx.amount)
+                    .ok_or_else(|| PaymentError::arithmetic_overflow("dispute", record.client))?;
+                Ok(())
+            },
+        )?;
+
+        self.post(Posting::new(
+            record.tx_type,
+            record.client,
+            record.tx,
+            stored_tx.amount,
+        ));
+        Ok(())
     }
 
     /// Process a resolve transaction
@@ -286,8 +535,9 @@ impl AsyncTransactionEngine {
     /// 1. Validating the referenced transaction exists
     /// 2. Validating the client ID matches
     /// 3. Validating the transaction is currently disputed
-    /// 4. Marking the transaction as not disputed
-    /// 5. Moving funds from held back to available
+    /// 4. Marking the transaction as resolved
+    /// 5. Moving funds from held back to available, crediting interest on them
+    ///    if `EngineConfig::held_interest` is set
     ///
     /// # Arguments
     ///
@@ -299,12 +549,24 @@ impl AsyncTransactionEngine {
     /// * `Err(PaymentError::TransactionNotFound)` - If the referenced transaction doesn't exist
     /// * `Err(PaymentError::ClientMismatch)` - If the client ID doesn't match
     /// * `Err(PaymentError::TransactionNotDisputed)` - If the transaction is not disputed
+    /// * `Err(PaymentError::TransactionChargedBack)` - If the transaction has been charged back
     /// * `Err(PaymentError::ArithmeticUnderflow)` - If moving funds would cause underflow
-    /// * `Err(PaymentError::ArithmeticOverflow)` - If moving funds would cause overflow
+    /// * `Err(PaymentError::ArithmeticOverflow)` - If moving funds or crediting
+    ///   interest would cause overflow
     pub fn process_resolve(
         &self,
         record: crate::types::TransactionRecord,
     ) -> Result<(), crate::types::PaymentError> {
+        self.resolve(record, None)
+    }
+
+    /// Process a resolve like `process_resolve`, counting it in `activity`
+    /// under the account access that applies or rejects it
+    fn resolve(
+        &self,
+        record: TransactionRecord,
+        activity: Option<&Activity>,
+    ) -> Result<(), PaymentError> {
         // Get the referenced transaction
         let stored_tx = self
             .transaction_store
@@ -321,33 +583,61 @@ impl AsyncTransactionEngine {
             ));
         }
 
-        // Verify transaction is disputed
-        if !stored_tx.under_dispute {
-            return Err(PaymentError::transaction_not_disputed(
-                record.tx,
-                stored_tx.client,
-                "resolve",
-            ));
-        }
+        let interest = self
+            .config
+            .held_interest_on(stored_tx.amount)
+            .ok_or_else(|| PaymentError::arithmetic_overflow("resolve", record.client))?;
 
-        // Mark transaction as not disputed
-        self.transaction_store.update(record.tx, |tx| {
-            tx.under_dispute = false;
-            Ok(())
-        })?;
+        self.update_counted(
+            record.tx,
+            record.client,
+            activity,
+            // Mark transaction as resolved (this will fail if it is not disputed,
+            // checked under the transaction's lock so a racing resolve or
+            // chargeback cannot settle the same dispute)
+            |tx| {
+                tx.dispute_state =
+                    tx.dispute_state
+                        .transition(record.tx_type, record.tx, tx.client)?;
+                Ok(())
+            },
+            // Move funds from held back to available, with interest on them;
+            // every new balance is computed before any is assigned
+            |account| {
+                let held = account
+                    .held
+                    .checked_sub(stored_tx.amount)
+                    .ok_or_else(|| PaymentError::arithmetic_underflow("resolve", record.client))?;
+                let available = account
+                    .available
+                    .checked_add(stored_tx.amount)
+                    .and_then(|available| available.checked_add(interest))
+                    .ok_or_else(|| PaymentError::arithmetic_overflow("resolve", record.client))?;
+                let total = account
+                    .total
+                    .checked_add(interest)
+                    .ok_or_else(|| PaymentError::arithmetic_overflow("resolve", record.client))?;
+                account.held = held;
+                account.available = available;
+                account.total = total;
+                Ok(())
+            },
+        )?;
 
-        // Move funds from held back to available
-        self.account_manager.update(record.client, |account| {
-            account.held = account
-                .held
-                .checked_sub(stored_tx.amount)
-                .ok_or_else(|| PaymentError::arithmetic_underflow("resolve", record.client))?;
-            account.available = account
-                .available
-                .checked_add(stored_tx.amount)
-                .ok_or_else(|| PaymentError::arithmetic_overflow("resolve", record.client))?;
-            Ok(())
-        })
+        self.post(Posting::new(
+            record.tx_type,
+            record.client,
+            record.tx,
+            stored_tx.amount,
+        ));
+        if !interest.is_zero() {
+            self.flows
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record_interest(interest);
+            self.post(Posting::interest(record.client, record.tx, interest));
+        }
+        Ok(())
     }
 
     /// Process a chargeback transaction
@@ -355,9 +645,11 @@ impl AsyncTransactionEngine {
     /// This method processes a chargeback by:
     /// 1. Validating the referenced transaction exists
     /// 2. Validating the client ID matches
-    /// 3. Validating the transaction is currently disputed
+    /// 3. Validating the transaction is currently disputed, or disputing it
+    ///    first when direct chargebacks are allowed
     /// 4. Removing held funds and decreasing total
     /// 5. Locking the account
+    /// 6. Marking the transaction as charged back
     ///
     /// # Arguments
     ///
@@ -369,11 +661,22 @@ impl AsyncTransactionEngine {
     /// * `Err(PaymentError::TransactionNotFound)` - If the referenced transaction doesn't exist
     /// * `Err(PaymentError::ClientMismatch)` - If the client ID doesn't match
     /// * `Err(PaymentError::TransactionNotDisputed)` - If the transaction is not disputed
+    /// * `Err(PaymentError::TransactionChargedBack)` - If the transaction has been charged back
     /// * `Err(PaymentError::ArithmeticUnderflow)` - If removing funds would cause underflow
     pub fn process_chargeback(
         &self,
         record: crate::types::TransactionRecord,
     ) -> Result<(), crate::types::PaymentError> {
+        self.chargeback(record, None)
+    }
+
+    /// Process a chargeback like `process_chargeback`, counting it in `activity`
+    /// under the account access that applies or rejects it
+    fn chargeback(
+        &self,
+        record: TransactionRecord,
+        activity: Option<&Activity>,
+    ) -> Result<(), PaymentError> {
         // Get the referenced transaction
         let stored_tx = self
             .transaction_store
@@ -390,35 +693,400 @@ impl AsyncTransactionEngine {
             ));
         }
 
-        // Verify transaction is disputed
-        if !stored_tx.under_dispute {
-            return Err(PaymentError::transaction_not_disputed(
+        // In direct chargeback mode, dispute the transaction first
+        if self.config.needs_implicit_dispute(&stored_tx) {
+            self.dispute(
+                TransactionRecord {
+                    tx_type: TransactionType::Dispute,
+                    ..record.clone()
+                },
+                None,
+            )?;
+            return self.chargeback(record, activity);
+        }
+
+        self.update_counted(
+            record.tx,
+            record.client,
+            activity,
+            // Mark transaction as charged back so it can never be disputed again
+            // (this will fail if it is not disputed, checked under the
+            // transaction's lock like for resolves)
+            |tx| {
+                tx.dispute_state =
+                    tx.dispute_state
+                        .transition(record.tx_type, record.tx, tx.client)?;
+                Ok(())
+            },
+            // Remove held funds, decrease total, and lock account
+            |account| {
+                account.held = account.held.checked_sub(stored_tx.amount).ok_or_else(|| {
+                    PaymentError::arithmetic_underflow("chargeback", record.client)
+                })?;
+                account.total = account.total.checked_sub(stored_tx.amount).ok_or_else(|| {
+                    PaymentError::arithmetic_underflow("chargeback", record.client)
+                })?;
+                account.lock(LockReason::ChargebackTx(record.tx));
+                Ok(())
+            },
+        )?;
+
+        self.post(Posting::new(
+            record.tx_type,
+            record.client,
+            record.tx,
+            stored_tx.amount,
+        ));
+        Ok(())
+    }
+
+    /// Process an approve or reject transaction
+    ///
+    /// This method completes a withdrawal pending approval by:
+    /// 1. Validating the referenced transaction exists
+    /// 2. Validating the client ID matches
+    /// 3. Validating the transaction is pending approval
+    /// 4. Marking the withdrawal as approved or rejected
+    /// 5. Removing the held funds and decreasing total (approve), or moving
+    ///    them back to available (reject)
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The transaction record containing approve or reject details
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the approve or reject was processed successfully
+    /// * `Err(PaymentError::TransactionNotFound)` - If the referenced transaction doesn't exist
+    /// * `Err(PaymentError::ClientMismatch)` - If the client ID doesn't match
+    /// * `Err(PaymentError::WithdrawalNotPendingApproval)` - If the transaction is not pending
+    /// * `Err(PaymentError::WithdrawalRejected)` - If the withdrawal was already rejected
+    /// * `Err(PaymentError::ArithmeticUnderflow)` - If moving funds would cause underflow
+    /// * `Err(PaymentError::ArithmeticOverflow)` - If moving funds would cause overflow
+    pub fn process_approval(
+        &self,
+        record: crate::types::TransactionRecord,
+    ) -> Result<(), crate::types::PaymentError> {
+        self.approval(record, None)
+    }
+
+    /// Process an approve or reject like `process_approval`, counting it in `activity`
+    /// under the account access that applies or rejects it
+    fn approval(
+        &self,
+        record: TransactionRecord,
+        activity: Option<&Activity>,
+    ) -> Result<(), PaymentError> {
+        let approve = record.tx_type == crate::types::TransactionType::Approve;
+        let operation = if approve { "approve" } else { "reject" };
+
+        // Get the referenced transaction
+        let stored_tx = self
+            .transaction_store
+            .get(record.tx)
+            .ok_or_else(|| PaymentError::transaction_not_found(record.tx, operation))?;
+
+        // Verify client ID matches
+        if stored_tx.client != record.client {
+            return Err(PaymentError::client_mismatch(
                 record.tx,
                 stored_tx.client,
-                "chargeback",
+                record.client,
+                operation,
             ));
         }
 
-        // Remove held funds, decrease total, and lock account (atomic operation)
-        self.account_manager.update(record.client, |account| {
-            account.held = account
-                .held
-                .checked_sub(stored_tx.amount)
-                .ok_or_else(|| PaymentError::arithmetic_underflow("chargeback", record.client))?;
-            account.total = account
+        self.update_counted(
+            record.tx,
+            record.client,
+            activity,
+            // Mark the withdrawal approved or rejected (this will fail if it is
+            // not pending, checked under the transaction's lock)
+            |tx| {
+                tx.dispute_state =
+                    tx.dispute_state
+                        .transition(record.tx_type, record.tx, tx.client)?;
+                Ok(())
+            },
+            // Remove the held funds, or move them back to available
+            |account| {
+                account.held = account
+                    .held
+                    .checked_sub(stored_tx.amount)
+                    .ok_or_else(|| PaymentError::arithmetic_underflow(operation, record.client))?;
+                if approve {
+                    account.total =
+                        account.total.checked_sub(stored_tx.amount).ok_or_else(|| {
+                            PaymentError::arithmetic_underflow(operation, record.client)
+                        })?;
+                } else {
+                    account.available = account
+                        .available
+                        .checked_add(stored_tx.amount)
+                        .ok_or_else(|| {
+                            PaymentError::arithmetic_overflow(operation, record.client)
+                        })?;
+                }
+                Ok(())
+            },
+        )?;
+
+        self.post(Posting::new(
+            record.tx_type,
+            record.client,
+            record.tx,
+            stored_tx.amount,
+        ));
+        Ok(())
+    }
+
+    /// Process a batch of transactions, partitioned by client
+    ///
+    /// Processes the batch as the async strategy does (see
+    /// `BatchProcessor::process_batch`): each client's transactions run in
+    /// order on one tokio task, and different clients run concurrently. The
+    /// batch's records are applied to this engine's shared state, so batches
+    /// processed one after the other see each other's effects.
+    ///
+    /// Must be called within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch` - The transaction records to process
+    ///
+    /// # Returns
+    ///
+    /// One `ProcessingResult` per record. Results of one client keep their
+    /// input order; clients are not in input order (see
+    /// `process_batch_ordered`).
+    pub async fn process_batch(&self, batch: Vec<TransactionRecord>) -> Vec<ProcessingResult> {
+        BatchProcessor::new(Arc::new(self.clone()))
+            .process_batch(batch)
+            .await
+    }
+
+    /// Process a batch like `process_batch`, returning results in input order
+    ///
+    /// Must be called within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch` - The transaction records to process
+    ///
+    /// # Returns
+    ///
+    /// One slot per record, the slot at index `i` holding the outcome of the
+    /// record at index `i` of `batch`, or `None` if the record has no result
+    /// (see `BatchProcessor::process_batch_ordered`). Batches of an engine
+    /// are never cancelled, so a `None` slot means a client's task panicked.
+    pub async fn process_batch_ordered(
+        &self,
+        batch: Vec<TransactionRecord>,
+    ) -> Vec<Option<ProcessingResult>> {
+        BatchProcessor::new(Arc::new(self.clone()))
+            .process_batch_ordered(batch)
+            .await
+    }
+
+    /// Update a stored transaction and then its client's account, as one step
+    ///
+    /// Disputes, resolves and chargebacks each change a stored transaction's
+    /// dispute state and move funds on an account, in two map accesses. This
+    /// applies `mark` to the transaction, then `apply` to the account, and
+    /// undoes the first if the second fails, so a failed step never leaves a
+    /// transaction marked without its funds moved. Each closure is also all
+    /// or nothing: changes it made before returning an error are discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_id` - The stored transaction to update
+    /// * `client` - The client whose account to update
+    /// * `mark` - A closure that receives a mutable reference to the transaction
+    /// * `apply` - A closure that receives a mutable reference to the account
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If both closures succeeded
+    /// * `Err(PaymentError::TransactionNotFound)` - If the transaction doesn't exist
+    /// * `Err(...)` - The error of the closure that failed; neither the
+    ///   transaction nor the account is changed
+    ///
+    /// # Thread Safety
+    ///
+    /// The transaction and the account are locked one after the other, not
+    /// together. `mark` should check the transaction's state itself, as it
+    /// runs under the transaction's lock: then of two threads racing to mark
+    /// the same transaction, only one succeeds (see `tests/loom_tests.rs`).
+    /// Undoing `mark` restores the transaction as it was before, so no other
+    /// thread should update the same transaction meanwhile; the async
+    /// strategy guarantees this by processing each client's records in order
+    /// on one task.
+    pub fn update_transaction_and_account<M, A>(
+        &self,
+        tx_id: TransactionId,
+        client: ClientId,
+        mark: M,
+        apply: A,
+    ) -> Result<(), PaymentError>
+    where
+        M: FnOnce(&mut StoredTransaction) -> Result<(), PaymentError>,
+        A: FnOnce(&mut Account) -> Result<(), PaymentError>,
+    {
+        self.update_counted(tx_id, client, None, mark, apply)
+    }
+
+    /// Update a stored transaction and then its client's account like
+    /// `update_transaction_and_account`, counting `activity` in the account's
+    /// activity under the access that applies `apply` or undoes it
+    fn update_counted<M, A>(
+        &self,
+        tx_id: TransactionId,
+        client: ClientId,
+        activity: Option<&Activity>,
+        mark: M,
+        apply: A,
+    ) -> Result<(), PaymentError>
+    where
+        M: FnOnce(&mut StoredTransaction) -> Result<(), PaymentError>,
+        A: FnOnce(&mut Account) -> Result<(), PaymentError>,
+    {
+        let mut previous = None;
+        self.transaction_store.update(tx_id, |tx| {
+            let before = tx.clone();
+            mark(tx).inspect_err(|_| *tx = before.clone())?;
+            previous = Some(before);
+            Ok(())
+        })?;
+
+        let applied = self.account_manager.update(client, |account| {
+            let before = account.clone();
+            let result = apply(account).inspect_err(|_| *account = before);
+            Activity::count(activity, account, result.is_ok());
+            result
+        });
+
+        // Funds never moved, so put the transaction back as it was
+        if let Err(e) = applied {
+            if let Some(previous) = previous {
+                self.transaction_store.update(tx_id, |tx| {
+                    *tx = previous;
+                    Ok(())
+                })?;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Record the posting of an applied transaction, if the journal is enabled
+    fn post(&self, posting: Posting) {
+        if self.config.journal {
+            self.postings
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(posting);
+        }
+    }
+
+    /// Credit the fee charged to a client, if any, to the fee account
+    ///
+    /// Called once the fee has been debited from the client, after its entry
+    /// is released, so the two accounts are never locked together; if this
+    /// fails, the caller undoes the client's side with `take_back`. The fee
+    /// account is credited even if it is locked.
+    fn collect_fee(
+        &self,
+        client: ClientId,
+        fee: Option<(ClientId, Decimal)>,
+    ) -> Result<(), PaymentError> {
+        let Some((fee_account, fee)) = fee else {
+            return Ok(());
+        };
+        self.account_manager.update(fee_account, |account| {
+            let available = account
+                .available
+                .checked_add(fee)
+                .ok_or_else(|| PaymentError::arithmetic_overflow("fee", client))?;
+            let total = account
                 .total
-                .checked_sub(stored_tx.amount)
-                .ok_or_else(|| PaymentError::arithmetic_underflow("chargeback", record.client))?;
-            account.locked = true;
+                .checked_add(fee)
+                .ok_or_else(|| PaymentError::arithmetic_overflow("fee", client))?;
+            account.available = available;
+            account.total = total;
             Ok(())
         })
     }
 
+    /// Undo the balance changes of a deposit or withdrawal whose fee could not
+    /// be credited to the fee account
+    ///
+    /// `available`, `held` and `total` are the amounts the transaction added
+    /// to each balance (negative if it removed funds); they are subtracted in
+    /// one access to the account, rather than restoring the account as it
+    /// was, so changes made by other threads meanwhile are kept. The same
+    /// access counts the record in `activity` as rejected.
+    fn take_back(
+        &self,
+        client: ClientId,
+        activity: Option<&Activity>,
+        available: Decimal,
+        held: Decimal,
+        total: Decimal,
+    ) -> Result<(), PaymentError> {
+        self.account_manager.update(client, |account| {
+            let undo = |balance: Decimal, change: Decimal| {
+                balance
+                    .checked_sub(change)
+                    .ok_or_else(|| PaymentError::arithmetic_overflow("fee", client))
+            };
+            let (available, held, total) = (
+                undo(account.available, available)?,
+                undo(account.held, held)?,
+                undo(account.total, total)?,
+            );
+            account.available = available;
+            account.held = held;
+            account.total = total;
+            Activity::reject(activity, account);
+            Ok(())
+        })
+    }
+
+    /// Record the posting of the fee charged for a transaction, if any
+    fn post_fee(
+        &self,
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        fee: Option<(ClientId, Decimal)>,
+    ) {
+        if let Some((fee_account, fee)) = fee {
+            self.post(Posting::fee(tx_type, client, tx, fee_account, fee));
+        }
+    }
+
+    /// Take the postings of the transactions applied so far by this engine
+    /// and its clones
+    pub fn take_postings(&self) -> Vec<Posting> {
+        std::mem::take(&mut *self.postings.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Take the balance history samples recorded so far by this engine and
+    /// its clones
+    pub fn take_balance_history(&self) -> Vec<BalancePoint> {
+        self.history.as_ref().map_or_else(Vec::new, |history| {
+            history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+        })
+    }
+
     /// Process a transaction record by routing to the appropriate handler
     ///
-    /// This is the main entry point for processing transactions. It checks if the
-    /// account is locked and routes the transaction to the appropriate handler based
-    /// on the transaction type.
+    /// This is the main entry point for processing transactions. It routes the
+    /// transaction to the appropriate handler based on the transaction type;
+    /// deposits and withdrawals are rejected there if the account is locked.
     ///
     /// # Arguments
     ///
@@ -433,38 +1101,1087 @@ impl AsyncTransactionEngine {
         &self,
         record: crate::types::TransactionRecord,
     ) -> Result<(), crate::types::PaymentError> {
-        use crate::types::{PaymentError, TransactionType};
+        let activity = Activity::new(&record);
+        let client = record.client;
+        let result = self.apply(record, &activity);
+
+        // Count the record in the client's activity, accepted or not. Records
+        // reaching the account are counted by the access that applies or
+        // rejects them, so the two are seen together (and recounted if the
+        // change is undone); others on their own, except ignored duplicates
+        if !activity.done.get() {
+            self.account_manager.record_activity(
+                client,
+                activity.tx_type,
+                activity.tx,
+                result.is_ok(),
+            );
+        }
+        result
+    }
 
-        // Check if account is locked (except for dispute-related operations on locked accounts)
-        // Disputes, resolves, and chargebacks can be processed on locked accounts
+    /// Apply a record to the accounts and the stored transactions, and track
+    /// its effects (money flows, velocity, balance history)
+    fn apply(&self, record: TransactionRecord, activity: &Activity) -> Result<(), PaymentError> {
+        // Reused transaction IDs are skipped without an error if so configured
+        if self.config.duplicate_tx_policy == DuplicateTxPolicy::Ignore
+            && matches!(
+                record.tx_type,
+                TransactionType::Deposit | TransactionType::Withdrawal
+            )
+            && self.transaction_store.contains(record.tx)
+        {
+            activity.skip();
+            return Ok(());
+        }
+
+        // Route to appropriate handler. Deposits and withdrawals check the
+        // lock in the same map access as their update; disputes, resolves,
+        // chargebacks, approves and rejects can be processed on locked accounts
+        let (tx_type, client, tx, amount) =
+            (record.tx_type, record.client, record.tx, record.amount);
         match record.tx_type {
-            TransactionType::Deposit | TransactionType::Withdrawal => {
-                if self.account_manager.is_locked(record.client) {
-                    return Err(PaymentError::account_locked(record.client));
-                }
+            TransactionType::Deposit => self.deposit(record, Some(activity)),
+            TransactionType::Withdrawal => self.withdrawal(record, Some(activity)),
+            TransactionType::Dispute => self.dispute(record, Some(activity)),
+            TransactionType::Resolve => self.resolve(record, Some(activity)),
+            TransactionType::Chargeback => self.chargeback(record, Some(activity)),
+            TransactionType::Approve | TransactionType::Reject => {
+                self.approval(record, Some(activity))
+            }
+        }?;
+
+        // Track the money moved; a chargeback reverses the stored transaction,
+        // an approve completes it, and a pending withdrawal moves nothing yet
+        let moved = match tx_type {
+            TransactionType::Chargeback | TransactionType::Approve => {
+                self.transaction_store.get(tx).map(|tx| tx.amount)
             }
-            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
-                // These can be processed on locked accounts
+            TransactionType::Withdrawal
+                if amount.is_some_and(|amount| self.config.needs_approval(amount)) =>
+            {
+                None
             }
+            _ => amount,
+        };
+        if let Some(moved) = moved {
+            self.flows
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(tx_type, moved);
         }
 
-        // Route to appropriate handler
-        match record.tx_type {
-            TransactionType::Deposit => self.process_deposit(record),
-            TransactionType::Withdrawal => self.process_withdrawal(record),
-            TransactionType::Dispute => self.process_dispute(record),
-            TransactionType::Resolve => self.process_resolve(record),
-            TransactionType::Chargeback => self.process_chargeback(record),
+        // Count the transaction towards the client's velocity window
+        if let Some(velocity) = &self.velocity {
+            velocity
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(
+                    client,
+                    amount.filter(|_| tx_type == TransactionType::Withdrawal),
+                );
         }
+
+        // Sample the client's balances; no other task processes this client
+        // meanwhile, so the account is as this transaction left it
+        if let Some(history) = &self.history {
+            let account = self.account_manager.get_or_create(client);
+            history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(&account);
+        }
+        Ok(())
+    }
+}
+
+/// A record being processed, to count in its client's activity
+struct Activity {
+    /// The type of the record
+    tx_type: TransactionType,
+
+    /// The transaction ID of the record
+    tx: TransactionId,
+
+    /// Whether the record is settled: counted by an account access, or left
+    /// out as an ignored duplicate
+    done: Cell<bool>,
+}
+
+impl Activity {
+    fn new(record: &TransactionRecord) -> Self {
+        Self {
+            tx_type: record.tx_type,
+            tx: record.tx,
+            done: Cell::new(false),
+        }
+    }
+
+    /// Count the record in the account's activity, if it is being counted
+    fn count(activity: Option<&Self>, account: &mut Account, applied: bool) {
+        if let Some(activity) = activity {
+            account
+                .activity
+                .record(activity.tx_type, activity.tx, applied);
+            activity.done.set(true);
+        }
+    }
+
+    /// Count the record, counted as accepted by `count`, as rejected
+    fn reject(activity: Option<&Self>, account: &mut Account) {
+        if activity.is_some_and(|activity| activity.done.get()) {
+            account.activity.reject_last();
+        }
+    }
+
+    /// Leave the record out of the activity counters
+    fn skip(&self) {
+        self.done.set(true);
+    }
+}
+
+impl Engine for AsyncTransactionEngine {
+    fn process_transaction(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
+        AsyncTransactionEngine::process_transaction(self, record)
+    }
+
+    fn get_accounts(&self) -> Vec<Account> {
+        let mut accounts = self.account_manager.get_all_accounts();
+        accounts.sort_by_key(|account| account.client);
+        accounts
+    }
+
+    fn snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot::new(
+            self.account_manager.get_all_accounts(),
+            self.transaction_store.get_all_transactions(),
+        )
+    }
+
+    fn flows(&self) -> MoneyFlows {
+        *self.flows.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn take_postings(&mut self) -> Vec<Posting> {
+        AsyncTransactionEngine::take_postings(self)
+    }
+
+    fn take_balance_history(&mut self) -> Vec<BalancePoint> {
+        AsyncTransactionEngine::take_balance_history(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{TransactionRecord, TransactionType};
+    use crate::core::config::NegativeBalancePolicy;
+    use crate::core::fees::FeeSchedule;
+    use crate::types::TransactionType;
     use rust_decimal::Decimal;
 
+    /// Record without a line or source
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<Decimal>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount,
+            line: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_withdrawal_requirement_blocks_unverified_client() {
+        use crate::core::config::MetadataMap;
+        use crate::types::AccountMetadata;
+
+        let verified = AccountMetadata::from([("kyc_status".to_string(), "verified".to_string())]);
+        let config = EngineConfig::new()
+            .with_account_metadata(MetadataMap::from([(1, Arc::new(verified))]))
+            .with_withdrawal_requirement("kyc_status=verified".parse().unwrap());
+        let account_manager =
+            Arc::new(AsyncAccountManager::new().with_metadata(config.account_metadata.clone()));
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(config);
+
+        engine
+            .process_deposit(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process_deposit(record(
+                TransactionType::Deposit,
+                2,
+                2,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+
+        assert!(engine
+            .process_withdrawal(record(
+                TransactionType::Withdrawal,
+                1,
+                3,
+                Some(Decimal::new(10000, 4))
+            ))
+            .is_ok());
+        assert_eq!(
+            engine.process_withdrawal(record(
+                TransactionType::Withdrawal,
+                2,
+                4,
+                Some(Decimal::new(10000, 4))
+            )),
+            Err(PaymentError::withdrawal_blocked(
+                2,
+                "requires kyc_status=verified"
+            ))
+        );
+        assert!(engine.transaction_store.get(4).is_none());
+        assert_eq!(
+            account_manager.get_or_create(2).total,
+            Decimal::new(10000, 4)
+        );
+        assert!(account_manager.get_or_create(1).metadata.is_some());
+    }
+
+    #[test]
+    fn test_disputable_types_reject_withdrawal_disputes() {
+        use crate::core::config::DisputableTypes;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(EngineConfig::new().with_disputable_types(DisputableTypes::Deposits));
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 3, Some(Decimal::from(100))),
+            record(TransactionType::Withdrawal, 1, 2, Some(Decimal::from(40))),
+        ] {
+            engine.process_transaction(record).unwrap();
+        }
+        assert_eq!(
+            engine.process_transaction(record(TransactionType::Dispute, 1, 2, None)),
+            Err(PaymentError::transaction_not_disputable(2, 1, "withdrawal"))
+        );
+        assert_eq!(account_manager.get_or_create(1).held, Decimal::ZERO);
+        engine
+            .process_transaction(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        assert_eq!(account_manager.get_or_create(1).held, Decimal::from(100));
+    }
+
+    #[test]
+    fn test_amount_limits_reject_transactions() {
+        use crate::core::config::AmountLimits;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(EngineConfig::new().with_limits(AmountLimits {
+            max_deposit: Some(Decimal::from(100)),
+            max_withdrawal: Some(Decimal::from(20)),
+            max_total: Some(Decimal::from(150)),
+        }));
+        assert!(engine
+            .process_deposit(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100))
+            ))
+            .is_ok());
+        for (tx, amount) in [(2, 101), (3, 60)] {
+            assert!(engine
+                .process_deposit(record(
+                    TransactionType::Deposit,
+                    1,
+                    tx,
+                    Some(Decimal::from(amount))
+                ))
+                .unwrap_err()
+                .is_limit_exceeded());
+        }
+        assert!(engine
+            .process_withdrawal(record(
+                TransactionType::Withdrawal,
+                1,
+                4,
+                Some(Decimal::from(21))
+            ))
+            .unwrap_err()
+            .is_limit_exceeded());
+
+        assert_eq!(account_manager.get_or_create(1).total, Decimal::from(100));
+        assert!(engine.transaction_store.get(2).is_none());
+        assert!(engine.transaction_store.get(3).is_none());
+    }
+
+    #[test]
+    fn test_velocity_limit_rejects_excess_withdrawals() {
+        use crate::core::velocity::VelocityLimit;
+
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(
+            EngineConfig::new()
+                .with_velocity_limit(VelocityLimit::new(3).with_max_withdrawn(Decimal::from(50))),
+        );
+        for (tx_type, tx, amount) in [
+            (TransactionType::Deposit, 1, 100),
+            (TransactionType::Withdrawal, 2, 30),
+        ] {
+            assert!(engine
+                .process_transaction(record(tx_type, 1, tx, Some(Decimal::from(amount))))
+                .is_ok());
+        }
+        assert!(engine
+            .process_transaction(record(
+                TransactionType::Withdrawal,
+                1,
+                3,
+                Some(Decimal::from(30))
+            ))
+            .unwrap_err()
+            .is_limit_exceeded());
+        assert!(engine
+            .process_transaction(record(
+                TransactionType::Withdrawal,
+                1,
+                4,
+                Some(Decimal::from(20))
+            ))
+            .is_ok());
+
+        assert_eq!(account_manager.get_or_create(1).total, Decimal::from(50));
+    }
+
+    #[test]
+    fn test_charged_back_transaction_cannot_be_disputed_again() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        for (tx, amount) in [(1, 10000), (2, 5000)] {
+            engine
+                .process_transaction(record(
+                    TransactionType::Deposit,
+                    1,
+                    tx,
+                    Some(Decimal::new(amount, 4)),
+                ))
+                .unwrap();
+        }
+        engine
+            .process_transaction(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        engine
+            .process_transaction(record(TransactionType::Chargeback, 1, 1, None))
+            .unwrap();
+        assert_eq!(
+            engine.transaction_store.get(1).unwrap().dispute_state,
+            DisputeState::ChargedBack
+        );
+
+        // Disputes are allowed on locked accounts, but not on charged-back transactions
+        for tx_type in [
+            TransactionType::Dispute,
+            TransactionType::Resolve,
+            TransactionType::Chargeback,
+        ] {
+            assert!(matches!(
+                engine.process_transaction(record(tx_type, 1, 1, None)),
+                Err(PaymentError::TransactionChargedBack { tx: 1, .. })
+            ));
+        }
+
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.available, Decimal::new(5000, 4));
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::new(5000, 4));
+        assert!(account.locked);
+    }
+
+    #[rstest::rstest]
+    #[case::approve(TransactionType::Approve, 20, 80)]
+    #[case::reject(TransactionType::Reject, 100, 0)]
+    fn test_withdrawal_approval(
+        #[case] tx_type: TransactionType,
+        #[case] total: i64,
+        #[case] withdrawn: i64,
+    ) {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            account_manager.clone(),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(EngineConfig::new().with_withdrawal_approval_above(Decimal::from(50)));
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::from(80)),
+            ))
+            .unwrap();
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.available, Decimal::from(20));
+        assert_eq!(account.held, Decimal::from(80));
+        assert_eq!(account.total, Decimal::from(100));
+        assert!(matches!(
+            engine.process_transaction(record(TransactionType::Dispute, 1, 2, None)),
+            Err(PaymentError::WithdrawalPendingApproval { tx: 2, .. })
+        ));
+
+        engine
+            .process_transaction(record(tx_type, 1, 2, None))
+            .unwrap();
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::from(total));
+        assert_eq!(Engine::flows(&engine).withdrawn, Decimal::from(withdrawn));
+        assert!(matches!(
+            engine.process_transaction(record(TransactionType::Approve, 1, 2, None)),
+            Err(PaymentError::WithdrawalNotPendingApproval { .. }
+                | PaymentError::WithdrawalRejected { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fees_credited_to_fee_account() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            account_manager.clone(),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(
+            EngineConfig::new()
+                .with_fees(
+                    FeeSchedule::new(99)
+                        .with_deposit_fee("1%".parse().unwrap())
+                        .with_withdrawal_fee("0.6".parse().unwrap()),
+                )
+                .with_journal(true),
+        );
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::from(50)),
+            ))
+            .unwrap();
+        // 48.4 is left, which covers 48 but not its fee on top
+        assert!(matches!(
+            engine.process_transaction(record(
+                TransactionType::Withdrawal,
+                1,
+                3,
+                Some(Decimal::from(48))
+            )),
+            Err(PaymentError::InsufficientFunds { .. })
+        ));
+
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.available, Decimal::new(484, 1));
+        assert_eq!(account.total, Decimal::new(484, 1));
+        assert_eq!(account_manager.get_or_create(99).total, Decimal::new(16, 1));
+        let fees: Vec<Posting> = engine
+            .take_postings()
+            .into_iter()
+            .filter(Posting::is_fee)
+            .collect();
+        assert_eq!(
+            fees,
+            vec![
+                Posting::fee(TransactionType::Deposit, 1, 1, 99, Decimal::ONE),
+                Posting::fee(TransactionType::Withdrawal, 1, 2, 99, Decimal::new(6, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fee_account_overflow_moves_no_money() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine =
+            AsyncTransactionEngine::new(account_manager.clone(), Arc::clone(&transaction_store))
+                .with_config(
+                    EngineConfig::new().with_fees(
+                        FeeSchedule::new(99)
+                            .with_deposit_fee("5".parse().unwrap())
+                            .with_withdrawal_fee("5".parse().unwrap()),
+                    ),
+                );
+        // Fill the fee account up, so it cannot take any further fee
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                99,
+                2,
+                Some(Decimal::MAX - Decimal::from(5)),
+            ))
+            .unwrap();
+
+        for (tx_type, tx) in [
+            (TransactionType::Deposit, 3),
+            (TransactionType::Withdrawal, 4),
+        ] {
+            assert_eq!(
+                engine.process_transaction(record(tx_type, 1, tx, Some(Decimal::from(10)))),
+                Err(PaymentError::arithmetic_overflow("fee", 1))
+            );
+            assert!(transaction_store.get(tx).is_none());
+        }
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.available, Decimal::from(95));
+        assert_eq!(account.total, Decimal::from(95));
+        // Counted as accepted by the account access, then as rejected
+        assert_eq!(account.activity.transactions, 3);
+        assert_eq!(account.activity.errors, 2);
+        assert_eq!(account_manager.get_or_create(99).total, Decimal::MAX);
+    }
+
+    #[test]
+    fn test_ignored_duplicate_is_not_counted() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            account_manager.clone(),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(EngineConfig::new().with_duplicate_tx_policy(DuplicateTxPolicy::Ignore));
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(50)),
+            ))
+            .unwrap();
+
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.total, Decimal::from(100));
+        assert_eq!(account.activity.transactions, 1);
+    }
+
+    #[test]
+    fn test_resolve_credits_held_interest() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            account_manager.clone(),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(
+            EngineConfig::new()
+                .with_held_interest(Decimal::new(25, 3))
+                .with_journal(true),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Dispute, 1, 1, None),
+            record(TransactionType::Resolve, 1, 1, None),
+        ] {
+            engine.process_transaction(record).unwrap();
+        }
+
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.available, Decimal::new(1025, 1));
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::new(1025, 1));
+        assert_eq!(Engine::flows(&engine).expected_total(), account.total);
+        assert_eq!(
+            engine.take_postings().last(),
+            Some(&Posting::interest(1, 1, Decimal::new(25, 1)))
+        );
+    }
+
+    #[test]
+    fn test_redispute_limit_reached() {
+        use crate::core::config::RedisputePolicy;
+
+        let engine = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(EngineConfig::new().with_redispute_policy(RedisputePolicy::Limit(1)));
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        for _ in 0..2 {
+            engine
+                .process_transaction(record(TransactionType::Dispute, 1, 1, None))
+                .unwrap();
+            engine
+                .process_transaction(record(TransactionType::Resolve, 1, 1, None))
+                .unwrap();
+        }
+
+        assert_eq!(
+            engine.process_transaction(record(TransactionType::Dispute, 1, 1, None)),
+            Err(PaymentError::redispute_limit_reached(1, 1, 1))
+        );
+        let stored = engine.transaction_store.get(1).unwrap();
+        assert_eq!(stored.dispute_state, DisputeState::Resolved);
+        assert_eq!(stored.disputes, 2);
+    }
+
+    #[test]
+    fn test_direct_chargeback_disputes_first() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::clone(&transaction_store),
+        )
+        .with_config(EngineConfig::new().with_direct_chargeback(true));
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(TransactionType::Chargeback, 1, 1, None))
+            .unwrap();
+
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.available, Decimal::ZERO);
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::ZERO);
+        assert!(account.locked);
+        assert_eq!(account.lock_reason, Some(LockReason::ChargebackTx(1)));
+        // The implicit dispute is not counted as a record of its own
+        assert_eq!(account.activity.transactions, 2);
+        assert_eq!(account.activity.disputes, 0);
+        let stored = transaction_store.get(1).unwrap();
+        assert_eq!(stored.dispute_state, DisputeState::ChargedBack);
+        assert_eq!(stored.disputes, 1);
+    }
+
+    #[test]
+    fn test_journal_records_postings() {
+        let engine = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(
+            EngineConfig::new()
+                .with_direct_chargeback(true)
+                .with_journal(true),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(50))),
+            record(TransactionType::Withdrawal, 1, 3, Some(Decimal::from(30))),
+            record(TransactionType::Chargeback, 1, 1, None),
+        ] {
+            engine.process_transaction(record).unwrap();
+        }
+        // Rejected transactions post nothing
+        assert!(engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                4,
+                Some(Decimal::from(10))
+            ))
+            .is_err());
+
+        assert_eq!(
+            engine.take_postings(),
+            vec![
+                Posting::new(TransactionType::Deposit, 1, 1, Decimal::from(100)),
+                Posting::new(TransactionType::Deposit, 1, 2, Decimal::from(50)),
+                Posting::new(TransactionType::Withdrawal, 1, 3, Decimal::from(30)),
+                Posting::new(TransactionType::Dispute, 1, 1, Decimal::from(100)),
+                Posting::new(TransactionType::Chargeback, 1, 1, Decimal::from(100)),
+            ]
+        );
+        assert!(engine.take_postings().is_empty());
+    }
+
+    #[test]
+    fn test_balance_history_samples_each_client() {
+        let engine = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        )
+        .with_config(EngineConfig::new().with_balance_history(2));
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(10))),
+            record(TransactionType::Deposit, 2, 2, Some(Decimal::from(5))),
+            record(TransactionType::Deposit, 1, 3, Some(Decimal::from(20))),
+        ] {
+            engine.process_transaction(record).unwrap();
+        }
+
+        assert_eq!(
+            engine.take_balance_history(),
+            vec![BalancePoint {
+                client: 1,
+                transactions: 2,
+                available: Decimal::from(30),
+                held: Decimal::ZERO,
+                total: Decimal::from(30),
+                locked: false,
+            }]
+        );
+        assert!(engine.take_balance_history().is_empty());
+    }
+
+    #[rstest::rstest]
+    #[case::deposit(TransactionType::Deposit, 3, Some(Decimal::ONE))]
+    #[case::withdrawal(TransactionType::Withdrawal, 3, Some(Decimal::ONE))]
+    #[case::duplicate_id(TransactionType::Deposit, 1, Some(Decimal::ONE))]
+    #[case::missing_amount(TransactionType::Withdrawal, 3, None)]
+    fn test_locked_account_rejects_before_other_checks(
+        #[case] tx_type: TransactionType,
+        #[case] tx: TransactionId,
+        #[case] amount: Option<Decimal>,
+    ) {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(5))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(5))),
+            record(TransactionType::Dispute, 1, 2, None),
+            record(TransactionType::Chargeback, 1, 2, None),
+        ] {
+            engine.process_transaction(record).unwrap();
+        }
+
+        assert_eq!(
+            engine.process_transaction(record(tx_type, 1, tx, amount)),
+            Err(PaymentError::account_locked(1))
+        );
+        let account = account_manager.get_or_create(1);
+        assert_eq!(account.total, Decimal::from(5));
+        assert_eq!(account.activity.transactions, 5);
+        assert_eq!(account.activity.errors, 1);
+    }
+
+    #[rstest::rstest]
+    #[case::duplicate_id(TransactionType::Withdrawal, 1, Some(Decimal::ONE))]
+    #[case::missing_amount(TransactionType::Deposit, 2, None)]
+    fn test_rejected_record_creates_no_account(
+        #[case] tx_type: TransactionType,
+        #[case] tx: TransactionId,
+        #[case] amount: Option<Decimal>,
+    ) {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        engine
+            .process_transaction(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+
+        assert!(engine
+            .process_transaction(TransactionRecord {
+                tx_type,
+                client: 2,
+                tx,
+                amount,
+                line: None,
+                source: None,
+            })
+            .is_err());
+        assert_eq!(account_manager.get_all_accounts().len(), 1);
+    }
+
+    #[rstest::rstest]
+    #[case::resolve(
+        TransactionType::Resolve,
+        |account: &mut Account| account.available = Decimal::MAX,
+        PaymentError::arithmetic_overflow("resolve", 1)
+    )]
+    #[case::chargeback(
+        TransactionType::Chargeback,
+        |account: &mut Account| account.total = Decimal::MIN,
+        PaymentError::arithmetic_underflow("chargeback", 1)
+    )]
+    fn test_failed_balance_move_keeps_transaction_disputed(
+        #[case] tx_type: TransactionType,
+        #[case] corrupt: fn(&mut Account),
+        #[case] error: PaymentError,
+    ) {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::clone(&transaction_store),
+        );
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(5)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        account_manager
+            .update(1, |account| {
+                corrupt(account);
+                Ok(())
+            })
+            .unwrap();
+        let mut before = account_manager.get_or_create(1);
+
+        assert_eq!(
+            engine.process_transaction(record(tx_type, 1, 1, None)),
+            Err(error)
+        );
+        // Only the rejected record is counted
+        before.activity.record(tx_type, 1, false);
+        assert_eq!(account_manager.get_or_create(1), before);
+        assert_eq!(
+            transaction_store.get(1).unwrap().dispute_state,
+            DisputeState::Disputed
+        );
+    }
+
+    #[test]
+    fn test_update_transaction_and_account_discards_failed_mark() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::clone(&transaction_store),
+        );
+        engine
+            .process_transaction(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::from(5)),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+
+        let result = engine.update_transaction_and_account(
+            1,
+            1,
+            |tx| {
+                tx.disputes = 7;
+                Err(PaymentError::transaction_not_disputed(1, 1, "test"))
+            },
+            |_| panic!("account updated after a failed mark"),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(transaction_store.get(1).unwrap().disputes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_process_batch() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        let batch = vec![
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(10))),
+            record(TransactionType::Deposit, 2, 2, Some(Decimal::from(5))),
+            record(TransactionType::Withdrawal, 1, 3, Some(Decimal::from(4))),
+            record(TransactionType::Withdrawal, 2, 4, Some(Decimal::from(6))),
+        ];
+
+        let results = engine.process_batch(batch.clone()).await;
+        assert_eq!(results.len(), 4);
+        let rejected: Vec<_> = results
+            .iter()
+            .filter(|result| result.result.is_err())
+            .map(|result| result.record.tx)
+            .collect();
+        assert_eq!(rejected, vec![4]);
+
+        // Replayed on a fresh engine, the results follow the input order
+        let fresh = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        let ordered: Vec<_> = fresh
+            .process_batch_ordered(batch)
+            .await
+            .into_iter()
+            .map(|result| {
+                let result = result.unwrap();
+                (result.record.tx, result.result.is_ok())
+            })
+            .collect();
+        assert_eq!(ordered, vec![(1, true), (2, true), (3, true), (4, false)]);
+
+        // A later batch sees the state left by the first
+        let results = engine
+            .process_batch(vec![record(
+                TransactionType::Withdrawal,
+                1,
+                5,
+                Some(Decimal::from(6)),
+            )])
+            .await;
+        assert!(results[0].result.is_ok());
+        assert_eq!(account_manager.get_or_create(1).available, Decimal::ZERO);
+        assert_eq!(account_manager.get_or_create(2).available, Decimal::from(5));
+    }
+
+    #[test]
+    fn test_chargeback_without_dispute_rejected_by_default() {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            engine.process_transaction(record(TransactionType::Chargeback, 1, 1, None)),
+            Err(PaymentError::transaction_not_disputed(1, 1, "chargeback"))
+        );
+        assert!(!account_manager.get_or_create(1).locked);
+    }
+
+    #[rstest::rstest]
+    #[case::reject(NegativeBalancePolicy::Reject, false)]
+    #[case::allow_debt(NegativeBalancePolicy::AllowDebt, true)]
+    fn test_dispute_of_withdrawn_deposit(
+        #[case] policy: NegativeBalancePolicy,
+        #[case] disputed: bool,
+    ) {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            Arc::clone(&transaction_store),
+        )
+        .with_config(EngineConfig::new().with_negative_balance_policy(policy));
+        engine
+            .process_transaction(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process_transaction(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::new(8000, 4)),
+            ))
+            .unwrap();
+
+        let result = engine.process_transaction(record(TransactionType::Dispute, 1, 1, None));
+
+        let account = account_manager.get_or_create(1);
+        let stored = transaction_store.get(1).unwrap();
+        assert_eq!(account.total, Decimal::new(2000, 4));
+        if disputed {
+            assert!(result.is_ok());
+            assert_eq!(account.available, Decimal::new(-8000, 4));
+            assert_eq!(account.held, Decimal::new(10000, 4));
+            assert_eq!(stored.dispute_state, DisputeState::Disputed);
+            assert_eq!(stored.disputes, 1);
+        } else {
+            assert_eq!(
+                result,
+                Err(PaymentError::insufficient_available_funds(
+                    1,
+                    Decimal::new(2000, 4),
+                    Decimal::new(10000, 4),
+                    "dispute"
+                ))
+            );
+            assert_eq!(account.available, Decimal::new(2000, 4));
+            assert_eq!(account.held, Decimal::ZERO);
+            assert_eq!(stored.dispute_state, DisputeState::None);
+            assert_eq!(stored.disputes, 0);
+        }
+    }
+
     #[test]
     fn test_new_creates_engine() {
         let account_manager = Arc::new(AsyncAccountManager::new());
@@ -543,6 +2260,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_deposit(record);
@@ -562,7 +2281,7 @@ mod tests {
         assert_eq!(stored_tx.client, 1);
         assert_eq!(stored_tx.amount, Decimal::new(10000, 4));
         assert_eq!(stored_tx.tx_type, TransactionType::Deposit);
-        assert!(!stored_tx.under_dispute);
+        assert!(!stored_tx.dispute_state.is_disputed());
     }
 
     #[test]
@@ -579,6 +2298,8 @@ mod tests {
             client: 42,
             tx: 1,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_deposit(record);
@@ -605,6 +2326,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None, // Missing amount
+            line: None,
+            source: None,
         };
 
         let result = engine.process_deposit(record);
@@ -647,6 +2370,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(record1).unwrap();
 
@@ -656,6 +2381,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(record2).unwrap();
 
@@ -684,6 +2411,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(record1).unwrap();
 
@@ -693,6 +2422,8 @@ mod tests {
             client: 2,
             tx: 2,
             amount: Some(Decimal::new(20000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(record2).unwrap();
 
@@ -728,6 +2459,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(1, 0)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_deposit(record);
@@ -761,14 +2494,16 @@ mod tests {
         let mut handles = vec![];
 
         // Spawn 10 threads, each depositing to a different account
-        for i in 0u16..10 {
+        for i in 0..10 {
             let engine_clone = engine.clone();
             let handle = thread::spawn(move || {
                 let record = TransactionRecord {
                     tx_type: TransactionType::Deposit,
                     client: i,
-                    tx: i as u32,
+                    tx: i as TransactionId,
                     amount: Some(Decimal::new((i as i64 + 1) * 1000, 4)),
+                    line: None,
+                    source: None,
                 };
                 engine_clone.process_deposit(record).unwrap();
             });
@@ -781,7 +2516,7 @@ mod tests {
         }
 
         // Verify all accounts have correct balances
-        for i in 0u16..10 {
+        for i in 0..10 {
             let account = account_manager.get_or_create(i);
             let expected = Decimal::new((i as i64 + 1) * 1000, 4);
             assert_eq!(account.available, expected);
@@ -803,7 +2538,7 @@ mod tests {
         let mut handles = vec![];
 
         // Spawn 100 threads, all depositing to the same account
-        for i in 0u32..100 {
+        for i in 0u64..100 {
             let engine_clone = engine.clone();
             let handle = thread::spawn(move || {
                 let record = TransactionRecord {
@@ -811,6 +2546,8 @@ mod tests {
                     client: 1,
                     tx: i,
                     amount: Some(Decimal::new(100, 4)),
+                    line: None,
+                    source: None,
                 };
                 engine_clone.process_deposit(record).unwrap();
             });
@@ -828,7 +2565,7 @@ mod tests {
         assert_eq!(account.total, Decimal::new(10000, 4));
 
         // Verify all transactions were stored
-        for i in 0u32..100 {
+        for i in 0u64..100 {
             assert!(transaction_store.get(i).is_some());
         }
     }
@@ -848,6 +2585,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit).unwrap();
 
@@ -857,6 +2596,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_withdrawal(withdrawal);
@@ -876,7 +2617,7 @@ mod tests {
         assert_eq!(stored_tx.client, 1);
         assert_eq!(stored_tx.amount, Decimal::new(5000, 4));
         assert_eq!(stored_tx.tx_type, TransactionType::Withdrawal);
-        assert!(!stored_tx.under_dispute);
+        assert!(!stored_tx.dispute_state.is_disputed());
     }
 
     #[test]
@@ -894,6 +2635,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit).unwrap();
 
@@ -903,6 +2646,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_withdrawal(withdrawal);
@@ -944,6 +2689,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None, // Missing amount
+            line: None,
+            source: None,
         };
 
         let result = engine.process_withdrawal(withdrawal);
@@ -986,6 +2733,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_withdrawal(withdrawal);
@@ -1020,6 +2769,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit).unwrap();
 
@@ -1029,6 +2780,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(3000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_withdrawal(withdrawal1).unwrap();
 
@@ -1038,6 +2791,8 @@ mod tests {
             client: 1,
             tx: 3,
             amount: Some(Decimal::new(2000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_withdrawal(withdrawal2).unwrap();
 
@@ -1066,6 +2821,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit1).unwrap();
 
@@ -1074,6 +2831,8 @@ mod tests {
             client: 2,
             tx: 2,
             amount: Some(Decimal::new(20000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit2).unwrap();
 
@@ -1083,6 +2842,8 @@ mod tests {
             client: 1,
             tx: 3,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_withdrawal(withdrawal1).unwrap();
 
@@ -1091,6 +2852,8 @@ mod tests {
             client: 2,
             tx: 4,
             amount: Some(Decimal::new(8000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_withdrawal(withdrawal2).unwrap();
 
@@ -1117,6 +2880,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit).unwrap();
 
@@ -1125,6 +2890,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
 
         let result = engine.process_withdrawal(withdrawal);
@@ -1143,12 +2910,14 @@ mod tests {
         );
 
         // Deposit to 10 different accounts
-        for i in 0u16..10 {
+        for i in 0..10 {
             let deposit = TransactionRecord {
                 tx_type: TransactionType::Deposit,
                 client: i,
-                tx: i as u32,
+                tx: i as TransactionId,
                 amount: Some(Decimal::new((i as i64 + 1) * 10000, 4)),
+                line: None,
+                source: None,
             };
             engine.process_deposit(deposit).unwrap();
         }
@@ -1156,14 +2925,16 @@ mod tests {
         let mut handles = vec![];
 
         // Spawn 10 threads, each withdrawing from a different account
-        for i in 0u16..10 {
+        for i in 0..10 {
             let engine_clone = engine.clone();
             let handle = thread::spawn(move || {
                 let withdrawal = TransactionRecord {
                     tx_type: TransactionType::Withdrawal,
                     client: i,
-                    tx: (i as u32) + 100,
+                    tx: (i as TransactionId) + 100,
                     amount: Some(Decimal::new((i as i64 + 1) * 5000, 4)),
+                    line: None,
+                    source: None,
                 };
                 engine_clone.process_withdrawal(withdrawal).unwrap();
             });
@@ -1176,7 +2947,7 @@ mod tests {
         }
 
         // Verify all accounts have correct balances (half withdrawn)
-        for i in 0u16..10 {
+        for i in 0..10 {
             let account = account_manager.get_or_create(i);
             let expected = Decimal::new((i as i64 + 1) * 5000, 4);
             assert_eq!(account.available, expected);
@@ -1201,13 +2972,15 @@ mod tests {
             client: 1,
             tx: 0,
             amount: Some(Decimal::new(50000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit).unwrap();
 
         let mut handles = vec![];
 
         // Spawn 50 threads, all withdrawing from the same account
-        for i in 1u32..=50 {
+        for i in 1u64..=50 {
             let engine_clone = engine.clone();
             let handle = thread::spawn(move || {
                 let withdrawal = TransactionRecord {
@@ -1215,6 +2988,8 @@ mod tests {
                     client: 1,
                     tx: i,
                     amount: Some(Decimal::new(1000, 4)),
+                    line: None,
+                    source: None,
                 };
                 engine_clone.process_withdrawal(withdrawal)
             });
@@ -1241,7 +3016,7 @@ mod tests {
         assert_eq!(account.total, Decimal::ZERO);
 
         // Verify all successful transactions were stored
-        let stored_count = (1u32..=50)
+        let stored_count = (1u64..=50)
             .filter(|&i| transaction_store.get(i).is_some())
             .count();
         assert_eq!(stored_count, 50);
@@ -1264,13 +3039,15 @@ mod tests {
             client: 1,
             tx: 0,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         };
         engine.process_deposit(deposit).unwrap();
 
         let mut handles = vec![];
 
         // Spawn 20 threads, all trying to withdraw 0.1000 (total would be 2.0000)
-        for i in 1u32..=20 {
+        for i in 1u64..=20 {
             let engine_clone = engine.clone();
             let handle = thread::spawn(move || {
                 let withdrawal = TransactionRecord {
@@ -1278,6 +3055,8 @@ mod tests {
                     client: 1,
                     tx: i,
                     amount: Some(Decimal::new(1000, 4)), // 0.1000 each
+                    line: None,
+                    source: None,
                 };
                 engine_clone.process_withdrawal(withdrawal)
             });
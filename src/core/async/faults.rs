@@ -0,0 +1,237 @@
+//! Fault injection for testing the async pipeline (feature `fault-injection`)
+//!
+//! The engine's stores never fail today, so the pipeline's handling of
+//! transient errors and slow updates cannot be exercised with real inputs.
+//! `FaultInjection` makes the `BatchProcessor` misbehave on purpose:
+//!
+//! - delays: a share of the transactions sleep for a random time up to a
+//!   maximum before being processed, shuffling the order in which clients'
+//!   partitions finish
+//! - errors: a share of the processing attempts fail with a transient
+//!   `IoError` instead of reaching the engine, leaving the state unchanged,
+//!   so the retry policy has something to retry
+//!
+//! Whether an attempt is delayed or fails is a function of the seed, the
+//! transaction ID and the attempt number alone, so a run with the same seed
+//! injects the same faults however its tasks are scheduled.
+
+use super::duplicate_filter::mix;
+use crate::types::{PaymentError, TransactionRecord};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Salt separating the draws of delays from the draws of errors
+const DELAY_SALT: u64 = 0x6465_6c61_7973;
+
+/// Faults injected into the async pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaultInjection {
+    /// Share of processing attempts failing with a transient error (0 to 1)
+    pub error_rate: f64,
+    /// Share of transactions delayed before processing (0 to 1)
+    pub delay_rate: f64,
+    /// Longest injected delay
+    pub max_delay: Duration,
+    /// Seed of the pseudo-random draws
+    pub seed: u64,
+}
+
+impl FaultInjection {
+    /// Inject no faults yet, drawing them from `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            error_rate: 0.0,
+            delay_rate: 0.0,
+            max_delay: Duration::ZERO,
+            seed,
+        }
+    }
+
+    /// Fail a share `rate` of the processing attempts
+    pub fn with_errors(mut self, rate: f64) -> Self {
+        self.error_rate = rate;
+        self
+    }
+
+    /// Delay a share `rate` of the transactions by up to `max_delay`
+    pub fn with_delays(mut self, rate: f64, max_delay: Duration) -> Self {
+        self.delay_rate = rate;
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Uniform draw in `[0, 1)` for a transaction and a salt
+    fn draw(&self, record: &TransactionRecord, salt: u64) -> f64 {
+        let bits = mix(self.seed ^ mix(record.tx ^ mix(salt)));
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Delay to inject before processing a transaction, if any
+    pub fn delay(&self, record: &TransactionRecord) -> Option<Duration> {
+        if self.draw(record, DELAY_SALT) >= self.delay_rate {
+            return None;
+        }
+        Some(self.max_delay.mul_f64(self.draw(record, !DELAY_SALT)))
+    }
+
+    /// Error to inject instead of the attempt number `attempt` (counting
+    /// from 1) at processing a transaction, if any
+    pub fn error(&self, record: &TransactionRecord, attempt: u32) -> Option<PaymentError> {
+        (self.draw(record, u64::from(attempt)) < self.error_rate).then(|| PaymentError::IoError {
+            message: format!(
+                "injected fault processing transaction {} (attempt {})",
+                record.tx, attempt
+            ),
+        })
+    }
+}
+
+impl fmt::Display for FaultInjection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "errors={},delays={},max-delay-ms={},seed={}",
+            self.error_rate * 100.0,
+            self.delay_rate * 100.0,
+            self.max_delay.as_millis(),
+            self.seed
+        )
+    }
+}
+
+/// Parses `errors=PERCENT,delays=PERCENT,max-delay-ms=MS,seed=N`, where
+/// every setting is optional, e.g. `errors=5,delays=20,max-delay-ms=2`
+impl FromStr for FaultInjection {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut faults = FaultInjection::new(0);
+        for setting in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (name, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", setting))?;
+            let invalid = |e: &dyn fmt::Display| format!("invalid {} '{}': {}", name, value, e);
+            let percent = || -> Result<f64, String> {
+                match value.parse::<f64>() {
+                    Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent / 100.0),
+                    Ok(_) => Err(invalid(&"must be between 0 and 100")),
+                    Err(e) => Err(invalid(&e)),
+                }
+            };
+            match name {
+                "errors" => faults.error_rate = percent()?,
+                "delays" => faults.delay_rate = percent()?,
+                "max-delay-ms" => {
+                    faults.max_delay =
+                        Duration::from_millis(value.parse().map_err(|e| invalid(&e))?)
+                }
+                "seed" => faults.seed = value.parse().map_err(|e| invalid(&e))?,
+                _ => {
+                    return Err(format!(
+                        "unknown setting '{}' (expected errors, delays, max-delay-ms or seed)",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(faults)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TransactionType;
+    use rstest::rstest;
+
+    fn record(tx: u64) -> TransactionRecord {
+        TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            tx,
+            amount: None,
+            line: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_faults_follow_rates() {
+        let faults = FaultInjection::new(7)
+            .with_errors(0.25)
+            .with_delays(0.5, Duration::from_millis(10));
+
+        let errors = (0..10_000)
+            .filter(|&tx| faults.error(&record(tx), 1).is_some())
+            .count();
+        let delays: Vec<Duration> = (0..10_000)
+            .filter_map(|tx| faults.delay(&record(tx)))
+            .collect();
+
+        assert!((2_300..2_700).contains(&errors), "{}", errors);
+        assert!((4_800..5_200).contains(&delays.len()), "{}", delays.len());
+        assert!(delays
+            .iter()
+            .all(|delay| *delay < Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_faults_are_deterministic_per_attempt() {
+        let faults = FaultInjection::new(7).with_errors(0.5);
+        let failing = (0..100)
+            .find(|&tx| faults.error(&record(tx), 1).is_some())
+            .unwrap();
+
+        let error = faults.error(&record(failing), 1).unwrap();
+        assert!(error.is_transient());
+        assert_eq!(faults.error(&record(failing), 1), Some(error));
+        // Later attempts draw again, so retries eventually get through
+        assert!((2..50).any(|attempt| faults.error(&record(failing), attempt).is_none()));
+        assert_ne!(
+            (0..100)
+                .map(|tx| faults.error(&record(tx), 1).is_some())
+                .collect::<Vec<_>>(),
+            (0..100)
+                .map(|tx| FaultInjection::new(8)
+                    .with_errors(0.5)
+                    .error(&record(tx), 1)
+                    .is_some())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_no_faults_by_default() {
+        let faults = FaultInjection::new(7);
+        assert!((0..1000).all(
+            |tx| faults.error(&record(tx), 1).is_none() && faults.delay(&record(tx)).is_none()
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        let faults: FaultInjection = "errors=5, delays=20,max-delay-ms=2,seed=42"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            faults,
+            FaultInjection::new(42)
+                .with_errors(0.05)
+                .with_delays(0.2, Duration::from_millis(2))
+        );
+        assert_eq!(faults.to_string().parse::<FaultInjection>(), Ok(faults));
+        assert_eq!("".parse::<FaultInjection>(), Ok(FaultInjection::new(0)));
+    }
+
+    #[rstest]
+    #[case::no_value("errors", "expected NAME=VALUE")]
+    #[case::out_of_range("errors=150", "between 0 and 100")]
+    #[case::not_a_number("delays=x", "invalid delays 'x'")]
+    #[case::unknown("panics=1", "unknown setting 'panics'")]
+    fn test_parse_invalid(#[case] spec: &str, #[case] expected: &str) {
+        let err = spec.parse::<FaultInjection>().unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+}
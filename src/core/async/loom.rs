@@ -0,0 +1,55 @@
+//! Shims for model checking the async components with loom
+//!
+//! Loom runs a test once for every interleaving of its threads, switching
+//! threads only where they use loom's synchronization primitives. The account
+//! and transaction maps lock with DashMap's own primitives, which loom cannot
+//! see, so each map owns an `AccessPoint` and reaches it before every access:
+//!
+//! - normally it is empty and compiles away
+//! - built with `--cfg payments_loom` it is a loom atomic, so loom may
+//!   switch threads before every map access
+//!
+//! One map access holds a shard lock only for its own duration and never
+//! touches loom, so running it without switching is what the lock ensures
+//! anyway. Every order of the accesses of concurrent transactions (e.g. a
+//! withdrawal between a dispute marking its transaction and holding its
+//! funds) is explored; see `tests/loom_tests.rs`.
+//!
+//! An access made while another map's entry is held (the duplicate check in
+//! `AsyncTransactionStore::contains`) reaches no point, and guards returned
+//! by the maps (`AccountRef`) must not be held across another access in a
+//! loom test: a switch while holding a lock loom does not know about could
+//! block the next thread on it for good.
+
+/// Point where loom may switch threads before a map access
+#[cfg(not(payments_loom))]
+#[derive(Debug)]
+pub(crate) struct AccessPoint;
+
+#[cfg(not(payments_loom))]
+impl AccessPoint {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+
+    /// Reach the point before accessing the map
+    #[inline(always)]
+    pub(crate) fn reach(&self) {}
+}
+
+/// Point where loom may switch threads before a map access
+#[cfg(payments_loom)]
+#[derive(Debug)]
+pub(crate) struct AccessPoint(loom::sync::atomic::AtomicUsize);
+
+#[cfg(payments_loom)]
+impl AccessPoint {
+    pub(crate) fn new() -> Self {
+        Self(loom::sync::atomic::AtomicUsize::new(0))
+    }
+
+    /// Reach the point before accessing the map
+    pub(crate) fn reach(&self) {
+        self.0.fetch_add(1, loom::sync::atomic::Ordering::SeqCst);
+    }
+}
@@ -11,6 +11,13 @@
 //! - **AsyncAccountManager**: Thread-safe account state management using DashMap
 //! - **AsyncTransactionStore**: Thread-safe transaction history using DashMap
 //! - **AsyncTransactionEngine**: Orchestrates async transaction processing
+//! - **BatchPipeline**: Overlaps batches while preserving per-client ordering,
+//!   reporting batches that miss a `BatchDeadline`
+//! - **DuplicateFilter**: Bloom filter in front of the duplicate transaction check
+//! - **CancellationToken**: Stops a `BatchProcessor`, and the pipeline and
+//!   async strategy built on it, between transactions
+//! - **FaultInjection**: Delays and transient errors injected for testing
+//!   (feature `fault-injection`)
 //!
 //! # Thread Safety
 //!
@@ -18,13 +25,28 @@
 //! - Operations on different accounts/transactions proceed in parallel
 //! - Operations on the same account/transaction are properly synchronized
 //! - No global locks - fine-grained locking per entity
+//!
+//! Built with `--cfg payments_loom`, every access to the account and
+//! transaction maps is a point where loom may switch threads, so
+//! `tests/loom_tests.rs` can check every interleaving of concurrent
+//! transactions (see the `loom` module).
 
 pub mod account_manager;
 pub mod batch_processor;
+pub mod duplicate_filter;
 pub mod engine;
+#[cfg(feature = "fault-injection")]
+pub mod faults;
+mod loom;
+pub mod pipeline;
 pub mod transaction_store;
 
-pub use account_manager::AsyncAccountManager;
+pub use account_manager::{AccountRef, AsyncAccountManager};
 pub use batch_processor::BatchProcessor;
+pub use duplicate_filter::DuplicateFilter;
 pub use engine::AsyncTransactionEngine;
+#[cfg(feature = "fault-injection")]
+pub use faults::FaultInjection;
+pub use pipeline::{BatchDeadline, BatchPipeline, DeadlinePolicy};
+pub use tokio_util::sync::CancellationToken;
 pub use transaction_store::AsyncTransactionStore;
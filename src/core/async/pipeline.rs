@@ -0,0 +1,648 @@
+//! Cross-batch pipelining keyed by client
+//!
+//! This module provides the `BatchPipeline`, which lets consecutive batches
+//! overlap instead of waiting for each batch to complete before the next one
+//! starts.
+//!
+//! # Design
+//!
+//! Per-client ordering only requires that a client's partition in batch N+1
+//! runs after its partition in batch N. The pipeline tracks, for every client,
+//! a completion signal from the most recently submitted partition. A new
+//! partition waits on that signal before it starts; partitions for clients that
+//! have nothing in flight start immediately.
+//!
+//! ```text
+//! batch N:    [client 1: ██████████] [client 2: ██]
+//! batch N+1:                          [client 3: ███]      <- starts immediately
+//!                        [client 1 waits ...] [client 1: ███]
+//! ```
+//!
+//! # Backpressure
+//!
+//! At most `max_batches_in_flight` batches are outstanding. Submitting another
+//! batch first waits for the oldest one to complete, which bounds memory usage
+//! when the reader is faster than processing.
+//!
+//! # Ordering
+//!
+//! As with `BatchProcessor::process_batch`, transactions for different clients
+//! may be applied in any order relative to each other.
+//!
+//! # Deadlines
+//!
+//! With a `BatchDeadline`, a batch that is still running its timeout after it
+//! was submitted is reported: a warning names its unfinished client
+//! partitions, largest first, with the number of batches in flight, the
+//! partitions and records still queued behind them and the biggest partition.
+//! `DeadlinePolicy::Continue` then keeps waiting for the batch;
+//! `DeadlinePolicy::Abort` cancels the processor (see below), so the run
+//! stops instead of stalling behind a pathological batch. A batch's deadline
+//! is only checked while the pipeline waits for it, and while every worker
+//! thread is busy, a missed deadline is only noticed when one of the batch's
+//! partitions completes.
+//!
+//! # Cancellation
+//!
+//! The pipeline stops with its processor's `CancellationToken` (see
+//! `BatchProcessor::with_cancellation`): once it is cancelled, submitted
+//! batches are no longer dispatched, and the partitions in flight stop between
+//! transactions. `finish` still returns the results of every transaction that
+//! was applied.
+
+use crate::core::hash::KeyMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{oneshot, Semaphore};
+use tokio::task::JoinHandle;
+
+use super::batch_processor::{BatchProcessor, ProcessingResult};
+use super::CancellationToken;
+use crate::io::{log, LogLine};
+use crate::types::{ClientId, TransactionRecord};
+
+/// What the pipeline does when a batch misses its deadline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeadlinePolicy {
+    /// Report the batch and keep waiting for it
+    #[default]
+    Continue,
+    /// Report the batch and cancel the processor
+    Abort,
+}
+
+impl FromStr for DeadlinePolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "continue" => Ok(DeadlinePolicy::Continue),
+            "abort" => Ok(DeadlinePolicy::Abort),
+            _ => Err(format!(
+                "unknown deadline policy '{}', expected 'continue' or 'abort'",
+                policy
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DeadlinePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadlinePolicy::Continue => write!(f, "continue"),
+            DeadlinePolicy::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+/// Time a batch may take from its submission, and what happens after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchDeadline {
+    /// Time from submission after which a batch is reported
+    pub timeout: Duration,
+    /// What to do once it is reported
+    pub policy: DeadlinePolicy,
+}
+
+impl BatchDeadline {
+    /// Report batches running longer than `timeout`, and keep waiting for them
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            policy: DeadlinePolicy::default(),
+        }
+    }
+
+    /// Set what happens once a batch is reported
+    pub fn with_policy(mut self, policy: DeadlinePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+/// A client partition spawned by the pipeline
+#[derive(Debug)]
+struct PartitionTask {
+    client: ClientId,
+    records: usize,
+    handle: JoinHandle<Vec<ProcessingResult>>,
+}
+
+/// The partitions of an outstanding batch
+#[derive(Debug)]
+struct InFlightBatch {
+    /// Position of the batch among the submitted batches, counting from 1
+    number: u64,
+    submitted: Instant,
+    tasks: Vec<PartitionTask>,
+}
+
+/// Pipeline that overlaps batches while preserving per-client ordering
+///
+/// Results are returned batch by batch, in submission order, once each batch
+/// has completed.
+#[derive(Debug)]
+pub struct BatchPipeline {
+    /// Processor used to run each client partition
+    processor: BatchProcessor,
+
+    /// Maximum number of batches outstanding at once
+    max_batches_in_flight: usize,
+
+    /// Limits client partitions in flight across all outstanding batches
+    limiter: Option<Arc<Semaphore>>,
+
+    /// Completion signal of the most recently submitted partition per client
+    last_partition: KeyMap<ClientId, oneshot::Receiver<()>>,
+
+    /// Tasks of each outstanding batch, oldest first
+    in_flight: VecDeque<InFlightBatch>,
+
+    /// Number of batches submitted so far
+    submitted: u64,
+
+    /// Deadline of each batch, if any
+    deadline: Option<BatchDeadline>,
+}
+
+impl BatchPipeline {
+    /// Create a new BatchPipeline
+    ///
+    /// # Arguments
+    ///
+    /// * `processor` - BatchProcessor used to process client partitions; its
+    ///   `max_inflight_clients` limit applies across all outstanding batches
+    /// * `max_batches_in_flight` - Maximum number of outstanding batches. A value
+    ///   of zero is treated as one, which processes batches sequentially.
+    ///
+    /// # Returns
+    ///
+    /// A new `BatchPipeline` with no batches in flight
+    pub fn new(processor: BatchProcessor, max_batches_in_flight: usize) -> Self {
+        let limiter = processor
+            .max_inflight_clients()
+            .map(|limit| Arc::new(Semaphore::new(limit)));
+
+        Self {
+            processor,
+            max_batches_in_flight: max_batches_in_flight.max(1),
+            limiter,
+            last_partition: KeyMap::default(),
+            in_flight: VecDeque::new(),
+            submitted: 0,
+            deadline: None,
+        }
+    }
+
+    /// Report batches that miss `deadline`, and abort on them if its policy
+    /// says so
+    ///
+    /// With `DeadlinePolicy::Abort`, the processor's cancellation token is
+    /// cancelled; a processor without one is given a new one.
+    pub fn with_deadline(mut self, deadline: BatchDeadline) -> Self {
+        if deadline.policy == DeadlinePolicy::Abort && self.processor.cancellation().is_none() {
+            self.processor = self
+                .processor
+                .clone()
+                .with_cancellation(CancellationToken::new());
+        }
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Submit a batch for processing
+    ///
+    /// The batch is partitioned by client and every partition is spawned
+    /// immediately; partitions for clients with work still in flight wait for
+    /// that work to finish first.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch` - A vector of transaction records to process
+    ///
+    /// # Returns
+    ///
+    /// Results of any batches that had to complete to stay within
+    /// `max_batches_in_flight`. Empty if the pipeline still had capacity.
+    pub async fn submit(&mut self, batch: Vec<TransactionRecord>) -> Vec<ProcessingResult> {
+        let partitions = BatchProcessor::schedule(self.processor.partition_by_client(batch));
+        self.submitted += 1;
+        let submitted = Instant::now();
+
+        let mut tasks = Vec::with_capacity(partitions.len());
+        for (client_id, transactions) in partitions {
+            if self.processor.is_cancelled() {
+                break;
+            }
+
+            // Chain this partition behind the client's previous one
+            let (done, signal) = oneshot::channel();
+            let predecessor = self.last_partition.insert(client_id, signal);
+
+            let processor = self.processor.clone();
+            let limiter = self.limiter.clone();
+            let records = transactions.len();
+            let handle = tokio::spawn(async move {
+                // An error means the predecessor finished without signalling (it
+                // panicked); either way it is no longer running
+                if let Some(predecessor) = predecessor {
+                    let _ = predecessor.await;
+                }
+
+                let _permit = match limiter {
+                    Some(limiter) => Some(
+                        limiter
+                            .acquire_owned()
+                            .await
+                            .expect("semaphore is never closed"),
+                    ),
+                    None => None,
+                };
+
+                let results = processor.process_client_transactions(transactions).await;
+                let _ = done.send(());
+                results
+            });
+            tasks.push(PartitionTask {
+                client: client_id,
+                records,
+                handle,
+            });
+        }
+        self.in_flight.push_back(InFlightBatch {
+            number: self.submitted,
+            submitted,
+            tasks,
+        });
+
+        let mut results = Vec::new();
+        while self.in_flight.len() > self.max_batches_in_flight {
+            results.extend(self.complete_oldest().await);
+        }
+        results
+    }
+
+    /// Wait for every outstanding batch to complete
+    ///
+    /// # Returns
+    ///
+    /// Results of all batches still in flight, in submission order
+    pub async fn finish(mut self) -> Vec<ProcessingResult> {
+        let mut results = Vec::new();
+        while !self.in_flight.is_empty() {
+            results.extend(self.complete_oldest().await);
+        }
+        results
+    }
+
+    /// Wait for the oldest outstanding batch and collect its results
+    async fn complete_oldest(&mut self) -> Vec<ProcessingResult> {
+        let mut results = Vec::new();
+        let Some(mut batch) = self.in_flight.pop_front() else {
+            return results;
+        };
+
+        let mut expiry = self
+            .deadline
+            .map(|deadline| batch.submitted + deadline.timeout);
+        for i in 0..batch.tasks.len() {
+            let outcome = match expiry {
+                Some(at) => {
+                    let deadline = tokio::time::Instant::from_std(at);
+                    match tokio::time::timeout_at(deadline, &mut batch.tasks[i].handle).await {
+                        // The timer only fires while a worker thread is free to
+                        // drive it, so a partition completing late is reported
+                        // too, with the partitions after it
+                        Ok(outcome) if Instant::now() >= at => {
+                            expiry = None;
+                            self.deadline_missed(batch.number, &batch.tasks[i + 1..]);
+                            outcome
+                        }
+                        Ok(outcome) => outcome,
+                        Err(_) => {
+                            // Report once, then wait for the rest of the batch
+                            expiry = None;
+                            self.deadline_missed(batch.number, &batch.tasks[i..]);
+                            (&mut batch.tasks[i].handle).await
+                        }
+                    }
+                }
+                None => (&mut batch.tasks[i].handle).await,
+            };
+            match outcome {
+                Ok(client_results) => results.extend(client_results),
+                Err(e) => {
+                    log(LogLine::error(format!("Task panicked: {:?}", e)));
+                }
+            }
+        }
+        results
+    }
+
+    /// Report a batch that missed its deadline, and abort if the policy says so
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - Position of the batch among the submitted batches
+    /// * `pending` - Partitions of the batch that had not been collected yet;
+    ///   those still running are reported
+    fn deadline_missed(&self, number: u64, pending: &[PartitionTask]) {
+        let Some(deadline) = self.deadline else {
+            return;
+        };
+        let unfinished = |tasks: &[PartitionTask]| -> Vec<(ClientId, usize)> {
+            tasks
+                .iter()
+                .filter(|task| !task.handle.is_finished())
+                .map(|task| (task.client, task.records))
+                .collect()
+        };
+        let slow = unfinished(pending);
+        let queued: Vec<(ClientId, usize)> = self
+            .in_flight
+            .iter()
+            .flat_map(|batch| unfinished(&batch.tasks))
+            .collect();
+
+        let mut message = describe_missed_deadline(
+            number,
+            deadline.timeout,
+            &slow,
+            self.in_flight.len() + 1,
+            &queued,
+        );
+        if deadline.policy == DeadlinePolicy::Abort {
+            message.push_str("; aborting");
+            if let Some(cancel) = self.processor.cancellation() {
+                cancel.cancel();
+            }
+        }
+        log(LogLine::warning(message));
+    }
+}
+
+/// Number of slow partitions named in a missed deadline warning
+const REPORTED_PARTITIONS: usize = 10;
+
+/// Describe a batch that missed its deadline
+///
+/// # Arguments
+///
+/// * `number` - Position of the batch among the submitted batches
+/// * `timeout` - The deadline it missed
+/// * `slow` - Client and record count of its unfinished partitions
+/// * `batches_in_flight` - Number of outstanding batches, including this one
+/// * `queued` - Client and record count of the unfinished partitions of the
+///   later batches
+fn describe_missed_deadline(
+    number: u64,
+    timeout: Duration,
+    slow: &[(ClientId, usize)],
+    batches_in_flight: usize,
+    queued: &[(ClientId, usize)],
+) -> String {
+    let mut slow = slow.to_vec();
+    slow.sort_unstable_by(|(client_a, records_a), (client_b, records_b)| {
+        records_b.cmp(records_a).then(client_a.cmp(client_b))
+    });
+    let mut named: Vec<String> = slow
+        .iter()
+        .take(REPORTED_PARTITIONS)
+        .map(|(client, records)| format!("client {} ({} records)", client, records))
+        .collect();
+    if slow.len() > REPORTED_PARTITIONS {
+        named.push(format!("{} more", slow.len() - REPORTED_PARTITIONS));
+    }
+
+    let mut message = format!(
+        "Warning: batch {} missed its deadline of {} ms; unfinished partitions: {}; \
+         {} batches in flight, {} partitions with {} records queued behind it",
+        number,
+        timeout.as_millis(),
+        if named.is_empty() {
+            "none".to_string()
+        } else {
+            named.join(", ")
+        },
+        batches_in_flight,
+        queued.len(),
+        queued.iter().map(|(_, records)| records).sum::<usize>(),
+    );
+    if let Some((client, records)) =
+        slow.iter()
+            .chain(queued)
+            .max_by(|(client_a, records_a), (client_b, records_b)| {
+                records_a.cmp(records_b).then(client_b.cmp(client_a))
+            })
+    {
+        message.push_str(&format!(
+            "; biggest partition: client {} with {} records",
+            client, records
+        ));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::r#async::{
+        AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore, CancellationToken,
+    };
+    use crate::types::{TransactionId, TransactionType};
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    fn create_processor() -> (Arc<AsyncAccountManager>, BatchProcessor) {
+        let account_manager = Arc::new(AsyncAccountManager::new());
+        let transaction_store = Arc::new(AsyncTransactionStore::new());
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            Arc::clone(&account_manager),
+            transaction_store,
+        ));
+        (account_manager, BatchProcessor::new(engine))
+    }
+
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<i64>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount: amount.map(|amount| Decimal::new(amount, 0)),
+            line: None,
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_empty() {
+        let (_, processor) = create_processor();
+        let pipeline = BatchPipeline::new(processor, 4);
+
+        assert!(pipeline.finish().await.is_empty());
+    }
+
+    #[rstest]
+    #[case::sequential(1, None)]
+    #[case::pipelined(4, None)]
+    #[case::pipelined_with_client_limit(4, Some(1))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pipeline_preserves_client_order_across_batches(
+        #[case] max_batches_in_flight: usize,
+        #[case] max_inflight_clients: Option<usize>,
+    ) {
+        let (account_manager, processor) = create_processor();
+        let processor = processor.with_max_inflight_clients(max_inflight_clients);
+        let mut pipeline = BatchPipeline::new(processor, max_batches_in_flight);
+
+        // Each withdrawal only succeeds if the deposit in the previous batch
+        // has already been applied
+        let mut results = Vec::new();
+        for i in 0..20u64 {
+            let batch = vec![
+                record(TransactionType::Deposit, 1, i * 4, Some(10)),
+                record(
+                    TransactionType::Deposit,
+                    (i % 5) as ClientId + 2,
+                    i * 4 + 1,
+                    Some(1),
+                ),
+            ];
+            results.extend(pipeline.submit(batch).await);
+            let batch = vec![record(TransactionType::Withdrawal, 1, i * 4 + 2, Some(10))];
+            results.extend(pipeline.submit(batch).await);
+        }
+        results.extend(pipeline.finish().await);
+
+        assert_eq!(results.len(), 60);
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        assert_eq!(account_manager.get_or_create(1).available, Decimal::ZERO);
+        for client in 2..7 {
+            assert_eq!(
+                account_manager.get_or_create(client).available,
+                Decimal::new(4, 0)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_returns_results_when_full() {
+        let (_, processor) = create_processor();
+        let mut pipeline = BatchPipeline::new(processor, 1);
+
+        let first = pipeline
+            .submit(vec![record(TransactionType::Deposit, 1, 1, Some(10))])
+            .await;
+        assert!(first.is_empty());
+
+        // Submitting a second batch forces the first one to complete
+        let second = pipeline
+            .submit(vec![record(TransactionType::Deposit, 2, 2, Some(10))])
+            .await;
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].record.tx, 1);
+
+        let rest = pipeline.finish().await;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].record.tx, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_stops_when_cancelled() {
+        let (account_manager, processor) = create_processor();
+        let cancel = CancellationToken::new();
+        let mut pipeline = BatchPipeline::new(processor.with_cancellation(cancel.clone()), 1);
+
+        cancel.cancel();
+        let mut results = pipeline
+            .submit(vec![record(TransactionType::Deposit, 1, 1, Some(10))])
+            .await;
+        results.extend(pipeline.finish().await);
+
+        // The batch was never dispatched
+        assert!(results.is_empty());
+        assert_eq!(account_manager.get_or_create(1).available, Decimal::ZERO);
+    }
+
+    #[rstest]
+    #[case::continue_policy(DeadlinePolicy::Continue)]
+    #[case::abort_policy(DeadlinePolicy::Abort)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_pipeline_deadline(#[case] policy: DeadlinePolicy) {
+        let (_, processor) = create_processor();
+        let deadline = BatchDeadline::new(Duration::from_millis(1)).with_policy(policy);
+        let mut pipeline = BatchPipeline::new(processor, 2).with_deadline(deadline);
+
+        // A single partition far too large for the deadline
+        let batch: Vec<_> = (0..50_000)
+            .map(|tx| record(TransactionType::Deposit, 1, tx, Some(1)))
+            .collect();
+        let mut results = pipeline.submit(batch).await;
+        results.extend(pipeline.finish().await);
+
+        match policy {
+            DeadlinePolicy::Continue => assert_eq!(results.len(), 50_000),
+            DeadlinePolicy::Abort => assert!(results.len() < 50_000),
+        }
+    }
+
+    #[test]
+    fn test_describe_missed_deadline() {
+        assert_eq!(
+            describe_missed_deadline(
+                3,
+                Duration::from_millis(250),
+                &[(4, 2), (7, 900)],
+                2,
+                &[(4, 5), (9, 1000)],
+            ),
+            "Warning: batch 3 missed its deadline of 250 ms; unfinished partitions: \
+             client 7 (900 records), client 4 (2 records); 2 batches in flight, \
+             2 partitions with 1005 records queued behind it; \
+             biggest partition: client 9 with 1000 records"
+        );
+        let many: Vec<_> = (1..=12).map(|client| (client, 1)).collect();
+        let message = describe_missed_deadline(1, Duration::from_secs(1), &many, 1, &[]);
+        assert!(
+            message.contains("client 10 (1 records), 2 more;"),
+            "{}",
+            message
+        );
+        assert!(message.ends_with("biggest partition: client 1 with 1 records"));
+    }
+
+    #[test]
+    fn test_deadline_policy_from_str() {
+        assert_eq!("continue".parse(), Ok(DeadlinePolicy::Continue));
+        assert_eq!("abort".parse(), Ok(DeadlinePolicy::Abort));
+        assert!("stop".parse::<DeadlinePolicy>().is_err());
+        assert_eq!(DeadlinePolicy::Abort.to_string(), "abort");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_captures_errors() {
+        let (_, processor) = create_processor();
+        let mut pipeline = BatchPipeline::new(processor, 2);
+
+        pipeline
+            .submit(vec![record(TransactionType::Deposit, 1, 1, Some(10))])
+            .await;
+        pipeline
+            .submit(vec![record(TransactionType::Withdrawal, 1, 2, Some(50))])
+            .await;
+        let results = pipeline.finish().await;
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].result.is_ok());
+        assert!(results[1].result.is_err());
+    }
+}
@@ -19,14 +19,35 @@
 //! - Verify the client ID matches
 //! - Track dispute state (disputed, resolved, charged back)
 //!
+//! # Client Index
+//!
+//! A secondary index maps each client to the IDs of its stored transactions,
+//! so `transactions_for_client` does not have to scan the whole store.
+//!
+//! # Compact Encoding
+//!
+//! With the `compact-store` feature, transactions are kept in the encoding of
+//! `crate::core::compact`. Transactions it cannot represent go to a second
+//! map at full width; an entry only ever moves into that map while its slot in
+//! the first map is locked, so a lookup never misses it.
+//!
+//! # Duplicate Filter
+//!
+//! An optional `DuplicateFilter` in front of the store lets `contains` answer
+//! for new transaction IDs without touching the map.
+//!
 //! # Thread Safety
 //!
 //! All operations are thread-safe and prevent data races through DashMap's internal
 //! synchronization. The Rust type system ensures that shared references cannot be
 //! used to mutate state, and mutable operations are properly synchronized.
 
-use crate::types::{StoredTransaction, TransactionId};
-use dashmap::DashMap;
+use super::loom::AccessPoint;
+use super::DuplicateFilter;
+use crate::core::compact::{self, Slot};
+use crate::core::hash::KeyHasher;
+use crate::types::{ClientId, StoredTransaction, TransactionId};
+use dashmap::{DashMap, Entry};
 
 /// Thread-safe transaction store for async batch processing
 ///
@@ -60,7 +81,28 @@ pub struct AsyncTransactionStore {
     ///
     /// DashMap provides fine-grained locking through internal sharding,
     /// allowing concurrent access to different transactions without global locks.
-    transactions: DashMap<TransactionId, StoredTransaction>,
+    transactions: DashMap<TransactionId, Slot, KeyHasher>,
+
+    /// Transactions without a compact encoding, kept at full width
+    ///
+    /// Always empty unless the `compact-store` feature is enabled. Only
+    /// written while the transaction's entry in `transactions` is locked.
+    spilled: DashMap<TransactionId, StoredTransaction, KeyHasher>,
+
+    /// IDs of each client's stored transactions, in the order they were stored
+    ///
+    /// Only updated while the new transaction's entry is locked, so a
+    /// transaction ID is indexed at most once.
+    by_client: DashMap<ClientId, Vec<TransactionId>, KeyHasher>,
+
+    /// Bloom filter of the stored transaction IDs, if enabled
+    ///
+    /// An ID is inserted before its transaction, so the filter never misses a
+    /// stored transaction.
+    filter: Option<DuplicateFilter>,
+
+    /// Reached before every access to the maps (see the `loom` module)
+    access: AccessPoint,
 }
 
 impl AsyncTransactionStore {
@@ -72,9 +114,43 @@ impl AsyncTransactionStore {
     /// as they are processed (deposits and withdrawals only).
     pub fn new() -> Self {
         Self {
-            transactions: DashMap::new(),
+            transactions: DashMap::default(),
+            spilled: DashMap::default(),
+            by_client: DashMap::default(),
+            filter: None,
+            access: AccessPoint::new(),
         }
     }
+
+    /// Create a new empty AsyncTransactionStore with room for `transactions`
+    /// stored transactions of `clients` clients
+    ///
+    /// Avoids resizing the store and its client index while they grow.
+    pub fn with_capacity(transactions: usize, clients: usize) -> Self {
+        Self {
+            transactions: DashMap::with_capacity_and_hasher(transactions, KeyHasher::default()),
+            spilled: DashMap::default(),
+            by_client: DashMap::with_capacity_and_hasher(clients, KeyHasher::default()),
+            filter: None,
+            access: AccessPoint::new(),
+        }
+    }
+
+    /// Number of transactions the store can hold without resizing
+    pub fn capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
+
+    /// Check for duplicate transaction IDs through a bloom filter first
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - An empty filter, sized for the expected number of
+    ///   deposits and withdrawals
+    pub fn with_duplicate_filter(mut self, filter: DuplicateFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
 }
 
 impl Default for AsyncTransactionStore {
@@ -84,6 +160,24 @@ impl Default for AsyncTransactionStore {
 }
 
 impl AsyncTransactionStore {
+    /// Get all stored transactions (thread-safe)
+    ///
+    /// Like `AsyncAccountManager::get_all_accounts`, the returned vector is a
+    /// snapshot; transactions stored or updated concurrently may be missing
+    /// or stale.
+    pub fn get_all_transactions(&self) -> Vec<(TransactionId, StoredTransaction)> {
+        self.access.reach();
+        self.transactions
+            .iter()
+            .map(|entry| (*entry.key(), compact::unpack(entry.value())))
+            .chain(
+                self.spilled
+                    .iter()
+                    .map(|entry| (*entry.key(), entry.value().clone())),
+            )
+            .collect()
+    }
+
     /// Store a transaction in the store (thread-safe)
     ///
     /// This method inserts a transaction into the store, making it available for
@@ -105,7 +199,73 @@ impl AsyncTransactionStore {
     /// win and the others will be ignored.
     pub fn store(&self, tx_id: TransactionId, transaction: StoredTransaction) {
         // Only store if not already present (first occurrence wins)
-        self.transactions.entry(tx_id).or_insert(transaction);
+        self.access.reach();
+        if let Entry::Vacant(entry) = self.transactions.entry(tx_id) {
+            if self.spilled.contains_key(&tx_id) {
+                return;
+            }
+            if let Some(filter) = &self.filter {
+                filter.insert(tx_id);
+            }
+            self.by_client
+                .entry(transaction.client)
+                .or_default()
+                .push(tx_id);
+            match compact::pack(transaction) {
+                Ok(slot) => {
+                    entry.insert(slot);
+                }
+                Err(transaction) => {
+                    self.spilled.insert(tx_id, transaction);
+                }
+            }
+        }
+    }
+
+    /// Get the stored transactions of one client (thread-safe)
+    ///
+    /// Uses the client index, so the cost is proportional to the client's own
+    /// transactions rather than to the size of the store. The index entry is
+    /// copied before the transactions are looked up, so no two locks are held
+    /// at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client whose transactions to return
+    ///
+    /// # Returns
+    ///
+    /// The client's stored (disputable) transactions in the order they were
+    /// stored; empty if the client has none
+    pub fn transactions_for_client(
+        &self,
+        client: ClientId,
+    ) -> Vec<(TransactionId, StoredTransaction)> {
+        self.access.reach();
+        let tx_ids = match self.by_client.get(&client) {
+            Some(entry) => entry.value().clone(),
+            None => return Vec::new(),
+        };
+        tx_ids
+            .into_iter()
+            .filter_map(|tx_id| Some((tx_id, self.get(tx_id)?)))
+            .collect()
+    }
+
+    /// Returns true if a transaction with this ID is stored (thread-safe)
+    ///
+    /// With a duplicate filter, IDs the filter has not seen are reported as
+    /// new without looking them up, which is the common case for the
+    /// duplicate check of deposits and withdrawals.
+    pub fn contains(&self, tx_id: TransactionId) -> bool {
+        if let Some(filter) = &self.filter {
+            if !filter.may_contain(tx_id) {
+                return false;
+            }
+        }
+        // No access point: the engine checks for duplicates while holding the
+        // account's entry, where loom must not switch threads
+        self.transactions.contains_key(&tx_id) || self.spilled.contains_key(&tx_id)
     }
 
     /// Get a transaction from the store (read-only, thread-safe)
@@ -127,9 +287,11 @@ impl AsyncTransactionStore {
     /// This method is safe to call from multiple threads concurrently. Multiple
     /// threads can read different transactions simultaneously without blocking.
     pub fn get(&self, tx_id: TransactionId) -> Option<StoredTransaction> {
-        self.transactions
-            .get(&tx_id)
-            .map(|entry| entry.value().clone())
+        self.access.reach();
+        match self.transactions.get(&tx_id) {
+            Some(entry) => Some(compact::unpack(entry.value())),
+            None => self.spilled.get(&tx_id).map(|entry| entry.value().clone()),
+        }
     }
 
     /// Update a transaction with a closure (atomic operation, thread-safe)
@@ -137,7 +299,8 @@ impl AsyncTransactionStore {
     /// This method allows atomic updates to a transaction's state. The closure
     /// receives a mutable reference to the transaction and can modify it. The
     /// modification is atomic - no other thread can access the transaction while
-    /// the closure is executing.
+    /// the closure is executing. With the `compact-store` feature the closure
+    /// modifies a decoded copy, which is encoded again once it returns.
     ///
     /// # Arguments
     ///
@@ -160,7 +323,20 @@ impl AsyncTransactionStore {
     where
         F: FnOnce(&mut StoredTransaction) -> Result<(), crate::types::PaymentError>,
     {
-        match self.transactions.get_mut(&tx_id) {
+        self.access.reach();
+        if let Entry::Occupied(mut entry) = self.transactions.entry(tx_id) {
+            let mut transaction = compact::unpack(entry.get());
+            let result = f(&mut transaction);
+            match compact::pack(transaction) {
+                Ok(slot) => *entry.get_mut() = slot,
+                Err(transaction) => {
+                    self.spilled.insert(tx_id, transaction);
+                    entry.remove();
+                }
+            }
+            return result;
+        }
+        match self.spilled.get_mut(&tx_id) {
             Some(mut entry) => f(entry.value_mut()),
             None => Err(crate::types::PaymentError::transaction_not_found(
                 tx_id, "update",
@@ -172,7 +348,8 @@ impl AsyncTransactionStore {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{PaymentError, TransactionType};
+    use crate::types::{ClientId, DisputeState, PaymentError, TransactionType};
+    use rstest::rstest;
     use rust_decimal::Decimal;
 
     #[test]
@@ -183,7 +360,8 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4), // 1.0000
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         store.store(123, tx.clone());
@@ -194,7 +372,133 @@ mod tests {
         assert_eq!(retrieved.client, 1);
         assert_eq!(retrieved.amount, Decimal::new(10000, 4));
         assert_eq!(retrieved.tx_type, TransactionType::Deposit);
-        assert!(!retrieved.under_dispute);
+        assert!(!retrieved.dispute_state.is_disputed());
+    }
+
+    #[test]
+    fn test_transactions_for_client() {
+        let store = AsyncTransactionStore::new();
+        let tx = |client, amount| StoredTransaction {
+            client,
+            amount: Decimal::from(amount),
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+
+        store.store(5, tx(1, 10));
+        store.store(2, tx(2, 20));
+        store.store(9, tx(1, 30));
+        store.store(5, tx(3, 40));
+
+        let client_1: Vec<TransactionId> = store
+            .transactions_for_client(1)
+            .into_iter()
+            .map(|(tx_id, _)| tx_id)
+            .collect();
+        assert_eq!(client_1, vec![5, 9]);
+        assert_eq!(
+            store.transactions_for_client(2)[0].1.amount,
+            Decimal::from(20)
+        );
+        assert!(store.transactions_for_client(3).is_empty());
+    }
+
+    #[test]
+    fn test_transactions_for_client_concurrent_stores() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let store = Arc::new(AsyncTransactionStore::new());
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread_id| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    // Every thread stores the same IDs; each must be indexed once
+                    for tx_id in 0..100u64 {
+                        store.store(
+                            tx_id,
+                            StoredTransaction {
+                                client: (tx_id % 2) as ClientId,
+                                amount: Decimal::from(thread_id),
+                                tx_type: TransactionType::Deposit,
+                                dispute_state: DisputeState::None,
+                                disputes: 0,
+                            },
+                        );
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(store.transactions_for_client(0).len(), 50);
+        assert_eq!(store.transactions_for_client(1).len(), 50);
+    }
+
+    #[test]
+    fn test_wide_amount_round_trips() {
+        let store = AsyncTransactionStore::new();
+        // Too wide for the compact encoding, so it is spilled with the feature
+        let wide = Decimal::MAX;
+        let tx = |amount| StoredTransaction {
+            client: 1,
+            amount,
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+        store.store(1, tx(wide));
+        store.store(1, tx(Decimal::ONE));
+        store.store(2, tx(Decimal::new(15, 1)));
+
+        for tx_id in [1, 2] {
+            store
+                .update(tx_id, |tx| {
+                    tx.dispute_state = DisputeState::Disputed;
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        let stored = store.get(1).unwrap();
+        assert_eq!(stored.amount, wide);
+        assert!(stored.dispute_state.is_disputed());
+        assert_eq!(store.get(2).unwrap().amount.to_string(), "1.5");
+        assert_eq!(store.transactions_for_client(1).len(), 2);
+        assert_eq!(store.get_all_transactions().len(), 2);
+    }
+
+    #[rstest]
+    #[case::without_filter(AsyncTransactionStore::new())]
+    #[case::with_filter(
+        AsyncTransactionStore::new().with_duplicate_filter(DuplicateFilter::new(100, 0.01))
+    )]
+    fn test_contains(#[case] store: AsyncTransactionStore) {
+        for tx_id in 0..100 {
+            store.store(
+                tx_id * 2,
+                StoredTransaction {
+                    client: 1,
+                    amount: Decimal::ONE,
+                    tx_type: TransactionType::Deposit,
+                    dispute_state: DisputeState::None,
+                    disputes: 0,
+                },
+            );
+        }
+
+        assert!((0..100).all(|tx_id| store.contains(tx_id * 2)));
+        assert!((0..100).all(|tx_id| !store.contains(tx_id * 2 + 1)));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let store = AsyncTransactionStore::with_capacity(10_000, 100);
+        assert!(store.capacity() >= 10_000);
+        assert!(store.get_all_transactions().is_empty());
     }
 
     #[test]
@@ -211,14 +515,16 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         let tx2 = StoredTransaction {
             client: 2,
             amount: Decimal::new(20000, 4),
             tx_type: TransactionType::Withdrawal,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         store.store(1, tx1);
@@ -241,14 +547,15 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         store.store(123, tx);
 
         // Mark as disputed
         let result = store.update(123, |tx| {
-            tx.under_dispute = true;
+            tx.dispute_state = DisputeState::Disputed;
             Ok(())
         });
 
@@ -256,7 +563,7 @@ mod tests {
 
         // Verify the update
         let updated = store.get(123).unwrap();
-        assert!(updated.under_dispute);
+        assert!(updated.dispute_state.is_disputed());
     }
 
     #[test]
@@ -264,7 +571,7 @@ mod tests {
         let store = AsyncTransactionStore::new();
 
         let result = store.update(999, |tx| {
-            tx.under_dispute = true;
+            tx.dispute_state = DisputeState::Disputed;
             Ok(())
         });
 
@@ -286,17 +593,18 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: true, // Already disputed
+            dispute_state: DisputeState::Disputed, // Already disputed
+            disputes: 1,
         };
 
         store.store(123, tx);
 
         // Try to dispute again
         let result = store.update(123, |tx| {
-            if tx.under_dispute {
+            if tx.dispute_state.is_disputed() {
                 return Err(PaymentError::transaction_already_disputed(123, tx.client));
             }
-            tx.under_dispute = true;
+            tx.dispute_state = DisputeState::Disputed;
             Ok(())
         });
 
@@ -311,7 +619,7 @@ mod tests {
 
         // Verify transaction state unchanged
         let unchanged = store.get(123).unwrap();
-        assert!(unchanged.under_dispute);
+        assert!(unchanged.dispute_state.is_disputed());
     }
 
     #[test]
@@ -322,19 +630,20 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: true,
+            dispute_state: DisputeState::Disputed,
+            disputes: 1,
         };
 
         store.store(123, tx);
 
         // Resolve the dispute
         let result = store.update(123, |tx| {
-            if !tx.under_dispute {
+            if !tx.dispute_state.is_disputed() {
                 return Err(PaymentError::transaction_not_disputed(
                     123, tx.client, "resolve",
                 ));
             }
-            tx.under_dispute = false;
+            tx.dispute_state = DisputeState::Resolved;
             Ok(())
         });
 
@@ -342,7 +651,7 @@ mod tests {
 
         // Verify the update
         let resolved = store.get(123).unwrap();
-        assert!(!resolved.under_dispute);
+        assert!(!resolved.dispute_state.is_disputed());
     }
 
     #[test]
@@ -353,14 +662,16 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         let tx2 = StoredTransaction {
             client: 2,
             amount: Decimal::new(20000, 4),
             tx_type: TransactionType::Withdrawal,
-            under_dispute: true,
+            dispute_state: DisputeState::Disputed,
+            disputes: 1,
         };
 
         store.store(123, tx1);
@@ -369,7 +680,7 @@ mod tests {
         let retrieved = store.get(123).unwrap();
         assert_eq!(retrieved.client, 1); // Should be the first transaction
         assert_eq!(retrieved.amount, Decimal::new(10000, 4));
-        assert!(!retrieved.under_dispute);
+        assert!(!retrieved.dispute_state.is_disputed());
     }
 
     #[test]
@@ -380,23 +691,24 @@ mod tests {
         let store = Arc::new(AsyncTransactionStore::new());
 
         // Store initial transactions
-        for i in 0u32..10u32 {
+        for i in 0u64..10u64 {
             let tx = StoredTransaction {
-                client: i as u16,
+                client: i as ClientId,
                 amount: Decimal::new(10000 * i as i64, 4),
                 tx_type: TransactionType::Deposit,
-                under_dispute: false,
+                dispute_state: DisputeState::None,
+                disputes: 0,
             };
             store.store(i, tx);
         }
 
         // Spawn threads to access different transactions
         let mut handles = vec![];
-        for i in 0u32..10u32 {
+        for i in 0u64..10u64 {
             let store_clone = Arc::clone(&store);
             let handle = thread::spawn(move || {
                 let tx = store_clone.get(i).unwrap();
-                assert_eq!(tx.client, i as u16);
+                assert_eq!(tx.client, i as ClientId);
                 assert_eq!(tx.amount, Decimal::new(10000 * i as i64, 4));
             });
             handles.push(handle);
@@ -416,24 +728,25 @@ mod tests {
         let store = Arc::new(AsyncTransactionStore::new());
 
         // Store initial transactions
-        for i in 0u32..10u32 {
+        for i in 0u64..10u64 {
             let tx = StoredTransaction {
-                client: i as u16,
+                client: i as ClientId,
                 amount: Decimal::new(10000 * i as i64, 4),
                 tx_type: TransactionType::Deposit,
-                under_dispute: false,
+                dispute_state: DisputeState::None,
+                disputes: 0,
             };
             store.store(i, tx);
         }
 
         // Spawn threads to update different transactions
         let mut handles = vec![];
-        for i in 0u32..10u32 {
+        for i in 0u64..10u64 {
             let store_clone = Arc::clone(&store);
             let handle = thread::spawn(move || {
                 store_clone
                     .update(i, |tx| {
-                        tx.under_dispute = true;
+                        tx.dispute_state = DisputeState::Disputed;
                         Ok(())
                     })
                     .unwrap();
@@ -447,9 +760,9 @@ mod tests {
         }
 
         // Verify all transactions were updated
-        for i in 0u32..10u32 {
+        for i in 0u64..10u64 {
             let tx = store.get(i).unwrap();
-            assert!(tx.under_dispute);
+            assert!(tx.dispute_state.is_disputed());
         }
     }
 }
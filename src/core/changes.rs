@@ -0,0 +1,179 @@
+//! Balance change events
+//!
+//! The account output and the balance history only show balances at given
+//! points. For downstream systems that maintain derived views of the
+//! accounts, every change is also needed as an event with the balances
+//! before and after it, so the views can be kept up to date (and checked)
+//! without re-reading the inputs. `BalanceChanges` derives these events from
+//! the engine's postings: one `BalanceChange` per client account a posting
+//! changes, numbered in order. A dispute, which moves funds between a
+//! client's available and held accounts, is a single change of that client.
+//!
+//! The balances start at zero for new clients, or at the balances the
+//! accounts were seeded with, e.g. from a loaded state.
+
+use crate::core::hash::KeyMap;
+use crate::core::journal::{LedgerAccount, Posting};
+use crate::types::{Account, ClientId, TransactionId, TransactionType};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+/// A client's balances before or after a change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Balances {
+    pub available: Decimal,
+    pub held: Decimal,
+    pub total: Decimal,
+}
+
+/// A change of a client's balances by one posting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BalanceChange {
+    /// Number of the change in the stream, from 1
+    pub seq: u64,
+    /// The transaction, as in its posting
+    pub tx: TransactionId,
+    /// Type of the transaction
+    #[serde(rename = "type")]
+    pub tx_type: TransactionType,
+    /// The client whose balances changed
+    pub client: ClientId,
+    pub before: Balances,
+    pub after: Balances,
+}
+
+/// Balances of every client, kept up to date from postings
+#[derive(Debug, Clone, Default)]
+pub struct BalanceChanges {
+    balances: KeyMap<ClientId, Balances>,
+    /// Number of changes derived so far
+    seq: u64,
+}
+
+impl BalanceChanges {
+    /// Start with no clients, all at zero balances
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the given clients at the balances of their accounts
+    pub fn seed<'a>(&mut self, accounts: impl IntoIterator<Item = &'a Account>) {
+        for account in accounts {
+            self.balances.insert(
+                account.client,
+                Balances {
+                    available: account.available,
+                    held: account.held,
+                    total: account.total,
+                },
+            );
+        }
+    }
+
+    /// Apply a posting, returning the change of every client it moved funds of
+    pub fn record(&mut self, posting: &Posting) -> Vec<BalanceChange> {
+        // Client accounts are liabilities: debits lower and credits raise them
+        let mut deltas: Vec<(ClientId, Decimal, Decimal)> = Vec::with_capacity(2);
+        for (account, amount) in [
+            (posting.debit, -posting.amount),
+            (posting.credit, posting.amount),
+        ] {
+            let (client, available, held) = match account {
+                LedgerAccount::Settlement => continue,
+                LedgerAccount::Available(client) => (client, amount, Decimal::ZERO),
+                LedgerAccount::Held(client) => (client, Decimal::ZERO, amount),
+            };
+            match deltas.iter_mut().find(|delta| delta.0 == client) {
+                Some(delta) => {
+                    delta.1 = delta.1.saturating_add(available);
+                    delta.2 = delta.2.saturating_add(held);
+                }
+                None => deltas.push((client, available, held)),
+            }
+        }
+
+        deltas
+            .into_iter()
+            .map(|(client, available, held)| {
+                let balances = self.balances.entry(client).or_default();
+                let before = *balances;
+                *balances = Balances {
+                    available: before.available.saturating_add(available),
+                    held: before.held.saturating_add(held),
+                    total: before.total.saturating_add(available).saturating_add(held),
+                };
+                self.seq += 1;
+                BalanceChange {
+                    seq: self.seq,
+                    tx: posting.tx,
+                    tx_type: posting.tx_type,
+                    client,
+                    before,
+                    after: *balances,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balances(available: i64, held: i64) -> Balances {
+        Balances {
+            available: Decimal::from(available),
+            held: Decimal::from(held),
+            total: Decimal::from(available + held),
+        }
+    }
+
+    #[test]
+    fn test_record_changes() {
+        let mut changes = BalanceChanges::new();
+        let mut seeded = Account::new(2);
+        seeded.available = Decimal::from(5);
+        seeded.total = Decimal::from(5);
+        changes.seed([&seeded]);
+
+        let recorded: Vec<BalanceChange> = [
+            Posting::new(TransactionType::Deposit, 1, 1, Decimal::TEN),
+            Posting::fee(TransactionType::Deposit, 1, 1, 2, Decimal::ONE),
+            Posting::new(TransactionType::Dispute, 1, 1, Decimal::from(4)),
+        ]
+        .iter()
+        .flat_map(|posting| changes.record(posting))
+        .collect();
+
+        let summary: Vec<_> = recorded
+            .iter()
+            .map(|change| (change.seq, change.client, change.before, change.after))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 1, balances(0, 0), balances(10, 0)),
+                (2, 1, balances(10, 0), balances(9, 0)),
+                (3, 2, balances(5, 0), balances(6, 0)),
+                (4, 1, balances(9, 0), balances(5, 4)),
+            ]
+        );
+        assert_eq!(recorded[3].tx_type, TransactionType::Dispute);
+    }
+
+    #[test]
+    fn test_change_json() {
+        let change = BalanceChange {
+            seq: 1,
+            tx: 7,
+            tx_type: TransactionType::Deposit,
+            client: 3,
+            before: Balances::default(),
+            after: balances(2, 0),
+        };
+        let json = serde_json::to_value(&change).unwrap();
+        assert_eq!(json["type"], "deposit");
+        assert_eq!(json["client"], 3);
+        assert_eq!(json["after"]["available"], "2");
+    }
+}
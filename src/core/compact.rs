@@ -0,0 +1,206 @@
+//! Compact encoding of stored transactions
+//!
+//! Both transaction stores keep one `Slot` per stored transaction. By default
+//! a slot is the `StoredTransaction` itself (24 bytes with the default
+//! `ClientId`). With the `compact-store` feature it is a `CompactTransaction`
+//! of 12 bytes instead: the amount's mantissa as an `i64`, and the transaction
+//! type, dispute state, amount scale and dispute count packed into 16 bits.
+//!
+//! The encoding is exact. A transaction it cannot represent (an amount whose
+//! mantissa does not fit in an `i64`, a negative zero, more than 255 disputes,
+//! a withdrawal pending approval or rejected)
+//! is handed back by `pack`, and the store keeps it at full width in a
+//! separate map, so the feature changes memory use but never results.
+
+use crate::types::StoredTransaction;
+
+#[cfg(feature = "compact-store")]
+use crate::types::{ClientId, DisputeState, TransactionType};
+#[cfg(feature = "compact-store")]
+use rust_decimal::Decimal;
+
+/// What a transaction store keeps per transaction
+#[cfg(not(feature = "compact-store"))]
+pub(crate) type Slot = StoredTransaction;
+
+/// What a transaction store keeps per transaction
+#[cfg(feature = "compact-store")]
+pub(crate) type Slot = CompactTransaction;
+
+/// Encode a transaction for storage
+///
+/// # Returns
+///
+/// * `Ok(Slot)` - The encoded transaction
+/// * `Err(StoredTransaction)` - The transaction itself, if it has no compact
+///   encoding and has to be kept at full width
+#[cfg(not(feature = "compact-store"))]
+pub(crate) fn pack(tx: StoredTransaction) -> Result<Slot, StoredTransaction> {
+    Ok(tx)
+}
+
+/// Encode a transaction for storage
+///
+/// # Returns
+///
+/// * `Ok(Slot)` - The encoded transaction
+/// * `Err(StoredTransaction)` - The transaction itself, if it has no compact
+///   encoding and has to be kept at full width
+#[cfg(feature = "compact-store")]
+pub(crate) fn pack(tx: StoredTransaction) -> Result<Slot, StoredTransaction> {
+    CompactTransaction::encode(&tx).ok_or(tx)
+}
+
+/// Decode a stored transaction
+#[cfg(not(feature = "compact-store"))]
+pub(crate) fn unpack(slot: &Slot) -> StoredTransaction {
+    slot.clone()
+}
+
+/// Decode a stored transaction
+#[cfg(feature = "compact-store")]
+pub(crate) fn unpack(slot: &Slot) -> StoredTransaction {
+    slot.decode()
+}
+
+/// Flag bit of a withdrawal; deposits leave it clear
+#[cfg(feature = "compact-store")]
+const WITHDRAWAL: u16 = 1;
+#[cfg(feature = "compact-store")]
+const STATE_SHIFT: u16 = 1;
+#[cfg(feature = "compact-store")]
+const SCALE_SHIFT: u16 = 3;
+#[cfg(feature = "compact-store")]
+const DISPUTES_SHIFT: u16 = 8;
+/// Largest dispute count that fits in the flags
+#[cfg(feature = "compact-store")]
+const MAX_DISPUTES: u32 = 0xff;
+
+/// A deposit or withdrawal packed into 12 bytes (with the default `ClientId`)
+#[cfg(feature = "compact-store")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CompactTransaction {
+    /// Mantissa of the amount, split into halves so the alignment stays at 4
+    amount: [u32; 2],
+    client: ClientId,
+    /// Transaction type (bit 0), dispute state (bits 1-2), amount scale
+    /// (bits 3-7) and dispute count (bits 8-15)
+    flags: u16,
+}
+
+#[cfg(feature = "compact-store")]
+impl CompactTransaction {
+    /// Encode a transaction, or `None` if it cannot be represented exactly
+    fn encode(tx: &StoredTransaction) -> Option<Self> {
+        let tx_type = match tx.tx_type {
+            TransactionType::Deposit => 0,
+            TransactionType::Withdrawal => WITHDRAWAL,
+            _ => return None,
+        };
+        let state: u16 = match tx.dispute_state {
+            DisputeState::None => 0,
+            DisputeState::Disputed => 1,
+            DisputeState::Resolved => 2,
+            DisputeState::ChargedBack => 3,
+            DisputeState::Pending | DisputeState::Rejected => return None,
+        };
+        let mantissa = i64::try_from(tx.amount.mantissa()).ok()?;
+        // The sign of a negative zero is not in its mantissa
+        if (mantissa == 0 && tx.amount.is_sign_negative()) || tx.disputes > MAX_DISPUTES {
+            return None;
+        }
+        let bits = mantissa as u64;
+        Some(Self {
+            amount: [bits as u32, (bits >> 32) as u32],
+            client: tx.client,
+            flags: tx_type
+                | state << STATE_SHIFT
+                | (tx.amount.scale() as u16) << SCALE_SHIFT
+                | (tx.disputes as u16) << DISPUTES_SHIFT,
+        })
+    }
+
+    /// Decode the transaction
+    fn decode(&self) -> StoredTransaction {
+        let mantissa = (u64::from(self.amount[1]) << 32 | u64::from(self.amount[0])) as i64;
+        let scale = u32::from(self.flags >> SCALE_SHIFT & 0x1f);
+        StoredTransaction {
+            client: self.client,
+            amount: Decimal::new(mantissa, scale),
+            tx_type: if self.flags & WITHDRAWAL == 0 {
+                TransactionType::Deposit
+            } else {
+                TransactionType::Withdrawal
+            },
+            dispute_state: match self.flags >> STATE_SHIFT & 0b11 {
+                0 => DisputeState::None,
+                1 => DisputeState::Disputed,
+                2 => DisputeState::Resolved,
+                _ => DisputeState::ChargedBack,
+            },
+            disputes: u32::from(self.flags >> DISPUTES_SHIFT),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DisputeState, TransactionType};
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn stored(amount: &str, tx_type: TransactionType, disputes: u32) -> StoredTransaction {
+        StoredTransaction {
+            client: 7,
+            amount: Decimal::from_str(amount).unwrap(),
+            tx_type,
+            dispute_state: DisputeState::Resolved,
+            disputes,
+        }
+    }
+
+    #[rstest]
+    #[case::deposit(stored("1.5", TransactionType::Deposit, 0))]
+    #[case::withdrawal(stored("0.0001", TransactionType::Withdrawal, 3))]
+    #[case::many_places(stored("12.3456789", TransactionType::Deposit, 1))]
+    #[case::negative(stored("-922337203685477.5808", TransactionType::Deposit, 0))]
+    #[case::max_disputes(stored("2", TransactionType::Withdrawal, 255))]
+    fn test_round_trip(#[case] tx: StoredTransaction) {
+        let slot = pack(tx.clone()).unwrap();
+        let decoded = unpack(&slot);
+        assert_eq!(decoded, tx);
+        // The scale survives, so amounts print the same
+        assert_eq!(decoded.amount.to_string(), tx.amount.to_string());
+    }
+
+    #[cfg(feature = "compact-store")]
+    #[rstest]
+    #[case::wide_amount(stored("922337203685477.5808", TransactionType::Deposit, 0))]
+    #[case::negative_zero({
+        let mut tx = stored("0.0", TransactionType::Deposit, 0);
+        tx.amount.set_sign_negative(true);
+        tx
+    })]
+    #[case::too_many_disputes(stored("1", TransactionType::Deposit, 256))]
+    #[case::not_disputable(stored("1", TransactionType::Dispute, 0))]
+    #[case::pending({
+        let mut tx = stored("1", TransactionType::Withdrawal, 0);
+        tx.dispute_state = DisputeState::Pending;
+        tx
+    })]
+    fn test_unrepresentable_is_handed_back(#[case] tx: StoredTransaction) {
+        assert_eq!(pack(tx.clone()), Err(tx));
+    }
+
+    #[cfg(all(
+        feature = "compact-store",
+        not(any(feature = "client-id-u32", feature = "client-id-u64"))
+    ))]
+    #[test]
+    fn test_half_the_size() {
+        assert_eq!(std::mem::size_of::<StoredTransaction>(), 24);
+        assert_eq!(std::mem::size_of::<Slot>(), 12);
+    }
+}
@@ -0,0 +1,1020 @@
+//! Engine configuration
+//!
+//! This module provides `EngineConfig`, the settings shared by the synchronous
+//! and asynchronous engines:
+//! - Account metadata, attached to accounts when they are created
+//! - Risk rules evaluated against that metadata (e.g. blocking withdrawals for
+//!   clients that have not passed KYC)
+//! - The duplicate transaction ID policy: reject duplicates, ignore them, or
+//!   only require IDs to be unique per client
+//! - Dispute policies (e.g. whether resolved transactions can be disputed again,
+//!   whether chargebacks may arrive without a preceding dispute, or whether a
+//!   dispute may leave the client in debt, or after how many records an
+//!   unanswered dispute expires, and which transaction types can be
+//!   disputed at all)
+//! - Amount limits (maximum deposit, withdrawal and total balance), which
+//!   catch mistyped amounts before they reach the balances
+//! - Velocity limits on the withdrawals within a client's recent transactions
+//! - Fees charged on deposits and withdrawals (see `fees`)
+//! - Whether to record double-entry postings for a journal, and how often to
+//!   sample each client's balance history
+//!
+//! The amount limits, velocity limit, withdrawal requirements and held funds
+//! interest together are the `RiskRules`, which a running engine can replace without losing its
+//! state (see `TransactionEngine::set_risk_rules`).
+
+use crate::core::fees::FeeSchedule;
+use crate::core::velocity::VelocityLimit;
+use crate::types::transaction::operation_name;
+use crate::types::{
+    AccountMetadata, ClientId, DisputeState, PaymentError, StoredTransaction, TransactionId,
+    TransactionType,
+};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Account metadata for every known client
+pub type MetadataMap = HashMap<ClientId, Arc<AccountMetadata>>;
+
+/// Requirement that an account metadata field has a specific value
+///
+/// Parsed from `KEY=VALUE`, e.g. `kyc_status=verified`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataRequirement {
+    /// Metadata key to check
+    pub key: String,
+    /// Value the key must have
+    pub value: String,
+}
+
+impl MetadataRequirement {
+    /// Returns true if the metadata satisfies this requirement
+    ///
+    /// Clients without metadata, or without the key, never satisfy it.
+    pub fn is_met(&self, metadata: Option<&AccountMetadata>) -> bool {
+        metadata
+            .and_then(|metadata| metadata.get(&self.key))
+            .is_some_and(|value| *value == self.value)
+    }
+}
+
+impl FromStr for MetadataRequirement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(MetadataRequirement {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+            }),
+            _ => Err(format!("Invalid requirement '{}': expected KEY=VALUE", s)),
+        }
+    }
+}
+
+impl fmt::Display for MetadataRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// Policy for disputing a transaction again after its dispute was resolved
+///
+/// Parsed from `unlimited`, `never`, `once` or a number of re-disputes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RedisputePolicy {
+    /// Resolved transactions can be disputed again any number of times
+    #[default]
+    Unlimited,
+    /// Resolved transactions can be disputed again at most this many times
+    /// (`Limit(0)` never allows a re-dispute)
+    Limit(u32),
+}
+
+impl RedisputePolicy {
+    /// Check whether a stored transaction may be disputed under this policy
+    ///
+    /// Only resolved transactions are affected; other states are validated by
+    /// the dispute lifecycle itself.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the dispute is allowed
+    /// * `Err(PaymentError::RedisputeNotAllowed)` if re-disputes are disabled
+    /// * `Err(PaymentError::RedisputeLimitReached)` if the limit has been used up
+    pub fn check(&self, tx: TransactionId, stored: &StoredTransaction) -> Result<(), PaymentError> {
+        if stored.dispute_state != DisputeState::Resolved {
+            return Ok(());
+        }
+
+        // The first dispute is not a re-dispute
+        let redisputes = stored.disputes.saturating_sub(1);
+        match *self {
+            RedisputePolicy::Unlimited => Ok(()),
+            RedisputePolicy::Limit(0) => {
+                Err(PaymentError::redispute_not_allowed(tx, stored.client))
+            }
+            RedisputePolicy::Limit(limit) if redisputes >= limit => Err(
+                PaymentError::redispute_limit_reached(tx, stored.client, limit),
+            ),
+            RedisputePolicy::Limit(_) => Ok(()),
+        }
+    }
+}
+
+impl FromStr for RedisputePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unlimited" => Ok(RedisputePolicy::Unlimited),
+            "never" => Ok(RedisputePolicy::Limit(0)),
+            "once" => Ok(RedisputePolicy::Limit(1)),
+            _ => s.parse().map(RedisputePolicy::Limit).map_err(|_| {
+                format!(
+                    "Invalid re-dispute policy '{}': expected 'unlimited', 'never', 'once' or a number",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for RedisputePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedisputePolicy::Unlimited => write!(f, "unlimited"),
+            RedisputePolicy::Limit(0) => write!(f, "never"),
+            RedisputePolicy::Limit(1) => write!(f, "once"),
+            RedisputePolicy::Limit(limit) => write!(f, "{}", limit),
+        }
+    }
+}
+
+/// Transaction types that can be disputed
+///
+/// Parsed from `deposits`, `deposits-withdrawals` or `all`. Schemes differ:
+/// some only allow disputing deposits, others withdrawals as well.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisputableTypes {
+    /// Only deposits can be disputed
+    Deposits,
+    /// Deposits and withdrawals can be disputed
+    DepositsAndWithdrawals,
+    /// Every stored transaction can be disputed, whatever its type
+    #[default]
+    All,
+}
+
+impl DisputableTypes {
+    /// Check whether a stored transaction's type can be disputed
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the transaction's type is disputable
+    /// * `Err(PaymentError::TransactionNotDisputable)` otherwise
+    pub fn check(&self, tx: TransactionId, stored: &StoredTransaction) -> Result<(), PaymentError> {
+        let disputable = match self {
+            DisputableTypes::Deposits => stored.tx_type == TransactionType::Deposit,
+            DisputableTypes::DepositsAndWithdrawals => matches!(
+                stored.tx_type,
+                TransactionType::Deposit | TransactionType::Withdrawal
+            ),
+            DisputableTypes::All => true,
+        };
+        if disputable {
+            Ok(())
+        } else {
+            Err(PaymentError::transaction_not_disputable(
+                tx,
+                stored.client,
+                operation_name(stored.tx_type),
+            ))
+        }
+    }
+}
+
+impl FromStr for DisputableTypes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deposits" => Ok(DisputableTypes::Deposits),
+            "deposits-withdrawals" => Ok(DisputableTypes::DepositsAndWithdrawals),
+            "all" => Ok(DisputableTypes::All),
+            _ => Err(format!(
+                "Invalid disputable types '{}': expected 'deposits', 'deposits-withdrawals' or 'all'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DisputableTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisputableTypes::Deposits => write!(f, "deposits"),
+            DisputableTypes::DepositsAndWithdrawals => write!(f, "deposits-withdrawals"),
+            DisputableTypes::All => write!(f, "all"),
+        }
+    }
+}
+
+/// Policy for disputes on funds the client no longer has available
+///
+/// Parsed from `reject` or `allow`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NegativeBalancePolicy {
+    /// Reject the dispute, leaving the funds unheld
+    #[default]
+    Reject,
+    /// Hold the funds anyway, driving available negative (a debt)
+    AllowDebt,
+}
+
+impl NegativeBalancePolicy {
+    /// Check whether a dispute may hold `amount` from an account's available funds
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the hold is allowed
+    /// * `Err(PaymentError::InsufficientAvailableFunds)` if it would leave
+    ///   available negative and debt is not allowed
+    pub fn check_hold(
+        &self,
+        client: ClientId,
+        available: Decimal,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
+        match self {
+            NegativeBalancePolicy::Reject if available < amount => Err(
+                PaymentError::insufficient_available_funds(client, available, amount, "dispute"),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for NegativeBalancePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(NegativeBalancePolicy::Reject),
+            "allow" => Ok(NegativeBalancePolicy::AllowDebt),
+            _ => Err(format!(
+                "Invalid negative balance policy '{}': expected 'reject' or 'allow'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NegativeBalancePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NegativeBalancePolicy::Reject => write!(f, "reject"),
+            NegativeBalancePolicy::AllowDebt => write!(f, "allow"),
+        }
+    }
+}
+
+/// Policy for deposits and withdrawals reusing a transaction ID
+///
+/// Parsed from `reject`, `ignore` or `per-client`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateTxPolicy {
+    /// Reject a transaction whose ID is already stored, as an error
+    #[default]
+    Reject,
+    /// Skip a transaction whose ID is already stored, without an error
+    Ignore,
+    /// Transaction IDs only have to be unique per client: a client may reuse
+    /// another client's ID, and disputes refer to the disputing client's own
+    /// transaction (sequential engine only)
+    PerClient,
+}
+
+impl FromStr for DuplicateTxPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(DuplicateTxPolicy::Reject),
+            "ignore" => Ok(DuplicateTxPolicy::Ignore),
+            "per-client" => Ok(DuplicateTxPolicy::PerClient),
+            _ => Err(format!(
+                "Invalid duplicate transaction policy '{}': expected 'reject', 'ignore' or 'per-client'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DuplicateTxPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuplicateTxPolicy::Reject => write!(f, "reject"),
+            DuplicateTxPolicy::Ignore => write!(f, "ignore"),
+            DuplicateTxPolicy::PerClient => write!(f, "per-client"),
+        }
+    }
+}
+
+/// Maximum amounts accepted by the engine
+///
+/// Every limit is optional; `AmountLimits::default()` accepts any amount.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AmountLimits {
+    /// Largest amount of a single deposit
+    pub max_deposit: Option<Decimal>,
+    /// Largest amount of a single withdrawal
+    pub max_withdrawal: Option<Decimal>,
+    /// Largest total balance a deposit may leave a client with
+    pub max_total: Option<Decimal>,
+}
+
+impl AmountLimits {
+    /// Check a deposit against the per-transaction and total balance limits
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - The deposit's transaction ID
+    /// * `client` - The depositing client
+    /// * `amount` - The deposited amount
+    /// * `total` - The client's total balance before the deposit
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the deposit is within the limits
+    /// * `Err(PaymentError::AmountLimitExceeded)` if the amount is above `max_deposit`
+    /// * `Err(PaymentError::BalanceLimitExceeded)` if the new total would be above `max_total`
+    pub fn check_deposit(
+        &self,
+        tx: TransactionId,
+        client: ClientId,
+        amount: Decimal,
+        total: Decimal,
+    ) -> Result<(), PaymentError> {
+        if let Some(limit) = self.max_deposit.filter(|&limit| amount > limit) {
+            return Err(PaymentError::amount_limit_exceeded(
+                tx, client, "deposit", amount, limit,
+            ));
+        }
+        match self.max_total {
+            // A total that overflows is above any limit
+            Some(limit) if total.checked_add(amount).is_none_or(|new| new > limit) => Err(
+                PaymentError::balance_limit_exceeded(tx, client, total, amount, limit),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a withdrawal against the per-transaction limit
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the withdrawal is within the limit
+    /// * `Err(PaymentError::AmountLimitExceeded)` if the amount is above `max_withdrawal`
+    pub fn check_withdrawal(
+        &self,
+        tx: TransactionId,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
+        match self.max_withdrawal {
+            Some(limit) if amount > limit => Err(PaymentError::amount_limit_exceeded(
+                tx,
+                client,
+                "withdrawal",
+                amount,
+                limit,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Risk rules of the engines, which can be replaced while they run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RiskRules {
+    /// Maximum deposit, withdrawal and total balance amounts
+    pub limits: AmountLimits,
+    /// Maximum withdrawals within a client's recent transactions, if any
+    pub velocity_limit: Option<VelocityLimit>,
+    /// Requirements a client's metadata must meet for withdrawals to be accepted
+    pub withdrawal_requirements: Vec<MetadataRequirement>,
+    /// Interest rate credited on held funds released by a resolve, if any
+    pub held_interest: Option<Decimal>,
+}
+
+/// Configuration shared by the transaction engines
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfig {
+    /// Metadata attached to accounts when they are created, keyed by client
+    pub account_metadata: Arc<MetadataMap>,
+
+    /// Requirements a client's metadata must meet for withdrawals to be accepted
+    pub withdrawal_requirements: Vec<MetadataRequirement>,
+
+    /// What to do with deposits and withdrawals reusing a transaction ID
+    pub duplicate_tx_policy: DuplicateTxPolicy,
+
+    /// Transaction types that can be disputed
+    pub disputable_types: DisputableTypes,
+
+    /// Whether resolved transactions can be disputed again
+    pub redispute_policy: RedisputePolicy,
+
+    /// Whether a chargeback on a transaction that is not under dispute
+    /// implicitly disputes it first instead of being rejected
+    pub allow_direct_chargeback: bool,
+
+    /// Whether a dispute may drive available funds negative
+    pub negative_balance_policy: NegativeBalancePolicy,
+
+    /// Number of records after which a dispute that was neither resolved nor
+    /// charged back is resolved automatically, if any (sequential engine only)
+    pub dispute_expiry: Option<u32>,
+
+    /// Maximum deposit, withdrawal and total balance amounts
+    pub limits: AmountLimits,
+
+    /// Maximum withdrawals within a client's recent transactions, if any
+    pub velocity_limit: Option<VelocityLimit>,
+
+    /// Interest rate credited on the held funds of a dispute resolved in the
+    /// client's favor, if any
+    pub held_interest: Option<Decimal>,
+
+    /// Whether the engines record a `Posting` for every applied transaction
+    pub journal: bool,
+
+    /// Sample a client's balances after every this many of its applied
+    /// transactions, if set
+    pub balance_history: Option<u32>,
+
+    /// Withdrawals above this amount are held pending an `approve` or
+    /// `reject` record instead of being applied, if set
+    pub withdrawal_approval_above: Option<Decimal>,
+
+    /// Fees charged on deposits and withdrawals, if any
+    pub fees: Option<FeeSchedule>,
+
+    /// Expected number of distinct clients, to pre-size the account maps
+    pub expected_clients: Option<usize>,
+
+    /// Expected number of deposits and withdrawals, to pre-size the
+    /// transaction stores
+    pub expected_transactions: Option<usize>,
+}
+
+impl EngineConfig {
+    /// Create a configuration with no metadata and no risk rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the account metadata
+    pub fn with_account_metadata(mut self, metadata: MetadataMap) -> Self {
+        self.account_metadata = Arc::new(metadata);
+        self
+    }
+
+    /// Add a requirement that must be met for withdrawals to be accepted
+    pub fn with_withdrawal_requirement(mut self, requirement: MetadataRequirement) -> Self {
+        self.withdrawal_requirements.push(requirement);
+        self
+    }
+
+    /// Set the duplicate transaction ID policy
+    pub fn with_duplicate_tx_policy(mut self, policy: DuplicateTxPolicy) -> Self {
+        self.duplicate_tx_policy = policy;
+        self
+    }
+
+    /// Set the transaction types that can be disputed
+    pub fn with_disputable_types(mut self, types: DisputableTypes) -> Self {
+        self.disputable_types = types;
+        self
+    }
+
+    /// Set the re-dispute policy
+    pub fn with_redispute_policy(mut self, policy: RedisputePolicy) -> Self {
+        self.redispute_policy = policy;
+        self
+    }
+
+    /// Allow or reject chargebacks without a preceding dispute
+    pub fn with_direct_chargeback(mut self, allow: bool) -> Self {
+        self.allow_direct_chargeback = allow;
+        self
+    }
+
+    /// Set the negative balance policy for disputes
+    pub fn with_negative_balance_policy(mut self, policy: NegativeBalancePolicy) -> Self {
+        self.negative_balance_policy = policy;
+        self
+    }
+
+    /// Resolve disputes left open for more than `records` records
+    pub fn with_dispute_expiry(mut self, records: u32) -> Self {
+        self.dispute_expiry = Some(records);
+        self
+    }
+
+    /// Set the amount limits
+    pub fn with_limits(mut self, limits: AmountLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the velocity limit on withdrawals
+    pub fn with_velocity_limit(mut self, limit: VelocityLimit) -> Self {
+        self.velocity_limit = Some(limit);
+        self
+    }
+
+    /// Credit interest at `rate` on the held funds released by a resolve
+    pub fn with_held_interest(mut self, rate: Decimal) -> Self {
+        self.held_interest = Some(rate);
+        self
+    }
+
+    /// Replace the amount limits, velocity limit, withdrawal requirements and
+    /// held funds interest
+    pub fn with_risk_rules(mut self, rules: RiskRules) -> Self {
+        self.limits = rules.limits;
+        self.velocity_limit = rules.velocity_limit;
+        self.withdrawal_requirements = rules.withdrawal_requirements;
+        self.held_interest = rules.held_interest;
+        self
+    }
+
+    /// The amount limits, velocity limit, withdrawal requirements and held
+    /// funds interest
+    pub fn risk_rules(&self) -> RiskRules {
+        RiskRules {
+            limits: self.limits,
+            velocity_limit: self.velocity_limit,
+            withdrawal_requirements: self.withdrawal_requirements.clone(),
+            held_interest: self.held_interest,
+        }
+    }
+
+    /// The interest credited when `held` funds are released by a resolve
+    ///
+    /// Rounded to four decimal places, like the account output.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Decimal)` - The interest, zero without a held funds interest rate
+    /// * `None` - If the interest overflows
+    pub fn held_interest_on(&self, held: Decimal) -> Option<Decimal> {
+        match self.held_interest {
+            Some(rate) => held.checked_mul(rate).map(|interest| interest.round_dp(4)),
+            None => Some(Decimal::ZERO),
+        }
+    }
+
+    /// Record or stop recording postings for a journal
+    pub fn with_journal(mut self, journal: bool) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Sample each client's balances after every `every` applied transactions
+    pub fn with_balance_history(mut self, every: u32) -> Self {
+        self.balance_history = Some(every);
+        self
+    }
+
+    /// Hold withdrawals above `amount` until they are approved or rejected
+    pub fn with_withdrawal_approval_above(mut self, amount: Decimal) -> Self {
+        self.withdrawal_approval_above = Some(amount);
+        self
+    }
+
+    /// Returns true if a withdrawal of `amount` must wait for approval
+    pub fn needs_approval(&self, amount: Decimal) -> bool {
+        self.withdrawal_approval_above
+            .is_some_and(|threshold| amount > threshold)
+    }
+
+    /// Charge the fees of a fee schedule
+    pub fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = Some(fees);
+        self
+    }
+
+    /// The fee charged to `client` for a transaction of `amount`, and the
+    /// account it is credited to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some((ClientId, Decimal)))` - The fee account and the fee
+    /// * `Ok(None)` - If no fee is charged
+    /// * `Err(PaymentError::ArithmeticOverflow)` - If the fee overflows
+    pub fn fee(
+        &self,
+        tx_type: TransactionType,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<Option<(ClientId, Decimal)>, PaymentError> {
+        let Some(fees) = &self.fees else {
+            return Ok(None);
+        };
+        Ok(fees
+            .fee(tx_type, client, amount)?
+            .map(|fee| (fees.account, fee)))
+    }
+
+    /// Pre-size the account maps for `clients` distinct clients
+    pub fn with_expected_clients(mut self, clients: usize) -> Self {
+        self.expected_clients = Some(clients);
+        self
+    }
+
+    /// Pre-size the transaction stores for `transactions` deposits and
+    /// withdrawals
+    pub fn with_expected_transactions(mut self, transactions: usize) -> Self {
+        self.expected_transactions = Some(transactions);
+        self
+    }
+
+    /// Returns true if a chargeback on this transaction must dispute it first
+    ///
+    /// Only applies in direct chargeback mode, to transactions that are not
+    /// under dispute and have not been charged back.
+    pub fn needs_implicit_dispute(&self, stored: &StoredTransaction) -> bool {
+        self.allow_direct_chargeback
+            && matches!(
+                stored.dispute_state,
+                DisputeState::None | DisputeState::Resolved
+            )
+    }
+
+    /// Get the metadata for a client, if any
+    pub fn metadata(&self, client: ClientId) -> Option<&Arc<AccountMetadata>> {
+        self.account_metadata.get(&client)
+    }
+
+    /// Check the withdrawal risk rules for a client
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if every withdrawal requirement is met
+    /// * `Err(PaymentError::WithdrawalBlocked)` naming the first unmet requirement
+    pub fn check_withdrawal(&self, client: ClientId) -> Result<(), PaymentError> {
+        let metadata = self.metadata(client).map(|metadata| metadata.as_ref());
+        match self
+            .withdrawal_requirements
+            .iter()
+            .find(|requirement| !requirement.is_met(metadata))
+        {
+            Some(requirement) => Err(PaymentError::withdrawal_blocked(
+                client,
+                &format!("requires {}", requirement),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn config() -> EngineConfig {
+        let verified = AccountMetadata::from([("kyc_status".to_string(), "verified".to_string())]);
+        let pending = AccountMetadata::from([("kyc_status".to_string(), "pending".to_string())]);
+        EngineConfig::new()
+            .with_account_metadata(MetadataMap::from([
+                (1, Arc::new(verified)),
+                (2, Arc::new(pending)),
+            ]))
+            .with_withdrawal_requirement("kyc_status=verified".parse().unwrap())
+    }
+
+    #[rstest]
+    #[case::valid("kyc_status=verified", Ok(("kyc_status", "verified")))]
+    #[case::trimmed(" tier = gold ", Ok(("tier", "gold")))]
+    #[case::empty_value("flag=", Ok(("flag", "")))]
+    #[case::missing_separator("kyc_status", Err(()))]
+    #[case::missing_key("=verified", Err(()))]
+    fn test_parse_requirement(#[case] input: &str, #[case] expected: Result<(&str, &str), ()>) {
+        let parsed = input.parse::<MetadataRequirement>();
+        match expected {
+            Ok((key, value)) => {
+                let requirement = parsed.unwrap();
+                assert_eq!(requirement.key, key);
+                assert_eq!(requirement.value, value);
+            }
+            Err(()) => assert!(parsed.unwrap_err().contains("expected KEY=VALUE")),
+        }
+    }
+
+    #[rstest]
+    #[case::requirement_met(1, true)]
+    #[case::wrong_value(2, false)]
+    #[case::no_metadata(3, false)]
+    fn test_check_withdrawal(#[case] client: ClientId, #[case] allowed: bool) {
+        let result = config().check_withdrawal(client);
+
+        if allowed {
+            assert!(result.is_ok());
+        } else {
+            assert_eq!(
+                result.unwrap_err(),
+                PaymentError::withdrawal_blocked(client, "requires kyc_status=verified")
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::no_rate(None, 100, Some(Decimal::ZERO))]
+    #[case::rate(Some(Decimal::new(25, 3)), 100, Some(Decimal::new(25, 1)))]
+    #[case::rounded(Some(Decimal::new(1, 2)), 1, Some(Decimal::new(1, 2)))]
+    #[case::rounded_half_even(Some(Decimal::new(5, 5)), 1, Some(Decimal::ZERO))]
+    #[case::overflow(Some(Decimal::TEN), i64::MAX, None)]
+    fn test_held_interest_on(
+        #[case] rate: Option<Decimal>,
+        #[case] held: i64,
+        #[case] expected: Option<Decimal>,
+    ) {
+        let config = EngineConfig {
+            held_interest: rate,
+            ..EngineConfig::default()
+        };
+        let held = match held {
+            i64::MAX => Decimal::MAX,
+            held => Decimal::from(held),
+        };
+        assert_eq!(config.held_interest_on(held), expected);
+    }
+
+    #[rstest]
+    #[case::no_threshold(None, 1000, false)]
+    #[case::below(Some(100), 50, false)]
+    #[case::at_threshold(Some(100), 100, false)]
+    #[case::above(Some(100), 101, true)]
+    fn test_needs_approval(
+        #[case] threshold: Option<i64>,
+        #[case] amount: i64,
+        #[case] expected: bool,
+    ) {
+        let config = match threshold {
+            Some(threshold) => {
+                EngineConfig::new().with_withdrawal_approval_above(Decimal::from(threshold))
+            }
+            None => EngineConfig::new(),
+        };
+        assert_eq!(config.needs_approval(Decimal::from(amount)), expected);
+    }
+
+    #[rstest]
+    #[case::unlimited("unlimited", RedisputePolicy::Unlimited)]
+    #[case::never("never", RedisputePolicy::Limit(0))]
+    #[case::once("once", RedisputePolicy::Limit(1))]
+    #[case::number("3", RedisputePolicy::Limit(3))]
+    fn test_parse_redispute_policy(#[case] input: &str, #[case] expected: RedisputePolicy) {
+        let policy: RedisputePolicy = input.parse().unwrap();
+        assert_eq!(policy, expected);
+        assert_eq!(policy.to_string(), input);
+    }
+
+    #[test]
+    fn test_parse_redispute_policy_invalid() {
+        assert!("twice".parse::<RedisputePolicy>().is_err());
+        assert!("-1".parse::<RedisputePolicy>().is_err());
+    }
+
+    #[rstest]
+    #[case::unlimited(RedisputePolicy::Unlimited, DisputeState::Resolved, 5, Ok(()))]
+    #[case::never(
+        RedisputePolicy::Limit(0),
+        DisputeState::Resolved,
+        1,
+        Err(PaymentError::redispute_not_allowed(7, 1))
+    )]
+    #[case::once_first_redispute(RedisputePolicy::Limit(1), DisputeState::Resolved, 1, Ok(()))]
+    #[case::once_second_redispute(
+        RedisputePolicy::Limit(1),
+        DisputeState::Resolved,
+        2,
+        Err(PaymentError::redispute_limit_reached(7, 1, 1))
+    )]
+    #[case::first_dispute(RedisputePolicy::Limit(0), DisputeState::None, 0, Ok(()))]
+    fn test_redispute_policy_check(
+        #[case] policy: RedisputePolicy,
+        #[case] dispute_state: DisputeState,
+        #[case] disputes: u32,
+        #[case] expected: Result<(), PaymentError>,
+    ) {
+        let stored = StoredTransaction {
+            client: 1,
+            amount: Decimal::ONE,
+            tx_type: crate::types::TransactionType::Deposit,
+            dispute_state,
+            disputes,
+        };
+
+        assert_eq!(policy.check(7, &stored), expected);
+    }
+
+    #[rstest]
+    #[case::strict_undisputed(false, DisputeState::None, false)]
+    #[case::direct_undisputed(true, DisputeState::None, true)]
+    #[case::direct_resolved(true, DisputeState::Resolved, true)]
+    #[case::direct_disputed(true, DisputeState::Disputed, false)]
+    #[case::direct_charged_back(true, DisputeState::ChargedBack, false)]
+    #[case::direct_pending(true, DisputeState::Pending, false)]
+    fn test_needs_implicit_dispute(
+        #[case] allow_direct_chargeback: bool,
+        #[case] dispute_state: DisputeState,
+        #[case] expected: bool,
+    ) {
+        let config = EngineConfig::new().with_direct_chargeback(allow_direct_chargeback);
+        let stored = StoredTransaction {
+            client: 1,
+            amount: Decimal::ONE,
+            tx_type: crate::types::TransactionType::Deposit,
+            dispute_state,
+            disputes: 0,
+        };
+
+        assert_eq!(config.needs_implicit_dispute(&stored), expected);
+    }
+
+    #[rstest]
+    #[case::reject("reject", NegativeBalancePolicy::Reject)]
+    #[case::allow("allow", NegativeBalancePolicy::AllowDebt)]
+    fn test_parse_negative_balance_policy(
+        #[case] input: &str,
+        #[case] expected: NegativeBalancePolicy,
+    ) {
+        let policy: NegativeBalancePolicy = input.parse().unwrap();
+        assert_eq!(policy, expected);
+        assert_eq!(policy.to_string(), input);
+        assert!("debt".parse::<NegativeBalancePolicy>().is_err());
+    }
+
+    #[rstest]
+    #[case::deposits("deposits", DisputableTypes::Deposits)]
+    #[case::deposits_withdrawals("deposits-withdrawals", DisputableTypes::DepositsAndWithdrawals)]
+    #[case::all("all", DisputableTypes::All)]
+    fn test_parse_disputable_types(#[case] input: &str, #[case] expected: DisputableTypes) {
+        let types: DisputableTypes = input.parse().unwrap();
+        assert_eq!(types, expected);
+        assert_eq!(types.to_string(), input);
+        assert!("withdrawals".parse::<DisputableTypes>().is_err());
+    }
+
+    #[rstest]
+    #[case::deposits_deposit(DisputableTypes::Deposits, TransactionType::Deposit, true)]
+    #[case::deposits_withdrawal(DisputableTypes::Deposits, TransactionType::Withdrawal, false)]
+    #[case::both_withdrawal(
+        DisputableTypes::DepositsAndWithdrawals,
+        TransactionType::Withdrawal,
+        true
+    )]
+    #[case::all_withdrawal(DisputableTypes::All, TransactionType::Withdrawal, true)]
+    fn test_disputable_types_check(
+        #[case] types: DisputableTypes,
+        #[case] tx_type: TransactionType,
+        #[case] expected: bool,
+    ) {
+        let stored = StoredTransaction {
+            client: 1,
+            amount: Decimal::ONE,
+            tx_type,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+
+        let result = types.check(7, &stored);
+        if expected {
+            assert_eq!(result, Ok(()));
+        } else {
+            assert_eq!(
+                result,
+                Err(PaymentError::transaction_not_disputable(7, 1, "withdrawal"))
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::reject("reject", DuplicateTxPolicy::Reject)]
+    #[case::ignore("ignore", DuplicateTxPolicy::Ignore)]
+    #[case::per_client("per-client", DuplicateTxPolicy::PerClient)]
+    fn test_parse_duplicate_tx_policy(#[case] input: &str, #[case] expected: DuplicateTxPolicy) {
+        let policy: DuplicateTxPolicy = input.parse().unwrap();
+        assert_eq!(policy, expected);
+        assert_eq!(policy.to_string(), input);
+        assert!("allow".parse::<DuplicateTxPolicy>().is_err());
+    }
+
+    #[rstest]
+    #[case::reject_covered(NegativeBalancePolicy::Reject, 10, true)]
+    #[case::reject_short(NegativeBalancePolicy::Reject, 5, false)]
+    #[case::allow_short(NegativeBalancePolicy::AllowDebt, 5, true)]
+    #[case::allow_negative(NegativeBalancePolicy::AllowDebt, -5, true)]
+    fn test_negative_balance_policy_check_hold(
+        #[case] policy: NegativeBalancePolicy,
+        #[case] available: i64,
+        #[case] allowed: bool,
+    ) {
+        let result = policy.check_hold(1, Decimal::from(available), Decimal::TEN);
+
+        if allowed {
+            assert!(result.is_ok());
+        } else {
+            assert_eq!(
+                result.unwrap_err(),
+                PaymentError::insufficient_available_funds(
+                    1,
+                    Decimal::from(available),
+                    Decimal::TEN,
+                    "dispute"
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_config_allows_withdrawals() {
+        assert!(EngineConfig::default().check_withdrawal(1).is_ok());
+    }
+
+    fn limits() -> AmountLimits {
+        AmountLimits {
+            max_deposit: Some(Decimal::from(1000)),
+            max_withdrawal: Some(Decimal::from(500)),
+            max_total: Some(Decimal::from(1500)),
+        }
+    }
+
+    #[rstest]
+    #[case::within(1000, 0, Ok(()))]
+    #[case::up_to_max_total(500, 1000, Ok(()))]
+    #[case::above_max_deposit(
+        1001,
+        0,
+        Err(PaymentError::amount_limit_exceeded(
+            7,
+            1,
+            "deposit",
+            Decimal::from(1001),
+            Decimal::from(1000)
+        ))
+    )]
+    #[case::above_max_total(
+        600,
+        1000,
+        Err(PaymentError::balance_limit_exceeded(
+            7,
+            1,
+            Decimal::from(1000),
+            Decimal::from(600),
+            Decimal::from(1500)
+        ))
+    )]
+    fn test_limits_check_deposit(
+        #[case] amount: i64,
+        #[case] total: i64,
+        #[case] expected: Result<(), PaymentError>,
+    ) {
+        assert_eq!(
+            limits().check_deposit(7, 1, Decimal::from(amount), Decimal::from(total)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_limits_check_withdrawal() {
+        assert!(limits().check_withdrawal(7, 1, Decimal::from(500)).is_ok());
+        assert_eq!(
+            limits().check_withdrawal(7, 1, Decimal::from(501)),
+            Err(PaymentError::amount_limit_exceeded(
+                7,
+                1,
+                "withdrawal",
+                Decimal::from(501),
+                Decimal::from(500)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_default_limits_accept_any_amount() {
+        let limits = AmountLimits::default();
+        assert!(limits
+            .check_deposit(7, 1, Decimal::MAX, Decimal::MAX)
+            .is_ok());
+        assert!(limits.check_withdrawal(7, 1, Decimal::MAX).is_ok());
+    }
+}
@@ -7,10 +7,26 @@
 //! - Account lock checks before processing transactions
 //! - Transaction validation (amounts present, client matching, etc.)
 //! - Proper dispute lifecycle management (dispute → resolve/chargeback)
+//! - Risk rules from the `EngineConfig` (e.g. withdrawal requirements)
+//! - Expiration of disputes left open for too long (`EngineConfig::dispute_expiry`)
+//! - Approval of large withdrawals (`EngineConfig::withdrawal_approval_above`)
+//! - Interest on held funds released by a resolve (`EngineConfig::held_interest`)
+//! - Fees on deposits and withdrawals (`EngineConfig::fees`)
 
 use crate::core::account_manager::AccountManager;
+use crate::core::config::{DuplicateTxPolicy, EngineConfig, NegativeBalancePolicy, RiskRules};
+use crate::core::expiry::{DisputeExpiry, ExpiredDispute};
+use crate::core::flows::MoneyFlows;
+use crate::core::history::{BalanceHistory, BalancePoint};
+use crate::core::journal::Posting;
+use crate::core::traits::{Engine, EngineSnapshot};
 use crate::core::transaction_store::TransactionStore;
-use crate::types::{Account, PaymentError, StoredTransaction, TransactionRecord, TransactionType};
+use crate::core::velocity::VelocityTracker;
+use crate::types::{
+    Account, ClientId, DisputeState, PaymentError, StoredTransaction, TransactionId,
+    TransactionRecord, TransactionType,
+};
+use rust_decimal::Decimal;
 
 /// Transaction processing engine
 ///
@@ -19,6 +35,19 @@ use crate::types::{Account, PaymentError, StoredTransaction, TransactionRecord,
 pub struct TransactionEngine {
     account_manager: AccountManager,
     transaction_store: TransactionStore,
+    config: EngineConfig,
+    /// Money moved by the transactions applied by this engine
+    flows: MoneyFlows,
+    /// Recent transactions of every client, if a velocity limit is configured
+    velocity: Option<VelocityTracker>,
+    /// Open disputes, if a dispute expiry is configured
+    expiry: Option<DisputeExpiry>,
+    /// Disputes expired since the last `take_expired_disputes`
+    expired: Vec<ExpiredDispute>,
+    /// Postings since the last `take_postings`, if the journal is enabled
+    postings: Vec<Posting>,
+    /// Sampled balances of every client, if a balance history is configured
+    history: Option<BalanceHistory>,
 }
 
 impl TransactionEngine {
@@ -30,12 +59,83 @@ impl TransactionEngine {
     ///
     /// A new TransactionEngine ready to process transactions
     pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    /// Create a new TransactionEngine with the given configuration
+    ///
+    /// Accounts are created with the configured metadata attached, and the
+    /// configured risk rules are enforced.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Engine configuration
+    ///
+    /// # Returns
+    ///
+    /// A new TransactionEngine ready to process transactions
+    pub fn with_config(config: EngineConfig) -> Self {
+        let clients = config.expected_clients.unwrap_or(0);
+        let transactions = config.expected_transactions.unwrap_or(0);
         TransactionEngine {
-            account_manager: AccountManager::new(),
-            transaction_store: TransactionStore::new(),
+            account_manager: AccountManager::with_capacity(clients)
+                .with_metadata(config.account_metadata.clone()),
+            transaction_store: TransactionStore::with_capacity(transactions, clients),
+            velocity: config.velocity_limit.map(VelocityTracker::new),
+            expiry: config.dispute_expiry.map(DisputeExpiry::new),
+            expired: Vec::new(),
+            postings: Vec::new(),
+            history: config.balance_history.map(BalanceHistory::new),
+            config,
+            flows: MoneyFlows::default(),
         }
     }
 
+    /// Create a TransactionEngine seeded with existing state
+    ///
+    /// Used by persistent backends that load the accounts and stored
+    /// transactions relevant to a record, apply it with the regular business
+    /// rules, and write the resulting state back. The seeded balances are not
+    /// counted in the engine's `MoneyFlows`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Engine configuration
+    /// * `accounts` - Existing account states
+    /// * `transactions` - Existing stored transactions keyed by transaction ID
+    ///
+    /// # Returns
+    ///
+    /// A TransactionEngine containing the given state
+    pub fn with_state(
+        config: EngineConfig,
+        accounts: impl IntoIterator<Item = Account>,
+        transactions: impl IntoIterator<Item = (TransactionId, StoredTransaction)>,
+    ) -> Self {
+        let mut engine = Self::with_config(config);
+        for account in accounts {
+            engine.account_manager.insert_account(account);
+        }
+        for (tx_id, tx) in transactions {
+            engine.store_transaction(tx_id, tx);
+        }
+        engine
+    }
+
+    /// Replace the risk rules without losing any state
+    ///
+    /// Later transactions are checked against the new amount limits, velocity
+    /// limit and withdrawal requirements. A velocity limit that replaces
+    /// another keeps counting the transactions recorded within its window; one
+    /// that is added starts counting from the next transaction.
+    pub fn set_risk_rules(&mut self, rules: RiskRules) {
+        match (&mut self.velocity, rules.velocity_limit) {
+            (Some(velocity), Some(limit)) => velocity.set_limit(limit),
+            (velocity, limit) => *velocity = limit.map(VelocityTracker::new),
+        }
+        self.config = std::mem::take(&mut self.config).with_risk_rules(rules);
+    }
+
     /// Process a single transaction record
     ///
     /// Routes the transaction to the appropriate handler based on transaction type.
@@ -58,19 +158,134 @@ impl TransactionEngine {
     /// - The transaction validation fails
     /// - The account operation fails (insufficient funds, arithmetic overflow, etc.)
     pub fn process(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
+        // Disputes left open for too long are resolved before the next record
+        self.expire_disputes();
+
+        // Reused transaction IDs are skipped without an error if so
+        // configured, and are not counted in the client's activity
+        if self.config.duplicate_tx_policy == DuplicateTxPolicy::Ignore
+            && self.is_duplicate(&record)
+        {
+            return Ok(());
+        }
+
+        let (tx_type, client, tx) = (record.tx_type, record.client, record.tx);
+        let result = self.apply(record);
+
+        // Count the record in the client's activity, accepted or not
+        self.account_manager
+            .record_activity(client, tx_type, tx, result.is_ok());
+        result
+    }
+
+    /// Apply a record to the accounts and the stored transactions, and track
+    /// its effects (money flows, velocity, open disputes, balance history)
+    fn apply(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
         // Check if account is locked (except for chargebacks which lock the account)
         // Note: We check before processing to prevent any operations on locked accounts
         if self.account_manager.is_locked(record.client) {
             return Err(PaymentError::account_locked(record.client));
         }
 
+        let (tx_type, client, tx, amount) =
+            (record.tx_type, record.client, record.tx, record.amount);
         match record.tx_type {
             TransactionType::Deposit => self.process_deposit(record),
             TransactionType::Withdrawal => self.process_withdrawal(record),
             TransactionType::Dispute => self.process_dispute(record),
             TransactionType::Resolve => self.process_resolve(record),
             TransactionType::Chargeback => self.process_chargeback(record),
+            TransactionType::Approve | TransactionType::Reject => self.process_approval(record),
+        }?;
+
+        // Track the money moved; a chargeback reverses the stored transaction,
+        // an approve completes it, and a pending withdrawal moves nothing yet
+        let moved = match tx_type {
+            TransactionType::Chargeback | TransactionType::Approve => self
+                .transaction_store
+                .get_for_client(client, tx)
+                .map(|tx| tx.amount),
+            TransactionType::Withdrawal
+                if amount.is_some_and(|amount| self.config.needs_approval(amount)) =>
+            {
+                None
+            }
+            _ => amount,
+        };
+        if let Some(moved) = moved {
+            self.flows.record(tx_type, moved);
+        }
+
+        // Count the transaction towards the client's velocity window
+        if let Some(velocity) = &mut self.velocity {
+            velocity.record(
+                client,
+                amount.filter(|_| tx_type == TransactionType::Withdrawal),
+            );
+        }
+
+        // Track the disputes that can expire
+        if let Some(expiry) = &mut self.expiry {
+            match tx_type {
+                TransactionType::Dispute => expiry.opened(client, tx),
+                TransactionType::Resolve | TransactionType::Chargeback => expiry.closed(client, tx),
+                TransactionType::Deposit
+                | TransactionType::Withdrawal
+                | TransactionType::Approve
+                | TransactionType::Reject => {}
+            }
         }
+
+        // Sample the client's balances
+        if let (Some(history), Some(account)) =
+            (&mut self.history, self.account_manager.get_account(client))
+        {
+            history.record(account);
+        }
+        Ok(())
+    }
+
+    /// Count a new record and resolve the disputes that expire before it
+    ///
+    /// An expired dispute is resolved like a resolve record from its client,
+    /// so it stays open if the client's account is locked. Each dispute that
+    /// is resolved is reported as an `ExpiredDispute`.
+    fn expire_disputes(&mut self) {
+        let Some(expiry) = &mut self.expiry else {
+            return;
+        };
+        let expired_at = expiry.records() + 1;
+        for (client, tx, disputed_at) in expiry.next_record() {
+            let Some(stored_tx) = self.transaction_store.get_for_client(client, tx) else {
+                continue;
+            };
+            let amount = stored_tx.amount;
+            if self.account_manager.is_locked(client) {
+                continue;
+            }
+            let resolve = TransactionRecord {
+                tx_type: TransactionType::Resolve,
+                client,
+                tx,
+                amount: None,
+                line: None,
+                source: None,
+            };
+            if self.process_resolve(resolve).is_ok() {
+                self.expired.push(ExpiredDispute {
+                    tx,
+                    client,
+                    amount,
+                    disputed_at,
+                    expired_at,
+                });
+            }
+        }
+    }
+
+    /// Take the audit events of the disputes expired so far
+    pub fn take_expired_disputes(&mut self) -> Vec<ExpiredDispute> {
+        std::mem::take(&mut self.expired)
     }
 
     /// Process a deposit transaction
@@ -93,6 +308,8 @@ impl TransactionEngine {
     /// Returns an error if:
     /// - The amount field is missing
     /// - The transaction ID is a duplicate (already exists)
+    /// - The amount or the resulting total is above the configured limits
+    /// - The balance after the deposit does not cover its fee
     /// - The account operation fails (arithmetic overflow)
     fn process_deposit(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
         let amount = record
@@ -100,27 +317,48 @@ impl TransactionEngine {
             .ok_or_else(|| PaymentError::missing_amount("deposit", record.tx, record.client))?;
 
         // Check for duplicate transaction ID
-        if self.transaction_store.get(record.tx).is_some() {
+        if self.is_duplicate(&record) {
             return Err(PaymentError::duplicate_transaction(
                 record.tx,
                 record.client,
             ));
         }
 
-        // Update account
-        self.account_manager.deposit(record.client, amount)?;
+        // Apply amount limits
+        let account = self.account_manager.get_or_create_account(record.client);
+        let (available, total) = (account.available, account.total);
+        self.config
+            .limits
+            .check_deposit(record.tx, record.client, amount, total)?;
+
+        // The fee, if any, must be covered by the balance after the deposit
+        let fee = self.config.fee(record.tx_type, record.client, amount)?;
+        if let Some((_, fee)) = fee {
+            let funds = available.saturating_add(amount);
+            if funds < fee {
+                return Err(PaymentError::insufficient_funds(record.client, funds, fee));
+            }
+        }
+
+        // Update account, and move the fee to the fee account
+        self.apply_with_fee(record.client, fee, |accounts| {
+            accounts.deposit(record.client, amount)
+        })?;
 
         // Store transaction for potential disputes
-        self.transaction_store.store(
+        self.store_transaction(
             record.tx,
             StoredTransaction {
                 client: record.client,
                 amount,
                 tx_type: TransactionType::Deposit,
-                under_dispute: false,
+                dispute_state: DisputeState::None,
+                disputes: 0,
             },
         );
 
+        self.post(record.tx_type, record.client, record.tx, amount);
+        self.post_fee(&record, fee);
         Ok(())
     }
 
@@ -130,6 +368,10 @@ impl TransactionEngine {
     /// checks for sufficient funds, updates the account balance, and stores
     /// the transaction for potential future disputes.
     ///
+    /// A withdrawal above `EngineConfig::withdrawal_approval_above` is held
+    /// instead: its funds move from available to held, and it is stored as
+    /// `Pending` until an approve or reject record completes it.
+    ///
     /// # Arguments
     ///
     /// * `record` - The withdrawal transaction record
@@ -144,7 +386,10 @@ impl TransactionEngine {
     /// Returns an error if:
     /// - The amount field is missing
     /// - The transaction ID is a duplicate (already exists)
-    /// - Insufficient available funds
+    /// - The client does not meet the configured withdrawal requirements
+    /// - The amount is above the configured limit, or the withdrawal exceeds
+    ///   the velocity limit
+    /// - Insufficient available funds, including for the withdrawal's fee
     /// - The account operation fails (arithmetic underflow)
     fn process_withdrawal(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
         let amount = record
@@ -152,27 +397,81 @@ impl TransactionEngine {
             .ok_or_else(|| PaymentError::missing_amount("withdrawal", record.tx, record.client))?;
 
         // Check for duplicate transaction ID
-        if self.transaction_store.get(record.tx).is_some() {
+        if self.is_duplicate(&record) {
             return Err(PaymentError::duplicate_transaction(
                 record.tx,
                 record.client,
             ));
         }
 
-        // Update account (will fail if insufficient funds)
-        self.account_manager.withdraw(record.client, amount)?;
+        // Apply risk rules and amount limits
+        self.config.check_withdrawal(record.client)?;
+        self.config
+            .limits
+            .check_withdrawal(record.tx, record.client, amount)?;
+        if let Some(velocity) = &self.velocity {
+            velocity.check_withdrawal(record.tx, record.client, amount)?;
+        }
+
+        // The available funds must cover the fee, if any, on top
+        let fee = self.config.fee(record.tx_type, record.client, amount)?;
+        if let Some((_, fee)) = fee {
+            let available = self
+                .account_manager
+                .get_account(record.client)
+                .map_or(Decimal::ZERO, |account| account.available);
+            let needed = amount
+                .checked_add(fee)
+                .ok_or_else(|| PaymentError::arithmetic_overflow("withdrawal", record.client))?;
+            if available < needed {
+                return Err(PaymentError::insufficient_funds(
+                    record.client,
+                    available,
+                    needed,
+                ));
+            }
+        }
 
-        // Store transaction for potential disputes
-        self.transaction_store.store(
+        // Update account (will fail if insufficient funds), or hold the funds
+        // of a withdrawal that needs approval, and move the fee to the fee
+        // account
+        let pending = self.config.needs_approval(amount);
+        self.apply_with_fee(record.client, fee, |accounts| {
+            if pending {
+                accounts.hold_withdrawal(record.client, amount)
+            } else {
+                accounts.withdraw(record.client, amount)
+            }
+        })?;
+
+        // Store transaction for potential disputes, or for its approval
+        self.store_transaction(
             record.tx,
             StoredTransaction {
                 client: record.client,
                 amount,
                 tx_type: TransactionType::Withdrawal,
-                under_dispute: false,
+                dispute_state: if pending {
+                    DisputeState::Pending
+                } else {
+                    DisputeState::None
+                },
+                disputes: 0,
             },
         );
 
+        if pending {
+            if self.config.journal {
+                self.postings.push(Posting::pending_withdrawal(
+                    record.client,
+                    record.tx,
+                    amount,
+                ));
+            }
+        } else {
+            self.post(record.tx_type, record.client, record.tx, amount);
+        }
+        self.post_fee(&record, fee);
         Ok(())
     }
 
@@ -196,13 +495,15 @@ impl TransactionEngine {
     /// Returns an error if:
     /// - The transaction ID is not found
     /// - The client ID doesn't match the original transaction
-    /// - The transaction is already under dispute
+    /// - The transaction's type is not one of the configured disputable types
+    /// - The transaction is already under dispute or has been charged back
+    /// - The transaction was resolved and the re-dispute policy forbids disputing it again
     /// - Insufficient available funds to hold
     fn process_dispute(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
         // Look up the original transaction
         let stored_tx = self
             .transaction_store
-            .get(record.tx)
+            .get_for_client(record.client, record.tx)
             .ok_or_else(|| PaymentError::transaction_not_found(record.tx, "dispute"))?;
 
         // Verify client matches
@@ -215,21 +516,32 @@ impl TransactionEngine {
             ));
         }
 
-        // Verify not already disputed
-        if stored_tx.under_dispute {
-            return Err(PaymentError::transaction_already_disputed(
-                record.tx,
-                record.client,
-            ));
+        // Verify the scheme allows disputing this type of transaction
+        self.config.disputable_types.check(record.tx, &stored_tx)?;
+
+        // Verify the transaction can be disputed (not already disputed or charged back)
+        stored_tx
+            .dispute_state
+            .transition(record.tx_type, record.tx, record.client)?;
+        self.config.redispute_policy.check(record.tx, &stored_tx)?;
+
+        // Hold the funds, letting available go negative if the policy allows debt
+        match self.config.negative_balance_policy {
+            NegativeBalancePolicy::Reject => self
+                .account_manager
+                .hold_funds(record.client, stored_tx.amount)?,
+            NegativeBalancePolicy::AllowDebt => self
+                .account_manager
+                .hold_funds_allowing_debt(record.client, stored_tx.amount)?,
         }
 
-        // Hold the funds
-        self.account_manager
-            .hold_funds(record.client, stored_tx.amount)?;
+        let amount = stored_tx.amount;
 
         // Mark as disputed
-        self.transaction_store.mark_disputed(record.tx)?;
+        self.transaction_store
+            .mark_disputed(record.client, record.tx)?;
 
+        self.post(record.tx_type, record.client, record.tx, amount);
         Ok(())
     }
 
@@ -237,7 +549,8 @@ impl TransactionEngine {
     ///
     /// Looks up the original transaction, validates the client matches,
     /// verifies the transaction is under dispute, releases the held funds,
-    /// and marks the transaction as resolved.
+    /// and marks the transaction as resolved. With a held funds interest rate
+    /// configured, interest on the released funds is credited as well.
     ///
     /// # Arguments
     ///
@@ -255,11 +568,12 @@ impl TransactionEngine {
     /// - The client ID doesn't match the original transaction
     /// - The transaction is not under dispute
     /// - Insufficient held funds to release
+    /// - The interest overflows
     fn process_resolve(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
         // Look up the original transaction
         let stored_tx = self
             .transaction_store
-            .get(record.tx)
+            .get_for_client(record.client, record.tx)
             .ok_or_else(|| PaymentError::transaction_not_found(record.tx, "resolve"))?;
 
         // Verify client matches
@@ -273,21 +587,31 @@ impl TransactionEngine {
         }
 
         // Verify it's under dispute
-        if !stored_tx.under_dispute {
-            return Err(PaymentError::transaction_not_disputed(
-                record.tx,
-                record.client,
-                "resolve",
-            ));
-        }
-
-        // Release the funds
+        stored_tx
+            .dispute_state
+            .transition(record.tx_type, record.tx, record.client)?;
+
+        // Release the funds, with interest on them if configured
+        let amount = stored_tx.amount;
+        let interest = self
+            .config
+            .held_interest_on(amount)
+            .ok_or_else(|| PaymentError::arithmetic_overflow("resolve", record.client))?;
         self.account_manager
-            .release_funds(record.client, stored_tx.amount)?;
+            .release_funds_with_interest(record.client, amount, interest)?;
 
         // Mark as resolved
-        self.transaction_store.mark_resolved(record.tx)?;
-
+        self.transaction_store
+            .mark_resolved(record.client, record.tx)?;
+
+        self.post(record.tx_type, record.client, record.tx, amount);
+        if !interest.is_zero() {
+            self.flows.record_interest(interest);
+            if self.config.journal {
+                self.postings
+                    .push(Posting::interest(record.client, record.tx, interest));
+            }
+        }
         Ok(())
     }
 
@@ -295,7 +619,10 @@ impl TransactionEngine {
     ///
     /// Looks up the original transaction, validates the client matches,
     /// verifies the transaction is under dispute, removes the held funds,
-    /// and locks the account.
+    /// locks the account, and marks the transaction as charged back.
+    ///
+    /// With direct chargebacks allowed, a transaction that is not under
+    /// dispute is disputed first, so its funds are reversed immediately.
     ///
     /// # Arguments
     ///
@@ -311,13 +638,14 @@ impl TransactionEngine {
     /// Returns an error if:
     /// - The transaction ID is not found
     /// - The client ID doesn't match the original transaction
-    /// - The transaction is not under dispute
+    /// - The transaction is not under dispute (and direct chargebacks are not allowed)
+    /// - The implicit dispute of a direct chargeback fails
     /// - Insufficient held funds for chargeback
     fn process_chargeback(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
         // Look up the original transaction
         let stored_tx = self
             .transaction_store
-            .get(record.tx)
+            .get_for_client(record.client, record.tx)
             .ok_or_else(|| PaymentError::transaction_not_found(record.tx, "chargeback"))?;
 
         // Verify client matches
@@ -330,22 +658,195 @@ impl TransactionEngine {
             ));
         }
 
+        // In direct chargeback mode, dispute the transaction first
+        if self.config.needs_implicit_dispute(&stored_tx) {
+            self.process_dispute(TransactionRecord {
+                tx_type: TransactionType::Dispute,
+                ..record.clone()
+            })?;
+            return self.process_chargeback(record);
+        }
+
         // Verify it's under dispute
-        if !stored_tx.under_dispute {
-            return Err(PaymentError::transaction_not_disputed(
+        stored_tx
+            .dispute_state
+            .transition(record.tx_type, record.tx, record.client)?;
+
+        // Execute chargeback (removes held funds and locks account)
+        self.account_manager
+            .chargeback(record.client, record.tx, stored_tx.amount)?;
+
+        let amount = stored_tx.amount;
+
+        // Mark as charged back so it can never be disputed again
+        self.transaction_store
+            .mark_charged_back(record.client, record.tx)?;
+
+        self.post(record.tx_type, record.client, record.tx, amount);
+        Ok(())
+    }
+
+    /// Process an approve or reject transaction
+    ///
+    /// Looks up the withdrawal pending approval, validates the client matches,
+    /// and completes it: an approve removes the held funds from the account,
+    /// a reject moves them back to available.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The approve or reject transaction record
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the approve or reject was processed successfully
+    /// * `Err(PaymentError)` if it failed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The transaction ID is not found
+    /// - The client ID doesn't match the original transaction
+    /// - The transaction is not a withdrawal pending approval
+    /// - Insufficient held funds to remove or release
+    fn process_approval(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
+        let operation = match record.tx_type {
+            TransactionType::Approve => "approve",
+            _ => "reject",
+        };
+
+        // Look up the original transaction
+        let stored_tx = self
+            .transaction_store
+            .get_for_client(record.client, record.tx)
+            .ok_or_else(|| PaymentError::transaction_not_found(record.tx, operation))?;
+
+        // Verify client matches
+        if stored_tx.client != record.client {
+            return Err(PaymentError::client_mismatch(
                 record.tx,
+                stored_tx.client,
                 record.client,
-                "chargeback",
+                operation,
             ));
         }
 
-        // Execute chargeback (removes held funds and locks account)
-        self.account_manager
-            .chargeback(record.client, stored_tx.amount)?;
+        // Verify it's pending approval
+        let state = stored_tx
+            .dispute_state
+            .transition(record.tx_type, record.tx, record.client)?;
+
+        // Pay out or release the held funds
+        if record.tx_type == TransactionType::Approve {
+            self.account_manager
+                .complete_withdrawal(record.client, stored_tx.amount)?;
+        } else {
+            self.account_manager
+                .release_funds(record.client, stored_tx.amount)?;
+        }
+
+        self.transaction_store
+            .set_dispute_state(record.client, record.tx, state)?;
 
+        self.post(record.tx_type, record.client, record.tx, stored_tx.amount);
         Ok(())
     }
 
+    /// Whether a deposit or withdrawal reuses the ID of a stored transaction,
+    /// of the same client if transaction IDs are per client
+    fn is_duplicate(&self, record: &TransactionRecord) -> bool {
+        if !matches!(
+            record.tx_type,
+            TransactionType::Deposit | TransactionType::Withdrawal
+        ) {
+            return false;
+        }
+        match self.config.duplicate_tx_policy {
+            DuplicateTxPolicy::PerClient => self
+                .transaction_store
+                .contains_for_client(record.client, record.tx),
+            DuplicateTxPolicy::Reject | DuplicateTxPolicy::Ignore => {
+                self.transaction_store.contains(record.tx)
+            }
+        }
+    }
+
+    /// Store a disputable transaction, under the client if transaction IDs
+    /// are per client
+    fn store_transaction(&mut self, tx_id: TransactionId, tx: StoredTransaction) {
+        match self.config.duplicate_tx_policy {
+            DuplicateTxPolicy::PerClient => self.transaction_store.store_for_client(tx_id, tx),
+            DuplicateTxPolicy::Reject | DuplicateTxPolicy::Ignore => {
+                self.transaction_store.store(tx_id, tx)
+            }
+        }
+    }
+
+    /// Record the posting of an applied transaction, if the journal is enabled
+    fn post(
+        &mut self,
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Decimal,
+    ) {
+        if self.config.journal {
+            self.postings
+                .push(Posting::new(tx_type, client, tx, amount));
+        }
+    }
+
+    /// Apply a deposit or withdrawal to the client's account with `change`,
+    /// then move its fee, if any, to the fee account
+    ///
+    /// If the fee cannot be moved (the fee account would overflow), the
+    /// client's account is put back as it was, so the failed transaction
+    /// moves no money.
+    fn apply_with_fee<F>(
+        &mut self,
+        client: ClientId,
+        fee: Option<(ClientId, Decimal)>,
+        change: F,
+    ) -> Result<(), PaymentError>
+    where
+        F: FnOnce(&mut AccountManager) -> Result<(), PaymentError>,
+    {
+        let Some((fee_account, fee)) = fee else {
+            return change(&mut self.account_manager);
+        };
+        let before = self.account_manager.get_or_create_account(client).clone();
+        change(&mut self.account_manager)?;
+        self.account_manager
+            .charge_fee(client, fee_account, fee)
+            .inspect_err(|_| *self.account_manager.get_or_create_account(client) = before)
+    }
+
+    /// Record the posting of the fee charged for a transaction, if any and
+    /// the journal is enabled
+    fn post_fee(&mut self, record: &TransactionRecord, fee: Option<(ClientId, Decimal)>) {
+        if let (true, Some((fee_account, fee))) = (self.config.journal, fee) {
+            self.postings.push(Posting::fee(
+                record.tx_type,
+                record.client,
+                record.tx,
+                fee_account,
+                fee,
+            ));
+        }
+    }
+
+    /// Take the postings of the transactions applied so far
+    pub fn take_postings(&mut self) -> Vec<Posting> {
+        std::mem::take(&mut self.postings)
+    }
+
+    /// Take the balance history samples recorded so far
+    pub fn take_balance_history(&mut self) -> Vec<BalancePoint> {
+        self.history
+            .as_mut()
+            .map(BalanceHistory::take)
+            .unwrap_or_default()
+    }
+
     /// Get final account states for output
     ///
     /// Returns a sorted list of all accounts that have been created
@@ -357,6 +858,16 @@ impl TransactionEngine {
     pub fn get_accounts(&self) -> Vec<&Account> {
         self.account_manager.get_all_accounts()
     }
+
+    /// Get the current state of a single account, if it exists
+    pub fn account(&self, client: ClientId) -> Option<&Account> {
+        self.account_manager.get_account(client)
+    }
+
+    /// Get a stored (disputable) transaction, if it exists
+    pub fn transaction(&self, tx_id: TransactionId) -> Option<StoredTransaction> {
+        self.transaction_store.get(tx_id)
+    }
 }
 
 impl Default for TransactionEngine {
@@ -365,11 +876,68 @@ impl Default for TransactionEngine {
     }
 }
 
+impl Engine for TransactionEngine {
+    fn process_transaction(&mut self, record: TransactionRecord) -> Result<(), PaymentError> {
+        self.process(record)
+    }
+
+    fn get_accounts(&self) -> Vec<Account> {
+        TransactionEngine::get_accounts(self)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    fn snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot::new(
+            Engine::get_accounts(self),
+            self.transaction_store.iter().collect(),
+        )
+    }
+
+    fn flows(&self) -> MoneyFlows {
+        self.flows
+    }
+
+    fn take_expired_disputes(&mut self) -> Vec<ExpiredDispute> {
+        TransactionEngine::take_expired_disputes(self)
+    }
+
+    fn take_postings(&mut self) -> Vec<Posting> {
+        TransactionEngine::take_postings(self)
+    }
+
+    fn take_balance_history(&mut self) -> Vec<BalancePoint> {
+        TransactionEngine::take_balance_history(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::config::{DisputableTypes, RedisputePolicy};
+    use crate::core::fees::FeeSchedule;
+    use crate::types::LockReason;
+    use rstest::rstest;
     use rust_decimal::Decimal;
 
+    /// Record without a line or source
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<Decimal>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount,
+            line: None,
+            source: None,
+        }
+    }
+
     #[test]
     fn test_process_deposit_creates_account() {
         let mut engine = TransactionEngine::new();
@@ -379,6 +947,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: Some(Decimal::new(10000, 4)), // 1.0000
+            line: None,
+            source: None,
         });
 
         assert!(result.is_ok());
@@ -390,6 +960,28 @@ mod tests {
         assert_eq!(accounts[0].total, Decimal::new(10000, 4));
     }
 
+    #[test]
+    fn test_capacity_hints_presize_maps() {
+        let config = EngineConfig::new()
+            .with_expected_clients(100)
+            .with_expected_transactions(5000);
+        let mut engine = TransactionEngine::with_config(config);
+        assert!(engine.account_manager.capacity() >= 100);
+        assert!(engine.transaction_store.capacity() >= 5000);
+
+        engine
+            .process(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::ONE),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+        assert_eq!(engine.get_accounts().len(), 1);
+    }
+
     #[test]
     fn test_process_deposit_without_amount_fails() {
         let mut engine = TransactionEngine::new();
@@ -399,6 +991,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -419,6 +1013,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -428,6 +1024,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(10000, 4)),
+            line: None,
+            source: None,
         });
 
         assert!(result.is_ok());
@@ -448,6 +1046,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -457,6 +1057,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(20000, 4)),
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -479,6 +1081,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -499,6 +1103,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -508,6 +1114,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_ok());
@@ -527,6 +1135,8 @@ mod tests {
             client: 1,
             tx: 999,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -548,6 +1158,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -557,6 +1169,8 @@ mod tests {
             client: 2,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -578,6 +1192,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -588,6 +1204,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -597,6 +1215,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -618,6 +1238,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -628,6 +1250,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -637,6 +1261,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_ok());
@@ -656,6 +1282,8 @@ mod tests {
             client: 1,
             tx: 999,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -677,6 +1305,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -687,6 +1317,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -696,6 +1328,8 @@ mod tests {
             client: 2,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -717,6 +1351,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -726,6 +1362,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -746,6 +1384,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -756,6 +1396,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -765,6 +1407,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_ok());
@@ -774,6 +1418,7 @@ mod tests {
         assert_eq!(accounts[0].held, Decimal::ZERO);
         assert_eq!(accounts[0].total, Decimal::ZERO);
         assert!(accounts[0].locked);
+        assert_eq!(accounts[0].lock_reason, Some(LockReason::ChargebackTx(1)));
     }
 
     #[test]
@@ -785,6 +1430,8 @@ mod tests {
             client: 1,
             tx: 999,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -806,6 +1453,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -816,6 +1465,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -825,6 +1476,8 @@ mod tests {
             client: 2,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -846,6 +1499,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -855,6 +1510,8 @@ mod tests {
             client: 1,
             tx: 1,
             amount: None,
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -875,6 +1532,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -884,6 +1543,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -893,6 +1554,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -902,6 +1565,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -922,6 +1587,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -931,6 +1598,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -940,6 +1609,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -949,6 +1620,8 @@ mod tests {
             client: 1,
             tx: 2,
             amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
         });
 
         assert!(result.is_err());
@@ -969,6 +1642,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -979,6 +1654,8 @@ mod tests {
                 client: 2,
                 tx: 2,
                 amount: Some(Decimal::new(20000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1004,6 +1681,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1014,6 +1693,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1024,6 +1705,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1045,6 +1728,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: Some(Decimal::new(10000, 4)),
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1055,6 +1740,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1065,6 +1752,8 @@ mod tests {
                 client: 1,
                 tx: 1,
                 amount: None,
+                line: None,
+                source: None,
             })
             .unwrap();
 
@@ -1074,4 +1763,1241 @@ mod tests {
         assert_eq!(accounts[0].total, Decimal::ZERO);
         assert!(accounts[0].locked);
     }
+
+    #[test]
+    fn test_with_state_resumes_dispute() {
+        let mut account = Account::new(1);
+        account.available = Decimal::new(10000, 4);
+        account.total = Decimal::new(10000, 4);
+        let stored = StoredTransaction {
+            client: 1,
+            amount: Decimal::new(10000, 4),
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+
+        let mut engine =
+            TransactionEngine::with_state(EngineConfig::default(), [account], [(1, stored)]);
+
+        engine
+            .process(TransactionRecord {
+                tx_type: TransactionType::Dispute,
+                client: 1,
+                tx: 1,
+                amount: None,
+                line: None,
+                source: None,
+            })
+            .unwrap();
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::ZERO);
+        assert_eq!(account.held, Decimal::new(10000, 4));
+        assert!(engine.transaction(1).unwrap().dispute_state.is_disputed());
+        assert!(engine.account(2).is_none());
+    }
+
+    #[test]
+    fn test_withdrawal_requirement_blocks_unverified_client() {
+        use crate::core::config::MetadataMap;
+        use crate::types::AccountMetadata;
+        use std::sync::Arc;
+
+        let verified = AccountMetadata::from([("kyc_status".to_string(), "verified".to_string())]);
+        let config = EngineConfig::new()
+            .with_account_metadata(MetadataMap::from([(1, Arc::new(verified))]))
+            .with_withdrawal_requirement("kyc_status=verified".parse().unwrap());
+        let mut engine = TransactionEngine::with_config(config);
+
+        for client in [1, 2] {
+            engine
+                .process(TransactionRecord {
+                    tx_type: TransactionType::Deposit,
+                    client,
+                    tx: client as TransactionId,
+                    amount: Some(Decimal::new(10000, 4)),
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+
+        let withdraw = |client: ClientId, tx: TransactionId| TransactionRecord {
+            tx_type: TransactionType::Withdrawal,
+            client,
+            tx,
+            amount: Some(Decimal::new(5000, 4)),
+            line: None,
+            source: None,
+        };
+        assert!(engine.process(withdraw(1, 3)).is_ok());
+        assert_eq!(
+            engine.process(withdraw(2, 4)),
+            Err(PaymentError::withdrawal_blocked(
+                2,
+                "requires kyc_status=verified"
+            ))
+        );
+
+        let accounts = engine.get_accounts();
+        assert_eq!(accounts[0].available, Decimal::new(5000, 4));
+        assert_eq!(
+            accounts[0].metadata.as_deref().unwrap()["kyc_status"],
+            "verified"
+        );
+        assert_eq!(accounts[1].available, Decimal::new(10000, 4));
+        assert!(accounts[1].metadata.is_none());
+    }
+
+    #[test]
+    fn test_amount_limits_reject_transactions() {
+        use crate::core::config::AmountLimits;
+
+        let config = EngineConfig::new().with_limits(AmountLimits {
+            max_deposit: Some(Decimal::from(100)),
+            max_withdrawal: Some(Decimal::from(20)),
+            max_total: Some(Decimal::from(150)),
+        });
+        let mut engine = TransactionEngine::with_config(config);
+        assert!(engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100))
+            ))
+            .is_ok());
+        assert_eq!(
+            engine.process(record(
+                TransactionType::Deposit,
+                1,
+                2,
+                Some(Decimal::from(1_000_000_000_000i64))
+            )),
+            Err(PaymentError::amount_limit_exceeded(
+                2,
+                1,
+                "deposit",
+                Decimal::from(1_000_000_000_000i64),
+                Decimal::from(100)
+            ))
+        );
+        assert_eq!(
+            engine.process(record(
+                TransactionType::Deposit,
+                1,
+                3,
+                Some(Decimal::from(60))
+            )),
+            Err(PaymentError::balance_limit_exceeded(
+                3,
+                1,
+                Decimal::from(100),
+                Decimal::from(60),
+                Decimal::from(150)
+            ))
+        );
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                4,
+                Some(Decimal::from(21))
+            ))
+            .unwrap_err()
+            .is_limit_exceeded());
+        assert!(engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                5,
+                Some(Decimal::from(50))
+            ))
+            .is_ok());
+
+        // Rejected transactions are neither applied nor stored
+        assert_eq!(engine.account(1).unwrap().total, Decimal::from(150));
+        assert!(engine.transaction(2).is_none());
+        assert!(engine.transaction(3).is_none());
+        assert!(engine.transaction(4).is_none());
+    }
+
+    #[test]
+    fn test_set_risk_rules_keeps_state() {
+        use crate::core::config::AmountLimits;
+        use crate::core::velocity::VelocityLimit;
+
+        let mut engine = TransactionEngine::new();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::from(10)),
+            ))
+            .unwrap();
+
+        engine.set_risk_rules(RiskRules {
+            limits: AmountLimits {
+                max_withdrawal: Some(Decimal::from(20)),
+                ..AmountLimits::default()
+            },
+            velocity_limit: Some(VelocityLimit::new(3).with_max_withdrawals(1)),
+            withdrawal_requirements: Vec::new(),
+            held_interest: None,
+        });
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                3,
+                Some(Decimal::from(30))
+            ))
+            .unwrap_err()
+            .is_limit_exceeded());
+        // The velocity window starts with the new rules
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                4,
+                Some(Decimal::from(5))
+            ))
+            .is_ok());
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                5,
+                Some(Decimal::from(5))
+            ))
+            .unwrap_err()
+            .is_limit_exceeded());
+
+        engine.set_risk_rules(RiskRules::default());
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                6,
+                Some(Decimal::from(30))
+            ))
+            .is_ok());
+        assert_eq!(engine.account(1).unwrap().total, Decimal::from(55));
+        assert!(engine.transaction(1).is_some());
+    }
+
+    #[rstest]
+    #[case::deposits(DisputableTypes::Deposits, false)]
+    #[case::deposits_and_withdrawals(DisputableTypes::DepositsAndWithdrawals, true)]
+    #[case::all(DisputableTypes::All, true)]
+    fn test_disputable_types(#[case] types: DisputableTypes, #[case] withdrawals: bool) {
+        let config = EngineConfig::new()
+            .with_disputable_types(types)
+            .with_direct_chargeback(true);
+        let mut engine = TransactionEngine::with_config(config);
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 3, Some(Decimal::from(100))),
+            record(TransactionType::Withdrawal, 1, 2, Some(Decimal::from(40))),
+            record(TransactionType::Dispute, 1, 1, None),
+        ] {
+            engine.process(record).unwrap();
+        }
+
+        // A direct chargeback disputes implicitly, so it is checked as well
+        for tx_type in [TransactionType::Dispute, TransactionType::Chargeback] {
+            let result = engine.process(record(tx_type, 1, 2, None));
+            if withdrawals {
+                assert_eq!(result, Ok(()));
+            } else {
+                assert_eq!(
+                    result,
+                    Err(PaymentError::transaction_not_disputable(2, 1, "withdrawal"))
+                );
+            }
+        }
+        assert_eq!(engine.account(1).unwrap().held, Decimal::from(100));
+    }
+
+    #[test]
+    fn test_ignored_duplicate_has_no_effect() {
+        let config = EngineConfig::new().with_duplicate_tx_policy(DuplicateTxPolicy::Ignore);
+        let mut engine = TransactionEngine::with_config(config);
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(50)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Withdrawal,
+                2,
+                1,
+                Some(Decimal::from(10)),
+            ))
+            .unwrap();
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.total, Decimal::from(100));
+        assert_eq!(account.activity.transactions, 1);
+        assert!(engine.account(2).is_none());
+        assert_eq!(Engine::flows(&engine).deposited, Decimal::from(100));
+        assert_eq!(Engine::flows(&engine).withdrawn, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_per_client_tx_ids() {
+        let config = EngineConfig::new()
+            .with_duplicate_tx_policy(DuplicateTxPolicy::PerClient)
+            .with_dispute_expiry(2);
+        let mut engine = TransactionEngine::with_config(config.clone());
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                2,
+                1,
+                Some(Decimal::from(30)),
+            ))
+            .unwrap();
+        // The ID is still unique within a client
+        assert_eq!(
+            engine.process(record(
+                TransactionType::Deposit,
+                2,
+                1,
+                Some(Decimal::from(5))
+            )),
+            Err(PaymentError::duplicate_transaction(1, 2))
+        );
+
+        // Each client disputes its own transaction
+        engine
+            .process(record(TransactionType::Dispute, 2, 1, None))
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().held, Decimal::ZERO);
+        assert_eq!(engine.account(2).unwrap().held, Decimal::from(30));
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Chargeback, 1, 1, None))
+            .unwrap();
+        assert!(engine.account(1).unwrap().locked);
+        assert!(!engine.account(2).unwrap().locked);
+
+        // Client 2's dispute expires on its own
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                3,
+                1,
+                Some(Decimal::from(1)),
+            ))
+            .unwrap();
+        let expired = engine.take_expired_disputes();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(
+            (expired[0].client, expired[0].amount),
+            (2, Decimal::from(30))
+        );
+
+        // Both transactions survive a snapshot
+        let snapshot = Engine::snapshot(&engine);
+        assert_eq!(snapshot.transactions.len(), 3);
+        let mut engine =
+            TransactionEngine::with_state(config, snapshot.accounts, snapshot.transactions);
+        engine
+            .process(record(TransactionType::Dispute, 2, 1, None))
+            .unwrap();
+        assert_eq!(engine.account(2).unwrap().held, Decimal::from(30));
+    }
+
+    #[test]
+    fn test_dispute_expiry_resolves_stale_disputes() {
+        let mut engine = TransactionEngine::with_config(EngineConfig::new().with_dispute_expiry(3));
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(50))),
+            record(TransactionType::Dispute, 1, 1, None),
+            record(TransactionType::Dispute, 1, 2, None),
+            record(TransactionType::Resolve, 1, 2, None),
+            record(TransactionType::Deposit, 1, 3, Some(Decimal::from(10))),
+        ] {
+            engine.process(record).unwrap();
+        }
+        assert_eq!(engine.account(1).unwrap().held, Decimal::from(100));
+        assert!(engine.take_expired_disputes().is_empty());
+
+        // The chargeback comes too late: the dispute expired before it
+        assert_eq!(
+            engine.process(record(TransactionType::Chargeback, 1, 1, None)),
+            Err(PaymentError::transaction_not_disputed(1, 1, "chargeback"))
+        );
+        assert_eq!(
+            engine.take_expired_disputes(),
+            vec![ExpiredDispute {
+                tx: 1,
+                client: 1,
+                amount: Decimal::from(100),
+                disputed_at: 3,
+                expired_at: 7,
+            }]
+        );
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.available, Decimal::from(160));
+        assert!(!account.locked);
+        assert_eq!(
+            engine.transaction(1).unwrap().dispute_state,
+            DisputeState::Resolved
+        );
+    }
+
+    #[test]
+    fn test_dispute_expiry_skips_locked_accounts() {
+        let mut engine = TransactionEngine::with_config(EngineConfig::new().with_dispute_expiry(2));
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(50))),
+            record(TransactionType::Dispute, 1, 1, None),
+            record(TransactionType::Dispute, 1, 2, None),
+            record(TransactionType::Chargeback, 1, 2, None),
+        ] {
+            engine.process(record).unwrap();
+        }
+        assert_eq!(
+            engine.process(record(
+                TransactionType::Deposit,
+                1,
+                3,
+                Some(Decimal::from(1))
+            )),
+            Err(PaymentError::account_locked(1))
+        );
+
+        // Locked accounts accept no resolves, so their disputes stay open
+        assert!(engine.take_expired_disputes().is_empty());
+        assert_eq!(engine.account(1).unwrap().held, Decimal::from(100));
+    }
+
+    #[test]
+    fn test_velocity_limit_rejects_excess_withdrawals() {
+        use crate::core::velocity::VelocityLimit;
+
+        let config =
+            EngineConfig::new().with_velocity_limit(VelocityLimit::new(3).with_max_withdrawals(1));
+        let mut engine = TransactionEngine::with_config(config);
+        assert!(engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100))
+            ))
+            .is_ok());
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::from(10))
+            ))
+            .is_ok());
+        assert_eq!(
+            engine.process(record(
+                TransactionType::Withdrawal,
+                1,
+                3,
+                Some(Decimal::from(10))
+            )),
+            Err(PaymentError::velocity_limit_exceeded(
+                3,
+                1,
+                "1 withdrawals per 3 transactions"
+            ))
+        );
+        // Rejected withdrawals don't count, so two more deposits move tx 2
+        // out of the window
+        for tx in [4, 5] {
+            assert!(engine
+                .process(record(
+                    TransactionType::Deposit,
+                    1,
+                    tx,
+                    Some(Decimal::from(10))
+                ))
+                .is_ok());
+        }
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                6,
+                Some(Decimal::from(10))
+            ))
+            .is_ok());
+
+        assert_eq!(engine.account(1).unwrap().total, Decimal::from(100));
+        assert!(engine.transaction(3).is_none());
+    }
+
+    #[test]
+    fn test_chargeback_marks_transaction_charged_back() {
+        let mut engine = TransactionEngine::new();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        assert_eq!(
+            engine.transaction(1).unwrap().dispute_state,
+            DisputeState::Disputed
+        );
+        engine
+            .process(record(TransactionType::Chargeback, 1, 1, None))
+            .unwrap();
+
+        assert_eq!(
+            engine.transaction(1).unwrap().dispute_state,
+            DisputeState::ChargedBack
+        );
+    }
+
+    #[test]
+    fn test_charged_back_transaction_cannot_be_disputed_again() {
+        let stored = StoredTransaction {
+            client: 1,
+            amount: Decimal::new(10000, 4),
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::ChargedBack,
+            disputes: 1,
+        };
+        let mut account = Account::new(1);
+        account.available = Decimal::new(10000, 4);
+        account.total = Decimal::new(10000, 4);
+        let mut engine =
+            TransactionEngine::with_state(EngineConfig::default(), [account], [(1, stored)]);
+
+        let result = engine.process(TransactionRecord {
+            tx_type: TransactionType::Dispute,
+            client: 1,
+            tx: 1,
+            amount: None,
+            line: None,
+            source: None,
+        });
+
+        assert_eq!(
+            result,
+            Err(PaymentError::transaction_charged_back(1, 1, "dispute"))
+        );
+        assert_eq!(engine.account(1).unwrap().held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_resolved_transaction_can_be_disputed_again() {
+        let mut engine = TransactionEngine::new();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Resolve, 1, 1, None))
+            .unwrap();
+        assert_eq!(
+            engine.transaction(1).unwrap().dispute_state,
+            DisputeState::Resolved
+        );
+
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().held, Decimal::new(10000, 4));
+    }
+
+    #[rstest::rstest]
+    #[case::never(
+        RedisputePolicy::Limit(0),
+        0,
+        Some(PaymentError::redispute_not_allowed(1, 1))
+    )]
+    #[case::once(
+        RedisputePolicy::Limit(1),
+        1,
+        Some(PaymentError::redispute_limit_reached(1, 1, 1))
+    )]
+    #[case::unlimited(RedisputePolicy::Unlimited, 3, None)]
+    fn test_redispute_policy(
+        #[case] policy: RedisputePolicy,
+        #[case] allowed: usize,
+        #[case] error: Option<PaymentError>,
+    ) {
+        let mut engine =
+            TransactionEngine::with_config(EngineConfig::new().with_redispute_policy(policy));
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Resolve, 1, 1, None))
+            .unwrap();
+
+        for _ in 0..allowed {
+            engine
+                .process(record(TransactionType::Dispute, 1, 1, None))
+                .unwrap();
+            engine
+                .process(record(TransactionType::Resolve, 1, 1, None))
+                .unwrap();
+        }
+
+        let result = engine.process(record(TransactionType::Dispute, 1, 1, None));
+        match error {
+            Some(error) => {
+                assert_eq!(result, Err(error));
+                assert_eq!(engine.account(1).unwrap().held, Decimal::ZERO);
+            }
+            None => assert!(result.is_ok()),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::strict(false, Err(PaymentError::transaction_not_disputed(1, 1, "chargeback")))]
+    #[case::direct(true, Ok(()))]
+    fn test_chargeback_without_dispute(
+        #[case] allow_direct_chargeback: bool,
+        #[case] expected: Result<(), PaymentError>,
+    ) {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new().with_direct_chargeback(allow_direct_chargeback),
+        );
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                2,
+                Some(Decimal::new(5000, 4)),
+            ))
+            .unwrap();
+
+        let result = engine.process(record(TransactionType::Chargeback, 1, 1, None));
+        assert_eq!(result, expected);
+
+        let account = engine.account(1).unwrap();
+        let stored = engine.transaction(1).unwrap();
+        assert_eq!(account.held, Decimal::ZERO);
+        if allow_direct_chargeback {
+            assert_eq!(account.available, Decimal::new(5000, 4));
+            assert_eq!(account.total, Decimal::new(5000, 4));
+            assert!(account.locked);
+            assert_eq!(stored.dispute_state, DisputeState::ChargedBack);
+            assert_eq!(stored.disputes, 1);
+        } else {
+            assert_eq!(account.available, Decimal::new(15000, 4));
+            assert!(!account.locked);
+            assert_eq!(stored.dispute_state, DisputeState::None);
+        }
+    }
+
+    #[test]
+    fn test_direct_chargeback_of_resolved_transaction_respects_redispute_policy() {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new()
+                .with_direct_chargeback(true)
+                .with_redispute_policy(RedisputePolicy::Limit(0)),
+        );
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Resolve, 1, 1, None))
+            .unwrap();
+
+        assert_eq!(
+            engine.process(record(TransactionType::Chargeback, 1, 1, None)),
+            Err(PaymentError::redispute_not_allowed(1, 1))
+        );
+        assert!(!engine.account(1).unwrap().locked);
+    }
+
+    #[test]
+    fn test_direct_chargeback_with_insufficient_funds_fails() {
+        let mut engine =
+            TransactionEngine::with_config(EngineConfig::new().with_direct_chargeback(true));
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::new(8000, 4)),
+            ))
+            .unwrap();
+
+        assert!(engine
+            .process(record(TransactionType::Chargeback, 1, 1, None))
+            .is_err());
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::new(2000, 4));
+        assert!(!account.locked);
+        assert_eq!(
+            engine.transaction(1).unwrap().dispute_state,
+            DisputeState::None
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::reject(NegativeBalancePolicy::Reject, false)]
+    #[case::allow_debt(NegativeBalancePolicy::AllowDebt, true)]
+    fn test_dispute_of_withdrawn_deposit(
+        #[case] policy: NegativeBalancePolicy,
+        #[case] disputed: bool,
+    ) {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new().with_negative_balance_policy(policy),
+        );
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::new(8000, 4)),
+            ))
+            .unwrap();
+
+        let result = engine.process(record(TransactionType::Dispute, 1, 1, None));
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.total, Decimal::new(2000, 4));
+        if disputed {
+            assert!(result.is_ok());
+            assert_eq!(account.available, Decimal::new(-8000, 4));
+            assert_eq!(account.held, Decimal::new(10000, 4));
+        } else {
+            assert!(matches!(
+                result,
+                Err(PaymentError::InsufficientAvailableFunds { .. })
+            ));
+            assert_eq!(account.available, Decimal::new(2000, 4));
+            assert_eq!(account.held, Decimal::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_chargeback_of_withdrawn_deposit_leaves_debt() {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new().with_negative_balance_policy(NegativeBalancePolicy::AllowDebt),
+        );
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Withdrawal,
+                1,
+                2,
+                Some(Decimal::new(10000, 4)),
+            ))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        engine
+            .process(record(TransactionType::Chargeback, 1, 1, None))
+            .unwrap();
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::new(-10000, 4));
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::new(-10000, 4));
+        assert!(account.locked);
+    }
+
+    /// Engine holding withdrawals above 50, with a deposit of 100 for client 1
+    /// and a withdrawal of 80 (tx 2) pending approval
+    fn engine_with_pending_withdrawal() -> TransactionEngine {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new()
+                .with_withdrawal_approval_above(Decimal::from(50))
+                .with_journal(true),
+        );
+        for (tx_type, tx, amount) in [
+            (TransactionType::Deposit, 1, 100),
+            (TransactionType::Withdrawal, 2, 80),
+        ] {
+            engine
+                .process(TransactionRecord {
+                    tx_type,
+                    client: 1,
+                    tx,
+                    amount: Some(Decimal::from(amount)),
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+        engine
+    }
+
+    #[test]
+    fn test_withdrawal_above_threshold_is_pending() {
+        let mut engine = engine_with_pending_withdrawal();
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::from(20));
+        assert_eq!(account.held, Decimal::from(80));
+        assert_eq!(account.total, Decimal::from(100));
+        assert_eq!(
+            engine.transaction(2).unwrap().dispute_state,
+            DisputeState::Pending
+        );
+        assert_eq!(engine.flows.withdrawn, Decimal::ZERO);
+        assert_eq!(
+            engine.take_postings()[1],
+            Posting::pending_withdrawal(1, 2, Decimal::from(80))
+        );
+
+        // A withdrawal at the threshold is applied directly
+        engine
+            .process(TransactionRecord {
+                tx_type: TransactionType::Withdrawal,
+                client: 1,
+                tx: 3,
+                amount: Some(Decimal::from(20)),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().total, Decimal::from(80));
+    }
+
+    #[rstest::rstest]
+    #[case::approve(TransactionType::Approve, 20, 0, 20, 80)]
+    #[case::reject(TransactionType::Reject, 100, 0, 100, 0)]
+    fn test_approval_completes_pending_withdrawal(
+        #[case] tx_type: TransactionType,
+        #[case] available: i64,
+        #[case] held: i64,
+        #[case] total: i64,
+        #[case] withdrawn: i64,
+    ) {
+        let mut engine = engine_with_pending_withdrawal();
+        engine.process(record(tx_type, 1, 2, None)).unwrap();
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::from(available));
+        assert_eq!(account.held, Decimal::from(held));
+        assert_eq!(account.total, Decimal::from(total));
+        assert_eq!(engine.flows.withdrawn, Decimal::from(withdrawn));
+        assert_eq!(
+            engine.flows.expected_total(),
+            Decimal::from(total),
+            "flows match the balances"
+        );
+        assert_eq!(
+            engine.take_postings()[2],
+            Posting::new(tx_type, 1, 2, Decimal::from(80))
+        );
+
+        // The withdrawal can only be completed once
+        assert!(engine
+            .process(record(TransactionType::Approve, 1, 2, None))
+            .is_err());
+        assert!(engine
+            .process(record(TransactionType::Reject, 1, 2, None))
+            .is_err());
+    }
+
+    #[rstest::rstest]
+    #[case::dispute_pending(
+        &[TransactionType::Dispute],
+        PaymentError::withdrawal_pending(2, 1, "dispute")
+    )]
+    #[case::approve_twice(
+        &[TransactionType::Approve, TransactionType::Approve],
+        PaymentError::withdrawal_not_pending(2, 1, "approve")
+    )]
+    #[case::dispute_rejected(
+        &[TransactionType::Reject, TransactionType::Dispute],
+        PaymentError::withdrawal_rejected(2, 1, "dispute")
+    )]
+    fn test_approval_lifecycle_errors(
+        #[case] tx_types: &[TransactionType],
+        #[case] expected: PaymentError,
+    ) {
+        let mut engine = engine_with_pending_withdrawal();
+        let (last, first) = tx_types.split_last().unwrap();
+        for &tx_type in first {
+            engine
+                .process(TransactionRecord {
+                    tx_type,
+                    client: 1,
+                    tx: 2,
+                    amount: None,
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+
+        let result = engine.process(TransactionRecord {
+            tx_type: *last,
+            client: 1,
+            tx: 2,
+            amount: None,
+            line: None,
+            source: None,
+        });
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn test_approve_of_deposit_rejected() {
+        let mut engine = engine_with_pending_withdrawal();
+        assert_eq!(
+            engine.process(TransactionRecord {
+                tx_type: TransactionType::Approve,
+                client: 1,
+                tx: 1,
+                amount: None,
+                line: None,
+                source: None,
+            }),
+            Err(PaymentError::withdrawal_not_pending(1, 1, "approve"))
+        );
+        assert_eq!(
+            engine.process(TransactionRecord {
+                tx_type: TransactionType::Reject,
+                client: 2,
+                tx: 2,
+                amount: None,
+                line: None,
+                source: None,
+            }),
+            Err(PaymentError::client_mismatch(2, 1, 2, "reject"))
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::enabled(true)]
+    #[case::disabled(false)]
+    fn test_journal_records_postings(#[case] journal: bool) {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new()
+                .with_direct_chargeback(true)
+                .with_journal(journal),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(50))),
+            record(TransactionType::Withdrawal, 1, 3, Some(Decimal::from(30))),
+            record(TransactionType::Dispute, 1, 2, None),
+            record(TransactionType::Resolve, 1, 2, None),
+            record(TransactionType::Chargeback, 1, 1, None),
+        ] {
+            engine.process(record).unwrap();
+        }
+        // Rejected transactions post nothing
+        assert!(engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                4,
+                Some(Decimal::from(10))
+            ))
+            .is_err());
+
+        let postings = engine.take_postings();
+        assert!(engine.take_postings().is_empty());
+        if !journal {
+            assert!(postings.is_empty());
+            return;
+        }
+        let expected = [
+            (TransactionType::Deposit, 1, 100),
+            (TransactionType::Deposit, 2, 50),
+            (TransactionType::Withdrawal, 3, 30),
+            (TransactionType::Dispute, 2, 50),
+            (TransactionType::Resolve, 2, 50),
+            // The implicit dispute of the direct chargeback
+            (TransactionType::Dispute, 1, 100),
+            (TransactionType::Chargeback, 1, 100),
+        ]
+        .map(|(tx_type, tx, amount)| Posting::new(tx_type, 1, tx, Decimal::from(amount)));
+        assert_eq!(postings, expected);
+    }
+
+    #[test]
+    fn test_fees_credited_to_fee_account() {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new()
+                .with_fees(
+                    FeeSchedule::new(99)
+                        .with_deposit_fee("0.25+1%".parse().unwrap())
+                        .with_withdrawal_fee("0.5".parse().unwrap()),
+                )
+                .with_withdrawal_approval_above(Decimal::from(40))
+                .with_journal(true),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Withdrawal, 1, 2, Some(Decimal::from(10))),
+            // A pending withdrawal is charged its fee right away
+            record(TransactionType::Withdrawal, 1, 3, Some(Decimal::from(50))),
+            // The fee account is charged no fees
+            record(TransactionType::Deposit, 99, 4, Some(Decimal::from(10))),
+        ] {
+            engine.process(record).unwrap();
+        }
+        // Neither can be paid together with its fee, so nothing is applied
+        assert!(matches!(
+            engine.process(record(
+                TransactionType::Withdrawal,
+                1,
+                5,
+                Some(Decimal::from(38))
+            )),
+            Err(PaymentError::InsufficientFunds { .. })
+        ));
+        assert!(matches!(
+            engine.process(record(
+                TransactionType::Deposit,
+                2,
+                6,
+                Some(Decimal::from(0))
+            )),
+            Err(PaymentError::InsufficientFunds { .. })
+        ));
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::new(3775, 2));
+        assert_eq!(account.held, Decimal::from(50));
+        assert_eq!(account.total, Decimal::new(8775, 2));
+        assert_eq!(engine.account(99).unwrap().total, Decimal::new(1225, 2));
+        // Fees move money between accounts, so the flows still add up
+        assert_eq!(engine.flows.expected_total(), Decimal::from(100));
+        assert_eq!(
+            engine
+                .take_postings()
+                .into_iter()
+                .filter(Posting::is_fee)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_fee_account_overflow_moves_no_money() {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new().with_fees(
+                FeeSchedule::new(99)
+                    .with_deposit_fee("5".parse().unwrap())
+                    .with_withdrawal_fee("5".parse().unwrap()),
+            ),
+        );
+        // Fill the fee account up, so it cannot take any further fee
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                1,
+                1,
+                Some(Decimal::from(100)),
+            ))
+            .unwrap();
+        engine
+            .process(record(
+                TransactionType::Deposit,
+                99,
+                2,
+                Some(Decimal::MAX - Decimal::from(5)),
+            ))
+            .unwrap();
+
+        for (tx_type, tx) in [
+            (TransactionType::Deposit, 3),
+            (TransactionType::Withdrawal, 4),
+        ] {
+            assert!(matches!(
+                engine.process(record(tx_type, 1, tx, Some(Decimal::from(10)))),
+                Err(PaymentError::ArithmeticOverflow { .. })
+            ));
+            assert!(engine.transaction_store.get_for_client(1, tx).is_none());
+        }
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::from(95));
+        assert_eq!(account.total, Decimal::from(95));
+        assert_eq!(account.activity.errors, 2);
+        assert_eq!(engine.account(99).unwrap().total, Decimal::MAX);
+    }
+
+    #[test]
+    fn test_resolve_credits_held_interest() {
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new()
+                .with_held_interest(Decimal::new(1, 2))
+                .with_journal(true),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(50))),
+            record(TransactionType::Dispute, 1, 1, None),
+            record(TransactionType::Resolve, 1, 1, None),
+            // Charged back funds earn no interest
+            record(TransactionType::Dispute, 1, 2, None),
+            record(TransactionType::Chargeback, 1, 2, None),
+        ] {
+            engine.process(record).unwrap();
+        }
+
+        let account = engine.account(1).unwrap();
+        assert_eq!(account.available, Decimal::from(101));
+        assert_eq!(account.total, Decimal::from(101));
+        assert_eq!(engine.flows.interest, Decimal::ONE);
+        assert_eq!(engine.flows.expected_total(), account.total);
+        assert!(engine
+            .take_postings()
+            .contains(&Posting::interest(1, 1, Decimal::ONE)));
+
+        // Expired disputes are resolved with interest too
+        let mut engine = TransactionEngine::with_config(
+            EngineConfig::new()
+                .with_held_interest(Decimal::new(1, 2))
+                .with_dispute_expiry(1),
+        );
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Dispute, 1, 1, None),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(50))),
+            record(TransactionType::Deposit, 1, 3, Some(Decimal::from(50))),
+        ] {
+            engine.process(record).unwrap();
+        }
+        assert_eq!(engine.take_expired_disputes().len(), 1);
+        assert_eq!(engine.account(1).unwrap().total, Decimal::from(201));
+    }
+
+    #[test]
+    fn test_balance_history_samples_each_client() {
+        let mut engine =
+            TransactionEngine::with_config(EngineConfig::new().with_balance_history(2));
+        for record in [
+            record(TransactionType::Deposit, 1, 1, Some(Decimal::from(100))),
+            record(TransactionType::Deposit, 2, 2, Some(Decimal::from(5))),
+            record(TransactionType::Withdrawal, 1, 3, Some(Decimal::from(30))),
+            record(TransactionType::Deposit, 1, 4, Some(Decimal::from(50))),
+        ] {
+            engine.process(record).unwrap();
+        }
+        // Rejected transactions are not counted
+        assert!(engine
+            .process(record(
+                TransactionType::Withdrawal,
+                2,
+                5,
+                Some(Decimal::from(50))
+            ))
+            .is_err());
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+
+        let point = |transactions, available: i64, held: i64| BalancePoint {
+            client: 1,
+            transactions,
+            available: Decimal::from(available),
+            held: Decimal::from(held),
+            total: Decimal::from(available + held),
+            locked: false,
+        };
+        assert_eq!(
+            engine.take_balance_history(),
+            vec![point(2, 70, 0), point(4, 20, 100)]
+        );
+        assert!(engine.take_balance_history().is_empty());
+    }
 }
@@ -0,0 +1,152 @@
+//! Expiration of stale disputes
+//!
+//! A dispute holds the disputed funds until a resolve or chargeback arrives.
+//! If neither ever does, the funds stay held forever. With a dispute expiry
+//! of N records, the engine resolves a dispute itself once N more records have
+//! been processed without it being resolved or charged back, releasing the
+//! held funds, and reports every expiration as an `ExpiredDispute` audit event.
+//! Records carry no timestamps, so the expiry is counted in records rather
+//! than time.
+//!
+//! Only the sequential `TransactionEngine` expires disputes: the async engine
+//! processes clients concurrently, so "N records later" has no stable meaning
+//! there.
+
+use crate::core::hash::KeyMap;
+use crate::types::{ClientId, TransactionId};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Audit event for a dispute the engine resolved because it expired
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExpiredDispute {
+    /// The disputed transaction
+    pub tx: TransactionId,
+    /// Client whose held funds were released
+    pub client: ClientId,
+    /// Amount moved from held back to available
+    pub amount: Decimal,
+    /// Number of the record that opened the dispute
+    pub disputed_at: u64,
+    /// Number of the record before which the dispute expired
+    pub expired_at: u64,
+}
+
+impl fmt::Display for ExpiredDispute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Dispute of transaction {} for client {} (record {}) expired at record {}, released {}",
+            self.tx, self.client, self.disputed_at, self.expired_at, self.amount
+        )
+    }
+}
+
+/// Open disputes in the order they were opened, checked against an expiry
+#[derive(Debug, Clone)]
+pub struct DisputeExpiry {
+    /// Number of records after a dispute within which it must be closed
+    after: u32,
+    /// Number of records seen so far
+    records: u64,
+    /// Record number of every open dispute, by client and transaction
+    open: KeyMap<(ClientId, TransactionId), u64>,
+    /// Disputes in the order they were opened; entries of disputes closed
+    /// since are skipped when they reach the front
+    queue: VecDeque<(u64, ClientId, TransactionId)>,
+}
+
+impl DisputeExpiry {
+    /// Create an expiry of disputes not closed within `after` records
+    pub fn new(after: u32) -> Self {
+        Self {
+            after,
+            records: 0,
+            open: KeyMap::default(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Number of the current record (1 for the first record)
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Count a new record, returning the disputes that expire before it
+    ///
+    /// Each expired dispute is returned with its client and the number of the
+    /// record that opened it, and is no longer tracked.
+    pub fn next_record(&mut self) -> Vec<(ClientId, TransactionId, u64)> {
+        self.records += 1;
+        let mut expired = Vec::new();
+        while let Some(&(disputed_at, client, tx)) = self.queue.front() {
+            if disputed_at + u64::from(self.after) >= self.records {
+                break;
+            }
+            self.queue.pop_front();
+            if self.open.get(&(client, tx)) == Some(&disputed_at) {
+                self.open.remove(&(client, tx));
+                expired.push((client, tx, disputed_at));
+            }
+        }
+        expired
+    }
+
+    /// Track a dispute opened by the current record
+    pub fn opened(&mut self, client: ClientId, tx: TransactionId) {
+        self.open.insert((client, tx), self.records);
+        self.queue.push_back((self.records, client, tx));
+    }
+
+    /// Stop tracking a dispute that was resolved or charged back
+    pub fn closed(&mut self, client: ClientId, tx: TransactionId) {
+        self.open.remove(&(client, tx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dispute_expires_after_records() {
+        let mut expiry = DisputeExpiry::new(2);
+        assert!(expiry.next_record().is_empty());
+        expiry.opened(1, 7);
+
+        assert!(expiry.next_record().is_empty());
+        assert!(expiry.next_record().is_empty());
+        assert_eq!(expiry.next_record(), vec![(1, 7, 1)]);
+        assert_eq!(expiry.records(), 4);
+        assert!(expiry.next_record().is_empty());
+    }
+
+    #[test]
+    fn test_closed_dispute_does_not_expire() {
+        let mut expiry = DisputeExpiry::new(1);
+        expiry.next_record();
+        expiry.opened(1, 7);
+        expiry.next_record();
+        expiry.closed(1, 7);
+
+        assert!(expiry.next_record().is_empty());
+    }
+
+    #[test]
+    fn test_redispute_restarts_expiry() {
+        let mut expiry = DisputeExpiry::new(2);
+        expiry.next_record();
+        expiry.opened(1, 7);
+        expiry.next_record();
+        expiry.closed(1, 7);
+        expiry.next_record();
+        expiry.opened(1, 7);
+
+        // The first dispute would expire now, the second two records later
+        assert!(expiry.next_record().is_empty());
+        assert!(expiry.next_record().is_empty());
+        assert_eq!(expiry.next_record(), vec![(1, 7, 3)]);
+    }
+}
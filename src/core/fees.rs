@@ -0,0 +1,215 @@
+//! Per-transaction fees
+//!
+//! A fee schedule charges a fee on every deposit and withdrawal: a fixed
+//! amount, a percentage of the transaction amount, or both. The fee is debited
+//! from the client's available funds and credited to a designated
+//! fee-collection account, in the same step that applies the transaction, so
+//! the fee account's row in the account output always matches the fees
+//! charged.
+//!
+//! The fee account is a regular client account. It is charged no fees itself,
+//! and fees are credited to it even when it is locked. Fees are not refunded
+//! when the transaction is later disputed, charged back or, for a withdrawal
+//! held for approval, rejected.
+
+use crate::types::{ClientId, PaymentError, TransactionType};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::str::FromStr;
+
+/// A fee of a fixed amount plus a percentage of the transaction amount
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fee {
+    /// Fixed amount charged per transaction
+    pub fixed: Decimal,
+    /// Percentage of the transaction amount charged on top
+    pub percent: Decimal,
+}
+
+impl Fee {
+    /// The fee on a transaction of `amount`, rounded to four decimal places
+    ///
+    /// Returns `None` if the fee overflows.
+    pub fn on(&self, amount: Decimal) -> Option<Decimal> {
+        amount
+            .checked_mul(self.percent)?
+            .checked_div(Decimal::ONE_HUNDRED)?
+            .checked_add(self.fixed)
+            .map(|fee| fee.round_dp(4))
+    }
+}
+
+impl FromStr for Fee {
+    type Err = String;
+
+    /// Parse `AMOUNT`, `PERCENT%` or `AMOUNT+PERCENT%`, e.g. `0.25+1.5%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid fee '{}': expected AMOUNT, PERCENT% or AMOUNT+PERCENT%",
+                s
+            )
+        };
+        let (fixed, percent) = match s.split_once('+') {
+            Some((fixed, percent)) => (Some(fixed), Some(percent)),
+            None if s.trim_end().ends_with('%') => (None, Some(s)),
+            None => (Some(s), None),
+        };
+        let fixed = match fixed {
+            Some(fixed) => Decimal::from_str(fixed.trim()).map_err(|_| invalid())?,
+            None => Decimal::ZERO,
+        };
+        let percent = match percent {
+            Some(percent) => {
+                let percent = percent.trim().strip_suffix('%').ok_or_else(invalid)?;
+                Decimal::from_str(percent.trim()).map_err(|_| invalid())?
+            }
+            None => Decimal::ZERO,
+        };
+        if fixed.is_sign_negative() || percent.is_sign_negative() {
+            return Err(format!("invalid fee '{}': must not be negative", s));
+        }
+        Ok(Self { fixed, percent })
+    }
+}
+
+impl fmt::Display for Fee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.fixed.is_zero(), self.percent.is_zero()) {
+            (false, false) => write!(f, "{}+{}%", self.fixed, self.percent),
+            (true, false) => write!(f, "{}%", self.percent),
+            _ => write!(f, "{}", self.fixed),
+        }
+    }
+}
+
+/// Fees charged on deposits and withdrawals, and the account collecting them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// Client account the fees are credited to
+    pub account: ClientId,
+    /// Fee on every deposit, if any
+    pub deposit: Option<Fee>,
+    /// Fee on every withdrawal, if any
+    pub withdrawal: Option<Fee>,
+}
+
+impl FeeSchedule {
+    /// Create a schedule collecting fees in `account`, with no fees yet
+    pub fn new(account: ClientId) -> Self {
+        Self {
+            account,
+            deposit: None,
+            withdrawal: None,
+        }
+    }
+
+    /// Set the fee on deposits
+    pub fn with_deposit_fee(mut self, fee: Fee) -> Self {
+        self.deposit = Some(fee);
+        self
+    }
+
+    /// Set the fee on withdrawals
+    pub fn with_withdrawal_fee(mut self, fee: Fee) -> Self {
+        self.withdrawal = Some(fee);
+        self
+    }
+
+    /// The fee charged to `client` for a transaction of `amount`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(Decimal))` - The fee, if it is not zero
+    /// * `Ok(None)` - If the transaction is charged no fee, such as one of the
+    ///   fee account itself
+    /// * `Err(PaymentError::ArithmeticOverflow)` - If the fee overflows
+    pub fn fee(
+        &self,
+        tx_type: TransactionType,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<Option<Decimal>, PaymentError> {
+        let fee = match tx_type {
+            TransactionType::Deposit => self.deposit,
+            TransactionType::Withdrawal => self.withdrawal,
+            _ => None,
+        };
+        let Some(fee) = fee.filter(|_| client != self.account) else {
+            return Ok(None);
+        };
+        let fee = fee
+            .on(amount)
+            .ok_or_else(|| PaymentError::arithmetic_overflow("fee", client))?;
+        Ok((!fee.is_zero()).then_some(fee))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::fixed("0.25", Decimal::new(25, 2), Decimal::ZERO)]
+    #[case::percent("1.5%", Decimal::ZERO, Decimal::new(15, 1))]
+    #[case::both(" 0.25 + 1.5% ", Decimal::new(25, 2), Decimal::new(15, 1))]
+    fn test_parse_fee(#[case] input: &str, #[case] fixed: Decimal, #[case] percent: Decimal) {
+        assert_eq!(input.parse::<Fee>(), Ok(Fee { fixed, percent }));
+    }
+
+    #[rstest]
+    #[case::empty("", "expected AMOUNT")]
+    #[case::not_a_number("free", "expected AMOUNT")]
+    #[case::percent_first("1%+0.25", "expected AMOUNT")]
+    #[case::negative("-0.25", "must not be negative")]
+    fn test_parse_fee_invalid(#[case] input: &str, #[case] expected: &str) {
+        let err = input.parse::<Fee>().unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    #[test]
+    fn test_fee_display_round_trips() {
+        for input in ["0.25", "1.5%", "0.25+1.5%"] {
+            assert_eq!(input.parse::<Fee>().unwrap().to_string(), input);
+        }
+    }
+
+    #[rstest]
+    #[case::deposit(TransactionType::Deposit, 1, 1000, Ok(Some(Decimal::new(1025, 2))))]
+    #[case::withdrawal(TransactionType::Withdrawal, 1, 1000, Ok(Some(Decimal::new(5, 1))))]
+    #[case::dispute(TransactionType::Dispute, 1, 1000, Ok(None))]
+    #[case::fee_account(TransactionType::Deposit, 9, 1000, Ok(None))]
+    #[case::zero(TransactionType::Withdrawal, 2, 1000, Ok(None))]
+    fn test_fee(
+        #[case] tx_type: TransactionType,
+        #[case] client: ClientId,
+        #[case] amount: i64,
+        #[case] expected: Result<Option<Decimal>, PaymentError>,
+    ) {
+        let schedule = FeeSchedule::new(9)
+            .with_deposit_fee("0.25+1%".parse().unwrap())
+            .with_withdrawal_fee(if client == 2 {
+                Fee::default()
+            } else {
+                "0.5".parse().unwrap()
+            });
+        assert_eq!(
+            schedule.fee(tx_type, client, Decimal::from(amount)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_rounded_fee() {
+        let fee: Fee = "0.1%".parse().unwrap();
+        assert_eq!(fee.on(Decimal::new(12355, 2)), Some(Decimal::new(1236, 4)));
+        assert_eq!(Fee::default().on(Decimal::ONE), Some(Decimal::ZERO));
+
+        let fee = Fee {
+            fixed: Decimal::ZERO,
+            percent: Decimal::from(200),
+        };
+        assert_eq!(fee.on(Decimal::MAX), None);
+    }
+}
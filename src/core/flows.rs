@@ -0,0 +1,116 @@
+//! Money flowing into and out of an engine
+//!
+//! Every applied transaction that moves money changes the sum of all account
+//! totals by a known amount: deposits add to it, withdrawals and chargebacks
+//! remove from it, and disputes and resolves only move funds between available
+//! and held. A withdrawal held for approval only moves funds to held too; the
+//! approve that completes it removes them. Interest credited on the held funds
+//! released by a resolve adds to it (see `EngineConfig::held_interest`). `MoneyFlows` accumulates these amounts independently of the
+//! account balances, so the two can be compared at the end of a run to detect
+//! accounting bugs (see `Conservation` in the strategy summary).
+
+use crate::types::TransactionType;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+/// Totals of the money moved by successfully applied transactions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MoneyFlows {
+    /// Sum of applied deposits
+    pub deposited: Decimal,
+    /// Sum of applied and approved withdrawals
+    pub withdrawn: Decimal,
+    /// Sum of the transactions reversed by applied chargebacks
+    pub charged_back: Decimal,
+    /// Sum of the interest credited on released held funds
+    pub interest: Decimal,
+}
+
+impl MoneyFlows {
+    /// Record an applied transaction moving `amount`
+    ///
+    /// An approve records its withdrawal as withdrawn. Disputes, resolves and
+    /// rejects move no money in or out and are ignored. The sums saturate
+    /// rather than overflow.
+    pub fn record(&mut self, tx_type: TransactionType, amount: Decimal) {
+        match tx_type {
+            TransactionType::Deposit => self.deposited = self.deposited.saturating_add(amount),
+            TransactionType::Withdrawal | TransactionType::Approve => {
+                self.withdrawn = self.withdrawn.saturating_add(amount)
+            }
+            TransactionType::Chargeback => {
+                self.charged_back = self.charged_back.saturating_add(amount)
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Reject => {}
+        }
+    }
+
+    /// Record interest credited on released held funds
+    pub fn record_interest(&mut self, interest: Decimal) {
+        self.interest = self.interest.saturating_add(interest);
+    }
+
+    /// Add the flows of another run over disjoint accounts, such as a shard
+    pub fn add(&mut self, other: &MoneyFlows) {
+        self.deposited = self.deposited.saturating_add(other.deposited);
+        self.withdrawn = self.withdrawn.saturating_add(other.withdrawn);
+        self.charged_back = self.charged_back.saturating_add(other.charged_back);
+        self.interest = self.interest.saturating_add(other.interest);
+    }
+
+    /// The sum of all account totals these flows should have produced
+    pub fn expected_total(&self) -> Decimal {
+        self.deposited
+            .saturating_add(self.interest)
+            .saturating_sub(self.withdrawn)
+            .saturating_sub(self.charged_back)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::deposit(TransactionType::Deposit, 10, 0, 0)]
+    #[case::withdrawal(TransactionType::Withdrawal, 0, 10, 0)]
+    #[case::chargeback(TransactionType::Chargeback, 0, 0, 10)]
+    #[case::dispute(TransactionType::Dispute, 0, 0, 0)]
+    #[case::resolve(TransactionType::Resolve, 0, 0, 0)]
+    #[case::approve(TransactionType::Approve, 0, 10, 0)]
+    #[case::reject(TransactionType::Reject, 0, 0, 0)]
+    fn test_record(
+        #[case] tx_type: TransactionType,
+        #[case] deposited: i64,
+        #[case] withdrawn: i64,
+        #[case] charged_back: i64,
+    ) {
+        let mut flows = MoneyFlows::default();
+        flows.record(tx_type, Decimal::from(10));
+        assert_eq!(
+            flows,
+            MoneyFlows {
+                deposited: Decimal::from(deposited),
+                withdrawn: Decimal::from(withdrawn),
+                charged_back: Decimal::from(charged_back),
+                interest: Decimal::ZERO,
+            }
+        );
+    }
+
+    #[test]
+    fn test_expected_total() {
+        let flows = MoneyFlows {
+            deposited: Decimal::from(100),
+            withdrawn: Decimal::from(30),
+            charged_back: Decimal::from(20),
+            interest: Decimal::ZERO,
+        };
+        assert_eq!(flows.expected_total(), Decimal::from(50));
+
+        let mut flows = flows;
+        flows.record_interest(Decimal::new(25, 1));
+        assert_eq!(flows.expected_total(), Decimal::new(525, 1));
+    }
+}
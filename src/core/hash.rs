@@ -0,0 +1,65 @@
+//! Hasher of the engines' internal maps
+//!
+//! Accounts, stored transactions and the per-client state of the engines are
+//! kept in maps keyed by client and transaction IDs. These are small integers,
+//! for which the standard library's SipHash spends most of a lookup on
+//! hashing. Two features select a faster non-cryptographic hasher instead:
+//!
+//! - `fxhash`: `rustc_hash::FxBuildHasher`, a multiply-rotate hash that is
+//!   the fastest for integer keys. It is not seeded, so input crafted to
+//!   collide can slow the maps down; use it for trusted input only.
+//! - `ahash`: `ahash::RandomState`, nearly as fast and seeded at random, so it
+//!   keeps SipHash's resistance to such input.
+//!
+//! If both are enabled, `fxhash` is used.
+
+use std::collections::HashMap;
+
+/// Hasher of the engines' internal maps (SipHash, the standard library's)
+#[cfg(not(any(feature = "fxhash", feature = "ahash")))]
+pub type KeyHasher = std::collections::hash_map::RandomState;
+
+/// Hasher of the engines' internal maps (feature `fxhash`: FxHash)
+#[cfg(feature = "fxhash")]
+pub type KeyHasher = rustc_hash::FxBuildHasher;
+
+/// Hasher of the engines' internal maps (feature `ahash`: aHash)
+#[cfg(all(feature = "ahash", not(feature = "fxhash")))]
+pub type KeyHasher = ahash::RandomState;
+
+/// Name of the selected hasher
+#[cfg(not(any(feature = "fxhash", feature = "ahash")))]
+pub const KEY_HASHER_NAME: &str = "siphash";
+
+/// Name of the selected hasher
+#[cfg(feature = "fxhash")]
+pub const KEY_HASHER_NAME: &str = "fxhash";
+
+/// Name of the selected hasher
+#[cfg(all(feature = "ahash", not(feature = "fxhash")))]
+pub const KEY_HASHER_NAME: &str = "ahash";
+
+/// HashMap keyed by client or transaction IDs, using the selected hasher
+pub type KeyMap<K, V> = HashMap<K, V, KeyHasher>;
+
+/// Create an empty `KeyMap` with room for `capacity` entries
+pub fn key_map<K, V>(capacity: usize) -> KeyMap<K, V> {
+    HashMap::with_capacity_and_hasher(capacity, KeyHasher::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_map() {
+        let mut map: KeyMap<u64, u16> = key_map(100);
+        assert!(map.capacity() >= 100);
+        for key in 0..1000 {
+            map.insert(key, (key % 7) as u16);
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get(&699), Some(&6));
+        assert_eq!(map.get(&1000), None);
+    }
+}
@@ -0,0 +1,106 @@
+//! Per-client balance history
+//!
+//! The account output only shows the final balances. For charting and
+//! anomaly detection, the engines can also sample a client's balances after
+//! every K of its applied transactions, giving a compact running balance
+//! series per client without replaying the input. With
+//! `EngineConfig::balance_history` set, the engines record a `BalancePoint`
+//! for every sample and hand them out through `Engine::take_balance_history`.
+
+use crate::core::hash::KeyMap;
+use crate::types::{Account, ClientId};
+use rust_decimal::Decimal;
+
+/// A client's balances after one of its applied transactions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalancePoint {
+    /// The client
+    pub client: ClientId,
+    /// Number of the client's transactions applied so far, including this one
+    pub transactions: u64,
+    /// Available funds after the transaction
+    pub available: Decimal,
+    /// Held funds after the transaction
+    pub held: Decimal,
+    /// Total funds after the transaction
+    pub total: Decimal,
+    /// Whether the account is locked after the transaction
+    pub locked: bool,
+}
+
+/// Sampler of every client's balances after every K applied transactions
+#[derive(Debug, Clone)]
+pub struct BalanceHistory {
+    /// Number of a client's transactions between samples (at least 1)
+    every: u64,
+    /// Number of applied transactions of every client
+    counts: KeyMap<ClientId, u64>,
+    /// Samples since the last `take`
+    points: Vec<BalancePoint>,
+}
+
+impl BalanceHistory {
+    /// Create a sampler of every `every`-th transaction of each client
+    pub fn new(every: u32) -> Self {
+        Self {
+            every: u64::from(every.max(1)),
+            counts: KeyMap::default(),
+            points: Vec::new(),
+        }
+    }
+
+    /// Count a transaction applied to an account, sampling it if due
+    pub fn record(&mut self, account: &Account) {
+        let count = self.counts.entry(account.client).or_default();
+        *count += 1;
+        if count.is_multiple_of(self.every) {
+            self.points.push(BalancePoint {
+                client: account.client,
+                transactions: *count,
+                available: account.available,
+                held: account.held,
+                total: account.total,
+                locked: account.locked,
+            });
+        }
+    }
+
+    /// Take the samples recorded since the last call
+    pub fn take(&mut self) -> Vec<BalancePoint> {
+        std::mem::take(&mut self.points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_every_kth_transaction_per_client() {
+        let mut history = BalanceHistory::new(2);
+        let mut account = Account::new(1);
+        let other = Account::new(2);
+
+        for available in 1..=5 {
+            account.available = Decimal::from(available);
+            history.record(&account);
+            history.record(&other);
+        }
+
+        let points = history.take();
+        let samples: Vec<_> = points
+            .iter()
+            .map(|point| (point.client, point.transactions, point.available))
+            .collect();
+        assert_eq!(
+            samples,
+            vec![
+                (1, 2, Decimal::from(2)),
+                (2, 2, Decimal::ZERO),
+                (1, 4, Decimal::from(4)),
+                (2, 4, Decimal::ZERO),
+            ]
+        );
+        assert!(history.take().is_empty());
+    }
+}
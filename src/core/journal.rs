@@ -0,0 +1,338 @@
+//! Double-entry postings for applied transactions
+//!
+//! The account output only shows where each client's balances ended up. For a
+//! general ledger, every movement of money is also needed as a balanced
+//! posting: one ledger account is debited and another credited with the same
+//! amount. With `EngineConfig::journal` enabled, the engines record a
+//! `Posting` for every transaction they apply, including the implicit dispute
+//! of a direct chargeback and the resolve of an expired dispute, and hand them
+//! out through `Engine::take_postings`.
+//!
+//! Each client has an `available` and a `held` ledger account. Money enters
+//! and leaves the system through the `settlement` account:
+//!
+//! | Transaction | Debit | Credit |
+//! |-------------|-------|--------|
+//! | deposit     | settlement | client available |
+//! | withdrawal  | client available | settlement |
+//! | dispute     | client available | client held |
+//! | resolve     | client held | client available |
+//! | chargeback  | client held | settlement |
+//! | approve     | client held | settlement |
+//! | reject      | client held | client available |
+//!
+//! A withdrawal held for approval (`EngineConfig::withdrawal_approval_above`)
+//! debits the client's available and credits its held account instead; the
+//! approve or reject that completes it moves the funds on.
+//!
+//! Interest credited by a resolve (`EngineConfig::held_interest`) is a second
+//! resolve posting, debiting settlement and crediting the client's available
+//! account. The fee charged for a deposit or withdrawal (`EngineConfig::fees`)
+//! is a second posting of that transaction, debiting the client's available
+//! account and crediting the fee account's.
+//!
+//! Client accounts are liabilities, so credits raise and debits lower their
+//! balance: the credits minus the debits of a client account add up to its
+//! final balance. The settlement account stands for the cash behind them.
+//!
+//! A `TrialBalance` totals the postings of a run per ledger account. Every
+//! posting debits and credits the same amount, so the balances of all
+//! accounts, including the settlement and fee accounts, sum to zero.
+
+use crate::types::{ClientId, TransactionId, TransactionType};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Ledger account debited or credited by a posting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// Money held outside the engine: deposits come from and withdrawals and
+    /// chargebacks go to this account
+    Settlement,
+    /// A client's available funds
+    Available(ClientId),
+    /// A client's held (disputed) funds
+    Held(ClientId),
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerAccount::Settlement => write!(f, "settlement"),
+            LedgerAccount::Available(client) => write!(f, "client:{}:available", client),
+            LedgerAccount::Held(client) => write!(f, "client:{}:held", client),
+        }
+    }
+}
+
+/// Balanced movement of money caused by one applied transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Posting {
+    /// The transaction, or the disputed transaction for disputes, resolves and
+    /// chargebacks, or the pending withdrawal for approves and rejects
+    pub tx: TransactionId,
+    /// Client whose account the transaction was applied to
+    pub client: ClientId,
+    /// Type of the applied transaction
+    pub tx_type: TransactionType,
+    /// Account debited
+    pub debit: LedgerAccount,
+    /// Account credited
+    pub credit: LedgerAccount,
+    /// Amount moved
+    pub amount: Decimal,
+}
+
+impl Posting {
+    /// Create the posting of a transaction applied to a client's account
+    pub fn new(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Decimal,
+    ) -> Self {
+        let (debit, credit) = match tx_type {
+            TransactionType::Deposit => {
+                (LedgerAccount::Settlement, LedgerAccount::Available(client))
+            }
+            TransactionType::Withdrawal => {
+                (LedgerAccount::Available(client), LedgerAccount::Settlement)
+            }
+            TransactionType::Dispute => (
+                LedgerAccount::Available(client),
+                LedgerAccount::Held(client),
+            ),
+            TransactionType::Resolve => (
+                LedgerAccount::Held(client),
+                LedgerAccount::Available(client),
+            ),
+            TransactionType::Chargeback | TransactionType::Approve => {
+                (LedgerAccount::Held(client), LedgerAccount::Settlement)
+            }
+            TransactionType::Reject => (
+                LedgerAccount::Held(client),
+                LedgerAccount::Available(client),
+            ),
+        };
+        Self {
+            tx,
+            client,
+            tx_type,
+            debit,
+            credit,
+            amount,
+        }
+    }
+
+    /// Create the posting of the interest a resolve credits on released funds
+    pub fn interest(client: ClientId, tx: TransactionId, amount: Decimal) -> Self {
+        Self {
+            debit: LedgerAccount::Settlement,
+            credit: LedgerAccount::Available(client),
+            ..Self::new(TransactionType::Resolve, client, tx, amount)
+        }
+    }
+
+    /// Create the posting of the fee charged to a client for a transaction
+    pub fn fee(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        fee_account: ClientId,
+        amount: Decimal,
+    ) -> Self {
+        Self {
+            debit: LedgerAccount::Available(client),
+            credit: LedgerAccount::Available(fee_account),
+            ..Self::new(tx_type, client, tx, amount)
+        }
+    }
+
+    /// Whether this is the posting of a fee, which moves funds to another
+    /// client's account
+    pub fn is_fee(&self) -> bool {
+        matches!(self.credit, LedgerAccount::Available(client) if client != self.client)
+    }
+
+    /// Create the posting of a withdrawal held pending approval
+    pub fn pending_withdrawal(client: ClientId, tx: TransactionId, amount: Decimal) -> Self {
+        Self {
+            debit: LedgerAccount::Available(client),
+            credit: LedgerAccount::Held(client),
+            ..Self::new(TransactionType::Withdrawal, client, tx, amount)
+        }
+    }
+}
+
+/// Debit and credit totals of one ledger account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrialBalanceRow {
+    /// The account
+    pub account: LedgerAccount,
+    /// Whether it is a system account: the settlement account, or a client
+    /// account that collected fees
+    pub system: bool,
+    /// Sum of the amounts debited
+    pub debit: Decimal,
+    /// Sum of the amounts credited
+    pub credit: Decimal,
+}
+
+impl TrialBalanceRow {
+    /// Credits minus debits, the balance of a client account
+    pub fn balance(&self) -> Decimal {
+        self.credit.saturating_sub(self.debit)
+    }
+}
+
+/// Debit and credit totals of every ledger account posted to in a run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrialBalance {
+    /// Debit and credit totals per account
+    accounts: BTreeMap<LedgerAccount, (Decimal, Decimal)>,
+    /// Clients whose available account was credited with a fee
+    fee_accounts: BTreeSet<ClientId>,
+}
+
+impl TrialBalance {
+    /// Create an empty trial balance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a posting to the totals of the accounts it debits and credits
+    pub fn record(&mut self, posting: &Posting) {
+        let debit = &mut self.accounts.entry(posting.debit).or_default().0;
+        *debit = debit.saturating_add(posting.amount);
+        let credit = &mut self.accounts.entry(posting.credit).or_default().1;
+        *credit = credit.saturating_add(posting.amount);
+        if let (true, LedgerAccount::Available(client)) = (posting.is_fee(), posting.credit) {
+            self.fee_accounts.insert(client);
+        }
+    }
+
+    /// Whether an account is a system account
+    fn is_system(&self, account: LedgerAccount) -> bool {
+        match account {
+            LedgerAccount::Settlement => true,
+            LedgerAccount::Available(client) | LedgerAccount::Held(client) => {
+                self.fee_accounts.contains(&client)
+            }
+        }
+    }
+
+    /// The totals of every account: the system accounts first, then the
+    /// client accounts by client, each client's available before its held
+    /// account
+    pub fn rows(&self) -> Vec<TrialBalanceRow> {
+        let mut rows: Vec<TrialBalanceRow> = self
+            .accounts
+            .iter()
+            .map(|(&account, &(debit, credit))| TrialBalanceRow {
+                account,
+                system: self.is_system(account),
+                debit,
+                credit,
+            })
+            .collect();
+        let client = |account: LedgerAccount| match account {
+            LedgerAccount::Settlement => None,
+            LedgerAccount::Available(client) | LedgerAccount::Held(client) => Some(client),
+        };
+        // Stable, so each client's accounts keep their `Available, Held` order
+        rows.sort_by_key(|row| (!row.system, client(row.account)));
+        rows
+    }
+
+    /// Sums of the debits and of the credits of all accounts, which are equal
+    pub fn totals(&self) -> (Decimal, Decimal) {
+        self.accounts.values().fold(
+            (Decimal::ZERO, Decimal::ZERO),
+            |(d, c), &(debit, credit)| (d.saturating_add(debit), c.saturating_add(credit)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::deposit(TransactionType::Deposit, "settlement", "client:7:available")]
+    #[case::withdrawal(TransactionType::Withdrawal, "client:7:available", "settlement")]
+    #[case::dispute(TransactionType::Dispute, "client:7:available", "client:7:held")]
+    #[case::resolve(TransactionType::Resolve, "client:7:held", "client:7:available")]
+    #[case::chargeback(TransactionType::Chargeback, "client:7:held", "settlement")]
+    #[case::approve(TransactionType::Approve, "client:7:held", "settlement")]
+    #[case::reject(TransactionType::Reject, "client:7:held", "client:7:available")]
+    fn test_posting_accounts(
+        #[case] tx_type: TransactionType,
+        #[case] debit: &str,
+        #[case] credit: &str,
+    ) {
+        let posting = Posting::new(tx_type, 7, 1, Decimal::ONE);
+        assert_eq!(posting.debit.to_string(), debit);
+        assert_eq!(posting.credit.to_string(), credit);
+    }
+
+    #[test]
+    fn test_pending_withdrawal_posting() {
+        let posting = Posting::pending_withdrawal(7, 1, Decimal::ONE);
+        assert_eq!(posting.tx_type, TransactionType::Withdrawal);
+        assert_eq!(posting.debit.to_string(), "client:7:available");
+        assert_eq!(posting.credit.to_string(), "client:7:held");
+    }
+
+    #[test]
+    fn test_fee_posting() {
+        let posting = Posting::fee(TransactionType::Deposit, 7, 1, 99, Decimal::ONE);
+        assert_eq!(posting.tx_type, TransactionType::Deposit);
+        assert_eq!(posting.debit.to_string(), "client:7:available");
+        assert_eq!(posting.credit.to_string(), "client:99:available");
+        assert!(posting.is_fee());
+        assert!(!Posting::new(TransactionType::Deposit, 7, 1, Decimal::ONE).is_fee());
+    }
+
+    #[test]
+    fn test_trial_balance() {
+        let mut trial_balance = TrialBalance::new();
+        for posting in [
+            Posting::new(TransactionType::Deposit, 2, 1, Decimal::TEN),
+            Posting::fee(TransactionType::Deposit, 2, 1, 9, Decimal::ONE),
+            Posting::new(TransactionType::Deposit, 1, 2, Decimal::from(5)),
+            Posting::new(TransactionType::Dispute, 1, 2, Decimal::from(5)),
+        ] {
+            trial_balance.record(&posting);
+        }
+
+        let rows: Vec<(String, bool, Decimal)> = trial_balance
+            .rows()
+            .iter()
+            .map(|row| (row.account.to_string(), row.system, row.balance()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("settlement".to_string(), true, Decimal::from(-15)),
+                ("client:9:available".to_string(), true, Decimal::ONE),
+                ("client:1:available".to_string(), false, Decimal::ZERO),
+                ("client:1:held".to_string(), false, Decimal::from(5)),
+                ("client:2:available".to_string(), false, Decimal::from(9)),
+            ]
+        );
+        assert_eq!(
+            trial_balance.totals(),
+            (Decimal::from(21), Decimal::from(21))
+        );
+    }
+
+    #[test]
+    fn test_interest_posting() {
+        let posting = Posting::interest(7, 1, Decimal::ONE);
+        assert_eq!(posting.tx_type, TransactionType::Resolve);
+        assert_eq!(posting.debit.to_string(), "settlement");
+        assert_eq!(posting.credit.to_string(), "client:7:available");
+    }
+}
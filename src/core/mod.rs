@@ -4,16 +4,77 @@
 //! - `traits` - Trait abstractions for interchangeable implementations
 //! - `engine` - Transaction processing orchestration
 //! - `account_manager` - Account state management and balance operations
+//! - `changes` - Balance change events derived from postings
 //! - `transaction_store` - Transaction storage for dispute resolution
-//! - `async` - Asynchronous implementations (feature-gated)
+//! - `compact` - Compact encoding of stored transactions (feature `compact-store`)
+//! - `config` - Engine configuration (account metadata and risk rules)
+//! - `expiry` - Expiration of disputes that are never resolved or charged back
+//! - `journal` - Double-entry postings for applied transactions
+//! - `fees` - Per-transaction fees credited to a fee account
+//! - `flows` - Money moved in and out by applied transactions
+//! - `hash` - Hasher of the internal maps (features `fxhash` and `ahash`)
+//! - `history` - Per-client balance history sampled every K transactions
+//! - `reconcile` - Comparison of final balances against expected balances
+//! - `report` - Per-record outcomes returned by `Engine::process_all`
+//! - `retry` - Retry policy with exponential backoff
+//! - `async` - Asynchronous implementations (feature `native`)
+//! - `persistent_accounts` - Account manager persisted in an embedded key-value
+//!   store (feature `sqlite`)
+//! - `sqlite_ledger` - SQLite-backed persistent ledger (feature `sqlite`)
+//! - `state` - State files holding an engine snapshot (`--save-state`)
+//! - `velocity` - Velocity limits on withdrawals
+//! - `wal` - Write-ahead log, crash recovery and read-only replicas
 
 pub mod account_manager;
+#[cfg(feature = "native")]
 pub mod r#async;
+pub mod changes;
+pub(crate) mod compact;
+pub mod config;
 pub mod engine;
+pub mod expiry;
+pub mod fees;
+pub mod flows;
+pub mod hash;
+pub mod history;
+pub mod journal;
+#[cfg(feature = "sqlite")]
+pub mod persistent_accounts;
+pub mod reconcile;
+pub mod report;
+pub mod retry;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_ledger;
+pub mod state;
 pub mod traits;
 pub mod transaction_store;
+pub mod velocity;
+pub mod wal;
 
 pub use account_manager::AccountManager;
+pub use changes::{BalanceChange, BalanceChanges, Balances};
+pub use config::{
+    AmountLimits, DisputableTypes, DuplicateTxPolicy, EngineConfig, MetadataMap,
+    MetadataRequirement, NegativeBalancePolicy, RedisputePolicy, RiskRules,
+};
 pub use engine::TransactionEngine;
-pub use r#async::{AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore};
+pub use expiry::{DisputeExpiry, ExpiredDispute};
+pub use fees::{Fee, FeeSchedule};
+pub use flows::MoneyFlows;
+pub use hash::KeyHasher;
+pub use history::{BalanceHistory, BalancePoint};
+pub use journal::{LedgerAccount, Posting, TrialBalance, TrialBalanceRow};
+#[cfg(feature = "fault-injection")]
+pub use r#async::FaultInjection;
+#[cfg(feature = "native")]
+pub use r#async::{
+    AccountRef, AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore, DuplicateFilter,
+};
+pub use reconcile::{reconcile, BalanceField, Discrepancy};
+pub use report::{ProcessingReport, ProcessingResult};
+pub use retry::RetryPolicy;
+pub use state::{is_state_file, load_state, save_state};
+pub use traits::{Engine, EngineSnapshot};
 pub use transaction_store::TransactionStore;
+pub use velocity::{VelocityLimit, VelocityTracker};
+pub use wal::{DurableEngine, WalReplica, WriteAheadLog};
@@ -0,0 +1,355 @@
+//! Persistent account manager over an embedded key-value store
+//!
+//! This module provides `PersistentAccountManager`, an implementation of the
+//! `AccountManager` trait that keeps every account in a database file instead
+//! of in memory, so account state survives restarts of a long-running
+//! process.
+//!
+//! # Storage
+//!
+//! The store is a key-value table in an embedded SQLite database (feature
+//! `sqlite`), with no other structure:
+//!
+//! ```sql
+//! CREATE TABLE account_kv (
+//!     key   BLOB PRIMARY KEY,   -- client ID, 8 bytes big-endian
+//!     value BLOB NOT NULL       -- the account, encoded with bincode
+//! );
+//! ```
+//!
+//! Big-endian keys sort like the client IDs, so `accounts` iterates the
+//! accounts in client order a page at a time, without loading all of them.
+//!
+//! # Durability and Errors
+//!
+//! Every write is committed before the method making it returns. `update`
+//! reports storage failures as `PaymentError::IoError` and leaves the stored
+//! account unchanged. `get_or_create`, `is_locked` and `get_all_accounts` cannot
+//! report errors through the trait, so they fall back to a new, unlocked
+//! account or to no accounts, and keep the first error for `take_error`; check
+//! it after processing, like a writer's error after formatting.
+
+use crate::core::traits::AccountManager;
+use crate::types::{Account, ClientId, PaymentError};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// Statement creating the key-value table if it does not exist yet
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS account_kv (
+        key   BLOB PRIMARY KEY,
+        value BLOB NOT NULL
+    ) WITHOUT ROWID;
+";
+
+/// Accounts read per page by `accounts`
+const PAGE_SIZE: usize = 1024;
+
+/// Account manager persisting every account in a key-value store
+#[derive(Debug)]
+pub struct PersistentAccountManager {
+    conn: Connection,
+    /// First storage error of a method that cannot return it
+    error: RefCell<Option<String>>,
+}
+
+impl PersistentAccountManager {
+    /// Open (or create) an account store file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the database file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(PersistentAccountManager)` with the accounts stored in the file
+    /// * `Err(String)` if the file could not be opened or initialized
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open account store '{}': {}", path.display(), e))?;
+        Self::init(conn)
+    }
+
+    /// Open an account store in an in-memory database
+    ///
+    /// Useful for tests; the accounts are discarded when dropped.
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open in-memory account store: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to initialize account store: {}", e))?;
+        Ok(Self {
+            conn,
+            error: RefCell::new(None),
+        })
+    }
+
+    /// Get the stored account of a client, if any
+    pub fn account(&self, client_id: ClientId) -> Result<Option<Account>, String> {
+        self.conn
+            .query_row(
+                "SELECT value FROM account_kv WHERE key = ?1",
+                params![key(client_id)],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()
+            .map_err(store_error)?
+            .map(|value| decode(&value))
+            .transpose()
+    }
+
+    /// Store an account, replacing the stored account of its client
+    pub fn put(&self, account: &Account) -> Result<(), String> {
+        let value = bincode::serialize(account)
+            .map_err(|e| format!("Failed to encode account {}: {}", account.client, e))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO account_kv (key, value) VALUES (?1, ?2)",
+                params![key(account.client), value],
+            )
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    /// Iterate over the stored accounts in client order
+    ///
+    /// Accounts are read a page at a time, so the iterator can write the
+    /// output of a store larger than memory.
+    pub fn accounts(&self) -> PersistentAccounts<'_> {
+        PersistentAccounts {
+            manager: self,
+            page: VecDeque::new(),
+            after: None,
+            done: false,
+        }
+    }
+
+    /// Take the first error of a method that could not return it
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.get_mut().take()
+    }
+
+    /// Keep the first error of a method that cannot return it
+    fn keep_error(&self, error: String) {
+        self.error.borrow_mut().get_or_insert(error);
+    }
+
+    /// Read the page of accounts after a key
+    fn page(&self, after: Option<&[u8]>) -> Result<VecDeque<(Vec<u8>, Account)>, String> {
+        let mut statement = self
+            .conn
+            .prepare_cached(
+                "SELECT key, value FROM account_kv WHERE key > ?1 ORDER BY key LIMIT ?2",
+            )
+            .map_err(store_error)?;
+        let rows = statement
+            .query_map(
+                params![after.unwrap_or_default(), PAGE_SIZE as i64],
+                |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            )
+            .map_err(store_error)?;
+        rows.map(|row| {
+            let (key, value) = row.map_err(store_error)?;
+            Ok((key, decode(&value)?))
+        })
+        .collect()
+    }
+}
+
+impl AccountManager for PersistentAccountManager {
+    /// Get the stored account, storing a new one if the client has none
+    fn get_or_create(&mut self, client_id: ClientId) -> Account {
+        let account = self.account(client_id).and_then(|account| match account {
+            Some(account) => Ok(account),
+            None => {
+                let account = Account::new(client_id);
+                self.put(&account)?;
+                Ok(account)
+            }
+        });
+        account.unwrap_or_else(|e| {
+            self.keep_error(e);
+            Account::new(client_id)
+        })
+    }
+
+    /// Apply `f` to the stored account (or a new one), storing the result
+    /// only if `f` succeeds
+    fn update<F>(&mut self, client_id: ClientId, f: F) -> Result<(), PaymentError>
+    where
+        F: FnOnce(&mut Account) -> Result<(), PaymentError>,
+    {
+        let mut account = self
+            .account(client_id)
+            .map_err(|message| PaymentError::IoError { message })?
+            .unwrap_or_else(|| Account::new(client_id));
+        f(&mut account)?;
+        self.put(&account)
+            .map_err(|message| PaymentError::IoError { message })
+    }
+
+    fn is_locked(&self, client_id: ClientId) -> bool {
+        match self.account(client_id) {
+            Ok(account) => account.is_some_and(|account| account.locked),
+            Err(e) => {
+                self.keep_error(e);
+                false
+            }
+        }
+    }
+
+    /// Get all accounts, sorted by client ID
+    ///
+    /// Prefer `accounts` for stores too large to hold in memory.
+    fn get_all_accounts(&self) -> Vec<Account> {
+        self.accounts()
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| {
+                self.keep_error(e);
+                Vec::new()
+            })
+    }
+}
+
+/// Iterator over the accounts of a `PersistentAccountManager`, in client
+/// order
+#[derive(Debug)]
+pub struct PersistentAccounts<'a> {
+    manager: &'a PersistentAccountManager,
+    /// Accounts read but not returned yet, with their keys
+    page: VecDeque<(Vec<u8>, Account)>,
+    /// Key of the last account read
+    after: Option<Vec<u8>>,
+    /// Whether the last page has been read, or reading failed
+    done: bool,
+}
+
+impl Iterator for PersistentAccounts<'_> {
+    type Item = Result<Account, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() && !self.done {
+            match self.manager.page(self.after.as_deref()) {
+                Ok(page) => {
+                    self.done = page.len() < PAGE_SIZE;
+                    self.after = page.back().map(|(key, _)| key.clone());
+                    self.page = page;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.page.pop_front().map(|(_, account)| Ok(account))
+    }
+}
+
+/// Key of a client's account: its ID, big-endian so keys sort like IDs
+// `ClientId` is already `u64` with the `client-id-u64` feature
+#[allow(clippy::useless_conversion)]
+fn key(client_id: ClientId) -> [u8; 8] {
+    u64::from(client_id).to_be_bytes()
+}
+
+/// Decode a stored account
+fn decode(value: &[u8]) -> Result<Account, String> {
+    bincode::deserialize(value).map_err(|e| format!("Invalid stored account: {}", e))
+}
+
+/// Convert a SQLite error into an error message
+fn store_error(error: rusqlite::Error) -> String {
+    format!("Account store error: {}", error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    fn deposit(amount: i64) -> impl FnOnce(&mut Account) -> Result<(), PaymentError> {
+        move |account| {
+            account.available += Decimal::from(amount);
+            account.total += Decimal::from(amount);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_accounts_survive_reopening() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.db");
+
+        let mut manager = PersistentAccountManager::open(&path).unwrap();
+        assert_eq!(manager.get_or_create(2), Account::new(2));
+        manager.update(1, deposit(10)).unwrap();
+        manager
+            .update(1, |account| {
+                account.locked = true;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(manager.take_error(), None);
+        drop(manager);
+
+        let mut manager = PersistentAccountManager::open(&path).unwrap();
+        let accounts = manager.get_all_accounts();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].client, 1);
+        assert_eq!(accounts[0].total, Decimal::from(10));
+        assert!(manager.is_locked(1));
+        assert!(!manager.is_locked(2));
+        assert!(!manager.is_locked(3));
+        assert_eq!(manager.get_or_create(1), accounts[0]);
+        assert_eq!(manager.take_error(), None);
+    }
+
+    #[test]
+    fn test_failed_update_is_not_stored() {
+        let mut manager = PersistentAccountManager::open_in_memory().unwrap();
+        manager.update(1, deposit(10)).unwrap();
+
+        let result = manager.update(1, |account| {
+            account.total = Decimal::ZERO;
+            Err(PaymentError::account_locked(1))
+        });
+
+        assert_eq!(result, Err(PaymentError::account_locked(1)));
+        assert_eq!(
+            manager.account(1).unwrap().unwrap().total,
+            Decimal::from(10)
+        );
+        assert_eq!(manager.account(2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_accounts_iterate_in_client_order_across_pages() {
+        let mut manager = PersistentAccountManager::open_in_memory().unwrap();
+        let clients: Vec<ClientId> = (0..(PAGE_SIZE * 2 + 10) as u64)
+            .rev()
+            .map(|client| (client * 7 % 5003) as ClientId)
+            .collect();
+        manager.conn.execute_batch("BEGIN").unwrap();
+        for &client in &clients {
+            manager.update(client, deposit(1)).unwrap();
+        }
+        manager.conn.execute_batch("COMMIT").unwrap();
+
+        let accounts: Vec<ClientId> = manager
+            .accounts()
+            .map(|account| account.unwrap().client)
+            .collect();
+
+        let mut expected = clients;
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(accounts, expected);
+    }
+}
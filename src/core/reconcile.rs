@@ -0,0 +1,183 @@
+//! Reconciliation of final balances against expected balances
+//!
+//! Compares the accounts computed by a run with a file of expected balances,
+//! such as a statement from a bank or from another system, and lists every
+//! difference: clients missing from the computed accounts, computed accounts
+//! nobody expected, and balances or lock states that differ.
+
+use crate::types::{Account, ClientId};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Balance field of an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceField {
+    Available,
+    Held,
+    Total,
+}
+
+impl fmt::Display for BalanceField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalanceField::Available => write!(f, "available"),
+            BalanceField::Held => write!(f, "held"),
+            BalanceField::Total => write!(f, "total"),
+        }
+    }
+}
+
+/// Difference between the expected and the computed accounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// An expected client has no computed account
+    Missing { client: ClientId },
+    /// A computed account has no expected balances
+    Unexpected { client: ClientId },
+    /// A balance differs from the expected one
+    Balance {
+        client: ClientId,
+        field: BalanceField,
+        expected: Decimal,
+        actual: Decimal,
+    },
+    /// The account is locked but expected unlocked, or the other way round
+    Locked {
+        client: ClientId,
+        expected: bool,
+        actual: bool,
+    },
+}
+
+impl Discrepancy {
+    /// Client the discrepancy is about
+    pub fn client(&self) -> ClientId {
+        match self {
+            Discrepancy::Missing { client }
+            | Discrepancy::Unexpected { client }
+            | Discrepancy::Balance { client, .. }
+            | Discrepancy::Locked { client, .. } => *client,
+        }
+    }
+}
+
+/// Compare computed accounts against expected balances
+///
+/// Accounts are matched by client; balances compare by value, so `1.5` and
+/// `1.5000` are equal.
+///
+/// # Returns
+///
+/// Every discrepancy, ordered by client and then by field
+pub fn reconcile(expected: &[Account], actual: &[Account]) -> Vec<Discrepancy> {
+    let mut clients: BTreeMap<ClientId, (Option<&Account>, Option<&Account>)> = BTreeMap::new();
+    for account in expected {
+        clients.entry(account.client).or_default().0 = Some(account);
+    }
+    for account in actual {
+        clients.entry(account.client).or_default().1 = Some(account);
+    }
+
+    let mut discrepancies = Vec::new();
+    for (client, accounts) in clients {
+        let (expected, actual) = match accounts {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (Some(_), None) => {
+                discrepancies.push(Discrepancy::Missing { client });
+                continue;
+            }
+            (None, _) => {
+                discrepancies.push(Discrepancy::Unexpected { client });
+                continue;
+            }
+        };
+        for (field, expected, actual) in [
+            (
+                BalanceField::Available,
+                expected.available,
+                actual.available,
+            ),
+            (BalanceField::Held, expected.held, actual.held),
+            (BalanceField::Total, expected.total, actual.total),
+        ] {
+            if expected != actual {
+                discrepancies.push(Discrepancy::Balance {
+                    client,
+                    field,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        if expected.locked != actual.locked {
+            discrepancies.push(Discrepancy::Locked {
+                client,
+                expected: expected.locked,
+                actual: actual.locked,
+            });
+        }
+    }
+    discrepancies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(client: ClientId, available: i64, held: i64, locked: bool) -> Account {
+        let mut account = Account::new(client);
+        account.available = Decimal::from(available);
+        account.held = Decimal::from(held);
+        account.total = Decimal::from(available + held);
+        account.locked = locked;
+        account
+    }
+
+    #[test]
+    fn test_matching_accounts_have_no_discrepancies() {
+        let mut rescaled = account(1, 10, 5, false);
+        rescaled.available = Decimal::new(100000, 4);
+
+        assert!(reconcile(&[account(1, 10, 5, false)], &[rescaled]).is_empty());
+    }
+
+    #[test]
+    fn test_discrepancies_ordered_by_client() {
+        let expected = [
+            account(3, 0, 0, false),
+            account(1, 10, 5, false),
+            account(2, 7, 0, false),
+        ];
+        let actual = [
+            account(1, 10, 5, false),
+            account(2, 6, 0, true),
+            account(4, 1, 0, false),
+        ];
+
+        assert_eq!(
+            reconcile(&expected, &actual),
+            vec![
+                Discrepancy::Balance {
+                    client: 2,
+                    field: BalanceField::Available,
+                    expected: Decimal::from(7),
+                    actual: Decimal::from(6),
+                },
+                Discrepancy::Balance {
+                    client: 2,
+                    field: BalanceField::Total,
+                    expected: Decimal::from(7),
+                    actual: Decimal::from(6),
+                },
+                Discrepancy::Locked {
+                    client: 2,
+                    expected: false,
+                    actual: true,
+                },
+                Discrepancy::Missing { client: 3 },
+                Discrepancy::Unexpected { client: 4 },
+            ]
+        );
+    }
+}
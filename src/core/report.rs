@@ -0,0 +1,123 @@
+//! Per-record outcomes of processing
+//!
+//! The strategies only count rejected records in the run summary. Library
+//! users embedding an engine often need to know exactly which records were
+//! rejected and why; `Engine::process_all` returns a `ProcessingReport` with
+//! the outcome of every record instead.
+
+use crate::types::{PaymentError, TransactionRecord};
+
+/// Result of processing a single transaction
+///
+/// Contains the original transaction record and the result of processing it.
+#[derive(Debug, Clone)]
+pub struct ProcessingResult {
+    /// The transaction record that was processed
+    pub record: TransactionRecord,
+
+    /// The result of processing (success or error)
+    pub result: Result<(), PaymentError>,
+
+    /// Number of times processing was attempted: 1, unless the record failed
+    /// with a transient error and was retried
+    pub attempts: u32,
+}
+
+/// Outcome of every record processed by `Engine::process_all`
+///
+/// Results are in input order, so the index of a result is the position of its
+/// record in the input.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessingReport {
+    /// One result per input record, in input order
+    pub results: Vec<ProcessingResult>,
+}
+
+impl ProcessingReport {
+    /// Number of records that were applied
+    pub fn succeeded(&self) -> usize {
+        self.results.len() - self.failed()
+    }
+
+    /// Number of records that were rejected
+    pub fn failed(&self) -> usize {
+        self.rejected().count()
+    }
+
+    /// The rejected records with their input position and error
+    pub fn rejected(&self) -> impl Iterator<Item = (usize, &TransactionRecord, &PaymentError)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, processed)| match &processed.result {
+                Ok(()) => None,
+                Err(error) => Some((index, &processed.record, error)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ClientId, TransactionId, TransactionType};
+    use rust_decimal::Decimal;
+
+    /// Record without a line or source
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<Decimal>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount,
+            line: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_report_counts_and_positions() {
+        let report = ProcessingReport {
+            results: vec![
+                ProcessingResult {
+                    record: record(TransactionType::Dispute, 1, 1, None),
+                    result: Ok(()),
+                    attempts: 1,
+                },
+                ProcessingResult {
+                    record: record(TransactionType::Dispute, 1, 2, None),
+                    result: Err(PaymentError::account_locked(1)),
+                    attempts: 1,
+                },
+                ProcessingResult {
+                    record: record(TransactionType::Dispute, 1, 3, None),
+                    result: Ok(()),
+                    attempts: 1,
+                },
+            ],
+        };
+
+        assert_eq!(report.succeeded(), 2);
+        assert_eq!(report.failed(), 1);
+        let rejected: Vec<_> = report.rejected().collect();
+        assert_eq!(
+            rejected,
+            vec![(
+                1,
+                &record(TransactionType::Dispute, 1, 2, None),
+                &PaymentError::account_locked(1)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_empty_report() {
+        let report = ProcessingReport::default();
+        assert_eq!(report.succeeded(), 0);
+        assert_eq!(report.failed(), 0);
+    }
+}
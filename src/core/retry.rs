@@ -0,0 +1,188 @@
+//! Retries with exponential backoff
+//!
+//! Deliveries to other services, and stores backed by them, can fail for
+//! reasons that go away by themselves: a dropped connection, an overloaded
+//! server. A `RetryPolicy`
+//! runs such an operation again after a growing delay, as long as its errors
+//! are transient and attempts remain; permanent errors are returned at once.
+//!
+//! The async strategy retries transactions failing with transient errors
+//! (`--retry-attempts`), and remote dead-letter sinks retry deliveries.
+
+use std::time::Duration;
+
+/// How often and how patiently to retry an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first (at least 1)
+    pub max_attempts: u32,
+    /// Delay before the second attempt, doubled before each further attempt
+    pub initial_backoff: Duration,
+    /// Longest delay between two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Make up to `max_attempts` attempts, starting with `initial_backoff`
+    /// between them
+    pub fn new(max_attempts: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+            ..Self::default()
+        }
+    }
+
+    /// Make a single attempt
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+
+    /// Delay after the failed attempt number `attempt` (counting from 1)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run `operation` until it succeeds, fails with an error that is not
+    /// transient, or runs out of attempts
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The operation, run once per attempt
+    /// * `is_transient` - Whether an error may go away when retried
+    ///
+    /// # Returns
+    ///
+    /// The result of the last attempt, and the number of attempts made
+    pub fn run<T, E>(
+        &self,
+        mut operation: impl FnMut() -> Result<T, E>,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> (Result<T, E>, u32) {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    std::thread::sleep(self.backoff(attempt));
+                    attempt += 1;
+                }
+                result => return (result, attempt),
+            }
+        }
+    }
+
+    /// Like `run`, but waits between attempts without blocking the thread,
+    /// for operations run on an async runtime's workers
+    #[cfg(feature = "native")]
+    pub async fn run_async<T, E>(
+        &self,
+        mut operation: impl FnMut() -> Result<T, E>,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> (Result<T, E>, u32) {
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return (result, attempt),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1, 100)]
+    #[case(2, 200)]
+    #[case(4, 800)]
+    #[case(10, 1000)]
+    #[case(u32::MAX, 1000)]
+    fn test_backoff_doubles_up_to_max(#[case] attempt: u32, #[case] expected_ms: u64) {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        assert_eq!(policy.backoff(attempt), Duration::from_millis(expected_ms));
+    }
+
+    #[test]
+    fn test_run_retries_transient_errors() {
+        let policy = RetryPolicy::new(3, Duration::ZERO);
+        let mut calls = 0;
+
+        let (result, attempts) = policy.run(
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err("unavailable")
+                } else {
+                    Ok(calls)
+                }
+            },
+            |_| true,
+        );
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_run_gives_up_after_max_attempts() {
+        let (result, attempts) =
+            RetryPolicy::new(2, Duration::ZERO).run(|| Err::<(), _>("unavailable"), |_| true);
+
+        assert_eq!(result, Err("unavailable"));
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn test_run_does_not_retry_permanent_errors() {
+        let mut calls = 0;
+        let (result, attempts) = RetryPolicy::new(5, Duration::ZERO).run(
+            || {
+                calls += 1;
+                Err::<(), _>("bad request")
+            },
+            |e| *e != "bad request",
+        );
+
+        assert_eq!(result, Err("bad request"));
+        assert_eq!((attempts, calls), (1, 1));
+        assert_eq!(RetryPolicy::none().max_attempts, 1);
+    }
+
+    #[cfg(feature = "native")]
+    #[tokio::test(start_paused = true)]
+    async fn test_run_async_waits_between_attempts() {
+        let policy = RetryPolicy::new(3, Duration::from_secs(1));
+        let start = tokio::time::Instant::now();
+
+        let (result, attempts) = policy
+            .run_async(|| Err::<(), _>("unavailable"), |_| true)
+            .await;
+
+        assert_eq!(result, Err("unavailable"));
+        assert_eq!(attempts, 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,728 @@
+//! SQLite-backed ledger for accounts and stored transactions
+//!
+//! This module provides the `SqliteLedger`, which keeps account balances and
+//! disputable transactions in a SQLite database instead of in memory. The
+//! database outlives the run, so it can be queried afterwards and later input
+//! files can be loaded incrementally into the same ledger (e.g. one file per day),
+//! with disputes referencing transactions from earlier loads.
+//!
+//! # Schema
+//!
+//! ```sql
+//! CREATE TABLE accounts (
+//!     client    INTEGER PRIMARY KEY,
+//!     available TEXT    NOT NULL,   -- exact decimal, e.g. '1.5000'
+//!     held      TEXT    NOT NULL,
+//!     total     TEXT    NOT NULL,
+//!     locked    INTEGER NOT NULL,   -- 0 or 1
+//!     lock_reason TEXT              -- e.g. 'chargeback:12', NULL if unknown
+//! );
+//!
+//! CREATE TABLE transactions (
+//!     tx            INTEGER PRIMARY KEY,
+//!     client        INTEGER NOT NULL,
+//!     amount        TEXT    NOT NULL,
+//!     tx_type       TEXT    NOT NULL,   -- 'deposit' or 'withdrawal'
+//!     dispute_state TEXT    NOT NULL,   -- 'none', 'disputed', 'resolved', 'chargedback', 'pending' or 'rejected'
+//!     disputes      INTEGER NOT NULL    -- number of times the transaction was disputed
+//! );
+//! ```
+//!
+//! Older ledgers are migrated when opened: an `under_dispute` flag becomes
+//! `dispute_state`, a missing `disputes` count is set to 1 for transactions
+//! that have been disputed (earlier re-disputes were not counted), and a
+//! missing `lock_reason` is left NULL.
+//!
+//! Amounts are stored as text so no precision is lost; use `CAST(... AS REAL)`
+//! for approximate numeric queries.
+//!
+//! # Atomicity
+//!
+//! A load runs inside a single SQLite transaction: if it is not committed
+//! (e.g. because of a fatal error), none of its changes become visible. Within
+//! a load, every record is applied inside its own savepoint, so the account and
+//! transaction rows it touches are always updated together.
+//!
+//! # Business Rules
+//!
+//! Records are applied by the regular `TransactionEngine`, seeded with the
+//! account and stored transaction the record refers to, so the ledger enforces
+//! exactly the same rules as in-memory processing.
+
+use crate::core::{EngineConfig, TransactionEngine};
+use crate::types::{
+    Account, ClientActivity, ClientId, DisputeState, PaymentError, StoredTransaction,
+    TransactionId, TransactionRecord, TransactionType,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::Decimal;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Statements creating the ledger schema if it does not exist yet
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        client    INTEGER PRIMARY KEY,
+        available TEXT    NOT NULL,
+        held      TEXT    NOT NULL,
+        total     TEXT    NOT NULL,
+        locked    INTEGER NOT NULL,
+        lock_reason TEXT
+    );
+    CREATE TABLE IF NOT EXISTS transactions (
+        tx            INTEGER PRIMARY KEY,
+        client        INTEGER NOT NULL,
+        amount        TEXT    NOT NULL,
+        tx_type       TEXT    NOT NULL,
+        dispute_state TEXT    NOT NULL,
+        disputes      INTEGER NOT NULL
+    );
+";
+
+/// Statements migrating an `under_dispute` flag column to `dispute_state`
+const MIGRATE_UNDER_DISPUTE: &str = "
+    ALTER TABLE transactions RENAME COLUMN under_dispute TO dispute_state;
+    UPDATE transactions
+        SET dispute_state = CASE dispute_state WHEN 1 THEN 'disputed' ELSE 'none' END;
+";
+
+/// Statements adding the `disputes` count column
+const MIGRATE_DISPUTE_COUNT: &str = "
+    ALTER TABLE transactions ADD COLUMN disputes INTEGER NOT NULL DEFAULT 0;
+    UPDATE transactions SET disputes = 1 WHERE dispute_state != 'none';
+";
+
+/// Statements adding the `lock_reason` column
+const MIGRATE_LOCK_REASON: &str = "
+    ALTER TABLE accounts ADD COLUMN lock_reason TEXT;
+";
+
+/// SQLite-backed ledger
+///
+/// Owns the database connection. Records are applied through a `LedgerLoad`
+/// obtained from `begin_load`.
+#[derive(Debug)]
+pub struct SqliteLedger {
+    conn: Connection,
+    config: EngineConfig,
+}
+
+impl SqliteLedger {
+    /// Open (or create) a ledger database file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the SQLite database file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SqliteLedger)` if the database was opened and its schema is in place
+    /// * `Err(String)` if the database could not be opened or initialized
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open ledger '{}': {}", path.display(), e))?;
+        Self::init(conn)
+    }
+
+    /// Open a ledger in an in-memory database
+    ///
+    /// Useful for tests; the ledger is discarded when dropped.
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open in-memory ledger: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to initialize ledger schema: {}", e))?;
+
+        if has_column(&conn, "transactions", "under_dispute")? {
+            migrate(&conn, MIGRATE_UNDER_DISPUTE)?;
+        }
+        if !has_column(&conn, "transactions", "disputes")? {
+            migrate(&conn, MIGRATE_DISPUTE_COUNT)?;
+        }
+        if !has_column(&conn, "accounts", "lock_reason")? {
+            migrate(&conn, MIGRATE_LOCK_REASON)?;
+        }
+
+        Ok(Self {
+            conn,
+            config: EngineConfig::default(),
+        })
+    }
+
+    /// Set the engine configuration used to apply records
+    pub fn with_config(mut self, config: EngineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Begin loading records into the ledger
+    ///
+    /// # Returns
+    ///
+    /// A `LedgerLoad` whose changes become visible only once it is committed
+    pub fn begin_load(&mut self) -> Result<LedgerLoad<'_>, String> {
+        let tx = self
+            .conn
+            .transaction()
+            .map_err(|e| format!("Failed to begin ledger transaction: {}", e))?;
+        Ok(LedgerLoad {
+            tx,
+            config: &self.config,
+        })
+    }
+
+    /// Get all accounts in the ledger, sorted by client ID
+    pub fn accounts(&self) -> Result<Vec<Account>, String> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT client, available, held, total, locked, lock_reason FROM accounts \
+                 ORDER BY client",
+            )
+            .map_err(ledger_error)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, ClientId>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, bool>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })
+            .map_err(ledger_error)?;
+
+        rows.map(|row| {
+            let (client, available, held, total, locked, lock_reason) =
+                row.map_err(ledger_error)?;
+            account_from_row(
+                client,
+                &available,
+                &held,
+                &total,
+                locked,
+                lock_reason.as_deref(),
+            )
+        })
+        .collect()
+    }
+}
+
+/// An in-progress load into a `SqliteLedger`
+///
+/// Dropping a load without calling `commit` rolls back every record it applied.
+#[derive(Debug)]
+pub struct LedgerLoad<'a> {
+    tx: rusqlite::Transaction<'a>,
+    config: &'a EngineConfig,
+}
+
+impl LedgerLoad<'_> {
+    /// Apply a single transaction record to the ledger
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The transaction record to apply
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Ok(()))` if the record was applied
+    /// * `Ok(Err(PaymentError))` if the record was rejected by the business rules
+    /// * `Err(String)` if the database failed; the load should be abandoned
+    pub fn process(
+        &mut self,
+        record: TransactionRecord,
+    ) -> Result<Result<(), PaymentError>, String> {
+        let savepoint = self.tx.savepoint().map_err(ledger_error)?;
+
+        let account = load_account(&savepoint, record.client)?;
+        let stored = load_transaction(&savepoint, record.tx)?;
+
+        let mut engine = TransactionEngine::with_state(
+            self.config.clone(),
+            account,
+            stored.map(|tx| (record.tx, tx)),
+        );
+        let (client, tx_id) = (record.client, record.tx);
+        let result = engine.process(record);
+
+        if let Some(account) = engine.account(client) {
+            save_account(&savepoint, account)?;
+        }
+        if let Some(stored) = engine.transaction(tx_id) {
+            save_transaction(&savepoint, tx_id, &stored)?;
+        }
+
+        savepoint.commit().map_err(ledger_error)?;
+        Ok(result)
+    }
+
+    /// Commit the load, making all applied records visible
+    pub fn commit(self) -> Result<(), String> {
+        self.tx
+            .commit()
+            .map_err(|e| format!("Failed to commit ledger transaction: {}", e))
+    }
+}
+
+/// Check whether the transactions table has the given column
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to inspect ledger schema: {}", e))
+}
+
+/// Apply a schema migration atomically
+fn migrate(conn: &Connection, statements: &str) -> Result<(), String> {
+    conn.execute_batch(&format!("BEGIN; {} COMMIT;", statements))
+        .map_err(|e| format!("Failed to migrate ledger schema: {}", e))
+}
+
+fn ledger_error(e: rusqlite::Error) -> String {
+    format!("Ledger error: {}", e)
+}
+
+fn parse_amount(value: &str) -> Result<Decimal, String> {
+    Decimal::from_str(value)
+        .map_err(|e| format!("Ledger contains invalid amount '{}': {}", value, e))
+}
+
+fn account_from_row(
+    client: ClientId,
+    available: &str,
+    held: &str,
+    total: &str,
+    locked: bool,
+    lock_reason: Option<&str>,
+) -> Result<Account, String> {
+    Ok(Account {
+        client,
+        available: parse_amount(available)?,
+        held: parse_amount(held)?,
+        total: parse_amount(total)?,
+        locked,
+        lock_reason: lock_reason
+            .map(|reason| {
+                reason.parse().map_err(|e| {
+                    format!(
+                        "Ledger contains invalid lock reason for client {}: {}",
+                        client, e
+                    )
+                })
+            })
+            .transpose()?,
+        activity: ClientActivity::default(),
+        metadata: None,
+    })
+}
+
+fn load_account(conn: &Connection, client: ClientId) -> Result<Option<Account>, String> {
+    let row = conn
+        .query_row(
+            "SELECT available, held, total, locked, lock_reason FROM accounts WHERE client = ?1",
+            [client],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, bool>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(ledger_error)?;
+
+    row.map(|(available, held, total, locked, lock_reason)| {
+        account_from_row(
+            client,
+            &available,
+            &held,
+            &total,
+            locked,
+            lock_reason.as_deref(),
+        )
+    })
+    .transpose()
+}
+
+fn load_transaction(
+    conn: &Connection,
+    tx_id: TransactionId,
+) -> Result<Option<StoredTransaction>, String> {
+    let row = conn
+        .query_row(
+            "SELECT client, amount, tx_type, dispute_state, disputes FROM transactions WHERE tx = ?1",
+            [tx_id],
+            |row| {
+                Ok((
+                    row.get::<_, ClientId>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, u32>(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(ledger_error)?;
+
+    row.map(|(client, amount, tx_type, dispute_state, disputes)| {
+        let tx_type = match tx_type.as_str() {
+            "deposit" => TransactionType::Deposit,
+            "withdrawal" => TransactionType::Withdrawal,
+            other => {
+                return Err(format!(
+                    "Ledger contains invalid transaction type '{}' for tx {}",
+                    other, tx_id
+                ))
+            }
+        };
+        let dispute_state = match dispute_state.as_str() {
+            "none" => DisputeState::None,
+            "disputed" => DisputeState::Disputed,
+            "resolved" => DisputeState::Resolved,
+            "chargedback" => DisputeState::ChargedBack,
+            "pending" => DisputeState::Pending,
+            "rejected" => DisputeState::Rejected,
+            other => {
+                return Err(format!(
+                    "Ledger contains invalid dispute state '{}' for tx {}",
+                    other, tx_id
+                ))
+            }
+        };
+        Ok(StoredTransaction {
+            client,
+            amount: parse_amount(&amount)?,
+            tx_type,
+            dispute_state,
+            disputes,
+        })
+    })
+    .transpose()
+}
+
+fn save_account(conn: &Connection, account: &Account) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO accounts (client, available, held, total, locked, lock_reason)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(client) DO UPDATE SET
+             available = excluded.available,
+             held = excluded.held,
+             total = excluded.total,
+             locked = excluded.locked,
+             lock_reason = excluded.lock_reason",
+        params![
+            account.client,
+            account.available.to_string(),
+            account.held.to_string(),
+            account.total.to_string(),
+            account.locked,
+            account.lock_reason.as_ref().map(ToString::to_string),
+        ],
+    )
+    .map_err(ledger_error)?;
+    Ok(())
+}
+
+fn save_transaction(
+    conn: &Connection,
+    tx_id: TransactionId,
+    stored: &StoredTransaction,
+) -> Result<(), String> {
+    let tx_type = match stored.tx_type {
+        TransactionType::Deposit => "deposit",
+        TransactionType::Withdrawal => "withdrawal",
+        other => {
+            return Err(format!(
+                "Cannot store {:?} transaction {} in the ledger",
+                other, tx_id
+            ))
+        }
+    };
+
+    let dispute_state = match stored.dispute_state {
+        DisputeState::None => "none",
+        DisputeState::Disputed => "disputed",
+        DisputeState::Resolved => "resolved",
+        DisputeState::ChargedBack => "chargedback",
+        DisputeState::Pending => "pending",
+        DisputeState::Rejected => "rejected",
+    };
+
+    conn.execute(
+        "INSERT INTO transactions (tx, client, amount, tx_type, dispute_state, disputes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(tx) DO UPDATE SET
+             dispute_state = excluded.dispute_state,
+             disputes = excluded.disputes",
+        params![
+            tx_id,
+            stored.client,
+            stored.amount.to_string(),
+            tx_type,
+            dispute_state,
+            stored.disputes,
+        ],
+    )
+    .map_err(ledger_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LockReason;
+    use tempfile::NamedTempFile;
+
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<i64>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount: amount.map(|amount| Decimal::new(amount, 4)),
+            line: None,
+            source: None,
+        }
+    }
+
+    fn load(ledger: &mut SqliteLedger, records: Vec<TransactionRecord>) -> Vec<bool> {
+        let mut load = ledger.begin_load().unwrap();
+        let results = records
+            .into_iter()
+            .map(|record| load.process(record).unwrap().is_ok())
+            .collect();
+        load.commit().unwrap();
+        results
+    }
+
+    #[test]
+    fn test_deposit_and_withdrawal() {
+        let mut ledger = SqliteLedger::open_in_memory().unwrap();
+
+        let results = load(
+            &mut ledger,
+            vec![
+                record(TransactionType::Deposit, 1, 1, Some(100_000)),
+                record(TransactionType::Withdrawal, 1, 2, Some(25_000)),
+                record(TransactionType::Withdrawal, 1, 3, Some(500_000)),
+            ],
+        );
+
+        assert_eq!(results, vec![true, true, false]);
+        let accounts = ledger.accounts().unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].available, Decimal::new(75_000, 4));
+        assert_eq!(accounts[0].total, Decimal::new(75_000, 4));
+    }
+
+    #[test]
+    fn test_incremental_loads_share_state() {
+        let file = NamedTempFile::new().unwrap();
+
+        // Day 1: deposit
+        {
+            let mut ledger = SqliteLedger::open(file.path()).unwrap();
+            load(
+                &mut ledger,
+                vec![record(TransactionType::Deposit, 1, 1, Some(100_000))],
+            );
+        }
+
+        // Day 2: dispute and chargeback a transaction from day 1, duplicate tx rejected
+        let mut ledger = SqliteLedger::open(file.path()).unwrap();
+        let results = load(
+            &mut ledger,
+            vec![
+                record(TransactionType::Deposit, 1, 1, Some(50_000)),
+                record(TransactionType::Dispute, 1, 1, None),
+                record(TransactionType::Chargeback, 1, 1, None),
+                record(TransactionType::Deposit, 1, 2, Some(50_000)),
+            ],
+        );
+
+        assert_eq!(results, vec![false, true, true, false]);
+        let account = &ledger.accounts().unwrap()[0];
+        assert_eq!(account.available, Decimal::ZERO);
+        assert_eq!(account.held, Decimal::ZERO);
+        assert_eq!(account.total, Decimal::ZERO);
+        assert!(account.locked);
+        assert_eq!(account.lock_reason, Some(LockReason::ChargebackTx(1)));
+    }
+
+    #[test]
+    fn test_uncommitted_load_is_rolled_back() {
+        let mut ledger = SqliteLedger::open_in_memory().unwrap();
+
+        {
+            let mut load = ledger.begin_load().unwrap();
+            load.process(record(TransactionType::Deposit, 1, 1, Some(100_000)))
+                .unwrap()
+                .unwrap();
+            // Dropped without commit
+        }
+
+        assert!(ledger.accounts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dispute_state_is_persisted() {
+        let mut ledger = SqliteLedger::open_in_memory().unwrap();
+        load(
+            &mut ledger,
+            vec![
+                record(TransactionType::Deposit, 1, 1, Some(100_000)),
+                record(TransactionType::Dispute, 1, 1, None),
+            ],
+        );
+
+        // A second dispute of the same transaction is rejected in a later load
+        let results = load(
+            &mut ledger,
+            vec![
+                record(TransactionType::Dispute, 1, 1, None),
+                record(TransactionType::Resolve, 1, 1, None),
+            ],
+        );
+
+        assert_eq!(results, vec![false, true]);
+        let account = &ledger.accounts().unwrap()[0];
+        assert_eq!(account.available, Decimal::new(100_000, 4));
+        assert_eq!(account.held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_legacy_under_dispute_column_is_migrated() {
+        let file = NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE transactions (
+                     tx            INTEGER PRIMARY KEY,
+                     client        INTEGER NOT NULL,
+                     amount        TEXT    NOT NULL,
+                     tx_type       TEXT    NOT NULL,
+                     under_dispute INTEGER NOT NULL
+                 );
+                 INSERT INTO transactions VALUES (1, 1, '1', 'deposit', 1);
+                 INSERT INTO transactions VALUES (2, 1, '1', 'deposit', 0);",
+            )
+            .unwrap();
+        }
+
+        let ledger = SqliteLedger::open(file.path()).unwrap();
+
+        let state = |tx: TransactionId| {
+            load_transaction(&ledger.conn, tx)
+                .unwrap()
+                .unwrap()
+                .dispute_state
+        };
+        assert_eq!(state(1), DisputeState::Disputed);
+        assert_eq!(state(2), DisputeState::None);
+        let disputes: Vec<u32> = [1, 2]
+            .map(|tx| {
+                load_transaction(&ledger.conn, tx)
+                    .unwrap()
+                    .unwrap()
+                    .disputes
+            })
+            .to_vec();
+        assert_eq!(disputes, vec![1, 0]);
+
+        // Reopening an already migrated ledger leaves it alone
+        drop(ledger);
+        assert!(SqliteLedger::open(file.path()).is_ok());
+    }
+
+    #[test]
+    fn test_accounts_without_lock_reason_are_migrated() {
+        let file = NamedTempFile::new().unwrap();
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE accounts (
+                     client    INTEGER PRIMARY KEY,
+                     available TEXT    NOT NULL,
+                     held      TEXT    NOT NULL,
+                     total     TEXT    NOT NULL,
+                     locked    INTEGER NOT NULL
+                 );
+                 INSERT INTO accounts VALUES (1, '0', '0', '0', 1);",
+            )
+            .unwrap();
+        }
+
+        let ledger = SqliteLedger::open(file.path()).unwrap();
+
+        let account = &ledger.accounts().unwrap()[0];
+        assert!(account.locked);
+        assert_eq!(account.lock_reason, None);
+    }
+
+    #[test]
+    fn test_redispute_policy_applies_across_loads() {
+        use crate::core::RedisputePolicy;
+
+        let mut ledger = SqliteLedger::open_in_memory()
+            .unwrap()
+            .with_config(EngineConfig::new().with_redispute_policy(RedisputePolicy::Limit(1)));
+        load(
+            &mut ledger,
+            vec![
+                record(TransactionType::Deposit, 1, 1, Some(100_000)),
+                record(TransactionType::Dispute, 1, 1, None),
+                record(TransactionType::Resolve, 1, 1, None),
+            ],
+        );
+
+        // One re-dispute is allowed, a second one is not
+        let results = load(
+            &mut ledger,
+            vec![
+                record(TransactionType::Dispute, 1, 1, None),
+                record(TransactionType::Resolve, 1, 1, None),
+                record(TransactionType::Dispute, 1, 1, None),
+            ],
+        );
+
+        assert_eq!(results, vec![true, true, false]);
+        assert_eq!(
+            load_transaction(&ledger.conn, 1).unwrap().unwrap().disputes,
+            2
+        );
+    }
+
+    #[test]
+    fn test_amounts_keep_full_precision() {
+        let mut ledger = SqliteLedger::open_in_memory().unwrap();
+        load(
+            &mut ledger,
+            vec![record(TransactionType::Deposit, 7, 1, Some(1))],
+        );
+
+        let amount: String = ledger
+            .conn
+            .query_row("SELECT amount FROM transactions WHERE tx = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(amount, "0.0001");
+    }
+}
@@ -0,0 +1,181 @@
+//! State files holding a complete engine snapshot
+//!
+//! A run started with `--save-state` writes the final `EngineSnapshot` to a
+//! state file, which the `query` subcommand reads back to look up balances and
+//! stored transactions without reprocessing the input.
+//!
+//! # Format
+//!
+//! The file starts with the magic bytes `PESTATE` and a format version byte,
+//! followed by the snapshot encoded with `bincode`. Amounts are encoded as
+//! decimal strings, so they round-trip exactly.
+//!
+//! The file is written to a temporary file next to the target and renamed
+//! over it, so an interrupted run never leaves a truncated state behind.
+
+use crate::core::EngineSnapshot;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes identifying a state file, followed by the format version
+const MAGIC: &[u8; 7] = b"PESTATE";
+
+/// Version of the state file format written by this build
+///
+/// Version 2 added the lock reason of accounts, version 3 their activity
+/// counters.
+const FORMAT_VERSION: u8 = 3;
+
+/// Write a snapshot to a state file, replacing any existing file
+///
+/// # Returns
+///
+/// * `Ok(())` - If the state file was written
+/// * `Err(String)` - If the file could not be created or written
+pub fn save_state(path: &Path, snapshot: &EngineSnapshot) -> Result<(), String> {
+    let write_error = |e: &dyn std::fmt::Display| {
+        format!("Failed to write state file '{}': {}", path.display(), e)
+    };
+
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+
+    let file = File::create(&temp_path).map_err(|e| write_error(&e))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC).map_err(|e| write_error(&e))?;
+    writer
+        .write_all(&[FORMAT_VERSION])
+        .map_err(|e| write_error(&e))?;
+    bincode::serialize_into(&mut writer, snapshot).map_err(|e| write_error(&e))?;
+    writer
+        .into_inner()
+        .map_err(|e| write_error(&e))?
+        .sync_all()
+        .map_err(|e| write_error(&e))?;
+
+    std::fs::rename(&temp_path, path).map_err(|e| write_error(&e))
+}
+
+/// Check whether a file is a state file, by its magic bytes
+///
+/// # Returns
+///
+/// * `Ok(bool)` - Whether the file starts like a state file
+/// * `Err(String)` - If the file cannot be opened or read
+pub fn is_state_file(path: &Path) -> Result<bool, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read file '{}': {}", path.display(), e);
+    let file = File::open(path).map_err(read_error)?;
+    let mut magic = Vec::with_capacity(MAGIC.len());
+    file.take(MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .map_err(read_error)?;
+    Ok(magic == MAGIC)
+}
+
+/// Read a snapshot from a state file
+///
+/// # Returns
+///
+/// * `Ok(EngineSnapshot)` - The saved snapshot
+/// * `Err(String)` - If the file cannot be read, is not a state file, or was
+///   written by an incompatible version
+pub fn load_state(path: &Path) -> Result<EngineSnapshot, String> {
+    let read_error = |e: &dyn std::fmt::Display| {
+        format!("Failed to read state file '{}': {}", path.display(), e)
+    };
+
+    let file = File::open(path).map_err(|e| read_error(&e))?;
+    let mut reader = BufReader::new(file);
+
+    let mut header = [0u8; MAGIC.len() + 1];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| read_error(&"not a state file"))?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(read_error(&"not a state file"));
+    }
+    let version = header[MAGIC.len()];
+    if version != FORMAT_VERSION {
+        return Err(read_error(&format!(
+            "unsupported format version {} (expected {})",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    bincode::deserialize_from(reader).map_err(|e| read_error(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Account, DisputeState, LockReason, StoredTransaction, TransactionType};
+    use rust_decimal::Decimal;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn snapshot() -> EngineSnapshot {
+        let mut account = Account::new(7);
+        account.available = Decimal::new(12345, 4);
+        account.held = Decimal::new(5, 1);
+        account.total = Decimal::new(17345, 4);
+        account.metadata = Some(Arc::new([("owner".to_string(), "acme".to_string())].into()));
+        let mut locked = Account::new(3);
+        locked.lock(LockReason::RiskRule("velocity".to_string()));
+        EngineSnapshot::new(
+            vec![account, locked],
+            vec![(
+                42,
+                StoredTransaction {
+                    client: 7,
+                    amount: Decimal::new(5, 1),
+                    tx_type: TransactionType::Deposit,
+                    dispute_state: DisputeState::Disputed,
+                    disputes: 1,
+                },
+            )],
+        )
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.bin");
+
+        save_state(&path, &snapshot()).unwrap();
+        let loaded = load_state(&path).unwrap();
+
+        assert_eq!(loaded, snapshot());
+        assert!(is_state_file(&path).unwrap());
+        assert_eq!(loaded.account(7).unwrap().available.to_string(), "1.2345");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_load_rejects_other_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+        std::fs::write(&path, "client,available,held,total,locked\n").unwrap();
+
+        let err = load_state(&path).unwrap_err();
+        assert!(err.contains("not a state file"), "{err}");
+        assert!(!is_state_file(&path).unwrap());
+    }
+
+    #[test]
+    fn test_load_rejects_other_versions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.bin");
+        std::fs::write(&path, b"PESTATE\x09").unwrap();
+
+        let err = load_state(&path).unwrap_err();
+        assert!(err.contains("unsupported format version 9"), "{err}");
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let err = load_state(Path::new("nonexistent.bin")).unwrap_err();
+        assert!(err.contains("Failed to read state file"));
+    }
+}
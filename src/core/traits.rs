@@ -3,9 +3,15 @@
 //! This module defines the trait abstractions that allow both synchronous and
 //! asynchronous implementations to be used interchangeably.
 
+use crate::core::expiry::ExpiredDispute;
+use crate::core::flows::MoneyFlows;
+use crate::core::history::BalancePoint;
+use crate::core::journal::Posting;
+use crate::core::report::{ProcessingReport, ProcessingResult};
 use crate::types::{
     Account, ClientId, PaymentError, StoredTransaction, TransactionId, TransactionRecord,
 };
+use serde::{Deserialize, Serialize};
 
 /// Trait for managing account state
 ///
@@ -44,14 +50,302 @@ pub trait TransactionStore {
         F: FnOnce(&mut StoredTransaction) -> Result<(), PaymentError>;
 }
 
+/// Snapshot of an engine's complete state
+///
+/// Contains everything needed to continue processing later: the accounts and
+/// the stored (disputable) transactions, sorted by client and transaction ID.
+/// `TransactionEngine::with_state` restores an engine from it, and
+/// `core::state` saves it to and loads it from a state file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    /// Every account, sorted by client ID
+    pub accounts: Vec<Account>,
+    /// Every stored transaction, sorted by transaction ID (and by client, for
+    /// IDs reused with per-client transaction IDs)
+    pub transactions: Vec<(TransactionId, StoredTransaction)>,
+}
+
+impl EngineSnapshot {
+    /// Create a snapshot from unordered state, sorting it
+    pub fn new(
+        mut accounts: Vec<Account>,
+        mut transactions: Vec<(TransactionId, StoredTransaction)>,
+    ) -> Self {
+        accounts.sort_by_key(|account| account.client);
+        transactions.sort_by_key(|(tx_id, tx)| (*tx_id, tx.client));
+        Self {
+            accounts,
+            transactions,
+        }
+    }
+
+    /// Look up the account of a client
+    pub fn account(&self, client: ClientId) -> Option<&Account> {
+        self.accounts
+            .binary_search_by_key(&client, |account| account.client)
+            .ok()
+            .map(|index| &self.accounts[index])
+    }
+
+    /// Look up a stored transaction
+    pub fn transaction(&self, tx_id: TransactionId) -> Option<&StoredTransaction> {
+        self.transactions
+            .binary_search_by_key(&tx_id, |(id, _)| *id)
+            .ok()
+            .map(|index| &self.transactions[index].1)
+    }
+}
+
 /// Trait for processing transactions
 ///
-/// Provides the main transaction processing interface that coordinates between
-/// account management and transaction storage.
-pub trait TransactionEngine {
+/// Implemented by both the synchronous `TransactionEngine` and the
+/// `AsyncTransactionEngine`, so orchestration code can be written once and
+/// used with either engine.
+pub trait Engine {
     /// Process a single transaction record
-    fn process(&mut self, record: TransactionRecord) -> Result<(), PaymentError>;
+    fn process_transaction(&mut self, record: TransactionRecord) -> Result<(), PaymentError>;
 
-    /// Get all accounts for output
+    /// Get all accounts for output, sorted by client ID
     fn get_accounts(&self) -> Vec<Account>;
+
+    /// Capture the engine's complete state
+    fn snapshot(&self) -> EngineSnapshot;
+
+    /// Money moved in and out by the transactions applied so far
+    fn flows(&self) -> MoneyFlows;
+
+    /// Take the audit events of the disputes expired since the last call
+    ///
+    /// Engines that don't expire disputes never report any.
+    fn take_expired_disputes(&mut self) -> Vec<ExpiredDispute> {
+        Vec::new()
+    }
+
+    /// Take the postings of the transactions applied since the last call
+    ///
+    /// Postings are only recorded with `EngineConfig::journal` enabled.
+    fn take_postings(&mut self) -> Vec<Posting> {
+        Vec::new()
+    }
+
+    /// Take the balance history samples recorded since the last call
+    ///
+    /// Samples are only recorded with `EngineConfig::balance_history` set.
+    fn take_balance_history(&mut self) -> Vec<BalancePoint> {
+        Vec::new()
+    }
+
+    /// Process records in order, keeping the outcome of each
+    ///
+    /// Rejected records don't stop processing; the report tells which input
+    /// records were rejected and why.
+    fn process_all(
+        &mut self,
+        records: impl IntoIterator<Item = TransactionRecord>,
+    ) -> ProcessingReport
+    where
+        Self: Sized,
+    {
+        let results = records
+            .into_iter()
+            .map(|record| ProcessingResult {
+                result: self.process_transaction(record.clone()),
+                record,
+                attempts: 1,
+            })
+            .collect();
+        ProcessingReport { results }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "native")]
+    use crate::core::{AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore};
+    #[cfg(feature = "native")]
+    use crate::types::ClientActivity;
+    use crate::types::{DisputeState, TransactionType};
+    use rust_decimal::Decimal;
+    #[cfg(feature = "native")]
+    use std::sync::Arc;
+
+    /// Apply records to any engine, counting the rejected ones
+    fn apply<E: Engine>(engine: &mut E, records: &[TransactionRecord]) -> usize {
+        records
+            .iter()
+            .filter(|record| engine.process_transaction((*record).clone()).is_err())
+            .count()
+    }
+
+    /// Record without a line or source
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<Decimal>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount,
+            line: None,
+            source: None,
+        }
+    }
+
+    fn records() -> Vec<TransactionRecord> {
+        vec![
+            record(TransactionType::Deposit, 2, 1, Some(Decimal::from(10))),
+            record(TransactionType::Deposit, 1, 2, Some(Decimal::from(5))),
+            record(TransactionType::Withdrawal, 1, 3, Some(Decimal::from(7))),
+            record(TransactionType::Dispute, 2, 1, None),
+        ]
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_engines_agree() {
+        let mut sync_engine = crate::core::TransactionEngine::new();
+        let mut async_engine = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+
+        assert_eq!(apply(&mut sync_engine, &records()), 1);
+        assert_eq!(apply(&mut async_engine, &records()), 1);
+
+        let sync_accounts = Engine::get_accounts(&sync_engine);
+        assert_eq!(sync_accounts, Engine::get_accounts(&async_engine));
+        assert_eq!(
+            sync_accounts.iter().map(|a| a.client).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(sync_accounts[1].held, Decimal::from(10));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_engines_track_flows() {
+        let mut records = records();
+        records.push(TransactionRecord {
+            tx_type: TransactionType::Chargeback,
+            client: 2,
+            tx: 1,
+            amount: None,
+            line: None,
+            source: None,
+        });
+        let mut sync_engine = crate::core::TransactionEngine::new();
+        let mut async_engine = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        apply(&mut sync_engine, &records);
+        apply(&mut async_engine, &records);
+
+        let expected = MoneyFlows {
+            deposited: Decimal::from(15),
+            withdrawn: Decimal::ZERO,
+            charged_back: Decimal::from(10),
+            ..MoneyFlows::default()
+        };
+        assert_eq!(sync_engine.flows(), expected);
+        assert_eq!(async_engine.flows(), expected);
+        let total: Decimal = Engine::get_accounts(&sync_engine)
+            .iter()
+            .map(|account| account.total)
+            .sum();
+        assert_eq!(total, expected.expected_total());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_engines_count_activity() {
+        let mut records = records();
+        // Rejected without creating an account for client 3, so not counted
+        records.push(TransactionRecord {
+            tx_type: TransactionType::Dispute,
+            client: 3,
+            tx: 4,
+            amount: None,
+            line: None,
+            source: None,
+        });
+        let mut sync_engine = crate::core::TransactionEngine::new();
+        let mut async_engine = AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        );
+        apply(&mut sync_engine, &records);
+        apply(&mut async_engine, &records);
+
+        let expected = vec![
+            ClientActivity {
+                transactions: 2,
+                errors: 1,
+                disputes: 0,
+                last_tx: Some(3),
+            },
+            ClientActivity {
+                transactions: 2,
+                errors: 0,
+                disputes: 1,
+                last_tx: Some(1),
+            },
+        ];
+        for accounts in [
+            Engine::get_accounts(&sync_engine),
+            Engine::get_accounts(&async_engine),
+        ] {
+            let activity: Vec<_> = accounts.iter().map(|account| account.activity).collect();
+            assert_eq!(activity, expected);
+        }
+    }
+
+    #[test]
+    fn test_process_all_reports_rejected_records() {
+        let mut engine = crate::core::TransactionEngine::new();
+        let report = engine.process_all(records());
+
+        assert_eq!(report.results.len(), 4);
+        assert_eq!(report.succeeded(), 3);
+        let rejected: Vec<_> = report.rejected().collect();
+        assert_eq!(rejected.len(), 1);
+        let (index, record, error) = rejected[0];
+        assert_eq!(index, 2);
+        assert_eq!(record, &records()[2]);
+        assert!(matches!(
+            error,
+            PaymentError::InsufficientFunds { client: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_contains_stored_transactions() {
+        let mut engine = crate::core::TransactionEngine::new();
+        apply(&mut engine, &records());
+
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.accounts.len(), 2);
+        let tx_ids: Vec<TransactionId> = snapshot.transactions.iter().map(|(id, _)| *id).collect();
+        assert_eq!(tx_ids, vec![1, 2]);
+        assert_eq!(
+            snapshot.transactions[0].1.dispute_state,
+            DisputeState::Disputed
+        );
+
+        assert_eq!(snapshot.account(2).unwrap().held, Decimal::from(10));
+        assert!(snapshot.account(3).is_none());
+        assert_eq!(snapshot.transaction(2).unwrap().client, 1);
+        assert!(snapshot.transaction(3).is_none());
+
+        let restored = crate::core::TransactionEngine::with_state(
+            Default::default(),
+            snapshot.accounts.clone(),
+            snapshot.transactions,
+        );
+        assert_eq!(Engine::get_accounts(&restored), snapshot.accounts);
+    }
 }
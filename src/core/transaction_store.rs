@@ -11,13 +11,28 @@
 //! types that can be disputed. Dispute, resolve, and chargeback operations are
 //! not stored, reducing memory usage.
 //!
+//! With the `compact-store` feature, transactions are kept in the compact
+//! encoding of the `compact` module and decoded on access, which is why
+//! lookups return transactions by value.
+//!
 //! # Duplicate Handling
 //!
 //! If a duplicate transaction ID is encountered, only the
 //! first occurrence is stored. Subsequent transactions with the same ID are ignored.
+//!
+//! With per-client transaction IDs, `store_for_client` also stores a
+//! transaction whose ID another client already used, and the `_for_client`
+//! lookups find each client's own transaction under the shared ID.
+//!
+//! # Client Index
+//!
+//! A secondary index maps each client to the IDs of its stored transactions,
+//! so `transactions_for_client` does not have to scan the whole store.
 
-use crate::types::{PaymentError, StoredTransaction, TransactionId};
-use std::collections::HashMap;
+use crate::core::compact::{self, Slot};
+use crate::core::hash::{key_map, KeyMap};
+use crate::types::{ClientId, DisputeState, PaymentError, StoredTransaction, TransactionId};
+use std::collections::hash_map::Entry;
 
 /// Transaction store for dispute resolution
 ///
@@ -25,7 +40,18 @@ use std::collections::HashMap;
 /// Supports storing, retrieving, and updating dispute status of transactions.
 pub struct TransactionStore {
     /// Map of transaction ID to stored transaction
-    transactions: HashMap<TransactionId, StoredTransaction>,
+    transactions: KeyMap<TransactionId, Slot>,
+    /// Transactions without a compact encoding, kept at full width
+    ///
+    /// Always empty unless the `compact-store` feature is enabled.
+    spilled: KeyMap<TransactionId, StoredTransaction>,
+    /// IDs of each client's stored transactions, in the order they were stored
+    by_client: KeyMap<ClientId, Vec<TransactionId>>,
+    /// Transactions whose ID another client's transaction already had, kept
+    /// at full width under their client and ID
+    ///
+    /// Always empty unless `store_for_client` is used.
+    reused: KeyMap<(ClientId, TransactionId), StoredTransaction>,
 }
 
 impl TransactionStore {
@@ -36,10 +62,31 @@ impl TransactionStore {
     /// A new TransactionStore with no stored transactions
     pub fn new() -> Self {
         TransactionStore {
-            transactions: HashMap::new(),
+            transactions: KeyMap::default(),
+            spilled: KeyMap::default(),
+            by_client: KeyMap::default(),
+            reused: KeyMap::default(),
+        }
+    }
+
+    /// Create a new empty transaction store with room for `transactions`
+    /// stored transactions of `clients` clients
+    ///
+    /// Avoids rehashing the store and its client index while they grow.
+    pub fn with_capacity(transactions: usize, clients: usize) -> Self {
+        TransactionStore {
+            transactions: key_map(transactions),
+            spilled: KeyMap::default(),
+            by_client: key_map(clients),
+            reused: KeyMap::default(),
         }
     }
 
+    /// Number of transactions the store can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.transactions.capacity()
+    }
+
     /// Store a disputable transaction (deposit or withdrawal)
     ///
     /// If a transaction with the same ID already exists, the new transaction
@@ -52,10 +99,56 @@ impl TransactionStore {
     ///
     pub fn store(&mut self, tx_id: TransactionId, tx: StoredTransaction) {
         // Only store if not already present (first occurrence wins)
-        self.transactions.entry(tx_id).or_insert(tx);
+        if self.contains(tx_id) {
+            return;
+        }
+        self.by_client.entry(tx.client).or_default().push(tx_id);
+        match compact::pack(tx) {
+            Ok(slot) => {
+                self.transactions.insert(tx_id, slot);
+            }
+            Err(tx) => {
+                self.spilled.insert(tx_id, tx);
+            }
+        }
+    }
+
+    /// Store a transaction whose ID only has to be unique for its client
+    ///
+    /// A transaction reusing the ID of another client's transaction is stored
+    /// next to it; one reusing the ID of the same client's transaction is
+    /// ignored, like in `store`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx_id` - The transaction identifier, unique for the client
+    /// * `tx` - The transaction data to store
+    pub fn store_for_client(&mut self, tx_id: TransactionId, tx: StoredTransaction) {
+        match self.get(tx_id) {
+            None => self.store(tx_id, tx),
+            Some(stored) if stored.client == tx.client => {}
+            Some(_) => {
+                let client = tx.client;
+                if let Entry::Vacant(entry) = self.reused.entry((client, tx_id)) {
+                    entry.insert(tx);
+                    self.by_client.entry(client).or_default().push(tx_id);
+                }
+            }
+        }
+    }
+
+    /// Returns true if a transaction with this ID is stored
+    pub fn contains(&self, tx_id: TransactionId) -> bool {
+        self.transactions.contains_key(&tx_id) || self.spilled.contains_key(&tx_id)
+    }
+
+    /// Returns true if the client has a stored transaction with this ID
+    pub fn contains_for_client(&self, client: ClientId, tx_id: TransactionId) -> bool {
+        self.reused.contains_key(&(client, tx_id))
+            || self.get(tx_id).is_some_and(|tx| tx.client == client)
     }
 
-    /// Get an immutable reference to a stored transaction
+    /// Get a copy of a stored transaction
     ///
     /// # Arguments
     ///
@@ -63,34 +156,134 @@ impl TransactionStore {
     ///
     /// # Returns
     ///
-    /// * `Some(&StoredTransaction)` - If the transaction exists
+    /// * `Some(StoredTransaction)` - If the transaction exists
     /// * `None` - If the transaction ID is not found
-    pub fn get(&self, tx_id: TransactionId) -> Option<&StoredTransaction> {
-        self.transactions.get(&tx_id)
+    pub fn get(&self, tx_id: TransactionId) -> Option<StoredTransaction> {
+        match self.transactions.get(&tx_id) {
+            Some(slot) => Some(compact::unpack(slot)),
+            None => self.spilled.get(&tx_id).cloned(),
+        }
     }
 
-    /// Get a mutable reference to a stored transaction
+    /// Get a copy of a client's stored transaction
     ///
-    /// Used for updating dispute status of transactions.
+    /// Finds the client's own transaction if the ID was reused by several
+    /// clients, and otherwise the transaction stored under the ID, which may
+    /// belong to another client.
     ///
     /// # Arguments
     ///
+    /// * `client` - The client whose transaction to look up
     /// * `tx_id` - The transaction identifier to lookup
     ///
     /// # Returns
     ///
-    /// * `Some(&mut StoredTransaction)` - If the transaction exists
+    /// * `Some(StoredTransaction)` - If a transaction with the ID exists
     /// * `None` - If the transaction ID is not found
-    pub fn get_mut(&mut self, tx_id: TransactionId) -> Option<&mut StoredTransaction> {
-        self.transactions.get_mut(&tx_id)
+    pub fn get_for_client(
+        &self,
+        client: ClientId,
+        tx_id: TransactionId,
+    ) -> Option<StoredTransaction> {
+        match self.reused.get(&(client, tx_id)) {
+            Some(tx) => Some(tx.clone()),
+            None => self.get(tx_id),
+        }
+    }
+
+    /// Iterate over the stored transactions of one client
+    ///
+    /// Uses the client index, so the cost is proportional to the client's own
+    /// transactions rather than to the size of the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client whose transactions to return
+    ///
+    /// # Returns
+    ///
+    /// The client's stored (disputable) transactions in the order they were
+    /// stored; empty if the client has none
+    pub fn transactions_for_client(
+        &self,
+        client: ClientId,
+    ) -> impl Iterator<Item = (TransactionId, StoredTransaction)> + '_ {
+        self.by_client
+            .get(&client)
+            .into_iter()
+            .flatten()
+            .filter_map(move |tx_id| Some((*tx_id, self.get_for_client(client, *tx_id)?)))
+    }
+
+    /// Iterate over all stored transactions, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (TransactionId, StoredTransaction)> + '_ {
+        self.transactions
+            .iter()
+            .map(|(tx_id, slot)| (*tx_id, compact::unpack(slot)))
+            .chain(self.spilled.iter().map(|(tx_id, tx)| (*tx_id, tx.clone())))
+            .chain(
+                self.reused
+                    .iter()
+                    .map(|((_, tx_id), tx)| (*tx_id, tx.clone())),
+            )
+    }
+
+    /// Update a stored transaction with a closure
+    ///
+    /// Used for updating dispute status of transactions. The transaction is
+    /// decoded, updated and encoded again.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client whose transaction to update, if the ID was
+    ///   reused by several clients
+    /// * `tx_id` - The transaction identifier to update
+    /// * `operation` - The operation named in the error if it is not found
+    /// * `f` - A closure that receives a mutable reference to the transaction
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the transaction exists
+    /// * `Err(PaymentError)` - If the transaction ID is not found
+    fn update(
+        &mut self,
+        client: ClientId,
+        tx_id: TransactionId,
+        operation: &str,
+        f: impl FnOnce(&mut StoredTransaction),
+    ) -> Result<(), PaymentError> {
+        if let Some(tx) = self.reused.get_mut(&(client, tx_id)) {
+            f(tx);
+            return Ok(());
+        }
+        if let Some(tx) = self.spilled.get_mut(&tx_id) {
+            f(tx);
+            return Ok(());
+        }
+        let slot = self
+            .transactions
+            .get_mut(&tx_id)
+            .ok_or_else(|| PaymentError::transaction_not_found(tx_id, operation))?;
+        let mut tx = compact::unpack(slot);
+        f(&mut tx);
+        match compact::pack(tx) {
+            Ok(packed) => *slot = packed,
+            Err(tx) => {
+                self.transactions.remove(&tx_id);
+                self.spilled.insert(tx_id, tx);
+            }
+        }
+        Ok(())
     }
 
     /// Mark a transaction as under dispute
     ///
-    /// Sets the `under_dispute` flag to true for the specified transaction.
+    /// Sets the dispute state of the specified transaction to `Disputed` and
+    /// counts the dispute.
     ///
     /// # Arguments
     ///
+    /// * `client` - The client whose transaction it is
     /// * `tx_id` - The transaction identifier to mark as disputed
     ///
     /// # Returns
@@ -98,32 +291,88 @@ impl TransactionStore {
     /// * `Ok(())` - If the transaction was successfully marked as disputed
     /// * `Err(PaymentError)` - If the transaction ID is not found
     /// ```
-    pub fn mark_disputed(&mut self, tx_id: TransactionId) -> Result<(), PaymentError> {
-        let tx = self
-            .get_mut(tx_id)
-            .ok_or_else(|| PaymentError::transaction_not_found(tx_id, "mark_disputed"))?;
-        tx.under_dispute = true;
-        Ok(())
+    pub fn mark_disputed(
+        &mut self,
+        client: ClientId,
+        tx_id: TransactionId,
+    ) -> Result<(), PaymentError> {
+        self.update(client, tx_id, "mark_disputed", |tx| {
+            tx.dispute_state = DisputeState::Disputed;
+            tx.disputes = tx.disputes.saturating_add(1);
+        })
     }
 
     /// Mark a transaction as resolved (no longer disputed)
     ///
-    /// Sets the `under_dispute` flag to false for the specified transaction.
+    /// Sets the dispute state of the specified transaction to `Resolved`.
     ///
     /// # Arguments
     ///
+    /// * `client` - The client whose transaction it is
     /// * `tx_id` - The transaction identifier to mark as resolved
     ///
     /// # Returns
     ///
     /// * `Ok(())` - If the transaction was successfully marked as resolved
     /// * `Err(PaymentError)` - If the transaction ID is not found
-    pub fn mark_resolved(&mut self, tx_id: TransactionId) -> Result<(), PaymentError> {
-        let tx = self
-            .get_mut(tx_id)
-            .ok_or_else(|| PaymentError::transaction_not_found(tx_id, "mark_resolved"))?;
-        tx.under_dispute = false;
-        Ok(())
+    pub fn mark_resolved(
+        &mut self,
+        client: ClientId,
+        tx_id: TransactionId,
+    ) -> Result<(), PaymentError> {
+        self.update(client, tx_id, "mark_resolved", |tx| {
+            tx.dispute_state = DisputeState::Resolved;
+        })
+    }
+
+    /// Mark a transaction as charged back
+    ///
+    /// Sets the dispute state of the specified transaction to `ChargedBack`,
+    /// after which it can no longer be disputed.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client whose transaction it is
+    /// * `tx_id` - The transaction identifier to mark as charged back
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the transaction was successfully marked as charged back
+    /// * `Err(PaymentError)` - If the transaction ID is not found
+    pub fn mark_charged_back(
+        &mut self,
+        client: ClientId,
+        tx_id: TransactionId,
+    ) -> Result<(), PaymentError> {
+        self.update(client, tx_id, "mark_charged_back", |tx| {
+            tx.dispute_state = DisputeState::ChargedBack;
+        })
+    }
+
+    /// Set the dispute state of a transaction
+    ///
+    /// Used for the states without a dedicated `mark_*` method, such as
+    /// completing a withdrawal pending approval.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client whose transaction it is
+    /// * `tx_id` - The transaction identifier to update
+    /// * `state` - The new dispute state
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the transaction's state was set
+    /// * `Err(PaymentError)` - If the transaction ID is not found
+    pub fn set_dispute_state(
+        &mut self,
+        client: ClientId,
+        tx_id: TransactionId,
+        state: DisputeState,
+    ) -> Result<(), PaymentError> {
+        self.update(client, tx_id, "set_dispute_state", |tx| {
+            tx.dispute_state = state;
+        })
     }
 }
 
@@ -147,7 +396,8 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         store.store(1, tx.clone());
@@ -158,7 +408,7 @@ mod tests {
         assert_eq!(retrieved.client, 1);
         assert_eq!(retrieved.amount, Decimal::new(10000, 4));
         assert_eq!(retrieved.tx_type, TransactionType::Deposit);
-        assert!(!retrieved.under_dispute);
+        assert!(!retrieved.dispute_state.is_disputed());
     }
 
     #[test]
@@ -169,14 +419,16 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         let tx2 = StoredTransaction {
             client: 2,
             amount: Decimal::new(20000, 4),
             tx_type: TransactionType::Withdrawal,
-            under_dispute: true,
+            dispute_state: DisputeState::Disputed,
+            disputes: 1,
         };
 
         // Store first transaction
@@ -190,7 +442,134 @@ mod tests {
         assert_eq!(retrieved.client, 1);
         assert_eq!(retrieved.amount, Decimal::new(10000, 4));
         assert_eq!(retrieved.tx_type, TransactionType::Deposit);
-        assert!(!retrieved.under_dispute);
+        assert!(!retrieved.dispute_state.is_disputed());
+    }
+
+    #[test]
+    fn test_transactions_for_client() {
+        let mut store = TransactionStore::new();
+        let tx = |client, amount| StoredTransaction {
+            client,
+            amount: Decimal::from(amount),
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+
+        store.store(5, tx(1, 10));
+        store.store(2, tx(2, 20));
+        store.store(9, tx(1, 30));
+        // A duplicate ID is not indexed a second time, even for another client
+        store.store(5, tx(3, 40));
+
+        let client_1: Vec<(TransactionId, Decimal)> = store
+            .transactions_for_client(1)
+            .map(|(tx_id, tx)| (tx_id, tx.amount))
+            .collect();
+        assert_eq!(
+            client_1,
+            vec![(5, Decimal::from(10)), (9, Decimal::from(30))]
+        );
+        assert_eq!(store.transactions_for_client(2).count(), 1);
+        assert_eq!(store.transactions_for_client(3).count(), 0);
+    }
+
+    #[test]
+    fn test_store_for_client_scopes_ids_to_the_client() {
+        let mut store = TransactionStore::new();
+        let tx = |client, amount| StoredTransaction {
+            client,
+            amount: Decimal::from(amount),
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+
+        store.store_for_client(5, tx(1, 10));
+        store.store_for_client(5, tx(2, 20));
+        // The same client reusing its own ID is still ignored
+        store.store_for_client(5, tx(2, 30));
+
+        assert!(store.contains_for_client(1, 5));
+        assert!(store.contains_for_client(2, 5));
+        assert!(!store.contains_for_client(3, 5));
+        assert_eq!(
+            store.get_for_client(2, 5).unwrap().amount,
+            Decimal::from(20)
+        );
+        // Other clients find the first transaction stored under the ID
+        assert_eq!(store.get_for_client(3, 5).unwrap().client, 1);
+
+        store.mark_disputed(2, 5).unwrap();
+        assert!(store
+            .get_for_client(2, 5)
+            .unwrap()
+            .dispute_state
+            .is_disputed());
+        assert!(!store
+            .get_for_client(1, 5)
+            .unwrap()
+            .dispute_state
+            .is_disputed());
+
+        let client_2: Vec<_> = store.transactions_for_client(2).collect();
+        assert_eq!(client_2.len(), 1);
+        assert_eq!(client_2[0].1.amount, Decimal::from(20));
+        assert_eq!(store.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_wide_amount_round_trips() {
+        let mut store = TransactionStore::new();
+        // Too wide for the compact encoding, so it is spilled with the feature
+        let wide = Decimal::MAX;
+        let tx = |amount| StoredTransaction {
+            client: 1,
+            amount,
+            tx_type: TransactionType::Withdrawal,
+            dispute_state: DisputeState::None,
+            disputes: 0,
+        };
+        store.store(1, tx(wide));
+        store.store(1, tx(Decimal::ONE));
+        store.store(2, tx(Decimal::new(15, 1)));
+        store.mark_disputed(1, 1).unwrap();
+        store.mark_disputed(1, 2).unwrap();
+
+        let stored = store.get(1).unwrap();
+        assert_eq!(stored.amount, wide);
+        assert_eq!(stored.disputes, 1);
+        assert!(store.contains(1));
+        assert_eq!(store.get(2).unwrap().amount.to_string(), "1.5");
+        assert_eq!(store.transactions_for_client(1).count(), 2);
+        let mut all: Vec<TransactionId> = store.iter().map(|(tx_id, _)| tx_id).collect();
+        all.sort_unstable();
+        assert_eq!(all, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_disputes_past_the_compact_range() {
+        let mut store = TransactionStore::new();
+        store.store(
+            1,
+            StoredTransaction {
+                client: 1,
+                amount: Decimal::ONE,
+                tx_type: TransactionType::Deposit,
+                dispute_state: DisputeState::None,
+                disputes: 0,
+            },
+        );
+
+        for _ in 0..300 {
+            store.mark_disputed(1, 1).unwrap();
+            store.mark_resolved(1, 1).unwrap();
+        }
+
+        let stored = store.get(1).unwrap();
+        assert_eq!(stored.disputes, 300);
+        assert_eq!(stored.dispute_state, DisputeState::Resolved);
+        assert_eq!(store.iter().count(), 1);
     }
 
     #[test]
@@ -201,15 +580,16 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         store.store(1, tx);
 
         // Mark as disputed
-        let result = store.mark_disputed(1);
+        let result = store.mark_disputed(1, 1);
         assert!(result.is_ok());
-        assert!(store.get(1).unwrap().under_dispute);
+        assert!(store.get(1).unwrap().dispute_state.is_disputed());
     }
 
     #[test]
@@ -217,7 +597,7 @@ mod tests {
         let mut store = TransactionStore::new();
 
         // Try to mark non-existent transaction
-        let result = store.mark_disputed(999);
+        let result = store.mark_disputed(1, 999);
         assert!(result.is_err());
         assert!(matches!(
             result.unwrap_err(),
@@ -233,15 +613,16 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: true,
+            dispute_state: DisputeState::Disputed,
+            disputes: 1,
         };
 
         store.store(1, tx);
 
         // Mark as resolved
-        let result = store.mark_resolved(1);
+        let result = store.mark_resolved(1, 1);
         assert!(result.is_ok());
-        assert!(!store.get(1).unwrap().under_dispute);
+        assert!(!store.get(1).unwrap().dispute_state.is_disputed());
     }
 
     #[test]
@@ -249,7 +630,7 @@ mod tests {
         let mut store = TransactionStore::new();
 
         // Try to mark non-existent transaction
-        let result = store.mark_resolved(999);
+        let result = store.mark_resolved(1, 999);
         assert!(result.is_err());
         assert!(matches!(
             result.unwrap_err(),
@@ -265,25 +646,49 @@ mod tests {
             client: 1,
             amount: Decimal::new(10000, 4),
             tx_type: TransactionType::Deposit,
-            under_dispute: false,
+            dispute_state: DisputeState::None,
+            disputes: 0,
         };
 
         store.store(1, tx);
 
         // Initial state: not disputed
-        assert!(!store.get(1).unwrap().under_dispute);
+        assert!(!store.get(1).unwrap().dispute_state.is_disputed());
 
         // Mark as disputed
-        store.mark_disputed(1).unwrap();
-        assert!(store.get(1).unwrap().under_dispute);
+        store.mark_disputed(1, 1).unwrap();
+        assert!(store.get(1).unwrap().dispute_state.is_disputed());
 
         // Mark as resolved
-        store.mark_resolved(1).unwrap();
-        assert!(!store.get(1).unwrap().under_dispute);
+        store.mark_resolved(1, 1).unwrap();
+        assert!(!store.get(1).unwrap().dispute_state.is_disputed());
 
         // Mark as disputed again
-        store.mark_disputed(1).unwrap();
-        assert!(store.get(1).unwrap().under_dispute);
+        store.mark_disputed(1, 1).unwrap();
+        assert!(store.get(1).unwrap().dispute_state.is_disputed());
+    }
+
+    #[test]
+    fn test_mark_charged_back_success() {
+        let mut store = TransactionStore::new();
+
+        store.store(
+            1,
+            StoredTransaction {
+                client: 1,
+                amount: Decimal::new(10000, 4),
+                tx_type: TransactionType::Deposit,
+                dispute_state: DisputeState::Disputed,
+                disputes: 1,
+            },
+        );
+
+        store.mark_charged_back(1, 1).unwrap();
+        assert_eq!(
+            store.get(1).unwrap().dispute_state,
+            DisputeState::ChargedBack
+        );
+        assert!(store.mark_charged_back(1, 999).is_err());
     }
 
     #[test]
@@ -300,14 +705,15 @@ mod tests {
                 } else {
                     TransactionType::Withdrawal
                 },
-                under_dispute: false,
+                dispute_state: DisputeState::None,
+                disputes: 0,
             };
-            store.store(i as u32, tx);
+            store.store(i as TransactionId, tx);
         }
 
         // Verify all transactions are stored
         for i in 1..=10 {
-            let tx = store.get(i as u32);
+            let tx = store.get(i as TransactionId);
             assert!(tx.is_some());
             assert_eq!(tx.unwrap().client, i);
         }
@@ -0,0 +1,239 @@
+//! Velocity limits on withdrawals
+//!
+//! A velocity limit caps how many withdrawals, or how much money, a client can
+//! withdraw within a window of its most recent transactions. Records carry no
+//! timestamps, so the window is counted in transactions rather than time: with
+//! a window of 10, a withdrawal is checked together with the client's previous
+//! 9 transactions.
+//!
+//! The engines reject withdrawals over the limit. The quarantine stage can
+//! instead divert them for review before they reach the engine (see
+//! `QuarantineRule::Velocity`); it then counts the transactions it lets
+//! through rather than the ones the engine applied.
+
+use crate::core::hash::KeyMap;
+use crate::types::{ClientId, PaymentError, TransactionId};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Maximum number and sum of withdrawals within a window of transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VelocityLimit {
+    /// Number of a client's most recent transactions, including the one being
+    /// checked, that the limits apply to (at least 1)
+    pub window: u32,
+    /// Maximum number of withdrawals within the window
+    pub max_withdrawals: Option<u32>,
+    /// Maximum sum of withdrawals within the window
+    pub max_withdrawn: Option<Decimal>,
+}
+
+impl VelocityLimit {
+    /// Create a limit over the last `window` transactions, with no maximums yet
+    pub fn new(window: u32) -> Self {
+        Self {
+            window: window.max(1),
+            max_withdrawals: None,
+            max_withdrawn: None,
+        }
+    }
+
+    /// Set the maximum number of withdrawals within the window
+    pub fn with_max_withdrawals(mut self, max: u32) -> Self {
+        self.max_withdrawals = Some(max);
+        self
+    }
+
+    /// Set the maximum sum of withdrawals within the window
+    pub fn with_max_withdrawn(mut self, max: Decimal) -> Self {
+        self.max_withdrawn = Some(max);
+        self
+    }
+}
+
+impl fmt::Display for VelocityLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut limits = Vec::new();
+        if let Some(max) = self.max_withdrawals {
+            limits.push(format!("{} withdrawals", max));
+        }
+        if let Some(max) = self.max_withdrawn {
+            limits.push(format!("{} withdrawn", max));
+        }
+        write!(
+            f,
+            "{} per {} transactions",
+            limits.join(" or "),
+            self.window
+        )
+    }
+}
+
+/// Recent transactions of every client, checked against a velocity limit
+#[derive(Debug, Clone)]
+pub struct VelocityTracker {
+    limit: VelocityLimit,
+    /// Amount of each recent transaction that was a withdrawal (`None` for
+    /// other transactions), oldest first; at most `window - 1` entries
+    recent: KeyMap<ClientId, VecDeque<Option<Decimal>>>,
+}
+
+impl VelocityTracker {
+    /// Create a tracker with no recorded transactions
+    pub fn new(limit: VelocityLimit) -> Self {
+        Self {
+            limit,
+            recent: KeyMap::default(),
+        }
+    }
+
+    /// The limit checked by this tracker
+    pub fn limit(&self) -> &VelocityLimit {
+        &self.limit
+    }
+
+    /// Replace the limit, keeping the recorded transactions that fit in its
+    /// window
+    pub fn set_limit(&mut self, limit: VelocityLimit) {
+        let kept = (limit.window as usize).saturating_sub(1);
+        for recent in self.recent.values_mut() {
+            while recent.len() > kept {
+                recent.pop_front();
+            }
+        }
+        self.recent.retain(|_, recent| !recent.is_empty());
+        self.limit = limit;
+    }
+
+    /// Check whether a withdrawal stays within the limit
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the withdrawal may be applied
+    /// * `Err(PaymentError::VelocityLimitExceeded)` - If it would exceed the
+    ///   number or sum of withdrawals allowed within the window
+    pub fn check_withdrawal(
+        &self,
+        tx: TransactionId,
+        client: ClientId,
+        amount: Decimal,
+    ) -> Result<(), PaymentError> {
+        let withdrawals = self
+            .recent
+            .get(&client)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .copied();
+        let (count, sum) = withdrawals.fold((1u32, amount), |(count, sum), amount| {
+            (count.saturating_add(1), sum.saturating_add(amount))
+        });
+
+        let exceeded = self.limit.max_withdrawals.is_some_and(|max| count > max)
+            || self.limit.max_withdrawn.is_some_and(|max| sum > max);
+        if exceeded {
+            return Err(PaymentError::velocity_limit_exceeded(
+                tx,
+                client,
+                &self.limit.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Record a transaction of a client, with its amount if it is a withdrawal
+    pub fn record(&mut self, client: ClientId, withdrawal: Option<Decimal>) {
+        let kept = (self.limit.window as usize).saturating_sub(1);
+        if kept == 0 {
+            return;
+        }
+        let recent = self.recent.entry(client).or_default();
+        if recent.len() == kept {
+            recent.pop_front();
+        }
+        recent.push_back(withdrawal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// Record a client's transactions, where positive amounts are withdrawals
+    fn tracker(limit: VelocityLimit, history: &[i64]) -> VelocityTracker {
+        let mut tracker = VelocityTracker::new(limit);
+        for &amount in history {
+            tracker.record(1, (amount > 0).then(|| Decimal::from(amount)));
+        }
+        tracker
+    }
+
+    #[rstest]
+    #[case::first_withdrawal(&[], 10, true)]
+    #[case::within_count(&[10], 10, true)]
+    #[case::over_count(&[10, 10], 10, false)]
+    #[case::older_withdrawal_left_window(&[10, 10, 0, 0], 10, true)]
+    #[case::over_sum(&[0, 60], 50, false)]
+    #[case::single_withdrawal_over_sum(&[], 101, false)]
+    fn test_check_withdrawal(#[case] history: &[i64], #[case] amount: i64, #[case] ok: bool) {
+        let limit = VelocityLimit::new(4)
+            .with_max_withdrawals(2)
+            .with_max_withdrawn(Decimal::from(100));
+        let result = tracker(limit, history).check_withdrawal(7, 1, Decimal::from(amount));
+        assert_eq!(result.is_ok(), ok);
+    }
+
+    #[test]
+    fn test_clients_are_tracked_separately() {
+        let limit = VelocityLimit::new(3).with_max_withdrawals(1);
+        let tracker = tracker(limit, &[10]);
+
+        assert_eq!(
+            tracker.check_withdrawal(7, 1, Decimal::ONE),
+            Err(PaymentError::velocity_limit_exceeded(
+                7,
+                1,
+                "1 withdrawals per 3 transactions"
+            ))
+        );
+        assert!(tracker.check_withdrawal(7, 2, Decimal::ONE).is_ok());
+    }
+
+    #[test]
+    fn test_set_limit_keeps_recent_transactions() {
+        let mut tracker = tracker(VelocityLimit::new(4).with_max_withdrawals(3), &[10, 10, 10]);
+        assert!(tracker.check_withdrawal(7, 1, Decimal::ONE).is_err());
+
+        // A narrower window forgets the transactions that left it
+        tracker.set_limit(VelocityLimit::new(2).with_max_withdrawals(1));
+        assert!(tracker.check_withdrawal(7, 1, Decimal::ONE).is_err());
+        tracker.record(1, None);
+        assert!(tracker.check_withdrawal(7, 1, Decimal::ONE).is_ok());
+
+        tracker.set_limit(VelocityLimit::new(4).with_max_withdrawals(1));
+        tracker.record(1, Some(Decimal::ONE));
+        assert!(tracker.check_withdrawal(7, 1, Decimal::ONE).is_err());
+    }
+
+    #[test]
+    fn test_window_of_one_checks_single_withdrawals() {
+        let limit = VelocityLimit::new(1).with_max_withdrawn(Decimal::from(100));
+        let tracker = tracker(limit, &[90, 90]);
+
+        assert!(tracker.check_withdrawal(7, 1, Decimal::from(100)).is_ok());
+        assert!(tracker.check_withdrawal(7, 1, Decimal::from(101)).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let limit = VelocityLimit::new(10)
+            .with_max_withdrawals(3)
+            .with_max_withdrawn(Decimal::from(500));
+        assert_eq!(
+            limit.to_string(),
+            "3 withdrawals or 500 withdrawn per 10 transactions"
+        );
+    }
+}
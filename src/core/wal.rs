@@ -0,0 +1,608 @@
+//! Write-ahead log for crash-consistent processing
+//!
+//! This module provides the `WriteAheadLog`, an append-only file of transaction
+//! records, and the `DurableEngine`, which appends every record to the log
+//! before applying it to a `TransactionEngine`.
+//!
+//! # Recovery
+//!
+//! Opening a `DurableEngine` replays the existing log into a fresh engine, so
+//! state after a crash is exactly the state produced by every record that made
+//! it into the log. Processing is deterministic, so records that were rejected
+//! originally are rejected again on replay and leave no trace in the state.
+//!
+//! # Format
+//!
+//! One record per line, in the input column order without a header:
+//!
+//! ```text
+//! deposit,1,1,1.5
+//! dispute,1,1,
+//! ```
+//!
+//! A final line without a trailing newline is a torn write from a crash; it is
+//! discarded (and truncated from the file) on recovery. Any other line that
+//! cannot be decoded means the log is corrupted and recovery fails.
+//!
+//! # Durability
+//!
+//! Each append is a single unbuffered write, so an appended record survives a
+//! crash of the process. Call `sync` to also make it survive a crash of the
+//! machine.
+//!
+//! # Replicas
+//!
+//! A `WalReplica` reads a log that another process is appending to, without
+//! ever writing it, and applies its records to an engine of its own as they
+//! are appended. It keeps a read-only copy of the state of the process
+//! ingesting the records, so queries can be served from it without loading
+//! that process. A final line without a newline is left for the next
+//! `catch_up`, as the writer may still be appending it.
+
+use crate::core::{BalancePoint, EngineConfig, ExpiredDispute, Posting, TransactionEngine};
+use crate::io::csv_format::{convert_csv_record, CsvRecord};
+use crate::types::{PaymentError, TransactionRecord, TransactionType};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Append-only log of transaction records
+#[derive(Debug)]
+pub struct WriteAheadLog {
+    /// Log file, opened for appending
+    file: File,
+    /// Path of the log file, for error messages
+    path: PathBuf,
+}
+
+impl WriteAheadLog {
+    /// Open a log file, creating it if it does not exist
+    ///
+    /// Reads back every record already in the log and truncates a torn final
+    /// line, leaving the file ready for appending.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the log file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((WriteAheadLog, Vec<TransactionRecord>))` - The log and its existing records
+    /// * `Err(String)` - If the file cannot be opened, read or truncated, or is corrupted
+    pub fn open(path: &Path) -> Result<(Self, Vec<TransactionRecord>), String> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open write-ahead log '{}': {}", path.display(), e))?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read write-ahead log '{}': {}", path.display(), e))?;
+
+        // Everything after the last newline is a torn write
+        let complete = contents
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |pos| pos + 1);
+        if complete < contents.len() {
+            file.set_len(complete as u64).map_err(|e| {
+                format!(
+                    "Failed to truncate write-ahead log '{}': {}",
+                    path.display(),
+                    e
+                )
+            })?;
+        }
+
+        let text = std::str::from_utf8(&contents[..complete])
+            .map_err(|e| format!("Corrupted write-ahead log '{}': {}", path.display(), e))?;
+
+        let records = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                decode_record(line).map_err(|e| {
+                    format!(
+                        "Corrupted write-ahead log '{}' at line {}: {}",
+                        path.display(),
+                        index + 1,
+                        e
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let wal = WriteAheadLog {
+            file,
+            path: path.to_path_buf(),
+        };
+        Ok((wal, records))
+    }
+
+    /// Append a record to the log
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the record has been written to the file
+    /// * `Err(String)` if the write failed; the record must not be applied
+    pub fn append(&mut self, record: &TransactionRecord) -> Result<(), String> {
+        self.file
+            .write_all(encode_record(record).as_bytes())
+            .map_err(|e| {
+                format!(
+                    "Failed to append to write-ahead log '{}': {}",
+                    self.path.display(),
+                    e
+                )
+            })
+    }
+
+    /// Flush appended records to stable storage
+    pub fn sync(&mut self) -> Result<(), String> {
+        self.file.sync_data().map_err(|e| {
+            format!(
+                "Failed to sync write-ahead log '{}': {}",
+                self.path.display(),
+                e
+            )
+        })
+    }
+}
+
+/// Encode a record as a single log line, including the trailing newline
+fn encode_record(record: &TransactionRecord) -> String {
+    let tx_type = match record.tx_type {
+        TransactionType::Deposit => "deposit",
+        TransactionType::Withdrawal => "withdrawal",
+        TransactionType::Dispute => "dispute",
+        TransactionType::Resolve => "resolve",
+        TransactionType::Chargeback => "chargeback",
+        TransactionType::Approve => "approve",
+        TransactionType::Reject => "reject",
+    };
+    let amount = record.amount.map(|a| a.to_string()).unwrap_or_default();
+    format!("{},{},{},{}\n", tx_type, record.client, record.tx, amount)
+}
+
+/// Decode a log line (without its newline) back into a record
+fn decode_record(line: &str) -> Result<TransactionRecord, String> {
+    let fields: Vec<&str> = line.split(',').collect();
+    let [tx_type, client, tx, amount] = fields[..] else {
+        return Err(format!("expected 4 fields, found {}", fields.len()));
+    };
+
+    convert_csv_record(CsvRecord {
+        tx_type: tx_type.to_string(),
+        client: client
+            .parse()
+            .map_err(|e| format!("Invalid client '{}': {}", client, e))?,
+        tx: tx
+            .parse()
+            .map_err(|e| format!("Invalid tx '{}': {}", tx, e))?,
+        amount: Some(amount.to_string()),
+    })
+}
+
+/// Transaction engine that logs every record before applying it
+pub struct DurableEngine {
+    engine: TransactionEngine,
+    wal: WriteAheadLog,
+    /// Number of records replayed from the log on open
+    replayed: u64,
+}
+
+impl DurableEngine {
+    /// Open a write-ahead log and recover the engine state it describes
+    ///
+    /// # Arguments
+    ///
+    /// * `wal_path` - Path to the log file (created if missing)
+    /// * `config` - Engine configuration; replay only reproduces the original
+    ///   outcomes if it matches the configuration the log was written with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(DurableEngine)` - Engine with every logged record replayed
+    /// * `Err(String)` - If the log cannot be opened or is corrupted
+    pub fn open(wal_path: &Path, config: EngineConfig) -> Result<Self, String> {
+        let (wal, records) = WriteAheadLog::open(wal_path)?;
+
+        let mut engine = TransactionEngine::with_config(config);
+        let replayed = records.len() as u64;
+        for record in records {
+            // Rejections were already reported when the record was first processed
+            let _ = engine.process(record);
+        }
+        // Likewise the disputes that expired, the postings journaled and the
+        // balances sampled during those runs
+        engine.take_expired_disputes();
+        engine.take_postings();
+        engine.take_balance_history();
+
+        Ok(DurableEngine {
+            engine,
+            wal,
+            replayed,
+        })
+    }
+
+    /// Log a record, then apply it to the engine
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Ok(()))` if the record was logged and applied
+    /// * `Ok(Err(PaymentError))` if the record was logged but rejected by the engine
+    /// * `Err(String)` if the record could not be logged; it is not applied
+    pub fn process(
+        &mut self,
+        record: TransactionRecord,
+    ) -> Result<Result<(), PaymentError>, String> {
+        self.wal.append(&record)?;
+        Ok(self.engine.process(record))
+    }
+
+    /// Flush the log to stable storage
+    pub fn sync(&mut self) -> Result<(), String> {
+        self.wal.sync()
+    }
+
+    /// Number of records replayed from the log when it was opened
+    pub fn replayed(&self) -> u64 {
+        self.replayed
+    }
+
+    /// Take the audit events of the disputes expired since the last call
+    pub fn take_expired_disputes(&mut self) -> Vec<ExpiredDispute> {
+        self.engine.take_expired_disputes()
+    }
+
+    /// Take the postings of the transactions applied since the last call
+    pub fn take_postings(&mut self) -> Vec<Posting> {
+        self.engine.take_postings()
+    }
+
+    /// Take the balance history samples recorded since the last call
+    pub fn take_balance_history(&mut self) -> Vec<BalancePoint> {
+        self.engine.take_balance_history()
+    }
+
+    /// The underlying engine
+    pub fn engine(&self) -> &TransactionEngine {
+        &self.engine
+    }
+}
+
+/// Read-only copy of the engine state described by a growing log
+pub struct WalReplica {
+    engine: TransactionEngine,
+    /// Log file, opened for reading only
+    file: File,
+    /// Path of the log file, for error messages
+    path: PathBuf,
+    /// Bytes of the log applied so far, always ending at a line end
+    offset: u64,
+    /// Number of records applied so far
+    applied: u64,
+}
+
+impl WalReplica {
+    /// Open a log and apply every complete record already in it
+    ///
+    /// # Arguments
+    ///
+    /// * `wal_path` - Path to the log file, which must exist
+    /// * `config` - Engine configuration; it must match the configuration the
+    ///   log is written with for the replica to reach the same state
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(WalReplica)` - Replica with every complete record applied
+    /// * `Err(String)` - If the log cannot be opened or read, or is corrupted
+    pub fn open(wal_path: &Path, config: EngineConfig) -> Result<Self, String> {
+        let file = File::open(wal_path).map_err(|e| {
+            format!(
+                "Failed to open write-ahead log '{}': {}",
+                wal_path.display(),
+                e
+            )
+        })?;
+        let mut replica = WalReplica {
+            engine: TransactionEngine::with_config(config),
+            file,
+            path: wal_path.to_path_buf(),
+            offset: 0,
+            applied: 0,
+        };
+        replica.catch_up()?;
+        Ok(replica)
+    }
+
+    /// Apply the complete records appended to the log since the last call
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The number of records applied
+    /// * `Err(String)` - If the log cannot be read, is corrupted, or is now
+    ///   shorter than the records already applied
+    pub fn catch_up(&mut self) -> Result<u64, String> {
+        let len = self
+            .file
+            .metadata()
+            .map_err(|e| {
+                format!(
+                    "Failed to read write-ahead log '{}': {}",
+                    self.path.display(),
+                    e
+                )
+            })?
+            .len();
+        if len < self.offset {
+            return Err(format!(
+                "Write-ahead log '{}' is shorter than the {} bytes already replicated; \
+                 it was truncated or replaced",
+                self.path.display(),
+                self.offset
+            ));
+        }
+
+        let mut appended = Vec::new();
+        self.file
+            .seek(SeekFrom::Start(self.offset))
+            .and_then(|_| self.file.read_to_end(&mut appended))
+            .map_err(|e| {
+                format!(
+                    "Failed to read write-ahead log '{}': {}",
+                    self.path.display(),
+                    e
+                )
+            })?;
+
+        // A final line without a newline may still be being written
+        let complete = appended
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |pos| pos + 1);
+        let text = std::str::from_utf8(&appended[..complete])
+            .map_err(|e| format!("Corrupted write-ahead log '{}': {}", self.path.display(), e))?;
+
+        let before = self.applied;
+        for line in text.lines() {
+            let record = decode_record(line).map_err(|e| {
+                format!(
+                    "Corrupted write-ahead log '{}' at line {}: {}",
+                    self.path.display(),
+                    self.applied + 1,
+                    e
+                )
+            })?;
+            // Rejections were reported by the process that logged the record
+            let _ = self.engine.process(record);
+            self.applied += 1;
+        }
+        self.offset += complete as u64;
+
+        // Nothing reads the events of the replayed records
+        self.engine.take_expired_disputes();
+        self.engine.take_postings();
+        self.engine.take_balance_history();
+        Ok(self.applied - before)
+    }
+
+    /// Number of records applied since the replica was opened
+    pub fn applied(&self) -> u64 {
+        self.applied
+    }
+
+    /// The replica's engine
+    pub fn engine(&self) -> &TransactionEngine {
+        &self.engine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ClientId, TransactionId};
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    fn record(
+        tx_type: TransactionType,
+        client: ClientId,
+        tx: TransactionId,
+        amount: Option<Decimal>,
+    ) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx,
+            amount,
+            line: None,
+            source: None,
+        }
+    }
+
+    #[rstest]
+    #[case::deposit(
+        record(TransactionType::Deposit, 1, 1, Some(Decimal::new(15000, 4))),
+        "deposit,1,1,1.5000\n"
+    )]
+    #[case::withdrawal(
+        record(TransactionType::Withdrawal, 2, 7, Some(Decimal::new(3, 0))),
+        "withdrawal,2,7,3\n"
+    )]
+    #[case::dispute(record(TransactionType::Dispute, 1, 1, None), "dispute,1,1,\n")]
+    #[case::resolve(record(TransactionType::Resolve, 1, 1, None), "resolve,1,1,\n")]
+    #[case::chargeback(
+        record(TransactionType::Chargeback, 65535, 4294967295, None),
+        "chargeback,65535,4294967295,\n"
+    )]
+    fn test_record_round_trip(#[case] original: TransactionRecord, #[case] line: &str) {
+        assert_eq!(encode_record(&original), line);
+
+        let decoded = decode_record(line.trim_end_matches('\n')).unwrap();
+        assert_eq!(decoded.tx_type, original.tx_type);
+        assert_eq!(decoded.client, original.client);
+        assert_eq!(decoded.tx, original.tx);
+        assert_eq!(decoded.amount, original.amount);
+    }
+
+    #[rstest]
+    #[case::too_few_fields("deposit,1,1")]
+    #[case::bad_type("transfer,1,1,1.0")]
+    #[case::bad_client("deposit,x,1,1.0")]
+    #[case::missing_amount("deposit,1,1,")]
+    fn test_decode_record_invalid(#[case] line: &str) {
+        assert!(decode_record(line).is_err());
+    }
+
+    #[test]
+    fn test_durable_engine_recovers_state() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("engine.wal");
+
+        {
+            let mut engine = DurableEngine::open(&path, EngineConfig::default()).unwrap();
+            assert_eq!(engine.replayed(), 0);
+            engine
+                .process(record(
+                    TransactionType::Deposit,
+                    1,
+                    1,
+                    Some(Decimal::new(10, 0)),
+                ))
+                .unwrap()
+                .unwrap();
+            // Rejected records are logged too and rejected again on replay
+            let rejected = engine
+                .process(record(
+                    TransactionType::Withdrawal,
+                    1,
+                    2,
+                    Some(Decimal::new(50, 0)),
+                ))
+                .unwrap();
+            assert!(rejected.is_err());
+            engine.sync().unwrap();
+        }
+
+        let mut engine = DurableEngine::open(&path, EngineConfig::default()).unwrap();
+        assert_eq!(engine.replayed(), 2);
+        assert_eq!(
+            engine.engine().account(1).unwrap().available,
+            Decimal::new(10, 0)
+        );
+
+        // Dispute a transaction logged before the restart
+        engine
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            engine.engine().account(1).unwrap().held,
+            Decimal::new(10, 0)
+        );
+    }
+
+    #[test]
+    fn test_replay_does_not_report_expired_disputes_again() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("engine.wal");
+        std::fs::write(
+            &path,
+            "deposit,1,1,5\ndispute,1,1,\ndeposit,1,2,5\ndeposit,1,3,5\n",
+        )
+        .unwrap();
+        let config = EngineConfig::new().with_dispute_expiry(1);
+
+        let mut engine = DurableEngine::open(&path, config).unwrap();
+        assert!(engine.take_expired_disputes().is_empty());
+        assert_eq!(engine.engine().account(1).unwrap().held, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_open_truncates_torn_write() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("engine.wal");
+        std::fs::write(&path, "deposit,1,1,5\nwithdrawal,1,2,").unwrap();
+
+        let (mut wal, records) = WriteAheadLog::open(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "deposit,1,1,5\n");
+
+        // New appends start on a fresh line
+        wal.append(&record(TransactionType::Dispute, 1, 1, None))
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "deposit,1,1,5\ndispute,1,1,\n"
+        );
+    }
+
+    #[test]
+    fn test_open_rejects_corrupted_log() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("engine.wal");
+        std::fs::write(&path, "deposit,1,1,5\ngarbage\ndeposit,1,2,5\n").unwrap();
+
+        let err = WriteAheadLog::open(&path).unwrap_err();
+        assert!(err.contains("at line 2"));
+    }
+
+    #[test]
+    fn test_replica_follows_log() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("engine.wal");
+        let mut primary = DurableEngine::open(&path, EngineConfig::default()).unwrap();
+        primary
+            .process(record(TransactionType::Deposit, 1, 1, Some(Decimal::TEN)))
+            .unwrap()
+            .unwrap();
+
+        let mut replica = WalReplica::open(&path, EngineConfig::default()).unwrap();
+        assert_eq!(replica.applied(), 1);
+        assert_eq!(replica.catch_up(), Ok(0));
+
+        primary
+            .process(record(TransactionType::Dispute, 1, 1, None))
+            .unwrap()
+            .unwrap();
+        // A torn line is applied once it is complete
+        let mut writer = OpenOptions::new().append(true).open(&path).unwrap();
+        writer.write_all(b"deposit,2,2,").unwrap();
+        assert_eq!(replica.catch_up(), Ok(1));
+        assert_eq!(replica.engine().account(1).unwrap().held, Decimal::TEN);
+        writer.write_all(b"3\n").unwrap();
+        assert_eq!(replica.catch_up(), Ok(1));
+        assert_eq!(
+            replica.engine().account(2).unwrap().available,
+            Decimal::from(3)
+        );
+        assert_eq!(replica.applied(), 3);
+    }
+
+    #[test]
+    fn test_replica_rejects_truncated_log() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("engine.wal");
+        std::fs::write(&path, "deposit,1,1,5\ndeposit,1,2,5\n").unwrap();
+        let mut replica = WalReplica::open(&path, EngineConfig::default()).unwrap();
+
+        std::fs::write(&path, "deposit,1,1,5\n").unwrap();
+        let err = replica.catch_up().unwrap_err();
+        assert!(err.contains("truncated or replaced"), "{}", err);
+    }
+
+    #[test]
+    fn test_replica_requires_existing_log() {
+        let dir = TempDir::new().unwrap();
+        let result = WalReplica::open(&dir.path().join("missing.wal"), EngineConfig::default());
+        assert!(result
+            .err()
+            .unwrap()
+            .contains("Failed to open write-ahead log"));
+    }
+}
@@ -0,0 +1,446 @@
+//! C bindings for the engine (feature `ffi`)
+//!
+//! Lets C and C++ code run the sync engine in-process: create an engine, feed
+//! it transactions one at a time and read back account balances. The matching
+//! declarations are in `include/payments_engine.h`.
+//!
+//! Build a shared or static library with
+//! `cargo rustc --release --lib --no-default-features --features ffi --crate-type cdylib`
+//! (or `staticlib`).
+//!
+//! # Amounts
+//!
+//! Amounts cross the boundary as signed 64-bit fixed-point integers in units of
+//! 1/10,000 (`12345` is `1.2345`), the precision of the CSV output. Balances
+//! that do not fit saturate at `INT64_MIN`/`INT64_MAX`.
+//!
+//! # Errors
+//!
+//! Functions return a `PE_*` status code. After a failed call, the message of
+//! the error is available from `pe_engine_last_error` until the next call on
+//! the same engine. Messages of rejected transactions start with the stable
+//! `PaymentError` code, e.g. `[E301] Insufficient funds ...`.
+
+use crate::core::TransactionEngine;
+use crate::types::{Account, ClientId, TransactionRecord, TransactionType};
+use rust_decimal::Decimal;
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+/// The call succeeded
+pub const PE_OK: i32 = 0;
+/// A required pointer argument was null
+pub const PE_NULL_ARGUMENT: i32 = -1;
+/// The arguments do not describe a valid transaction
+pub const PE_INVALID_ARGUMENT: i32 = -2;
+/// The engine rejected the transaction (see `pe_engine_last_error`)
+pub const PE_REJECTED: i32 = -3;
+/// The requested account does not exist
+pub const PE_NOT_FOUND: i32 = -4;
+
+/// Deposit type code for `pe_engine_process`
+pub const PE_DEPOSIT: u8 = 0;
+/// Withdrawal type code for `pe_engine_process`
+pub const PE_WITHDRAWAL: u8 = 1;
+/// Dispute type code for `pe_engine_process`
+pub const PE_DISPUTE: u8 = 2;
+/// Resolve type code for `pe_engine_process`
+pub const PE_RESOLVE: u8 = 3;
+/// Chargeback type code for `pe_engine_process`
+pub const PE_CHARGEBACK: u8 = 4;
+/// Approve type code for `pe_engine_process`
+pub const PE_APPROVE: u8 = 5;
+/// Reject type code for `pe_engine_process`
+pub const PE_REJECT: u8 = 6;
+
+/// Number of decimal places of fixed-point amounts
+const AMOUNT_SCALE: u32 = 4;
+
+/// Engine handle, opaque to C
+pub struct PeEngine {
+    engine: TransactionEngine,
+    /// Message of the last failed call
+    last_error: Option<CString>,
+}
+
+impl PeEngine {
+    /// Record a failed call and return its status
+    fn fail(&mut self, status: i32, message: impl Into<String>) -> i32 {
+        // Messages never contain NUL bytes; fall back to an empty message
+        self.last_error = Some(CString::new(message.into()).unwrap_or_default());
+        status
+    }
+}
+
+/// Account balances as returned to C
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeAccount {
+    pub client: u64,
+    /// Available funds in 1/10,000 units
+    pub available: i64,
+    /// Held funds in 1/10,000 units
+    pub held: i64,
+    /// Total funds in 1/10,000 units
+    pub total: i64,
+    pub locked: bool,
+}
+
+impl From<&Account> for PeAccount {
+    // `ClientId` is already `u64` with the `client-id-u64` feature
+    #[allow(clippy::useless_conversion)]
+    fn from(account: &Account) -> Self {
+        Self {
+            client: account.client.into(),
+            available: to_fixed(account.available),
+            held: to_fixed(account.held),
+            total: to_fixed(account.total),
+            locked: account.locked,
+        }
+    }
+}
+
+/// Convert an amount to 1/10,000 units, rounding and saturating
+fn to_fixed(amount: Decimal) -> i64 {
+    let mut amount = amount.round_dp(AMOUNT_SCALE);
+    amount.rescale(AMOUNT_SCALE);
+    let mantissa = amount.mantissa();
+    i64::try_from(mantissa).unwrap_or(if mantissa < 0 { i64::MIN } else { i64::MAX })
+}
+
+/// Create an engine with the default configuration
+///
+/// The engine must be released with `pe_engine_free`.
+#[no_mangle]
+pub extern "C" fn pe_engine_new() -> *mut PeEngine {
+    Box::into_raw(Box::new(PeEngine {
+        engine: TransactionEngine::new(),
+        last_error: None,
+    }))
+}
+
+/// Release an engine created with `pe_engine_new`
+///
+/// # Safety
+///
+/// `engine` must be null or a pointer returned by `pe_engine_new` that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pe_engine_free(engine: *mut PeEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Apply one transaction
+///
+/// `amount` is in 1/10,000 units and only used for deposits and withdrawals.
+///
+/// # Safety
+///
+/// `engine` must be null or a live pointer returned by `pe_engine_new`, not
+/// used concurrently by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn pe_engine_process(
+    engine: *mut PeEngine,
+    tx_type: u8,
+    client: u64,
+    tx: u64,
+    amount: i64,
+) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return PE_NULL_ARGUMENT;
+    };
+    engine.last_error = None;
+
+    let tx_type = match tx_type {
+        PE_DEPOSIT => TransactionType::Deposit,
+        PE_WITHDRAWAL => TransactionType::Withdrawal,
+        PE_DISPUTE => TransactionType::Dispute,
+        PE_RESOLVE => TransactionType::Resolve,
+        PE_CHARGEBACK => TransactionType::Chargeback,
+        PE_APPROVE => TransactionType::Approve,
+        PE_REJECT => TransactionType::Reject,
+        other => {
+            return engine.fail(
+                PE_INVALID_ARGUMENT,
+                format!("Invalid transaction type code {}", other),
+            )
+        }
+    };
+    let Some(client) = ClientId::try_from(client).ok() else {
+        return engine.fail(
+            PE_INVALID_ARGUMENT,
+            format!("Client ID {} exceeds the client ID range", client),
+        );
+    };
+    let amount = matches!(
+        tx_type,
+        TransactionType::Deposit | TransactionType::Withdrawal
+    )
+    .then(|| Decimal::new(amount, AMOUNT_SCALE));
+
+    match engine.engine.process(TransactionRecord {
+        tx_type,
+        client,
+        tx,
+        amount,
+        line: None,
+        source: None,
+    }) {
+        Ok(()) => PE_OK,
+        Err(e) => engine.fail(PE_REJECTED, e.with_code()),
+    }
+}
+
+/// Read the balances of one account into `out`
+///
+/// # Safety
+///
+/// `engine` must be null or a live pointer returned by `pe_engine_new`, and
+/// `out` must be null or valid for writing one `PeAccount`.
+#[no_mangle]
+pub unsafe extern "C" fn pe_engine_account(
+    engine: *mut PeEngine,
+    client: u64,
+    out: *mut PeAccount,
+) -> i32 {
+    let (Some(engine), false) = (engine.as_mut(), out.is_null()) else {
+        return PE_NULL_ARGUMENT;
+    };
+    engine.last_error = None;
+
+    let account = ClientId::try_from(client)
+        .ok()
+        .and_then(|client| engine.engine.account(client));
+    match account {
+        Some(account) => {
+            out.write(PeAccount::from(account));
+            PE_OK
+        }
+        None => engine.fail(PE_NOT_FOUND, format!("Client {} not found", client)),
+    }
+}
+
+/// Copy the balances of all accounts, sorted by client ID, into `out`
+///
+/// At most `capacity` accounts are written. Call with a null `out` and a
+/// `capacity` of 0 to size the buffer.
+///
+/// # Returns
+///
+/// The total number of accounts, which may exceed `capacity`
+///
+/// # Safety
+///
+/// `engine` must be null or a live pointer returned by `pe_engine_new`, and
+/// `out` must be valid for writing `capacity` accounts.
+#[no_mangle]
+pub unsafe extern "C" fn pe_engine_accounts(
+    engine: *const PeEngine,
+    out: *mut PeAccount,
+    capacity: usize,
+) -> usize {
+    let Some(engine) = engine.as_ref() else {
+        return 0;
+    };
+
+    let accounts = engine.engine.get_accounts();
+    if !out.is_null() {
+        for (index, account) in accounts.iter().take(capacity).enumerate() {
+            out.add(index).write(PeAccount::from(*account));
+        }
+    }
+    accounts.len()
+}
+
+/// Message of the last failed call on `engine`, or null if it succeeded
+///
+/// The string is owned by the engine and valid until the next call on it.
+///
+/// # Safety
+///
+/// `engine` must be null or a live pointer returned by `pe_engine_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pe_engine_last_error(engine: *const PeEngine) -> *const c_char {
+    engine
+        .as_ref()
+        .and_then(|engine| engine.last_error.as_ref())
+        .map_or(ptr::null(), |message| message.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::ffi::CStr;
+
+    /// Header shipped for C callers
+    const HEADER: &str = include_str!("../include/payments_engine.h");
+
+    struct Engine(*mut PeEngine);
+
+    impl Engine {
+        fn new() -> Self {
+            Self(pe_engine_new())
+        }
+
+        fn process(&self, tx_type: u8, client: u64, tx: u64, amount: i64) -> i32 {
+            unsafe { pe_engine_process(self.0, tx_type, client, tx, amount) }
+        }
+
+        fn last_error(&self) -> Option<String> {
+            let message = unsafe { pe_engine_last_error(self.0) };
+            (!message.is_null()).then(|| {
+                unsafe { CStr::from_ptr(message) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+        }
+    }
+
+    impl Drop for Engine {
+        fn drop(&mut self) {
+            unsafe { pe_engine_free(self.0) }
+        }
+    }
+
+    #[test]
+    fn test_process_and_read_accounts() {
+        let engine = Engine::new();
+        assert_eq!(engine.process(PE_DEPOSIT, 2, 1, 100_000), PE_OK);
+        assert_eq!(engine.process(PE_DEPOSIT, 1, 2, 12_345), PE_OK);
+        assert_eq!(engine.process(PE_DISPUTE, 2, 1, 0), PE_OK);
+        assert_eq!(engine.last_error(), None);
+
+        let mut account = PeAccount::default();
+        assert_eq!(
+            unsafe { pe_engine_account(engine.0, 2, &mut account) },
+            PE_OK
+        );
+        assert_eq!(
+            account,
+            PeAccount {
+                client: 2,
+                available: 0,
+                held: 100_000,
+                total: 100_000,
+                locked: false,
+            }
+        );
+
+        let count = unsafe { pe_engine_accounts(engine.0, ptr::null_mut(), 0) };
+        assert_eq!(count, 2);
+        let mut accounts = vec![PeAccount::default(); count];
+        unsafe { pe_engine_accounts(engine.0, accounts.as_mut_ptr(), accounts.len()) };
+        assert_eq!(accounts[0].client, 1);
+        assert_eq!(accounts[0].available, 12_345);
+    }
+
+    #[rstest]
+    #[case::rejected(PE_WITHDRAWAL, 1, 2, 500_000, PE_REJECTED)]
+    #[case::unknown_type(9, 1, 2, 0, PE_INVALID_ARGUMENT)]
+    fn test_process_errors(
+        #[case] tx_type: u8,
+        #[case] client: u64,
+        #[case] tx: u64,
+        #[case] amount: i64,
+        #[case] expected: i32,
+    ) {
+        let engine = Engine::new();
+        assert_eq!(engine.process(PE_DEPOSIT, 1, 1, 10_000), PE_OK);
+
+        assert_eq!(engine.process(tx_type, client, tx, amount), expected);
+        assert!(engine.last_error().is_some());
+
+        assert_eq!(engine.process(PE_DEPOSIT, 1, 3, 10_000), PE_OK);
+        assert_eq!(engine.last_error(), None);
+    }
+
+    // Every u64 is a valid client ID with `client-id-u64`
+    #[cfg(not(feature = "client-id-u64"))]
+    #[test]
+    fn test_process_rejects_client_out_of_range() {
+        let engine = Engine::new();
+        assert_eq!(
+            engine.process(PE_DEPOSIT, u64::MAX, 1, 10_000),
+            PE_INVALID_ARGUMENT
+        );
+        assert!(engine.last_error().unwrap().contains("client ID range"));
+    }
+
+    #[test]
+    fn test_missing_account_and_null_arguments() {
+        let engine = Engine::new();
+        let mut account = PeAccount::default();
+        assert_eq!(
+            unsafe { pe_engine_account(engine.0, 7, &mut account) },
+            PE_NOT_FOUND
+        );
+        assert_eq!(engine.last_error().as_deref(), Some("Client 7 not found"));
+
+        unsafe {
+            assert_eq!(
+                pe_engine_process(ptr::null_mut(), PE_DEPOSIT, 1, 1, 1),
+                PE_NULL_ARGUMENT
+            );
+            assert_eq!(
+                pe_engine_account(engine.0, 7, ptr::null_mut()),
+                PE_NULL_ARGUMENT
+            );
+            assert_eq!(pe_engine_accounts(ptr::null(), ptr::null_mut(), 0), 0);
+            assert!(pe_engine_last_error(ptr::null()).is_null());
+            pe_engine_free(ptr::null_mut());
+        }
+    }
+
+    #[rstest]
+    #[case(Decimal::new(12345, 4), 12345)]
+    #[case(Decimal::new(-5, 1), -5000)]
+    #[case(Decimal::new(123456, 5), 12346)]
+    #[case(Decimal::MAX, i64::MAX)]
+    #[case(Decimal::MIN, i64::MIN)]
+    fn test_to_fixed(#[case] amount: Decimal, #[case] expected: i64) {
+        assert_eq!(to_fixed(amount), expected);
+    }
+
+    #[test]
+    fn test_header_declares_every_function() {
+        let declarations: Vec<&str> = HEADER
+            .lines()
+            .filter(|line| !line.starts_with("/*") && !line.starts_with(" *"))
+            .collect();
+        for function in [
+            "pe_engine_new",
+            "pe_engine_free",
+            "pe_engine_process",
+            "pe_engine_account",
+            "pe_engine_accounts",
+            "pe_engine_last_error",
+        ] {
+            assert!(
+                declarations
+                    .iter()
+                    .any(|line| line.contains(&format!("{}(", function))),
+                "{function} is not declared in payments_engine.h"
+            );
+        }
+        for (name, value) in [
+            ("PE_OK", PE_OK),
+            ("PE_NULL_ARGUMENT", PE_NULL_ARGUMENT),
+            ("PE_INVALID_ARGUMENT", PE_INVALID_ARGUMENT),
+            ("PE_REJECTED", PE_REJECTED),
+            ("PE_NOT_FOUND", PE_NOT_FOUND),
+            ("PE_DEPOSIT", PE_DEPOSIT.into()),
+            ("PE_WITHDRAWAL", PE_WITHDRAWAL.into()),
+            ("PE_DISPUTE", PE_DISPUTE.into()),
+            ("PE_RESOLVE", PE_RESOLVE.into()),
+            ("PE_CHARGEBACK", PE_CHARGEBACK.into()),
+        ] {
+            assert!(
+                HEADER.contains(&format!("#define {} ({})", name, value)),
+                "{name} does not match payments_engine.h"
+            );
+        }
+    }
+}
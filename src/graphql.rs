@@ -0,0 +1,306 @@
+//! GraphQL queries over engine state (feature `graphql`)
+//!
+//! Exposes the accounts and stored transactions of an `EngineSnapshot` through
+//! a read-only GraphQL schema, for tooling that needs ad-hoc queries against a
+//! restored engine (a state file saved with `--save-state`) or a snapshot of a
+//! running one. The `graphql` subcommand serves the schema over HTTP, or runs
+//! a single query:
+//!
+//! ```graphql
+//! {
+//!   accounts(locked: true) { client available held total lockReason }
+//!   transactions(client: 42, disputeState: DISPUTED) { tx type amount disputes }
+//! }
+//! ```
+//!
+//! Amounts are strings with four decimal places, as in the account output, so
+//! no precision is lost to floating point. Only deposits and withdrawals are
+//! stored, so only they can be queried as transactions.
+
+use crate::core::EngineSnapshot;
+use crate::types::{Account, ClientId, StoredTransaction, TransactionId};
+use async_graphql::{EmptyMutation, EmptySubscription, Enum, Object, Schema, SimpleObject};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// The GraphQL schema over an engine snapshot
+pub type EngineSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Build the schema answering queries from `snapshot`
+pub fn schema(snapshot: EngineSnapshot) -> EngineSchema {
+    Schema::build(
+        QueryRoot {
+            snapshot: Arc::new(snapshot),
+        },
+        EmptyMutation,
+        EmptySubscription,
+    )
+    .finish()
+}
+
+/// Serve the schema on `listener`, answering POSTs to `/graphql`
+///
+/// # Returns
+///
+/// * `Err(String)` - If the server fails; it runs until then
+pub async fn serve(schema: EngineSchema, listener: TcpListener) -> Result<(), String> {
+    let app = Router::new()
+        .route("/graphql", post(execute))
+        .with_state(schema);
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| format!("GraphQL server failed: {}", e))
+}
+
+/// Run one GraphQL request
+async fn execute(
+    State(schema): State<EngineSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+/// Where a transaction is in the dispute lifecycle
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "DisputeState", remote = "crate::types::DisputeState")]
+enum DisputeStateValue {
+    None,
+    Disputed,
+    Resolved,
+    ChargedBack,
+    Pending,
+    Rejected,
+}
+
+/// Type of a transaction
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "TransactionType", remote = "crate::types::TransactionType")]
+enum TransactionTypeValue {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    Chargeback,
+    Approve,
+    Reject,
+}
+
+/// A client account
+#[derive(SimpleObject)]
+#[graphql(name = "Account")]
+struct AccountNode {
+    client: ClientId,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+    /// Why the account was locked, e.g. `chargeback:7`, if known
+    lock_reason: Option<String>,
+}
+
+impl From<&Account> for AccountNode {
+    fn from(account: &Account) -> Self {
+        Self {
+            client: account.client,
+            available: format!("{:.4}", account.available),
+            held: format!("{:.4}", account.held),
+            total: format!("{:.4}", account.total),
+            locked: account.locked,
+            lock_reason: account.lock_reason.as_ref().map(ToString::to_string),
+        }
+    }
+}
+
+/// A stored deposit or withdrawal
+#[derive(SimpleObject)]
+#[graphql(name = "Transaction")]
+struct TransactionNode {
+    tx: TransactionId,
+    client: ClientId,
+    #[graphql(name = "type")]
+    tx_type: TransactionTypeValue,
+    amount: String,
+    dispute_state: DisputeStateValue,
+    /// Number of times the transaction was disputed
+    disputes: u32,
+}
+
+impl TransactionNode {
+    fn new(tx: TransactionId, stored: &StoredTransaction) -> Self {
+        Self {
+            tx,
+            client: stored.client,
+            tx_type: stored.tx_type.into(),
+            amount: format!("{:.4}", stored.amount),
+            dispute_state: stored.dispute_state.into(),
+            disputes: stored.disputes,
+        }
+    }
+}
+
+/// Root of the queries
+pub struct QueryRoot {
+    snapshot: Arc<EngineSnapshot>,
+}
+
+#[Object]
+impl QueryRoot {
+    /// The account of a client
+    async fn account(&self, client: ClientId) -> Option<AccountNode> {
+        self.snapshot.account(client).map(AccountNode::from)
+    }
+
+    /// Accounts in client order, optionally only the given clients or only
+    /// locked or unlocked ones
+    async fn accounts(
+        &self,
+        clients: Option<Vec<ClientId>>,
+        locked: Option<bool>,
+    ) -> Vec<AccountNode> {
+        self.snapshot
+            .accounts
+            .iter()
+            .filter(|account| clients.as_ref().is_none_or(|c| c.contains(&account.client)))
+            .filter(|account| locked.is_none_or(|locked| account.locked == locked))
+            .map(AccountNode::from)
+            .collect()
+    }
+
+    /// A stored transaction
+    async fn transaction(&self, tx: TransactionId) -> Option<TransactionNode> {
+        self.snapshot
+            .transaction(tx)
+            .map(|stored| TransactionNode::new(tx, stored))
+    }
+
+    /// Stored transactions in transaction ID order, optionally only those of
+    /// a client, of a type or in a dispute state
+    async fn transactions(
+        &self,
+        client: Option<ClientId>,
+        #[graphql(name = "type")] tx_type: Option<TransactionTypeValue>,
+        dispute_state: Option<DisputeStateValue>,
+    ) -> Vec<TransactionNode> {
+        self.snapshot
+            .transactions
+            .iter()
+            .filter(|(_, stored)| client.is_none_or(|client| stored.client == client))
+            .filter(|(_, stored)| tx_type.is_none_or(|t| stored.tx_type == t.into()))
+            .filter(|(_, stored)| {
+                dispute_state.is_none_or(|state| stored.dispute_state == state.into())
+            })
+            .map(|(tx, stored)| TransactionNode::new(*tx, stored))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Engine, TransactionEngine};
+    use crate::types::{TransactionRecord, TransactionType};
+    use rust_decimal::Decimal;
+    use serde_json::json;
+
+    fn snapshot() -> EngineSnapshot {
+        let mut engine = TransactionEngine::new();
+        for (tx_type, client, tx, amount) in [
+            (TransactionType::Deposit, 1, 1, Some(Decimal::new(15, 1))),
+            (TransactionType::Deposit, 2, 2, Some(Decimal::TEN)),
+            (TransactionType::Withdrawal, 2, 3, Some(Decimal::ONE)),
+            (TransactionType::Deposit, 2, 4, Some(Decimal::from(5))),
+            (TransactionType::Dispute, 2, 4, None),
+            (TransactionType::Chargeback, 2, 4, None),
+        ] {
+            engine
+                .process_transaction(TransactionRecord {
+                    tx_type,
+                    client,
+                    tx,
+                    amount,
+                    line: None,
+                    source: None,
+                })
+                .unwrap();
+        }
+        engine.snapshot()
+    }
+
+    async fn query(query: &str) -> serde_json::Value {
+        let response = schema(snapshot()).execute(query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_query_accounts() {
+        assert_eq!(
+            query("{ accounts(locked: true) { client available total lockReason } }").await,
+            json!({
+                "accounts": [
+                    { "client": 2, "available": "9.0000", "total": "9.0000", "lockReason": "chargeback:4" }
+                ]
+            })
+        );
+        assert_eq!(
+            query("{ accounts(clients: [1, 3]) { client } account(client: 3) { client } }").await,
+            json!({ "accounts": [{ "client": 1 }], "account": null })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_transactions() {
+        assert_eq!(
+            query("{ transactions(client: 2) { tx type amount disputeState disputes } }").await,
+            json!({
+                "transactions": [
+                    { "tx": 2, "type": "DEPOSIT", "amount": "10.0000", "disputeState": "NONE", "disputes": 0 },
+                    { "tx": 3, "type": "WITHDRAWAL", "amount": "1.0000", "disputeState": "NONE", "disputes": 0 },
+                    { "tx": 4, "type": "DEPOSIT", "amount": "5.0000", "disputeState": "CHARGED_BACK", "disputes": 1 }
+                ]
+            })
+        );
+        assert_eq!(
+            query("{ transactions(type: DEPOSIT, disputeState: NONE) { tx } transaction(tx: 9) { tx } }")
+                .await,
+            json!({ "transactions": [{ "tx": 1 }, { "tx": 2 }], "transaction": null })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve() {
+        use std::io::{Read, Write};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(schema(snapshot()), listener));
+
+        let body = r#"{"query":"{ account(client: 1) { available } }"}"#;
+        let request = format!(
+            "POST /graphql HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            addr,
+            body.len(),
+            body
+        );
+        let response = tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        })
+        .await
+        .unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(
+            response.ends_with(r#"{"data":{"account":{"available":"1.5000"}}}"#),
+            "{}",
+            response
+        );
+    }
+}
@@ -16,14 +16,14 @@
 //! CSV Reader → AsyncReader → Batches of TransactionRecords
 //!                  ↓
 //!           csv_format module
-//!           (CsvRecord, convert_csv_record)
+//!           (CsvColumns, CsvDialect)
 //! ```
 
-use crate::io::csv_format::{convert_csv_record, CsvRecord};
-use crate::types::TransactionRecord;
-use csv_async::AsyncReaderBuilder;
+use crate::io::csv_format::{CsvColumns, CsvDialect};
+use crate::io::csv_schema::validate_header;
+use crate::io::record_source::RecordBatch;
+use csv_async::{AsyncReaderBuilder, ByteRecord, Position, StringRecord};
 use futures::io::AsyncRead;
-use futures::stream::StreamExt;
 
 /// Asynchronous CSV reader
 ///
@@ -31,6 +31,15 @@ use futures::stream::StreamExt;
 /// Maintains streaming behavior with constant memory usage.
 pub struct AsyncReader<R: AsyncRead + Unpin> {
     csv_reader: csv_async::AsyncDeserializer<R>,
+    dialect: CsvDialect,
+    /// Outcome of reading and checking the header, once it has been read
+    header: Option<Result<(), String>>,
+    /// Positions of the schema columns in the header
+    columns: CsvColumns,
+    /// Buffer for the current row, reused for every record
+    record: ByteRecord,
+    /// Number of records skipped so far because they failed to parse or convert
+    error_count: u64,
 }
 
 impl<R: AsyncRead + Unpin + Send + 'static> AsyncReader<R> {
@@ -44,50 +53,143 @@ impl<R: AsyncRead + Unpin + Send + 'static> AsyncReader<R> {
     ///
     /// A new AsyncReader instance
     pub fn new(reader: R) -> Self {
+        Self::with_dialect(reader, CsvDialect::default())
+    }
+
+    /// Create a new AsyncReader over CSV data written in the given dialect
+    ///
+    /// Header names with an alias in the dialect are renamed to their standard
+    /// column when the header is read (see `read_header`).
+    pub fn with_dialect(reader: R, dialect: CsvDialect) -> Self {
         let csv_reader = AsyncReaderBuilder::new()
+            .delimiter(dialect.delimiter)
             .flexible(true)
             .trim(csv_async::Trim::All)
             .create_deserializer(reader);
 
-        Self { csv_reader }
+        Self {
+            csv_reader,
+            header: None,
+            dialect,
+            columns: CsvColumns::default(),
+            record: ByteRecord::new(),
+            error_count: 0,
+        }
+    }
+
+    /// Number of records skipped so far because they failed to parse or convert
+    ///
+    /// Skipped records are returned as failures by `read_batch`, which this
+    /// count adds up.
+    pub fn error_count(&self) -> u64 {
+        self.error_count
+    }
+
+    /// Read the header and check it against the schema (see `csv_schema`)
+    ///
+    /// Header names with an alias in the dialect are renamed to their standard
+    /// column first. The header is read only once; later calls return `Ok`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the header is valid
+    /// * `Err(String)` if the header cannot be read or does not match the schema
+    pub async fn read_header(&mut self) -> Result<(), String> {
+        if let Some(result) = &self.header {
+            return result.clone();
+        }
+
+        let result = match self.csv_reader.headers().await {
+            Ok(headers) => {
+                let headers: StringRecord = headers
+                    .iter()
+                    .map(|header| self.dialect.column_name(header))
+                    .collect();
+                let result = validate_header(&headers)
+                    .and_then(|()| CsvColumns::locate(headers.iter()))
+                    .map(|columns| self.columns = columns);
+                self.csv_reader.set_headers(headers);
+                result
+            }
+            Err(e) => Err(format!("Failed to read CSV header: {}", e)),
+        };
+        self.header = Some(result.clone());
+        result
     }
 
     /// Read a batch of transaction records
     ///
     /// This method reads up to `batch_size` records from the CSV file,
-    /// converting them to TransactionRecords. Invalid records are logged
-    /// to stderr and skipped.
+    /// converting them to TransactionRecords. Records that fail to parse or
+    /// convert are skipped and returned as failures, prefixed with their line
+    /// number like the errors of `SyncReader`, and counted (see
+    /// `error_count`); failures count towards the batch size.
+    ///
+    /// If `read_header` was not called, the header is read with the first
+    /// batch; an invalid header is the only failure of that batch and ends the
+    /// input.
     ///
     /// # Arguments
     ///
-    /// * `batch_size` - Maximum number of records to read
+    /// * `batch_size` - Maximum number of records and failures to read
     ///
     /// # Returns
     ///
-    /// A vector of successfully converted transaction records.
-    /// Returns an empty vector when the end of the file is reached.
-    pub async fn read_batch(&mut self, batch_size: usize) -> Vec<TransactionRecord> {
-        let mut batch = Vec::with_capacity(batch_size);
-        let mut records = self.csv_reader.deserialize::<CsvRecord>();
+    /// The successfully converted records and the failures, in input order
+    /// within each. Fewer than `batch_size` of them are only returned at the
+    /// end of the file, and neither is returned once the end was reached.
+    pub async fn read_batch(&mut self, batch_size: usize) -> RecordBatch {
+        let first_read = self.header.is_none();
+        if let Err(e) = self.read_header().await {
+            let failures = if first_read { vec![e] } else { Vec::new() };
+            return (Vec::new(), failures);
+        }
 
-        while batch.len() < batch_size {
-            match records.next().await {
-                Some(Ok(csv_record)) => match convert_csv_record(csv_record) {
-                    Ok(transaction_record) => batch.push(transaction_record),
-                    Err(e) => eprintln!("Record conversion error: {}", e),
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut failures = Vec::new();
+        while batch.len() + failures.len() < batch_size {
+            match self.csv_reader.read_byte_record(&mut self.record).await {
+                Ok(true) => match self
+                    .dialect
+                    .parse_fields(&self.columns, |position| self.record.get(position))
+                {
+                    Ok(transaction_record) => batch.push(match self.record.position() {
+                        Some(position) => transaction_record.with_line(position.line()),
+                        None => transaction_record,
+                    }),
+                    Err(e) => {
+                        failures.push(with_line(self.record.position(), e));
+                        self.error_count += 1;
+                    }
                 },
-                Some(Err(e)) => eprintln!("CSV parse error: {}", e),
-                None => break,
+                Err(e) => {
+                    let position = e.position().cloned();
+                    failures.push(with_line(
+                        position.as_ref(),
+                        format!("CSV parse error: {}", e),
+                    ));
+                    self.error_count += 1;
+                }
+                Ok(false) => break,
             }
         }
 
-        batch
+        (batch, failures)
+    }
+}
+
+/// Prefix an error with the line of its record, if known
+fn with_line(position: Option<&Position>, error: String) -> String {
+    match position {
+        Some(position) => format!("Line {}: {}", position.line(), error),
+        None => error,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::csv_format::DecimalSeparator;
     use futures::io::Cursor;
     use rust_decimal::Decimal;
 
@@ -98,14 +200,17 @@ mod tests {
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch = async_reader.read_batch(2).await;
+        let (batch, failures) = async_reader.read_batch(2).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 2);
         assert_eq!(batch[0].client, 1);
         assert_eq!(batch[0].tx, 1);
         assert_eq!(batch[1].client, 1);
         assert_eq!(batch[1].tx, 2);
+        assert_eq!(batch[1].line, Some(3));
 
-        let batch = async_reader.read_batch(2).await;
+        let (batch, failures) = async_reader.read_batch(2).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 1);
         assert_eq!(batch[0].client, 2);
         assert_eq!(batch[0].tx, 3);
@@ -117,7 +222,8 @@ mod tests {
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch = async_reader.read_batch(10).await;
+        let (batch, failures) = async_reader.read_batch(10).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 0);
     }
 
@@ -129,10 +235,35 @@ mod tests {
 
         // First record should fail conversion (invalid type)
         // Second record should succeed
-        let batch = async_reader.read_batch(10).await;
-        // Only the valid record should be in the batch (invalid one is logged to stderr)
+        let (batch, failures) = async_reader.read_batch(10).await;
+        // Only the valid record should be in the batch, the invalid one is a failure
         assert_eq!(batch.len(), 1);
         assert_eq!(batch[0].tx, 2);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("Line 2: "), "{}", failures[0]);
+        assert_eq!(async_reader.error_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_failures_count_towards_batch_size() {
+        let csv_content = "type,client,tx,amount\n\
+            deposit,x,1,1.0\n\
+            deposit,1\n\
+            deposit,1,3,1.0\n";
+        let mut async_reader = AsyncReader::new(Cursor::new(csv_content.as_bytes()));
+
+        // A batch of failures only is not the end of the input
+        let (batch, failures) = async_reader.read_batch(2).await;
+        assert!(batch.is_empty());
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].starts_with("Line 2: "), "{}", failures[0]);
+        assert!(failures[1].starts_with("Line 3: "), "{}", failures[1]);
+
+        let (batch, failures) = async_reader.read_batch(2).await;
+        assert_eq!(batch.len(), 1);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(async_reader.read_batch(2).await, (Vec::new(), Vec::new()));
+        assert_eq!(async_reader.error_count(), 2);
     }
 
     #[tokio::test]
@@ -141,7 +272,8 @@ mod tests {
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch = async_reader.read_batch(10).await;
+        let (batch, failures) = async_reader.read_batch(10).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 2);
         assert_eq!(batch[0].amount, Some(Decimal::new(1000, 1)));
         assert_eq!(batch[1].amount, None);
@@ -153,7 +285,8 @@ mod tests {
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch = async_reader.read_batch(100).await;
+        let (batch, failures) = async_reader.read_batch(100).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 1);
     }
 
@@ -168,21 +301,25 @@ mod tests {
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch1 = async_reader.read_batch(2).await;
+        let (batch1, failures) = async_reader.read_batch(2).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch1.len(), 2);
         assert_eq!(batch1[0].tx, 1);
         assert_eq!(batch1[1].tx, 2);
 
-        let batch2 = async_reader.read_batch(2).await;
+        let (batch2, failures) = async_reader.read_batch(2).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch2.len(), 2);
         assert_eq!(batch2[0].tx, 3);
         assert_eq!(batch2[1].tx, 4);
 
-        let batch3 = async_reader.read_batch(2).await;
+        let (batch3, failures) = async_reader.read_batch(2).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch3.len(), 1);
         assert_eq!(batch3[0].tx, 5);
 
-        let batch4 = async_reader.read_batch(2).await;
+        let (batch4, failures) = async_reader.read_batch(2).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch4.len(), 0);
     }
 
@@ -192,19 +329,72 @@ mod tests {
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch = async_reader.read_batch(10).await;
+        let (batch, failures) = async_reader.read_batch(10).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 1);
         assert_eq!(batch[0].client, 1);
         assert_eq!(batch[0].tx, 1);
     }
 
+    #[tokio::test]
+    async fn test_async_reader_with_dialect() {
+        let csv_content =
+            "type;client;transaction_id;amount\ndeposit;1;7;100,5\nwithdrawal;1;8;0,5\n";
+        let dialect = CsvDialect::default()
+            .with_delimiter(b';')
+            .with_decimal_separator(DecimalSeparator::Comma)
+            .with_header_alias("transaction_id=tx".parse().unwrap());
+        let mut async_reader =
+            AsyncReader::with_dialect(Cursor::new(csv_content.as_bytes()), dialect);
+
+        let (batch, failures) = async_reader.read_batch(10).await;
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].tx, 7);
+        assert_eq!(batch[0].amount, Some(Decimal::new(1005, 1)));
+        assert_eq!(batch[1].amount, Some(Decimal::new(5, 1)));
+        assert_eq!(async_reader.error_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_rejects_invalid_header() {
+        let csv_content = "type,clinet,tx,amount\ndeposit,1,1,100.0\n";
+        let mut async_reader = AsyncReader::new(Cursor::new(csv_content.as_bytes()));
+
+        let err = async_reader.read_header().await.unwrap_err();
+        assert!(
+            err.contains("unknown column 'clinet' (did you mean 'client'?)"),
+            "{err}"
+        );
+        assert_eq!(async_reader.read_batch(10).await, (Vec::new(), Vec::new()));
+        assert_eq!(async_reader.read_batch(10).await, (Vec::new(), Vec::new()));
+        assert_eq!(async_reader.error_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_reports_invalid_header_with_first_batch() {
+        let csv_content = "type,clinet,tx,amount\ndeposit,1,1,100.0\n";
+        let mut async_reader = AsyncReader::new(Cursor::new(csv_content.as_bytes()));
+
+        let (batch, failures) = async_reader.read_batch(10).await;
+        assert!(batch.is_empty());
+        assert_eq!(failures.len(), 1);
+        assert!(
+            failures[0].contains("unknown column 'clinet'"),
+            "{}",
+            failures[0]
+        );
+        assert_eq!(async_reader.read_batch(10).await, (Vec::new(), Vec::new()));
+    }
+
     #[tokio::test]
     async fn test_async_reader_case_insensitive_type() {
         let csv_content = "type,client,tx,amount\nDEPOSIT,1,1,100.0\nWithdrawal,1,2,50.0\n";
         let reader = Cursor::new(csv_content.as_bytes());
         let mut async_reader = AsyncReader::new(reader);
 
-        let batch = async_reader.read_batch(10).await;
+        let (batch, failures) = async_reader.read_batch(10).await;
+        assert!(failures.is_empty(), "{:?}", failures);
         assert_eq!(batch.len(), 2);
     }
 }
@@ -0,0 +1,898 @@
+//! Avro object container file reader
+//!
+//! Provides a streaming iterator over transaction records stored in an Avro
+//! object container file, as exported by the data lake. Avro stores amounts as
+//! exact decimals, so no precision is lost on the way into the engine.
+//!
+//! # Schema
+//!
+//! The writer schema embedded in the file must be a record with these fields
+//! (in any order; additional fields of primitive, enum or fixed type are skipped):
+//!
+//! ```json
+//! {
+//!   "type": "record",
+//!   "name": "Transaction",
+//!   "fields": [
+//!     {"name": "type",   "type": "string"},
+//!     {"name": "client", "type": "int"},
+//!     {"name": "tx",     "type": "long"},
+//!     {"name": "amount", "type": ["null",
+//!         {"type": "bytes", "logicalType": "decimal", "precision": 38, "scale": 4}]}
+//!   ]
+//! }
+//! ```
+//!
+//! - `type` may be a `string` or an `enum` whose symbols are transaction types
+//! - `client` and `tx` may be `int` or `long`; values must fit `u16` and `u32`
+//! - `amount` may be a `decimal` (`bytes` or `fixed`) or a `string`, optionally
+//!   in a union with `null`; the field itself may be omitted
+//!
+//! The `null` and `deflate` codecs are supported.
+//!
+//! # Error Handling
+//!
+//! - Fatal errors (file not found, bad header, unsupported schema or codec) are
+//!   returned from `new()`
+//! - Records with invalid values are yielded as Err variants and iteration continues
+//! - Structural corruption (truncated block, bad sync marker) is yielded as a
+//!   final Err variant, after which the iterator is exhausted
+//! - Record numbers (1-based) are included in error messages for debugging
+
+use crate::io::csv_format::{convert_csv_record, CsvRecord};
+use crate::types::{ClientId, TransactionId, TransactionRecord};
+use rust_decimal::Decimal;
+use serde_json::Value as JsonValue;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+/// Magic bytes at the start of every Avro object container file
+const MAGIC: [u8; 4] = [b'O', b'b', b'j', 1];
+
+/// Length of the sync marker separating data blocks
+const SYNC_MARKER_LEN: usize = 16;
+
+/// Block compression codec
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Null,
+    Deflate,
+}
+
+/// Subset of the Avro type system supported by the reader
+#[derive(Debug, Clone, PartialEq)]
+enum AvroType {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Enum(Vec<String>),
+    Fixed(usize),
+    Decimal { scale: u32, size: Option<usize> },
+    Union(Vec<AvroType>),
+}
+
+/// Decoded Avro value
+#[derive(Debug, Clone, PartialEq)]
+enum AvroValue {
+    Null,
+    Boolean(bool),
+    Long(i64),
+    Double(f64),
+    Bytes(Vec<u8>),
+    String(String),
+    Decimal(Decimal),
+}
+
+/// Avro object container file reader
+///
+/// Provides an iterator interface over transaction records.
+/// Decodes one block at a time, so memory usage is bounded by the block size.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rust_payments_engine::io::avro_reader::AvroReader;
+/// use std::path::Path;
+///
+/// let reader = AvroReader::open(Path::new("transactions.avro")).unwrap();
+/// let records: Vec<_> = reader.filter_map(Result::ok).collect();
+/// println!("Successfully decoded {} records", records.len());
+/// ```
+#[derive(Debug)]
+pub struct AvroReader<R: Read> {
+    reader: R,
+    fields: Vec<(String, AvroType)>,
+    codec: Codec,
+    sync_marker: [u8; SYNC_MARKER_LEN],
+    block: Cursor<Vec<u8>>,
+    remaining_in_block: u64,
+    record_num: usize,
+    finished: bool,
+}
+
+impl AvroReader<BufReader<File>> {
+    /// Open an Avro object container file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the Avro file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AvroReader)` if the file was opened and its header is valid
+    /// * `Err(String)` if the file could not be opened or has an unsupported header
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open file '{}': {}", path.display(), e))?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> AvroReader<R> {
+    /// Create a new AvroReader and read the container header
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the Avro object container file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(AvroReader)` if the header, schema and codec are supported
+    /// * `Err(String)` describing the problem otherwise
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let mut magic = [0u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| format!("Failed to read Avro header: {}", e))?;
+        if magic != MAGIC {
+            return Err("Not an Avro object container file (bad magic bytes)".to_string());
+        }
+
+        let mut schema = None;
+        let mut codec = Codec::Null;
+        loop {
+            let count = read_long(&mut reader)
+                .map_err(|e| format!("Invalid Avro header metadata: {}", e))?;
+            if count == 0 {
+                break;
+            }
+            if count < 0 {
+                // Negative counts are followed by the block size in bytes
+                read_long(&mut reader)
+                    .map_err(|e| format!("Invalid Avro header metadata: {}", e))?;
+            }
+            for _ in 0..count.unsigned_abs() {
+                let key = read_string(&mut reader)
+                    .map_err(|e| format!("Invalid Avro header metadata: {}", e))?;
+                let value = read_bytes(&mut reader)
+                    .map_err(|e| format!("Invalid Avro header metadata: {}", e))?;
+                match key.as_str() {
+                    "avro.schema" => schema = Some(value),
+                    "avro.codec" => codec = parse_codec(&value)?,
+                    _ => {}
+                }
+            }
+        }
+
+        let schema = schema.ok_or("Avro header does not contain a schema")?;
+        let fields = parse_schema(&schema)?;
+
+        let mut sync_marker = [0u8; SYNC_MARKER_LEN];
+        reader
+            .read_exact(&mut sync_marker)
+            .map_err(|e| format!("Failed to read Avro sync marker: {}", e))?;
+
+        Ok(Self {
+            reader,
+            fields,
+            codec,
+            sync_marker,
+            block: Cursor::new(Vec::new()),
+            remaining_in_block: 0,
+            record_num: 0,
+            finished: false,
+        })
+    }
+
+    /// Load the next non-empty data block
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` if a block with at least one record was loaded
+    /// * `Ok(false)` at end of file
+    /// * `Err(String)` if the block is corrupt
+    fn load_block(&mut self) -> Result<bool, String> {
+        loop {
+            let count = match read_long_or_eof(&mut self.reader)? {
+                Some(count) => count,
+                None => return Ok(false),
+            };
+            let size = read_long(&mut self.reader)?;
+            if count < 0 || size < 0 {
+                return Err(format!(
+                    "Invalid block header (count {}, size {})",
+                    count, size
+                ));
+            }
+
+            let data = read_fixed(&mut self.reader, size as usize)
+                .map_err(|e| format!("Truncated data block: {}", e))?;
+
+            let mut marker = [0u8; SYNC_MARKER_LEN];
+            self.reader
+                .read_exact(&mut marker)
+                .map_err(|e| format!("Truncated data block: {}", e))?;
+            if marker != self.sync_marker {
+                return Err("Sync marker mismatch, file is corrupt".to_string());
+            }
+
+            let data = match self.codec {
+                Codec::Null => data,
+                Codec::Deflate => {
+                    let mut decompressed = Vec::new();
+                    flate2::read::DeflateDecoder::new(data.as_slice())
+                        .read_to_end(&mut decompressed)
+                        .map_err(|e| format!("Failed to decompress data block: {}", e))?;
+                    decompressed
+                }
+            };
+
+            if count > 0 {
+                self.block = Cursor::new(data);
+                self.remaining_in_block = count as u64;
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Decode the next record of the current block into a CsvRecord
+    ///
+    /// The outer error is structural (the block can no longer be decoded);
+    /// the inner error is specific to this record.
+    fn decode_record(&mut self) -> Result<Result<CsvRecord, String>, String> {
+        let mut tx_type = None;
+        let mut client = None;
+        let mut tx = None;
+        let mut amount = None;
+
+        for (name, avro_type) in &self.fields {
+            let value = decode_value(&mut self.block, avro_type)?;
+            match name.as_str() {
+                "type" => tx_type = Some(value),
+                "client" => client = Some(value),
+                "tx" => tx = Some(value),
+                "amount" => amount = Some(value),
+                _ => {}
+            }
+        }
+
+        Ok(build_csv_record(tx_type, client, tx, amount))
+    }
+}
+
+impl<R: Read> Iterator for AvroReader<R> {
+    type Item = Result<TransactionRecord, String>;
+
+    /// Get the next transaction record from the Avro file
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Ok(TransactionRecord))` - Successfully decoded record
+    /// * `Some(Err(String))` - Error decoding this record (with record number)
+    /// * `None` - End of file reached, or the file is corrupt past this point
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        if self.remaining_in_block == 0 {
+            match self.load_block() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(format!("Record {}: {}", self.record_num + 1, e)));
+                }
+            }
+        }
+
+        self.remaining_in_block -= 1;
+        self.record_num += 1;
+
+        match self.decode_record() {
+            Ok(record) => Some(
+                record
+                    .and_then(convert_csv_record)
+                    .map_err(|e| format!("Record {}: {}", self.record_num, e)),
+            ),
+            Err(e) => {
+                self.finished = true;
+                Some(Err(format!("Record {}: {}", self.record_num, e)))
+            }
+        }
+    }
+}
+
+/// Parse the `avro.codec` metadata value
+fn parse_codec(value: &[u8]) -> Result<Codec, String> {
+    match value {
+        b"null" => Ok(Codec::Null),
+        b"deflate" => Ok(Codec::Deflate),
+        other => Err(format!(
+            "Unsupported Avro codec '{}' (supported: null, deflate)",
+            String::from_utf8_lossy(other)
+        )),
+    }
+}
+
+/// Parse and validate the writer schema
+///
+/// # Returns
+///
+/// The record fields in declaration order
+fn parse_schema(schema: &[u8]) -> Result<Vec<(String, AvroType)>, String> {
+    let json: JsonValue =
+        serde_json::from_slice(schema).map_err(|e| format!("Invalid Avro schema: {}", e))?;
+
+    if json.get("type").and_then(JsonValue::as_str) != Some("record") {
+        return Err("Avro schema must be a record".to_string());
+    }
+
+    let fields = json
+        .get("fields")
+        .and_then(JsonValue::as_array)
+        .ok_or("Avro schema record has no fields")?
+        .iter()
+        .map(|field| {
+            let name = field
+                .get("name")
+                .and_then(JsonValue::as_str)
+                .ok_or("Avro schema field has no name")?;
+            let field_type = field
+                .get("type")
+                .ok_or_else(|| format!("Avro schema field '{}' has no type", name))?;
+            let avro_type = parse_type(field_type)
+                .map_err(|e| format!("Avro schema field '{}': {}", name, e))?;
+            Ok((name.to_string(), avro_type))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let field_type = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, avro_type)| avro_type)
+    };
+
+    for name in ["type", "client", "tx"] {
+        if field_type(name).is_none() {
+            return Err(format!("Avro schema is missing required field '{}'", name));
+        }
+    }
+
+    let check = |name: &str, allowed: fn(&AvroType) -> bool| match field_type(name) {
+        Some(avro_type) if !accepts(avro_type, allowed) => Err(format!(
+            "Avro schema field '{}' has unsupported type {:?}",
+            name, avro_type
+        )),
+        _ => Ok(()),
+    };
+    check("type", |t| {
+        matches!(t, AvroType::String | AvroType::Enum(_))
+    })?;
+    check("client", |t| matches!(t, AvroType::Int | AvroType::Long))?;
+    check("tx", |t| matches!(t, AvroType::Int | AvroType::Long))?;
+    check("amount", |t| {
+        matches!(t, AvroType::String | AvroType::Decimal { .. })
+    })?;
+
+    Ok(fields)
+}
+
+/// Whether a field type is `allowed`, optionally in a union with null
+fn accepts(avro_type: &AvroType, allowed: fn(&AvroType) -> bool) -> bool {
+    match avro_type {
+        AvroType::Union(variants) => variants
+            .iter()
+            .all(|variant| *variant == AvroType::Null || allowed(variant)),
+        other => allowed(other),
+    }
+}
+
+/// Parse a schema type definition
+fn parse_type(json: &JsonValue) -> Result<AvroType, String> {
+    match json {
+        JsonValue::String(name) => parse_named_type(name),
+        JsonValue::Array(variants) => variants
+            .iter()
+            .map(parse_type)
+            .collect::<Result<Vec<_>, _>>()
+            .map(AvroType::Union),
+        JsonValue::Object(object) => {
+            let type_name = object
+                .get("type")
+                .and_then(JsonValue::as_str)
+                .ok_or("type definition has no type name")?;
+            let logical_type = object.get("logicalType").and_then(JsonValue::as_str);
+
+            let size = || {
+                object
+                    .get("size")
+                    .and_then(JsonValue::as_u64)
+                    .map(|size| size as usize)
+                    .ok_or("fixed type has no size")
+            };
+
+            match (type_name, logical_type) {
+                ("bytes", Some("decimal")) | ("fixed", Some("decimal")) => {
+                    let scale = object.get("scale").and_then(JsonValue::as_u64).unwrap_or(0);
+                    if scale > 28 {
+                        return Err(format!("decimal scale {} exceeds maximum of 28", scale));
+                    }
+                    let size = if type_name == "fixed" {
+                        Some(size()?)
+                    } else {
+                        None
+                    };
+                    Ok(AvroType::Decimal {
+                        scale: scale as u32,
+                        size,
+                    })
+                }
+                ("enum", _) => {
+                    let symbols = object
+                        .get("symbols")
+                        .and_then(JsonValue::as_array)
+                        .ok_or("enum type has no symbols")?
+                        .iter()
+                        .map(|symbol| symbol.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or("enum symbols must be strings")?;
+                    Ok(AvroType::Enum(symbols))
+                }
+                ("fixed", _) => Ok(AvroType::Fixed(size()?)),
+                (name, _) => parse_named_type(name),
+            }
+        }
+        other => Err(format!("invalid type definition {}", other)),
+    }
+}
+
+/// Parse a primitive type name
+fn parse_named_type(name: &str) -> Result<AvroType, String> {
+    match name {
+        "null" => Ok(AvroType::Null),
+        "boolean" => Ok(AvroType::Boolean),
+        "int" => Ok(AvroType::Int),
+        "long" => Ok(AvroType::Long),
+        "float" => Ok(AvroType::Float),
+        "double" => Ok(AvroType::Double),
+        "bytes" => Ok(AvroType::Bytes),
+        "string" => Ok(AvroType::String),
+        other => Err(format!("unsupported type '{}'", other)),
+    }
+}
+
+/// Decode a single value of the given type
+fn decode_value(reader: &mut impl Read, avro_type: &AvroType) -> Result<AvroValue, String> {
+    match avro_type {
+        AvroType::Null => Ok(AvroValue::Null),
+        AvroType::Boolean => {
+            let mut byte = [0u8; 1];
+            reader
+                .read_exact(&mut byte)
+                .map_err(|e| format!("Truncated record: {}", e))?;
+            Ok(AvroValue::Boolean(byte[0] != 0))
+        }
+        AvroType::Int => {
+            let value = read_long(reader)?;
+            i32::try_from(value).map_err(|_| format!("Int value {} out of range", value))?;
+            Ok(AvroValue::Long(value))
+        }
+        AvroType::Long => Ok(AvroValue::Long(read_long(reader)?)),
+        AvroType::Float => {
+            let mut bytes = [0u8; 4];
+            reader
+                .read_exact(&mut bytes)
+                .map_err(|e| format!("Truncated record: {}", e))?;
+            Ok(AvroValue::Double(f32::from_le_bytes(bytes) as f64))
+        }
+        AvroType::Double => {
+            let mut bytes = [0u8; 8];
+            reader
+                .read_exact(&mut bytes)
+                .map_err(|e| format!("Truncated record: {}", e))?;
+            Ok(AvroValue::Double(f64::from_le_bytes(bytes)))
+        }
+        AvroType::Bytes => Ok(AvroValue::Bytes(read_bytes(reader)?)),
+        AvroType::String => Ok(AvroValue::String(read_string(reader)?)),
+        AvroType::Enum(symbols) => {
+            let index = read_long(reader)?;
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| symbols.get(index))
+                .map(|symbol| AvroValue::String(symbol.clone()))
+                .ok_or_else(|| format!("Enum index {} out of range", index))
+        }
+        AvroType::Fixed(size) => Ok(AvroValue::Bytes(read_fixed(reader, *size)?)),
+        AvroType::Decimal { scale, size } => {
+            let bytes = match size {
+                Some(size) => read_fixed(reader, *size)?,
+                None => read_bytes(reader)?,
+            };
+            decode_decimal(&bytes, *scale).map(AvroValue::Decimal)
+        }
+        AvroType::Union(variants) => {
+            let index = read_long(reader)?;
+            let variant = usize::try_from(index)
+                .ok()
+                .and_then(|index| variants.get(index))
+                .ok_or_else(|| format!("Union index {} out of range", index))?;
+            decode_value(reader, variant)
+        }
+    }
+}
+
+/// Decode a big-endian two's complement unscaled decimal
+fn decode_decimal(bytes: &[u8], scale: u32) -> Result<Decimal, String> {
+    if bytes.len() > 16 {
+        return Err(format!("Decimal of {} bytes is too large", bytes.len()));
+    }
+
+    let negative = bytes.first().is_some_and(|byte| byte & 0x80 != 0);
+    let mut buffer = if negative { [0xFFu8; 16] } else { [0u8; 16] };
+    buffer[16 - bytes.len()..].copy_from_slice(bytes);
+    let unscaled = i128::from_be_bytes(buffer);
+
+    Decimal::try_from_i128_with_scale(unscaled, scale)
+        .map_err(|e| format!("Decimal value out of range: {}", e))
+}
+
+/// Map decoded field values onto a CsvRecord so both formats share validation
+fn build_csv_record(
+    tx_type: Option<AvroValue>,
+    client: Option<AvroValue>,
+    tx: Option<AvroValue>,
+    amount: Option<AvroValue>,
+) -> Result<CsvRecord, String> {
+    let tx_type = match tx_type {
+        Some(AvroValue::String(tx_type)) => tx_type,
+        _ => return Err("Missing transaction type".to_string()),
+    };
+    let client = match client {
+        Some(AvroValue::Long(client)) => {
+            ClientId::try_from(client).map_err(|_| format!("Client ID {} out of range", client))?
+        }
+        _ => return Err("Missing client ID".to_string()),
+    };
+    let tx = match tx {
+        Some(AvroValue::Long(tx)) => TransactionId::try_from(tx)
+            .map_err(|_| format!("Transaction ID {} out of range", tx))?,
+        _ => return Err("Missing transaction ID".to_string()),
+    };
+    let amount = match amount {
+        Some(AvroValue::Decimal(amount)) => Some(amount.to_string()),
+        Some(AvroValue::String(amount)) => Some(amount),
+        _ => None,
+    };
+
+    Ok(CsvRecord {
+        tx_type,
+        client,
+        tx,
+        amount,
+    })
+}
+
+/// Read a zig-zag encoded variable-length long
+fn read_long(reader: &mut impl Read) -> Result<i64, String> {
+    read_long_or_eof(reader)?.ok_or_else(|| "Unexpected end of file".to_string())
+}
+
+/// Read a zig-zag encoded variable-length long, or `None` at a clean end of file
+fn read_long_or_eof(reader: &mut impl Read) -> Result<Option<i64>, String> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        let read = reader
+            .read(&mut byte)
+            .map_err(|e| format!("Failed to read: {}", e))?;
+        if read == 0 {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err("Unexpected end of file".to_string())
+            };
+        }
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some((value >> 1) as i64 ^ -((value & 1) as i64)));
+        }
+    }
+    Err("Variable-length integer is too long".to_string())
+}
+
+/// Read length-prefixed bytes
+fn read_bytes(reader: &mut impl Read) -> Result<Vec<u8>, String> {
+    let len = read_long(reader)?;
+    let len = usize::try_from(len).map_err(|_| format!("Invalid length {}", len))?;
+    read_fixed(reader, len)
+}
+
+/// Read exactly `len` bytes
+fn read_fixed(reader: &mut impl Read, len: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    reader
+        .take(len as u64)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read: {}", e))?;
+    if bytes.len() != len {
+        return Err("Unexpected end of file".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Read a length-prefixed UTF-8 string
+fn read_string(reader: &mut impl Read) -> Result<String, String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| "Invalid UTF-8 string".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TransactionType;
+    use rstest::rstest;
+    use std::io::Write;
+
+    const SYNC: [u8; SYNC_MARKER_LEN] = [7; SYNC_MARKER_LEN];
+
+    const SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Transaction",
+        "fields": [
+            {"name": "type", "type": "string"},
+            {"name": "client", "type": "int"},
+            {"name": "tx", "type": "long"},
+            {"name": "amount", "type": ["null",
+                {"type": "bytes", "logicalType": "decimal", "precision": 38, "scale": 4}]}
+        ]
+    }"#;
+
+    fn write_long(out: &mut Vec<u8>, value: i64) {
+        let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+        loop {
+            let byte = (zigzag & 0x7F) as u8;
+            zigzag >>= 7;
+            if zigzag == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+        write_long(out, bytes.len() as i64);
+        out.extend_from_slice(bytes);
+    }
+
+    fn write_decimal(out: &mut Vec<u8>, unscaled: i64) {
+        let bytes = unscaled.to_be_bytes();
+        write_bytes(out, &bytes);
+    }
+
+    /// Encode a record matching SCHEMA
+    fn encode(tx_type: &str, client: i64, tx: i64, amount: Option<i64>) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_bytes(&mut out, tx_type.as_bytes());
+        write_long(&mut out, client);
+        write_long(&mut out, tx);
+        match amount {
+            Some(unscaled) => {
+                write_long(&mut out, 1);
+                write_decimal(&mut out, unscaled);
+            }
+            None => write_long(&mut out, 0),
+        }
+        out
+    }
+
+    /// Build an object container file with one block per entry of `blocks`
+    fn container(schema: &str, codec: &str, blocks: &[Vec<Vec<u8>>]) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        write_long(&mut out, 2);
+        write_bytes(&mut out, b"avro.schema");
+        write_bytes(&mut out, schema.as_bytes());
+        write_bytes(&mut out, b"avro.codec");
+        write_bytes(&mut out, codec.as_bytes());
+        write_long(&mut out, 0);
+        out.extend_from_slice(&SYNC);
+
+        for records in blocks {
+            let data: Vec<u8> = records.concat();
+            let data = if codec == "deflate" {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&data).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                data
+            };
+            write_long(&mut out, records.len() as i64);
+            write_bytes(&mut out, &data);
+            out.extend_from_slice(&SYNC);
+        }
+        out
+    }
+
+    fn read_all(bytes: Vec<u8>) -> Vec<Result<TransactionRecord, String>> {
+        AvroReader::new(Cursor::new(bytes)).unwrap().collect()
+    }
+
+    #[rstest]
+    #[case::null_codec("null")]
+    #[case::deflate_codec("deflate")]
+    fn test_reads_records_across_blocks(#[case] codec: &str) {
+        let bytes = container(
+            SCHEMA,
+            codec,
+            &[
+                vec![
+                    encode("deposit", 1, 1, Some(1_000_000)),
+                    encode("withdrawal", 1, 2, Some(2_5001)),
+                ],
+                vec![],
+                vec![encode("dispute", 1, 1, None)],
+            ],
+        );
+
+        let records: Vec<TransactionRecord> =
+            read_all(bytes).into_iter().map(Result::unwrap).collect();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].tx_type, TransactionType::Deposit);
+        assert_eq!(records[0].amount, Some(Decimal::new(1_000_000, 4)));
+        assert_eq!(records[1].tx_type, TransactionType::Withdrawal);
+        assert_eq!(records[1].amount, Some(Decimal::new(2_5001, 4)));
+        assert_eq!(records[2].tx_type, TransactionType::Dispute);
+        assert_eq!(records[2].amount, None);
+    }
+
+    #[test]
+    fn test_preserves_decimal_precision() {
+        // 0.0001 survives exactly, which a float-based CSV export would not guarantee
+        let bytes = container(SCHEMA, "null", &[vec![encode("deposit", 1, 1, Some(1))]]);
+
+        let record = read_all(bytes).remove(0).unwrap();
+        assert_eq!(record.amount, Some(Decimal::new(1, 4)));
+    }
+
+    #[test]
+    fn test_invalid_records_do_not_stop_iteration() {
+        let bytes = container(
+            SCHEMA,
+            "null",
+            &[vec![
+                encode("deposit", -1, 1, Some(10_000)),
+                encode("refund", 1, 2, Some(10_000)),
+                encode("deposit", 1, 3, None),
+                encode("deposit", 1, 4, Some(10_000)),
+            ]],
+        );
+
+        let results = read_all(bytes);
+
+        assert_eq!(results.len(), 4);
+        assert!(results[0].as_ref().unwrap_err().contains("Record 1"));
+        assert!(results[0].as_ref().unwrap_err().contains("out of range"));
+        assert!(results[1].as_ref().unwrap_err().contains("Record 2"));
+        assert!(results[2]
+            .as_ref()
+            .unwrap_err()
+            .contains("requires an amount"));
+        assert_eq!(results[3].as_ref().unwrap().tx, 4);
+    }
+
+    #[test]
+    fn test_sync_marker_mismatch_stops_iteration() {
+        let mut bytes = container(
+            SCHEMA,
+            "null",
+            &[
+                vec![encode("deposit", 1, 1, Some(10_000))],
+                vec![encode("deposit", 1, 2, Some(10_000))],
+            ],
+        );
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+
+        let results = read_all(bytes);
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].as_ref().unwrap_err().contains("Sync marker"));
+    }
+
+    #[test]
+    fn test_alternative_field_types() {
+        let schema = r#"{
+            "type": "record",
+            "name": "Transaction",
+            "fields": [
+                {"name": "source", "type": "string"},
+                {"name": "type", "type": {"type": "enum", "name": "Kind",
+                    "symbols": ["deposit", "withdrawal"]}},
+                {"name": "client", "type": "long"},
+                {"name": "tx", "type": "int"},
+                {"name": "amount", "type": "string"}
+            ]
+        }"#;
+        let mut record = Vec::new();
+        write_bytes(&mut record, b"lake");
+        write_long(&mut record, 1);
+        write_long(&mut record, 5);
+        write_long(&mut record, 9);
+        write_bytes(&mut record, b"12.3456");
+        let bytes = container(schema, "null", &[vec![record]]);
+
+        let record = read_all(bytes).remove(0).unwrap();
+        assert_eq!(record.tx_type, TransactionType::Withdrawal);
+        assert_eq!(record.client, 5);
+        assert_eq!(record.tx, 9);
+        assert_eq!(record.amount, Some(Decimal::new(123456, 4)));
+    }
+
+    #[rstest]
+    #[case::negative(-12345, 2, "-123.45")]
+    #[case::zero(0, 4, "0.0000")]
+    #[case::positive(1, 4, "0.0001")]
+    fn test_decode_decimal(#[case] unscaled: i64, #[case] scale: u32, #[case] expected: &str) {
+        // Minimal-length encoding, as written by Avro libraries
+        let bytes = unscaled.to_be_bytes();
+        let skip = bytes
+            .iter()
+            .take(7)
+            .take_while(|&&b| (b == 0 && unscaled >= 0) || (b == 0xFF && unscaled < 0))
+            .count();
+        let decimal = decode_decimal(&bytes[skip..], scale).unwrap();
+        assert_eq!(decimal.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::bad_magic(b"PAR1".to_vec(), "bad magic")]
+    #[case::unsupported_codec(container(SCHEMA, "snappy", &[]), "Unsupported Avro codec")]
+    #[case::missing_field(
+        container(r#"{"type":"record","name":"T","fields":[{"name":"type","type":"string"}]}"#, "null", &[]),
+        "missing required field 'client'"
+    )]
+    #[case::float_amount(
+        container(
+            r#"{"type":"record","name":"T","fields":[{"name":"type","type":"string"},
+                {"name":"client","type":"int"},{"name":"tx","type":"long"},
+                {"name":"amount","type":"double"}]}"#,
+            "null",
+            &[]
+        ),
+        "field 'amount' has unsupported type"
+    )]
+    #[case::not_a_record(container(r#""string""#, "null", &[]), "must be a record")]
+    fn test_invalid_header(#[case] bytes: Vec<u8>, #[case] expected: &str) {
+        let err = AvroReader::new(Cursor::new(bytes)).unwrap_err();
+        assert!(err.contains(expected), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let err = AvroReader::open(Path::new("nonexistent.avro")).unwrap_err();
+        assert!(err.contains("Failed to open file"));
+    }
+}
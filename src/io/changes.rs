@@ -0,0 +1,288 @@
+//! Balance change streams
+//!
+//! With `--changes TARGET`, every balance change of a run is sent as a
+//! `BalanceChange` event with the client's balances before and after it, so
+//! downstream systems can maintain derived views of the accounts without
+//! re-reading the inputs. The target is one of:
+//!
+//! - a file path - JSON Lines, one event per line
+//! - `kafka+http://host:port/topics/TOPIC` (or `kafka+https://`) - events
+//!   produced to a Kafka topic through a Confluent REST Proxy, keyed by client
+//!   so a client's events stay in order (feature `dead-letter-http`)
+//!
+//! An event looks like:
+//!
+//! ```text
+//! {"seq":3,"tx":2,"type":"withdrawal","client":1,"before":{"available":"10","held":"0","total":"10"},"after":{"available":"8.5","held":"0","total":"8.5"}}
+//! ```
+//!
+//! The Kafka sink sends events in batches and retries transient failures with
+//! the default `RetryPolicy`. An event that cannot be delivered aborts the run.
+//! Events hold the clients as processed: they are not pseudonymized.
+
+use crate::core::BalanceChange;
+#[cfg(feature = "dead-letter-http")]
+use crate::core::RetryPolicy;
+use crate::io::dead_letter::is_http_url;
+#[cfg(feature = "dead-letter-http")]
+use crate::io::dead_letter::{names_kafka_topic, post_with_retry};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Destination of balance change events
+///
+/// Sinks may buffer events; `flush` delivers those buffered so far.
+pub trait ChangeSink: Send {
+    /// Send an event
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the event was delivered or buffered
+    /// * `Err(String)` - If it (or a batch it completed) cannot be delivered
+    fn send(&mut self, change: BalanceChange) -> Result<(), String>;
+
+    /// Deliver the events buffered so far
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Create the change sink for a target
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn ChangeSink>)` - The sink
+/// * `Err(String)` - If the file cannot be created, the Kafka target names no
+///   topic, or the target is remote and the `dead-letter-http` feature is off
+pub fn create_change_sink(target: &str) -> Result<Box<dyn ChangeSink>, String> {
+    if let Some(url) = target.strip_prefix("kafka+").filter(|url| is_http_url(url)) {
+        #[cfg(feature = "dead-letter-http")]
+        {
+            return Ok(Box::new(KafkaChangeSink::new(url, RetryPolicy::default())?));
+        }
+        #[cfg(not(feature = "dead-letter-http"))]
+        {
+            let _ = url;
+            return Err(format!(
+                "Change target '{}' requires building with the 'dead-letter-http' feature",
+                target
+            ));
+        }
+    }
+    Ok(Box::new(ChangeWriter::create(Path::new(target))?))
+}
+
+/// JSON Lines writer for balance change events
+pub struct ChangeWriter<W: Write> {
+    writer: BufWriter<W>,
+}
+
+impl ChangeWriter<File> {
+    /// Create (or truncate) a change file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ChangeWriter)` - The writer
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create change file '{}': {}", path.display(), e))?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write> ChangeWriter<W> {
+    /// Create a change writer over any output
+    pub fn new(output: W) -> Self {
+        Self {
+            writer: BufWriter::new(output),
+        }
+    }
+}
+
+impl<W: Write + Send> ChangeSink for ChangeWriter<W> {
+    fn send(&mut self, change: BalanceChange) -> Result<(), String> {
+        serde_json::to_writer(&mut self.writer, &change)
+            .map_err(|e| format!("Failed to write change: {}", e))?;
+        self.writer
+            .write_all(b"\n")
+            .map_err(|e| format!("Failed to write change: {}", e))
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush change file: {}", e))
+    }
+}
+
+/// Number of events the Kafka sink sends per request
+#[cfg(feature = "dead-letter-http")]
+pub const KAFKA_BATCH_SIZE: usize = 500;
+
+/// Change sink producing batches of events to a Kafka topic through the
+/// REST Proxy
+#[cfg(feature = "dead-letter-http")]
+pub struct KafkaChangeSink {
+    agent: ureq::Agent,
+    url: String,
+    retry: RetryPolicy,
+    pending: Vec<BalanceChange>,
+}
+
+#[cfg(feature = "dead-letter-http")]
+impl KafkaChangeSink {
+    /// Sink producing events to the topic at `url`, e.g.
+    /// `http://proxy:8082/topics/balance-changes`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(KafkaChangeSink)` - The sink
+    /// * `Err(String)` - If the URL does not name a topic
+    pub fn new(url: &str, retry: RetryPolicy) -> Result<Self, String> {
+        if !names_kafka_topic(url) {
+            return Err(format!(
+                "Kafka change target 'kafka+{}' must name a topic, \
+                 e.g. kafka+http://proxy:8082/topics/balance-changes",
+                url
+            ));
+        }
+        Ok(Self {
+            agent: ureq::Agent::new(),
+            url: url.to_string(),
+            retry,
+            pending: Vec::with_capacity(KAFKA_BATCH_SIZE),
+        })
+    }
+
+    /// Body of a request producing `changes`
+    fn request_body(changes: &[BalanceChange]) -> Result<String, String> {
+        let records: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                serde_json::json!({
+                    "key": change.client.to_string(),
+                    "value": change,
+                })
+            })
+            .collect();
+        serde_json::to_string(&serde_json::json!({ "records": records }))
+            .map_err(|e| format!("Failed to encode changes: {}", e))
+    }
+}
+
+#[cfg(feature = "dead-letter-http")]
+impl ChangeSink for KafkaChangeSink {
+    fn send(&mut self, change: BalanceChange) -> Result<(), String> {
+        self.pending.push(change);
+        if self.pending.len() >= KAFKA_BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let body = Self::request_body(&self.pending)?;
+        let (result, attempts) = post_with_retry(
+            &self.agent,
+            &self.url,
+            "application/vnd.kafka.json.v2+json",
+            &body,
+            &self.retry,
+        );
+        result.map_err(|e| {
+            format!(
+                "Failed to deliver {} changes to '{}' after {} attempts: {}",
+                self.pending.len(),
+                self.url,
+                attempts,
+                e
+            )
+        })?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Balances;
+    use crate::types::TransactionType;
+    use rust_decimal::Decimal;
+
+    fn change(seq: u64) -> BalanceChange {
+        BalanceChange {
+            seq,
+            tx: 1,
+            tx_type: TransactionType::Deposit,
+            client: 4,
+            before: Balances::default(),
+            after: Balances {
+                available: Decimal::TEN,
+                held: Decimal::ZERO,
+                total: Decimal::TEN,
+            },
+        }
+    }
+
+    #[test]
+    fn test_write_changes() {
+        let mut output = Vec::new();
+        {
+            let mut writer = ChangeWriter::new(&mut output);
+            writer.send(change(1)).unwrap();
+            writer.send(change(2)).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "{\"seq\":1,\"tx\":1,\"type\":\"deposit\",\"client\":4,\
+             \"before\":{\"available\":\"0\",\"held\":\"0\",\"total\":\"0\"},\
+             \"after\":{\"available\":\"10\",\"held\":\"0\",\"total\":\"10\"}}"
+        );
+    }
+
+    #[test]
+    fn test_create_file_sink() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("changes.jsonl");
+
+        let mut sink = create_change_sink(path.to_str().unwrap()).unwrap();
+        sink.send(change(1)).unwrap();
+        sink.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert!(create_change_sink("missing-dir/changes.jsonl")
+            .err()
+            .unwrap()
+            .contains("Failed to create change file"));
+    }
+
+    #[cfg(not(feature = "dead-letter-http"))]
+    #[test]
+    fn test_kafka_sink_requires_feature() {
+        let result = create_change_sink("kafka+http://proxy/topics/changes");
+        assert!(result.err().unwrap().contains("'dead-letter-http' feature"));
+    }
+
+    #[cfg(feature = "dead-letter-http")]
+    #[test]
+    fn test_kafka_sink() {
+        let body = KafkaChangeSink::request_body(&[change(1)]).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["records"][0]["key"], "4");
+        assert_eq!(json["records"][0]["value"]["seq"], 1);
+
+        let result = create_change_sink("kafka+http://proxy:8082");
+        assert!(result.err().unwrap().contains("must name a topic"));
+    }
+}
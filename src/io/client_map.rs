@@ -0,0 +1,361 @@
+//! Client ID remapping
+//!
+//! Reads the `--client-map` file: a CSV mapping external client identifiers,
+//! which may be any string (e.g. a partner's customer UUIDs), to the numeric
+//! client IDs the engine uses, e.g.
+//!
+//! ```text
+//! external,client
+//! 7f3c2a9e-1b4d-4e8a-9c1f-2d5b6a7e8f90,1
+//! CUST-0042,2
+//! ```
+//!
+//! With a map, the client field of CSV input is looked up in it instead of
+//! being parsed as a number, and the account output shows the external
+//! identifiers again.
+//!
+//! The readers and the output share the map through a `ClientRegistry`. In
+//! string client mode (`--string-clients`) the registry interns every
+//! external identifier it has not seen before, assigning it the next free
+//! client ID, so client fields can be arbitrary strings without a map file.
+
+use crate::types::ClientId;
+use csv::{ReaderBuilder, Trim};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+/// Mapping between external client identifiers and client IDs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientMap {
+    clients: HashMap<String, ClientId>,
+    external: HashMap<ClientId, String>,
+    /// Largest mapped client ID
+    max_client: Option<ClientId>,
+}
+
+impl ClientMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map `external` to `client`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If neither is mapped yet
+    /// * `Err(String)` - If the external identifier is empty, or either of
+    ///   them is already mapped, which would make the mapping ambiguous
+    pub fn insert(&mut self, external: &str, client: ClientId) -> Result<(), String> {
+        if external.is_empty() {
+            return Err("empty external client".to_string());
+        }
+        if self.clients.contains_key(external) {
+            return Err(format!("duplicate external client '{}'", external));
+        }
+        if let Some(previous) = self.external.get(&client) {
+            return Err(format!(
+                "client {} is already mapped from '{}'",
+                client, previous
+            ));
+        }
+        self.clients.insert(external.to_string(), client);
+        self.external.insert(client, external.to_string());
+        self.max_client = self.max_client.max(Some(client));
+        Ok(())
+    }
+
+    /// The client ID following the largest mapped one (1 for an empty map),
+    /// or `None` if the largest ID is mapped
+    fn next_client(&self) -> Option<ClientId> {
+        match self.max_client {
+            Some(client) => client.checked_add(1),
+            None => Some(1),
+        }
+    }
+
+    /// Number of mapped clients
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Whether no client is mapped
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// The client ID an external identifier is mapped to
+    pub fn client(&self, external: &str) -> Option<ClientId> {
+        self.clients.get(external).copied()
+    }
+
+    /// The external identifier of a client ID, if it is mapped
+    pub fn external(&self, client: ClientId) -> Option<&str> {
+        self.external.get(&client).map(String::as_str)
+    }
+
+    /// Parse the (trimmed) client field of a record by looking it up
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ClientId)` - The client ID the field is mapped to
+    /// * `Err(String)` - If the field is not in the map
+    pub fn parse_client(&self, field: &[u8]) -> Result<ClientId, String> {
+        std::str::from_utf8(field)
+            .ok()
+            .and_then(|external| self.client(external))
+            .ok_or_else(|| {
+                format!(
+                    "CSV parse error: client '{}' is not in the client map",
+                    String::from_utf8_lossy(field)
+                )
+            })
+    }
+}
+
+/// Client map shared by the CSV readers and the account output
+///
+/// A fixed registry rejects external identifiers that are not in its map; an
+/// interning one maps each of them to the next free client ID instead.
+#[derive(Debug, Default)]
+pub struct ClientRegistry {
+    map: RwLock<ClientMap>,
+    interning: bool,
+}
+
+impl ClientRegistry {
+    /// Registry that only accepts the external identifiers of `map`
+    pub fn fixed(map: ClientMap) -> Self {
+        Self {
+            map: RwLock::new(map),
+            interning: false,
+        }
+    }
+
+    /// Registry starting from `map` that interns new external identifiers
+    pub fn interning(map: ClientMap) -> Self {
+        Self {
+            map: RwLock::new(map),
+            interning: true,
+        }
+    }
+
+    /// Whether new external identifiers are interned
+    pub fn is_interning(&self) -> bool {
+        self.interning
+    }
+
+    /// The current map
+    ///
+    /// Interning waits while the guard is held, so it should not be kept
+    /// across the processing of records.
+    pub fn map(&self) -> RwLockReadGuard<'_, ClientMap> {
+        self.map.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Parse the (trimmed) client field of a record
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ClientId)` - The client ID the field is mapped to, newly
+    ///   assigned if the registry is interning
+    /// * `Err(String)` - If the field is not in a fixed registry, is empty or
+    ///   not UTF-8, or every client ID is already assigned
+    pub fn parse_client(&self, field: &[u8]) -> Result<ClientId, String> {
+        if !self.interning {
+            return self.map().parse_client(field);
+        }
+        let external = std::str::from_utf8(field).map_err(|_| {
+            format!(
+                "CSV parse error: client '{}' is not valid UTF-8",
+                String::from_utf8_lossy(field)
+            )
+        })?;
+        if let Some(client) = self.map().client(external) {
+            return Ok(client);
+        }
+
+        let mut map = self.map.write().unwrap_or_else(PoisonError::into_inner);
+        // Another reader may have interned it since the lookup
+        if let Some(client) = map.client(external) {
+            return Ok(client);
+        }
+        let client = map.next_client().ok_or_else(|| {
+            format!(
+                "CSV parse error: client '{}' needs a new client ID, but all {} are assigned; \
+                 build with the 'client-id-u32' or 'client-id-u64' feature",
+                external,
+                u128::from(ClientId::MAX) + 1
+            )
+        })?;
+        map.insert(external, client)
+            .map_err(|e| format!("CSV parse error: {}", e))?;
+        Ok(client)
+    }
+}
+
+impl PartialEq for ClientRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.interning == other.interning && *self.map() == *other.map()
+    }
+}
+
+impl Eq for ClientRegistry {}
+
+/// Read a client map from a CSV file
+///
+/// # Arguments
+///
+/// * `path` - Path to the map file
+///
+/// # Returns
+///
+/// * `Ok(ClientMap)` - Every mapping listed in the file
+/// * `Err(String)` - If the file cannot be read or is malformed
+pub fn read_client_map(path: &Path) -> Result<ClientMap, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open client map '{}': {}", path.display(), e))?;
+    parse_client_map(file).map_err(|e| format!("Invalid client map '{}': {}", path.display(), e))
+}
+
+/// Parse a client map from CSV
+fn parse_client_map(input: impl Read) -> Result<ClientMap, String> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(input);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read header: {}", e))?;
+    if headers.len() != 2 || &headers[0] != "external" || &headers[1] != "client" {
+        return Err("header must be 'external,client'".to_string());
+    }
+
+    let mut map = ClientMap::new();
+    for (index, result) in reader.records().enumerate() {
+        // Line 1 is the header
+        let line = index + 2;
+        let record = result.map_err(|e| format!("line {}: {}", line, e))?;
+
+        let client: ClientId = record[1]
+            .parse()
+            .map_err(|e| format!("line {}: invalid client '{}': {}", line, &record[1], e))?;
+        map.insert(&record[0], client)
+            .map_err(|e| format!("line {}: {}", line, e))?;
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse_client_map() {
+        let input = "external,client\n7f3c2a9e-1b4d, 1\n CUST-0042 ,2\n";
+
+        let map = parse_client_map(input.as_bytes()).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.client("7f3c2a9e-1b4d"), Some(1));
+        assert_eq!(map.client("CUST-0042"), Some(2));
+        assert_eq!(map.external(2), Some("CUST-0042"));
+        assert_eq!(map.external(3), None);
+        assert_eq!(map.parse_client(b"CUST-0042"), Ok(2));
+        assert!(map
+            .parse_client(b"CUST-0043")
+            .unwrap_err()
+            .contains("client 'CUST-0043' is not in the client map"));
+    }
+
+    #[rstest]
+    #[case::wrong_header("client,external\n1,a\n", "header must be 'external,client'")]
+    #[case::invalid_client("external,client\na,x\n", "line 2: invalid client 'x'")]
+    #[case::empty_external("external,client\n,1\n", "line 2: empty external client")]
+    #[case::duplicate_external("external,client\na,1\na,2\n", "line 3: duplicate external")]
+    #[case::duplicate_client("external,client\na,1\nb,1\n", "line 3: client 1 is already")]
+    #[case::wrong_field_count("external,client\na,1,extra\n", "line 2:")]
+    fn test_parse_client_map_invalid(#[case] input: &str, #[case] expected: &str) {
+        let err = parse_client_map(input.as_bytes()).unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    #[test]
+    fn test_read_client_map_missing_file() {
+        let err = read_client_map(Path::new("nonexistent.csv")).unwrap_err();
+        assert!(err.contains("Failed to open client map"));
+    }
+
+    #[test]
+    fn test_fixed_registry_rejects_unmapped_clients() {
+        let mut map = ClientMap::new();
+        map.insert("CUST-0042", 2).unwrap();
+        let registry = ClientRegistry::fixed(map);
+
+        assert!(!registry.is_interning());
+        assert_eq!(registry.parse_client(b"CUST-0042"), Ok(2));
+        assert!(registry.parse_client(b"CUST-0043").is_err());
+        assert_eq!(registry.map().len(), 1);
+    }
+
+    #[test]
+    fn test_interning_registry_assigns_next_free_ids() {
+        let mut map = ClientMap::new();
+        map.insert("CUST-0042", 5).unwrap();
+        let registry = ClientRegistry::interning(map);
+
+        assert_eq!(registry.parse_client(b"alice"), Ok(6));
+        assert_eq!(registry.parse_client(b"CUST-0042"), Ok(5));
+        assert_eq!(registry.parse_client(b"bob"), Ok(7));
+        assert_eq!(registry.parse_client(b"alice"), Ok(6));
+        assert_eq!(registry.map().external(7), Some("bob"));
+
+        let empty = ClientRegistry::interning(ClientMap::new());
+        assert_eq!(empty.parse_client(b"7"), Ok(1));
+        assert!(empty
+            .parse_client(b"")
+            .unwrap_err()
+            .contains("empty external client"));
+        assert!(empty
+            .parse_client(b"\xff")
+            .unwrap_err()
+            .contains("not valid UTF-8"));
+    }
+
+    #[test]
+    fn test_interning_registry_runs_out_of_ids() {
+        let mut map = ClientMap::new();
+        map.insert("last", ClientId::MAX).unwrap();
+        let registry = ClientRegistry::interning(map);
+
+        let err = registry.parse_client(b"one-too-many").unwrap_err();
+        assert!(err.contains("all"), "{}", err);
+        assert!(err.contains("'client-id-u32'"), "{}", err);
+    }
+
+    #[test]
+    fn test_concurrent_interning() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let registry = Arc::new(ClientRegistry::interning(ClientMap::new()));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let registry = Arc::clone(&registry);
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|i| registry.parse_client(format!("client-{}", i).as_bytes()))
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        // Every thread sees the same ID for each identifier
+        assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(registry.map().len(), 100);
+    }
+}
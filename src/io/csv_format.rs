@@ -2,16 +2,26 @@
 //!
 //! This module centralizes all CSV format concerns, providing:
 //! - CsvRecord structure for deserialization
+//! - CsvDialect describing delimiter, decimal separator, header aliases and
+//!   client map
 //! - Conversion from CSV records to domain types
-//! - Account output serialization
+//! - Parsing of raw record fields by column position (`CsvColumns`), which
+//!   the CSV readers use to avoid allocating per record
+//! - Account output serialization, in a layout of selected columns and
+//!   amount format (`AccountLayout`), and reading it back
 //!
 //! All functions are pure (no I/O) for easy testing.
 
+use crate::io::client_map::{ClientMap, ClientRegistry};
 use crate::types::{Account, ClientId, TransactionId, TransactionRecord, TransactionType};
-use rust_decimal::Decimal;
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::Deserialize;
-use std::io::Write;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{Read, Write};
 use std::str::FromStr;
+use std::sync::Arc;
 
 /// CSV record structure for deserialization
 ///
@@ -27,6 +37,290 @@ pub struct CsvRecord {
     pub amount: Option<String>,
 }
 
+/// Columns of the transaction CSV header
+pub const CSV_COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+
+/// Positions of the schema columns in a file's header
+///
+/// The CSV readers parse each record straight from its raw fields, located by
+/// these positions (see `CsvDialect::parse_fields`), instead of deserializing
+/// the fields into an owned `CsvRecord` first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvColumns {
+    tx_type: usize,
+    client: usize,
+    tx: usize,
+    amount: usize,
+}
+
+impl Default for CsvColumns {
+    /// The standard `type,client,tx,amount` order
+    fn default() -> Self {
+        Self {
+            tx_type: 0,
+            client: 1,
+            tx: 2,
+            amount: 3,
+        }
+    }
+}
+
+impl CsvColumns {
+    /// Locate the schema columns among the (alias-renamed) names of a header
+    ///
+    /// The first of duplicated columns is used; readers reject such headers
+    /// with `csv_schema::validate_header` before. An empty header, as read
+    /// from an empty file, has the standard positions.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(CsvColumns)` - The position of every column
+    /// * `Err(String)` - If a column is missing from the header
+    pub fn locate<'a>(headers: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut headers = headers.into_iter().peekable();
+        if headers.peek().is_none() {
+            return Ok(Self::default());
+        }
+        let mut positions = [None; CSV_COLUMNS.len()];
+        for (position, header) in headers.enumerate() {
+            if let Some(column) = CSV_COLUMNS.iter().position(|&column| column == header) {
+                positions[column].get_or_insert(position);
+            }
+        }
+        let position = |column: usize| {
+            positions[column]
+                .ok_or_else(|| format!("CSV header has no '{}' column", CSV_COLUMNS[column]))
+        };
+        Ok(Self {
+            tx_type: position(0)?,
+            client: position(1)?,
+            tx: position(2)?,
+            amount: position(3)?,
+        })
+    }
+}
+
+/// Character separating the integer and fractional digits of amounts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecimalSeparator {
+    /// `100.5`
+    #[default]
+    Point,
+    /// `100,5`, as written by most European systems
+    Comma,
+}
+
+impl FromStr for DecimalSeparator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "." => Ok(DecimalSeparator::Point),
+            "," => Ok(DecimalSeparator::Comma),
+            _ => Err(format!(
+                "Invalid decimal separator '{}': expected '.' or ','",
+                s
+            )),
+        }
+    }
+}
+
+/// Header name used by a file in place of one of the standard columns
+///
+/// Parsed from `ALIAS=COLUMN`, e.g. `transaction_id=tx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderAlias {
+    /// Header name found in the file
+    pub alias: String,
+    /// Standard column it stands for, one of `CSV_COLUMNS`
+    pub column: String,
+}
+
+impl FromStr for HeaderAlias {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((alias, column)) if !alias.trim().is_empty() => {
+                let column = column.trim();
+                if !CSV_COLUMNS.contains(&column) {
+                    return Err(format!(
+                        "Invalid header alias '{}': unknown column '{}' (expected one of {})",
+                        s,
+                        column,
+                        CSV_COLUMNS.join(", ")
+                    ));
+                }
+                Ok(HeaderAlias {
+                    alias: alias.trim().to_string(),
+                    column: column.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "Invalid header alias '{}': expected ALIAS=COLUMN",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for HeaderAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.alias, self.column)
+    }
+}
+
+/// Dialect of a transaction CSV file
+///
+/// The default dialect is the standard format: comma-delimited, with a point
+/// as decimal separator and the `type,client,tx,amount` header. Partner files
+/// that deviate from it, such as `;`-delimited files with decimal commas, are
+/// read by describing their dialect instead of preprocessing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// Field delimiter
+    pub delimiter: u8,
+    /// Decimal separator of amounts
+    pub decimal_separator: DecimalSeparator,
+    /// Header names to read as standard columns
+    pub header_aliases: Vec<HeaderAlias>,
+    /// External client identifiers to read in place of numeric client IDs
+    pub client_map: Option<Arc<ClientRegistry>>,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            decimal_separator: DecimalSeparator::default(),
+            header_aliases: Vec::new(),
+            client_map: None,
+        }
+    }
+}
+
+impl CsvDialect {
+    /// Set the field delimiter
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set the decimal separator of amounts
+    pub fn with_decimal_separator(mut self, decimal_separator: DecimalSeparator) -> Self {
+        self.decimal_separator = decimal_separator;
+        self
+    }
+
+    /// Read the header `alias` as one of the standard columns
+    pub fn with_header_alias(mut self, alias: HeaderAlias) -> Self {
+        self.header_aliases.push(alias);
+        self
+    }
+
+    /// Read client fields as external identifiers mapped to client IDs
+    ///
+    /// Every client field must then be in the registry's map, unless the
+    /// registry interns new identifiers; numbers are looked up like any
+    /// other identifier.
+    pub fn with_client_map(mut self, client_map: Arc<ClientRegistry>) -> Self {
+        self.client_map = Some(client_map);
+        self
+    }
+
+    /// The standard column name for a header name of the file
+    ///
+    /// Names without an alias are returned unchanged.
+    pub fn column_name<'a>(&'a self, header: &'a str) -> &'a str {
+        self.header_aliases
+            .iter()
+            .find(|alias| alias.alias == header)
+            .map_or(header, |alias| alias.column.as_str())
+    }
+
+    /// Parse a record in this dialect from its raw fields
+    ///
+    /// `field` returns the (trimmed) bytes of the field at a position, or
+    /// `None` past the end of a short row, which leaves the amount empty. The
+    /// transaction type is matched case-insensitively byte by byte and the
+    /// numbers are parsed in place, so no field is copied unless an amount
+    /// with a decimal comma is too long to normalize on the stack. Validation
+    /// and error messages are those of `convert_csv_record`. With a client
+    /// map, the client field is looked up in it instead of parsed.
+    pub fn parse_fields<'r>(
+        &self,
+        columns: &CsvColumns,
+        field: impl Fn(usize) -> Option<&'r [u8]>,
+    ) -> Result<TransactionRecord, String> {
+        let field = |position| field(position).unwrap_or_default().trim_ascii();
+        let client = match &self.client_map {
+            Some(client_map) => client_map.parse_client(field(columns.client))?,
+            None => parse_integer(field(columns.client), "client")?,
+        };
+        let tx = parse_integer(field(columns.tx), "tx")?;
+        let tx_type = parse_transaction_type(field(columns.tx_type), tx)?;
+        let amount = match field(columns.amount) {
+            [] => None,
+            amount => Some(self.parse_amount(amount, tx)?),
+        };
+        validate_record(tx_type, client, tx, amount)
+    }
+
+    /// Parse a non-empty amount field in this dialect
+    fn parse_amount(&self, amount: &[u8], tx: TransactionId) -> Result<Decimal, String> {
+        let invalid = || {
+            format!(
+                "Invalid amount '{}' for tx {}",
+                String::from_utf8_lossy(amount),
+                tx
+            )
+        };
+        if self.decimal_separator == DecimalSeparator::Point {
+            return std::str::from_utf8(amount)
+                .ok()
+                .and_then(|amount| Decimal::from_str(amount).ok())
+                .ok_or_else(invalid);
+        }
+        if amount.contains(&b'.') {
+            return Err(invalid());
+        }
+
+        // Normalize the decimal comma on the stack; any valid amount fits
+        let mut buffer = [0u8; 64];
+        let normalized = match buffer.get_mut(..amount.len()) {
+            Some(normalized) => normalized,
+            None => return Err(invalid()),
+        };
+        for (byte, &original) in normalized.iter_mut().zip(amount) {
+            *byte = if original == b',' { b'.' } else { original };
+        }
+        std::str::from_utf8(normalized)
+            .ok()
+            .and_then(|amount| Decimal::from_str(amount).ok())
+            .ok_or_else(invalid)
+    }
+
+    /// Convert a CsvRecord read in this dialect to a TransactionRecord
+    ///
+    /// Amounts written with a decimal comma are normalized before the record
+    /// is converted with `convert_csv_record`. Such amounts must not contain a
+    /// point, which the sender may have meant as a thousands separator.
+    pub fn convert(&self, mut csv_record: CsvRecord) -> Result<TransactionRecord, String> {
+        if self.decimal_separator == DecimalSeparator::Comma {
+            if let Some(amount) = &mut csv_record.amount {
+                if amount.contains('.') {
+                    return Err(format!(
+                        "Invalid amount '{}' for tx {}",
+                        amount, csv_record.tx
+                    ));
+                }
+                *amount = amount.replace(',', ".");
+            }
+        }
+        convert_csv_record(csv_record)
+    }
+}
+
 /// Convert a CsvRecord to a TransactionRecord
 ///
 /// This function:
@@ -45,19 +339,7 @@ pub struct CsvRecord {
 /// - Ok(TransactionRecord) - Successfully converted record
 /// - Err(String) - Error message describing the conversion failure
 pub fn convert_csv_record(csv_record: CsvRecord) -> Result<TransactionRecord, String> {
-    let tx_type = match csv_record.tx_type.to_lowercase().as_str() {
-        "deposit" => TransactionType::Deposit,
-        "withdrawal" => TransactionType::Withdrawal,
-        "dispute" => TransactionType::Dispute,
-        "resolve" => TransactionType::Resolve,
-        "chargeback" => TransactionType::Chargeback,
-        _ => {
-            return Err(format!(
-                "Invalid transaction type: '{}' for tx {}",
-                csv_record.tx_type, csv_record.tx
-            ))
-        }
-    };
+    let tx_type = parse_transaction_type(csv_record.tx_type.as_bytes(), csv_record.tx)?;
 
     // Parse amount if present
     let amount = match csv_record.amount {
@@ -75,17 +357,69 @@ pub fn convert_csv_record(csv_record: CsvRecord) -> Result<TransactionRecord, St
         _ => None,
     };
 
+    validate_record(tx_type, csv_record.client, csv_record.tx, amount)
+}
+
+/// Match a transaction type field, ignoring ASCII case
+fn parse_transaction_type(tx_type: &[u8], tx: TransactionId) -> Result<TransactionType, String> {
+    const TYPES: [(&[u8], TransactionType); 7] = [
+        (b"deposit", TransactionType::Deposit),
+        (b"withdrawal", TransactionType::Withdrawal),
+        (b"dispute", TransactionType::Dispute),
+        (b"resolve", TransactionType::Resolve),
+        (b"chargeback", TransactionType::Chargeback),
+        (b"approve", TransactionType::Approve),
+        (b"reject", TransactionType::Reject),
+    ];
+    TYPES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(tx_type))
+        .map(|&(_, tx_type)| tx_type)
+        .ok_or_else(|| {
+            format!(
+                "Invalid transaction type: '{}' for tx {}",
+                String::from_utf8_lossy(tx_type),
+                tx
+            )
+        })
+}
+
+/// Parse a client or transaction ID field
+fn parse_integer<T: FromStr>(field: &[u8], name: &str) -> Result<T, String> {
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| {
+            format!(
+                "CSV parse error: invalid {} '{}'",
+                name,
+                String::from_utf8_lossy(field)
+            )
+        })
+}
+
+/// Build a TransactionRecord, checking the amount against the type
+fn validate_record(
+    tx_type: TransactionType,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<Decimal>,
+) -> Result<TransactionRecord, String> {
     // Validate amount presence based on transaction type
     match tx_type {
         TransactionType::Deposit | TransactionType::Withdrawal => {
             if amount.is_none() {
                 return Err(format!(
                     "{:?} transaction {} for client {} requires an amount",
-                    tx_type, csv_record.tx, csv_record.client
+                    tx_type, tx, client
                 ));
             }
         }
-        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+        TransactionType::Dispute
+        | TransactionType::Resolve
+        | TransactionType::Chargeback
+        | TransactionType::Approve
+        | TransactionType::Reject => {
             // These transaction types should not have amounts
             // (they reference existing transactions)
             // We don't enforce this strictly - just ignore any amount provided
@@ -94,17 +428,267 @@ pub fn convert_csv_record(csv_record: CsvRecord) -> Result<TransactionRecord, St
 
     Ok(TransactionRecord {
         tx_type,
-        client: csv_record.client,
-        tx: csv_record.tx,
+        client,
+        tx,
         amount,
+        line: None,
+        source: None,
     })
 }
 
+/// A column of the account output
+///
+/// Parsed from its name in the header: `client`, `available`, `held`,
+/// `total` and `locked` are the standard columns, `lock_reason` the reason an
+/// account was locked, and `transactions`, `errors`, `dispute_count` and
+/// `last_tx` its client's activity counters (see `ClientActivity`). Any other
+/// name is a metadata column, e.g. from an accounts metadata file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccountColumn {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    LockReason,
+    Transactions,
+    Errors,
+    DisputeCount,
+    LastTx,
+    /// The metadata value with this key, empty for accounts without one
+    Metadata(String),
+}
+
+impl AccountColumn {
+    /// The columns of the standard 5-column output
+    pub const STANDARD: [AccountColumn; 5] = [
+        AccountColumn::Client,
+        AccountColumn::Available,
+        AccountColumn::Held,
+        AccountColumn::Total,
+        AccountColumn::Locked,
+    ];
+
+    /// The name of the column in the header
+    pub fn name(&self) -> &str {
+        match self {
+            AccountColumn::Client => "client",
+            AccountColumn::Available => "available",
+            AccountColumn::Held => "held",
+            AccountColumn::Total => "total",
+            AccountColumn::Locked => "locked",
+            AccountColumn::LockReason => "lock_reason",
+            AccountColumn::Transactions => "transactions",
+            AccountColumn::Errors => "errors",
+            AccountColumn::DisputeCount => "dispute_count",
+            AccountColumn::LastTx => "last_tx",
+            AccountColumn::Metadata(key) => key,
+        }
+    }
+
+    /// The field of `account` in this column, with amounts in `amounts` and
+    /// the client named by `client_name`
+    fn field(
+        &self,
+        account: &Account,
+        amounts: AmountFormat,
+        client_name: &impl Fn(ClientId) -> String,
+    ) -> String {
+        match self {
+            AccountColumn::Client => client_name(account.client),
+            AccountColumn::Available => amounts.format(account.available),
+            AccountColumn::Held => amounts.format(account.held),
+            AccountColumn::Total => amounts.format(account.total),
+            AccountColumn::Locked => account.locked.to_string(),
+            AccountColumn::LockReason => account
+                .lock_reason
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            AccountColumn::Transactions => account.activity.transactions.to_string(),
+            AccountColumn::Errors => account.activity.errors.to_string(),
+            AccountColumn::DisputeCount => account.activity.disputes.to_string(),
+            AccountColumn::LastTx => account
+                .activity
+                .last_tx
+                .map(|tx| tx.to_string())
+                .unwrap_or_default(),
+            AccountColumn::Metadata(key) => account
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get(key))
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for AccountColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let column = match s.trim() {
+            "" => return Err("Empty column name".to_string()),
+            "client" => AccountColumn::Client,
+            "available" => AccountColumn::Available,
+            "held" => AccountColumn::Held,
+            "total" => AccountColumn::Total,
+            "locked" => AccountColumn::Locked,
+            "lock_reason" => AccountColumn::LockReason,
+            "transactions" => AccountColumn::Transactions,
+            "errors" => AccountColumn::Errors,
+            "dispute_count" => AccountColumn::DisputeCount,
+            "last_tx" => AccountColumn::LastTx,
+            key => AccountColumn::Metadata(key.to_string()),
+        };
+        Ok(column)
+    }
+}
+
+impl fmt::Display for AccountColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The columns of the account output, in order (`--columns`)
+///
+/// Parsed from a comma-separated list of column names, e.g.
+/// `client,available,held,total,locked,last_tx,dispute_count`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountColumns(Vec<AccountColumn>);
+
+impl AccountColumns {
+    /// The standard columns, followed by the metadata columns of `accounts`
+    /// in key order, as written by `write_accounts_csv`
+    pub fn standard(accounts: &[Account]) -> Self {
+        let metadata_keys: BTreeSet<&str> = accounts
+            .iter()
+            .filter_map(|account| account.metadata.as_deref())
+            .flat_map(|metadata| metadata.keys().map(String::as_str))
+            .collect();
+        let mut columns = AccountColumn::STANDARD.to_vec();
+        columns.extend(
+            metadata_keys
+                .into_iter()
+                .map(|key| AccountColumn::Metadata(key.to_string())),
+        );
+        Self(columns)
+    }
+
+    /// The columns, in order
+    pub fn columns(&self) -> &[AccountColumn] {
+        &self.0
+    }
+}
+
+impl FromStr for AccountColumns {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut columns: Vec<AccountColumn> = Vec::new();
+        for name in s.split(',') {
+            let column: AccountColumn = name.parse()?;
+            if columns.contains(&column) {
+                return Err(format!("Duplicate column '{}'", column));
+            }
+            columns.push(column);
+        }
+        Ok(Self(columns))
+    }
+}
+
+/// Number of decimal places of amounts in the account output
+const OUTPUT_DECIMAL_PLACES: u32 = 4;
+
+/// Format of the amounts in the account output (`--amount-format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AmountFormat {
+    /// Decimal strings with four decimal places, e.g. `1.2345`
+    #[default]
+    Decimal,
+
+    /// Integers in units of 1/10,000, e.g. `12345` for `1.2345`
+    MinorUnits,
+}
+
+impl AmountFormat {
+    /// Format `amount`, truncated to four decimal places in both formats
+    pub fn format(self, amount: Decimal) -> String {
+        match self {
+            AmountFormat::Decimal => format!("{:.4}", amount),
+            AmountFormat::MinorUnits => {
+                let mut units =
+                    amount.round_dp_with_strategy(OUTPUT_DECIMAL_PLACES, RoundingStrategy::ToZero);
+                units.rescale(OUTPUT_DECIMAL_PLACES);
+                units.mantissa().to_string()
+            }
+        }
+    }
+}
+
+impl FromStr for AmountFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "decimal" => Ok(AmountFormat::Decimal),
+            "minor-units" => Ok(AmountFormat::MinorUnits),
+            _ => Err(format!(
+                "Invalid amount format '{}': expected decimal or minor-units",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AmountFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountFormat::Decimal => write!(f, "decimal"),
+            AmountFormat::MinorUnits => write!(f, "minor-units"),
+        }
+    }
+}
+
+/// Layout of the account output: its columns and the format of its amounts
+///
+/// The default layout is the standard one, written by `write_accounts_csv`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountLayout {
+    /// Columns in order, or `None` for the standard columns followed by the
+    /// metadata columns
+    columns: Option<AccountColumns>,
+    amounts: AmountFormat,
+}
+
+impl AccountLayout {
+    /// Write `columns`, in order
+    pub fn with_columns(mut self, columns: AccountColumns) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Write amounts in `amounts`
+    pub fn with_amounts(mut self, amounts: AmountFormat) -> Self {
+        self.amounts = amounts;
+        self
+    }
+
+    /// Whether this is the standard layout
+    pub fn is_standard(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 /// Write account states to CSV format
 ///
 /// Writes accounts in CSV format with columns: client, available, held, total, locked
 /// Accounts are sorted by client ID for deterministic output.
 ///
+/// If any account carries metadata, one extra column is appended per metadata
+/// key (sorted by key); accounts without a value for a key get an empty field.
+///
 /// # Arguments
 ///
 /// * `accounts` - Slice of account states to write
@@ -115,13 +699,87 @@ pub fn convert_csv_record(csv_record: CsvRecord) -> Result<TransactionRecord, St
 /// * `Ok(())` if writing succeeded
 /// * `Err(String)` if a write error occurred
 pub fn write_accounts_csv(accounts: &[Account], output: &mut dyn Write) -> Result<(), String> {
+    write_accounts_csv_named(accounts, output, |client| client.to_string())
+}
+
+/// Write account states to CSV, with external client identifiers
+///
+/// Like `write_accounts_csv`, except that the client column shows the
+/// external identifier each client ID is mapped from; clients missing from
+/// the map keep their numeric ID. Accounts are still sorted by client ID.
+///
+/// # Arguments
+///
+/// * `accounts` - Slice of account states to write
+/// * `output` - Mutable reference to a writer for outputting CSV
+/// * `client_map` - Map of the external client identifiers
+///
+/// # Returns
+///
+/// * `Ok(())` if writing succeeded
+/// * `Err(String)` if a write error occurred
+pub fn write_accounts_csv_mapped(
+    accounts: &[Account],
+    output: &mut dyn Write,
+    client_map: &ClientMap,
+) -> Result<(), String> {
+    write_accounts_csv_named(accounts, output, |client| {
+        client_map
+            .external(client)
+            .map_or_else(|| client.to_string(), str::to_string)
+    })
+}
+
+/// Write account states to CSV, naming clients with `client_name`
+///
+/// Like `write_accounts_csv`, except that the client column shows
+/// `client_name` of each client ID, e.g. its pseudonym. Accounts are still
+/// sorted by client ID.
+///
+/// # Returns
+///
+/// * `Ok(())` if writing succeeded
+/// * `Err(String)` if a write error occurred
+pub fn write_accounts_csv_named(
+    accounts: &[Account],
+    output: &mut dyn Write,
+    client_name: impl Fn(ClientId) -> String,
+) -> Result<(), String> {
+    write_accounts_csv_layout(accounts, output, &AccountLayout::default(), client_name)
+}
+
+/// Write account states to CSV, in the given layout
+///
+/// Like `write_accounts_csv_named`, except that the header and each record
+/// hold the columns of `layout`, in their order, with its format of amounts.
+/// Accounts are still sorted by client ID.
+///
+/// # Returns
+///
+/// * `Ok(())` if writing succeeded
+/// * `Err(String)` if a write error occurred
+pub fn write_accounts_csv_layout(
+    accounts: &[Account],
+    output: &mut dyn Write,
+    layout: &AccountLayout,
+    client_name: impl Fn(ClientId) -> String,
+) -> Result<(), String> {
     use csv::Writer;
 
+    let standard;
+    let columns = match &layout.columns {
+        Some(columns) => columns,
+        None => {
+            standard = AccountColumns::standard(accounts);
+            &standard
+        }
+    };
+
     let mut writer = Writer::from_writer(output);
 
     // Write header
     writer
-        .write_record(["client", "available", "held", "total", "locked"])
+        .write_record(columns.columns().iter().map(AccountColumn::name))
         .map_err(|e| format!("Failed to write CSV header: {}", e))?;
 
     // Sort accounts by client ID for deterministic output
@@ -130,14 +788,12 @@ pub fn write_accounts_csv(accounts: &[Account], output: &mut dyn Write) -> Resul
 
     // Write each account
     for account in sorted_accounts {
+        let record = columns
+            .columns()
+            .iter()
+            .map(|column| column.field(&account, layout.amounts, &client_name));
         writer
-            .write_record(&[
-                account.client.to_string(),
-                format!("{:.4}", account.available),
-                format!("{:.4}", account.held),
-                format!("{:.4}", account.total),
-                account.locked.to_string(),
-            ])
+            .write_record(record)
             .map_err(|e| format!("Failed to write account record: {}", e))?;
     }
 
@@ -148,11 +804,70 @@ pub fn write_accounts_csv(accounts: &[Account], output: &mut dyn Write) -> Resul
     Ok(())
 }
 
+/// Account row of an accounts CSV, as written by `write_accounts_csv`
+#[derive(Debug, Deserialize)]
+struct AccountRow {
+    client: ClientId,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+    /// Written by `--lock-reasons`
+    #[serde(default)]
+    lock_reason: Option<String>,
+}
+
+/// Read account states from CSV in the format written by `write_accounts_csv`
+///
+/// A `lock_reason` column (`--lock-reasons`) is read back as the lock reason
+/// of locked accounts; other metadata columns after `locked` are ignored.
+///
+/// # Arguments
+///
+/// * `input` - Reader over the CSV, starting with the header
+///
+/// # Returns
+///
+/// * `Ok(Vec<Account>)` - The accounts, in file order
+/// * `Err(String)` - If a row is malformed, naming its line
+pub fn read_accounts_csv(input: impl Read) -> Result<Vec<Account>, String> {
+    let mut reader = ReaderBuilder::new()
+        .trim(Trim::All)
+        .flexible(true)
+        .from_reader(input);
+    let mut accounts = Vec::new();
+    for (index, result) in reader.deserialize::<AccountRow>().enumerate() {
+        // Line 1 is the header
+        let line = index + 2;
+        let row = result.map_err(|e| format!("line {}: {}", line, e))?;
+        let amount = |field: &str, value: &str| {
+            Decimal::from_str(value)
+                .map_err(|e| format!("line {}: invalid {} '{}': {}", line, field, value, e))
+        };
+        let mut account = Account::new(row.client);
+        account.available = amount("available", &row.available)?;
+        account.held = amount("held", &row.held)?;
+        account.total = amount("total", &row.total)?;
+        account.locked = row.locked;
+        if row.locked {
+            account.lock_reason = row
+                .lock_reason
+                .map(|reason| reason.parse())
+                .transpose()
+                .map_err(|e| format!("line {}: {}", line, e))?;
+        }
+        accounts.push(account);
+    }
+    Ok(accounts)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{AccountMetadata, ClientActivity, LockReason};
     use rstest::rstest;
     use rust_decimal::Decimal;
+    use std::sync::Arc;
 
     #[rstest]
     #[case("deposit", TransactionType::Deposit, Some("100.0"))]
@@ -243,6 +958,171 @@ mod tests {
         assert_eq!(result.unwrap().amount, Some(expected));
     }
 
+    #[rstest]
+    #[case::point(DecimalSeparator::Point, "1234.5", Ok(Decimal::new(12345, 1)))]
+    #[case::point_rejects_comma(DecimalSeparator::Point, "1234,5", Err("Invalid amount"))]
+    #[case::comma(DecimalSeparator::Comma, " 1234,5 ", Ok(Decimal::new(12345, 1)))]
+    #[case::comma_integer(DecimalSeparator::Comma, "1234", Ok(Decimal::from(1234)))]
+    #[case::comma_rejects_point(
+        DecimalSeparator::Comma,
+        "1.234,5",
+        Err("Invalid amount '1.234,5'")
+    )]
+    fn test_dialect_decimal_separator(
+        #[case] decimal_separator: DecimalSeparator,
+        #[case] amount: &str,
+        #[case] expected: Result<Decimal, &str>,
+    ) {
+        let dialect = CsvDialect::default().with_decimal_separator(decimal_separator);
+        let result = dialect.convert(CsvRecord {
+            tx_type: "deposit".to_string(),
+            client: 1,
+            tx: 1,
+            amount: Some(amount.to_string()),
+        });
+        let fields: [&[u8]; 4] = [b"deposit", b"1", b"1", amount.as_bytes()];
+        let parsed = dialect.parse_fields(&CsvColumns::default(), |position| {
+            fields.get(position).copied()
+        });
+        assert_eq!(parsed, result);
+
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap().amount, Some(expected)),
+            Err(expected) => assert!(result.unwrap_err().contains(expected)),
+        }
+    }
+
+    #[rstest]
+    #[case::deposit(
+        &["deposit", "1", "2", "1.5"],
+        Ok(TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            tx: 2,
+            amount: Some(Decimal::new(15, 1)),
+            line: None,
+            source: None,
+        })
+    )]
+    #[case::mixed_case(
+        &["WithDrawal", " 1 ", "2", "3"],
+        Ok(TransactionRecord {
+            tx_type: TransactionType::Withdrawal,
+            client: 1,
+            tx: 2,
+            amount: Some(Decimal::from(3)),
+            line: None,
+            source: None,
+        })
+    )]
+    #[case::short_row(
+        &["dispute", "1", "2"],
+        Ok(TransactionRecord {
+            tx_type: TransactionType::Dispute,
+            client: 1,
+            tx: 2,
+            amount: None,
+            line: None,
+            source: None,
+        })
+    )]
+    #[case::missing_amount(&["deposit", "1", "2", ""], Err("Deposit transaction 2 for client 1 requires an amount"))]
+    #[case::invalid_type(&["transfer", "1", "2", "1"], Err("Invalid transaction type: 'transfer' for tx 2"))]
+    #[case::invalid_client(&["deposit", "x", "2", "1"], Err("CSV parse error: invalid client 'x'"))]
+    #[case::invalid_tx(&["deposit", "1", "-2", "1"], Err("CSV parse error: invalid tx '-2'"))]
+    #[case::invalid_amount(&["deposit", "1", "2", "1.2.3"], Err("Invalid amount '1.2.3' for tx 2"))]
+    fn test_parse_fields(
+        #[case] fields: &[&str],
+        #[case] expected: Result<TransactionRecord, &str>,
+    ) {
+        let result = CsvDialect::default().parse_fields(&CsvColumns::default(), |position| {
+            fields.get(position).map(|field| field.as_bytes())
+        });
+
+        assert_eq!(result, expected.map_err(str::to_string));
+    }
+
+    #[test]
+    fn test_parse_fields_by_located_columns() {
+        let columns = CsvColumns::locate(["amount", "note", "tx", "type", "client"]).unwrap();
+        let fields: [&[u8]; 5] = [b"7.25", b"ignored", b"9", b"deposit", b"4"];
+
+        let record = CsvDialect::default()
+            .parse_fields(&columns, |position| fields.get(position).copied())
+            .unwrap();
+        assert_eq!((record.client, record.tx), (4, 9));
+        assert_eq!(record.amount, Some(Decimal::new(725, 2)));
+        assert_eq!(
+            CsvColumns::locate(["type", "client", "amount"]).unwrap_err(),
+            "CSV header has no 'tx' column"
+        );
+        assert_eq!(CsvColumns::locate([]), Ok(CsvColumns::default()));
+    }
+
+    #[test]
+    fn test_parse_fields_with_client_map() {
+        let mut client_map = ClientMap::new();
+        client_map.insert("CUST-0042", 7).unwrap();
+        let dialect =
+            CsvDialect::default().with_client_map(Arc::new(ClientRegistry::fixed(client_map)));
+        let parse = |client: &'static str| {
+            let fields: [&[u8]; 4] = [b"deposit", client.as_bytes(), b"1", b"2.5"];
+            dialect.parse_fields(&CsvColumns::default(), |position| {
+                fields.get(position).copied()
+            })
+        };
+
+        assert_eq!(parse(" CUST-0042 ").unwrap().client, 7);
+        assert_eq!(
+            parse("7"),
+            Err("CSV parse error: client '7' is not in the client map".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_fields_with_string_clients() {
+        let registry = Arc::new(ClientRegistry::interning(ClientMap::new()));
+        let dialect = CsvDialect::default().with_client_map(registry.clone());
+        let parse = |client: &'static str| {
+            let fields: [&[u8]; 4] = [b"deposit", client.as_bytes(), b"1", b"2.5"];
+            dialect.parse_fields(&CsvColumns::default(), |position| {
+                fields.get(position).copied()
+            })
+        };
+
+        assert_eq!(parse("alice").unwrap().client, 1);
+        assert_eq!(parse(" bob").unwrap().client, 2);
+        assert_eq!(parse("alice ").unwrap().client, 1);
+        assert_eq!(registry.map().external(2), Some("bob"));
+    }
+
+    #[test]
+    fn test_dialect_column_name() {
+        let dialect = CsvDialect::default()
+            .with_header_alias("transaction_id=tx".parse().unwrap())
+            .with_header_alias(" kind = type ".parse().unwrap());
+
+        assert_eq!(dialect.column_name("transaction_id"), "tx");
+        assert_eq!(dialect.column_name("kind"), "type");
+        assert_eq!(dialect.column_name("client"), "client");
+    }
+
+    #[rstest]
+    #[case::missing_separator("transaction_id")]
+    #[case::empty_alias("=tx")]
+    #[case::unknown_column("transaction_id=id")]
+    fn test_header_alias_invalid(#[case] value: &str) {
+        assert!(value.parse::<HeaderAlias>().is_err());
+    }
+
+    #[rstest]
+    #[case::point(".", DecimalSeparator::Point)]
+    #[case::comma(",", DecimalSeparator::Comma)]
+    fn test_decimal_separator_parse(#[case] value: &str, #[case] expected: DecimalSeparator) {
+        assert_eq!(value.parse::<DecimalSeparator>(), Ok(expected));
+        assert!(";".parse::<DecimalSeparator>().is_err());
+    }
+
     #[rstest]
     #[case::single_account(
         vec![Account {
@@ -251,6 +1131,9 @@ mod tests {
             held: Decimal::ZERO,
             total: Decimal::new(1000000, 4),
             locked: false,
+            lock_reason: None,
+            activity: ClientActivity::default(),
+            metadata: None,
         }],
         "client,available,held,total,locked\n1,100.0000,0.0000,100.0000,false\n"
     )]
@@ -262,6 +1145,9 @@ mod tests {
                 held: Decimal::ZERO,
                 total: Decimal::new(1000000, 4),
                 locked: false,
+                lock_reason: None,
+                activity: ClientActivity::default(),
+                metadata: None,
             },
             Account {
                 client: 2,
@@ -269,6 +1155,9 @@ mod tests {
                 held: Decimal::ZERO,
                 total: Decimal::new(2000000, 4),
                 locked: false,
+                lock_reason: None,
+                activity: ClientActivity::default(),
+                metadata: None,
             },
         ],
         "client,available,held,total,locked\n1,100.0000,0.0000,100.0000,false\n2,200.0000,0.0000,200.0000,false\n"
@@ -281,6 +1170,9 @@ mod tests {
                 held: Decimal::ZERO,
                 total: Decimal::ZERO,
                 locked: false,
+                lock_reason: None,
+                activity: ClientActivity::default(),
+                metadata: None,
             },
             Account {
                 client: 1,
@@ -288,6 +1180,9 @@ mod tests {
                 held: Decimal::ZERO,
                 total: Decimal::ZERO,
                 locked: false,
+                lock_reason: None,
+                activity: ClientActivity::default(),
+                metadata: None,
             },
             Account {
                 client: 2,
@@ -295,6 +1190,9 @@ mod tests {
                 held: Decimal::ZERO,
                 total: Decimal::ZERO,
                 locked: false,
+                lock_reason: None,
+                activity: ClientActivity::default(),
+                metadata: None,
             },
         ],
         "client,available,held,total,locked\n1,0.0000,0.0000,0.0000,false\n2,0.0000,0.0000,0.0000,false\n3,0.0000,0.0000,0.0000,false\n"
@@ -306,6 +1204,9 @@ mod tests {
             held: Decimal::new(1000000, 4),
             total: Decimal::new(1000000, 4),
             locked: false,
+            lock_reason: None,
+            activity: ClientActivity::default(),
+            metadata: None,
         }],
         "client,available,held,total,locked\n1,0.0000,100.0000,100.0000,false\n"
     )]
@@ -316,6 +1217,9 @@ mod tests {
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: true,
+            lock_reason: None,
+            activity: ClientActivity::default(),
+            metadata: None,
         }],
         "client,available,held,total,locked\n1,0.0000,0.0000,0.0000,true\n"
     )]
@@ -330,6 +1234,9 @@ mod tests {
             held: Decimal::new(5678, 4),
             total: Decimal::new(1006912, 4),
             locked: false,
+            lock_reason: None,
+            activity: ClientActivity::default(),
+            metadata: None,
         }],
         "client,available,held,total,locked\n1,100.1234,0.5678,100.6912,false\n"
     )]
@@ -341,4 +1248,165 @@ mod tests {
         let output_str = String::from_utf8(output).unwrap();
         assert_eq!(output_str, expected_output);
     }
+
+    #[test]
+    fn test_read_accounts_csv() {
+        let input = "client, available, held, total, locked, owner\n\
+                     2,1.5000,0.5000,2.0000,true,Alice\n\
+                     1,-3,0,-3,false,\n";
+        let accounts = read_accounts_csv(input.as_bytes()).unwrap();
+
+        let mut locked = Account::new(2);
+        locked.available = Decimal::new(15, 1);
+        locked.held = Decimal::new(5, 1);
+        locked.total = Decimal::TWO;
+        locked.locked = true;
+        let mut debt = Account::new(1);
+        debt.available = Decimal::from(-3);
+        debt.total = Decimal::from(-3);
+        assert_eq!(accounts, vec![locked, debt]);
+    }
+
+    #[test]
+    fn test_read_accounts_csv_lock_reasons() {
+        let input = "client,available,held,total,locked,lock_reason\n\
+                     1,0,0,0,true,chargeback:4\n\
+                     2,0,0,0,true,\n\
+                     3,0,0,0,false,\n";
+        let accounts = read_accounts_csv(input.as_bytes()).unwrap();
+
+        let reasons: Vec<_> = accounts
+            .iter()
+            .map(|account| account.lock_reason.clone())
+            .collect();
+        assert_eq!(reasons, vec![Some(LockReason::ChargebackTx(4)), None, None]);
+        assert!(read_accounts_csv(
+            "client,available,held,total,locked,lock_reason\n1,0,0,0,true,frozen\n".as_bytes()
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    #[case::bad_amount(
+        "client,available,held,total,locked\n1,lots,0,0,false\n",
+        "line 2: invalid available 'lots'"
+    )]
+    #[case::bad_locked("client,available,held,total,locked\n1,0,0,0,yes\n", "line 2:")]
+    #[case::missing_column("client,available,held,total\n1,0,0,0\n", "line 2:")]
+    fn test_read_accounts_csv_invalid(#[case] input: &str, #[case] expected: &str) {
+        let err = read_accounts_csv(input.as_bytes()).unwrap_err();
+        assert!(err.contains(expected), "{err}");
+    }
+
+    #[test]
+    fn test_write_accounts_csv_with_metadata() {
+        let mut verified = Account::new(1);
+        verified.metadata = Some(Arc::new(AccountMetadata::from([
+            ("kyc_status".to_string(), "verified".to_string()),
+            ("owner".to_string(), "Alice, Ltd".to_string()),
+        ])));
+        let mut pending = Account::new(2);
+        pending.metadata = Some(Arc::new(AccountMetadata::from([(
+            "kyc_status".to_string(),
+            "pending".to_string(),
+        )])));
+        let unknown = Account::new(3);
+
+        let mut output = Vec::new();
+        write_accounts_csv(&[unknown, pending, verified], &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked,kyc_status,owner\n\
+             1,0.0000,0.0000,0.0000,false,verified,\"Alice, Ltd\"\n\
+             2,0.0000,0.0000,0.0000,false,pending,\n\
+             3,0.0000,0.0000,0.0000,false,,\n"
+        );
+    }
+
+    #[test]
+    fn test_write_accounts_csv_mapped() {
+        let mut client_map = ClientMap::new();
+        client_map.insert("CUST-0042", 2).unwrap();
+        client_map.insert("7f3c2a9e", 1).unwrap();
+
+        let mut output = Vec::new();
+        write_accounts_csv_mapped(
+            &[Account::new(3), Account::new(2), Account::new(1)],
+            &mut output,
+            &client_map,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             7f3c2a9e,0.0000,0.0000,0.0000,false\n\
+             CUST-0042,0.0000,0.0000,0.0000,false\n\
+             3,0.0000,0.0000,0.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_write_accounts_csv_layout() {
+        let mut account = Account::new(1);
+        account.lock(LockReason::ChargebackTx(4));
+        account
+            .activity
+            .record(TransactionType::Chargeback, 4, true);
+
+        let columns: AccountColumns =
+            "client,locked,lock_reason,transactions,errors,last_tx,kyc_status"
+                .parse()
+                .unwrap();
+        let mut output = Vec::new();
+        let layout = AccountLayout::default().with_columns(columns);
+        write_accounts_csv_layout(&[account], &mut output, &layout, |client| {
+            client.to_string()
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,locked,lock_reason,transactions,errors,last_tx,kyc_status\n\
+             1,true,chargeback:4,1,0,4,\n"
+        );
+    }
+
+    #[rstest]
+    #[case::whole(Decimal::from(12), "12.0000", "120000")]
+    #[case::fraction(Decimal::new(12345, 4), "1.2345", "12345")]
+    #[case::truncated(Decimal::new(123459, 5), "1.2345", "12345")]
+    #[case::negative(Decimal::new(-5, 2), "-0.0500", "-500")]
+    fn test_amount_formats(#[case] amount: Decimal, #[case] decimal: &str, #[case] minor: &str) {
+        assert_eq!(AmountFormat::Decimal.format(amount), decimal);
+        assert_eq!(AmountFormat::MinorUnits.format(amount), minor);
+    }
+
+    #[rstest]
+    #[case::empty("", "Empty column name")]
+    #[case::trailing_comma("client,", "Empty column name")]
+    #[case::duplicate("client, total,client", "Duplicate column 'client'")]
+    fn test_account_columns_invalid(#[case] list: &str, #[case] expected: &str) {
+        assert_eq!(list.parse::<AccountColumns>(), Err(expected.to_string()));
+    }
+
+    #[test]
+    fn test_account_columns_standard() {
+        let mut account = Account::new(1);
+        account.metadata = Some(Arc::new(AccountMetadata::from([(
+            "owner".to_string(),
+            "Alice".to_string(),
+        )])));
+
+        let names: Vec<String> = AccountColumns::standard(&[account])
+            .columns()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            names,
+            ["client", "available", "held", "total", "locked", "owner"]
+        );
+    }
 }
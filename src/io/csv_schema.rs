@@ -0,0 +1,241 @@
+//! Validation of CSV headers against the transaction schema
+//!
+//! Records are deserialized by column name, so a misspelt or missing column
+//! makes every row of the file fail to parse. Readers check the header once
+//! before reading any record instead, and fail with a single error naming the
+//! missing and duplicated columns, the unknown columns that may explain them,
+//! and the intended column for near misses such as `amout` or `client_id`.
+//!
+//! Unknown columns and a non-standard column order do not prevent parsing and
+//! are only reported as warnings.
+
+use crate::io::csv_format::CSV_COLUMNS;
+use crate::io::log::{log, LogLine};
+use std::fmt;
+
+/// Column of a header that is not part of the schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownColumn {
+    /// Name found in the header
+    pub name: String,
+    /// Missing column the name is probably meant to be
+    pub suggestion: Option<&'static str>,
+}
+
+/// Result of checking a CSV header against the schema
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderDiagnostics {
+    /// Schema columns absent from the header
+    pub missing: Vec<&'static str>,
+    /// Header columns that are not part of the schema
+    pub unknown: Vec<UnknownColumn>,
+    /// Schema columns appearing more than once
+    pub duplicated: Vec<&'static str>,
+    /// Whether the schema columns appear in a different order than
+    /// `type,client,tx,amount`
+    pub misordered: bool,
+}
+
+impl HeaderDiagnostics {
+    /// Check the (alias-renamed) column names of a header
+    pub fn check<'a>(headers: impl IntoIterator<Item = &'a str>) -> Self {
+        let headers: Vec<&str> = headers.into_iter().collect();
+        let mut diagnostics = Self::default();
+
+        let mut present = Vec::new();
+        for &header in &headers {
+            let Some(&column) = CSV_COLUMNS.iter().find(|&&column| column == header) else {
+                continue;
+            };
+            if !present.contains(&column) {
+                present.push(column);
+            } else if !diagnostics.duplicated.contains(&column) {
+                diagnostics.duplicated.push(column);
+            }
+        }
+        diagnostics.missing = CSV_COLUMNS
+            .iter()
+            .copied()
+            .filter(|column| !present.contains(column))
+            .collect();
+        diagnostics.unknown = headers
+            .iter()
+            .filter(|header| !CSV_COLUMNS.contains(header))
+            .map(|header| UnknownColumn {
+                name: header.to_string(),
+                suggestion: suggest(header, &diagnostics.missing),
+            })
+            .collect();
+        diagnostics.misordered = diagnostics.missing.is_empty()
+            && diagnostics.duplicated.is_empty()
+            && present != CSV_COLUMNS;
+
+        diagnostics
+    }
+
+    /// Whether records can be read with this header
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty() && self.duplicated.is_empty()
+    }
+
+    /// Whether there is anything to report
+    pub fn has_findings(&self) -> bool {
+        !self.is_valid() || !self.unknown.is_empty() || self.misordered
+    }
+}
+
+impl fmt::Display for HeaderDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quoted = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| format!("'{}'", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut findings = Vec::new();
+        if !self.missing.is_empty() {
+            findings.push(format!("missing {}", quoted(&self.missing)));
+        }
+        if !self.duplicated.is_empty() {
+            findings.push(format!("duplicated {}", quoted(&self.duplicated)));
+        }
+        for column in &self.unknown {
+            findings.push(match column.suggestion {
+                Some(suggestion) => format!(
+                    "unknown column '{}' (did you mean '{}'?)",
+                    column.name, suggestion
+                ),
+                None => format!("unknown column '{}'", column.name),
+            });
+        }
+        if self.misordered {
+            findings.push(format!(
+                "columns are not in the standard order {}",
+                CSV_COLUMNS.join(",")
+            ));
+        }
+        write!(f, "{}", findings.join("; "))
+    }
+}
+
+/// Check a CSV header before any record is read
+///
+/// A header without columns (an empty file) is accepted, since the file has
+/// no records either. Findings that do not prevent parsing, such as unknown
+/// columns (which are ignored), are printed to stderr as a warning.
+///
+/// # Returns
+///
+/// * `Ok(())` - If records can be read with this header
+/// * `Err(String)` - If columns are missing or duplicated, listing every finding
+pub fn validate_header<'a>(headers: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+    let mut headers = headers.into_iter().peekable();
+    if headers.peek().is_none() {
+        return Ok(());
+    }
+
+    let diagnostics = HeaderDiagnostics::check(headers);
+    if !diagnostics.is_valid() {
+        return Err(format!("Invalid CSV header: {}", diagnostics));
+    }
+    if diagnostics.has_findings() {
+        log(LogLine::warning(format!(
+            "Warning: CSV header: {}",
+            diagnostics
+        )));
+    }
+    Ok(())
+}
+
+/// The missing column an unknown header name is most likely meant to be
+///
+/// Names matching a column up to case, containing it (`client_id`) or within
+/// two edits of it (`amout`) are near misses.
+fn suggest(name: &str, missing: &[&'static str]) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    missing
+        .iter()
+        .copied()
+        .filter_map(|column| {
+            let distance = if name.contains(column) {
+                0
+            } else {
+                edit_distance(&name, column)
+            };
+            (distance <= 2 && distance < column.len()).then_some((distance, column))
+        })
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, column)| column)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_standard_header() {
+        let diagnostics = HeaderDiagnostics::check(["type", "client", "tx", "amount"]);
+        assert_eq!(diagnostics, HeaderDiagnostics::default());
+        assert!(diagnostics.is_valid());
+        assert!(!diagnostics.has_findings());
+    }
+
+    #[rstest]
+    #[case::misspelt(&["type", "client", "tx", "amout"], "missing 'amount'; unknown column 'amout' (did you mean 'amount'?)")]
+    #[case::wrong_case(&["Type", "client", "tx", "amount"], "missing 'type'; unknown column 'Type' (did you mean 'type'?)")]
+    #[case::suffixed(&["type", "client_id", "tx", "amount"], "missing 'client'; unknown column 'client_id' (did you mean 'client'?)")]
+    #[case::unrelated(&["type", "client", "transaction_id", "amount"], "missing 'tx'; unknown column 'transaction_id'")]
+    #[case::duplicated(&["type", "client", "tx", "tx", "amount"], "duplicated 'tx'")]
+    #[case::several_missing(&["type", "amount"], "missing 'client', 'tx'")]
+    fn test_invalid_header(#[case] headers: &[&str], #[case] expected: &str) {
+        let diagnostics = HeaderDiagnostics::check(headers.iter().copied());
+        assert!(!diagnostics.is_valid());
+        assert_eq!(diagnostics.to_string(), expected);
+
+        let err = validate_header(headers.iter().copied()).unwrap_err();
+        assert_eq!(err, format!("Invalid CSV header: {}", expected));
+    }
+
+    #[rstest]
+    #[case::extra_column(&["type", "client", "tx", "amount", "reason"], "unknown column 'reason'")]
+    #[case::misordered(&["client", "tx", "type", "amount"], "columns are not in the standard order type,client,tx,amount")]
+    fn test_header_warnings(#[case] headers: &[&str], #[case] expected: &str) {
+        let diagnostics = HeaderDiagnostics::check(headers.iter().copied());
+        assert!(diagnostics.is_valid());
+        assert!(diagnostics.has_findings());
+        assert_eq!(diagnostics.to_string(), expected);
+        assert!(validate_header(headers.iter().copied()).is_ok());
+    }
+
+    #[test]
+    fn test_empty_header_is_accepted() {
+        assert!(validate_header([]).is_ok());
+    }
+
+    #[rstest]
+    #[case("amount", "amount", 0)]
+    #[case("amout", "amount", 1)]
+    #[case("clinet", "client", 2)]
+    #[case("id", "tx", 2)]
+    fn test_edit_distance(#[case] a: &str, #[case] b: &str, #[case] expected: usize) {
+        assert_eq!(edit_distance(a, b), expected);
+    }
+}
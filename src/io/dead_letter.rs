@@ -0,0 +1,579 @@
+//! Dead letters of records that failed processing
+//!
+//! With `--dead-letter TARGET`, every record the engine rejects is sent to a
+//! dead-letter sink along with its error, so failures can be inspected and
+//! the records replayed once the cause is fixed. The target is one of:
+//!
+//! - a file path - CSV with the input columns plus `code`, `kind`, `error`,
+//!   `attempts`, `source` and `line` (the readers ignore the extra columns,
+//!   so the file can be fed back as input)
+//! - `http://...` or `https://...` - JSON arrays of dead letters POSTed to
+//!   the endpoint (feature `dead-letter-http`)
+//! - `kafka+http://host:port/topics/TOPIC` (or `kafka+https://`) - dead
+//!   letters produced to a Kafka topic through a Confluent REST Proxy, keyed
+//!   by client (feature `dead-letter-http`)
+//!
+//! Remote sinks send dead letters in batches and retry transient failures
+//! (connection errors, `429` and `5xx` responses) following the configured
+//! `RetryPolicy`. A dead letter that cannot be delivered aborts the run, so
+//! no failed record is lost silently.
+//!
+//! Only records rejected by the engine are dead-lettered; records that cannot
+//! be parsed have no fields to replay and are only logged. Dead letters hold
+//! the records as read: they are not pseudonymized.
+//!
+//! Each dead letter names where its record came from: the input file when a
+//! run reads several (`source`) and the input line (`line`), each empty (or
+//! `null`) when not known.
+
+use crate::core::RetryPolicy;
+use crate::types::{PaymentError, TransactionRecord, TransactionType};
+use csv::Writer;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A record that failed processing, with the reason it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadLetter {
+    #[serde(flatten)]
+    pub record: TransactionRecord,
+    /// Numeric code of the error (see `PaymentError::code`)
+    pub code: u16,
+    /// Name of the error variant (see `PaymentError::kind`)
+    pub kind: &'static str,
+    /// Error message
+    pub error: String,
+    /// Number of times processing the record was attempted
+    pub attempts: u32,
+    /// Input file the record was read from, when the run reads several
+    pub source: Option<Arc<str>>,
+    /// Input line the record was read from, if known
+    pub line: Option<u64>,
+}
+
+impl DeadLetter {
+    /// Dead letter of a record rejected on its first attempt
+    pub fn new(record: TransactionRecord, error: &PaymentError) -> Self {
+        Self {
+            source: record.source.clone(),
+            line: record.line,
+            record,
+            code: error.code(),
+            kind: error.kind(),
+            error: error.to_string(),
+            attempts: 1,
+        }
+    }
+
+    /// Record that processing was attempted `attempts` times
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+}
+
+/// Destination of dead letters
+///
+/// Sinks may buffer dead letters; `flush` delivers those buffered so far.
+pub trait DeadLetterSink: Send {
+    /// Send a dead letter
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the dead letter was delivered or buffered
+    /// * `Err(String)` - If it (or a batch it completed) cannot be delivered
+    fn send(&mut self, letter: DeadLetter) -> Result<(), String>;
+
+    /// Deliver the dead letters buffered so far
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Where to send dead letters and how to retry their delivery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetterOptions {
+    /// File path, HTTP endpoint or Kafka REST Proxy topic (see the module
+    /// documentation)
+    pub target: String,
+    /// Retries of failed deliveries to remote sinks
+    pub retry: RetryPolicy,
+}
+
+impl DeadLetterOptions {
+    /// Send dead letters to `target`, retrying with the default policy
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Retry failed deliveries following `retry`
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+/// Create the dead-letter sink for a target
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn DeadLetterSink>)` - The sink
+/// * `Err(String)` - If the file cannot be created, the Kafka target names no
+///   topic, or the target is remote and the `dead-letter-http` feature is off
+pub fn create_dead_letter_sink(
+    options: &DeadLetterOptions,
+) -> Result<Box<dyn DeadLetterSink>, String> {
+    let target = options.target.as_str();
+    let kafka = target.strip_prefix("kafka+").filter(|url| is_http_url(url));
+    if kafka.is_some() || is_http_url(target) {
+        #[cfg(feature = "dead-letter-http")]
+        {
+            return Ok(match kafka {
+                Some(url) => Box::new(HttpDeadLetterSink::kafka(url, options.retry)?),
+                None => Box::new(HttpDeadLetterSink::new(target, options.retry)),
+            });
+        }
+        #[cfg(not(feature = "dead-letter-http"))]
+        {
+            return Err(format!(
+                "Dead letter target '{}' requires building with the 'dead-letter-http' feature",
+                target
+            ));
+        }
+    }
+    Ok(Box::new(DeadLetterWriter::create(Path::new(target))?))
+}
+
+/// Whether a target is an HTTP(S) URL
+pub(crate) fn is_http_url(target: &str) -> bool {
+    target.starts_with("http://") || target.starts_with("https://")
+}
+
+/// Whether a Kafka REST Proxy URL names a topic, e.g.
+/// `http://proxy:8082/topics/dead-letters`
+#[cfg(feature = "dead-letter-http")]
+pub(crate) fn names_kafka_topic(url: &str) -> bool {
+    let topic = url.split_once("/topics/").map(|(_, topic)| topic);
+    topic.is_some_and(|topic| !topic.is_empty() && !topic.contains('/'))
+}
+
+/// POST `body` to `url`, retrying connection errors, `429` and `5xx`
+/// responses following `retry`
+///
+/// # Returns
+///
+/// The result of the last attempt, and the number of attempts made
+#[cfg(feature = "dead-letter-http")]
+pub(crate) fn post_with_retry(
+    agent: &ureq::Agent,
+    url: &str,
+    content_type: &str,
+    body: &str,
+    retry: &RetryPolicy,
+) -> (Result<(), Box<ureq::Error>>, u32) {
+    retry.run(
+        || {
+            agent
+                .post(url)
+                .set("Content-Type", content_type)
+                .send_string(body)
+                .map(|_| ())
+                .map_err(Box::new)
+        },
+        |error| match error.as_ref() {
+            ureq::Error::Status(status, _) => *status == 429 || *status >= 500,
+            ureq::Error::Transport(_) => true,
+        },
+    )
+}
+
+/// CSV writer for dead letters
+pub struct DeadLetterWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl DeadLetterWriter<File> {
+    /// Create (or truncate) a dead letter file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(DeadLetterWriter)` - With the header written
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| {
+            format!(
+                "Failed to create dead letter file '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        Self::new(file)
+    }
+}
+
+impl<W: Write> DeadLetterWriter<W> {
+    /// Create a dead letter writer over any output, writing the header
+    pub fn new(output: W) -> Result<Self, String> {
+        let mut writer = Writer::from_writer(output);
+        writer
+            .write_record([
+                "type", "client", "tx", "amount", "code", "kind", "error", "attempts", "source",
+                "line",
+            ])
+            .map_err(|e| format!("Failed to write dead letter header: {}", e))?;
+        Ok(Self { writer })
+    }
+}
+
+impl<W: Write + Send> DeadLetterSink for DeadLetterWriter<W> {
+    fn send(&mut self, letter: DeadLetter) -> Result<(), String> {
+        let record = &letter.record;
+        let tx_type = match record.tx_type {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Approve => "approve",
+            TransactionType::Reject => "reject",
+        };
+        self.writer
+            .write_record([
+                tx_type,
+                &record.client.to_string(),
+                &record.tx.to_string(),
+                &record.amount.map(|a| a.to_string()).unwrap_or_default(),
+                &letter.code.to_string(),
+                letter.kind,
+                &letter.error,
+                &letter.attempts.to_string(),
+                letter.source.as_deref().unwrap_or_default(),
+                &letter.line.map(|l| l.to_string()).unwrap_or_default(),
+            ])
+            .map_err(|e| format!("Failed to write dead letter: {}", e))
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush dead letter file: {}", e))
+    }
+}
+
+/// Number of dead letters a remote sink sends per request
+#[cfg(feature = "dead-letter-http")]
+pub const HTTP_BATCH_SIZE: usize = 100;
+
+/// Dead-letter sink POSTing batches of dead letters over HTTP
+#[cfg(feature = "dead-letter-http")]
+pub struct HttpDeadLetterSink {
+    agent: ureq::Agent,
+    url: String,
+    /// Whether the URL is a Kafka REST Proxy topic
+    kafka: bool,
+    retry: RetryPolicy,
+    pending: Vec<DeadLetter>,
+}
+
+#[cfg(feature = "dead-letter-http")]
+impl HttpDeadLetterSink {
+    /// Sink POSTing JSON arrays of dead letters to `url`
+    pub fn new(url: &str, retry: RetryPolicy) -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+            url: url.to_string(),
+            kafka: false,
+            retry,
+            pending: Vec::with_capacity(HTTP_BATCH_SIZE),
+        }
+    }
+
+    /// Sink producing dead letters to a Kafka topic through the REST Proxy
+    /// at `url`, e.g. `http://proxy:8082/topics/dead-letters`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HttpDeadLetterSink)` - The sink
+    /// * `Err(String)` - If the URL does not name a topic
+    pub fn kafka(url: &str, retry: RetryPolicy) -> Result<Self, String> {
+        if !names_kafka_topic(url) {
+            return Err(format!(
+                "Kafka dead letter target 'kafka+{}' must name a topic, \
+                 e.g. kafka+http://proxy:8082/topics/dead-letters",
+                url
+            ));
+        }
+        Ok(Self {
+            kafka: true,
+            ..Self::new(url, retry)
+        })
+    }
+
+    /// Body and content type of a request delivering `letters`
+    fn request_body(&self, letters: &[DeadLetter]) -> Result<(String, &'static str), String> {
+        let body = if self.kafka {
+            // Keyed by client, so a client's dead letters stay in order
+            let records: Vec<_> = letters
+                .iter()
+                .map(|letter| {
+                    serde_json::json!({
+                        "key": letter.record.client.to_string(),
+                        "value": letter,
+                    })
+                })
+                .collect();
+            serde_json::to_string(&serde_json::json!({ "records": records }))
+        } else {
+            serde_json::to_string(letters)
+        }
+        .map_err(|e| format!("Failed to encode dead letters: {}", e))?;
+        let content_type = if self.kafka {
+            "application/vnd.kafka.json.v2+json"
+        } else {
+            "application/json"
+        };
+        Ok((body, content_type))
+    }
+}
+
+#[cfg(feature = "dead-letter-http")]
+impl DeadLetterSink for HttpDeadLetterSink {
+    fn send(&mut self, letter: DeadLetter) -> Result<(), String> {
+        self.pending.push(letter);
+        if self.pending.len() >= HTTP_BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let (body, content_type) = self.request_body(&self.pending)?;
+        let (result, attempts) =
+            post_with_retry(&self.agent, &self.url, content_type, &body, &self.retry);
+        result.map_err(|e| {
+            format!(
+                "Failed to deliver {} dead letters to '{}' after {} attempts: {}",
+                self.pending.len(),
+                self.url,
+                attempts,
+                e
+            )
+        })?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn letter() -> DeadLetter {
+        DeadLetter::new(
+            TransactionRecord {
+                tx_type: TransactionType::Withdrawal,
+                client: 1,
+                tx: 7,
+                amount: Some(Decimal::new(15, 1)),
+                line: None,
+                source: None,
+            },
+            &PaymentError::insufficient_funds(1, Decimal::ONE, Decimal::new(15, 1)),
+        )
+    }
+
+    #[test]
+    fn test_write_dead_letters() {
+        let mut output = Vec::new();
+        {
+            let mut writer = DeadLetterWriter::new(&mut output).unwrap();
+            writer.send(letter().with_attempts(3)).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(
+            lines.next(),
+            Some("type,client,tx,amount,code,kind,error,attempts,source,line")
+        );
+        let line = lines.next().unwrap();
+        assert!(
+            line.starts_with("withdrawal,1,7,1.5,301,InsufficientFunds,"),
+            "{}",
+            line
+        );
+        assert!(line.ends_with(",3,,"), "{}", line);
+    }
+
+    #[test]
+    fn test_dead_letters_name_their_source() {
+        let record = letter()
+            .record
+            .with_line(4)
+            .with_source(Arc::from("day-2.csv"));
+        let letter = DeadLetter::new(
+            record,
+            &PaymentError::insufficient_funds(1, Decimal::ONE, Decimal::new(15, 1)),
+        );
+
+        let mut output = Vec::new();
+        {
+            let mut writer = DeadLetterWriter::new(&mut output).unwrap();
+            writer.send(letter.clone()).unwrap();
+            writer.flush().unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        let line = output.lines().nth(1).unwrap();
+        assert!(line.ends_with(",1,day-2.csv,4"), "{}", line);
+
+        let json = serde_json::to_value(letter).unwrap();
+        assert_eq!(json["source"], "day-2.csv");
+        assert_eq!(json["line"], 4);
+    }
+
+    #[test]
+    fn test_dead_letter_json() {
+        let json = serde_json::to_value(letter()).unwrap();
+
+        assert_eq!(json["type"], "withdrawal");
+        assert_eq!(json["client"], 1);
+        assert_eq!(json["amount"], "1.5");
+        assert_eq!(json["kind"], "InsufficientFunds");
+        assert_eq!(json["attempts"], 1);
+        assert_eq!(json["source"], serde_json::Value::Null);
+        assert_eq!(json["line"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_create_file_sink() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("dead.csv");
+
+        let mut sink =
+            create_dead_letter_sink(&DeadLetterOptions::new(path.to_str().unwrap())).unwrap();
+        sink.send(letter()).unwrap();
+        sink.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        assert!(
+            create_dead_letter_sink(&DeadLetterOptions::new("missing-dir/dead.csv"))
+                .err()
+                .unwrap()
+                .contains("Failed to create dead letter file")
+        );
+    }
+
+    #[cfg(not(feature = "dead-letter-http"))]
+    #[test]
+    fn test_remote_sinks_require_feature() {
+        for target in ["http://localhost/dead", "kafka+http://proxy/topics/dead"] {
+            let result = create_dead_letter_sink(&DeadLetterOptions::new(target));
+            assert!(result.err().unwrap().contains("'dead-letter-http' feature"));
+        }
+    }
+
+    #[cfg(feature = "dead-letter-http")]
+    mod http {
+        use super::*;
+        use std::io::{BufRead, BufReader, Read};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        /// Serve one request per status, returning the bodies received
+        fn serve(statuses: &'static [u16]) -> (String, std::thread::JoinHandle<Vec<String>>) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let handle = std::thread::spawn(move || {
+                statuses
+                    .iter()
+                    .map(|status| {
+                        let (stream, _) = listener.accept().unwrap();
+                        let mut reader = BufReader::new(stream);
+                        let mut length = 0;
+                        loop {
+                            let mut line = String::new();
+                            reader.read_line(&mut line).unwrap();
+                            if let Some(value) =
+                                line.to_ascii_lowercase().strip_prefix("content-length:")
+                            {
+                                length = value.trim().parse().unwrap();
+                            }
+                            if line == "\r\n" {
+                                break;
+                            }
+                        }
+                        let mut body = vec![0; length];
+                        reader.read_exact(&mut body).unwrap();
+                        write!(
+                            reader.get_mut(),
+                            "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                            status
+                        )
+                        .unwrap();
+                        String::from_utf8(body).unwrap()
+                    })
+                    .collect()
+            });
+            (url, handle)
+        }
+
+        #[test]
+        fn test_http_sink_retries_transient_failures() {
+            let (url, server) = serve(&[503, 200]);
+            let mut sink = HttpDeadLetterSink::new(&url, RetryPolicy::new(3, Duration::ZERO));
+
+            sink.send(letter()).unwrap();
+            sink.flush().unwrap();
+
+            let bodies = server.join().unwrap();
+            assert_eq!(bodies.len(), 2);
+            let json: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+            assert_eq!(json[0]["kind"], "InsufficientFunds");
+        }
+
+        #[test]
+        fn test_http_sink_gives_up_on_permanent_failures() {
+            let (url, server) = serve(&[400]);
+            let mut sink = HttpDeadLetterSink::new(&url, RetryPolicy::new(3, Duration::ZERO));
+
+            sink.send(letter()).unwrap();
+            let err = sink.flush().unwrap_err();
+
+            assert!(err.contains("after 1 attempts"), "{}", err);
+            assert_eq!(server.join().unwrap().len(), 1);
+        }
+
+        #[test]
+        fn test_kafka_sink_produces_keyed_records() {
+            let (url, server) = serve(&[200]);
+            let target = format!("kafka+{}/topics/dead-letters", url);
+            let mut sink = create_dead_letter_sink(&DeadLetterOptions::new(target)).unwrap();
+
+            sink.send(letter()).unwrap();
+            sink.flush().unwrap();
+
+            let bodies = server.join().unwrap();
+            let json: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+            assert_eq!(json["records"][0]["key"], "1");
+            assert_eq!(json["records"][0]["value"]["tx"], 7);
+        }
+
+        #[test]
+        fn test_kafka_target_must_name_topic() {
+            let result =
+                create_dead_letter_sink(&DeadLetterOptions::new("kafka+http://proxy:8082"));
+            assert!(result.err().unwrap().contains("must name a topic"));
+        }
+    }
+}
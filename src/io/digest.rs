@@ -0,0 +1,152 @@
+//! SHA-256 digests of inputs and account output
+//!
+//! Run manifests (`--manifest`) identify the inputs and the output of a run
+//! by their SHA-256 digests. Inputs are digested as read from their files or
+//! objects. The output is digested by `DigestSink`, which wraps the sink of
+//! the run and digests the accounts written to it in the standard CSV format,
+//! or in the selected `AccountLayout`, so the digest is the same
+//! whatever the sink writes them to.
+
+use crate::io::csv_format::{write_accounts_csv, AccountLayout};
+use crate::io::object_storage::open_input;
+use crate::io::sink::AccountSink;
+use crate::types::Account;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Lowercase hex encoding of `bytes`
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+/// SHA-256 digest of everything read from `input`, in hex
+pub fn sha256_hex(mut input: impl Read) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut input, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// SHA-256 digest of an input file or object, in hex
+///
+/// # Returns
+///
+/// * `Ok(String)` - The digest of the input's contents
+/// * `Err(String)` - If the input cannot be opened or read
+pub fn digest_input(path: &Path) -> Result<String, String> {
+    sha256_hex(open_input(path)?).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))
+}
+
+/// Digest of the accounts written to a `DigestSink`, readable after the sink
+/// has been handed to a strategy
+#[derive(Debug, Clone, Default)]
+pub struct OutputDigest(Arc<Mutex<Sha256>>);
+
+impl OutputDigest {
+    /// SHA-256 digest of the accounts written so far, in hex
+    pub fn hex(&self) -> String {
+        hex(&self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .finalize())
+    }
+}
+
+/// Sink digesting the accounts written to another sink
+pub struct DigestSink {
+    inner: Box<dyn AccountSink>,
+    digest: OutputDigest,
+}
+
+impl DigestSink {
+    /// Wrap `inner`, digesting every set of accounts written to it
+    pub fn new(inner: Box<dyn AccountSink>) -> Self {
+        Self {
+            inner,
+            digest: OutputDigest::default(),
+        }
+    }
+
+    /// The digest of the accounts written to this sink
+    pub fn digest(&self) -> OutputDigest {
+        self.digest.clone()
+    }
+}
+
+impl DigestSink {
+    /// Add CSV output to the digest
+    fn digest_csv(&self, csv: &[u8]) -> Result<(), String> {
+        self.digest
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(csv)
+            .map_err(|e| format!("Failed to digest output: {}", e))
+    }
+}
+
+impl AccountSink for DigestSink {
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        let mut csv = Vec::new();
+        write_accounts_csv(accounts, &mut csv)?;
+        self.digest_csv(&csv)?;
+        self.inner.write_accounts(accounts)
+    }
+
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        let mut csv = Vec::new();
+        csv.write_layout(accounts, layout)?;
+        self.digest_csv(&csv)?;
+        self.inner.write_layout(accounts, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 of the empty input
+    const EMPTY: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(sha256_hex(&b""[..]).unwrap(), EMPTY);
+        assert_eq!(
+            sha256_hex(&b"abc"[..]).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_digest_input() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("input.csv");
+        std::fs::write(&path, "").unwrap();
+
+        assert_eq!(digest_input(&path).unwrap(), EMPTY);
+        assert!(digest_input(&dir.path().join("missing.csv")).is_err());
+    }
+
+    #[test]
+    fn test_digest_sink_digests_standard_csv() {
+        let accounts = [Account::new(2), Account::new(1)];
+        let mut sink = DigestSink::new(Box::new(Vec::new()));
+        let digest = sink.digest();
+        assert_eq!(digest.hex(), EMPTY);
+
+        sink.write_accounts(&accounts).unwrap();
+
+        let mut csv = Vec::new();
+        write_accounts_csv(&accounts, &mut csv).unwrap();
+        assert_eq!(digest.hex(), sha256_hex(&csv[..]).unwrap());
+    }
+}
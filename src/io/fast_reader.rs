@@ -0,0 +1,348 @@
+//! Fast reader for well-formed transaction CSV
+//!
+//! Transaction files are mostly machine-written: one record per line and no
+//! quoted fields. `FastCsvReader` reads such files by splitting lines and
+//! fields with SIMD-accelerated byte searches (`memchr`) instead of running
+//! the general CSV state machine over every byte, and parses the fields in
+//! place like `SyncReader`.
+//!
+//! Anything the splitter does not handle is an anomaly: a quote, which may
+//! start a quoted field spanning several lines, a carriage return inside a
+//! line, or a header that is not UTF-8. On the first anomaly the reader hands
+//! the header, the anomalous line and the rest of the input to the flexible
+//! `SyncReader`, so every file reads as it does with `SyncReader`, only faster
+//! while it is well-formed.
+
+use crate::io::csv_format::{CsvColumns, CsvDialect};
+use crate::io::csv_schema::validate_header;
+use crate::io::sync_reader::SyncReader;
+use crate::types::TransactionRecord;
+use memchr::{memchr, memchr2, memchr_iter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Chain, Cursor, Read};
+use std::path::Path;
+
+/// Size of the input buffer lines are split in
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Input of the flexible reader after a fallback: the replayed header and
+/// anomalous line, then the unread input
+type FallbackInput<R> = Chain<Cursor<Vec<u8>>, BufReader<R>>;
+
+#[derive(Debug)]
+enum State<R: Read> {
+    /// Splitting lines of well-formed input
+    Fast(BufReader<R>),
+    /// Reading the rest of the input with the flexible reader
+    Fallback(Box<SyncReader<FallbackInput<R>>>),
+    /// Stopped after an I/O error
+    Done,
+}
+
+/// CSV reader splitting well-formed input with `memchr`
+///
+/// Yields the same records and errors as `SyncReader` over the same input.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rust_payments_engine::io::FastCsvReader;
+/// use std::path::Path;
+///
+/// let reader = FastCsvReader::new(Path::new("transactions.csv")).unwrap();
+/// let records: Vec<_> = reader.filter_map(Result::ok).collect();
+/// println!("Successfully parsed {} records", records.len());
+/// ```
+#[derive(Debug)]
+pub struct FastCsvReader<R: Read = File> {
+    state: State<R>,
+    dialect: CsvDialect,
+    /// Positions of the schema columns in the header
+    columns: CsvColumns,
+    /// Header line as read, replayed to the flexible reader on a fallback
+    header: Vec<u8>,
+    /// Buffer for the current line, reused for every record
+    line: Vec<u8>,
+    /// Bounds of the current line's fields, reused for every record
+    fields: Vec<(usize, usize)>,
+    line_num: usize,
+}
+
+impl FastCsvReader<File> {
+    /// Open a CSV file for fast reading
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(FastCsvReader)` if the file opened and its header is valid
+    /// * `Err(String)` if the file could not be opened or its header is invalid
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open file '{}': {}", path.display(), e))?;
+        Self::from_reader(file)
+    }
+}
+
+impl<R: Read> FastCsvReader<R> {
+    /// Create a fast reader over any byte source in the standard dialect
+    pub fn from_reader(input: R) -> Result<Self, String> {
+        Self::with_dialect(input, CsvDialect::default())
+    }
+
+    /// Create a fast reader over a byte source written in the given dialect
+    ///
+    /// The header is read and checked against the schema (see `csv_schema`)
+    /// before any record, after renaming header names with an alias in the
+    /// dialect to their standard column.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(FastCsvReader)` if the header is valid
+    /// * `Err(String)` if the header cannot be read or does not match the schema
+    pub fn with_dialect(input: R, dialect: CsvDialect) -> Result<Self, String> {
+        let mut input = BufReader::with_capacity(BUFFER_SIZE, input);
+        // Like the flexible reader, skip empty lines before the header
+        let mut header = Vec::new();
+        let mut start = 0;
+        while read_line(&mut input, &mut header)
+            .map_err(|e| format!("Failed to read CSV header: {}", e))?
+            > 0
+            && trim_line_end(&header[start..]).is_empty()
+        {
+            start = header.len();
+        }
+
+        let mut reader = Self {
+            state: State::Fast(input),
+            dialect,
+            columns: CsvColumns::default(),
+            header,
+            line: Vec::new(),
+            fields: Vec::new(),
+            line_num: 0,
+        };
+
+        let names = trim_line_end(&reader.header[start..]);
+        let names = names.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(names);
+        match std::str::from_utf8(names) {
+            Ok(names) if !is_anomalous(names.as_bytes()) => {
+                let headers: Vec<&str> = if names.is_empty() {
+                    Vec::new()
+                } else {
+                    names
+                        .split(char::from(reader.dialect.delimiter))
+                        .map(|name| reader.dialect.column_name(name.trim()))
+                        .collect()
+                };
+                validate_header(headers.iter().copied())?;
+                reader.columns = CsvColumns::locate(headers)?;
+            }
+            _ => reader.fall_back(Vec::new())?,
+        }
+        Ok(reader)
+    }
+
+    /// Whether the reader has fallen back to the flexible reader
+    pub fn is_fallback(&self) -> bool {
+        matches!(self.state, State::Fallback(_))
+    }
+
+    /// Hand the header, `line` and the unread input to the flexible reader
+    fn fall_back(&mut self, line: Vec<u8>) -> Result<(), String> {
+        let State::Fast(input) = std::mem::replace(&mut self.state, State::Done) else {
+            return Ok(());
+        };
+        let mut replay = std::mem::take(&mut self.header);
+        replay.extend_from_slice(&line);
+        let reader =
+            SyncReader::with_dialect(Cursor::new(replay).chain(input), self.dialect.clone())?
+                .with_records_read(self.line_num);
+        self.state = State::Fallback(Box::new(reader));
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for FastCsvReader<R> {
+    type Item = Result<TransactionRecord, String>;
+
+    /// Get the next transaction record
+    ///
+    /// Splits the next non-empty line at the delimiter and parses its fields
+    /// with the reader's CsvDialect, or continues with the flexible reader
+    /// from an anomalous line on.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Ok(TransactionRecord))` - Successfully parsed record
+    /// * `Some(Err(String))` - Parse or conversion error with line number
+    /// * `None` - End of input reached
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let input = match &mut self.state {
+                State::Fast(input) => input,
+                State::Fallback(reader) => return reader.next(),
+                State::Done => return None,
+            };
+
+            self.line.clear();
+            match read_line(input, &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    self.state = State::Done;
+                    self.line_num += 1;
+                    return Some(Err(format!(
+                        "Line {}: CSV parse error: {}",
+                        self.line_num + 1,
+                        e
+                    )));
+                }
+            }
+
+            let line = trim_line_end(&self.line);
+            if line.is_empty() {
+                continue;
+            }
+            if is_anomalous(line) {
+                let line = std::mem::take(&mut self.line);
+                if let Err(e) = self.fall_back(line) {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+
+            self.line_num += 1;
+            self.fields.clear();
+            let mut start = 0;
+            for end in memchr_iter(self.dialect.delimiter, line) {
+                self.fields.push((start, end));
+                start = end + 1;
+            }
+            self.fields.push((start, line.len()));
+
+            return Some(
+                self.dialect
+                    .parse_fields(&self.columns, |position| {
+                        self.fields
+                            .get(position)
+                            .map(|&(start, end)| &line[start..end])
+                    })
+                    .map(|record| record.with_line(self.line_num as u64 + 1))
+                    .map_err(|e| format!("Line {}: {}", self.line_num + 1, e)),
+            );
+        }
+    }
+}
+
+/// Append the next line, including its `\n`, to `line`
+///
+/// # Returns
+///
+/// The number of bytes appended, zero at the end of the input
+fn read_line<R: Read>(input: &mut BufReader<R>, line: &mut Vec<u8>) -> io::Result<usize> {
+    let start = line.len();
+    loop {
+        let buffer = match input.fill_buf() {
+            Ok(buffer) => buffer,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buffer.is_empty() {
+            return Ok(line.len() - start);
+        }
+        match memchr(b'\n', buffer) {
+            Some(end) => {
+                line.extend_from_slice(&buffer[..=end]);
+                input.consume(end + 1);
+                return Ok(line.len() - start);
+            }
+            None => {
+                let read = buffer.len();
+                line.extend_from_slice(buffer);
+                input.consume(read);
+            }
+        }
+    }
+}
+
+/// A line without its `\n` or `\r\n` terminator
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Whether a line needs the flexible reader: a quote may start a quoted
+/// field, and a carriage return ends a record in CSV
+fn is_anomalous(line: &[u8]) -> bool {
+    memchr2(b'"', b'\r', line).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::csv_format::DecimalSeparator;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::well_formed("type,client,tx,amount\ndeposit,1,1,1.5\nwithdrawal,1,2,0.5\n", false)]
+    #[case::crlf_and_spaces(
+        "type, client, tx, amount\r\ndeposit , 1, 1 , 1.5\r\n\r\ndispute,1,1,\r\n",
+        false
+    )]
+    #[case::no_final_newline("type,client,tx,amount\ndeposit,1,1,1.5", false)]
+    #[case::short_rows_and_errors(
+        "type,client,tx,amount\ndispute,1,1\ndeposit,x,2,1\ntransfer,1,3,1\n",
+        false
+    )]
+    #[case::reordered_header("\u{feff}tx,amount,client,type\n1,2.5,7,deposit\n", false)]
+    #[case::empty("", false)]
+    #[case::blank_lines_before_header("\n\r\ntype,client,tx,amount\n\ndeposit,1,1,1\n", false)]
+    #[case::quoted_field(
+        "type,client,tx,amount\ndeposit,1,1,1.5\n\"deposit\",2,2,\"2.5\"\ndeposit,3,3,3\n",
+        true
+    )]
+    #[case::multiline_quote("type,client,tx,amount\ndeposit,1,1,\"1\n.5\"\ndeposit,2,2,2\n", true)]
+    #[case::quoted_header("\"type\",client,tx,amount\ndeposit,1,1,1.5\n", true)]
+    #[case::carriage_return("type,client,tx,amount\ndeposit,1,1,1\rdeposit,2,2,2\n", true)]
+    fn test_matches_sync_reader(#[case] input: &str, #[case] fallback: bool) {
+        let mut reader = FastCsvReader::from_reader(input.as_bytes()).unwrap();
+        let expected: Vec<_> = SyncReader::from_reader(input.as_bytes()).unwrap().collect();
+
+        assert_eq!(reader.by_ref().collect::<Vec<_>>(), expected);
+        assert_eq!(reader.is_fallback(), fallback);
+    }
+
+    #[test]
+    fn test_line_numbers_continue_after_fallback() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1\n\"deposit\",1,2,1\ndeposit,1,x,1\n";
+        let errors: Vec<_> = FastCsvReader::from_reader(input.as_bytes())
+            .unwrap()
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Line 4:"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_dialect() {
+        let input = "kind;client;tx;amount\ndeposit;1;1;1234,5\n";
+        let dialect = CsvDialect::default()
+            .with_delimiter(b';')
+            .with_decimal_separator(DecimalSeparator::Comma)
+            .with_header_alias("kind=type".parse().unwrap());
+
+        let records: Vec<_> = FastCsvReader::with_dialect(input.as_bytes(), dialect)
+            .unwrap()
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].as_ref().unwrap().amount,
+            Some(rust_decimal::Decimal::new(12345, 1))
+        );
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let err = FastCsvReader::from_reader("type,clinet,tx,amount\n".as_bytes()).unwrap_err();
+        assert!(err.contains("unknown column 'clinet'"), "{}", err);
+    }
+}
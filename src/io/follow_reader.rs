@@ -0,0 +1,209 @@
+//! CSV reader for files that are still being written
+//!
+//! `FollowReader` tails a CSV file like `tail -f`: each call to `poll` returns
+//! the records appended since the previous call. Only complete lines are
+//! parsed; a trailing line without its newline is kept until the writer
+//! finishes it, so a record is never read half-written.
+
+use crate::io::csv_format::{CsvColumns, CsvDialect};
+use crate::io::csv_schema::validate_header;
+use crate::types::TransactionRecord;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Reader returning the records appended to a growing CSV file
+#[derive(Debug)]
+pub struct FollowReader {
+    file: File,
+    /// Bytes read after the last complete line
+    pending: Vec<u8>,
+    dialect: CsvDialect,
+    /// Positions of the schema columns, once the header has been read
+    columns: Option<CsvColumns>,
+    line_num: usize,
+}
+
+impl FollowReader {
+    /// Open a CSV file for following, starting at its beginning
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(FollowReader)` if the file opened successfully
+    /// * `Err(String)` if the file could not be opened
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open file '{}': {}", path.display(), e))?;
+        Ok(Self {
+            file,
+            pending: Vec::new(),
+            dialect: CsvDialect::default(),
+            columns: None,
+            line_num: 0,
+        })
+    }
+
+    /// Read the file in the given dialect instead of the standard one
+    pub fn with_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Read the records appended since the last call
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec)` - One result per complete row; empty if nothing new was written.
+    ///   Rows that fail to parse are returned as `Err` with their line number.
+    /// * `Err(String)` - If the file could not be read, or its header does not
+    ///   match the schema (see `csv_schema`)
+    pub fn poll(&mut self) -> Result<Vec<Result<TransactionRecord, String>>, String> {
+        self.file
+            .read_to_end(&mut self.pending)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+
+        let Some(end) = self.pending.iter().rposition(|&byte| byte == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.pending.drain(..=end).collect();
+
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(self.dialect.delimiter)
+            .trim(Trim::All)
+            .flexible(true)
+            .from_reader(complete.as_slice());
+
+        let mut records = Vec::new();
+        let mut row = StringRecord::new();
+        loop {
+            match reader.read_record(&mut row) {
+                Ok(true) => self.line_num += 1,
+                Ok(false) => break,
+                Err(e) => {
+                    self.line_num += 1;
+                    records.push(Err(format!(
+                        "Line {}: CSV parse error: {}",
+                        self.line_num, e
+                    )));
+                    continue;
+                }
+            }
+
+            match &self.columns {
+                None => {
+                    let headers: StringRecord = row
+                        .iter()
+                        .map(|header| self.dialect.column_name(header))
+                        .collect();
+                    validate_header(&headers)?;
+                    self.columns = Some(CsvColumns::locate(headers.iter())?);
+                }
+                Some(columns) => records.push(
+                    self.dialect
+                        .parse_fields(columns, |position| row.get(position).map(str::as_bytes))
+                        .map(|record| record.with_line(self.line_num as u64))
+                        .map_err(|e| format!("Line {}: {}", self.line_num, e)),
+                ),
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::csv_format::DecimalSeparator;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn append(file: &mut NamedTempFile, content: &str) {
+        file.write_all(content.as_bytes()).unwrap();
+        file.flush().unwrap();
+    }
+
+    #[test]
+    fn test_poll_returns_appended_records() {
+        let mut file = NamedTempFile::new().unwrap();
+        append(&mut file, "type,client,tx,amount\ndeposit,1,1,1.0\n");
+        let mut reader = FollowReader::open(file.path()).unwrap();
+
+        assert_eq!(reader.poll().unwrap().len(), 1);
+        assert!(reader.poll().unwrap().is_empty());
+
+        append(&mut file, "deposit,1,2,2.0\nwithdrawal,1,3,0.5\n");
+        let records = reader.poll().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].as_ref().unwrap().tx, 3);
+    }
+
+    #[test]
+    fn test_poll_waits_for_complete_lines() {
+        let mut file = NamedTempFile::new().unwrap();
+        append(&mut file, "type,client,tx,amount\ndeposit,1,1,10");
+        let mut reader = FollowReader::open(file.path()).unwrap();
+
+        assert!(reader.poll().unwrap().is_empty());
+
+        append(&mut file, ".5\n");
+        let records = reader.poll().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].as_ref().unwrap().amount,
+            Some("10.5".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_poll_reports_invalid_rows_with_line_numbers() {
+        let mut file = NamedTempFile::new().unwrap();
+        append(
+            &mut file,
+            "type,client,tx,amount\ndeposit,1,1,1.0\nbogus,1,2,1.0\n",
+        );
+        let mut reader = FollowReader::open(file.path()).unwrap();
+
+        let records = reader.poll().unwrap();
+        assert!(records[0].is_ok());
+        assert!(records[1].as_ref().unwrap_err().starts_with("Line 3:"));
+    }
+
+    #[test]
+    fn test_poll_with_dialect() {
+        let mut file = NamedTempFile::new().unwrap();
+        append(&mut file, "type;client;transaction_id;amount\n");
+        let dialect = CsvDialect::default()
+            .with_delimiter(b';')
+            .with_decimal_separator(DecimalSeparator::Comma)
+            .with_header_alias("transaction_id=tx".parse().unwrap());
+        let mut reader = FollowReader::open(file.path())
+            .unwrap()
+            .with_dialect(dialect);
+
+        assert!(reader.poll().unwrap().is_empty());
+
+        append(&mut file, "deposit;1;4;2,75\n");
+        let records = reader.poll().unwrap();
+        let record = records[0].as_ref().unwrap();
+        assert_eq!(record.tx, 4);
+        assert_eq!(record.amount, Some("2.75".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_poll_rejects_invalid_header() {
+        let mut file = NamedTempFile::new().unwrap();
+        append(&mut file, "type,client,tx\ndeposit,1,1,1.0\n");
+        let mut reader = FollowReader::open(file.path()).unwrap();
+
+        let err = reader.poll().unwrap_err();
+        assert_eq!(err, "Invalid CSV header: missing 'amount'");
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let err = FollowReader::open(Path::new("nonexistent.csv")).unwrap_err();
+        assert!(err.contains("Failed to open file"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Balance history output
+//!
+//! Every balance sample recorded by the engine is written as one CSV line,
+//! ready for charting a client's running balance:
+//!
+//! ```text
+//! client,transactions,available,held,total,locked
+//! 1,100,1520.0000,0.0000,1520.0000,false
+//! 1,200,310.5000,40.0000,350.5000,false
+//! ```
+//!
+//! `transactions` counts the client's applied transactions, so the lines of
+//! one client are ordered by it even when clients are processed concurrently.
+
+use crate::core::BalancePoint;
+use csv::Writer;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// CSV writer for balance history samples
+pub struct BalanceHistoryWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl BalanceHistoryWriter<File> {
+    /// Create (or truncate) a balance history file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(BalanceHistoryWriter)` - With the header written
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| {
+            format!(
+                "Failed to create balance history file '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        Self::new(file)
+    }
+}
+
+impl<W: Write> BalanceHistoryWriter<W> {
+    /// Create a balance history writer over any output, writing the header
+    pub fn new(output: W) -> Result<Self, String> {
+        let mut writer = Writer::from_writer(output);
+        writer
+            .write_record([
+                "client",
+                "transactions",
+                "available",
+                "held",
+                "total",
+                "locked",
+            ])
+            .map_err(|e| format!("Failed to write balance history header: {}", e))?;
+        Ok(Self { writer })
+    }
+
+    /// Write one balance sample
+    pub fn write(&mut self, point: &BalancePoint) -> Result<(), String> {
+        self.writer
+            .write_record([
+                point.client.to_string(),
+                point.transactions.to_string(),
+                format!("{:.4}", point.available),
+                format!("{:.4}", point.held),
+                format!("{:.4}", point.total),
+                point.locked.to_string(),
+            ])
+            .map_err(|e| format!("Failed to write balance history line: {}", e))
+    }
+
+    /// Flush buffered lines to the output
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush balance history file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_samples() {
+        let mut output = Vec::new();
+        {
+            let mut writer = BalanceHistoryWriter::new(&mut output).unwrap();
+            writer
+                .write(&BalancePoint {
+                    client: 1,
+                    transactions: 100,
+                    available: Decimal::new(3105, 1),
+                    held: Decimal::from(40),
+                    total: Decimal::new(3505, 1),
+                    locked: false,
+                })
+                .unwrap();
+            writer.flush().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,transactions,available,held,total,locked\n\
+             1,100,310.5000,40.0000,350.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_create_in_missing_directory_fails() {
+        let result = BalanceHistoryWriter::create(Path::new("missing-dir/history.csv"));
+        assert!(result
+            .err()
+            .unwrap()
+            .contains("Failed to create balance history file"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Double-entry journal output
+//!
+//! Every posting recorded by the engine is written as two journal lines, one
+//! debiting and one crediting the same amount, so the journal of any
+//! transaction (and of the whole run) balances:
+//!
+//! ```text
+//! tx,type,account,contra_account,debit,credit
+//! 1,deposit,settlement,client:1:available,10.0000,
+//! 1,deposit,client:1:available,settlement,,10.0000
+//! ```
+//!
+//! The lines can be imported into a general ledger that expects an account,
+//! a contra-account and a debit or credit amount per line.
+//!
+//! The trial balance of a run lists the totals of every ledger account, the
+//! system accounts (settlement and fee collection) in their own section, and
+//! a total line whose balance is always zero:
+//!
+//! ```text
+//! account,section,debit,credit,balance
+//! settlement,system,10.0000,0.0000,-10.0000
+//! client:1:available,client,0.0000,10.0000,10.0000
+//! total,,10.0000,10.0000,0.0000
+//! ```
+
+use crate::core::{Posting, TrialBalance};
+use crate::types::TransactionType;
+use csv::Writer;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// CSV writer for journal lines
+pub struct JournalWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl JournalWriter<File> {
+    /// Create (or truncate) a journal file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(JournalWriter)` - With the header written
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create journal file '{}': {}", path.display(), e))?;
+        Self::new(file)
+    }
+}
+
+impl<W: Write> JournalWriter<W> {
+    /// Create a journal writer over any output, writing the header
+    pub fn new(output: W) -> Result<Self, String> {
+        let mut writer = Writer::from_writer(output);
+        writer
+            .write_record(["tx", "type", "account", "contra_account", "debit", "credit"])
+            .map_err(|e| format!("Failed to write journal header: {}", e))?;
+        Ok(Self { writer })
+    }
+
+    /// Write the debit and credit lines of a posting
+    pub fn write(&mut self, posting: &Posting) -> Result<(), String> {
+        let tx_type = match posting.tx_type {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Approve => "approve",
+            TransactionType::Reject => "reject",
+        };
+        let tx = posting.tx.to_string();
+        let (debit, credit) = (posting.debit.to_string(), posting.credit.to_string());
+        let amount = format!("{:.4}", posting.amount);
+        for line in [
+            [&tx, tx_type, &debit, &credit, &amount, ""],
+            [&tx, tx_type, &credit, &debit, "", &amount],
+        ] {
+            self.writer
+                .write_record(line)
+                .map_err(|e| format!("Failed to write journal line: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Flush buffered lines to the output
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush journal file: {}", e))
+    }
+}
+
+/// CSV writer for the trial balance of a run
+pub struct TrialBalanceWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl TrialBalanceWriter<File> {
+    /// Create (or truncate) a trial balance file
+    ///
+    /// The file is created up front, so a bad path fails the run before any
+    /// record is processed; the trial balance itself is written at the end.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TrialBalanceWriter)` - Ready to write the trial balance
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| {
+            format!(
+                "Failed to create trial balance file '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write> TrialBalanceWriter<W> {
+    /// Create a trial balance writer over any output
+    pub fn new(output: W) -> Self {
+        Self {
+            writer: Writer::from_writer(output),
+        }
+    }
+
+    /// Write the header, one line per account and the total line, and flush
+    pub fn write(&mut self, trial_balance: &TrialBalance) -> Result<(), String> {
+        let mut lines = vec![[
+            "account".to_string(),
+            "section".to_string(),
+            "debit".to_string(),
+            "credit".to_string(),
+            "balance".to_string(),
+        ]];
+        for row in trial_balance.rows() {
+            let section = if row.system { "system" } else { "client" };
+            lines.push([
+                row.account.to_string(),
+                section.to_string(),
+                format!("{:.4}", row.debit),
+                format!("{:.4}", row.credit),
+                format!("{:.4}", row.balance()),
+            ]);
+        }
+        let (debit, credit) = trial_balance.totals();
+        lines.push([
+            "total".to_string(),
+            String::new(),
+            format!("{:.4}", debit),
+            format!("{:.4}", credit),
+            format!("{:.4}", credit.saturating_sub(debit)),
+        ]);
+        for line in lines {
+            self.writer
+                .write_record(line)
+                .map_err(|e| format!("Failed to write trial balance line: {}", e))?;
+        }
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush trial balance file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_balanced_lines() {
+        let mut output = Vec::new();
+        {
+            let mut writer = JournalWriter::new(&mut output).unwrap();
+            for posting in [
+                Posting::new(TransactionType::Deposit, 1, 1, Decimal::new(105, 1)),
+                Posting::new(TransactionType::Dispute, 1, 1, Decimal::new(105, 1)),
+            ] {
+                writer.write(&posting).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "tx,type,account,contra_account,debit,credit\n\
+             1,deposit,settlement,client:1:available,10.5000,\n\
+             1,deposit,client:1:available,settlement,,10.5000\n\
+             1,dispute,client:1:available,client:1:held,10.5000,\n\
+             1,dispute,client:1:held,client:1:available,,10.5000\n"
+        );
+    }
+
+    #[test]
+    fn test_write_trial_balance() {
+        let mut trial_balance = TrialBalance::new();
+        trial_balance.record(&Posting::new(
+            TransactionType::Deposit,
+            1,
+            1,
+            Decimal::new(105, 1),
+        ));
+        trial_balance.record(&Posting::fee(
+            TransactionType::Deposit,
+            1,
+            1,
+            9,
+            Decimal::new(5, 1),
+        ));
+        let mut output = Vec::new();
+        TrialBalanceWriter::new(&mut output)
+            .write(&trial_balance)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "account,section,debit,credit,balance\n\
+             settlement,system,10.5000,0.0000,-10.5000\n\
+             client:9:available,system,0.0000,0.5000,0.5000\n\
+             client:1:available,client,0.5000,10.5000,10.0000\n\
+             total,,11.0000,11.0000,0.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_create_in_missing_directory_fails() {
+        let result = JournalWriter::create(Path::new("missing-dir/journal.csv"));
+        assert!(result
+            .err()
+            .unwrap()
+            .contains("Failed to create journal file"));
+    }
+}
@@ -0,0 +1,375 @@
+//! Warning and error lines on stderr
+//!
+//! Every warning, rejected record, account event and fatal error of a run is
+//! written to stderr through `log`. By default a line is plain text. With
+//! `LogFormat::Json` (`--log-format json`) every line is a JSON object
+//! instead, so a log pipeline can read the error code, transaction, client,
+//! input file and input line without parsing the message:
+//!
+//! ```text
+//! {"level":"error","code":"E301","tx":2,"client":1,"source":null,"line":3,"message":"Transaction processing error: Line 3: [E301] Insufficient funds for client 1: available 1.0, requested 5.0"}
+//! ```
+//!
+//! Fields that do not apply to a line, or are not known, are `null`; the
+//! `message` is the text line. The format is set once for the whole process
+//! by `set_log_format`, since lines are written from the readers and the
+//! engines' worker tasks as well as from the strategies.
+//!
+//! A malformed input can produce millions of identical errors. With
+//! `set_max_lines_per_class` (`--max-errors-per-class`), only the first lines
+//! of each class of error, such as `InsufficientFunds` or `ParseError`, are
+//! written; `log_suppressed` then writes how many lines of each class were
+//! suppressed. Lines without a class, such as warnings and fatal errors, are
+//! always written.
+
+use crate::io::ClientPseudonymizer;
+use crate::types::{ClientId, PaymentError, TransactionId};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Whether lines are written as JSON
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Lines logged per class, and how many of them are written
+static CLASS_LIMIT: Mutex<ClassLimit> = Mutex::new(ClassLimit::new(None));
+
+/// Format of the lines written to stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Plain text
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "invalid log format '{}': expected 'text' or 'json'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Set the format of every line written by `log` from now on
+pub fn set_log_format(format: LogFormat) {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+/// The format lines are currently written in
+pub fn log_format() -> LogFormat {
+    if JSON.load(Ordering::Relaxed) {
+        LogFormat::Json
+    } else {
+        LogFormat::Text
+    }
+}
+
+/// Severity of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// An event of the run, such as an account being locked
+    Info,
+    /// A problem the run worked around
+    Warning,
+    /// A rejected record, or a fatal error
+    Error,
+}
+
+/// One line written to stderr
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLine {
+    pub level: LogLevel,
+    /// Error code, such as `E301`
+    pub code: Option<String>,
+    /// Transaction the line is about
+    pub tx: Option<TransactionId>,
+    /// Client the line is about
+    pub client: Option<ClientId>,
+    /// Input file the line is about, when the run reads several
+    pub source: Option<String>,
+    /// Input line the line is about
+    pub line: Option<u64>,
+    /// The line as written in the text format
+    pub message: String,
+    /// Class of error the line reports, for `--max-errors-per-class`
+    #[serde(skip)]
+    pub class: Option<&'static str>,
+}
+
+impl LogLine {
+    /// Create a line with no code, transaction, client or input line
+    pub fn new(level: LogLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            code: None,
+            tx: None,
+            client: None,
+            source: None,
+            line: None,
+            message: message.into(),
+            class: None,
+        }
+    }
+
+    /// Create an info line
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(LogLevel::Info, message)
+    }
+
+    /// Create a warning line
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(LogLevel::Warning, message)
+    }
+
+    /// Create an error line
+    ///
+    /// A message containing the `Line N:` of a reader's parse error gets that
+    /// input line.
+    pub fn error(message: impl Into<String>) -> Self {
+        let message = message.into();
+        let line = input_line(&message);
+        Self {
+            line,
+            ..Self::new(LogLevel::Error, message)
+        }
+    }
+
+    /// Set the error code of a processing error, e.g. `E301`, and its kind
+    /// as the class
+    pub fn with_error_code(mut self, error: &PaymentError) -> Self {
+        self.code = Some(format!("E{}", error.code()));
+        self.class = Some(error.kind());
+        self
+    }
+
+    /// Set the class of error the line reports, e.g. `ParseError`
+    pub fn with_class(mut self, class: &'static str) -> Self {
+        self.class = Some(class);
+        self
+    }
+
+    /// Set the transaction and client the line is about
+    pub fn with_record(mut self, tx: TransactionId, client: ClientId) -> Self {
+        self.tx = Some(tx);
+        self.client = Some(client);
+        self
+    }
+
+    /// Set the input file and input line the line is about, where known
+    pub fn with_input(mut self, source: Option<&str>, line: Option<u64>) -> Self {
+        self.source = source.map(str::to_string);
+        self.line = line.or(self.line);
+        self
+    }
+
+    /// Set the client the line is about
+    pub fn with_client(mut self, client: ClientId) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Replace the clients named in the message with their pseudonyms, and
+    /// drop the client field, if a pseudonymizer is given
+    pub fn masked(self, pseudonymizer: Option<&ClientPseudonymizer>) -> Self {
+        match pseudonymizer {
+            Some(pseudonymizer) => Self {
+                client: None,
+                message: pseudonymizer.mask_message(&self.message),
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// The line in the given format, without a trailing newline
+    pub fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => self.message.clone(),
+            // Serializing plain fields cannot fail
+            LogFormat::Json => serde_json::to_string(self).unwrap_or_default(),
+        }
+    }
+}
+
+/// Input line number of a reader's parse error (`Line N: ...`)
+fn input_line(message: &str) -> Option<u64> {
+    let rest = message.split_once("Line ")?.1;
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    if !rest[digits..].starts_with(':') {
+        return None;
+    }
+    rest[..digits].parse().ok()
+}
+
+/// Number of lines logged per class, and how many of each are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClassLimit {
+    /// Lines written per class, `None` for all
+    max: Option<u64>,
+    /// Lines logged per class, written or not
+    counts: BTreeMap<&'static str, u64>,
+}
+
+impl ClassLimit {
+    const fn new(max: Option<u64>) -> Self {
+        Self {
+            max,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Count a line of `class`, returning whether it is written
+    fn admit(&mut self, class: &'static str) -> bool {
+        let Some(max) = self.max else {
+            return true;
+        };
+        let count = self.counts.entry(class).or_default();
+        *count += 1;
+        *count <= max
+    }
+
+    /// The classes with suppressed lines, with the number of lines
+    /// suppressed and logged
+    fn suppressed(&self) -> Vec<(&'static str, u64, u64)> {
+        let max = self.max.unwrap_or(u64::MAX);
+        self.counts
+            .iter()
+            .filter(|(_, &count)| count > max)
+            .map(|(&class, &count)| (class, count - max, count))
+            .collect()
+    }
+}
+
+/// Write at most `max` lines of each class of error from now on, or all
+/// lines for `None`
+pub fn set_max_lines_per_class(max: Option<u64>) {
+    *CLASS_LIMIT.lock().unwrap_or_else(PoisonError::into_inner) = ClassLimit::new(max);
+}
+
+/// Write a line to stderr in the format set by `set_log_format`, unless its
+/// class has already had its `set_max_lines_per_class` lines
+pub fn log(line: LogLine) {
+    if let Some(class) = line.class {
+        let admitted = CLASS_LIMIT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .admit(class);
+        if !admitted {
+            return;
+        }
+    }
+    eprintln!("{}", line.format(log_format()));
+}
+
+/// Write how many lines of each class were suppressed so far, one line per
+/// class
+pub fn log_suppressed() {
+    let suppressed = CLASS_LIMIT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .suppressed();
+    for (class, suppressed, count) in suppressed {
+        log(LogLine::info(format!(
+            "Suppressed {} of {} {} lines",
+            suppressed, count, class
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("text".parse(), Ok(LogFormat::Text));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert!("xml".parse::<LogFormat>().unwrap_err().contains("'xml'"));
+    }
+
+    #[test]
+    fn test_format_text() {
+        let line = LogLine::warning("Warning: CSV header: no amount column");
+        assert_eq!(
+            line.format(LogFormat::Text),
+            "Warning: CSV header: no amount column"
+        );
+    }
+
+    #[test]
+    fn test_format_json() {
+        let error = PaymentError::account_locked(7);
+        let line = LogLine::error(format!(
+            "Transaction processing error: {}",
+            error.with_code()
+        ))
+        .with_error_code(&error)
+        .with_record(3, 7);
+        assert_eq!(
+            line.format(LogFormat::Json),
+            "{\"level\":\"error\",\"code\":\"E302\",\"tx\":3,\"client\":7,\"source\":null,\
+             \"line\":null,\"message\":\"Transaction processing error: [E302] Account 7 is locked\"}"
+        );
+    }
+
+    #[test]
+    fn test_with_input() {
+        let line = LogLine::info("Account 7 locked: chargeback of tx 3")
+            .with_input(Some("day-2.csv"), Some(4));
+        assert_eq!(line.source.as_deref(), Some("day-2.csv"));
+        assert_eq!(line.line, Some(4));
+
+        // A line taken from the message is kept if the input line is unknown
+        let line = LogLine::error("Line 9: bad row").with_input(None, None);
+        assert_eq!((line.source, line.line), (None, Some(9)));
+    }
+
+    #[test]
+    fn test_class_limit() {
+        let mut limit = ClassLimit::new(Some(2));
+        let admitted: Vec<bool> = ["ParseError", "AccountLocked", "ParseError", "ParseError"]
+            .into_iter()
+            .map(|class| limit.admit(class))
+            .collect();
+        assert_eq!(admitted, [true, true, true, false]);
+        assert!(limit.admit("AccountLocked"));
+        assert!(!limit.admit("ParseError"));
+        assert_eq!(limit.suppressed(), [("ParseError", 2, 4)]);
+
+        let mut unlimited = ClassLimit::new(None);
+        assert!((0..10).all(|_| unlimited.admit("ParseError")));
+        assert!(unlimited.suppressed().is_empty());
+    }
+
+    #[rstest]
+    #[case::reader("Line 42: CSV parse error: bad row", Some(42))]
+    #[case::prefixed("CSV parsing error: Line 7: invalid amount", Some(7))]
+    #[case::no_line("CSV parse error: bad row", None)]
+    #[case::not_a_line_number("Line one: bad row", None)]
+    fn test_error_input_line(#[case] message: &str, #[case] expected: Option<u64>) {
+        assert_eq!(LogLine::error(message).line, expected);
+    }
+}
@@ -0,0 +1,110 @@
+//! Account metadata loading
+//!
+//! Reads the `--accounts-metadata` file: a CSV with a `client` column followed by
+//! one column per metadata key, e.g.
+//!
+//! ```text
+//! client,owner,kyc_status
+//! 1,Alice,verified
+//! 2,Bob,pending
+//! ```
+//!
+//! Empty fields are skipped, so a client only gets the keys it has values for.
+
+use crate::core::MetadataMap;
+use crate::types::{AccountMetadata, ClientId};
+use csv::{ReaderBuilder, Trim};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Read account metadata from a CSV file
+///
+/// # Arguments
+///
+/// * `path` - Path to the metadata file
+///
+/// # Returns
+///
+/// * `Ok(MetadataMap)` - Metadata for every client listed in the file
+/// * `Err(String)` - If the file cannot be read or is malformed
+pub fn read_account_metadata(path: &Path) -> Result<MetadataMap, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open metadata file '{}': {}", path.display(), e))?;
+    parse_account_metadata(file)
+        .map_err(|e| format!("Invalid metadata file '{}': {}", path.display(), e))
+}
+
+/// Parse account metadata from CSV
+fn parse_account_metadata(input: impl Read) -> Result<MetadataMap, String> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(input);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read header: {}", e))?
+        .clone();
+    if headers.get(0) != Some("client") {
+        return Err("first column must be 'client'".to_string());
+    }
+
+    let mut metadata = MetadataMap::new();
+    for (index, result) in reader.records().enumerate() {
+        // Line 1 is the header
+        let line = index + 2;
+        let record = result.map_err(|e| format!("line {}: {}", line, e))?;
+
+        let client_field = record.get(0).unwrap_or_default();
+        let client: ClientId = client_field
+            .parse()
+            .map_err(|e| format!("line {}: invalid client '{}': {}", line, client_field, e))?;
+
+        let fields: AccountMetadata = headers
+            .iter()
+            .zip(record.iter())
+            .skip(1)
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        if metadata.insert(client, Arc::new(fields)).is_some() {
+            return Err(format!("line {}: duplicate client {}", line, client));
+        }
+    }
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse_account_metadata() {
+        let input = "client, owner ,kyc_status\n1,Alice,verified\n2, Bob ,\n";
+
+        let metadata = parse_account_metadata(input.as_bytes()).unwrap();
+
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata[&1]["owner"], "Alice");
+        assert_eq!(metadata[&1]["kyc_status"], "verified");
+        assert_eq!(metadata[&2]["owner"], "Bob");
+        assert!(!metadata[&2].contains_key("kyc_status"));
+    }
+
+    #[rstest]
+    #[case::missing_client_column("owner,client\nAlice,1\n", "first column must be 'client'")]
+    #[case::invalid_client("client,owner\nabc,Alice\n", "line 2: invalid client 'abc'")]
+    #[case::duplicate_client("client,owner\n1,Alice\n1,Bob\n", "line 3: duplicate client 1")]
+    #[case::wrong_field_count("client,owner\n1,Alice,extra\n", "line 2:")]
+    fn test_parse_account_metadata_invalid(#[case] input: &str, #[case] expected: &str) {
+        let err = parse_account_metadata(input.as_bytes()).unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    #[test]
+    fn test_read_account_metadata_missing_file() {
+        let err = read_account_metadata(Path::new("nonexistent.csv")).unwrap_err();
+        assert!(err.contains("Failed to open metadata file"));
+    }
+}
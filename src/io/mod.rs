@@ -1,17 +1,112 @@
 //! I/O module
 //!
-//! Handles CSV parsing and output.
+//! Handles CSV and Avro parsing and account output.
 //!
 //! # Components
 //!
+//! - `changes` - Balance change streams to a file or Kafka topic (`ChangeSink`)
+//! - `client_map` - Client map file reader and registry (external client identifiers)
 //! - `csv_format` - CSV format handling (record conversion, output serialization)
+//! - `csv_schema` - CSV header validation with diagnostics for wrong headers
+//! - `dead_letter` - Dead-letter sinks for records that failed processing (`DeadLetterSink`)
+//! - `digest` - SHA-256 digests of inputs and account output (run manifests)
 //! - `sync_reader` - Synchronous CSV reader with iterator interface
-//! - `async_reader` - Asynchronous CSV reader with batch reading interface
+//! - `fast_reader` - CSV reader splitting well-formed input with memchr (feature `fast-csv`)
+//! - `async_reader` - Asynchronous CSV reader with batch reading interface (feature `native`)
+//! - `follow_reader` - CSV reader for files that are still being appended to
+//! - `avro_reader` - Avro object container file reader (feature `avro`)
+//! - `history` - Balance history writer
+//! - `journal` - Double-entry journal and trial balance writers
+//! - `log` - Warning and error lines on stderr, as text or JSON
+//! - `metadata` - Account metadata file reader
+//! - `object_storage` - Local or object store (S3, GCS, Azure) inputs and object output
+//! - `quarantine` - Quarantine file writer for diverted transactions
+//! - `record_source` - Blocking and async record sources read by the strategies
+//! - `risk_rules` - Risk rules file reader (amount limits, velocity, withdrawal requirements)
+//! - `sink` - Destinations for the final account states (`AccountSink`)
+//! - `postgres_sink` - Postgres upsert sink (feature `postgres`)
+//! - `pseudonym` - Keyed pseudonyms of clients for output and error logs
+//! - `standing_orders` - Standing orders file reader (recurring deposits and withdrawals)
+//! - `warnings` - Warnings file writer for records failing a sanity check
 
+#[cfg(feature = "native")]
 pub mod async_reader;
+#[cfg(feature = "avro")]
+pub mod avro_reader;
+pub mod changes;
+pub mod client_map;
 pub mod csv_format;
+pub mod csv_schema;
+pub mod dead_letter;
+pub mod digest;
+#[cfg(feature = "fast-csv")]
+pub mod fast_reader;
+pub mod follow_reader;
+pub mod history;
+pub mod journal;
+pub mod log;
+pub mod metadata;
+pub mod object_storage;
+#[cfg(feature = "postgres")]
+pub mod postgres_sink;
+pub mod pseudonym;
+pub mod quarantine;
+pub mod record_source;
+pub mod risk_rules;
+pub mod sink;
+#[cfg(feature = "native")]
+pub mod standing_orders;
 pub mod sync_reader;
+#[cfg(feature = "native")]
+pub mod warnings;
 
+#[cfg(feature = "native")]
 pub use async_reader::AsyncReader;
-pub use csv_format::{convert_csv_record, write_accounts_csv, CsvRecord};
+#[cfg(feature = "avro")]
+pub use avro_reader::AvroReader;
+#[cfg(feature = "dead-letter-http")]
+pub use changes::KafkaChangeSink;
+pub use changes::{create_change_sink, ChangeSink, ChangeWriter};
+pub use client_map::{read_client_map, ClientMap, ClientRegistry};
+pub use csv_format::{
+    convert_csv_record, read_accounts_csv, write_accounts_csv, write_accounts_csv_layout,
+    write_accounts_csv_mapped, write_accounts_csv_named, AccountColumn, AccountColumns,
+    AccountLayout, AmountFormat, CsvColumns, CsvDialect, CsvRecord, DecimalSeparator, HeaderAlias,
+};
+pub use csv_schema::{validate_header, HeaderDiagnostics};
+#[cfg(feature = "dead-letter-http")]
+pub use dead_letter::HttpDeadLetterSink;
+pub use dead_letter::{
+    create_dead_letter_sink, DeadLetter, DeadLetterOptions, DeadLetterSink, DeadLetterWriter,
+};
+pub use digest::{digest_input, DigestSink, OutputDigest};
+#[cfg(feature = "fast-csv")]
+pub use fast_reader::FastCsvReader;
+pub use follow_reader::FollowReader;
+pub use history::BalanceHistoryWriter;
+pub use journal::{JournalWriter, TrialBalanceWriter};
+pub use log::{
+    log, log_format, log_suppressed, set_log_format, set_max_lines_per_class, LogFormat, LogLevel,
+    LogLine,
+};
+pub use metadata::read_account_metadata;
+pub use object_storage::{check_input, is_object_url, open_input};
+#[cfg(feature = "object-store")]
+pub use object_storage::{ObjectReader, ObjectSink};
+#[cfg(feature = "postgres")]
+pub use postgres_sink::PostgresSink;
+pub use pseudonym::{read_pseudonym_key, ClientPseudonymizer};
+pub use quarantine::QuarantineWriter;
+#[cfg(feature = "native")]
+pub use record_source::{AsyncRecordSource, BlockingBatches};
+pub use record_source::{RecordBatch, RecordSource};
+pub use risk_rules::read_risk_rules;
+pub use sink::{
+    create_mapped_sink, create_pseudonymized_sink, create_sink, create_snapshot_sink, AccountSink,
+    ActivitySink, ClientMapSink, FilteredSink, LayoutSink, LockReasonSink, PseudonymSink,
+};
+#[cfg(feature = "native")]
+pub use standing_orders::read_standing_orders;
 pub use sync_reader::SyncReader;
+#[cfg(feature = "native")]
+pub use warnings::WarningWriter;
@@ -0,0 +1,410 @@
+//! Object store input and output
+//!
+//! Input files and the `--output` target can be object URLs instead of local
+//! paths: `s3://bucket/key`, `gs://bucket/key` or `az://container/key`
+//! (feature `object-store`). Credentials, region and endpoint come from the
+//! environment variables each service's SDK uses (`AWS_ACCESS_KEY_ID`,
+//! `AWS_REGION`, `GOOGLE_SERVICE_ACCOUNT`, `AZURE_STORAGE_ACCOUNT_NAME`, ...).
+//!
+//! # Streaming
+//!
+//! Objects are never downloaded to local disk. A background thread streams the
+//! object into a bounded channel of chunks, which `ObjectReader` exposes as a
+//! `std::io::Read`, so the readers parse records while the download is still
+//! running and memory stays bounded to a few chunks. The thread runs its own
+//! tokio runtime, so the reader works the same from the blocking and the async
+//! strategies.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// URL schemes handled by the object store backends
+const OBJECT_SCHEMES: [&str; 3] = ["s3://", "gs://", "az://"];
+
+/// Returns true if the input or output target is an object store URL
+pub fn is_object_url(target: &str) -> bool {
+    OBJECT_SCHEMES
+        .iter()
+        .any(|scheme| target.starts_with(scheme))
+}
+
+/// Open an input for reading, from a local file or an object store URL
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn Read + Send>)` - An unbuffered reader over the input
+/// * `Err(String)` - If the file cannot be opened, or the input is an object
+///   URL and the crate was built without the `object-store` feature
+pub fn open_input(path: &Path) -> Result<Box<dyn Read + Send>, String> {
+    let location = path.to_string_lossy();
+    if is_object_url(&location) {
+        #[cfg(feature = "object-store")]
+        {
+            return Ok(Box::new(ObjectReader::open(&location)?));
+        }
+        #[cfg(not(feature = "object-store"))]
+        {
+            return Err(feature_error("input"));
+        }
+    }
+
+    let file =
+        File::open(path).map_err(|e| format!("Failed to open file '{}': {}", path.display(), e))?;
+    Ok(Box::new(file))
+}
+
+/// Check that an input exists and can be read, without reading it
+///
+/// # Returns
+///
+/// * `Ok(())` - If the file can be opened or the object exists
+/// * `Err(String)` - Otherwise
+pub fn check_input(path: &Path) -> Result<(), String> {
+    let location = path.to_string_lossy();
+    if is_object_url(&location) {
+        #[cfg(feature = "object-store")]
+        {
+            return ObjectLocation::parse(&location)?.check();
+        }
+        #[cfg(not(feature = "object-store"))]
+        {
+            return Err(feature_error("input"));
+        }
+    }
+
+    File::open(path)
+        .map(drop)
+        .map_err(|e| format!("Failed to open file '{}': {}", path.display(), e))
+}
+
+#[cfg(not(feature = "object-store"))]
+fn feature_error(kind: &str) -> String {
+    format!(
+        "Object store {} requires building with the 'object-store' feature",
+        kind
+    )
+}
+
+/// Error for object store output in a build without the `object-store` feature
+#[cfg(not(feature = "object-store"))]
+pub(crate) fn output_feature_error() -> String {
+    feature_error("output")
+}
+
+#[cfg(feature = "object-store")]
+use self::backend::ObjectLocation;
+#[cfg(feature = "object-store")]
+pub use self::backend::{ObjectReader, ObjectSink};
+
+#[cfg(feature = "object-store")]
+mod backend {
+    use crate::io::csv_format::{write_accounts_csv, AccountLayout};
+    use crate::io::AccountSink;
+    use crate::types::Account;
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use object_store::path::Path as ObjectPath;
+    use object_store::{ObjectStore, PutPayload};
+    use std::future::Future;
+    use std::io::{self, Read};
+    use std::sync::mpsc::{self, Receiver};
+    use std::sync::Arc;
+    use std::thread;
+    use url::Url;
+
+    /// Number of downloaded chunks buffered ahead of the reader
+    const CHUNK_BUFFER: usize = 16;
+
+    /// Run a future to completion on a dedicated thread with its own runtime
+    ///
+    /// A separate thread is needed because the async strategy calls the
+    /// blocking readers from inside its runtime, where starting another
+    /// runtime would panic.
+    fn run<T: Send + 'static>(
+        future: impl Future<Output = Result<T, String>> + Send + 'static,
+    ) -> Result<T, String> {
+        thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to start object store runtime: {}", e))?
+                .block_on(future)
+        })
+        .join()
+        .map_err(|_| "Object store thread panicked".to_string())?
+    }
+
+    /// An object in a store, addressed by URL
+    #[derive(Clone)]
+    pub(super) struct ObjectLocation {
+        url: String,
+        store: Arc<dyn ObjectStore>,
+        path: ObjectPath,
+    }
+
+    impl ObjectLocation {
+        /// Configure the store for an object URL from the environment
+        pub(super) fn parse(url: &str) -> Result<Self, String> {
+            let parsed =
+                Url::parse(url).map_err(|e| format!("Invalid object URL '{}': {}", url, e))?;
+            // The store builders expect lowercase configuration keys
+            let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+            let (store, path) = object_store::parse_url_opts(&parsed, options)
+                .map_err(|e| format!("Failed to configure object store for '{}': {}", url, e))?;
+            Ok(Self {
+                url: url.to_string(),
+                store: Arc::from(store),
+                path,
+            })
+        }
+
+        /// Check that the object exists
+        pub(super) fn check(&self) -> Result<(), String> {
+            let location = self.clone();
+            run(async move {
+                location
+                    .store
+                    .head(&location.path)
+                    .await
+                    .map(drop)
+                    .map_err(|e| format!("Failed to open object '{}': {}", location.url, e))
+            })
+        }
+    }
+
+    /// Streaming reader over an object
+    ///
+    /// Chunks are downloaded by a background thread into a bounded channel. A
+    /// download error is returned from the `read` call that reaches it.
+    pub struct ObjectReader {
+        chunks: Receiver<Result<Bytes, String>>,
+        current: Bytes,
+    }
+
+    impl ObjectReader {
+        /// Start streaming the object at `url`
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(ObjectReader)` - Reading the object from its start
+        /// * `Err(String)` - If the URL or the store configuration is invalid
+        pub fn open(url: &str) -> Result<Self, String> {
+            let location = ObjectLocation::parse(url)?;
+            let (sender, chunks) = mpsc::sync_channel(CHUNK_BUFFER);
+
+            thread::spawn(move || {
+                let download = async {
+                    let result =
+                        location.store.get(&location.path).await.map_err(|e| {
+                            format!("Failed to open object '{}': {}", location.url, e)
+                        })?;
+                    let mut stream = result.into_stream();
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = chunk.map_err(|e| {
+                            format!("Failed to read object '{}': {}", location.url, e)
+                        })?;
+                        if sender.send(Ok(chunk)).is_err() {
+                            // The reader was dropped; stop downloading
+                            break;
+                        }
+                    }
+                    Ok(())
+                };
+                let result = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|e| format!("Failed to start object store runtime: {}", e))
+                    .and_then(|runtime| runtime.block_on(download));
+                if let Err(e) = result {
+                    let _ = sender.send(Err(e));
+                }
+            });
+
+            Ok(Self::from_chunks(chunks))
+        }
+
+        /// Create a reader over chunks produced by a download
+        fn from_chunks(chunks: Receiver<Result<Bytes, String>>) -> Self {
+            Self {
+                chunks,
+                current: Bytes::new(),
+            }
+        }
+    }
+
+    impl Read for ObjectReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.current.is_empty() {
+                match self.chunks.recv() {
+                    Ok(Ok(chunk)) => self.current = chunk,
+                    Ok(Err(e)) => return Err(io::Error::other(e)),
+                    // The download finished and the sender was dropped
+                    Err(_) => return Ok(0),
+                }
+            }
+            let len = buf.len().min(self.current.len());
+            buf[..len].copy_from_slice(&self.current.split_to(len));
+            Ok(len)
+        }
+    }
+
+    /// Account sink writing the CSV output to an object
+    ///
+    /// The object is replaced with each set of accounts written, so follow mode
+    /// keeps only the latest snapshot in it.
+    pub struct ObjectSink {
+        location: ObjectLocation,
+    }
+
+    impl ObjectSink {
+        /// Create a sink for the object at `url`
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(ObjectSink)` - If the URL and store configuration are valid
+        /// * `Err(String)` - Otherwise
+        pub fn new(url: &str) -> Result<Self, String> {
+            Ok(Self {
+                location: ObjectLocation::parse(url)?,
+            })
+        }
+    }
+
+    impl ObjectSink {
+        /// Replace the object with `csv`
+        fn put(&self, csv: Vec<u8>) -> Result<(), String> {
+            let location = self.location.clone();
+            run(async move {
+                location
+                    .store
+                    .put(&location.path, PutPayload::from(csv))
+                    .await
+                    .map(drop)
+                    .map_err(|e| format!("Failed to write object '{}': {}", location.url, e))
+            })
+        }
+    }
+
+    impl AccountSink for ObjectSink {
+        fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+            let mut csv = Vec::new();
+            write_accounts_csv(accounts, &mut csv)?;
+            self.put(csv)
+        }
+
+        fn write_layout(
+            &mut self,
+            accounts: &[Account],
+            layout: &AccountLayout,
+        ) -> Result<(), String> {
+            let mut csv = Vec::new();
+            csv.write_layout(accounts, layout)?;
+            self.put(csv)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tempfile::TempDir;
+
+        fn file_url(path: &std::path::Path) -> String {
+            Url::from_file_path(path).unwrap().to_string()
+        }
+
+        #[test]
+        fn test_reader_joins_chunks() {
+            let (sender, chunks) = mpsc::sync_channel(4);
+            sender.send(Ok(Bytes::from_static(b"type,cli"))).unwrap();
+            sender.send(Ok(Bytes::from_static(b"ent\n"))).unwrap();
+            drop(sender);
+
+            let mut content = String::new();
+            ObjectReader::from_chunks(chunks)
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, "type,client\n");
+        }
+
+        #[test]
+        fn test_reader_surfaces_download_errors() {
+            let (sender, chunks) = mpsc::sync_channel(4);
+            sender.send(Ok(Bytes::from_static(b"partial"))).unwrap();
+            sender.send(Err("connection reset".to_string())).unwrap();
+
+            let mut content = Vec::new();
+            let err = ObjectReader::from_chunks(chunks)
+                .read_to_end(&mut content)
+                .unwrap_err();
+            assert!(err.to_string().contains("connection reset"));
+        }
+
+        #[test]
+        fn test_sink_and_reader_round_trip() {
+            let dir = TempDir::new().unwrap();
+            let url = file_url(&dir.path().join("accounts.csv"));
+
+            let mut sink = ObjectSink::new(&url).unwrap();
+            sink.write_accounts(&[Account::new(1)]).unwrap();
+            ObjectLocation::parse(&url).unwrap().check().unwrap();
+
+            let mut content = String::new();
+            ObjectReader::open(&url)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(
+                content,
+                "client,available,held,total,locked\n1,0.0000,0.0000,0.0000,false\n"
+            );
+        }
+
+        #[test]
+        fn test_missing_object() {
+            let dir = TempDir::new().unwrap();
+            let url = file_url(&dir.path().join("missing.csv"));
+
+            let err = ObjectLocation::parse(&url).unwrap().check().unwrap_err();
+            assert!(err.contains("Failed to open object"));
+
+            let mut content = Vec::new();
+            assert!(ObjectReader::open(&url)
+                .unwrap()
+                .read_to_end(&mut content)
+                .is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::s3("s3://bucket/transactions.csv", true)]
+    #[case::gcs("gs://bucket/transactions.csv", true)]
+    #[case::azure("az://container/transactions.csv", true)]
+    #[case::local("transactions.csv", false)]
+    #[case::postgres("postgres://localhost/payments", false)]
+    fn test_is_object_url(#[case] target: &str, #[case] expected: bool) {
+        assert_eq!(is_object_url(target), expected);
+    }
+
+    #[test]
+    fn test_open_local_input() {
+        let err = open_input(Path::new("nonexistent.csv")).err().unwrap();
+        assert!(err.contains("Failed to open file"));
+        assert!(check_input(Path::new("nonexistent.csv"))
+            .unwrap_err()
+            .contains("Failed to open file"));
+    }
+
+    #[cfg(not(feature = "object-store"))]
+    #[test]
+    fn test_object_input_requires_feature() {
+        let err = check_input(Path::new("s3://bucket/transactions.csv")).unwrap_err();
+        assert!(err.contains("'object-store' feature"));
+    }
+}
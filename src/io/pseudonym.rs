@@ -0,0 +1,238 @@
+//! Client pseudonymization
+//!
+//! With `--pseudonymize-key FILE`, every client in the account output and in
+//! the logged errors is replaced by a pseudonym: the first 16 bytes of the
+//! HMAC-SHA256 of its identifier under the key, in hex. The same key gives
+//! the same pseudonym for a client in every run, so analysts can join the
+//! results of several runs without learning who the clients are; without
+//! the key the pseudonyms cannot be linked back to the identifiers.
+//!
+//! The identifier of a client is its external identifier if the client
+//! registry (`--client-map` or `--string-clients`) has one, and its client ID
+//! otherwise.
+
+use crate::io::client_map::ClientRegistry;
+use crate::io::digest::hex;
+use crate::types::ClientId;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Shortest key accepted, in bytes
+pub const MIN_KEY_LEN: usize = 16;
+
+/// Number of bytes of the HMAC kept in a pseudonym
+const PSEUDONYM_BYTES: usize = 16;
+
+/// Phrases after which error messages name a client
+const CLIENT_MARKERS: [&str; 2] = ["client ", "Account "];
+
+/// Replaces client identifiers with keyed pseudonyms
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClientPseudonymizer {
+    key: Vec<u8>,
+    client_map: Option<Arc<ClientRegistry>>,
+}
+
+impl fmt::Debug for ClientPseudonymizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The key must not end up in logs
+        f.debug_struct("ClientPseudonymizer")
+            .field("key", &"<redacted>")
+            .field("client_map", &self.client_map)
+            .finish()
+    }
+}
+
+impl ClientPseudonymizer {
+    /// Create a pseudonymizer with the given key
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ClientPseudonymizer)` - If the key is at least `MIN_KEY_LEN` bytes
+    /// * `Err(String)` - If it is shorter, which would make the pseudonyms
+    ///   easy to reverse by guessing the key
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        if key.len() < MIN_KEY_LEN {
+            return Err(format!(
+                "pseudonymization key must be at least {} bytes, got {}",
+                MIN_KEY_LEN,
+                key.len()
+            ));
+        }
+        Ok(Self {
+            key: key.to_vec(),
+            client_map: None,
+        })
+    }
+
+    /// Pseudonymize clients by their external identifiers in `client_map`
+    pub fn with_client_map(mut self, client_map: Arc<ClientRegistry>) -> Self {
+        self.client_map = Some(client_map);
+        self
+    }
+
+    /// The pseudonym of a client identifier
+    pub fn pseudonym(&self, identifier: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key");
+        mac.update(identifier.as_bytes());
+        hex(&mac.finalize().into_bytes()[..PSEUDONYM_BYTES])
+    }
+
+    /// The pseudonym of a client ID
+    pub fn client(&self, client: ClientId) -> String {
+        let external = self
+            .client_map
+            .as_ref()
+            .and_then(|client_map| client_map.map().external(client).map(str::to_string));
+        match external {
+            Some(external) => self.pseudonym(&external),
+            None => self.pseudonym(&client.to_string()),
+        }
+    }
+
+    /// Replace the clients named in an error message with their pseudonyms
+    ///
+    /// Errors name clients as `client 42` or `Account 42`, and parse errors
+    /// quote client fields as `client 'CUST-0042'`; both forms are replaced.
+    pub fn mask_message(&self, message: &str) -> String {
+        let mut masked = String::with_capacity(message.len());
+        let mut rest = message;
+        while let Some((start, marker)) = CLIENT_MARKERS
+            .iter()
+            .filter_map(|marker| rest.find(marker).map(|start| (start, marker)))
+            .min()
+        {
+            let end = start + marker.len();
+            masked.push_str(&rest[..end]);
+            rest = &rest[end..];
+
+            if let Some(quoted) = rest.strip_prefix('\'') {
+                if let Some(close) = quoted.find('\'') {
+                    masked.push('\'');
+                    masked.push_str(&self.pseudonym(&quoted[..close]));
+                    masked.push('\'');
+                    rest = &quoted[close + 1..];
+                }
+                continue;
+            }
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+            match rest[..digits].parse::<ClientId>() {
+                Ok(client) => masked.push_str(&self.client(client)),
+                Err(_) => masked.push_str(&rest[..digits]),
+            }
+            rest = &rest[digits..];
+        }
+        masked.push_str(rest);
+        masked
+    }
+}
+
+/// Read a pseudonymization key from a file
+///
+/// Trailing whitespace, such as the newline of a key written with `echo`, is
+/// not part of the key.
+///
+/// # Returns
+///
+/// * `Ok(ClientPseudonymizer)` - A pseudonymizer with the key
+/// * `Err(String)` - If the file cannot be read or the key is too short
+pub fn read_pseudonym_key(path: &Path) -> Result<ClientPseudonymizer, String> {
+    let key = std::fs::read(path).map_err(|e| {
+        format!(
+            "Failed to read pseudonymization key '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    ClientPseudonymizer::new(key.trim_ascii_end())
+        .map_err(|e| format!("Invalid pseudonymization key '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::client_map::ClientMap;
+    use rstest::rstest;
+
+    const KEY: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn test_pseudonym_is_keyed_hmac() {
+        let pseudonymizer = ClientPseudonymizer::new(KEY).unwrap();
+
+        let pseudonym = pseudonymizer.pseudonym("42");
+        assert_eq!(pseudonym.len(), 32);
+        assert!(pseudonym.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_eq!(pseudonymizer.client(42), pseudonym);
+        assert_ne!(pseudonymizer.pseudonym("43"), pseudonym);
+
+        let other_key = ClientPseudonymizer::new(b"fedcba9876543210").unwrap();
+        assert_ne!(other_key.pseudonym("42"), pseudonym);
+    }
+
+    #[test]
+    fn test_pseudonym_of_mapped_client() {
+        let mut map = ClientMap::new();
+        map.insert("CUST-0042", 2).unwrap();
+        let pseudonymizer = ClientPseudonymizer::new(KEY)
+            .unwrap()
+            .with_client_map(Arc::new(ClientRegistry::fixed(map)));
+
+        assert_eq!(
+            pseudonymizer.client(2),
+            pseudonymizer.pseudonym("CUST-0042")
+        );
+        assert_eq!(pseudonymizer.client(3), pseudonymizer.pseudonym("3"));
+    }
+
+    #[rstest]
+    #[case::account("Account 42 is locked", &["Account ", " is locked"])]
+    #[case::two_clients(
+        "Client mismatch for dispute on transaction 7: expected client 1, got client 42",
+        &["Client mismatch for dispute on transaction 7: expected client ", ", got client "]
+    )]
+    #[case::quoted("CSV parse error: client 'CUST-0042' is not in the client map", &["CSV parse error: client '", "' is not in the client map"])]
+    #[case::no_client("Transaction 7 not found for dispute", &["Transaction 7 not found for dispute"])]
+    fn test_mask_message(#[case] message: &str, #[case] kept: &[&str]) {
+        let pseudonymizer = ClientPseudonymizer::new(KEY).unwrap();
+
+        let masked = pseudonymizer.mask_message(message);
+
+        for part in kept {
+            assert!(masked.contains(part), "{}", masked);
+        }
+        for client in ["1", "42", "CUST-0042"] {
+            let pseudonym = pseudonymizer.pseudonym(client);
+            assert_eq!(
+                masked.contains(&pseudonym),
+                message.contains(&format!(" {}", client))
+                    || message.contains(&format!("'{}'", client)),
+                "{}",
+                masked
+            );
+        }
+    }
+
+    #[test]
+    fn test_short_key_is_rejected() {
+        let err = ClientPseudonymizer::new(b"short").unwrap_err();
+        assert!(err.contains("at least 16 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_pseudonym_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, b"0123456789abcdef\n").unwrap();
+
+        let pseudonymizer = read_pseudonym_key(&path).unwrap();
+        assert_eq!(pseudonymizer, ClientPseudonymizer::new(KEY).unwrap());
+        assert!(format!("{:?}", pseudonymizer).contains("<redacted>"));
+        assert!(read_pseudonym_key(&dir.path().join("missing"))
+            .unwrap_err()
+            .contains("Failed to read pseudonymization key"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Quarantine file output
+//!
+//! Transactions diverted by the quarantine rules are written here instead of
+//! being applied. The file uses the input CSV columns plus a `reason` column,
+//! so after review it can be fed back to the engine as input (the readers
+//! ignore the extra column).
+
+use crate::types::{TransactionRecord, TransactionType};
+use csv::Writer;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// CSV writer for quarantined transactions
+pub struct QuarantineWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl QuarantineWriter<File> {
+    /// Create (or truncate) a quarantine file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(QuarantineWriter)` - With the header written
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| {
+            format!(
+                "Failed to create quarantine file '{}': {}",
+                path.display(),
+                e
+            )
+        })?;
+        Self::new(file)
+    }
+}
+
+impl<W: Write> QuarantineWriter<W> {
+    /// Create a quarantine writer over any output, writing the header
+    pub fn new(output: W) -> Result<Self, String> {
+        let mut writer = Writer::from_writer(output);
+        writer
+            .write_record(["type", "client", "tx", "amount", "reason"])
+            .map_err(|e| format!("Failed to write quarantine header: {}", e))?;
+        Ok(Self { writer })
+    }
+
+    /// Write a quarantined transaction and the reason it was quarantined
+    pub fn write(&mut self, record: &TransactionRecord, reason: &str) -> Result<(), String> {
+        let tx_type = match record.tx_type {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Approve => "approve",
+            TransactionType::Reject => "reject",
+        };
+        self.writer
+            .write_record([
+                tx_type,
+                &record.client.to_string(),
+                &record.tx.to_string(),
+                &record.amount.map(|a| a.to_string()).unwrap_or_default(),
+                reason,
+            ])
+            .map_err(|e| format!("Failed to write quarantined transaction: {}", e))
+    }
+
+    /// Flush buffered records to the output
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush quarantine file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_quarantined_transactions() {
+        let mut output = Vec::new();
+        {
+            let mut writer = QuarantineWriter::new(&mut output).unwrap();
+            writer
+                .write(
+                    &TransactionRecord {
+                        tx_type: TransactionType::Withdrawal,
+                        client: 1,
+                        tx: 7,
+                        amount: Some(Decimal::new(50000, 1)),
+                        line: None,
+                        source: None,
+                    },
+                    "amount 5000.0 above 1000",
+                )
+                .unwrap();
+            writer.flush().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "type,client,tx,amount,reason\nwithdrawal,1,7,5000.0,amount 5000.0 above 1000\n"
+        );
+    }
+
+    #[test]
+    fn test_create_in_missing_directory_fails() {
+        let result = QuarantineWriter::create(Path::new("missing-dir/quarantine.csv"));
+        assert!(result
+            .err()
+            .unwrap()
+            .contains("Failed to create quarantine file"));
+    }
+}
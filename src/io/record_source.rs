@@ -0,0 +1,130 @@
+//! Record sources shared by the processing strategies
+//!
+//! Input formats are read through two traits:
+//!
+//! - `RecordSource` - A blocking iterator of parsed records, yielding an error
+//!   for each record that fails to parse. Every reader iterating over
+//!   `Result<TransactionRecord, String>` (`SyncReader`, `FastCsvReader`,
+//!   `AvroReader`) is one.
+//! - `AsyncRecordSource` (feature `native`) - Reads batches of records,
+//!   returning the errors of the records that fail to parse alongside the
+//!   valid ones. `AsyncReader` is one.
+//!
+//! Neither logs the records that fail to parse: the strategies report them, so
+//! every strategy reports them the same way.
+//!
+//! `BlockingBatches` turns any `RecordSource` into an `AsyncRecordSource`, so
+//! a new input format only needs a blocking reader to work with every
+//! strategy; a streaming reader can be added later for the async strategy.
+
+#[cfg(feature = "native")]
+use crate::io::AsyncReader;
+use crate::types::TransactionRecord;
+#[cfg(feature = "native")]
+use futures::future::BoxFuture;
+#[cfg(feature = "native")]
+use futures::io::AsyncRead;
+
+/// Blocking source of parsed transaction records
+pub trait RecordSource: Iterator<Item = Result<TransactionRecord, String>> + Send {}
+
+impl<I> RecordSource for I where I: Iterator<Item = Result<TransactionRecord, String>> + Send {}
+
+/// Records read in a batch, and the errors of the records that failed to
+/// parse, each in input order
+pub type RecordBatch = (Vec<TransactionRecord>, Vec<String>);
+
+/// Source of batches of transaction records, read asynchronously
+#[cfg(feature = "native")]
+pub trait AsyncRecordSource: Send {
+    /// Read up to `batch_size` records and failures
+    ///
+    /// Fewer are only returned at the end of the input, and neither records
+    /// nor failures once the end was reached.
+    fn read_batch(&mut self, batch_size: usize) -> BoxFuture<'_, RecordBatch>;
+}
+
+#[cfg(feature = "native")]
+impl<R: AsyncRead + Unpin + Send + 'static> AsyncRecordSource for AsyncReader<R> {
+    fn read_batch(&mut self, batch_size: usize) -> BoxFuture<'_, RecordBatch> {
+        Box::pin(AsyncReader::read_batch(self, batch_size))
+    }
+}
+
+/// Reads batches from a blocking `RecordSource`
+///
+/// Records are read on the calling task, so the source should not block for
+/// long between records.
+#[cfg(feature = "native")]
+pub struct BlockingBatches {
+    records: Box<dyn RecordSource>,
+}
+
+#[cfg(feature = "native")]
+impl BlockingBatches {
+    /// Read batches from `records`
+    pub fn new(records: Box<dyn RecordSource>) -> Self {
+        Self { records }
+    }
+}
+
+#[cfg(feature = "native")]
+impl AsyncRecordSource for BlockingBatches {
+    fn read_batch(&mut self, batch_size: usize) -> BoxFuture<'_, RecordBatch> {
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut failures = Vec::new();
+        while batch.len() + failures.len() < batch_size {
+            match self.records.next() {
+                Some(Ok(record)) => batch.push(record),
+                Some(Err(e)) => failures.push(e),
+                None => break,
+            }
+        }
+        Box::pin(std::future::ready((batch, failures)))
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use crate::io::SyncReader;
+    use crate::types::TransactionType;
+
+    const INPUT: &str = "type,client,tx,amount\n\
+                         deposit,1,1,1.0\n\
+                         deposit,x,2,1.0\n\
+                         withdrawal,1,3,0.5\n\
+                         deposit,2,4,2.0\n";
+
+    fn blocking() -> BlockingBatches {
+        BlockingBatches::new(Box::new(SyncReader::from_reader(INPUT.as_bytes()).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_blocking_batches() {
+        let mut source = blocking();
+
+        let (batch, failures) = source.read_batch(2).await;
+        assert_eq!(batch.len(), 1);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("Line 3: "), "{}", failures[0]);
+
+        let (batch, failures) = source.read_batch(2).await;
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].tx_type, TransactionType::Withdrawal);
+        assert!(failures.is_empty());
+        assert_eq!(source.read_batch(2).await, (Vec::new(), Vec::new()));
+    }
+
+    #[tokio::test]
+    async fn test_sources_return_the_same_batches() {
+        let mut reader: Box<dyn AsyncRecordSource> = Box::new(AsyncReader::new(
+            futures::io::Cursor::new(INPUT.as_bytes().to_vec()),
+        ));
+        let mut blocking = blocking();
+
+        let batch = reader.read_batch(10).await;
+        assert_eq!(batch.0.len(), 3);
+        assert_eq!(batch, blocking.read_batch(10).await);
+    }
+}
@@ -0,0 +1,174 @@
+//! Risk rules file loading
+//!
+//! Reads the `--risk-rules` file: a JSON object with the amount limits,
+//! velocity limit, withdrawal requirements and held funds interest rate that
+//! can otherwise be given as options, e.g.
+//!
+//! ```json
+//! {
+//!   "max_deposit": "10000",
+//!   "max_withdrawal": "2500",
+//!   "max_total": "1000000",
+//!   "velocity": { "window": 10, "max_withdrawals": 3, "max_amount": "5000" },
+//!   "require_for_withdrawal": ["kyc_status=verified"],
+//!   "held_interest": "0.005"
+//! }
+//! ```
+//!
+//! Every field is optional; a missing field means no such rule. Amounts are
+//! strings, which keep their exact decimal value.
+
+use crate::core::{AmountLimits, MetadataRequirement, RiskRules, VelocityLimit};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+/// The rules as written in the file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RiskRulesFile {
+    max_deposit: Option<Decimal>,
+    max_withdrawal: Option<Decimal>,
+    max_total: Option<Decimal>,
+    velocity: Option<VelocityFile>,
+    require_for_withdrawal: Vec<String>,
+    held_interest: Option<Decimal>,
+}
+
+/// The velocity limit as written in the file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VelocityFile {
+    window: u32,
+    max_withdrawals: Option<u32>,
+    max_amount: Option<Decimal>,
+}
+
+/// Read risk rules from a JSON file
+///
+/// # Arguments
+///
+/// * `path` - Path to the risk rules file
+///
+/// # Returns
+///
+/// * `Ok(RiskRules)` - The rules in the file
+/// * `Err(String)` - If the file cannot be read or is malformed
+pub fn read_risk_rules(path: &Path) -> Result<RiskRules, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open risk rules file '{}': {}", path.display(), e))?;
+    parse_risk_rules(file)
+        .map_err(|e| format!("Invalid risk rules file '{}': {}", path.display(), e))
+}
+
+/// Parse risk rules from JSON
+fn parse_risk_rules(input: impl Read) -> Result<RiskRules, String> {
+    let file: RiskRulesFile = serde_json::from_reader(input).map_err(|e| e.to_string())?;
+
+    let velocity_limit = match file.velocity {
+        Some(velocity) => {
+            if velocity.window == 0 {
+                return Err("velocity window must be at least 1".to_string());
+            }
+            if velocity.max_withdrawals.is_none() && velocity.max_amount.is_none() {
+                return Err("velocity needs max_withdrawals, max_amount or both".to_string());
+            }
+            let mut limit = VelocityLimit::new(velocity.window);
+            if let Some(max) = velocity.max_withdrawals {
+                limit = limit.with_max_withdrawals(max);
+            }
+            if let Some(max) = velocity.max_amount {
+                limit = limit.with_max_withdrawn(max);
+            }
+            Some(limit)
+        }
+        None => None,
+    };
+
+    if file
+        .held_interest
+        .is_some_and(|rate| rate.is_sign_negative())
+    {
+        return Err("held_interest must not be negative".to_string());
+    }
+
+    let withdrawal_requirements = file
+        .require_for_withdrawal
+        .iter()
+        .map(|requirement| requirement.parse::<MetadataRequirement>())
+        .collect::<Result<_, _>>()?;
+
+    Ok(RiskRules {
+        limits: AmountLimits {
+            max_deposit: file.max_deposit,
+            max_withdrawal: file.max_withdrawal,
+            max_total: file.max_total,
+        },
+        velocity_limit,
+        withdrawal_requirements,
+        held_interest: file.held_interest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse_risk_rules() {
+        let rules = parse_risk_rules(
+            r#"{
+                "max_deposit": "10000.50",
+                "max_total": "1000000",
+                "velocity": { "window": 10, "max_withdrawals": 3 },
+                "require_for_withdrawal": ["kyc_status=verified", "tier=gold"],
+                "held_interest": "0.005"
+            }"#
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules,
+            RiskRules {
+                limits: AmountLimits {
+                    max_deposit: Some(Decimal::new(1_000_050, 2)),
+                    max_withdrawal: None,
+                    max_total: Some(Decimal::from(1_000_000)),
+                },
+                velocity_limit: Some(VelocityLimit::new(10).with_max_withdrawals(3)),
+                withdrawal_requirements: vec![
+                    "kyc_status=verified".parse().unwrap(),
+                    "tier=gold".parse().unwrap(),
+                ],
+                held_interest: Some(Decimal::new(5, 3)),
+            }
+        );
+        assert_eq!(
+            parse_risk_rules("{}".as_bytes()).unwrap(),
+            RiskRules::default()
+        );
+    }
+
+    #[rstest]
+    #[case::not_json("max_deposit: 10", "expected value")]
+    #[case::unknown_field(r#"{"max_deposits": "10"}"#, "unknown field")]
+    #[case::invalid_amount(r#"{"max_deposit": "ten"}"#, "string \"ten\"")]
+    #[case::number_amount(r#"{"max_deposit": 10}"#, "invalid type")]
+    #[case::zero_window(r#"{"velocity": {"window": 0, "max_withdrawals": 1}}"#, "at least 1")]
+    #[case::velocity_without_max(r#"{"velocity": {"window": 5}}"#, "max_withdrawals")]
+    #[case::invalid_requirement(r#"{"require_for_withdrawal": ["verified"]}"#, "KEY=VALUE")]
+    #[case::negative_interest(r#"{"held_interest": "-0.01"}"#, "must not be negative")]
+    fn test_parse_risk_rules_invalid(#[case] input: &str, #[case] expected: &str) {
+        let err = parse_risk_rules(input.as_bytes()).unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    #[test]
+    fn test_read_missing_file() {
+        let err = read_risk_rules(Path::new("nonexistent.json")).unwrap_err();
+        assert!(err.contains("Failed to open risk rules file"), "{}", err);
+    }
+}
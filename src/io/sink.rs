@@ -0,0 +1,795 @@
+//! Account output sinks
+//!
+//! This module defines the `AccountSink` trait, the destination for the final
+//! account states of a run. Every `std::io::Write` is a sink that writes the
+//! standard CSV output, so strategies can still be given stdout, a file or an
+//! in-memory buffer directly.
+//!
+//! # Targets
+//!
+//! `create_sink` selects a sink from an `--output` target:
+//!
+//! - `-` - CSV to stdout
+//! - `postgres://...` or `postgresql://...` - upsert into Postgres (feature `postgres`)
+//! - `s3://...`, `gs://...` or `az://...` - CSV object in an object store (feature `object-store`)
+//! - anything else - CSV to the given file path (created or truncated)
+//!
+//! `create_snapshot_sink` does the same for runs that write several snapshots
+//! (`--follow`), except that a file is rewritten with each snapshot instead of
+//! accumulating all of them.
+//!
+//! `FilteredSink` wraps any sink to write only the accounts of selected
+//! clients (`--clients`), `LockReasonSink` to add why each account was
+//! locked as a `lock_reason` column (`--lock-reasons`), and `ActivitySink` to
+//! add the activity counters of each client (`--client-stats`). `LayoutSink`
+//! writes another `AccountLayout` than the standard one: a selection of
+//! columns (`--columns`) or amounts in minor units (`--amount-format`), which
+//! only CSV sinks support.
+//!
+//! `create_mapped_sink` selects a `ClientMapSink`, which writes the external
+//! client identifiers of a client registry (`--client-map` or
+//! `--string-clients`) instead of client IDs, and `create_pseudonymized_sink`
+//! a `PseudonymSink`, which writes the pseudonyms of clients
+//! (`--pseudonymize-key`).
+
+use crate::io::client_map::ClientRegistry;
+use crate::io::csv_format::{
+    write_accounts_csv, write_accounts_csv_layout, write_accounts_csv_mapped,
+    write_accounts_csv_named, AccountLayout,
+};
+use crate::io::object_storage::is_object_url;
+use crate::io::pseudonym::ClientPseudonymizer;
+use crate::types::{Account, ClientSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Destination for the final account states of a run
+pub trait AccountSink {
+    /// Write the final account states
+    ///
+    /// # Arguments
+    ///
+    /// * `accounts` - Slice of account states to write
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if all accounts were written
+    /// * `Err(String)` if the sink could not be written
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String>;
+
+    /// Write the final account states in the given layout
+    ///
+    /// Only CSV sinks, and the sinks wrapping them, can change the layout;
+    /// the others return an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `accounts` - Slice of account states to write
+    /// * `layout` - Columns to write and format of their amounts
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if all accounts were written
+    /// * `Err(String)` if the sink could not be written, or has a fixed layout
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        let _ = (accounts, layout);
+        Err("Output columns and amount formats can only be selected for CSV output".to_string())
+    }
+}
+
+impl<W: Write + ?Sized> AccountSink for W {
+    /// Write accounts as CSV using `csv_format::write_accounts_csv`
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        // `&mut W` is sized even when `W` is not, so it can be passed as `dyn Write`
+        let mut writer = self;
+        write_accounts_csv(accounts, &mut writer)
+    }
+
+    /// Write accounts as CSV using `csv_format::write_accounts_csv_layout`
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        let mut writer = self;
+        write_accounts_csv_layout(accounts, &mut writer, layout, |client| client.to_string())
+    }
+}
+
+/// Returns true if the output target is a Postgres connection URL
+fn is_postgres_url(target: &str) -> bool {
+    target.starts_with("postgres://") || target.starts_with("postgresql://")
+}
+
+/// Create the account sink for an output target
+///
+/// # Arguments
+///
+/// * `target` - `-` for stdout, a Postgres URL, an object URL, or a file path
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn AccountSink>)` - The sink for the target
+/// * `Err(String)` - If the output file cannot be created, or the target is a
+///   Postgres or object URL and the crate was built without the corresponding
+///   feature
+pub fn create_sink(target: &str) -> Result<Box<dyn AccountSink>, String> {
+    if target == "-" {
+        return Ok(Box::new(std::io::stdout()));
+    }
+
+    if is_postgres_url(target) {
+        #[cfg(feature = "postgres")]
+        {
+            return Ok(Box::new(crate::io::PostgresSink::new(target)));
+        }
+        #[cfg(not(feature = "postgres"))]
+        {
+            return Err(
+                "Postgres output requires building with the 'postgres' feature".to_string(),
+            );
+        }
+    }
+
+    if is_object_url(target) {
+        #[cfg(feature = "object-store")]
+        {
+            return Ok(Box::new(crate::io::ObjectSink::new(target)?));
+        }
+        #[cfg(not(feature = "object-store"))]
+        {
+            return Err(crate::io::object_storage::output_feature_error());
+        }
+    }
+
+    let file = File::create(target)
+        .map_err(|e| format!("Failed to create output file '{}': {}", target, e))?;
+    Ok(Box::new(file))
+}
+
+/// File sink replacing the file contents with each set of accounts written
+///
+/// Every write goes to a temporary file next to the target, which is then
+/// renamed over it, so readers always see a complete snapshot.
+#[derive(Debug)]
+pub struct SnapshotFileSink {
+    path: PathBuf,
+}
+
+impl SnapshotFileSink {
+    /// Create a snapshot sink for `path`, creating (or truncating) the file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SnapshotFileSink)` - If the file can be created
+    /// * `Err(String)` - If it cannot
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        File::create(&path)
+            .map_err(|e| format!("Failed to create output file '{}': {}", path.display(), e))?;
+        Ok(Self { path })
+    }
+}
+
+impl SnapshotFileSink {
+    /// Replace the file with the output of `write`
+    fn replace(&self, write: impl FnOnce(&mut File) -> Result<(), String>) -> Result<(), String> {
+        let mut temp_name = self.path.clone().into_os_string();
+        temp_name.push(".tmp");
+        let temp_path = PathBuf::from(temp_name);
+
+        let mut file = File::create(&temp_path).map_err(|e| {
+            format!(
+                "Failed to create output file '{}': {}",
+                temp_path.display(),
+                e
+            )
+        })?;
+        write(&mut file)?;
+        std::fs::rename(&temp_path, &self.path).map_err(|e| {
+            format!(
+                "Failed to replace output file '{}': {}",
+                self.path.display(),
+                e
+            )
+        })
+    }
+}
+
+impl AccountSink for SnapshotFileSink {
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        self.replace(|file| file.write_accounts(accounts))
+    }
+
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        self.replace(|file| file.write_layout(accounts, layout))
+    }
+}
+
+/// Sink writing only the accounts of selected clients to another sink
+pub struct FilteredSink {
+    inner: Box<dyn AccountSink>,
+    clients: ClientSet,
+}
+
+impl FilteredSink {
+    /// Wrap `inner` so that it only receives the accounts of `clients`
+    pub fn new(inner: Box<dyn AccountSink>, clients: ClientSet) -> Self {
+        Self { inner, clients }
+    }
+}
+
+impl FilteredSink {
+    /// The accounts of the selected clients
+    fn select(&self, accounts: &[Account]) -> Vec<Account> {
+        accounts
+            .iter()
+            .filter(|account| self.clients.contains(account.client))
+            .cloned()
+            .collect()
+    }
+}
+
+impl AccountSink for FilteredSink {
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        let selected = self.select(accounts);
+        self.inner.write_accounts(&selected)
+    }
+
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        let selected = self.select(accounts);
+        self.inner.write_layout(&selected, layout)
+    }
+}
+
+/// Name of the column `LockReasonSink` adds
+const LOCK_REASON_COLUMN: &str = "lock_reason";
+
+/// Sink adding the lock reason of every account to another sink
+///
+/// The reason is added to the account metadata under `lock_reason`, so CSV
+/// outputs get a `lock_reason` column among the metadata columns, empty for
+/// unlocked accounts and accounts locked for an unknown reason. It replaces
+/// a metadata key of the same name.
+pub struct LockReasonSink {
+    inner: Box<dyn AccountSink>,
+}
+
+impl LockReasonSink {
+    /// Wrap `inner` so that it receives the lock reasons of the accounts
+    pub fn new(inner: Box<dyn AccountSink>) -> Self {
+        Self { inner }
+    }
+}
+
+impl AccountSink for LockReasonSink {
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        let with_reasons: Vec<Account> = accounts
+            .iter()
+            .map(|account| {
+                let mut metadata = account.metadata.as_deref().cloned().unwrap_or_default();
+                metadata.insert(
+                    LOCK_REASON_COLUMN.to_string(),
+                    account
+                        .lock_reason
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .unwrap_or_default(),
+                );
+                Account {
+                    metadata: Some(Arc::new(metadata)),
+                    ..account.clone()
+                }
+            })
+            .collect();
+        self.inner.write_accounts(&with_reasons)
+    }
+
+    /// A selected `lock_reason` column reads the lock reason directly
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        self.inner.write_layout(accounts, layout)
+    }
+}
+
+/// Names of the columns `ActivitySink` adds
+const ACTIVITY_COLUMNS: [&str; 4] = ["transactions", "errors", "dispute_count", "last_tx"];
+
+/// Sink adding the activity counters of every client to another sink
+///
+/// The counters (see `ClientActivity`) are added to the account metadata, so
+/// CSV outputs get `transactions`, `errors`, `dispute_count` and `last_tx`
+/// columns among the metadata columns, in name order. `last_tx` is empty for
+/// accounts without counted records. They replace metadata keys of the same
+/// names.
+pub struct ActivitySink {
+    inner: Box<dyn AccountSink>,
+}
+
+impl ActivitySink {
+    /// Wrap `inner` so that it receives the activity counters of the accounts
+    pub fn new(inner: Box<dyn AccountSink>) -> Self {
+        Self { inner }
+    }
+}
+
+impl AccountSink for ActivitySink {
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        let with_activity: Vec<Account> = accounts
+            .iter()
+            .map(|account| {
+                let activity = &account.activity;
+                let values = [
+                    activity.transactions.to_string(),
+                    activity.errors.to_string(),
+                    activity.disputes.to_string(),
+                    activity
+                        .last_tx
+                        .map(|tx| tx.to_string())
+                        .unwrap_or_default(),
+                ];
+                let mut metadata = account.metadata.as_deref().cloned().unwrap_or_default();
+                for (column, value) in ACTIVITY_COLUMNS.into_iter().zip(values) {
+                    metadata.insert(column.to_string(), value);
+                }
+                Account {
+                    metadata: Some(Arc::new(metadata)),
+                    ..account.clone()
+                }
+            })
+            .collect();
+        self.inner.write_accounts(&with_activity)
+    }
+
+    /// Selected activity columns read the counters directly
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        self.inner.write_layout(accounts, layout)
+    }
+}
+
+/// Sink writing the accounts to another sink in a given layout
+///
+/// Every write becomes a `write_layout` of the inner sink, so the inner
+/// sink must write CSV (stdout, files and objects, with client IDs, external
+/// identifiers or pseudonyms).
+pub struct LayoutSink {
+    inner: Box<dyn AccountSink>,
+    layout: AccountLayout,
+}
+
+impl LayoutSink {
+    /// Wrap `inner` so that it writes the accounts in `layout`
+    pub fn new(inner: Box<dyn AccountSink>, layout: AccountLayout) -> Self {
+        Self { inner, layout }
+    }
+}
+
+impl AccountSink for LayoutSink {
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        self.inner.write_layout(accounts, &self.layout)
+    }
+}
+
+/// CSV sink writing each client as its external identifier in a client
+/// registry
+///
+/// The registry's map is read when the accounts are written, so clients
+/// interned while processing are included.
+pub struct ClientMapSink {
+    output: Box<dyn Write>,
+    client_map: Arc<ClientRegistry>,
+}
+
+impl ClientMapSink {
+    /// Write the accounts to `output`, with clients mapped by `client_map`
+    pub fn new(output: Box<dyn Write>, client_map: Arc<ClientRegistry>) -> Self {
+        Self { output, client_map }
+    }
+}
+
+impl AccountSink for ClientMapSink {
+    /// Write accounts as CSV using `csv_format::write_accounts_csv_mapped`
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        write_accounts_csv_mapped(accounts, &mut self.output, &self.client_map.map())
+    }
+
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        let client_map = self.client_map.map();
+        write_accounts_csv_layout(accounts, &mut self.output, layout, |client| {
+            client_map
+                .external(client)
+                .map_or_else(|| client.to_string(), str::to_string)
+        })
+    }
+}
+
+/// Create the account sink for an output target, mapping clients back to
+/// their external identifiers
+///
+/// # Arguments
+///
+/// * `target` - `-` for stdout, or a file path
+/// * `client_map` - Registry of the external client identifiers
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn AccountSink>)` - The sink for the target
+/// * `Err(String)` - If the output file cannot be created, or the target is a
+///   Postgres or object URL, which take numeric client IDs only
+pub fn create_mapped_sink(
+    target: &str,
+    client_map: Arc<ClientRegistry>,
+) -> Result<Box<dyn AccountSink>, String> {
+    let output = create_csv_output(target, "--client-map")?;
+    Ok(Box::new(ClientMapSink::new(output, client_map)))
+}
+
+/// CSV sink writing the pseudonym of each client instead of its identifier
+pub struct PseudonymSink {
+    output: Box<dyn Write>,
+    pseudonymizer: Arc<ClientPseudonymizer>,
+}
+
+impl PseudonymSink {
+    /// Write the accounts to `output`, with clients replaced by their pseudonyms
+    pub fn new(output: Box<dyn Write>, pseudonymizer: Arc<ClientPseudonymizer>) -> Self {
+        Self {
+            output,
+            pseudonymizer,
+        }
+    }
+}
+
+impl AccountSink for PseudonymSink {
+    /// Write accounts as CSV using `csv_format::write_accounts_csv_named`
+    fn write_accounts(&mut self, accounts: &[Account]) -> Result<(), String> {
+        write_accounts_csv_named(accounts, &mut self.output, |client| {
+            self.pseudonymizer.client(client)
+        })
+    }
+
+    fn write_layout(&mut self, accounts: &[Account], layout: &AccountLayout) -> Result<(), String> {
+        write_accounts_csv_layout(accounts, &mut self.output, layout, |client| {
+            self.pseudonymizer.client(client)
+        })
+    }
+}
+
+/// Create the account sink for an output target, writing the pseudonyms of
+/// clients
+///
+/// # Arguments
+///
+/// * `target` - `-` for stdout, or a file path
+/// * `pseudonymizer` - Pseudonymizer of the clients
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn AccountSink>)` - The sink for the target
+/// * `Err(String)` - As for `create_mapped_sink`
+pub fn create_pseudonymized_sink(
+    target: &str,
+    pseudonymizer: Arc<ClientPseudonymizer>,
+) -> Result<Box<dyn AccountSink>, String> {
+    let output = create_csv_output(target, "--pseudonymize-key")?;
+    Ok(Box::new(PseudonymSink::new(output, pseudonymizer)))
+}
+
+/// Open stdout or a file for CSV output with client names other than IDs
+///
+/// `option` names the option requiring it in the error for Postgres and
+/// object URLs, which take numeric client IDs only.
+fn create_csv_output(target: &str, option: &str) -> Result<Box<dyn Write>, String> {
+    if is_postgres_url(target) || is_object_url(target) {
+        return Err(format!(
+            "{} output requires CSV written to stdout or a file",
+            option
+        ));
+    }
+    if target == "-" {
+        return Ok(Box::new(std::io::stdout()));
+    }
+    let file = File::create(target)
+        .map_err(|e| format!("Failed to create output file '{}': {}", target, e))?;
+    Ok(Box::new(file))
+}
+
+/// Create the account sink for an output target receiving repeated snapshots
+///
+/// Like `create_sink`, except that a file target holds only the latest
+/// snapshot. Stdout receives every snapshot in turn, Postgres upserts them,
+/// and an object is replaced by each of them.
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn AccountSink>)` - The sink for the target
+/// * `Err(String)` - As for `create_sink`
+pub fn create_snapshot_sink(target: &str) -> Result<Box<dyn AccountSink>, String> {
+    if target == "-" || is_postgres_url(target) || is_object_url(target) {
+        return create_sink(target);
+    }
+    Ok(Box::new(SnapshotFileSink::create(target)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::client_map::ClientMap;
+    use crate::io::csv_format::AmountFormat;
+    use crate::types::{LockReason, TransactionType};
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    fn accounts() -> Vec<Account> {
+        let mut second = Account::new(2);
+        second.available = Decimal::new(15, 1);
+        second.total = Decimal::new(15, 1);
+        vec![second, Account::new(1)]
+    }
+
+    #[test]
+    fn test_writer_sink_writes_csv() {
+        let mut output = Vec::new();
+        output.write_accounts(&accounts()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,0.0000,0.0000,0.0000,false\n\
+             2,1.5000,0.0000,1.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_create_sink_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+
+        let mut sink = create_sink(path.to_str().unwrap()).unwrap();
+        sink.write_accounts(&accounts()).unwrap();
+        drop(sink);
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("client,available,held,total,locked\n1,"));
+    }
+
+    #[test]
+    fn test_create_sink_unwritable_file() {
+        let result = create_sink("nonexistent-dir/accounts.csv");
+        assert!(result
+            .err()
+            .unwrap()
+            .contains("Failed to create output file"));
+    }
+
+    #[test]
+    fn test_snapshot_sink_keeps_latest_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+
+        let mut sink = create_snapshot_sink(path.to_str().unwrap()).unwrap();
+        sink.write_accounts(&accounts()).unwrap();
+        sink.write_accounts(&[Account::new(3)]).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "client,available,held,total,locked\n3,0.0000,0.0000,0.0000,false\n"
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_filtered_sink_writes_selected_clients() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+
+        let inner = create_sink(path.to_str().unwrap()).unwrap();
+        let mut sink = FilteredSink::new(inner, "2-5".parse().unwrap());
+        sink.write_accounts(&accounts()).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "client,available,held,total,locked\n2,1.5000,0.0000,1.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_lock_reason_sink_adds_column() {
+        let mut accounts = accounts();
+        accounts[1].lock(LockReason::ChargebackTx(9));
+        accounts[0].metadata = Some(Arc::new(
+            [("owner".to_string(), "Alice".to_string())].into(),
+        ));
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+
+        let inner = create_sink(path.to_str().unwrap()).unwrap();
+        let mut sink = LockReasonSink::new(inner);
+        sink.write_accounts(&accounts).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "client,available,held,total,locked,lock_reason,owner\n\
+             1,0.0000,0.0000,0.0000,true,chargeback:9,\n\
+             2,1.5000,0.0000,1.5000,false,,Alice\n"
+        );
+    }
+
+    #[test]
+    fn test_activity_sink_adds_columns() {
+        let mut accounts = accounts();
+        accounts[0]
+            .activity
+            .record(TransactionType::Deposit, 7, true);
+        accounts[0]
+            .activity
+            .record(TransactionType::Withdrawal, 8, false);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+
+        let inner = create_sink(path.to_str().unwrap()).unwrap();
+        let mut sink = ActivitySink::new(inner);
+        sink.write_accounts(&accounts).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "client,available,held,total,locked,dispute_count,errors,last_tx,transactions\n\
+             1,0.0000,0.0000,0.0000,false,0,0,,0\n\
+             2,1.5000,0.0000,1.5000,false,0,1,8,2\n"
+        );
+    }
+
+    #[test]
+    fn test_layout_sink_selects_columns() {
+        let mut accounts = accounts();
+        accounts[0].lock(LockReason::Admin);
+        accounts[0].metadata = Some(Arc::new(
+            [("owner".to_string(), "Alice".to_string())].into(),
+        ));
+        accounts[1]
+            .activity
+            .record(TransactionType::Dispute, 3, true);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+
+        let inner = create_sink(path.to_str().unwrap()).unwrap();
+        let filtered = FilteredSink::new(inner, "1-5".parse().unwrap());
+        let columns = "total,client,dispute_count,last_tx,lock_reason,owner"
+            .parse()
+            .unwrap();
+        let layout = AccountLayout::default().with_columns(columns);
+        let mut sink = LayoutSink::new(Box::new(filtered), layout);
+        sink.write_accounts(&accounts).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "total,client,dispute_count,last_tx,lock_reason,owner\n\
+             0.0000,1,1,3,,\n\
+             1.5000,2,0,,admin,Alice\n"
+        );
+    }
+
+    #[test]
+    fn test_layout_sink_maps_clients() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+        let mut client_map = ClientMap::new();
+        client_map.insert("CUST-0042", 2).unwrap();
+        let registry = Arc::new(ClientRegistry::fixed(client_map));
+
+        let inner = create_mapped_sink(path.to_str().unwrap(), registry).unwrap();
+        let layout = AccountLayout::default()
+            .with_columns("client,available".parse().unwrap())
+            .with_amounts(AmountFormat::MinorUnits);
+        let mut sink = LayoutSink::new(inner, layout);
+        sink.write_accounts(&accounts()).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "client,available\n1,0\nCUST-0042,15000\n"
+        );
+    }
+
+    #[test]
+    fn test_layout_sink_rejects_fixed_layouts() {
+        struct Table;
+        impl AccountSink for Table {
+            fn write_accounts(&mut self, _: &[Account]) -> Result<(), String> {
+                Ok(())
+            }
+        }
+
+        let layout = AccountLayout::default().with_amounts(AmountFormat::MinorUnits);
+        let mut sink = LayoutSink::new(Box::new(Table), layout);
+        assert!(sink
+            .write_accounts(&accounts())
+            .unwrap_err()
+            .contains("only be selected for CSV output"));
+    }
+
+    #[rstest]
+    #[case::postgres("postgres://localhost/payments", true)]
+    #[case::postgresql("postgresql://user@db:5432/payments", true)]
+    #[case::file("accounts.csv", false)]
+    #[case::stdout("-", false)]
+    fn test_is_postgres_url(#[case] target: &str, #[case] expected: bool) {
+        assert_eq!(is_postgres_url(target), expected);
+    }
+
+    #[cfg(not(feature = "object-store"))]
+    #[test]
+    fn test_create_sink_object_requires_feature() {
+        let result = create_sink("s3://bucket/accounts.csv");
+        assert!(result.err().unwrap().contains("'object-store' feature"));
+    }
+
+    #[cfg(not(feature = "postgres"))]
+    #[test]
+    fn test_create_sink_postgres_requires_feature() {
+        let result = create_sink("postgres://localhost/payments");
+        assert!(result.err().unwrap().contains("'postgres' feature"));
+    }
+
+    #[test]
+    fn test_mapped_sink_writes_external_clients() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+        let mut client_map = ClientMap::new();
+        client_map.insert("CUST-0042", 2).unwrap();
+        let registry = Arc::new(ClientRegistry::fixed(client_map));
+
+        let mut sink = create_mapped_sink(path.to_str().unwrap(), registry).unwrap();
+        sink.write_accounts(&accounts()).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "client,available,held,total,locked\n\
+             1,0.0000,0.0000,0.0000,false\n\
+             CUST-0042,1.5000,0.0000,1.5000,false\n"
+        );
+    }
+
+    #[rstest]
+    #[case::postgres("postgres://localhost/payments")]
+    #[case::object("s3://bucket/accounts.csv")]
+    fn test_mapped_sink_rejects_non_csv_targets(#[case] target: &str) {
+        let result = create_mapped_sink(target, Arc::new(ClientRegistry::default()));
+        assert!(result.err().unwrap().contains("--client-map output"));
+    }
+
+    #[test]
+    fn test_pseudonymized_sink_writes_pseudonyms() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("accounts.csv");
+        let pseudonymizer = Arc::new(ClientPseudonymizer::new(b"0123456789abcdef").unwrap());
+
+        let mut sink =
+            create_pseudonymized_sink(path.to_str().unwrap(), pseudonymizer.clone()).unwrap();
+        sink.write_accounts(&accounts()).unwrap();
+        drop(sink);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!(
+                "client,available,held,total,locked\n\
+                 {},0.0000,0.0000,0.0000,false\n\
+                 {},1.5000,0.0000,1.5000,false\n",
+                pseudonymizer.client(1),
+                pseudonymizer.client(2)
+            )
+        );
+        assert!(
+            create_pseudonymized_sink("s3://bucket/accounts.csv", pseudonymizer)
+                .err()
+                .unwrap()
+                .contains("--pseudonymize-key output")
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Standing orders file loading
+//!
+//! Reads the `--standing-orders` file: a CSV with one recurring deposit or
+//! withdrawal per row, and the number of input records between its
+//! occurrences, e.g.
+//!
+//! ```text
+//! client,type,amount,every
+//! 1,withdrawal,9.99,1000
+//! 2,deposit,2500.0,50000
+//! ```
+
+use crate::strategy::StandingOrder;
+use crate::types::{ClientId, TransactionType};
+use csv::{ReaderBuilder, Trim};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+/// A standing order as written in the file
+#[derive(Debug, Deserialize)]
+struct StandingOrderRow {
+    client: ClientId,
+    #[serde(rename = "type")]
+    tx_type: String,
+    amount: Decimal,
+    every: u64,
+}
+
+/// Read standing orders from a CSV file
+///
+/// # Arguments
+///
+/// * `path` - Path to the standing orders file
+///
+/// # Returns
+///
+/// * `Ok(Vec<StandingOrder>)` - The orders in file order
+/// * `Err(String)` - If the file cannot be read or is malformed
+pub fn read_standing_orders(path: &Path) -> Result<Vec<StandingOrder>, String> {
+    let file = std::fs::File::open(path).map_err(|e| {
+        format!(
+            "Failed to open standing orders file '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+    parse_standing_orders(file)
+        .map_err(|e| format!("Invalid standing orders file '{}': {}", path.display(), e))
+}
+
+/// Parse standing orders from CSV
+fn parse_standing_orders(input: impl Read) -> Result<Vec<StandingOrder>, String> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(input);
+
+    let mut orders = Vec::new();
+    for (index, result) in reader.deserialize::<StandingOrderRow>().enumerate() {
+        // Line 1 is the header
+        let line = index + 2;
+        let row = result.map_err(|e| format!("line {}: {}", line, e))?;
+
+        let tx_type = match row.tx_type.to_ascii_lowercase().as_str() {
+            "deposit" => TransactionType::Deposit,
+            "withdrawal" => TransactionType::Withdrawal,
+            other => {
+                return Err(format!(
+                    "line {}: type must be 'deposit' or 'withdrawal', not '{}'",
+                    line, other
+                ))
+            }
+        };
+        if row.amount <= Decimal::ZERO {
+            return Err(format!(
+                "line {}: amount must be positive, not {}",
+                line, row.amount
+            ));
+        }
+        if row.every == 0 {
+            return Err(format!("line {}: every must be at least 1", line));
+        }
+        orders.push(StandingOrder::new(
+            row.client, tx_type, row.amount, row.every,
+        ));
+    }
+
+    Ok(orders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_parse_standing_orders() {
+        let input = "client, type ,amount,every\n1,withdrawal,9.99,1000\n2, Deposit ,2500,50000\n";
+
+        let orders = parse_standing_orders(input.as_bytes()).unwrap();
+
+        assert_eq!(
+            orders,
+            vec![
+                StandingOrder::new(1, TransactionType::Withdrawal, Decimal::new(999, 2), 1000),
+                StandingOrder::new(2, TransactionType::Deposit, Decimal::from(2500), 50000),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::invalid_type(
+        "client,type,amount,every\n1,dispute,1.0,10\n",
+        "line 2: type must be 'deposit' or 'withdrawal', not 'dispute'"
+    )]
+    #[case::negative_amount(
+        "client,type,amount,every\n1,deposit,-1.0,10\n",
+        "line 2: amount must be positive"
+    )]
+    #[case::zero_every(
+        "client,type,amount,every\n1,deposit,1.0,10\n1,deposit,1.0,0\n",
+        "line 3: every must be at least 1"
+    )]
+    #[case::missing_column("client,type,amount\n1,deposit,1.0\n", "line 2:")]
+    #[case::invalid_client("client,type,amount,every\nabc,deposit,1.0,10\n", "line 2:")]
+    fn test_parse_standing_orders_invalid(#[case] input: &str, #[case] expected: &str) {
+        let err = parse_standing_orders(input.as_bytes()).unwrap_err();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    #[test]
+    fn test_read_standing_orders_missing_file() {
+        let err = read_standing_orders(Path::new("nonexistent.csv")).unwrap_err();
+        assert!(err.contains("Failed to open standing orders file"));
+    }
+}
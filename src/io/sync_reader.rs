@@ -30,7 +30,7 @@
 //!
 //! # Error Handling
 //!
-//! - Fatal errors (file not found, I/O errors) are returned from `new()`
+//! - Fatal errors (file not found, I/O errors, invalid header) are returned from `new()`
 //! - Individual record parsing errors are yielded as Err variants in the iterator
 //! - Line numbers are included in error messages for debugging
 //!
@@ -41,10 +41,12 @@
 //! - Does not load entire file into memory
 //! - Memory usage is O(1) per record, not O(file_size)
 
-use crate::io::csv_format::{convert_csv_record, CsvRecord};
+use crate::io::csv_format::{CsvColumns, CsvDialect};
+use crate::io::csv_schema::validate_header;
 use crate::types::TransactionRecord;
-use csv::{ReaderBuilder, Trim};
+use csv::{ByteRecord, ReaderBuilder, StringRecord, Trim};
 use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 
 /// Synchronous CSV reader
@@ -63,12 +65,17 @@ use std::path::Path;
 /// println!("Successfully parsed {} records", records.len());
 /// ```
 #[derive(Debug)]
-pub struct SyncReader {
-    reader: csv::Reader<File>,
+pub struct SyncReader<R: Read = File> {
+    reader: csv::Reader<R>,
+    dialect: CsvDialect,
+    /// Positions of the schema columns in the header
+    columns: CsvColumns,
+    /// Buffer for the current row, reused for every record
+    record: ByteRecord,
     line_num: usize,
 }
 
-impl SyncReader {
+impl SyncReader<File> {
     /// Create a new SyncReader from a file path
     ///
     /// Opens the CSV file and prepares it for streaming iteration.
@@ -100,28 +107,74 @@ impl SyncReader {
     pub fn new(path: &Path) -> Result<Self, String> {
         let file = File::open(path)
             .map_err(|e| format!("Failed to open file '{}': {}", path.display(), e))?;
+        Self::from_reader(file)
+    }
+}
+
+impl<R: Read> SyncReader<R> {
+    /// Create a new SyncReader over any byte source, such as an object stream
+    ///
+    /// The CSV reader is configured as in `SyncReader::new`.
+    pub fn from_reader(input: R) -> Result<Self, String> {
+        Self::with_dialect(input, CsvDialect::default())
+    }
 
-        let reader = ReaderBuilder::new()
+    /// Create a new SyncReader over a byte source written in the given dialect
+    ///
+    /// The header is read and checked against the schema (see `csv_schema`)
+    /// before any record, after renaming header names with an alias in the
+    /// dialect to their standard column.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SyncReader)` if the header is valid
+    /// * `Err(String)` if the header cannot be read or does not match the schema
+    pub fn with_dialect(input: R, dialect: CsvDialect) -> Result<Self, String> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(dialect.delimiter)
             .trim(Trim::All)
             .flexible(true)
             .buffer_capacity(8 * 1024)
-            .from_reader(file);
+            .from_reader(input);
+
+        let headers: StringRecord = reader
+            .headers()
+            .map_err(|e| format!("Failed to read CSV header: {}", e))?
+            .iter()
+            .map(|header| dialect.column_name(header))
+            .collect();
+        validate_header(&headers)?;
+        let columns = CsvColumns::locate(headers.iter())?;
+        reader.set_headers(headers);
 
         Ok(Self {
             reader,
+            dialect,
+            columns,
+            record: ByteRecord::new(),
             line_num: 0,
         })
     }
 }
 
-impl Iterator for SyncReader {
+#[cfg(feature = "fast-csv")]
+impl<R: Read> SyncReader<R> {
+    /// Continue the line numbering of another reader that has read `records`
+    /// records of the same input
+    pub(crate) fn with_records_read(mut self, records: usize) -> Self {
+        self.line_num = records;
+        self
+    }
+}
+
+impl<R: Read> Iterator for SyncReader<R> {
     type Item = Result<TransactionRecord, String>;
 
     /// Get the next transaction record from the CSV file
     ///
     /// This method:
-    /// 1. Reads the next CSV row and deserializes it to CsvRecord
-    /// 2. Converts the CsvRecord to TransactionRecord using csv_format::convert_csv_record
+    /// 1. Reads the next CSV row into the reader's reused byte buffer
+    /// 2. Parses its fields with the reader's CsvDialect, without allocating
     /// 3. Includes line numbers in error messages for debugging
     ///
     /// # Returns
@@ -130,16 +183,15 @@ impl Iterator for SyncReader {
     /// * `Some(Err(String))` - Parse or conversion error with line number
     /// * `None` - End of file reached
     fn next(&mut self) -> Option<Self::Item> {
-        // Get next CSV record
-        let mut deserializer = self.reader.deserialize::<CsvRecord>();
-
-        match deserializer.next()? {
-            Ok(csv_record) => {
+        match self.reader.read_byte_record(&mut self.record) {
+            Ok(false) => None,
+            Ok(true) => {
                 self.line_num += 1;
-                // Convert CSV record to TransactionRecord
                 // Add line number context to any conversion errors
                 Some(
-                    convert_csv_record(csv_record)
+                    self.dialect
+                        .parse_fields(&self.columns, |position| self.record.get(position))
+                        .map(|record| record.with_line(self.line_num as u64 + 1))
                         .map_err(|e| format!("Line {}: {}", self.line_num + 1, e)),
                 )
             }
@@ -158,7 +210,8 @@ impl Iterator for SyncReader {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::TransactionType;
+    use crate::io::csv_format::DecimalSeparator;
+    use crate::types::{ClientId, TransactionType};
     use rust_decimal::Decimal;
     use std::io::Write;
     use tempfile::NamedTempFile;
@@ -188,6 +241,12 @@ mod tests {
         assert!(result.unwrap_err().contains("Failed to open file"));
     }
 
+    #[test]
+    fn test_sync_reader_empty_input() {
+        let mut reader = SyncReader::from_reader("".as_bytes()).unwrap();
+        assert!(reader.next().is_none());
+    }
+
     #[test]
     fn test_sync_reader_iterates_valid_deposit() {
         let csv_content = "type,client,tx,amount\ndeposit,1,1,100.0\n";
@@ -204,6 +263,7 @@ mod tests {
         assert_eq!(record.client, 1);
         assert_eq!(record.tx, 1);
         assert_eq!(record.amount, Some(Decimal::new(1000, 1)));
+        assert_eq!(record.line, Some(2));
     }
 
     #[test]
@@ -351,4 +411,56 @@ mod tests {
         assert_eq!(records[1].tx_type, TransactionType::Withdrawal);
         assert_eq!(records[2].tx_type, TransactionType::Dispute);
     }
+
+    #[test]
+    fn test_sync_reader_with_dialect() {
+        let csv_content = "kind;client;transaction_id;amount\n\
+            deposit;1;1;1234,5\n\
+            withdrawal;1;2;0,25\n\
+            dispute;1;1;\n";
+        let dialect = CsvDialect::default()
+            .with_delimiter(b';')
+            .with_decimal_separator(DecimalSeparator::Comma)
+            .with_header_alias("kind=type".parse().unwrap())
+            .with_header_alias("transaction_id=tx".parse().unwrap());
+
+        let reader = SyncReader::with_dialect(csv_content.as_bytes(), dialect).unwrap();
+        let records: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].amount, Some(Decimal::new(12345, 1)));
+        assert_eq!(records[1].tx, 2);
+        assert_eq!(records[1].amount, Some(Decimal::new(25, 2)));
+        assert_eq!(records[2].tx_type, TransactionType::Dispute);
+    }
+
+    #[test]
+    fn test_sync_reader_rejects_invalid_header() {
+        let file = create_temp_csv("type,client,txn,amount\ndeposit,1,1,100.0\n");
+
+        let err = SyncReader::new(file.path()).unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid CSV header: missing 'tx'; unknown column 'txn' (did you mean 'tx'?)"
+        );
+    }
+
+    #[test]
+    fn test_sync_reader_client_id_range() {
+        // Holds for every `ClientId` width selected by the client-id features
+        let max = ClientId::MAX;
+        let csv_content = format!(
+            "type,client,tx,amount\ndeposit,{},1,1.0\ndeposit,{},2,1.0\n",
+            max,
+            u128::from(max) + 1
+        );
+        let file = create_temp_csv(&csv_content);
+
+        let reader = SyncReader::new(file.path()).unwrap();
+        let records: Vec<_> = reader.collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].as_ref().unwrap().client, max);
+        assert!(records[1].is_err());
+    }
 }
@@ -0,0 +1,119 @@
+//! Warnings file output
+//!
+//! Records failing a sanity check are written here, with the warning, while
+//! still being processed. The file uses the input CSV columns plus `warning`,
+//! `source` and `line` columns, so the flagged records can be reviewed next
+//! to the input they came from (the readers ignore the extra columns).
+
+use crate::types::{TransactionRecord, TransactionType};
+use csv::Writer;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// CSV writer for sanity warnings
+pub struct WarningWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl WarningWriter<File> {
+    /// Create (or truncate) a warnings file
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(WarningWriter)` - With the header written
+    /// * `Err(String)` - If the file cannot be created
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create warnings file '{}': {}", path.display(), e))?;
+        Self::new(file)
+    }
+}
+
+impl<W: Write> WarningWriter<W> {
+    /// Create a warnings writer over any output, writing the header
+    pub fn new(output: W) -> Result<Self, String> {
+        let mut writer = Writer::from_writer(output);
+        writer
+            .write_record([
+                "type", "client", "tx", "amount", "warning", "source", "line",
+            ])
+            .map_err(|e| format!("Failed to write warnings header: {}", e))?;
+        Ok(Self { writer })
+    }
+
+    /// Write a flagged transaction and the warning about it
+    pub fn write(&mut self, record: &TransactionRecord, warning: &str) -> Result<(), String> {
+        let tx_type = match record.tx_type {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Approve => "approve",
+            TransactionType::Reject => "reject",
+        };
+        self.writer
+            .write_record([
+                tx_type,
+                &record.client.to_string(),
+                &record.tx.to_string(),
+                &record.amount.map(|a| a.to_string()).unwrap_or_default(),
+                warning,
+                record.source.as_deref().unwrap_or_default(),
+                &record.line.map(|l| l.to_string()).unwrap_or_default(),
+            ])
+            .map_err(|e| format!("Failed to write warning: {}", e))
+    }
+
+    /// Flush buffered warnings to the output
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush warnings file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_warnings() {
+        let mut output = Vec::new();
+        {
+            let mut writer = WarningWriter::new(&mut output).unwrap();
+            let record = TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 7,
+                amount: Some(Decimal::new(123456, 5)),
+                line: None,
+                source: None,
+            };
+            writer
+                .write(
+                    &record.with_line(2),
+                    "amount 1.23456 has more than 4 decimal places",
+                )
+                .unwrap();
+            writer.flush().unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "type,client,tx,amount,warning,source,line\n\
+             deposit,1,7,1.23456,amount 1.23456 has more than 4 decimal places,,2\n"
+        );
+    }
+
+    #[test]
+    fn test_create_in_missing_directory_fails() {
+        let result = WarningWriter::create(Path::new("missing-dir/warnings.csv"));
+        assert!(result
+            .err()
+            .unwrap()
+            .contains("Failed to create warnings file"));
+    }
+}
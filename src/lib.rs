@@ -15,6 +15,16 @@
 //!   - [`core::transaction_store`] - Transaction history for dispute resolution
 //! - [`io`] - I/O handling with pluggable parsing strategies
 //!
+//! # Features
+//!
+//! The async engine, the processing strategies and the CLI need the `native`
+//! feature, which is enabled by default. Built with `--no-default-features`,
+//! the library only contains the sync engine and CSV parsing, without tokio or
+//! threads, and compiles to `wasm32-unknown-unknown`. The `wasm` feature adds
+//! `wasm::process_csv_bytes` for embedding the engine in such builds, and the
+//! `ffi` feature adds C bindings (see [`ffi`]). The `graphql` feature adds a
+//! GraphQL schema over engine state (see [`graphql`]).
+//!
 //! # Transaction Types
 //!
 //! The engine supports five transaction types:
@@ -34,15 +44,25 @@
 //! - `locked`: Whether the account is locked (due to chargeback)
 
 // Module declarations
+#[cfg(feature = "native")]
 pub mod cli;
 pub mod core;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "graphql")]
+pub mod graphql;
 pub mod io;
+#[cfg(feature = "native")]
 pub mod strategy;
 pub mod types;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use core::{AccountManager, TransactionEngine, TransactionStore};
+pub use core::{AccountManager, Engine, ProcessingReport, TransactionEngine, TransactionStore};
 pub use io::write_accounts_csv;
 pub use types::{
-    Account, ClientId, PaymentError, StoredTransaction, TransactionId, TransactionRecord,
-    TransactionType,
+    Account, ClientId, ConfigError, EngineError, ErrorCategory, PaymentError, StoredTransaction,
+    TransactionId, TransactionRecord, TransactionType,
 };
+#[cfg(feature = "wasm")]
+pub use wasm::process_csv_bytes;
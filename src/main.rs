@@ -9,23 +9,78 @@
 //! cargo run -- --strategy sync transactions.csv > accounts.csv
 //! cargo run -- --strategy async transactions.csv > accounts.csv
 //! cargo run -- --strategy async --batch-size 2000 --max-concurrent 8 transactions.csv > accounts.csv
+//! cargo run -- --strategy async --max-inflight-clients 4 transactions.csv > accounts.csv
+//! cargo run --features sqlite -- --ledger ledger.db transactions.csv > accounts.csv
+//! cargo run -- --wal engine.wal transactions.csv > accounts.csv
+//! cargo run -- --max-error-rate 5 transactions.csv > accounts.csv
+//! cargo run -- --summary summary.json transactions.csv > accounts.csv
+//! cargo run -- --log-format json transactions.csv > accounts.csv 2> errors.jsonl
+//! cargo run -- --max-errors-per-class 100 transactions.csv > accounts.csv
+//! cargo run -- --analytics analytics.json transactions.csv > accounts.csv
+//! cargo run -- --changes changes.jsonl transactions.csv > accounts.csv
+//! cargo run -- --check-conservation transactions.csv > accounts.csv
+//! cargo run -- --clients 1,2,7-20 --filter-input transactions.csv > accounts.csv
+//! cargo run -- --client-map clients.csv transactions.csv > accounts.csv
+//! cargo run -- --string-clients transactions.csv > accounts.csv
+//! cargo run -- --pseudonymize-key key.bin transactions.csv > accounts.csv
+//! cargo run -- --manifest manifest.json transactions.csv > accounts.csv
+//! cargo run -- --verify-manifest manifest.json transactions.csv > accounts.csv
+//! cargo run -- --save-state state.bin transactions.csv > accounts.csv
+//! cargo run -- --lock-reasons transactions.csv > accounts.csv
+//! cargo run -- --client-stats transactions.csv > accounts.csv
+//! cargo run -- --columns client,available,held,total,locked,last_tx,dispute_count transactions.csv > accounts.csv
+//! cargo run -- --amount-format minor-units transactions.csv > accounts.csv
+//! cargo run -- query --state state.bin --client 42 --tx 1234
+//! cargo run -- statement --state state.bin --client 42
+//! cargo run --features graphql -- graphql --state state.bin --listen 127.0.0.1:8080
+//! cargo run -- apply --state state.bin delta.csv > accounts.csv
+//! cargo run -- reconcile --expected expected.csv transactions.csv > report.csv
+//! cargo run -- generate --transactions 100000 --clients 500 > transactions.csv
+//! cargo run --release -- bench --iterations 10 transactions.csv
+//! cargo run -- --output accounts.csv transactions.csv
+//! cargo run --features postgres -- --output postgres://user@localhost/payments transactions.csv
+//! cargo run -- --accounts-metadata accounts.csv --require-for-withdrawal kyc_status=verified transactions.csv
+//! cargo run -- day1.csv day2.csv day3.csv > accounts.csv
+//! cargo run -- '2024-*.csv' > accounts.csv
+//! cargo run --features object-store -- --output s3://bucket/accounts.csv s3://bucket/transactions.csv
+//! cargo run -- --follow --snapshot-interval 10 --output accounts.csv incoming.csv
+//! cargo run -- --follow --risk-rules rules.json --output accounts.csv incoming.csv
+//! cargo run -- --replica --save-state replica.bin --output accounts.csv engine.wal
 //! ```
 //!
-//! The program reads transaction records from the input CSV file, processes them
+//! Every mode is a subcommand: `process`, `query`, `statement`, `reconcile`,
+//! `merge`, `apply`, `bench` and `generate`, plus `graphql` with the
+//! `graphql` feature. A command line that starts with an option or an input
+//! file runs `process`, so `cargo run -- transactions.csv` is short for
+//! `cargo run -- process transactions.csv`.
+//!
+//! The program reads transaction records from the input CSV files in order, processes them
 //! through the payments engine using the selected processing strategy, and outputs
-//! the final account states to stdout.
+//! the final account states to stdout, or to the target given with `--output`.
 //!
 //! # Processing Strategies
 //!
 //! - **sync**: Synchronous CSV parsing with single-threaded processing (default)
 //! - **async**: Asynchronous batch processing with multi-threaded parallelism
+//! - **ledger** (`--ledger DB`): Sequential processing against a persistent SQLite ledger
+//! - **wal** (`--wal FILE`): Sequential processing that logs every transaction before
+//!   applying it and recovers state from the log on startup
+//! - **follow** (`--follow`): Tails a growing CSV file, writing account snapshots
+//!   as records are appended
+//! - **replica** (`--replica`): Follows the write-ahead log of a `--wal` process
+//!   read-only, keeping a copy of its account state for serving queries
 //!
 //! # Exit Codes
 //!
 //! - 0: Success
 //! - 1: Error (missing arguments, file not found, file not readable, etc.)
+//! - 2: Error threshold exceeded (`--fail-on-error` or `--max-error-rate`), or money
+//!   not conserved (`--check-conservation`), or balances that differ from the
+//!   expected ones (`reconcile`), or a run that does not reproduce the given
+//!   manifest (`--verify-manifest`)
 
 use rust_payments_engine::cli;
+use rust_payments_engine::io;
 use rust_payments_engine::strategy;
 use std::process;
 
@@ -33,21 +88,296 @@ fn main() {
     // Parse command-line arguments using clap
     let args = cli::parse_args();
 
+    match &args.command {
+        cli::Command::Process(args) => run_process(args),
+        cli::Command::Query(query) => {
+            if let Err(e) = query.run(&mut std::io::stdout()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        cli::Command::Statement(statement) => {
+            if let Err(e) = statement.run(&mut std::io::stdout()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        cli::Command::Reconcile(reconcile) => match reconcile.run(&mut std::io::stdout()) {
+            Ok(0) => {}
+            Ok(discrepancies) => {
+                eprintln!(
+                    "Error: Balances differ from the expected balances (discrepancies: {})",
+                    discrepancies
+                );
+                process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        },
+        cli::Command::Merge(merge) => match merge.run() {
+            Ok(totals) => eprintln!(
+                "Merged {} accounts: available {}, held {}, total {}",
+                totals.accounts, totals.available, totals.held, totals.total
+            ),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        },
+        cli::Command::Apply(apply) => match apply.run() {
+            Ok(summary) => eprintln!("{}", summary),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        },
+        cli::Command::Bench(bench) => {
+            if let Err(e) = bench.run(&mut std::io::stdout()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        cli::Command::Generate(generate) => {
+            if let Err(e) = generate.run(&mut std::io::stdout()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        #[cfg(feature = "graphql")]
+        cli::Command::Graphql(graphql) => {
+            if let Err(e) = graphql.run(&mut std::io::stdout()) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Process the input files and write the final account states
+fn run_process(args: &cli::ProcessArgs) {
+    // Every warning and error line from here on is in the --log-format
+    io::set_log_format(args.log_format);
+    io::set_max_lines_per_class(args.max_errors_per_class);
+    let policy = args.exit_policy();
+    let mut input = args.input_options();
+
+    // Load the client map, or start interning string clients; CSV input is
+    // read through the registry
+    let client_map = match args.client_registry() {
+        Ok(client_map) => client_map,
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    };
+    if let Some(client_map) = &client_map {
+        input = input.with_csv_dialect(args.csv_dialect().with_client_map(client_map.clone()));
+    }
+
+    // Load the pseudonymization key; logged errors are masked with it
+    let pseudonymizer = match args.pseudonymizer(client_map.clone()) {
+        Ok(pseudonymizer) => pseudonymizer,
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    };
+    if let Some(pseudonymizer) = &pseudonymizer {
+        input = input.with_pseudonymizer(pseudonymizer.clone());
+    }
+
+    // Load the standing orders expanded into the input
+    match args.standing_orders() {
+        Ok(orders) => input = input.with_standing_orders(orders),
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    }
+
+    // Resolve the input files, expanding glob patterns
+    let input_paths = match args.input_paths() {
+        Ok(input_paths) => input_paths,
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    };
+
+    // Load account metadata and risk rules
+    let engine_config = match args.engine_config() {
+        Ok(engine_config) => engine_config,
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    };
+
     // Create the appropriate processing strategy based on CLI arguments
-    let strategy = {
-        let config = if matches!(args.strategy, cli::StrategyType::Async) {
+    let save_state = args.save_state.as_deref();
+    let strategy = if let Some(ledger_path) = &args.ledger {
+        match strategy::create_ledger_strategy(ledger_path, input, engine_config) {
+            Ok(strategy) => strategy,
+            Err(e) => {
+                io::log(io::LogLine::error(format!("Error: {}", e)));
+                process::exit(1);
+            }
+        }
+    } else if let Some(wal_path) = &args.wal {
+        strategy::create_wal_strategy(wal_path, input, engine_config, save_state)
+    } else if let Some(shards) = args.shards {
+        strategy::create_sharded_strategy(shards as usize, input, engine_config)
+    } else if args.replica {
+        strategy::create_replica_strategy(engine_config, args.follow_options(), save_state)
+    } else if args.follow {
+        strategy::create_follow_strategy(
+            input,
+            engine_config,
+            args.follow_options(),
+            save_state,
+            args.risk_rules.as_deref(),
+        )
+    } else {
+        let config = if !matches!(args.strategy, cli::StrategyType::Sync) {
             Some(args.to_batch_config())
         } else {
             None
         };
-        strategy::create_strategy(args.strategy, config)
+        strategy::create_strategy(
+            args.strategy.clone(),
+            config,
+            input,
+            engine_config,
+            save_state,
+        )
+    };
+
+    // Open the output sink (stdout unless --output is given); when following
+    // or replicating, an output file holds only the latest snapshot, and with a client map the
+    // output shows the external client identifiers, or their pseudonyms
+    let sink = if let Some(pseudonymizer) = pseudonymizer {
+        io::create_pseudonymized_sink(&args.output, pseudonymizer)
+    } else if let Some(client_map) = client_map {
+        io::create_mapped_sink(&args.output, client_map)
+    } else if args.follow || args.replica {
+        io::create_snapshot_sink(&args.output)
+    } else {
+        io::create_sink(&args.output)
+    };
+    let mut output = match sink {
+        Ok(output) => output,
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
     };
 
-    // Process transactions using the selected strategy
-    // Output goes to stdout
-    let mut output = std::io::stdout();
-    if let Err(e) = strategy.process(&args.input_file, &mut output) {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    // Digest the inputs and the output for the run manifest
+    let mut manifest = None;
+    if args.manifest.is_some() || args.verify_manifest.is_some() {
+        let recorder = match cli::ManifestRecorder::start(std::env::args_os(), &input_paths) {
+            Ok(recorder) => recorder,
+            Err(e) => {
+                io::log(io::LogLine::error(format!("Error: {}", e)));
+                process::exit(1);
+            }
+        };
+        let sink = io::DigestSink::new(output);
+        manifest = Some((recorder, sink.digest()));
+        output = Box::new(sink);
+    }
+
+    // Only write the accounts of the selected clients
+    if let Some(clients) = &args.clients {
+        output = Box::new(io::FilteredSink::new(output, clients.clone()));
+    }
+
+    // Add why each account was locked
+    if args.lock_reasons {
+        output = Box::new(io::LockReasonSink::new(output));
+    }
+
+    // Add the activity counters of each client
+    if args.client_stats {
+        output = Box::new(io::ActivitySink::new(output));
+    }
+
+    // Write only the selected columns, or amounts in minor units
+    let layout = args.output_layout();
+    if !layout.is_standard() {
+        output = Box::new(io::LayoutSink::new(output, layout));
+    }
+
+    // Process transactions using the selected strategy, then count the error
+    // lines --max-errors-per-class suppressed
+    let result = strategy.process_files(&input_paths, output.as_mut());
+    io::log_suppressed();
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(e) => {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    };
+
+    // Write the machine-readable summary if requested
+    if let Some(target) = &args.summary {
+        if let Err(e) = summary.write_json_to(target) {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    }
+
+    // Write the aggregate analytics if requested
+    if let (Some(target), Some(analytics)) = (&args.analytics, &summary.analytics) {
+        if let Err(e) = analytics.write_json_to(target) {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(1);
+        }
+    }
+
+    // Write the run manifest, and check it against the given one
+    if let Some((recorder, digest)) = manifest {
+        let manifest = recorder.finish(digest.hex());
+        if let Some(path) = &args.manifest {
+            if let Err(e) = manifest.write_to(path) {
+                io::log(io::LogLine::error(format!("Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        if let Some(path) = &args.verify_manifest {
+            let expected = match cli::RunManifest::read_from(path) {
+                Ok(expected) => expected,
+                Err(e) => {
+                    io::log(io::LogLine::error(format!("Error: {}", e)));
+                    process::exit(1);
+                }
+            };
+            let differences = manifest.differences(&expected);
+            for difference in &differences {
+                io::log(io::LogLine::error(format!(
+                    "Manifest mismatch: {}",
+                    difference
+                )));
+            }
+            if !differences.is_empty() {
+                io::log(io::LogLine::error(format!(
+                    "Error: Run does not reproduce the manifest (differences: {})",
+                    differences.len()
+                )));
+                process::exit(2);
+            }
+        }
+    }
+
+    // Apply the exit-code policy, summarizing error counts when it is active
+    if policy.is_enabled() {
+        io::log(io::LogLine::info(summary.to_string()));
+        if let Err(e) = policy.check(&summary) {
+            io::log(io::LogLine::error(format!("Error: {}", e)));
+            process::exit(2);
+        }
     }
 }
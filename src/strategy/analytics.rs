@@ -0,0 +1,284 @@
+//! Aggregate analytics of a processing run
+//!
+//! Collects statistics over the applied transactions while they are
+//! processed, which is cheap inline and expensive to compute downstream from
+//! the raw input: the top clients by volume and by dispute rate, chargeback
+//! totals and the distribution of transaction amounts. The statistics are fed
+//! from the engine's postings, so they cover exactly the applied transactions,
+//! including the implicit dispute of a direct chargeback.
+
+use crate::core::Posting;
+use crate::types::{ClientId, TransactionType};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+/// Number of amount buckets: one per power of ten from 1 to 1,000,000, below
+/// and above
+const AMOUNT_BUCKETS: usize = 8;
+
+/// Applied transactions of one client
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ClientStats {
+    pub client: ClientId,
+    pub deposits: u64,
+    pub withdrawals: u64,
+    pub disputes: u64,
+    pub chargebacks: u64,
+    /// Sum of the client's deposit and withdrawal amounts
+    pub volume: Decimal,
+    /// Sum of the amounts charged back
+    pub charged_back: Decimal,
+    /// Disputes per deposit or withdrawal, rounded to 4 decimal places
+    pub dispute_rate: Decimal,
+}
+
+/// Number of chargebacks and the amount charged back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChargebackTotals {
+    pub count: u64,
+    pub amount: Decimal,
+}
+
+/// Number of deposits and withdrawals with amounts in `[min, max)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AmountBucket {
+    pub min: Decimal,
+    /// Upper bound (exclusive), or `None` for the last bucket
+    pub max: Option<Decimal>,
+    pub count: u64,
+}
+
+/// Aggregate statistics of a run, as written by `--analytics`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AnalyticsReport {
+    /// Number of clients with at least one applied transaction
+    pub clients: u64,
+    /// Disputes per deposit or withdrawal over all clients
+    pub dispute_rate: Decimal,
+    pub chargebacks: ChargebackTotals,
+    /// Clients with the highest volume, highest first
+    pub top_by_volume: Vec<ClientStats>,
+    /// Clients with disputes with the highest dispute rate, highest first
+    pub top_by_dispute_rate: Vec<ClientStats>,
+    /// Distribution of deposit and withdrawal amounts
+    pub amounts: Vec<AmountBucket>,
+}
+
+impl AnalyticsReport {
+    /// Write the report as JSON
+    pub fn write_json(&self, mut output: impl Write) -> Result<(), String> {
+        serde_json::to_writer_pretty(&mut output, self)
+            .map_err(|e| format!("Failed to write analytics: {}", e))?;
+        writeln!(output).map_err(|e| format!("Failed to write analytics: {}", e))
+    }
+
+    /// Write the report as JSON to a file, or to stderr if `target` is `-`
+    pub fn write_json_to(&self, target: &str) -> Result<(), String> {
+        if target == "-" {
+            return self.write_json(std::io::stderr());
+        }
+        let file = File::create(target)
+            .map_err(|e| format!("Failed to create analytics file '{}': {}", target, e))?;
+        self.write_json(file)
+    }
+}
+
+/// Statistics collected from the postings of a run
+#[derive(Debug, Clone)]
+pub(crate) struct Analytics {
+    /// Number of clients listed per ranking
+    top: usize,
+    clients: HashMap<ClientId, ClientStats>,
+    chargebacks: ChargebackTotals,
+    amounts: [u64; AMOUNT_BUCKETS],
+}
+
+/// Dispute rate of `disputes` over `transactions`, rounded to 4 decimal places
+fn dispute_rate(disputes: u64, transactions: u64) -> Decimal {
+    if transactions == 0 {
+        return Decimal::ZERO;
+    }
+    (Decimal::from(disputes) / Decimal::from(transactions)).round_dp(4)
+}
+
+/// Lower bound of an amount bucket
+fn bucket_min(bucket: usize) -> Decimal {
+    match bucket {
+        0 => Decimal::ZERO,
+        _ => Decimal::from(10u64.pow(bucket as u32 - 1)),
+    }
+}
+
+impl Analytics {
+    /// Collect statistics, listing the `top` clients per ranking
+    pub(crate) fn new(top: usize) -> Self {
+        Self {
+            top,
+            clients: HashMap::new(),
+            chargebacks: ChargebackTotals::default(),
+            amounts: [0; AMOUNT_BUCKETS],
+        }
+    }
+
+    /// Count the transaction behind a posting
+    ///
+    /// The postings of fees are skipped, so a transaction with a fee is only
+    /// counted once.
+    pub(crate) fn record(&mut self, posting: &Posting) {
+        if posting.is_fee() {
+            return;
+        }
+        let stats = self
+            .clients
+            .entry(posting.client)
+            .or_insert_with(|| ClientStats {
+                client: posting.client,
+                ..ClientStats::default()
+            });
+        match posting.tx_type {
+            TransactionType::Deposit | TransactionType::Withdrawal => {
+                if posting.tx_type == TransactionType::Deposit {
+                    stats.deposits += 1;
+                } else {
+                    stats.withdrawals += 1;
+                }
+                stats.volume = stats.volume.saturating_add(posting.amount);
+                let bucket = (1..AMOUNT_BUCKETS)
+                    .take_while(|&bucket| posting.amount >= bucket_min(bucket))
+                    .count();
+                self.amounts[bucket] += 1;
+            }
+            TransactionType::Dispute => stats.disputes += 1,
+            TransactionType::Resolve | TransactionType::Approve | TransactionType::Reject => {}
+            TransactionType::Chargeback => {
+                stats.chargebacks += 1;
+                stats.charged_back = stats.charged_back.saturating_add(posting.amount);
+                self.chargebacks.count += 1;
+                self.chargebacks.amount = self.chargebacks.amount.saturating_add(posting.amount);
+            }
+        }
+    }
+
+    /// Build the report of the transactions counted so far
+    pub(crate) fn report(&self) -> AnalyticsReport {
+        let mut clients: Vec<ClientStats> = self
+            .clients
+            .values()
+            .map(|stats| ClientStats {
+                dispute_rate: dispute_rate(stats.disputes, stats.deposits + stats.withdrawals),
+                ..stats.clone()
+            })
+            .collect();
+        let (disputes, transactions) = clients.iter().fold((0, 0), |(d, t), stats| {
+            (d + stats.disputes, t + stats.deposits + stats.withdrawals)
+        });
+
+        // Ties are broken by client ID so the report is deterministic
+        clients.sort_by(|a, b| b.volume.cmp(&a.volume).then(a.client.cmp(&b.client)));
+        let top_by_volume = clients.iter().take(self.top).cloned().collect();
+        clients.retain(|stats| stats.disputes > 0);
+        clients.sort_by(|a, b| {
+            b.dispute_rate
+                .cmp(&a.dispute_rate)
+                .then(a.client.cmp(&b.client))
+        });
+        clients.truncate(self.top);
+
+        AnalyticsReport {
+            clients: self.clients.len() as u64,
+            dispute_rate: dispute_rate(disputes, transactions),
+            chargebacks: self.chargebacks,
+            top_by_volume,
+            top_by_dispute_rate: clients,
+            amounts: (0..AMOUNT_BUCKETS)
+                .map(|bucket| AmountBucket {
+                    min: bucket_min(bucket),
+                    max: (bucket + 1 < AMOUNT_BUCKETS).then(|| bucket_min(bucket + 1)),
+                    count: self.amounts[bucket],
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn analytics(top: usize, postings: &[(TransactionType, ClientId, i64)]) -> Analytics {
+        let mut analytics = Analytics::new(top);
+        for (tx, &(tx_type, client, amount)) in postings.iter().enumerate() {
+            analytics.record(&Posting::new(
+                tx_type,
+                client,
+                tx as u64 + 1,
+                Decimal::from(amount),
+            ));
+        }
+        analytics
+    }
+
+    #[test]
+    fn test_report_ranks_clients() {
+        let analytics = analytics(
+            2,
+            &[
+                (TransactionType::Deposit, 1, 100),
+                (TransactionType::Deposit, 2, 500),
+                (TransactionType::Withdrawal, 2, 50),
+                (TransactionType::Deposit, 3, 100),
+                (TransactionType::Dispute, 1, 100),
+                (TransactionType::Chargeback, 1, 100),
+                (TransactionType::Dispute, 2, 50),
+                (TransactionType::Resolve, 2, 50),
+            ],
+        );
+
+        let report = analytics.report();
+        assert_eq!(report.clients, 3);
+        assert_eq!(report.dispute_rate, Decimal::new(5, 1));
+        assert_eq!(
+            report.chargebacks,
+            ChargebackTotals {
+                count: 1,
+                amount: Decimal::from(100),
+            }
+        );
+        let ranked = |stats: &[ClientStats]| stats.iter().map(|s| s.client).collect::<Vec<_>>();
+        // Clients 1 and 3 have the same volume
+        assert_eq!(ranked(&report.top_by_volume), vec![2, 1]);
+        assert_eq!(ranked(&report.top_by_dispute_rate), vec![1, 2]);
+        assert_eq!(report.top_by_volume[0].volume, Decimal::from(550));
+        assert_eq!(report.top_by_dispute_rate[0].dispute_rate, Decimal::ONE);
+        assert_eq!(
+            report.top_by_dispute_rate[1].dispute_rate,
+            Decimal::new(5, 1)
+        );
+        assert_eq!(
+            report.top_by_dispute_rate[0].charged_back,
+            Decimal::from(100)
+        );
+    }
+
+    #[rstest]
+    #[case::below_one(Decimal::new(5, 1), 0)]
+    #[case::one(Decimal::ONE, 1)]
+    #[case::hundreds(Decimal::new(99999, 2), 3)]
+    #[case::thousand(Decimal::from(1000), 4)]
+    #[case::millions(Decimal::from(5_000_000), 7)]
+    fn test_amount_buckets(#[case] amount: Decimal, #[case] bucket: usize) {
+        let mut analytics = Analytics::new(10);
+        analytics.record(&Posting::new(TransactionType::Deposit, 1, 1, amount));
+
+        let amounts = analytics.report().amounts;
+        assert_eq!(amounts.len(), AMOUNT_BUCKETS);
+        assert_eq!(amounts[bucket].count, 1);
+        assert!(amounts[bucket].min <= amount);
+        assert!(amounts[bucket].max.is_none_or(|max| amount < max));
+        assert_eq!(amounts.iter().map(|b| b.count).sum::<u64>(), 1);
+    }
+}
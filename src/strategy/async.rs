@@ -8,8 +8,10 @@
 //!
 //! ```text
 //! AsyncProcessingStrategy
-//!     ├── BatchConfig (batch_size, max_concurrent_batches)
-//!     ├── AsyncReader (batch CSV reading)
+//!     ├── BatchConfig (batch_size, max_concurrent_batches, max_inflight_clients, runtime,
+//!     │                duplicate_filter)
+//!     ├── AsyncRecordSource (AsyncReader for CSV, blocking reader for other formats)
+//!     ├── BatchPipeline (cross-batch overlap keyed by client)
 //!     ├── BatchProcessor (client partitioning + threading)
 //!     └── AsyncTransactionEngine (thread-safe processing)
 //!         ├── AsyncAccountManager (thread-safe account state)
@@ -19,20 +21,54 @@
 //! # Thread-Based Parallelism
 //!
 //! This strategy uses true thread-based parallelism:
-//! - Processes batches sequentially to maintain per-client ordering across entire file
 //! - Within each batch, partitions by client ID for parallel processing
+//! - Pipelines up to `max_concurrent_batches` batches: a client's partition in a
+//!   later batch waits only for that client's earlier partitions, so clients
+//!   absent from the previous batch start immediately
 //! - Spawns worker threads via tokio multi-threaded runtime
 //! - Maintains per-client transaction ordering both within and across batches
 //! - Uses Arc + DashMap for thread-safe shared state
+//!
+//! # Cancellation
+//!
+//! With `with_cancellation`, cancelling the token from another thread stops
+//! the run between transactions: no further batches are read, and the batches
+//! in flight stop as described in `BatchProcessor`. The run then finishes
+//! normally with the transactions applied so far: the outputs are flushed, the
+//! state file is saved, the accounts are written, and the summary is marked
+//! `cancelled`.
+//!
+//! # Batch Deadlines
+//!
+//! With `BatchConfig::batch_deadline`, a batch still running its timeout after
+//! it was submitted is reported with diagnostics (see `BatchPipeline`). Under
+//! `DeadlinePolicy::Abort` the run then stops like a cancelled one, but fails
+//! with an error instead of completing.
 
+use crate::cli::RuntimeFlavor;
+use crate::core::r#async::batch_processor::ProcessingResult;
+#[cfg(feature = "fault-injection")]
+use crate::core::r#async::FaultInjection;
 use crate::core::r#async::{
-    AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore, BatchProcessor,
+    AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore, BatchDeadline,
+    BatchPipeline, BatchProcessor, CancellationToken, DuplicateFilter,
+};
+use crate::core::{
+    save_state, BalanceChanges, DuplicateTxPolicy, Engine, EngineConfig, RetryPolicy, TrialBalance,
+};
+use crate::io::{
+    create_change_sink, create_dead_letter_sink, is_object_url, log, AccountSink,
+    BalanceHistoryWriter, ChangeSink, DeadLetter, DeadLetterSink, JournalWriter, LogLine,
+    TrialBalanceWriter,
+};
+use crate::strategy::{
+    check_inputs, input_source, open_batches, transaction_error, AccountTotals, Analytics,
+    Conservation, DedupFilter, InputOptions, ProcessingStrategy, Quarantine, RunSummary,
+    SanityWarnings,
 };
-use crate::io::async_reader::AsyncReader;
-use crate::io::csv_format::write_accounts_csv;
-use crate::strategy::ProcessingStrategy;
-use std::io::Write;
-use std::path::Path;
+use crate::types::{ConfigError, EngineError};
+use std::fs::File;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 /// Configuration for batch processing
@@ -45,6 +81,27 @@ pub struct BatchConfig {
     pub batch_size: usize,
     /// Maximum number of batches processing concurrently
     pub max_concurrent_batches: usize,
+    /// Maximum number of client partitions processed concurrently within a batch
+    ///
+    /// `None` (the default) places no limit beyond the worker thread count.
+    pub max_inflight_clients: Option<usize>,
+    /// Tuning of the tokio runtime the batches run on
+    pub runtime: RuntimeOptions,
+    /// Bloom filter in front of the duplicate transaction check
+    ///
+    /// `None` (the default) looks every deposit and withdrawal up in the
+    /// transaction store.
+    pub duplicate_filter: Option<DuplicateFilterOptions>,
+    /// Retries of transactions failing with a transient error
+    ///
+    /// The default attempts every transaction once.
+    pub retry: RetryPolicy,
+    /// Time each batch may take before it is reported, and aborted on if
+    /// its policy says so (default: none)
+    pub batch_deadline: Option<BatchDeadline>,
+    /// Faults injected into processing, for testing (default: none)
+    #[cfg(feature = "fault-injection")]
+    pub faults: Option<FaultInjection>,
 }
 
 impl Default for BatchConfig {
@@ -52,38 +109,450 @@ impl Default for BatchConfig {
         Self {
             batch_size: 1000,
             max_concurrent_batches: num_cpus::get(),
+            max_inflight_clients: None,
+            runtime: RuntimeOptions::default(),
+            duplicate_filter: None,
+            retry: RetryPolicy::none(),
+            batch_deadline: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
         }
     }
 }
 
-impl BatchConfig {
-    /// Create a new BatchConfig with custom values
-    pub fn new(batch_size: usize, max_concurrent_batches: usize) -> Self {
-        let default = Self::default();
-
-        let batch_size = if batch_size == 0 {
-            eprintln!(
-                "Warning: Invalid batch_size ({}), using default ({})",
-                batch_size, default.batch_size
-            );
-            default.batch_size
-        } else {
-            batch_size
-        };
+/// Total input size up to which `RuntimeFlavor::Auto` picks the
+/// current-thread runtime
+pub const AUTO_CURRENT_THREAD_BYTES: u64 = 1 << 20;
 
-        let max_concurrent_batches = if max_concurrent_batches == 0 {
-            eprintln!(
-                "Warning: Invalid max_concurrent_batches ({}), using default ({})",
-                max_concurrent_batches, default.max_concurrent_batches
-            );
-            default.max_concurrent_batches
+/// Tuning of the tokio runtime of the async strategy
+///
+/// The defaults match a plain multi-threaded tokio runtime. On machines with
+/// many cores, pinning the worker threads keeps each on its own core (and
+/// its caches); for small inputs, the current-thread runtime avoids starting
+/// workers whose startup would dominate the run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuntimeOptions {
+    /// Runtime flavor
+    pub flavor: RuntimeFlavor,
+    /// Pin the runtime's threads to CPU cores, one core after the other
+    ///
+    /// Requires the `core-pinning` feature. Has no effect on the
+    /// current-thread runtime, which runs on the calling thread.
+    pub pin_threads: bool,
+    /// Name of the runtime's threads (default: tokio's `tokio-runtime-worker`)
+    pub thread_name: Option<String>,
+}
+
+impl RuntimeOptions {
+    /// Build the runtime for processing `input_paths` with `worker_threads`
+    /// worker threads
+    fn build(
+        &self,
+        worker_threads: usize,
+        input_paths: &[PathBuf],
+    ) -> Result<tokio::runtime::Runtime, EngineError> {
+        let current_thread = match self.flavor {
+            RuntimeFlavor::MultiThread => false,
+            RuntimeFlavor::CurrentThread => true,
+            RuntimeFlavor::Auto => is_small_input(input_paths),
+        };
+        let mut builder = if current_thread {
+            tokio::runtime::Builder::new_current_thread()
         } else {
-            max_concurrent_batches
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            builder.worker_threads(worker_threads);
+            if self.pin_threads {
+                pin_threads(&mut builder)?;
+            }
+            builder
         };
+        if let Some(name) = &self.thread_name {
+            builder.thread_name(name);
+        }
+        // Retries wait between attempts on the runtime's timer
+        builder.enable_time();
+        builder.build().map_err(EngineError::Runtime)
+    }
+}
+
+/// Whether the inputs are local files of at most `AUTO_CURRENT_THREAD_BYTES`
+/// in total
+fn is_small_input(input_paths: &[PathBuf]) -> bool {
+    local_input_bytes(input_paths).is_some_and(|total| total <= AUTO_CURRENT_THREAD_BYTES)
+}
 
+/// Total size of the inputs, or `None` unless they are all local files
+fn local_input_bytes(input_paths: &[PathBuf]) -> Option<u64> {
+    let mut total = 0;
+    for path in input_paths {
+        if is_object_url(&path.to_string_lossy()) {
+            return None;
+        }
+        total += std::fs::metadata(path).ok()?.len();
+    }
+    Some(total)
+}
+
+/// Number of transactions a duplicate filter is sized for when neither its
+/// capacity nor the input size is known
+pub const DEFAULT_DUPLICATE_FILTER_CAPACITY: usize = 1 << 20;
+
+/// Size of the shortest CSV record, `deposit,1,1,1` and a newline, used to
+/// estimate the number of records from the input size
+const MIN_RECORD_BYTES: u64 = 14;
+
+/// Bloom filter in front of the async transaction store's duplicate check
+///
+/// Deposits and withdrawals whose ID the filter has not seen skip the store
+/// lookup. False positives only cost that lookup, so the rate trades memory
+/// (about 10 bits per transaction at 1%) against lookups.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DuplicateFilterOptions {
+    /// Target rate of new IDs looked up anyway, strictly between 0 and 1
+    pub false_positive_rate: f64,
+    /// Number of deposits and withdrawals to size the filter for
+    ///
+    /// `None` estimates it from the size of local input files, or uses
+    /// `DEFAULT_DUPLICATE_FILTER_CAPACITY` for other inputs.
+    pub capacity: Option<usize>,
+}
+
+impl DuplicateFilterOptions {
+    /// Filter new IDs with the given false positive rate, sized from the input
+    pub fn new(false_positive_rate: f64) -> Self {
         Self {
+            false_positive_rate,
+            capacity: None,
+        }
+    }
+
+    /// Size the filter for `capacity` deposits and withdrawals
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Build an empty filter for processing `input_paths`
+    fn build(&self, input_paths: &[PathBuf]) -> DuplicateFilter {
+        let capacity = self.capacity.unwrap_or_else(|| {
+            local_input_bytes(input_paths).map_or(DEFAULT_DUPLICATE_FILTER_CAPACITY, |bytes| {
+                usize::try_from(bytes / MIN_RECORD_BYTES).unwrap_or(usize::MAX)
+            })
+        });
+        DuplicateFilter::new(capacity, self.false_positive_rate)
+    }
+}
+
+/// Pin every thread the runtime starts to the next CPU core, round-robin
+#[cfg(feature = "core-pinning")]
+fn pin_threads(builder: &mut tokio::runtime::Builder) -> Result<(), EngineError> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let cores = core_affinity::get_core_ids()
+        .filter(|cores| !cores.is_empty())
+        .ok_or_else(|| {
+            EngineError::Other("Failed to list the CPU cores to pin threads to".to_string())
+        })?;
+    let next = AtomicUsize::new(0);
+    builder.on_thread_start(move || {
+        let core = cores[next.fetch_add(1, Ordering::Relaxed) % cores.len()];
+        core_affinity::set_for_current(core);
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "core-pinning"))]
+fn pin_threads(_builder: &mut tokio::runtime::Builder) -> Result<(), EngineError> {
+    Err(EngineError::Other(
+        "Pinning threads requires building with the 'core-pinning' feature".to_string(),
+    ))
+}
+
+/// Largest allowed `batch_size`
+pub const MAX_BATCH_SIZE: usize = 1_000_000;
+
+/// Largest allowed `max_concurrent_batches`
+pub const MAX_CONCURRENT_BATCHES: usize = 1024;
+
+impl BatchConfig {
+    /// Start building a validated BatchConfig from the defaults
+    pub fn builder() -> BatchConfigBuilder {
+        BatchConfigBuilder::default()
+    }
+}
+
+/// Builder of a validated BatchConfig
+///
+/// Unset values keep their defaults. `build` rejects invalid values instead
+/// of replacing them:
+///
+/// ```
+/// use rust_payments_engine::strategy::BatchConfig;
+///
+/// let config = BatchConfig::builder()
+///     .batch_size(500)
+///     .max_concurrent_batches(4)
+///     .build()
+///     .unwrap();
+/// assert_eq!(config.batch_size, 500);
+/// assert!(BatchConfig::builder().batch_size(0).build().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BatchConfigBuilder {
+    batch_size: Option<usize>,
+    max_concurrent_batches: Option<usize>,
+    max_inflight_clients: Option<usize>,
+    expected_clients: Option<usize>,
+    runtime: RuntimeOptions,
+    duplicate_filter: Option<DuplicateFilterOptions>,
+    retry: Option<RetryPolicy>,
+    batch_deadline: Option<BatchDeadline>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<FaultInjection>,
+}
+
+/// Check that a setting is positive and at most `max`
+fn check_bounds(field: &'static str, value: usize, max: usize) -> Result<usize, ConfigError> {
+    if value == 0 {
+        Err(ConfigError::Zero { field })
+    } else if value > max {
+        Err(ConfigError::TooLarge { field, value, max })
+    } else {
+        Ok(value)
+    }
+}
+
+/// Check that a rate is strictly between 0 and 1
+fn check_rate(field: &'static str, value: f64) -> Result<f64, ConfigError> {
+    if value > 0.0 && value < 1.0 {
+        Ok(value)
+    } else {
+        Err(ConfigError::InvalidRate { field, value })
+    }
+}
+
+/// Check that a batch deadline has a timeout
+fn check_batch_deadline(deadline: BatchDeadline) -> Result<BatchDeadline, ConfigError> {
+    if deadline.timeout.is_zero() {
+        return Err(ConfigError::Zero {
+            field: "batch_deadline.timeout",
+        });
+    }
+    Ok(deadline)
+}
+
+/// Check the settings of a duplicate filter
+fn check_duplicate_filter(
+    options: DuplicateFilterOptions,
+) -> Result<DuplicateFilterOptions, ConfigError> {
+    check_rate(
+        "duplicate_filter.false_positive_rate",
+        options.false_positive_rate,
+    )?;
+    if let Some(capacity) = options.capacity {
+        check_bounds("duplicate_filter.capacity", capacity, usize::MAX)?;
+    }
+    Ok(options)
+}
+
+impl BatchConfigBuilder {
+    /// Set the number of transactions per batch (1 to `MAX_BATCH_SIZE`)
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Set the number of batches processing concurrently (1 to
+    /// `MAX_CONCURRENT_BATCHES`)
+    pub fn max_concurrent_batches(mut self, max_concurrent_batches: usize) -> Self {
+        self.max_concurrent_batches = Some(max_concurrent_batches);
+        self
+    }
+
+    /// Limit the number of client partitions processed concurrently (at
+    /// least 1)
+    pub fn max_inflight_clients(mut self, max_inflight_clients: usize) -> Self {
+        self.max_inflight_clients = Some(max_inflight_clients);
+        self
+    }
+
+    /// Set the tuning of the tokio runtime
+    pub fn runtime(mut self, runtime: RuntimeOptions) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Check for duplicate transaction IDs through a bloom filter
+    pub fn duplicate_filter(mut self, options: DuplicateFilterOptions) -> Self {
+        self.duplicate_filter = Some(options);
+        self
+    }
+
+    /// Retry transactions failing with a transient error (at least 1
+    /// attempt)
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Report batches running longer than the deadline's timeout (non-zero),
+    /// aborting on them if its policy says so
+    pub fn batch_deadline(mut self, deadline: BatchDeadline) -> Self {
+        self.batch_deadline = Some(deadline);
+        self
+    }
+
+    /// Inject faults into processing, for testing
+    #[cfg(feature = "fault-injection")]
+    pub fn faults(mut self, faults: FaultInjection) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Hint the number of distinct clients in the input
+    ///
+    /// Batches are partitioned by client, so a batch smaller than the number
+    /// of clients leaves little work per partition; `build` rejects such a
+    /// batch size.
+    pub fn expected_clients(mut self, clients: usize) -> Self {
+        self.expected_clients = Some(clients);
+        self
+    }
+
+    /// Validate the settings and build the BatchConfig
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(BatchConfig)` - If every setting is in range
+    /// * `Err(ConfigError)` - The first invalid setting
+    pub fn build(self) -> Result<BatchConfig, ConfigError> {
+        let default = BatchConfig::default();
+        let batch_size = match self.batch_size {
+            Some(value) => check_bounds("batch_size", value, MAX_BATCH_SIZE)?,
+            None => default.batch_size,
+        };
+        let max_concurrent_batches = match self.max_concurrent_batches {
+            Some(value) => check_bounds("max_concurrent_batches", value, MAX_CONCURRENT_BATCHES)?,
+            None => default.max_concurrent_batches,
+        };
+        let max_inflight_clients = self
+            .max_inflight_clients
+            .map(|value| check_bounds("max_inflight_clients", value, usize::MAX))
+            .transpose()?;
+        if let Some(clients) = self.expected_clients {
+            if batch_size < clients {
+                return Err(ConfigError::BatchSmallerThanClients {
+                    batch_size,
+                    clients,
+                });
+            }
+        }
+
+        let duplicate_filter = self
+            .duplicate_filter
+            .map(check_duplicate_filter)
+            .transpose()?;
+        if let Some(retry) = self.retry {
+            check_bounds(
+                "retry.max_attempts",
+                retry.max_attempts as usize,
+                usize::MAX,
+            )?;
+        }
+        let batch_deadline = self.batch_deadline.map(check_batch_deadline).transpose()?;
+
+        Ok(BatchConfig {
             batch_size,
             max_concurrent_batches,
+            max_inflight_clients,
+            runtime: self.runtime,
+            duplicate_filter,
+            retry: self.retry.unwrap_or(default.retry),
+            batch_deadline,
+            #[cfg(feature = "fault-injection")]
+            faults: self.faults,
+        })
+    }
+
+    /// Build the BatchConfig, replacing invalid settings instead of failing
+    ///
+    /// Zero values fall back to their defaults (no limit for
+    /// `max_inflight_clients`) and values above their maximum are capped, each
+    /// with a warning on stderr; an invalid duplicate filter is left out, and
+    /// a retry policy without attempts makes one. This keeps the CLI forgiving about tuning
+    /// flags; library users get the errors from `build`.
+    pub(crate) fn build_lenient(self) -> BatchConfig {
+        let default = BatchConfig::default();
+        BatchConfig {
+            batch_size: self.batch_size.map_or(default.batch_size, |value| {
+                lenient_bounds("batch_size", value, MAX_BATCH_SIZE).unwrap_or(default.batch_size)
+            }),
+            max_concurrent_batches: self.max_concurrent_batches.map_or(
+                default.max_concurrent_batches,
+                |value| {
+                    lenient_bounds("max_concurrent_batches", value, MAX_CONCURRENT_BATCHES)
+                        .unwrap_or(default.max_concurrent_batches)
+                },
+            ),
+            max_inflight_clients: self
+                .max_inflight_clients
+                .and_then(|value| lenient_bounds("max_inflight_clients", value, usize::MAX)),
+            runtime: self.runtime,
+            duplicate_filter: self.duplicate_filter.and_then(|options| {
+                check_duplicate_filter(options)
+                    .map_err(|e| {
+                        log(LogLine::warning(format!(
+                            "Warning: {}, disabling the duplicate filter",
+                            e
+                        )))
+                    })
+                    .ok()
+            }),
+            retry: self.retry.map_or(default.retry, |retry| {
+                if retry.max_attempts > 0 {
+                    return retry;
+                }
+                let field = "retry.max_attempts";
+                let e = ConfigError::Zero { field };
+                log(LogLine::warning(format!("Warning: {}, making one", e)));
+                RetryPolicy {
+                    max_attempts: 1,
+                    ..retry
+                }
+            }),
+            batch_deadline: self.batch_deadline.and_then(|deadline| {
+                check_batch_deadline(deadline)
+                    .map_err(|e| {
+                        log(LogLine::warning(format!(
+                            "Warning: {}, disabling the batch deadline",
+                            e
+                        )))
+                    })
+                    .ok()
+            }),
+            #[cfg(feature = "fault-injection")]
+            faults: self.faults,
+        }
+    }
+}
+
+/// Cap a setting at `max` with a warning, or warn and return `None` for zero
+fn lenient_bounds(field: &'static str, value: usize, max: usize) -> Option<usize> {
+    match check_bounds(field, value, max) {
+        Ok(value) => Some(value),
+        Err(e @ ConfigError::TooLarge { .. }) => {
+            log(LogLine::warning(format!(
+                "Warning: {}, using the maximum",
+                e
+            )));
+            Some(max)
+        }
+        Err(e) => {
+            log(LogLine::warning(format!(
+                "Warning: {}, using the default",
+                e
+            )));
+            None
         }
     }
 }
@@ -91,9 +560,10 @@ impl BatchConfig {
 /// Asynchronous batch processing strategy
 ///
 /// Implements the ProcessingStrategy trait using multi-threaded, asynchronous
-/// batch processing. Transactions are read in batches and processed sequentially
-/// (batch-by-batch) to maintain ordering guarantees. Within each batch, transactions
-/// are partitioned by client ID and processed in parallel across multiple threads.
+/// batch processing. Transactions are read in batches and partitioned by client
+/// ID; partitions for different clients are processed in parallel across multiple
+/// threads, and consecutive batches overlap as long as each client's partitions
+/// run in order.
 ///
 /// # Thread Safety
 ///
@@ -105,10 +575,24 @@ impl BatchConfig {
 /// The strategy accepts a BatchConfig with:
 /// - `batch_size`: Number of transactions per batch (default: 1000)
 /// - `max_concurrent_batches`: Number of worker threads (default: CPU cores)
+/// - `max_inflight_clients`: Client partitions in flight per batch (default: unlimited)
+/// - `runtime`: Runtime flavor, thread pinning and thread names (default: multi-thread)
+/// - `retry`: Retries of transactions failing with a transient error (default: none)
+/// - `batch_deadline`: Time a batch may take before it is reported (default: none)
+/// - `faults`: Faults injected for testing, with the `fault-injection` feature
+///   (default: none)
 #[derive(Debug, Clone)]
 pub struct AsyncProcessingStrategy {
     /// Batch processing configuration
     config: BatchConfig,
+    /// Options for reading the input file
+    input: InputOptions,
+    /// Configuration for the transaction engine
+    engine_config: EngineConfig,
+    /// State file to write the final engine snapshot to
+    save_state: Option<PathBuf>,
+    /// Token that stops the run early once cancelled
+    cancel: Option<CancellationToken>,
 }
 
 impl AsyncProcessingStrategy {
@@ -122,101 +606,416 @@ impl AsyncProcessingStrategy {
     ///
     /// A new `AsyncProcessingStrategy` configured for batch processing
     pub fn new(config: BatchConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            input: InputOptions::default(),
+            engine_config: EngineConfig::default(),
+            save_state: None,
+            cancel: None,
+        }
+    }
+
+    /// Set the input options, or just the format of the input file
+    pub fn with_input(mut self, input: impl Into<InputOptions>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Set the transaction engine configuration
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Write the final engine snapshot to a state file (`--save-state`)
+    pub fn with_save_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_state = Some(path.into());
+        self
+    }
+
+    /// Stop the run early, keeping the accounts processed so far, once
+    /// `cancel` is cancelled
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+}
+
+/// Count processed records, transaction errors and retries from a set of
+/// batch results, logging the transaction errors like the sync strategy
+fn record_results(summary: &mut RunSummary, results: &[ProcessingResult], input: &InputOptions) {
+    summary.records_read += results.len() as u64;
+    for result in results {
+        let error = result.result.as_ref().err();
+        summary.record_attempts(result.attempts, error);
+        if let Some(error) = error {
+            let record = &result.record;
+            input.log(transaction_error(
+                error,
+                record.tx,
+                record.client,
+                record.source.as_deref(),
+                record.line,
+            ));
+            summary.record_transaction_error(error);
+        }
+    }
+}
+
+/// Journal and balance history files, written as batches complete, the
+/// trial balance, balance changes and analytics derived from the same
+/// postings, and the dead letters of the records rejected in those batches
+struct BatchOutputs {
+    journal: Option<JournalWriter<File>>,
+    trial_balance: Option<(TrialBalanceWriter<File>, TrialBalance)>,
+    changes: Option<(Box<dyn ChangeSink>, BalanceChanges)>,
+    history: Option<BalanceHistoryWriter<File>>,
+    analytics: Option<Analytics>,
+    dead_letters: Option<Box<dyn DeadLetterSink>>,
+}
+
+impl BatchOutputs {
+    /// Create the files configured by the input options
+    fn open(input: &InputOptions) -> Result<Self, String> {
+        Ok(Self {
+            journal: input
+                .journal
+                .as_deref()
+                .map(JournalWriter::create)
+                .transpose()?,
+            trial_balance: match input.trial_balance.as_deref() {
+                Some(path) => Some((TrialBalanceWriter::create(path)?, TrialBalance::new())),
+                None => None,
+            },
+            changes: match input.changes.as_deref() {
+                Some(target) => Some((create_change_sink(target)?, BalanceChanges::new())),
+                None => None,
+            },
+            history: input
+                .balance_history
+                .as_ref()
+                .map(|history| BalanceHistoryWriter::create(&history.path))
+                .transpose()?,
+            analytics: input.analytics.map(Analytics::new),
+            dead_letters: input
+                .dead_letter
+                .as_ref()
+                .map(create_dead_letter_sink)
+                .transpose()?,
+        })
+    }
+
+    /// Send the records rejected in a set of batch results to the dead-letter
+    /// sink, if any
+    fn dead_letter(&mut self, results: &[ProcessingResult]) -> Result<(), String> {
+        let Some(dead_letters) = &mut self.dead_letters else {
+            return Ok(());
+        };
+        for result in results {
+            if let Err(error) = &result.result {
+                dead_letters.send(
+                    DeadLetter::new(result.record.clone(), error).with_attempts(result.attempts),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the postings and balance samples of the batches completed so far
+    fn write(&mut self, engine: &AsyncTransactionEngine) -> Result<(), String> {
+        for posting in engine.take_postings() {
+            if let Some(journal) = &mut self.journal {
+                journal.write(&posting)?;
+            }
+            if let Some((_, trial_balance)) = &mut self.trial_balance {
+                trial_balance.record(&posting);
+            }
+            if let Some((sink, changes)) = &mut self.changes {
+                for change in changes.record(&posting) {
+                    sink.send(change)?;
+                }
+            }
+            if let Some(analytics) = &mut self.analytics {
+                analytics.record(&posting);
+            }
+        }
+        if let Some(history) = &mut self.history {
+            for point in engine.take_balance_history() {
+                history.write(&point)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the trial balance of the postings written so far, if enabled
+    fn write_trial_balance(&mut self) -> Result<(), String> {
+        match &mut self.trial_balance {
+            Some((writer, trial_balance)) => writer.write(trial_balance),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush both files, the balance changes and the dead letters
+    fn flush(&mut self) -> Result<(), String> {
+        if let Some(journal) = &mut self.journal {
+            journal.flush()?;
+        }
+        if let Some((sink, _)) = &mut self.changes {
+            sink.flush()?;
+        }
+        if let Some(history) = &mut self.history {
+            history.flush()?;
+        }
+        if let Some(dead_letters) = &mut self.dead_letters {
+            dead_letters.flush()?;
+        }
+        Ok(())
     }
 }
 
 impl ProcessingStrategy for AsyncProcessingStrategy {
-    /// Process transactions from input file and write results to output
+    /// Process transactions from input files and write results to output
     ///
     /// This method implements the complete asynchronous batch processing pipeline:
     /// 1. Creates thread-safe engine components (AsyncTransactionEngine, etc.)
     /// 2. Creates a BatchProcessor for client-based partitioning
     /// 3. Creates a tokio multi-threaded runtime
-    /// 4. Reads transactions in batches from CSV using AsyncReader
-    /// 5. Processes each batch sequentially (waits for completion before next batch)
+    /// 4. Reads transactions in batches from each input file in turn, using
+    ///    AsyncReader for CSV
+    /// 5. Submits each batch to a BatchPipeline, which starts a client's partition
+    ///    as soon as that client's partitions from earlier batches have completed
     /// 6. Within each batch, processes different clients in parallel
-    /// 7. Collects final account states
-    /// 8. Writes account states to output using csv_format module
+    /// 7. Counts records read and records that failed (parse or processing errors)
+    /// 8. Collects final account states
+    /// 9. Shuts down the runtime and writes account states to the output sink
     ///
     /// # Arguments
     ///
-    /// * `input_path` - Path to the input CSV file
-    /// * `output` - Mutable reference to a writer for outputting account states
+    /// * `input_paths` - Paths to the input files, processed in order
+    /// * `output` - Sink for the account states
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if processing completed successfully
-    /// * `Err(String)` if a fatal error occurred
+    /// * `Ok(RunSummary)` if processing completed successfully
+    /// * `Err(EngineError)` if a fatal error occurred
     ///
     /// # Error Handling
     ///
     /// Fatal errors (file not found, I/O errors, runtime errors) are returned immediately.
-    /// Individual transaction errors are logged to stderr and processing continues.
-    fn process(&self, input_path: &Path, output: &mut dyn Write) -> Result<(), String> {
-        // Create tokio runtime for async execution
-        // Use multi-threaded runtime with configured number of worker threads
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(self.config.max_concurrent_batches)
-            .build()
-            .map_err(|e| format!("Failed to create tokio runtime: {}", e))?;
+    /// Individual transaction errors are counted in the summary and processing continues.
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        check_inputs(input_paths)?;
+        if self.engine_config.dispute_expiry.is_some() {
+            return Err(EngineError::Other(
+                "Dispute expiry requires sequential processing (--strategy sync)".to_string(),
+            ));
+        }
+        if self.engine_config.duplicate_tx_policy == DuplicateTxPolicy::PerClient {
+            return Err(EngineError::Other(
+                "Per-client transaction IDs require sequential processing (--strategy sync)"
+                    .to_string(),
+            ));
+        }
+        if self.input.cutoffs.is_some() {
+            return Err(EngineError::Other(
+                "Cutoff snapshots require sequential processing (--strategy sync)".to_string(),
+            ));
+        }
+        if !self.input.standing_orders.is_empty() {
+            return Err(EngineError::Other(
+                "Standing orders require sequential processing (--strategy sync)".to_string(),
+            ));
+        }
+
+        // Create tokio runtime for async execution, by default multi-threaded
+        // with the configured number of worker threads
+        let runtime = self
+            .config
+            .runtime
+            .build(self.config.max_concurrent_batches, input_paths)?;
 
         // Execute async processing within the runtime
-        runtime.block_on(async {
+        let (mut summary, accounts) = runtime.block_on(async {
             // Create thread-safe engine components
-            let account_manager = Arc::new(AsyncAccountManager::new());
-            let transaction_store = Arc::new(AsyncTransactionStore::new());
-            let engine = Arc::new(AsyncTransactionEngine::new(
-                Arc::clone(&account_manager),
-                Arc::clone(&transaction_store),
-            ));
+            let clients = self.engine_config.expected_clients.unwrap_or(0);
+            let transactions = self.engine_config.expected_transactions.unwrap_or(0);
+            let account_manager = Arc::new(
+                AsyncAccountManager::with_capacity(clients)
+                    .with_metadata(self.engine_config.account_metadata.clone()),
+            );
+            let mut transaction_store = AsyncTransactionStore::with_capacity(transactions, clients);
+            if let Some(filter) = &self.config.duplicate_filter {
+                transaction_store =
+                    transaction_store.with_duplicate_filter(filter.build(input_paths));
+            }
+            let transaction_store = Arc::new(transaction_store);
+            let engine = Arc::new(
+                AsyncTransactionEngine::new(
+                    Arc::clone(&account_manager),
+                    Arc::clone(&transaction_store),
+                )
+                .with_config(self.input.engine_config(&self.engine_config)),
+            );
+
+            // Create batch processor and the pipeline that overlaps batches. The
+            // processor stops when the run is cancelled, or when a batch deadline
+            // aborts it, which leaves the caller's token alone
+            let stop = self
+                .cancel
+                .as_ref()
+                .map_or_else(CancellationToken::new, CancellationToken::child_token);
+            let processor = BatchProcessor::new(Arc::clone(&engine))
+                .with_max_inflight_clients(self.config.max_inflight_clients)
+                .with_retry(self.config.retry)
+                .with_cancellation(stop.clone());
+            #[cfg(feature = "fault-injection")]
+            let processor = processor.with_faults(self.config.faults);
+            let mut pipeline = BatchPipeline::new(processor, self.config.max_concurrent_batches);
+            if let Some(deadline) = self.config.batch_deadline {
+                pipeline = pipeline.with_deadline(deadline);
+            }
+
+            let mut summary = RunSummary::default();
+            let mut dedup = DedupFilter::new(self.input.dedup_window);
+            let mut quarantine = Quarantine::open(self.input.quarantine.as_ref())?;
+            let mut sanity = SanityWarnings::open(self.input.sanity.as_ref())?;
+            let mut outputs = BatchOutputs::open(&self.input)?;
 
-            // Create batch processor
-            let processor = BatchProcessor::new(Arc::clone(&engine));
+            for input_path in input_paths {
+                // Open the input in the configured format
+                let mut reader = open_batches(
+                    input_path,
+                    &self.input,
+                    input_source(input_paths, input_path),
+                )
+                .await?;
 
-            // Open the CSV file
-            let file = tokio::fs::File::open(input_path)
-                .await
-                .map_err(|e| format!("Failed to open file '{}': {}", input_path.display(), e))?;
+                // Submit batches to the pipeline; per-client ordering is preserved across
+                // batches (and files) while clients without pending work start immediately
+                loop {
+                    if stop.is_cancelled() {
+                        break;
+                    }
 
-            // Wrap tokio file in a compatibility layer for csv-async
-            let compat_file = tokio_util::compat::TokioAsyncReadCompatExt::compat(file);
+                    // Read a batch of records
+                    let (mut batch, failures) = reader.read_batch(self.config.batch_size).await;
 
-            // Create async CSV reader
-            let mut reader = AsyncReader::new(compat_file);
+                    // If batch is empty, we've reached end of file
+                    if batch.is_empty() && failures.is_empty() {
+                        break;
+                    }
 
-            // Process batches sequentially to maintain per-client ordering across entire file
-            // Each batch is still processed in parallel across different clients
-            loop {
-                // Read a batch of records using AsyncReader
-                let batch = reader.read_batch(self.config.batch_size).await;
+                    // Records that failed to parse never reach the pipeline;
+                    // they are reported like the sync strategy does
+                    for e in &failures {
+                        self.input.log(
+                            LogLine::error(format!("{} parsing error: {}", self.input.format, e))
+                                .with_class("ParseError"),
+                        );
+                    }
+                    summary.parse_errors += failures.len() as u64;
+                    summary.records_read += failures.len() as u64;
 
-                // If batch is empty, we've reached end of file
-                if batch.is_empty() {
+                    for record in &batch {
+                        summary.record_parsed(record);
+                    }
+
+                    // Drop records of clients that were not selected
+                    let filtered = match &self.input.clients {
+                        Some(clients) => {
+                            let before = batch.len();
+                            batch.retain(|record| clients.contains(record.client));
+                            (before - batch.len()) as u64
+                        }
+                        None => 0,
+                    };
+                    summary.filtered += filtered;
+                    summary.records_read += filtered;
+
+                    // Drop records identical to a recent record, and divert suspicious
+                    // ones to the quarantine file, before they reach the engine
+                    let duplicates = dedup.retain_unique(&mut batch);
+                    let quarantined = quarantine.retain_clean(&mut batch)?;
+                    summary.duplicates += duplicates;
+                    summary.quarantined += quarantined;
+                    summary.records_read += duplicates + quarantined;
+                    if batch.is_empty() {
+                        continue;
+                    }
+                    summary.warnings += sanity.check_batch(&batch)?;
+
+                    // Returns results of batches that completed to make room for this one
+                    let results = pipeline.submit(batch).await;
+                    record_results(&mut summary, &results, &self.input);
+                    outputs.dead_letter(&results)?;
+                    outputs.write(&engine)?;
+                }
+
+                if stop.is_cancelled() {
                     break;
                 }
+            }
 
-                // Process batch and wait for completion before reading next batch
-                // This ensures that if a client's transactions span multiple batches,
-                // they are processed in the correct order
-                let _results = processor.process_batch(batch).await;
+            // Wait for the batches still in flight
+            let results = pipeline.finish().await;
+            record_results(&mut summary, &results, &self.input);
+            summary.cancelled = self
+                .cancel
+                .as_ref()
+                .is_some_and(CancellationToken::is_cancelled);
+            if stop.is_cancelled() && !summary.cancelled {
+                let timeout = self.config.batch_deadline.map(|d| d.timeout);
+                return Err(format!(
+                    "Aborted after a batch missed its deadline of {} ms",
+                    timeout.unwrap_or_default().as_millis()
+                ));
             }
+            outputs.dead_letter(&results)?;
+            outputs.write(&engine)?;
+            outputs.flush()?;
+            outputs.write_trial_balance()?;
+            summary.analytics = outputs.analytics.as_ref().map(Analytics::report);
+            quarantine.finish()?;
+            sanity.finish()?;
 
             // Get final account states
             let accounts = account_manager.get_all_accounts();
+            summary.conservation = Some(Conservation::check(engine.flows(), &accounts));
+            if let Some(path) = &self.save_state {
+                save_state(path, &engine.snapshot())?;
+            }
 
-            // Write account states to output using csv_format module
-            write_accounts_csv(&accounts, output)?;
+            Ok::<_, String>((summary, accounts))
+        })?;
 
-            Ok(())
-        })
+        // Write outside the runtime, since sinks may run their own async I/O
+        drop(runtime);
+        summary.accounts = AccountTotals::of(&accounts);
+        output.write_accounts(&accounts)?;
+
+        Ok(summary)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::r#async::DeadlinePolicy;
+    use crate::core::MoneyFlows;
+    use crate::io::DeadLetterOptions;
+    use crate::strategy::{
+        BalanceHistoryOptions, CutoffOptions, QuarantineOptions, QuarantineRule, RetryCounts,
+        SanityCheck, SanityOptions, StandingOrder, TransactionTypeCounts,
+    };
+    use crate::types::TransactionType;
+    use rust_decimal::Decimal;
     use std::io::Write;
+    use std::path::Path;
+    use std::time::Duration;
     use tempfile::NamedTempFile;
 
     /// Helper function to create a temporary CSV file for testing
@@ -228,6 +1027,15 @@ mod tests {
         file
     }
 
+    /// Build a valid BatchConfig
+    fn batch_config(batch_size: usize, max_concurrent_batches: usize) -> BatchConfig {
+        BatchConfig::builder()
+            .batch_size(batch_size)
+            .max_concurrent_batches(max_concurrent_batches)
+            .build()
+            .unwrap()
+    }
+
     #[test]
     fn test_async_strategy_processes_valid_deposit() {
         let csv_content = "type,client,tx,amount\ndeposit,1,1,100.0\n";
@@ -267,6 +1075,58 @@ mod tests {
         assert!(output_str.contains("2"));
     }
 
+    #[test]
+    fn test_async_strategy_cancellation() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+        let cancel = CancellationToken::new();
+        let strategy =
+            AsyncProcessingStrategy::new(BatchConfig::default()).with_cancellation(cancel.clone());
+
+        let mut output = Vec::new();
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert!(!summary.cancelled);
+        assert_eq!(summary.records_read, 1);
+
+        // A cancelled run still completes, with the accounts processed so far
+        cancel.cancel();
+        let mut output = Vec::new();
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert!(summary.cancelled);
+        assert_eq!(summary.records_read, 0);
+        assert!(summary.to_string().contains(", cancelled"));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n"
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_aborts_on_batch_deadline() {
+        let mut csv = String::from("type,client,tx,amount\n");
+        for tx in 0..50_000 {
+            csv.push_str(&format!("deposit,1,{},1.0\n", tx));
+        }
+        let file = create_temp_csv(&csv);
+
+        // One partition far too large for the deadline
+        let config = BatchConfig::builder()
+            .batch_size(50_000)
+            .max_concurrent_batches(2)
+            .batch_deadline(
+                BatchDeadline::new(Duration::from_millis(1)).with_policy(DeadlinePolicy::Abort),
+            )
+            .build()
+            .unwrap();
+        let strategy = AsyncProcessingStrategy::new(config);
+        let mut output = Vec::new();
+
+        let err = strategy.process(file.path(), &mut output).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Aborted after a batch missed its deadline of 1 ms"
+        );
+    }
+
     #[test]
     fn test_async_strategy_handles_missing_file() {
         let config = BatchConfig::default();
@@ -275,7 +1135,25 @@ mod tests {
 
         let result = strategy.process(Path::new("nonexistent.csv"), &mut output);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Failed to open file"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to open file"));
+    }
+
+    #[test]
+    fn test_async_strategy_rejects_invalid_header() {
+        let file = create_temp_csv("type,client_id,tx,amount\ndeposit,1,1,100.0\n");
+
+        let strategy = AsyncProcessingStrategy::new(BatchConfig::default());
+        let mut output = Vec::new();
+
+        let err = strategy
+            .process(file.path(), &mut output)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("did you mean 'client'?"), "{err}");
+        assert!(output.is_empty());
     }
 
     #[test]
@@ -291,7 +1169,7 @@ mod tests {
         let file = create_temp_csv(csv_content);
 
         // Use a small batch size to force multiple batches
-        let config = BatchConfig::new(2, num_cpus::get());
+        let config = batch_config(2, num_cpus::get());
         let strategy = AsyncProcessingStrategy::new(config);
         let mut output = Vec::new();
 
@@ -304,10 +1182,771 @@ mod tests {
 
         // Find client 1's balance (should be 100 - 30 - 20 = 50)
         let client1_line = lines.iter().find(|line| line.starts_with("1,")).unwrap();
-        assert!(client1_line.contains("50.0000"), "Client 1 should have 50.0000, got: {}", client1_line);
+        assert!(
+            client1_line.contains("50.0000"),
+            "Client 1 should have 50.0000, got: {}",
+            client1_line
+        );
 
         // Find client 2's balance (should be 50 + 25 = 75)
         let client2_line = lines.iter().find(|line| line.starts_with("2,")).unwrap();
-        assert!(client2_line.contains("75.0000"), "Client 2 should have 75.0000, got: {}", client2_line);
+        assert!(
+            client2_line.contains("75.0000"),
+            "Client 2 should have 75.0000, got: {}",
+            client2_line
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_summarizes_errors() {
+        // One malformed record and one rejected withdrawal out of four,
+        // spread over several batches
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,100.0\n\
+                          deposit,2,2,invalid\n\
+                          withdrawal,1,3,500.0\n\
+                          deposit,3,4,50.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let config = batch_config(2, num_cpus::get());
+        let strategy = AsyncProcessingStrategy::new(config);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                records_read: 4,
+                parse_errors: 1,
+                transaction_errors: 1,
+                limit_rejections: 0,
+                duplicates: 0,
+                quarantined: 0,
+                warnings: 0,
+                filtered: 0,
+                expired_disputes: 0,
+                transaction_types: TransactionTypeCounts {
+                    deposit: 2,
+                    withdrawal: 1,
+                    ..TransactionTypeCounts::default()
+                },
+                transaction_error_kinds: [("InsufficientFunds", 1)].into(),
+                retry: RetryCounts::default(),
+                accounts: AccountTotals {
+                    accounts: 2,
+                    locked: 0,
+                    available: Decimal::from(150),
+                    held: Decimal::ZERO,
+                    total: Decimal::from(150),
+                },
+                conservation: Some(Conservation {
+                    flows: MoneyFlows {
+                        deposited: Decimal::from(150),
+                        ..MoneyFlows::default()
+                    },
+                    expected_total: Decimal::from(150),
+                    actual_total: Decimal::from(150),
+                    ..Conservation::default()
+                }),
+                cancelled: false,
+                analytics: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_reads_past_failed_batch() {
+        // The first batch holds only records that fail to parse, which must
+        // not end reading early
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,x,1,1.0\n\
+                          deposit,1,2,invalid\n\
+                          deposit,1,3,1.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2));
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.parse_errors, 2);
+        assert!(String::from_utf8(output).unwrap().contains("1,1.0000"));
+    }
+
+    #[rstest::rstest]
+    #[case::default(false, 0, "1,3.0000")]
+    #[case::legacy(true, 2, "1,1.0000")]
+    fn test_async_strategy_legacy_tx_ids(
+        #[case] legacy_tx_ids: bool,
+        #[case] parse_errors: u64,
+        #[case] expected_line: &str,
+    ) {
+        // The second batch is rejected entirely in legacy mode, which must not
+        // end reading early
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,4294967296,1.0\n\
+                          deposit,1,4294967297,1.0\n\
+                          deposit,1,1,1.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let input = InputOptions::default().with_legacy_tx_ids(legacy_tx_ids);
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.parse_errors, parse_errors);
+        assert!(String::from_utf8(output).unwrap().contains(expected_line));
+    }
+
+    #[test]
+    fn test_async_strategy_dedup_window() {
+        // The second batch holds only duplicates, which must not end reading early
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,1.0\n\
+                          deposit,2,2,1.0\n\
+                          deposit,1,1,1.0\n\
+                          deposit,2,2,1.0\n\
+                          deposit,1,3,2.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let input = InputOptions::default().with_dedup_window(10);
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 5);
+        assert_eq!(summary.duplicates, 2);
+        assert_eq!(summary.transaction_errors, 0);
+        assert!(String::from_utf8(output).unwrap().contains("1,3.0000"));
+    }
+
+    #[test]
+    fn test_async_strategy_filters_clients() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,1.0\n\
+                          deposit,2,2,1.0\n\
+                          deposit,3,3,1.0\n\
+                          deposit,2,4,2.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let input = InputOptions::default().with_clients("2".parse().unwrap());
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 4);
+        assert_eq!(summary.filtered, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n2,3.0000,0.0000,3.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_quarantines_large_amounts() {
+        // The first batch is quarantined entirely, which must not end reading early
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,5000.0\n\
+                          deposit,2,2,2000.0\n\
+                          deposit,1,3,100.0\n";
+        let file = create_temp_csv(csv_content);
+        let quarantine_file = NamedTempFile::new().unwrap();
+
+        let input = InputOptions::default().with_quarantine(
+            QuarantineOptions::new(quarantine_file.path())
+                .with_rule(QuarantineRule::AmountAbove(1000.into())),
+        );
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.quarantined, 2);
+        assert!(String::from_utf8(output).unwrap().contains("1,100.0000"));
+        let quarantined = std::fs::read_to_string(quarantine_file.path()).unwrap();
+        assert_eq!(quarantined.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_async_strategy_dead_letters_rejected_records() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.0\n\
+                          withdrawal,1,2,50.0\n\
+                          dispute,2,9,\n\
+                          deposit,2,3,5.0\n";
+        let file = create_temp_csv(csv_content);
+        let dead_letter_file = NamedTempFile::new().unwrap();
+
+        let input = InputOptions::default().with_dead_letter(DeadLetterOptions::new(
+            dead_letter_file.path().to_str().unwrap(),
+        ));
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.transaction_errors, 2);
+        let dead_letters = std::fs::read_to_string(dead_letter_file.path()).unwrap();
+        let mut lines: Vec<&str> = dead_letters.lines().skip(1).collect();
+        lines.sort();
+        assert_eq!(lines.len(), 2, "{}", dead_letters);
+        assert!(lines[0].starts_with("dispute,2,9,,"), "{}", lines[0]);
+        assert!(
+            lines[1].starts_with("withdrawal,1,2,50.0,301,"),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_dead_letters_name_their_source() {
+        let first = create_temp_csv("type,client,tx,amount\ndeposit,1,1,10.0\n");
+        let second =
+            create_temp_csv("type,client,tx,amount\ndeposit,2,2,5.0\nwithdrawal,1,3,50.0\n");
+        let dead_letter_file = NamedTempFile::new().unwrap();
+
+        let input = InputOptions::default().with_dead_letter(DeadLetterOptions::new(
+            dead_letter_file.path().to_str().unwrap(),
+        ));
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let inputs = [first.path().to_path_buf(), second.path().to_path_buf()];
+        strategy.process_files(&inputs, &mut Vec::new()).unwrap();
+
+        let dead_letters = std::fs::read_to_string(dead_letter_file.path()).unwrap();
+        let line = dead_letters.lines().nth(1).unwrap();
+        assert!(line.starts_with("withdrawal,1,3,50.0,301,"), "{}", line);
+        assert!(
+            line.ends_with(&format!(",{},3", second.path().display())),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_writes_warnings() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.00001\n\
+                          deposit,2,2,5.0\n\
+                          deposit,1,3,1.12345\n";
+        let file = create_temp_csv(csv_content);
+        let warnings_file = NamedTempFile::new().unwrap();
+
+        let input = InputOptions::default().with_sanity(
+            SanityOptions::new(warnings_file.path()).with_check(SanityCheck::ExcessPrecision),
+        );
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.warnings, 2);
+        assert_eq!(summary.transaction_errors, 0);
+        let warnings = std::fs::read_to_string(warnings_file.path()).unwrap();
+        let lines: Vec<&str> = warnings.lines().skip(1).collect();
+        assert_eq!(lines.len(), 2, "{}", warnings);
+        assert!(
+            lines[0].starts_with("deposit,1,1,10.00001,"),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[1].ends_with(",,4"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_async_strategy_writes_trial_balance() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.0\n\
+                          deposit,2,2,20.0\n\
+                          withdrawal,1,3,4.0\n";
+        let file = create_temp_csv(csv_content);
+        let trial_balance = NamedTempFile::new().unwrap();
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2))
+            .with_input(InputOptions::default().with_trial_balance(trial_balance.path()));
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(trial_balance.path()).unwrap(),
+            "account,section,debit,credit,balance\n\
+             settlement,system,30.0000,4.0000,-26.0000\n\
+             client:1:available,client,4.0000,10.0000,6.0000\n\
+             client:2:available,client,0.0000,20.0000,20.0000\n\
+             total,,34.0000,34.0000,0.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_writes_journal() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.0\n\
+                          deposit,2,2,20.0\n\
+                          withdrawal,1,3,4.0\n";
+        let file = create_temp_csv(csv_content);
+        let journal = NamedTempFile::new().unwrap();
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2))
+            .with_input(InputOptions::default().with_journal(journal.path()));
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        // Clients are processed concurrently, so only the set of lines is fixed
+        let journal = std::fs::read_to_string(journal.path()).unwrap();
+        let mut lines: Vec<&str> = journal.lines().collect();
+        assert_eq!(
+            lines.remove(0),
+            "tx,type,account,contra_account,debit,credit"
+        );
+        lines.sort_unstable();
+        assert_eq!(
+            lines,
+            [
+                "1,deposit,client:1:available,settlement,,10.0000",
+                "1,deposit,settlement,client:1:available,10.0000,",
+                "2,deposit,client:2:available,settlement,,20.0000",
+                "2,deposit,settlement,client:2:available,20.0000,",
+                "3,withdrawal,client:1:available,settlement,4.0000,",
+                "3,withdrawal,settlement,client:1:available,,4.0000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_writes_balance_history() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.0\n\
+                          deposit,2,2,20.0\n\
+                          withdrawal,1,3,4.0\n\
+                          deposit,2,4,1.0\n";
+        let file = create_temp_csv(csv_content);
+        let history = NamedTempFile::new().unwrap();
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(1, 2)).with_input(
+            InputOptions::default()
+                .with_balance_history(BalanceHistoryOptions::new(history.path(), 2)),
+        );
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        let history = std::fs::read_to_string(history.path()).unwrap();
+        let mut lines: Vec<&str> = history.lines().collect();
+        lines.sort_unstable();
+        assert_eq!(
+            lines,
+            [
+                "1,2,6.0000,0.0000,6.0000,false",
+                "2,2,21.0000,0.0000,21.0000,false",
+                "client,transactions,available,held,total,locked",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_collects_analytics() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.0\n\
+                          deposit,2,2,20.0\n\
+                          dispute,2,2,\n";
+        let file = create_temp_csv(csv_content);
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(1, 2))
+            .with_input(InputOptions::default().with_analytics(5));
+        let mut output = Vec::new();
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+
+        let analytics = summary.analytics.unwrap();
+        assert_eq!(analytics.clients, 2);
+        let ranked: Vec<_> = analytics.top_by_volume.iter().map(|s| s.client).collect();
+        assert_eq!(ranked, vec![2, 1]);
+        assert_eq!(analytics.dispute_rate, Decimal::new(5, 1));
+        assert_eq!(analytics.amounts[2].count, 2);
+    }
+
+    #[test]
+    fn test_async_strategy_rejects_dispute_expiry() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2))
+            .with_engine_config(EngineConfig::new().with_dispute_expiry(10));
+        let mut output = Vec::new();
+
+        let result = strategy.process(file.path(), &mut output);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Dispute expiry requires sequential processing"));
+    }
+
+    #[test]
+    fn test_async_strategy_rejects_per_client_tx_ids() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2)).with_engine_config(
+            EngineConfig::new().with_duplicate_tx_policy(DuplicateTxPolicy::PerClient),
+        );
+        let mut output = Vec::new();
+
+        let result = strategy.process(file.path(), &mut output);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Per-client transaction IDs require sequential processing"));
+    }
+
+    #[test]
+    fn test_async_strategy_rejects_cutoffs() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2))
+            .with_input(InputOptions::default().with_cutoffs(CutoffOptions::new(dir.path(), 1)));
+        let mut output = Vec::new();
+
+        let result = strategy.process(file.path(), &mut output);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Cutoff snapshots require sequential processing"));
+    }
+
+    #[test]
+    fn test_async_strategy_rejects_standing_orders() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+        let order = StandingOrder::new(1, TransactionType::Withdrawal, Decimal::ONE, 1);
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(2, 2))
+            .with_input(InputOptions::default().with_standing_orders(vec![order]));
+        let mut output = Vec::new();
+
+        let result = strategy.process(file.path(), &mut output);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Standing orders require sequential processing"));
+    }
+
+    #[test]
+    fn test_async_strategy_processes_files_in_order() {
+        // The withdrawal in the second file needs the deposit from the first
+        let file1 = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\ndeposit,2,2,1.0\n");
+        let file2 = create_temp_csv("type,client,tx,amount\nwithdrawal,1,3,60.0\nbogus,1,4,1.0\n");
+
+        let strategy = AsyncProcessingStrategy::new(batch_config(1, 2));
+        let mut output = Vec::new();
+
+        let summary = strategy
+            .process_files(
+                &[file1.path().to_path_buf(), file2.path().to_path_buf()],
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(summary.records_read, 4);
+        assert_eq!(summary.parse_errors, 1);
+        assert_eq!(summary.transaction_errors, 0);
+        assert!(String::from_utf8(output).unwrap().contains("1,40.0000"));
+    }
+
+    #[test]
+    fn test_async_strategy_with_max_inflight_clients() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,100.0\n\
+                          deposit,1,2,100.0\n\
+                          deposit,1,3,100.0\n\
+                          deposit,2,4,50.0\n\
+                          withdrawal,1,5,20.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let config = BatchConfig::builder()
+            .batch_size(10)
+            .max_concurrent_batches(2)
+            .max_inflight_clients(1)
+            .build()
+            .unwrap();
+        let strategy = AsyncProcessingStrategy::new(config);
+        let mut output = Vec::new();
+
+        strategy.process(file.path(), &mut output).unwrap();
+
+        let output_str = String::from_utf8(output).unwrap();
+        let client1_line = output_str
+            .lines()
+            .find(|line| line.starts_with("1,"))
+            .unwrap();
+        assert!(client1_line.contains("280.0000"), "got: {}", client1_line);
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn test_async_strategy_with_injected_faults() {
+        use crate::strategy::SyncProcessingStrategy;
+        use std::fmt::Write as _;
+        use std::time::Duration;
+
+        // Deposits, withdrawals and disputes of a few clients, interleaved
+        // so each client's results depend on the order of its transactions
+        let mut csv_content = String::from("type,client,tx,amount\n");
+        for tx in 1..=600u64 {
+            let client = tx % 7 + 1;
+            let _ = match tx % 5 {
+                0 => writeln!(csv_content, "withdrawal,{},{},3.0", client, tx),
+                3 => writeln!(csv_content, "dispute,{},{},", client, tx - 2),
+                4 => writeln!(csv_content, "resolve,{},{},", client, tx - 3),
+                _ => writeln!(csv_content, "deposit,{},{},{}.5", client, tx, tx % 4),
+            };
+        }
+        let file = create_temp_csv(&csv_content);
+
+        let config = BatchConfig::builder()
+            .batch_size(50)
+            .max_concurrent_batches(4)
+            .retry(RetryPolicy::new(64, Duration::ZERO))
+            .faults(
+                FaultInjection::new(11)
+                    .with_errors(0.3)
+                    .with_delays(0.2, Duration::from_millis(1)),
+            )
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+        let summary = AsyncProcessingStrategy::new(config)
+            .process(file.path(), &mut output)
+            .unwrap();
+        let mut expected_output = Vec::new();
+        let expected = SyncProcessingStrategy::new()
+            .process(file.path(), &mut expected_output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(expected_output).unwrap()
+        );
+        assert!(summary.retry.retried > 0);
+        assert_eq!(summary.retry.exhausted, 0);
+        assert_eq!(summary.transaction_errors, expected.transaction_errors);
+    }
+
+    #[rstest::rstest]
+    #[case::multi_thread(RuntimeFlavor::MultiThread)]
+    #[case::current_thread(RuntimeFlavor::CurrentThread)]
+    #[case::auto(RuntimeFlavor::Auto)]
+    fn test_async_strategy_runtime_flavors(#[case] flavor: RuntimeFlavor) {
+        let file = create_temp_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,100.0\n\
+             deposit,2,2,50.0\n\
+             withdrawal,1,3,30.0\n",
+        );
+        let config = BatchConfig::builder()
+            .batch_size(2)
+            .max_concurrent_batches(2)
+            .runtime(RuntimeOptions {
+                flavor,
+                thread_name: Some("payments-test".to_string()),
+                ..RuntimeOptions::default()
+            })
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+
+        AsyncProcessingStrategy::new(config)
+            .process(file.path(), &mut output)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("1,70.0000"), "got: {}", output);
+        assert!(output.contains("2,50.0000"), "got: {}", output);
+    }
+
+    #[test]
+    fn test_is_small_input() {
+        let small = create_temp_csv("type,client,tx,amount\ndeposit,1,1,1.0\n");
+        assert!(is_small_input(&[small.path().to_path_buf()]));
+
+        let large = NamedTempFile::new().unwrap();
+        large
+            .as_file()
+            .set_len(AUTO_CURRENT_THREAD_BYTES + 1)
+            .unwrap();
+        assert!(!is_small_input(&[
+            small.path().to_path_buf(),
+            large.path().to_path_buf()
+        ]));
+        assert!(!is_small_input(&[PathBuf::from("s3://bucket/input.csv")]));
+    }
+
+    #[rstest::rstest]
+    #[case::sized_from_input(DuplicateFilterOptions::new(0.01))]
+    #[case::undersized(DuplicateFilterOptions::new(0.5).with_capacity(1))]
+    fn test_async_strategy_duplicate_filter(#[case] options: DuplicateFilterOptions) {
+        let file = create_temp_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,100.0\n\
+             deposit,2,2,50.0\n\
+             deposit,1,1,100.0\n\
+             withdrawal,2,3,20.0\n\
+             withdrawal,1,2,10.0\n",
+        );
+        let config = BatchConfig::builder()
+            .batch_size(2)
+            .duplicate_filter(options)
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+
+        let summary = AsyncProcessingStrategy::new(config)
+            .process(file.path(), &mut output)
+            .unwrap();
+
+        // Both reused IDs are still rejected
+        assert_eq!(summary.transaction_errors, 2);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("1,100.0000"), "got: {}", output);
+        assert!(output.contains("2,30.0000"), "got: {}", output);
+    }
+
+    #[test]
+    fn test_duplicate_filter_capacity() {
+        let content = "deposit,1,1,1.0\n".repeat(100);
+        let file = create_temp_csv(&content);
+        let paths = [file.path().to_path_buf()];
+        let sized =
+            |options: DuplicateFilterOptions, paths: &[PathBuf]| options.build(paths).size_bytes();
+
+        let options = DuplicateFilterOptions::new(0.01);
+        assert_eq!(
+            sized(options, &paths),
+            sized(
+                options.with_capacity(content.len() / MIN_RECORD_BYTES as usize),
+                &paths
+            )
+        );
+        assert_eq!(
+            sized(options, &[PathBuf::from("s3://bucket/input.csv")]),
+            sized(
+                options.with_capacity(DEFAULT_DUPLICATE_FILTER_CAPACITY),
+                &[]
+            )
+        );
+    }
+
+    #[test]
+    fn test_async_strategy_pin_threads() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+        let config = BatchConfig::builder()
+            .runtime(RuntimeOptions {
+                pin_threads: true,
+                ..RuntimeOptions::default()
+            })
+            .build()
+            .unwrap();
+        let mut output = Vec::new();
+
+        let result = AsyncProcessingStrategy::new(config).process(file.path(), &mut output);
+
+        if cfg!(feature = "core-pinning") {
+            result.unwrap();
+            assert!(String::from_utf8(output).unwrap().contains("1,100.0000"));
+        } else {
+            assert!(result
+                .unwrap_err()
+                .to_string()
+                .contains("requires building with the 'core-pinning' feature"));
+        }
+    }
+
+    #[test]
+    fn test_batch_config_builder() {
+        let config = BatchConfig::builder()
+            .batch_size(500)
+            .max_concurrent_batches(4)
+            .max_inflight_clients(8)
+            .expected_clients(500)
+            .build()
+            .unwrap();
+        assert_eq!(config.batch_size, 500);
+        assert_eq!(config.max_concurrent_batches, 4);
+        assert_eq!(config.max_inflight_clients, Some(8));
+
+        let config = BatchConfig::builder().build().unwrap();
+        assert_eq!(config.batch_size, BatchConfig::default().batch_size);
+        assert_eq!(config.max_inflight_clients, None);
+        assert_eq!(config.retry, RetryPolicy::none());
+
+        let retry = RetryPolicy::new(4, std::time::Duration::from_millis(5));
+        let config = BatchConfig::builder().retry(retry).build().unwrap();
+        assert_eq!(config.retry, retry);
+    }
+
+    #[rstest::rstest]
+    #[case::zero_batch_size(
+        BatchConfig::builder().batch_size(0),
+        ConfigError::Zero { field: "batch_size" }
+    )]
+    #[case::zero_concurrent(
+        BatchConfig::builder().max_concurrent_batches(0),
+        ConfigError::Zero { field: "max_concurrent_batches" }
+    )]
+    #[case::zero_inflight(
+        BatchConfig::builder().max_inflight_clients(0),
+        ConfigError::Zero { field: "max_inflight_clients" }
+    )]
+    #[case::batch_size_too_large(
+        BatchConfig::builder().batch_size(MAX_BATCH_SIZE + 1),
+        ConfigError::TooLarge { field: "batch_size", value: MAX_BATCH_SIZE + 1, max: MAX_BATCH_SIZE }
+    )]
+    #[case::concurrent_too_large(
+        BatchConfig::builder().max_concurrent_batches(5000),
+        ConfigError::TooLarge {
+            field: "max_concurrent_batches",
+            value: 5000,
+            max: MAX_CONCURRENT_BATCHES
+        }
+    )]
+    #[case::batch_smaller_than_clients(
+        BatchConfig::builder().batch_size(100).expected_clients(101),
+        ConfigError::BatchSmallerThanClients { batch_size: 100, clients: 101 }
+    )]
+    #[case::false_positive_rate_of_one(
+        BatchConfig::builder().duplicate_filter(DuplicateFilterOptions::new(1.0)),
+        ConfigError::InvalidRate { field: "duplicate_filter.false_positive_rate", value: 1.0 }
+    )]
+    #[case::zero_filter_capacity(
+        BatchConfig::builder().duplicate_filter(DuplicateFilterOptions::new(0.01).with_capacity(0)),
+        ConfigError::Zero { field: "duplicate_filter.capacity" }
+    )]
+    #[case::zero_retry_attempts(
+        BatchConfig::builder().retry(RetryPolicy { max_attempts: 0, ..RetryPolicy::default() }),
+        ConfigError::Zero { field: "retry.max_attempts" }
+    )]
+    #[case::zero_batch_deadline(
+        BatchConfig::builder().batch_deadline(BatchDeadline::new(Duration::ZERO)),
+        ConfigError::Zero { field: "batch_deadline.timeout" }
+    )]
+    fn test_batch_config_builder_rejects(
+        #[case] builder: BatchConfigBuilder,
+        #[case] expected: ConfigError,
+    ) {
+        assert_eq!(builder.build().unwrap_err(), expected);
+    }
+
+    #[test]
+    fn test_batch_config_build_lenient() {
+        let config = BatchConfig::builder()
+            .batch_size(0)
+            .max_concurrent_batches(MAX_CONCURRENT_BATCHES + 1)
+            .max_inflight_clients(0)
+            .expected_clients(usize::MAX)
+            .duplicate_filter(DuplicateFilterOptions::new(0.0))
+            .retry(RetryPolicy {
+                max_attempts: 0,
+                ..RetryPolicy::default()
+            })
+            .batch_deadline(BatchDeadline::new(Duration::ZERO))
+            .build_lenient();
+        assert_eq!(config.batch_size, BatchConfig::default().batch_size);
+        assert_eq!(config.max_concurrent_batches, MAX_CONCURRENT_BATCHES);
+        assert_eq!(config.max_inflight_clients, None);
+        assert_eq!(config.duplicate_filter, None);
+        assert_eq!(config.retry.max_attempts, 1);
+        assert_eq!(config.batch_deadline, None);
     }
 }
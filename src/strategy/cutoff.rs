@@ -0,0 +1,139 @@
+//! Cutoff snapshots of the account states during a run
+//!
+//! A run normally only writes the final account states. With cutoff snapshots,
+//! the sequential strategies also write the account states after every N input
+//! records to numbered files (`snapshot-000001.csv`, `snapshot-000002.csv`,
+//! ...), so the balances at settlement boundaries within a multi-day file can
+//! be reported. Records carry no timestamps, so cutoffs are counted in records;
+//! cutoffs at date boundaries (a daily cutoff) need a timestamp column first.
+
+use crate::io::AccountSink;
+use crate::types::Account;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+/// Where and how often to write cutoff snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutoffOptions {
+    /// Directory the numbered snapshot files are written to
+    pub dir: PathBuf,
+    /// Number of input records between snapshots (at least 1)
+    pub every: u64,
+}
+
+impl CutoffOptions {
+    /// Write a snapshot to `dir` after every `every` input records
+    pub fn new(dir: impl Into<PathBuf>, every: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            every: every.max(1),
+        }
+    }
+
+    /// Path of the snapshot with the given number (1 for the first)
+    pub fn path(&self, number: u64) -> PathBuf {
+        self.dir.join(format!("snapshot-{:06}.csv", number))
+    }
+}
+
+/// Cutoff stage of a processing run
+#[derive(Debug, Default)]
+pub(crate) struct Cutoffs {
+    options: Option<CutoffOptions>,
+    /// Number of snapshots written so far
+    written: u64,
+}
+
+impl Cutoffs {
+    /// Create the snapshot directory, if cutoff snapshots are configured
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Cutoffs)` - A stage that writes the configured snapshots, or none
+    /// * `Err(String)` - If the directory cannot be created
+    pub(crate) fn open(options: Option<&CutoffOptions>) -> Result<Self, String> {
+        if let Some(options) = options {
+            fs::create_dir_all(&options.dir).map_err(|e| {
+                format!(
+                    "Failed to create snapshot directory '{}': {}",
+                    options.dir.display(),
+                    e
+                )
+            })?;
+        }
+        Ok(Self {
+            options: options.cloned(),
+            written: 0,
+        })
+    }
+
+    /// Whether a snapshot is due after `records_read` input records
+    pub(crate) fn is_due(&self, records_read: u64) -> bool {
+        self.options
+            .as_ref()
+            .is_some_and(|options| records_read > 0 && records_read.is_multiple_of(options.every))
+    }
+
+    /// Write the next numbered snapshot
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the snapshot was written, or none is configured
+    /// * `Err(String)` - If the snapshot file cannot be written
+    pub(crate) fn write(&mut self, accounts: &[Account]) -> Result<(), String> {
+        let Some(options) = &self.options else {
+            return Ok(());
+        };
+        let path = options.path(self.written + 1);
+        let mut file = File::create(&path)
+            .map_err(|e| format!("Failed to create snapshot file '{}': {}", path.display(), e))?;
+        file.write_accounts(accounts)?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+    use tempfile::TempDir;
+
+    #[rstest]
+    #[case::before_first(0, false)]
+    #[case::between(3, false)]
+    #[case::first(4, true)]
+    #[case::second(8, true)]
+    fn test_is_due(#[case] records_read: u64, #[case] due: bool) {
+        let dir = TempDir::new().unwrap();
+        let cutoffs = Cutoffs::open(Some(&CutoffOptions::new(dir.path(), 4))).unwrap();
+        assert_eq!(cutoffs.is_due(records_read), due);
+        assert!(!Cutoffs::default().is_due(records_read));
+    }
+
+    #[test]
+    fn test_write_numbers_snapshots() {
+        let dir = TempDir::new().unwrap();
+        let options = CutoffOptions::new(dir.path().join("cutoffs"), 10);
+        let mut cutoffs = Cutoffs::open(Some(&options)).unwrap();
+        let mut account = Account::new(1);
+
+        account.available = Decimal::ONE;
+        account.total = Decimal::ONE;
+        cutoffs.write(&[account.clone()]).unwrap();
+        account.available = Decimal::TWO;
+        account.total = Decimal::TWO;
+        cutoffs.write(&[account]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(options.path(1)).unwrap(),
+            "client,available,held,total,locked\n1,1.0000,0.0000,1.0000,false\n"
+        );
+        assert_eq!(
+            fs::read_to_string(options.path(2)).unwrap(),
+            "client,available,held,total,locked\n1,2.0000,0.0000,2.0000,false\n"
+        );
+        assert!(options.path(2).ends_with("snapshot-000002.csv"));
+    }
+}
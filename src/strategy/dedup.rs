@@ -0,0 +1,155 @@
+//! Sliding-window deduplication of input records
+//!
+//! Upstream retries often deliver the same deposit or withdrawal row twice.
+//! The engine rejects the second copy as a duplicate transaction, which turns
+//! every retry into an error. `DedupFilter` instead drops records identical to
+//! one of the last `window` records before they reach the engine.
+//!
+//! Only deposits and withdrawals are deduplicated. Disputes, resolves and
+//! chargebacks reference existing transactions, so identical rows can be
+//! legitimate (a re-dispute after a resolve), and a retried one is already
+//! rejected by the dispute lifecycle.
+
+use crate::types::{TransactionRecord, TransactionType};
+use std::collections::{HashSet, VecDeque};
+
+/// Filter that recognizes records identical to a recently seen record
+#[derive(Debug, Default)]
+pub(crate) struct DedupFilter {
+    /// Number of recent records to compare against (0 disables the filter)
+    window: usize,
+    /// Recent records, oldest first
+    recent: VecDeque<TransactionRecord>,
+    /// The same records, for constant-time lookup
+    seen: HashSet<TransactionRecord>,
+}
+
+impl DedupFilter {
+    /// Create a filter comparing each record against the last `window` records
+    pub(crate) fn new(window: usize) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if the record duplicates one of the recent records
+    ///
+    /// Records that are not duplicates enter the window, evicting the oldest
+    /// record once the window is full.
+    pub(crate) fn is_duplicate(&mut self, record: &TransactionRecord) -> bool {
+        if self.window == 0
+            || !matches!(
+                record.tx_type,
+                TransactionType::Deposit | TransactionType::Withdrawal
+            )
+        {
+            return false;
+        }
+        if self.seen.contains(record) {
+            return true;
+        }
+
+        if self.recent.len() == self.window {
+            if let Some(oldest) = self.recent.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.recent.push_back(record.clone());
+        self.seen.insert(record.clone());
+        false
+    }
+
+    /// Remove duplicate records from a batch
+    ///
+    /// # Returns
+    ///
+    /// The number of records removed
+    pub(crate) fn retain_unique(&mut self, batch: &mut Vec<TransactionRecord>) -> u64 {
+        let before = batch.len();
+        batch.retain(|record| !self.is_duplicate(record));
+        (before - batch.len()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TransactionId;
+    use rstest::rstest;
+    use rust_decimal::Decimal;
+
+    fn deposit(tx: TransactionId, amount: &str) -> TransactionRecord {
+        TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            tx,
+            amount: Some(amount.parse().unwrap()),
+            line: None,
+            source: None,
+        }
+    }
+
+    #[rstest]
+    #[case::identical(deposit(1, "1.0"), true)]
+    #[case::equal_amount(deposit(1, "1.0000"), true)]
+    #[case::different_amount(deposit(1, "2.0"), false)]
+    #[case::different_tx(deposit(2, "1.0"), false)]
+    fn test_is_duplicate(#[case] record: TransactionRecord, #[case] expected: bool) {
+        let mut filter = DedupFilter::new(10);
+        assert!(!filter.is_duplicate(&deposit(1, "1.0")));
+
+        assert_eq!(filter.is_duplicate(&record), expected);
+    }
+
+    #[test]
+    fn test_disabled_filter_keeps_everything() {
+        let mut filter = DedupFilter::new(0);
+        assert!(!filter.is_duplicate(&deposit(1, "1.0")));
+        assert!(!filter.is_duplicate(&deposit(1, "1.0")));
+    }
+
+    #[test]
+    fn test_window_evicts_oldest_record() {
+        let mut filter = DedupFilter::new(2);
+        assert!(!filter.is_duplicate(&deposit(1, "1.0")));
+        assert!(!filter.is_duplicate(&deposit(2, "1.0")));
+        assert!(!filter.is_duplicate(&deposit(3, "1.0")));
+
+        // tx 1 has left the window, tx 3 is still in it
+        assert!(!filter.is_duplicate(&deposit(1, "1.0")));
+        assert!(filter.is_duplicate(&deposit(3, "1.0")));
+    }
+
+    #[test]
+    fn test_dispute_rows_are_never_duplicates() {
+        let mut filter = DedupFilter::new(10);
+        let dispute = TransactionRecord {
+            tx_type: TransactionType::Dispute,
+            client: 1,
+            tx: 1,
+            amount: None,
+            line: None,
+            source: None,
+        };
+
+        assert!(!filter.is_duplicate(&dispute));
+        assert!(!filter.is_duplicate(&dispute));
+    }
+
+    #[test]
+    fn test_retain_unique() {
+        let mut filter = DedupFilter::new(10);
+        let mut batch = vec![
+            deposit(1, "1.0"),
+            deposit(1, "1.0"),
+            deposit(2, "1.0"),
+            deposit(1, "1.0"),
+        ];
+
+        assert_eq!(filter.retain_unique(&mut batch), 2);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[1].tx, 2);
+        assert_eq!(batch[0].amount, Some(Decimal::ONE));
+    }
+}
@@ -0,0 +1,450 @@
+//! Follow processing strategy for growing input files
+//!
+//! This module provides a ProcessingStrategy that tails its input like
+//! `tail -f`: records are applied as they are appended to the file, and the
+//! account states are written to the output periodically instead of once at
+//! the end. It is meant for files written continuously by a collector, where
+//! balances are wanted in near real time.
+//!
+//! # Snapshots
+//!
+//! Each snapshot contains every account, like the output of a normal run. A
+//! snapshot is only written when records were applied since the previous one,
+//! and a final snapshot is always written when following stops.
+//!
+//! # Reloading Risk Rules
+//!
+//! With a risk rules file (`--risk-rules`), the file is checked on every poll
+//! and read again whenever its modification time changes, so the amount
+//! limits, velocity limit and withdrawal requirements can be tightened or
+//! relaxed without stopping and losing the engine's state. A file that cannot
+//! be read, or is invalid, is reported on stderr and the previous rules stay
+//! in force until it is fixed.
+//!
+//! # Stopping
+//!
+//! Without an idle timeout the strategy follows the file until the process is
+//! terminated. With one, it stops once no complete line has been appended for
+//! that long, which lets scripts and tests drain a file that has stopped
+//! growing.
+
+use crate::cli::InputFormat;
+use crate::core::{save_state, Engine, EngineConfig, RiskRules, TransactionEngine};
+use crate::io::{is_object_url, log, read_risk_rules, AccountSink, FollowReader, LogLine};
+use crate::strategy::{
+    check_inputs, AccountTotals, Conservation, InputOptions, ProcessingStrategy, RecordStages,
+    RunSummary, StandingOrders,
+};
+use crate::types::EngineError;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Timing options for following a growing file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowOptions {
+    /// How long to wait before checking the file again when nothing was appended
+    pub poll_interval: Duration,
+    /// Minimum time between two account snapshots
+    pub snapshot_interval: Duration,
+    /// Stop following once nothing was appended for this long (`None` follows forever)
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(250),
+            snapshot_interval: Duration::from_secs(5),
+            idle_timeout: None,
+        }
+    }
+}
+
+impl FollowOptions {
+    /// Set the minimum time between two account snapshots
+    pub fn with_snapshot_interval(mut self, snapshot_interval: Duration) -> Self {
+        self.snapshot_interval = snapshot_interval;
+        self
+    }
+
+    /// Stop following once nothing was appended for `idle_timeout`
+    pub fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Set how long to wait between checks of an idle file
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+}
+
+/// Risk rules file, read again whenever it changes
+#[derive(Debug)]
+struct RulesWatch {
+    path: PathBuf,
+    /// Modification time of the file when it was last read, if it was
+    modified: Option<Option<SystemTime>>,
+}
+
+impl RulesWatch {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: None,
+        }
+    }
+
+    /// The rules in the file, if it changed since it was last read
+    fn changed(&mut self) -> Option<Result<RiskRules, String>> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        // A missing file is reported once, not on every poll
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+        Some(read_risk_rules(&self.path))
+    }
+}
+
+/// Processing strategy that follows a growing CSV file
+///
+/// # Examples
+///
+/// ```no_run
+/// use rust_payments_engine::strategy::{FollowOptions, FollowProcessingStrategy, ProcessingStrategy};
+/// use std::path::Path;
+/// use std::io;
+///
+/// let strategy = FollowProcessingStrategy::new(FollowOptions::default());
+/// let mut output = io::stdout();
+///
+/// // Runs until the process is terminated, printing a snapshot every 5 seconds
+/// strategy.process(Path::new("transactions.csv"), &mut output)
+///     .expect("Processing failed");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FollowProcessingStrategy {
+    /// Timing of polls and snapshots
+    follow: FollowOptions,
+    /// Options for reading the input file
+    input: InputOptions,
+    /// Configuration for the transaction engine
+    engine_config: EngineConfig,
+    /// State file to write the final engine snapshot to
+    save_state: Option<PathBuf>,
+    /// Risk rules file to reload when it changes
+    risk_rules: Option<PathBuf>,
+}
+
+impl FollowProcessingStrategy {
+    /// Create a new FollowProcessingStrategy with the given timing options
+    pub fn new(follow: FollowOptions) -> Self {
+        Self {
+            follow,
+            ..Self::default()
+        }
+    }
+
+    /// Set the input options, or just the format of the input file
+    pub fn with_input(mut self, input: impl Into<InputOptions>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Set the transaction engine configuration
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Write the final engine snapshot to a state file (`--save-state`)
+    pub fn with_save_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_state = Some(path.into());
+        self
+    }
+
+    /// Take the risk rules from a file, reloading them whenever it changes
+    /// (`--risk-rules`)
+    pub fn with_risk_rules_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.risk_rules = Some(path.into());
+        self
+    }
+}
+
+impl ProcessingStrategy for FollowProcessingStrategy {
+    /// Follow a single CSV file, writing account snapshots as records arrive
+    ///
+    /// # Arguments
+    ///
+    /// * `input_paths` - Exactly one path, to a CSV file
+    /// * `output` - Sink receiving every snapshot
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RunSummary)` once the idle timeout expires
+    /// * `Err(EngineError)` if the input is not a single CSV file, or a fatal I/O
+    ///   error occurred
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        let [input_path] = input_paths else {
+            return Err(EngineError::Other(
+                "Follow mode requires exactly one input file".to_string(),
+            ));
+        };
+        if self.input.format != InputFormat::Csv {
+            return Err(EngineError::Other(format!(
+                "Follow mode only supports CSV input, not {}",
+                self.input.format
+            )));
+        }
+        if is_object_url(&input_path.to_string_lossy()) {
+            return Err(EngineError::Other(
+                "Follow mode requires a local input file".to_string(),
+            ));
+        }
+        check_inputs(input_paths)?;
+
+        let mut reader =
+            FollowReader::open(input_path)?.with_dialect(self.input.csv_dialect.clone());
+        let mut engine =
+            TransactionEngine::with_config(self.input.engine_config(&self.engine_config));
+        let mut stages = RecordStages::open(&self.input)?;
+        let mut standing_orders = StandingOrders::new(&self.input.standing_orders);
+
+        // The rules must be valid to start with; later errors keep the
+        // previous rules
+        let mut rules_watch = self.risk_rules.as_deref().map(RulesWatch::new);
+        if let Some(Some(rules)) = rules_watch.as_mut().map(RulesWatch::changed) {
+            engine.set_risk_rules(rules.map_err(EngineError::Other)?);
+        }
+
+        let mut last_append = Instant::now();
+        let mut last_snapshot = Instant::now();
+        let mut changed = false;
+
+        loop {
+            match rules_watch.as_mut().and_then(RulesWatch::changed) {
+                Some(Ok(rules)) => {
+                    engine.set_risk_rules(rules);
+                    if let Some(watch) = &rules_watch {
+                        let event = format!("Reloaded risk rules from '{}'", watch.path.display());
+                        log(LogLine::info(event));
+                    }
+                }
+                Some(Err(e)) => log(LogLine::warning(format!(
+                    "Warning: {}; keeping the previous risk rules",
+                    e
+                ))),
+                None => {}
+            }
+
+            let records = reader.poll()?;
+            let idle = records.is_empty();
+            if !idle {
+                last_append = Instant::now();
+                changed = true;
+            }
+
+            let checked = records
+                .into_iter()
+                .map(|result| result.and_then(|record| self.input.check(record)));
+            for result in standing_orders.expand(checked) {
+                stages.apply(&mut engine, result)?;
+            }
+
+            if changed && last_snapshot.elapsed() >= self.follow.snapshot_interval {
+                stages.flush()?;
+                output.write_accounts(&Engine::get_accounts(&engine))?;
+                last_snapshot = Instant::now();
+                changed = false;
+            }
+
+            if idle {
+                if let Some(idle_timeout) = self.follow.idle_timeout {
+                    if last_append.elapsed() >= idle_timeout {
+                        break;
+                    }
+                }
+                thread::sleep(self.follow.poll_interval);
+            }
+        }
+
+        let mut summary = stages.finish()?;
+        let accounts = Engine::get_accounts(&engine);
+        summary.accounts = AccountTotals::of(&accounts);
+        summary.conservation = Some(Conservation::check(engine.flows(), &accounts));
+        if let Some(path) = &self.save_state {
+            save_state(path, &engine.snapshot())?;
+        }
+        output.write_accounts(&accounts)?;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::NamedTempFile;
+
+    fn options(idle_timeout_ms: u64) -> FollowOptions {
+        FollowOptions::default()
+            .with_poll_interval(Duration::from_millis(10))
+            .with_snapshot_interval(Duration::ZERO)
+            .with_idle_timeout(Some(Duration::from_millis(idle_timeout_ms)))
+    }
+
+    #[test]
+    fn test_follow_processes_appended_records() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"type,client,tx,amount\ndeposit,1,1,10.0\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let mut writer = OpenOptions::new().append(true).open(file.path()).unwrap();
+        let appender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            writer
+                .write_all(b"deposit,1,2,5.0\nwithdrawal,1,3,2.5\n")
+                .unwrap();
+        });
+
+        let strategy = FollowProcessingStrategy::new(options(500));
+        let mut output = Vec::new();
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        appender.join().unwrap();
+
+        assert_eq!(summary.records_read, 3);
+        let output = String::from_utf8(output).unwrap();
+        let snapshots: Vec<&str> = output
+            .split("client,available,held,total,locked\n")
+            .filter(|snapshot| !snapshot.is_empty())
+            .collect();
+        assert!(snapshots.len() >= 2, "expected several snapshots: {output}");
+        assert_eq!(snapshots[0], "1,10.0000,0.0000,10.0000,false\n");
+        assert_eq!(
+            snapshots.last().unwrap(),
+            &"1,12.5000,0.0000,12.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_follow_reloads_risk_rules() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let rules = dir.path().join("rules.json");
+        std::fs::write(&rules, r#"{"max_deposit": "100"}"#).unwrap();
+        let input = dir.path().join("input.csv");
+        std::fs::write(&input, "type,client,tx,amount\ndeposit,1,1,50.0\n").unwrap();
+
+        let mut writer = OpenOptions::new().append(true).open(&input).unwrap();
+        let rules_path = rules.clone();
+        let updater = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            std::fs::write(&rules_path, "not json").unwrap();
+            thread::sleep(Duration::from_millis(100));
+            std::fs::write(&rules_path, r#"{"max_deposit": "10"}"#).unwrap();
+            thread::sleep(Duration::from_millis(100));
+            writer
+                .write_all(b"deposit,1,2,50.0\ndeposit,1,3,5.0\n")
+                .unwrap();
+        });
+
+        let strategy = FollowProcessingStrategy::new(options(500)).with_risk_rules_file(&rules);
+        let mut output = Vec::new();
+        let summary = strategy.process(&input, &mut output).unwrap();
+        updater.join().unwrap();
+
+        // The invalid file kept the first rules, and the state survived the
+        // reload
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.limit_rejections, 1);
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.ends_with("1,55.0000,0.0000,55.0000,false\n"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn test_follow_rejects_invalid_risk_rules() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let rules = dir.path().join("rules.json");
+        std::fs::write(&rules, r#"{"max_deposit": "ten"}"#).unwrap();
+        let input = dir.path().join("input.csv");
+        std::fs::write(&input, "type,client,tx,amount\n").unwrap();
+
+        let strategy = FollowProcessingStrategy::new(options(50)).with_risk_rules_file(&rules);
+        let err = strategy
+            .process(&input, &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Invalid risk rules file"), "{}", err);
+    }
+
+    #[test]
+    fn test_follow_writes_final_snapshot_of_empty_file() {
+        let file = NamedTempFile::new().unwrap();
+
+        let strategy = FollowProcessingStrategy::new(options(50));
+        let mut output = Vec::new();
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+
+        assert_eq!(summary.records_read, 0);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n"
+        );
+    }
+
+    #[test]
+    fn test_follow_requires_single_input() {
+        let strategy = FollowProcessingStrategy::new(options(50));
+        let paths = vec![PathBuf::from("a.csv"), PathBuf::from("b.csv")];
+        let err = strategy
+            .process_files(&paths, &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("exactly one input file"));
+    }
+
+    #[test]
+    fn test_follow_rejects_avro_input() {
+        let strategy = FollowProcessingStrategy::new(options(50)).with_input(InputFormat::Avro);
+        let err = strategy
+            .process(Path::new("input.avro"), &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("only supports CSV input"));
+    }
+
+    #[test]
+    fn test_follow_rejects_object_input() {
+        let strategy = FollowProcessingStrategy::new(options(50));
+        let err = strategy
+            .process(Path::new("s3://bucket/input.csv"), &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("local input file"));
+    }
+
+    #[test]
+    fn test_follow_handles_missing_file() {
+        let strategy = FollowProcessingStrategy::new(options(50));
+        let err = strategy
+            .process(Path::new("nonexistent.csv"), &mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to open file"));
+    }
+}
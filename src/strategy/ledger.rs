@@ -0,0 +1,199 @@
+//! SQLite ledger processing strategy
+//!
+//! This module provides a ProcessingStrategy that applies transactions to a
+//! persistent `SqliteLedger` instead of in-memory state. Each run is one
+//! incremental load: it continues from the balances and stored transactions
+//! left by earlier runs against the same database file.
+//!
+//! Records are applied sequentially, in input order. The output contains every
+//! account in the ledger, not only the accounts touched by this run.
+
+use crate::core::sqlite_ledger::SqliteLedger;
+use crate::core::{DuplicateTxPolicy, EngineConfig};
+use crate::io::AccountSink;
+use crate::strategy::{
+    check_inputs, input_source, open_records, AccountTotals, InputOptions, ProcessingStrategy,
+    RecordStages, RunSummary, StandingOrders,
+};
+use crate::types::EngineError;
+use std::path::PathBuf;
+
+/// Processing strategy backed by a SQLite ledger
+///
+/// # Atomicity
+///
+/// The whole run is a single ledger load: if a fatal error occurs (unreadable
+/// input, database failure), nothing from this run is committed.
+#[derive(Debug, Clone)]
+pub struct LedgerProcessingStrategy {
+    /// Path to the SQLite database file
+    ledger_path: PathBuf,
+    /// Options for reading the input file
+    input: InputOptions,
+    /// Configuration for the transaction engine
+    engine_config: EngineConfig,
+}
+
+impl LedgerProcessingStrategy {
+    /// Create a new LedgerProcessingStrategy
+    ///
+    /// # Arguments
+    ///
+    /// * `ledger_path` - Path to the SQLite database file (created if missing)
+    pub fn new(ledger_path: impl Into<PathBuf>) -> Self {
+        Self {
+            ledger_path: ledger_path.into(),
+            input: InputOptions::default(),
+            engine_config: EngineConfig::default(),
+        }
+    }
+
+    /// Set the input options, or just the format of the input file
+    pub fn with_input(mut self, input: impl Into<InputOptions>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Set the transaction engine configuration
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+}
+
+impl ProcessingStrategy for LedgerProcessingStrategy {
+    /// Load transactions from input files into the ledger and write all accounts
+    ///
+    /// # Arguments
+    ///
+    /// * `input_paths` - Paths to the input files, processed in order
+    /// * `output` - Sink for the account states
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RunSummary)` if the load was committed
+    /// * `Err(EngineError)` if a fatal error occurred; the ledger is left unchanged
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        check_inputs(input_paths)?;
+        if self.input.cutoffs.is_some() {
+            return Err(EngineError::Other(
+                "Cutoff snapshots are not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        if self.input.journal.is_some() {
+            return Err(EngineError::Other(
+                "Journal output is not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        if self.input.trial_balance.is_some() {
+            return Err(EngineError::Other(
+                "Trial balance output is not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        if self.input.changes.is_some() {
+            return Err(EngineError::Other(
+                "Balance change output is not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        if self.input.analytics.is_some() {
+            return Err(EngineError::Other(
+                "Analytics are not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        if self.input.balance_history.is_some() {
+            return Err(EngineError::Other(
+                "Balance history is not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        if self.engine_config.duplicate_tx_policy == DuplicateTxPolicy::PerClient {
+            return Err(EngineError::Other(
+                "Per-client transaction IDs are not supported with the SQLite ledger".to_string(),
+            ));
+        }
+        let mut ledger =
+            SqliteLedger::open(&self.ledger_path)?.with_config(self.engine_config.clone());
+
+        let mut stages = RecordStages::open(&self.input)?;
+        let mut standing_orders = StandingOrders::new(&self.input.standing_orders);
+        let mut load = ledger.begin_load()?;
+
+        for input_path in input_paths {
+            for result in standing_orders.expand(open_records(
+                input_path,
+                &self.input,
+                input_source(input_paths, input_path),
+            )?) {
+                stages.apply_with(result, |record| load.process(record))?;
+            }
+        }
+
+        let mut summary = stages.finish()?;
+        load.commit()?;
+
+        let accounts = ledger.accounts()?;
+        summary.accounts = AccountTotals::of(&accounts);
+        output.write_accounts(&accounts)?;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::{NamedTempFile, TempDir};
+
+    /// Helper function to create a temporary CSV file for testing
+    fn create_temp_csv(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().expect("Failed to create temp file");
+        file.write_all(content.as_bytes())
+            .expect("Failed to write to temp file");
+        file.flush().expect("Failed to flush temp file");
+        file
+    }
+
+    #[test]
+    fn test_ledger_strategy_incremental_loads() {
+        let dir = TempDir::new().unwrap();
+        let strategy = LedgerProcessingStrategy::new(dir.path().join("ledger.db"));
+
+        let day1 = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\ndeposit,2,2,5.0\n");
+        let mut output = Vec::new();
+        strategy.process(day1.path(), &mut output).unwrap();
+
+        let day2 = create_temp_csv("type,client,tx,amount\ndispute,1,1,\nwithdrawal,2,3,10.0\n");
+        let mut output = Vec::new();
+        let summary = strategy.process(day2.path(), &mut output).unwrap();
+
+        assert_eq!(summary.records_read, 2);
+        assert_eq!(summary.transaction_errors, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,0.0000,100.0000,100.0000,false\n\
+             2,5.0000,0.0000,5.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_ledger_strategy_missing_input_leaves_ledger_untouched() {
+        let dir = TempDir::new().unwrap();
+        let ledger_path = dir.path().join("ledger.db");
+        let strategy = LedgerProcessingStrategy::new(&ledger_path);
+
+        let mut output = Vec::new();
+        let result = strategy.process(Path::new("nonexistent.csv"), &mut output);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to open file"));
+        assert!(!ledger_path.exists());
+    }
+}
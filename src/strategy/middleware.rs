@@ -0,0 +1,201 @@
+//! Record middleware applied before the engine
+//!
+//! A `RecordMiddleware` transforms each parsed record before any stage or the
+//! engine sees it, for example to move the client IDs of one tenant into their
+//! own range when merging the files of several tenants, or to convert amounts
+//! given in minor units. Middleware is registered on the input options and
+//! runs in registration order; a record it rejects is counted as a parse error.
+
+use crate::types::{ClientId, TransactionRecord};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::sync::Arc;
+
+/// Transformation applied to every record before it is processed
+pub trait RecordMiddleware: fmt::Debug + Send + Sync {
+    /// Transform a parsed record
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TransactionRecord)` - The record to process in its place
+    /// * `Err(String)` - If the record must be rejected as a parse error
+    fn transform(&self, record: TransactionRecord) -> Result<TransactionRecord, String>;
+}
+
+/// Middleware registered on a pipeline, in the order it is applied
+#[derive(Debug, Clone, Default)]
+pub struct MiddlewareChain {
+    middleware: Vec<Arc<dyn RecordMiddleware>>,
+}
+
+impl MiddlewareChain {
+    /// Append middleware to the end of the chain
+    pub fn push(&mut self, middleware: impl RecordMiddleware + 'static) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// Whether no middleware is registered
+    pub fn is_empty(&self) -> bool {
+        self.middleware.is_empty()
+    }
+
+    /// Run a record through every middleware in turn
+    pub fn apply(&self, record: TransactionRecord) -> Result<TransactionRecord, String> {
+        self.middleware
+            .iter()
+            .try_fold(record, |record, middleware| middleware.transform(record))
+    }
+}
+
+/// Chains are equal if they hold the same middleware instances
+impl PartialEq for MiddlewareChain {
+    fn eq(&self, other: &Self) -> bool {
+        self.middleware.len() == other.middleware.len()
+            && self
+                .middleware
+                .iter()
+                .zip(&other.middleware)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl Eq for MiddlewareChain {}
+
+/// Adds a fixed offset to every client ID
+///
+/// Merging the files of several tenants whose client IDs overlap is done by
+/// processing each tenant with its own offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIdOffset {
+    /// Added to the client ID of every record
+    pub offset: ClientId,
+}
+
+impl ClientIdOffset {
+    /// Create middleware adding `offset` to every client ID
+    pub fn new(offset: ClientId) -> Self {
+        Self { offset }
+    }
+}
+
+impl RecordMiddleware for ClientIdOffset {
+    /// Rejects records whose offset client ID would not fit in a `ClientId`
+    fn transform(&self, mut record: TransactionRecord) -> Result<TransactionRecord, String> {
+        record.client = record.client.checked_add(self.offset).ok_or_else(|| {
+            format!(
+                "Client {} with offset {} exceeds the client ID range",
+                record.client, self.offset
+            )
+        })?;
+        Ok(record)
+    }
+}
+
+/// Multiplies every amount by a fixed factor
+///
+/// A factor of `0.01` reads amounts given in cents as currency units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmountScale {
+    /// Multiplied with the amount of every record that has one
+    pub factor: Decimal,
+}
+
+impl AmountScale {
+    /// Create middleware multiplying every amount by `factor`
+    pub fn new(factor: Decimal) -> Self {
+        Self { factor }
+    }
+}
+
+impl RecordMiddleware for AmountScale {
+    /// Rejects records whose scaled amount would overflow
+    fn transform(&self, mut record: TransactionRecord) -> Result<TransactionRecord, String> {
+        if let Some(amount) = record.amount {
+            record.amount = Some(amount.checked_mul(self.factor).ok_or_else(|| {
+                format!(
+                    "Amount {} of tx {} overflows when scaled by {}",
+                    amount, record.tx, self.factor
+                )
+            })?);
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TransactionType;
+    use rstest::rstest;
+
+    fn deposit(client: ClientId, amount: Decimal) -> TransactionRecord {
+        TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client,
+            tx: 1,
+            amount: Some(amount),
+            line: None,
+            source: None,
+        }
+    }
+
+    #[rstest]
+    #[case::offset(1, 1000, Ok(1001))]
+    #[case::zero(7, 0, Ok(7))]
+    #[case::overflow(ClientId::MAX, 1, Err(()))]
+    fn test_client_id_offset(
+        #[case] client: ClientId,
+        #[case] offset: ClientId,
+        #[case] expected: Result<ClientId, ()>,
+    ) {
+        let result = ClientIdOffset::new(offset).transform(deposit(client, Decimal::ONE));
+        assert_eq!(result.map(|record| record.client).map_err(|_| ()), expected);
+    }
+
+    #[test]
+    fn test_amount_scale() {
+        let scale = AmountScale::new(Decimal::new(1, 2));
+
+        let record = scale.transform(deposit(1, Decimal::from(12345))).unwrap();
+        assert_eq!(record.amount, Some(Decimal::new(12345, 2)));
+
+        let dispute = TransactionRecord {
+            tx_type: TransactionType::Dispute,
+            amount: None,
+            ..record
+        };
+        assert_eq!(scale.transform(dispute.clone()).unwrap(), dispute);
+
+        let overflow = AmountScale::new(Decimal::MAX).transform(deposit(1, Decimal::TWO));
+        assert!(overflow.unwrap_err().contains("overflows"));
+    }
+
+    #[test]
+    fn test_chain_applies_in_order() {
+        let mut chain = MiddlewareChain::default();
+        assert!(chain.is_empty());
+        assert_eq!(
+            chain.apply(deposit(1, Decimal::ONE)),
+            Ok(deposit(1, Decimal::ONE))
+        );
+
+        chain.push(AmountScale::new(Decimal::TEN));
+        chain.push(ClientIdOffset::new(100));
+        assert_eq!(
+            chain.apply(deposit(1, Decimal::ONE)),
+            Ok(deposit(101, Decimal::TEN))
+        );
+        assert!(chain.apply(deposit(ClientId::MAX, Decimal::ONE)).is_err());
+    }
+
+    #[test]
+    fn test_chain_equality() {
+        let mut chain = MiddlewareChain::default();
+        chain.push(ClientIdOffset::new(1));
+
+        assert_eq!(chain, chain.clone());
+        let mut other = MiddlewareChain::default();
+        other.push(ClientIdOffset::new(1));
+        assert_ne!(chain, other);
+    }
+}
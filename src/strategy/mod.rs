@@ -4,50 +4,350 @@
 //! encompassing both CSV parsing and transaction engine processing. This allows different
 //! processing implementations (synchronous, asynchronous batch) to be selected at runtime.
 
-use crate::cli::StrategyType;
-use std::io::Write;
-use std::path::Path;
+use crate::cli::{InputFormat, StrategyType};
+use crate::core::EngineConfig;
+use crate::io::{log, AccountSink, ClientPseudonymizer, CsvDialect, DeadLetterOptions, LogLine};
+use crate::types::{ClientSet, EngineError, TransactionId, TransactionRecord};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+pub mod analytics;
 pub mod r#async;
+pub mod cutoff;
+mod dedup;
+pub mod follow;
+#[cfg(feature = "sqlite")]
+pub mod ledger;
+pub mod middleware;
+pub mod quarantine;
+pub mod registry;
+pub mod replica;
+pub mod sanity;
+pub mod sharded;
+mod source;
+mod stages;
+pub mod standing_orders;
+pub mod summary;
 pub mod sync;
+pub mod wal;
 
-pub use self::r#async::{AsyncProcessingStrategy, BatchConfig};
+pub use self::r#async::{
+    AsyncProcessingStrategy, BatchConfig, BatchConfigBuilder, DuplicateFilterOptions,
+    RuntimeOptions, AUTO_CURRENT_THREAD_BYTES, DEFAULT_DUPLICATE_FILTER_CAPACITY, MAX_BATCH_SIZE,
+    MAX_CONCURRENT_BATCHES,
+};
+pub(crate) use analytics::Analytics;
+pub use analytics::{AmountBucket, AnalyticsReport, ChargebackTotals, ClientStats};
+pub use cutoff::CutoffOptions;
+pub(crate) use cutoff::Cutoffs;
+pub(crate) use dedup::DedupFilter;
+pub use follow::{FollowOptions, FollowProcessingStrategy};
+#[cfg(feature = "sqlite")]
+pub use ledger::LedgerProcessingStrategy;
+pub use middleware::{AmountScale, ClientIdOffset, MiddlewareChain, RecordMiddleware};
+pub(crate) use quarantine::Quarantine;
+pub use quarantine::{QuarantineOptions, QuarantineRule};
+pub use registry::{
+    register_strategy, registered_strategies, RegisteredStrategy, StrategyFactory,
+    StrategySettings, BUILTIN_STRATEGIES,
+};
+pub use replica::ReplicaProcessingStrategy;
+pub(crate) use sanity::SanityWarnings;
+pub use sanity::{SanityCheck, SanityOptions, PERCENTILE_MIN_AMOUNTS};
+pub use sharded::{shard_of, ShardedProcessingStrategy};
+pub(crate) use source::{input_source, open_batches, open_records};
+pub(crate) use stages::{transaction_error, RecordStages};
+pub use standing_orders::StandingOrder;
+pub(crate) use standing_orders::StandingOrders;
+pub use summary::{AccountTotals, Conservation, RetryCounts, RunSummary, TransactionTypeCounts};
 pub use sync::SyncProcessingStrategy;
+pub use wal::WalProcessingStrategy;
+
+/// Where and how often to write each client's balance history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceHistoryOptions {
+    /// File the balance samples are written to
+    pub path: PathBuf,
+    /// Number of a client's applied transactions between samples (at least 1)
+    pub every: u32,
+}
+
+impl BalanceHistoryOptions {
+    /// Write a client's balances after every `every` of its transactions to `path`
+    pub fn new(path: impl Into<PathBuf>, every: u32) -> Self {
+        Self {
+            path: path.into(),
+            every: every.max(1),
+        }
+    }
+}
+
+/// Options for reading and filtering input records, shared by all strategies
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputOptions {
+    /// Format of the input file
+    pub format: InputFormat,
+    /// Reject transaction IDs above `u32::MAX`, for files that must stay
+    /// compatible with consumers of the legacy 32-bit transaction ID
+    pub legacy_tx_ids: bool,
+    /// Skip deposits and withdrawals identical to one of this many preceding
+    /// records (0 disables deduplication)
+    pub dedup_window: usize,
+    /// Divert transactions matching risk rules to a quarantine file instead
+    /// of applying them
+    pub quarantine: Option<QuarantineOptions>,
+    /// Write warnings about suspicious records to a file, processing them as
+    /// usual
+    pub sanity: Option<SanityOptions>,
+    /// Only process records of these clients, skipping all others
+    pub clients: Option<ClientSet>,
+    /// Delimiter, decimal separator and header names of CSV input
+    pub csv_dialect: CsvDialect,
+    /// Transformations applied to every record before it is processed
+    pub middleware: MiddlewareChain,
+    /// Write numbered account snapshots every N input records (sequential
+    /// strategies only)
+    pub cutoffs: Option<CutoffOptions>,
+    /// Write a double-entry journal of the applied transactions to this file
+    pub journal: Option<PathBuf>,
+    /// Write the trial balance of the applied transactions to this file
+    pub trial_balance: Option<PathBuf>,
+    /// Send every balance change to this file or Kafka topic
+    pub changes: Option<String>,
+    /// Write every client's balances after every K applied transactions
+    pub balance_history: Option<BalanceHistoryOptions>,
+    /// Collect aggregate analytics, listing this many top clients per ranking
+    pub analytics: Option<usize>,
+    /// Read CSV input with the `FastCsvReader` (feature `fast-csv`), which
+    /// falls back to the flexible reader on anomalies
+    pub fast_csv: bool,
+    /// Replace the clients named in logged errors with their pseudonyms
+    pub pseudonymizer: Option<Arc<ClientPseudonymizer>>,
+    /// Send the records the engine rejects to a dead-letter sink
+    pub dead_letter: Option<DeadLetterOptions>,
+    /// Recurring deposits and withdrawals merged into the input (sequential
+    /// strategies only)
+    pub standing_orders: Vec<StandingOrder>,
+}
+
+impl InputOptions {
+    /// Largest transaction ID accepted in legacy mode
+    pub const LEGACY_MAX_TX_ID: TransactionId = u32::MAX as TransactionId;
+
+    /// Create input options for the given format with default settings
+    pub fn new(format: InputFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Enable or disable legacy 32-bit transaction ID checking
+    pub fn with_legacy_tx_ids(mut self, legacy_tx_ids: bool) -> Self {
+        self.legacy_tx_ids = legacy_tx_ids;
+        self
+    }
+
+    /// Set the number of preceding records to check for identical duplicates
+    pub fn with_dedup_window(mut self, dedup_window: usize) -> Self {
+        self.dedup_window = dedup_window;
+        self
+    }
+
+    /// Quarantine transactions matching the given rules
+    pub fn with_quarantine(mut self, quarantine: QuarantineOptions) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
+    /// Write warnings about records failing the given sanity checks
+    pub fn with_sanity(mut self, sanity: SanityOptions) -> Self {
+        self.sanity = Some(sanity);
+        self
+    }
+
+    /// Only process records of the given clients
+    pub fn with_clients(mut self, clients: ClientSet) -> Self {
+        self.clients = Some(clients);
+        self
+    }
+
+    /// Read CSV input in the given dialect
+    pub fn with_csv_dialect(mut self, csv_dialect: CsvDialect) -> Self {
+        self.csv_dialect = csv_dialect;
+        self
+    }
+
+    /// Apply middleware to every record, after any registered before it
+    pub fn with_middleware(mut self, middleware: impl RecordMiddleware + 'static) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Write numbered account snapshots every N input records
+    pub fn with_cutoffs(mut self, cutoffs: CutoffOptions) -> Self {
+        self.cutoffs = Some(cutoffs);
+        self
+    }
+
+    /// Write a double-entry journal of the applied transactions to `path`
+    pub fn with_journal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.journal = Some(path.into());
+        self
+    }
+
+    /// Write the trial balance of the applied transactions to `path`
+    pub fn with_trial_balance(mut self, path: impl Into<PathBuf>) -> Self {
+        self.trial_balance = Some(path.into());
+        self
+    }
+
+    /// Send every balance change to `target`, a file path or Kafka topic
+    pub fn with_changes(mut self, target: impl Into<String>) -> Self {
+        self.changes = Some(target.into());
+        self
+    }
+
+    /// Write every client's balance history
+    pub fn with_balance_history(mut self, history: BalanceHistoryOptions) -> Self {
+        self.balance_history = Some(history);
+        self
+    }
+
+    /// Collect aggregate analytics, listing the `top` clients per ranking
+    pub fn with_analytics(mut self, top: usize) -> Self {
+        self.analytics = Some(top);
+        self
+    }
+
+    /// Enable or disable the fast CSV reader
+    pub fn with_fast_csv(mut self, fast_csv: bool) -> Self {
+        self.fast_csv = fast_csv;
+        self
+    }
+
+    /// Log errors with clients replaced by their pseudonyms
+    pub fn with_pseudonymizer(mut self, pseudonymizer: Arc<ClientPseudonymizer>) -> Self {
+        self.pseudonymizer = Some(pseudonymizer);
+        self
+    }
+
+    /// Send the records the engine rejects to a dead-letter sink
+    pub fn with_dead_letter(mut self, dead_letter: DeadLetterOptions) -> Self {
+        self.dead_letter = Some(dead_letter);
+        self
+    }
+
+    /// Merge the records of standing orders into the input
+    pub fn with_standing_orders(mut self, orders: Vec<StandingOrder>) -> Self {
+        self.standing_orders = orders;
+        self
+    }
+
+    /// Log a line, with clients pseudonymized if configured
+    pub(crate) fn log(&self, line: LogLine) {
+        log(line.masked(self.pseudonymizer.as_deref()));
+    }
+
+    /// The engine configuration to process these inputs with
+    ///
+    /// Enables the engine's postings when they are written to a journal or
+    /// feed the trial balance, the balance changes or the analytics, and its balance samples when
+    /// they are written to a history file.
+    pub(crate) fn engine_config(&self, config: &EngineConfig) -> EngineConfig {
+        let mut config = config.clone().with_journal(
+            config.journal
+                || self.journal.is_some()
+                || self.trial_balance.is_some()
+                || self.changes.is_some()
+                || self.analytics.is_some(),
+        );
+        if let Some(history) = &self.balance_history {
+            config = config.with_balance_history(history.every);
+        }
+        config
+    }
+
+    /// Whether `check` can reject or change records
+    pub(crate) fn checks_records(&self) -> bool {
+        self.legacy_tx_ids || !self.middleware.is_empty()
+    }
+
+    /// Check a parsed record against these options and run it through the
+    /// middleware
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TransactionRecord)` - The record as transformed by the middleware
+    /// * `Err(String)` - If the record must be rejected as a parse error
+    pub(crate) fn check(&self, record: TransactionRecord) -> Result<TransactionRecord, String> {
+        if self.legacy_tx_ids && record.tx > Self::LEGACY_MAX_TX_ID {
+            return Err(format!(
+                "Transaction ID {} exceeds the legacy 32-bit range",
+                record.tx
+            ));
+        }
+        self.middleware.apply(record)
+    }
+}
+
+impl From<InputFormat> for InputOptions {
+    fn from(format: InputFormat) -> Self {
+        Self::new(format)
+    }
+}
 
 /// Processing strategy trait for complete transaction processing pipelines
 ///
 /// This trait defines the interface for different transaction processing implementations.
-/// Each strategy must be able to read transactions from a CSV file, process them through
-/// the appropriate engine, and write the final account states to output.
+/// Each strategy must be able to read transactions from one or more input files, process
+/// them through the appropriate transaction engine, and write the final account states
+/// to output.
 pub trait ProcessingStrategy: Send + Sync {
-    /// Process transactions from input file and write results to output
+    /// Process transactions from input files and write results to output
     ///
-    /// This method reads transaction records from the specified CSV file, processes
-    /// them through the appropriate transaction engine, and writes the final account
-    /// states to the provided output writer.
+    /// The files are processed in the given order through a single engine, as if
+    /// they were one concatenated input, and the final account states are written
+    /// to the provided output once all of them have been read.
     ///
     /// # Arguments
     ///
-    /// * `input_path` - Path to the input CSV file containing transaction records
-    /// * `output` - Mutable reference to a writer for outputting account states
+    /// * `input_paths` - Paths to the input files containing transaction records
+    /// * `output` - Sink for the final account states (any `Write` produces CSV)
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if all processing completed successfully (or with recoverable errors)
-    /// * `Err(String)` if a fatal error occurred (file not found, I/O error, etc.)
+    /// * `Ok(RunSummary)` if all processing completed (possibly with recoverable errors),
+    ///   containing the number of records read and how many of them failed
+    /// * `Err(EngineError)` if a fatal error occurred (file not found, I/O error, etc.)
     ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - The input file cannot be opened (file not found, permission denied)
+    /// - An input file cannot be opened (file not found, permission denied)
     /// - A fatal I/O error occurs during reading or writing
     /// - The CSV structure is fundamentally invalid
     /// - Output cannot be written
     ///
     /// Individual transaction processing errors should be logged to stderr but
     /// should not cause this method to return an error. Processing should continue
-    /// with the next transaction.
-    fn process(&self, input_path: &Path, output: &mut dyn Write) -> Result<(), String>;
+    /// with the next transaction, and the failure should be counted in the summary.
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError>;
+
+    /// Process transactions from a single input file and write results to output
+    ///
+    /// Equivalent to `process_files` with one input path.
+    fn process(
+        &self,
+        input_path: &Path,
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        self.process_files(&[input_path.to_path_buf()], output)
+    }
 }
 
 /// Create a processing strategy based on the specified strategy type
@@ -58,8 +358,13 @@ pub trait ProcessingStrategy: Send + Sync {
 ///
 /// # Arguments
 ///
-/// * `strategy_type` - The type of processing strategy to create (Sync or Async)
-/// * `config` - Optional configuration for async batch processing (ignored for sync)
+/// * `strategy_type` - The type of processing strategy to create (Sync, Async
+///   or a registered strategy)
+/// * `config` - Optional configuration for async batch processing (ignored for
+///   sync, passed on to registered strategies)
+/// * `input` - Input options, or just the format of the input file
+/// * `engine` - Configuration for the transaction engine
+/// * `save_state` - Optional state file for the final engine snapshot
 ///
 /// # Returns
 ///
@@ -67,12 +372,189 @@ pub trait ProcessingStrategy: Send + Sync {
 pub fn create_strategy(
     strategy_type: StrategyType,
     config: Option<crate::strategy::BatchConfig>,
+    input: impl Into<InputOptions>,
+    engine: EngineConfig,
+    save_state: Option<&Path>,
 ) -> Box<dyn ProcessingStrategy> {
     match strategy_type {
-        StrategyType::Sync => Box::new(SyncProcessingStrategy),
+        StrategyType::Sync => {
+            let mut strategy = SyncProcessingStrategy::new()
+                .with_input(input)
+                .with_engine_config(engine);
+            if let Some(path) = save_state {
+                strategy = strategy.with_save_state(path);
+            }
+            Box::new(strategy)
+        }
         StrategyType::Async => {
             let config = config.unwrap_or_default();
-            Box::new(AsyncProcessingStrategy::new(config))
+            let mut strategy = AsyncProcessingStrategy::new(config)
+                .with_input(input)
+                .with_engine_config(engine);
+            if let Some(path) = save_state {
+                strategy = strategy.with_save_state(path);
+            }
+            Box::new(strategy)
+        }
+        StrategyType::Custom(strategy) => strategy.create(StrategySettings {
+            batch: config,
+            input: input.into(),
+            engine,
+            save_state: save_state.map(Path::to_path_buf),
+        }),
+    }
+}
+
+/// Create a processing strategy that loads into a persistent SQLite ledger
+///
+/// # Arguments
+///
+/// * `ledger_path` - Path to the SQLite database file (created if missing)
+/// * `input` - Input options, or just the format of the input file
+/// * `engine` - Configuration for the transaction engine
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn ProcessingStrategy>)` - The ledger strategy
+/// * `Err(String)` - If the crate was built without the `sqlite` feature
+pub fn create_ledger_strategy(
+    ledger_path: &Path,
+    input: impl Into<InputOptions>,
+    engine: EngineConfig,
+) -> Result<Box<dyn ProcessingStrategy>, String> {
+    #[cfg(feature = "sqlite")]
+    {
+        Ok(Box::new(
+            LedgerProcessingStrategy::new(ledger_path)
+                .with_input(input)
+                .with_engine_config(engine),
+        ))
+    }
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = (ledger_path, input.into(), engine);
+        Err("The SQLite ledger requires building with the 'sqlite' feature".to_string())
+    }
+}
+
+/// Create a processing strategy that logs every record to a write-ahead log
+///
+/// # Arguments
+///
+/// * `wal_path` - Path to the write-ahead log file (replayed if it exists)
+/// * `input` - Input options, or just the format of the input file
+/// * `engine` - Configuration for the transaction engine
+/// * `save_state` - Optional state file for the final engine snapshot
+///
+/// # Returns
+///
+/// A boxed trait object implementing the ProcessingStrategy trait
+pub fn create_wal_strategy(
+    wal_path: &Path,
+    input: impl Into<InputOptions>,
+    engine: EngineConfig,
+    save_state: Option<&Path>,
+) -> Box<dyn ProcessingStrategy> {
+    let mut strategy = WalProcessingStrategy::new(wal_path)
+        .with_input(input)
+        .with_engine_config(engine);
+    if let Some(path) = save_state {
+        strategy = strategy.with_save_state(path);
+    }
+    Box::new(strategy)
+}
+
+/// Create a processing strategy that replicates the state of a write-ahead log
+///
+/// # Arguments
+///
+/// * `engine` - Configuration for the transaction engine, as used by the log's writer
+/// * `follow` - Timing of polls and snapshots
+/// * `save_state` - Optional state file, replaced with every snapshot
+///
+/// # Returns
+///
+/// A boxed trait object implementing the ProcessingStrategy trait
+pub fn create_replica_strategy(
+    engine: EngineConfig,
+    follow: FollowOptions,
+    save_state: Option<&Path>,
+) -> Box<dyn ProcessingStrategy> {
+    let mut strategy = ReplicaProcessingStrategy::new(follow).with_engine_config(engine);
+    if let Some(path) = save_state {
+        strategy = strategy.with_save_state(path);
+    }
+    Box::new(strategy)
+}
+
+/// Create a processing strategy that follows a growing input file
+///
+/// # Arguments
+///
+/// * `input` - Input options, or just the format of the input file
+/// * `engine` - Configuration for the transaction engine
+/// * `follow` - Timing of polls and snapshots
+/// * `save_state` - Optional state file for the final engine snapshot
+/// * `risk_rules` - Optional risk rules file, reloaded whenever it changes
+///
+/// # Returns
+///
+/// A boxed trait object implementing the ProcessingStrategy trait
+pub fn create_follow_strategy(
+    input: impl Into<InputOptions>,
+    engine: EngineConfig,
+    follow: FollowOptions,
+    save_state: Option<&Path>,
+    risk_rules: Option<&Path>,
+) -> Box<dyn ProcessingStrategy> {
+    let mut strategy = FollowProcessingStrategy::new(follow)
+        .with_input(input)
+        .with_engine_config(engine);
+    if let Some(path) = save_state {
+        strategy = strategy.with_save_state(path);
+    }
+    if let Some(path) = risk_rules {
+        strategy = strategy.with_risk_rules_file(path);
+    }
+    Box::new(strategy)
+}
+
+/// Create a processing strategy that splits the input into per-client shards
+///
+/// # Arguments
+///
+/// * `shards` - Number of shards, each processed on its own thread
+/// * `input` - Input options, or just the format of the input file
+/// * `engine` - Configuration for the shards' transaction engines
+///
+/// # Returns
+///
+/// A boxed trait object implementing the ProcessingStrategy trait
+pub fn create_sharded_strategy(
+    shards: usize,
+    input: impl Into<InputOptions>,
+    engine: EngineConfig,
+) -> Box<dyn ProcessingStrategy> {
+    Box::new(
+        ShardedProcessingStrategy::new(shards)
+            .with_input(input)
+            .with_engine_config(engine),
+    )
+}
+
+/// Check that every input file can be opened before processing any of them
+///
+/// A missing file late in the list would otherwise only be noticed after the
+/// earlier files had been processed.
+pub(crate) fn check_inputs(input_paths: &[PathBuf]) -> Result<(), EngineError> {
+    for path in input_paths {
+        if crate::io::is_object_url(&path.to_string_lossy()) {
+            crate::io::check_input(path)?;
+        } else {
+            std::fs::File::open(path).map_err(|e| {
+                EngineError::io(format!("Failed to open file '{}'", path.display()), e)
+            })?;
         }
     }
+    Ok(())
 }
@@ -0,0 +1,278 @@
+//! Quarantine of suspicious transactions
+//!
+//! Records matching a quarantine rule (e.g. an amount above a threshold) are
+//! written to the quarantine file instead of reaching the engine, so they can
+//! be reviewed and replayed later. Like deduplication, this runs as a stage
+//! between the reader and the engine in every strategy.
+//!
+//! Velocity rules depend on the records seen before: the stage tracks the
+//! transactions it lets through to the engine for each client.
+
+use crate::core::{VelocityLimit, VelocityTracker};
+use crate::io::QuarantineWriter;
+use crate::types::{TransactionRecord, TransactionType};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+
+/// Rule selecting transactions to quarantine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuarantineRule {
+    /// Deposits and withdrawals with an amount strictly above this threshold
+    AmountAbove(Decimal),
+    /// Withdrawals exceeding a velocity limit, instead of the engine rejecting
+    /// them
+    Velocity(VelocityLimit),
+}
+
+impl QuarantineRule {
+    /// Reason for quarantining the record, or `None` if the rule doesn't match
+    ///
+    /// Velocity rules need the client's earlier transactions and never match
+    /// here; the quarantine stage evaluates them.
+    pub fn reason(&self, record: &TransactionRecord) -> Option<String> {
+        match self {
+            QuarantineRule::AmountAbove(threshold) => record
+                .amount
+                .filter(|amount| amount > threshold)
+                .map(|amount| format!("amount {} above {}", amount, threshold)),
+            QuarantineRule::Velocity(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for QuarantineRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuarantineRule::AmountAbove(threshold) => write!(f, "amount above {}", threshold),
+            QuarantineRule::Velocity(limit) => write!(f, "velocity above {}", limit),
+        }
+    }
+}
+
+/// Where to quarantine transactions and which ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantineOptions {
+    /// Quarantine file, created or truncated at the start of each run
+    pub path: PathBuf,
+    /// Rules selecting transactions to quarantine; a record matching any of
+    /// them is quarantined
+    pub rules: Vec<QuarantineRule>,
+}
+
+impl QuarantineOptions {
+    /// Create quarantine options writing to `path`, with no rules yet
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a rule selecting transactions to quarantine
+    pub fn with_rule(mut self, rule: QuarantineRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+}
+
+/// Quarantine stage of a processing run
+#[derive(Default)]
+pub(crate) struct Quarantine {
+    rules: Vec<QuarantineRule>,
+    writer: Option<QuarantineWriter<File>>,
+    /// Transactions let through so far, for a velocity rule
+    velocity: Option<VelocityTracker>,
+}
+
+impl Quarantine {
+    /// Open the quarantine file, if quarantine is configured
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Quarantine)` - A stage that diverts matching records, or none
+    /// * `Err(String)` - If the quarantine file cannot be created
+    pub(crate) fn open(options: Option<&QuarantineOptions>) -> Result<Self, String> {
+        match options {
+            Some(options) => Ok(Self {
+                rules: options.rules.clone(),
+                writer: Some(QuarantineWriter::create(&options.path)?),
+                velocity: options.rules.iter().find_map(|rule| match rule {
+                    QuarantineRule::Velocity(limit) => Some(VelocityTracker::new(*limit)),
+                    QuarantineRule::AmountAbove(_) => None,
+                }),
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Write the record to the quarantine file if it matches a rule
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the record was quarantined and must not be applied
+    /// * `Ok(false)` - If the record should be processed normally
+    /// * `Err(String)` - If the quarantine file cannot be written
+    pub(crate) fn divert(&mut self, record: &TransactionRecord) -> Result<bool, String> {
+        if self.writer.is_none() {
+            return Ok(false);
+        }
+        let reason = self
+            .rules
+            .iter()
+            .find_map(|rule| rule.reason(record))
+            .or_else(|| self.velocity_reason(record));
+        match (reason, self.writer.as_mut()) {
+            (Some(reason), Some(writer)) => {
+                writer.write(record, &reason)?;
+                Ok(true)
+            }
+            _ => {
+                if let Some(velocity) = self.velocity.as_mut() {
+                    let withdrawal = record
+                        .amount
+                        .filter(|_| record.tx_type == TransactionType::Withdrawal);
+                    velocity.record(record.client, withdrawal);
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Reason for quarantining a withdrawal over the velocity limit, if any
+    fn velocity_reason(&self, record: &TransactionRecord) -> Option<String> {
+        let velocity = self.velocity.as_ref()?;
+        let amount = record
+            .amount
+            .filter(|_| record.tx_type == TransactionType::Withdrawal)?;
+        velocity
+            .check_withdrawal(record.tx, record.client, amount)
+            .err()
+            .map(|_| format!("velocity above {}", velocity.limit()))
+    }
+
+    /// Divert matching records out of a batch
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The number of records quarantined
+    /// * `Err(String)` - If the quarantine file cannot be written
+    pub(crate) fn retain_clean(
+        &mut self,
+        batch: &mut Vec<TransactionRecord>,
+    ) -> Result<u64, String> {
+        let mut kept = Vec::with_capacity(batch.len());
+        let mut quarantined = 0;
+        for record in batch.drain(..) {
+            if self.divert(&record)? {
+                quarantined += 1;
+            } else {
+                kept.push(record);
+            }
+        }
+        *batch = kept;
+        Ok(quarantined)
+    }
+
+    /// Flush the records quarantined so far to the quarantine file
+    pub(crate) fn flush(&mut self) -> Result<(), String> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush the quarantine file
+    pub(crate) fn finish(mut self) -> Result<(), String> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    fn record(tx_type: TransactionType, tx: u16, amount: Option<i64>) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client: 1,
+            tx: tx.into(),
+            amount: amount.map(Decimal::from),
+            line: None,
+            source: None,
+        }
+    }
+
+    #[rstest]
+    #[case::above(
+        record(TransactionType::Deposit, 1, Some(1001)),
+        Some("amount 1001 above 1000")
+    )]
+    #[case::equal(record(TransactionType::Withdrawal, 1, Some(1000)), None)]
+    #[case::no_amount(record(TransactionType::Dispute, 1, None), None)]
+    fn test_amount_above_rule(#[case] record: TransactionRecord, #[case] expected: Option<&str>) {
+        let rule = QuarantineRule::AmountAbove(Decimal::from(1000));
+        assert_eq!(rule.reason(&record).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_divert_writes_quarantine_file() {
+        let file = NamedTempFile::new().unwrap();
+        let options = QuarantineOptions::new(file.path())
+            .with_rule(QuarantineRule::AmountAbove(Decimal::from(1000)));
+
+        let mut quarantine = Quarantine::open(Some(&options)).unwrap();
+        let mut batch = vec![
+            record(TransactionType::Deposit, 1, Some(5000)),
+            record(TransactionType::Deposit, 2, Some(10)),
+        ];
+        assert_eq!(quarantine.retain_clean(&mut batch).unwrap(), 1);
+        quarantine.finish().unwrap();
+
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].tx, 2);
+        assert_eq!(
+            std::fs::read_to_string(file.path()).unwrap(),
+            "type,client,tx,amount,reason\ndeposit,1,1,5000,amount 5000 above 1000\n"
+        );
+    }
+
+    #[test]
+    fn test_velocity_rule_quarantines_excess_withdrawals() {
+        let file = NamedTempFile::new().unwrap();
+        let options = QuarantineOptions::new(file.path()).with_rule(QuarantineRule::Velocity(
+            VelocityLimit::new(3).with_max_withdrawals(1),
+        ));
+
+        let mut quarantine = Quarantine::open(Some(&options)).unwrap();
+        let mut batch = vec![
+            record(TransactionType::Withdrawal, 1, Some(10)),
+            record(TransactionType::Withdrawal, 2, Some(10)),
+            record(TransactionType::Deposit, 3, Some(10)),
+            record(TransactionType::Deposit, 4, Some(10)),
+            record(TransactionType::Withdrawal, 5, Some(10)),
+        ];
+        assert_eq!(quarantine.retain_clean(&mut batch).unwrap(), 1);
+        quarantine.finish().unwrap();
+
+        assert_eq!(
+            batch.iter().map(|record| record.tx).collect::<Vec<_>>(),
+            vec![1, 3, 4, 5]
+        );
+        assert_eq!(
+            std::fs::read_to_string(file.path()).unwrap(),
+            "type,client,tx,amount,reason\nwithdrawal,1,2,10,velocity above 1 withdrawals per 3 transactions\n"
+        );
+    }
+
+    #[test]
+    fn test_no_quarantine_keeps_everything() {
+        let mut quarantine = Quarantine::open(None).unwrap();
+        assert!(!quarantine
+            .divert(&record(TransactionType::Deposit, 1, Some(i64::MAX)))
+            .unwrap());
+    }
+}
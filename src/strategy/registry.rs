@@ -0,0 +1,206 @@
+//! Registry of custom processing strategies
+//!
+//! Library users can add their own `ProcessingStrategy` implementations
+//! under a name with `register_strategy`. Once registered, the name is
+//! accepted wherever a built-in strategy is (`--strategy NAME`, `bench
+//! --strategy`), and `create_strategy` builds the strategy with the
+//! registered factory, so a binary built on the library selects it through
+//! the same CLI machinery:
+//!
+//! ```no_run
+//! use rust_payments_engine::cli;
+//! use rust_payments_engine::strategy::{register_strategy, SyncProcessingStrategy};
+//!
+//! register_strategy("experimental", |settings| {
+//!     Box::new(
+//!         SyncProcessingStrategy::new()
+//!             .with_input(settings.input)
+//!             .with_engine_config(settings.engine),
+//!     )
+//! })
+//! .expect("Invalid strategy name");
+//!
+//! // Strategies must be registered before the arguments are parsed
+//! let args = cli::parse_args();
+//! ```
+//!
+//! The registry is global to the process, and strategies cannot be
+//! unregistered.
+
+use crate::core::EngineConfig;
+use crate::strategy::{BatchConfig, InputOptions, ProcessingStrategy};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Names of the built-in strategies, which cannot be registered
+pub const BUILTIN_STRATEGIES: [&str; 2] = ["sync", "async"];
+
+/// Builds a registered strategy from the settings of a run
+pub type StrategyFactory =
+    Arc<dyn Fn(StrategySettings) -> Box<dyn ProcessingStrategy> + Send + Sync>;
+
+/// Registered strategies by name
+static REGISTRY: Mutex<BTreeMap<String, StrategyFactory>> = Mutex::new(BTreeMap::new());
+
+/// Settings a registered strategy is created with, as given to
+/// `create_strategy`
+#[derive(Debug, Clone, Default)]
+pub struct StrategySettings {
+    /// Batch configuration from the async options, if given
+    pub batch: Option<BatchConfig>,
+    /// Input options
+    pub input: InputOptions,
+    /// Configuration for the transaction engine
+    pub engine: EngineConfig,
+    /// State file for the final engine snapshot, if any
+    pub save_state: Option<PathBuf>,
+}
+
+/// A strategy found in the registry
+///
+/// Only obtained from `RegisteredStrategy::lookup` (or by parsing a
+/// `StrategyType`), so its factory always exists.
+#[derive(Clone)]
+pub struct RegisteredStrategy {
+    name: String,
+    factory: StrategyFactory,
+}
+
+impl RegisteredStrategy {
+    /// Look up the strategy registered under `name`
+    pub fn lookup(name: &str) -> Option<Self> {
+        let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+        registry.get(name).map(|factory| Self {
+            name: name.to_string(),
+            factory: Arc::clone(factory),
+        })
+    }
+
+    /// Name the strategy was registered under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Create the strategy with the given settings
+    pub fn create(&self, settings: StrategySettings) -> Box<dyn ProcessingStrategy> {
+        (self.factory)(settings)
+    }
+}
+
+impl fmt::Debug for RegisteredStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RegisteredStrategy")
+            .field(&self.name)
+            .finish()
+    }
+}
+
+/// Register a custom strategy under `name`
+///
+/// # Arguments
+///
+/// * `name` - Name selecting the strategy, of lowercase ASCII letters,
+///   digits, `-` and `_`
+/// * `factory` - Builds the strategy for a run
+///
+/// # Returns
+///
+/// * `Ok(())` - If the strategy was registered
+/// * `Err(String)` - If the name is invalid, is a built-in strategy's or is
+///   already registered
+pub fn register_strategy(
+    name: &str,
+    factory: impl Fn(StrategySettings) -> Box<dyn ProcessingStrategy> + Send + Sync + 'static,
+) -> Result<(), String> {
+    let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_';
+    if name.is_empty() || !name.chars().all(valid) {
+        return Err(format!(
+            "Invalid strategy name '{}': expected lowercase letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    if BUILTIN_STRATEGIES.contains(&name) {
+        return Err(format!("Strategy '{}' is built in", name));
+    }
+
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    if registry.contains_key(name) {
+        return Err(format!("Strategy '{}' is already registered", name));
+    }
+    registry.insert(name.to_string(), Arc::new(factory));
+    Ok(())
+}
+
+/// Names of the registered strategies, in order
+pub fn registered_strategies() -> Vec<String> {
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    registry.keys().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::SyncProcessingStrategy;
+    use rstest::rstest;
+
+    fn sync_factory(settings: StrategySettings) -> Box<dyn ProcessingStrategy> {
+        Box::new(SyncProcessingStrategy::new().with_input(settings.input))
+    }
+
+    #[test]
+    fn test_register_and_lookup() {
+        register_strategy("registry-test", sync_factory).unwrap();
+
+        let strategy = RegisteredStrategy::lookup("registry-test").unwrap();
+        assert_eq!(strategy.name(), "registry-test");
+        assert!(registered_strategies().contains(&"registry-test".to_string()));
+        assert!(RegisteredStrategy::lookup("registry-missing").is_none());
+        assert_eq!(
+            register_strategy("registry-test", sync_factory).unwrap_err(),
+            "Strategy 'registry-test' is already registered"
+        );
+    }
+
+    #[test]
+    fn test_create_strategy_passes_settings() {
+        use crate::cli::StrategyType;
+        use crate::strategy::create_strategy;
+        use std::path::Path;
+
+        let seen = Arc::new(Mutex::new(None));
+        let seen_by_factory = Arc::clone(&seen);
+        register_strategy("registry-settings", move |settings| {
+            *seen_by_factory.lock().unwrap() =
+                Some((settings.batch.is_some(), settings.save_state.clone()));
+            sync_factory(settings)
+        })
+        .unwrap();
+
+        let strategy =
+            StrategyType::Custom(RegisteredStrategy::lookup("registry-settings").unwrap());
+        create_strategy(
+            strategy,
+            Some(BatchConfig::default()),
+            InputOptions::default(),
+            EngineConfig::default(),
+            Some(Path::new("state.bin")),
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            Some((true, Some(PathBuf::from("state.bin"))))
+        );
+    }
+
+    #[rstest]
+    #[case::empty("", "Invalid strategy name ''")]
+    #[case::uppercase("Gpu", "Invalid strategy name 'Gpu'")]
+    #[case::delimiter("gpu,fpga", "Invalid strategy name 'gpu,fpga'")]
+    #[case::builtin("async", "Strategy 'async' is built in")]
+    fn test_register_rejects_names(#[case] name: &str, #[case] expected: &str) {
+        let err = register_strategy(name, sync_factory).unwrap_err();
+        assert!(err.starts_with(expected), "{}", err);
+        assert!(RegisteredStrategy::lookup(name).is_none());
+    }
+}
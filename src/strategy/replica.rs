@@ -0,0 +1,247 @@
+//! Replica processing strategy for write-ahead logs
+//!
+//! This module provides a ProcessingStrategy that keeps a read-only copy of
+//! the state of another process running with `--wal`. Its input is that
+//! process's write-ahead log, which it follows like `tail -f` with a
+//! `WalReplica`, never writing to it. Account snapshots are written to the
+//! output, and the engine state to the state file, periodically, so queries
+//! (`query`, `graphql`) can be served from the replica's files without adding
+//! load to the process ingesting the records.
+//!
+//! # Snapshots
+//!
+//! Like in follow mode, a snapshot is only written when records were applied
+//! since the previous one, and a final snapshot is always written when the
+//! replica stops. With a state file, every snapshot also replaces the state
+//! file.
+//!
+//! # Configuration
+//!
+//! The replica must be run with the same engine configuration (risk rules,
+//! fees, dispute expiry, ...) as the process writing the log, or its state
+//! diverges. Records rejected by that process are rejected again silently:
+//! the replica logs no per-record errors.
+
+use crate::core::{save_state, Engine, EngineConfig, WalReplica};
+use crate::io::{is_object_url, AccountSink};
+use crate::strategy::{
+    check_inputs, AccountTotals, Conservation, FollowOptions, ProcessingStrategy, RunSummary,
+};
+use crate::types::EngineError;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Instant;
+
+/// Processing strategy that replicates the state of a write-ahead log
+///
+/// # Examples
+///
+/// ```no_run
+/// use rust_payments_engine::strategy::{FollowOptions, ProcessingStrategy, ReplicaProcessingStrategy};
+/// use std::path::Path;
+/// use std::io;
+///
+/// let strategy = ReplicaProcessingStrategy::new(FollowOptions::default())
+///     .with_save_state("replica.bin");
+/// let mut output = io::stdout();
+///
+/// // Runs until the process is terminated, saving the state every 5 seconds
+/// strategy.process(Path::new("engine.wal"), &mut output)
+///     .expect("Replication failed");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReplicaProcessingStrategy {
+    /// Timing of polls and snapshots
+    follow: FollowOptions,
+    /// Configuration for the transaction engine, as used by the log's writer
+    engine_config: EngineConfig,
+    /// State file replaced with every snapshot
+    save_state: Option<PathBuf>,
+}
+
+impl ReplicaProcessingStrategy {
+    /// Create a new ReplicaProcessingStrategy with the given timing options
+    pub fn new(follow: FollowOptions) -> Self {
+        Self {
+            follow,
+            ..Self::default()
+        }
+    }
+
+    /// Set the transaction engine configuration
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Replace a state file with every snapshot (`--save-state`)
+    pub fn with_save_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_state = Some(path.into());
+        self
+    }
+
+    /// Write the replica's accounts to the output, and its state to the
+    /// state file if any
+    fn snapshot(
+        &self,
+        replica: &WalReplica,
+        output: &mut dyn AccountSink,
+    ) -> Result<(), EngineError> {
+        if let Some(path) = &self.save_state {
+            save_state(path, &replica.engine().snapshot())?;
+        }
+        output.write_accounts(&Engine::get_accounts(replica.engine()))?;
+        Ok(())
+    }
+}
+
+impl ProcessingStrategy for ReplicaProcessingStrategy {
+    /// Follow a single write-ahead log, writing snapshots as records arrive
+    ///
+    /// # Arguments
+    ///
+    /// * `input_paths` - Exactly one path, to a write-ahead log
+    /// * `output` - Sink receiving every snapshot
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RunSummary)` once the idle timeout expires, counting the records
+    ///   replicated
+    /// * `Err(EngineError)` if the input is not a single local log, the log is
+    ///   corrupted or truncated, or a snapshot cannot be written
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        let [wal_path] = input_paths else {
+            return Err(EngineError::Other(
+                "Replica mode requires exactly one write-ahead log".to_string(),
+            ));
+        };
+        if is_object_url(&wal_path.to_string_lossy()) {
+            return Err(EngineError::Other(
+                "Replica mode requires a local write-ahead log".to_string(),
+            ));
+        }
+        check_inputs(input_paths)?;
+
+        let mut replica = WalReplica::open(wal_path, self.engine_config.clone())?;
+        let mut last_append = Instant::now();
+        let mut last_snapshot = Instant::now();
+        let mut changed = replica.applied() > 0;
+
+        loop {
+            let idle = replica.catch_up()? == 0;
+            if !idle {
+                last_append = Instant::now();
+                changed = true;
+            }
+
+            if changed && last_snapshot.elapsed() >= self.follow.snapshot_interval {
+                self.snapshot(&replica, output)?;
+                last_snapshot = Instant::now();
+                changed = false;
+            }
+
+            if idle {
+                if let Some(idle_timeout) = self.follow.idle_timeout {
+                    if last_append.elapsed() >= idle_timeout {
+                        break;
+                    }
+                }
+                thread::sleep(self.follow.poll_interval);
+            }
+        }
+
+        self.snapshot(&replica, output)?;
+        let accounts = Engine::get_accounts(replica.engine());
+        Ok(RunSummary {
+            records_read: replica.applied(),
+            accounts: AccountTotals::of(&accounts),
+            conservation: Some(Conservation::check(replica.engine().flows(), &accounts)),
+            ..RunSummary::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{load_state, DurableEngine};
+    use crate::types::{TransactionRecord, TransactionType};
+    use rust_decimal::Decimal;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn deposit(tx: u16, amount: i64) -> TransactionRecord {
+        TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            tx: tx.into(),
+            amount: Some(Decimal::from(amount)),
+            line: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_replica_follows_primary() {
+        let dir = TempDir::new().unwrap();
+        let wal = dir.path().join("engine.wal");
+        let state = dir.path().join("replica.bin");
+        let mut primary = DurableEngine::open(&wal, EngineConfig::default()).unwrap();
+        primary.process(deposit(1, 10)).unwrap().unwrap();
+
+        let appender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            primary.process(deposit(2, 5)).unwrap().unwrap();
+        });
+
+        let strategy = ReplicaProcessingStrategy::new(
+            FollowOptions::default()
+                .with_poll_interval(Duration::from_millis(10))
+                .with_snapshot_interval(Duration::ZERO)
+                .with_idle_timeout(Some(Duration::from_millis(500))),
+        )
+        .with_save_state(&state);
+        let mut output = Vec::new();
+        let summary = strategy.process(&wal, &mut output).unwrap();
+        appender.join().unwrap();
+
+        assert_eq!(summary.records_read, 2);
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.starts_with("client,available,held,total,locked\n1,10.0000,"),
+            "{}",
+            output
+        );
+        assert!(
+            output.ends_with("1,15.0000,0.0000,15.0000,false\n"),
+            "{}",
+            output
+        );
+        assert_eq!(
+            load_state(&state).unwrap().account(1).unwrap().total,
+            Decimal::from(15)
+        );
+        // The replica never writes to the log
+        assert_eq!(
+            std::fs::read_to_string(&wal).unwrap(),
+            "deposit,1,1,10\ndeposit,1,2,5\n"
+        );
+    }
+
+    #[test]
+    fn test_replica_requires_one_log() {
+        let strategy = ReplicaProcessingStrategy::default();
+        let mut output = Vec::new();
+        let err = strategy
+            .process_files(
+                &[PathBuf::from("a.wal"), PathBuf::from("b.wal")],
+                &mut output,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("exactly one write-ahead log"));
+    }
+}
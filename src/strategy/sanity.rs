@@ -0,0 +1,336 @@
+//! Sanity warnings about suspicious records
+//!
+//! Sanity checks flag values that are valid but unusual, such as an amount
+//! far above the others. Unlike quarantine rules they never hold a record
+//! back: a flagged record is written to the warnings file along with the
+//! warning and processed as usual, so it can be reviewed after the run. Like
+//! the quarantine, this runs as a stage between the reader and the engine in
+//! every strategy, on the records that reach the engine.
+//!
+//! The percentile check compares an amount with the earlier amounts of the
+//! same transaction type. Those are counted in logarithmic buckets about 12%
+//! wide rather than kept, so the percentile is an estimate, and it only
+//! applies once `PERCENTILE_MIN_AMOUNTS` earlier amounts were seen.
+
+use crate::io::WarningWriter;
+use crate::types::{ClientId, TransactionRecord, TransactionType};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+
+/// Earlier amounts of a transaction type the percentile check needs before
+/// it flags any
+pub const PERCENTILE_MIN_AMOUNTS: u64 = 1000;
+
+/// Buckets per power of ten of the amount distributions
+const BUCKETS_PER_DECADE: f64 = 20.0;
+
+/// Decimal places an amount can have without being truncated in the output
+const AMOUNT_DECIMAL_PLACES: u32 = 4;
+
+/// Check flagging suspicious records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanityCheck {
+    /// Amounts with more than four decimal places, which the account output
+    /// truncates
+    ExcessPrecision,
+    /// Deposits and withdrawals above this percentile (strictly between 0 and
+    /// 100) of the earlier amounts of their type
+    AbovePercentile(Decimal),
+    /// Withdrawals by a client with no earlier transaction
+    NewClientWithdrawal,
+}
+
+impl fmt::Display for SanityCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanityCheck::ExcessPrecision => write!(f, "excess precision"),
+            SanityCheck::AbovePercentile(percentile) => {
+                write!(f, "amount above percentile {}", percentile)
+            }
+            SanityCheck::NewClientWithdrawal => write!(f, "withdrawal by a new client"),
+        }
+    }
+}
+
+/// Where to write sanity warnings and which checks to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanityOptions {
+    /// Warnings file, created or truncated at the start of each run
+    pub path: PathBuf,
+    /// Checks to run on every record; a record gets a warning for each check
+    /// it fails
+    pub checks: Vec<SanityCheck>,
+}
+
+impl SanityOptions {
+    /// Create sanity options writing to `path`, with no checks yet
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            checks: Vec::new(),
+        }
+    }
+
+    /// Add a check flagging suspicious records
+    pub fn with_check(mut self, check: SanityCheck) -> Self {
+        self.checks.push(check);
+        self
+    }
+}
+
+/// Counts of amounts in logarithmic buckets
+#[derive(Debug, Default)]
+struct AmountDistribution {
+    buckets: BTreeMap<i32, u64>,
+    count: u64,
+}
+
+impl AmountDistribution {
+    /// Bucket of an amount; zero falls below every other
+    fn bucket(amount: Decimal) -> i32 {
+        let log = amount.to_f64().unwrap_or_default().log10();
+        (log * BUCKETS_PER_DECADE).floor() as i32
+    }
+
+    /// Whether `amount` is above the `percentile` of the amounts counted so
+    /// far, i.e. at most `100 - percentile` percent of them are in its bucket
+    /// or above
+    fn is_above(&self, amount: Decimal, percentile: Decimal) -> bool {
+        if self.count < PERCENTILE_MIN_AMOUNTS {
+            return false;
+        }
+        let at_or_above: u64 = self
+            .buckets
+            .range(Self::bucket(amount)..)
+            .map(|(_, count)| count)
+            .sum();
+        Decimal::from(at_or_above) * Decimal::ONE_HUNDRED
+            <= (Decimal::ONE_HUNDRED - percentile) * Decimal::from(self.count)
+    }
+
+    fn record(&mut self, amount: Decimal) {
+        *self.buckets.entry(Self::bucket(amount)).or_default() += 1;
+        self.count += 1;
+    }
+}
+
+/// Sanity warning stage of a processing run
+#[derive(Default)]
+pub(crate) struct SanityWarnings {
+    checks: Vec<SanityCheck>,
+    writer: Option<WarningWriter<File>>,
+    deposits: AmountDistribution,
+    withdrawals: AmountDistribution,
+    /// Clients of the records checked so far
+    clients: HashSet<ClientId>,
+}
+
+impl SanityWarnings {
+    /// Open the warnings file, if sanity checks are configured
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SanityWarnings)` - A stage that flags suspicious records, or none
+    /// * `Err(String)` - If the warnings file cannot be created
+    pub(crate) fn open(options: Option<&SanityOptions>) -> Result<Self, String> {
+        match options {
+            Some(options) => Ok(Self {
+                checks: options.checks.clone(),
+                writer: Some(WarningWriter::create(&options.path)?),
+                ..Self::default()
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Write a warning for every check the record fails
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The number of warnings written
+    /// * `Err(String)` - If the warnings file cannot be written
+    pub(crate) fn check(&mut self, record: &TransactionRecord) -> Result<u64, String> {
+        if self.writer.is_none() {
+            return Ok(0);
+        }
+        let warnings: Vec<String> = self
+            .checks
+            .iter()
+            .filter_map(|check| self.warning(check, record))
+            .collect();
+        if let Some(writer) = self.writer.as_mut() {
+            for warning in &warnings {
+                writer.write(record, warning)?;
+            }
+        }
+
+        match (record.tx_type, record.amount) {
+            (TransactionType::Deposit, Some(amount)) => self.deposits.record(amount),
+            (TransactionType::Withdrawal, Some(amount)) => self.withdrawals.record(amount),
+            _ => {}
+        }
+        self.clients.insert(record.client);
+        Ok(warnings.len() as u64)
+    }
+
+    /// Warning about the record if it fails the check
+    fn warning(&self, check: &SanityCheck, record: &TransactionRecord) -> Option<String> {
+        match check {
+            SanityCheck::ExcessPrecision => record
+                .amount
+                .filter(|amount| amount.normalize().scale() > AMOUNT_DECIMAL_PLACES)
+                .map(|amount| {
+                    format!(
+                        "amount {} has more than {} decimal places",
+                        amount, AMOUNT_DECIMAL_PLACES
+                    )
+                }),
+            SanityCheck::AbovePercentile(percentile) => {
+                let (distribution, earlier) = match record.tx_type {
+                    TransactionType::Deposit => (&self.deposits, "deposits"),
+                    TransactionType::Withdrawal => (&self.withdrawals, "withdrawals"),
+                    _ => return None,
+                };
+                record
+                    .amount
+                    .filter(|amount| distribution.is_above(*amount, *percentile))
+                    .map(|amount| {
+                        format!(
+                            "amount {} above percentile {} of earlier {}",
+                            amount, percentile, earlier
+                        )
+                    })
+            }
+            SanityCheck::NewClientWithdrawal => (record.tx_type == TransactionType::Withdrawal
+                && !self.clients.contains(&record.client))
+            .then(|| {
+                format!(
+                    "withdrawal by client {} with no earlier transaction",
+                    record.client
+                )
+            }),
+        }
+    }
+
+    /// Write warnings for the records of a batch, in order
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(u64)` - The number of warnings written
+    /// * `Err(String)` - If the warnings file cannot be written
+    pub(crate) fn check_batch(&mut self, batch: &[TransactionRecord]) -> Result<u64, String> {
+        let mut warnings = 0;
+        for record in batch {
+            warnings += self.check(record)?;
+        }
+        Ok(warnings)
+    }
+
+    /// Flush the warnings written so far to the warnings file
+    pub(crate) fn flush(&mut self) -> Result<(), String> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush the warnings file
+    pub(crate) fn finish(mut self) -> Result<(), String> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    fn record(tx_type: TransactionType, client: ClientId, amount: Decimal) -> TransactionRecord {
+        TransactionRecord {
+            tx_type,
+            client,
+            tx: 1,
+            amount: Some(amount),
+            line: None,
+            source: None,
+        }
+    }
+
+    fn stage(check: SanityCheck) -> (SanityWarnings, NamedTempFile) {
+        let file = NamedTempFile::new().unwrap();
+        let options = SanityOptions::new(file.path()).with_check(check);
+        (SanityWarnings::open(Some(&options)).unwrap(), file)
+    }
+
+    #[rstest]
+    #[case::four_places(Decimal::new(12345, 4), false)]
+    #[case::five_places(Decimal::new(123456, 5), true)]
+    #[case::trailing_zeros(Decimal::new(1000000, 6), false)]
+    fn test_excess_precision(#[case] amount: Decimal, #[case] flagged: bool) {
+        let (mut warnings, _file) = stage(SanityCheck::ExcessPrecision);
+        let count = warnings
+            .check(&record(TransactionType::Deposit, 1, amount))
+            .unwrap();
+        assert_eq!(count, u64::from(flagged));
+    }
+
+    #[test]
+    fn test_above_percentile_after_enough_amounts() {
+        let (mut warnings, file) = stage(SanityCheck::AbovePercentile(Decimal::new(999, 1)));
+        let large = record(TransactionType::Deposit, 1, Decimal::from(1_000_000));
+
+        // Not enough earlier amounts to estimate the percentile yet
+        assert_eq!(warnings.check(&large).unwrap(), 0);
+        for i in 1..PERCENTILE_MIN_AMOUNTS {
+            let amount = Decimal::from(i % 100 + 1);
+            warnings
+                .check(&record(TransactionType::Deposit, 1, amount))
+                .unwrap();
+        }
+
+        assert_eq!(warnings.check(&large).unwrap(), 1);
+        let typical = record(TransactionType::Deposit, 1, Decimal::from(50));
+        assert_eq!(warnings.check(&typical).unwrap(), 0);
+        // Withdrawals are compared with withdrawals only
+        let withdrawal = record(TransactionType::Withdrawal, 1, Decimal::from(1_000_000));
+        assert_eq!(warnings.check(&withdrawal).unwrap(), 0);
+        warnings.finish().unwrap();
+
+        let written = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(written.lines().count(), 2, "{}", written);
+        assert!(written.contains(
+            "deposit,1,1,1000000,amount 1000000 above percentile 99.9 of earlier deposits,,"
+        ));
+    }
+
+    #[test]
+    fn test_new_client_withdrawal() {
+        let (mut warnings, file) = stage(SanityCheck::NewClientWithdrawal);
+        let batch = [
+            record(TransactionType::Withdrawal, 1, Decimal::ONE),
+            record(TransactionType::Deposit, 2, Decimal::ONE),
+            record(TransactionType::Withdrawal, 2, Decimal::ONE),
+            record(TransactionType::Withdrawal, 1, Decimal::ONE),
+        ];
+        assert_eq!(warnings.check_batch(&batch).unwrap(), 1);
+        warnings.finish().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(file.path()).unwrap(),
+            "type,client,tx,amount,warning,source,line\n\
+             withdrawal,1,1,1,withdrawal by client 1 with no earlier transaction,,\n"
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_stage_flags_nothing() {
+        let mut warnings = SanityWarnings::open(None).unwrap();
+        let record = record(TransactionType::Withdrawal, 1, Decimal::new(1, 6));
+        assert_eq!(warnings.check(&record).unwrap(), 0);
+    }
+}
@@ -0,0 +1,387 @@
+//! Sharded processing strategy
+//!
+//! This module provides `ShardedProcessingStrategy`, which splits the input
+//! by client into N shards, each processed on its own thread by its own
+//! `TransactionEngine` with its own account and transaction stores, and
+//! merges their final accounts.
+//!
+//! # Design
+//!
+//! One thread reads and parses the input, sending each record to the shard
+//! of its client (`shard_of`) in batches over a bounded channel. A client's
+//! records all go to the same shard, in input order, so every shard applies
+//! them exactly as a single engine would. Records that fail to parse go to
+//! the first shard, which logs and counts them.
+//!
+//! Unlike the async strategy, the shards share no maps and no locks, so they
+//! scale with the number of cores instead of contending on shared state. Once
+//! the input is read, the shards' accounts are merged in client order and
+//! their summaries added up.
+//!
+//! # Limitations
+//!
+//! - Transaction IDs are only checked for duplicates within a shard, so
+//!   sharding requires the per-client duplicate policy: with it a client
+//!   reusing another client's ID gets a transaction of its own in any engine,
+//!   and the shards agree with a single engine. The other policies are
+//!   rejected.
+//! - Stages writing files (the quarantine, warnings, journal, balance
+//!   history, cutoff snapshots and dead letters) and the analytics are not
+//!   sharded, and are rejected.
+//! - Dispute expiry is rejected too: it counts records across all clients,
+//!   which no shard sees.
+
+use crate::core::{DuplicateTxPolicy, Engine, EngineConfig, MoneyFlows, TransactionEngine};
+use crate::io::AccountSink;
+use crate::strategy::{
+    check_inputs, input_source, open_records, AccountTotals, Conservation, InputOptions,
+    ProcessingStrategy, RecordStages, RunSummary,
+};
+use crate::types::{Account, ClientId, EngineError, TransactionRecord};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+/// Records sent to a shard at a time
+const SHARD_BATCH_SIZE: usize = 1024;
+
+/// Batches queued for a shard before the reader waits for it
+const SHARD_QUEUE_BATCHES: usize = 16;
+
+/// A batch of records read for one shard
+type ShardBatch = Vec<Result<TransactionRecord, String>>;
+
+/// What a shard returns once the input is read
+struct ShardResult {
+    summary: RunSummary,
+    accounts: Vec<Account>,
+    flows: MoneyFlows,
+}
+
+/// Shard of a client among `shards` shards
+///
+/// Client IDs are hashed first, so consecutive IDs are spread over the
+/// shards.
+// `ClientId` is already `u64` with the `client-id-u64` feature
+#[allow(clippy::useless_conversion)]
+pub fn shard_of(client: ClientId, shards: usize) -> usize {
+    let hash = u64::from(client).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    ((hash >> 32) % shards.max(1) as u64) as usize
+}
+
+/// Processing strategy splitting the input into per-client shards
+///
+/// The engine configuration must use `DuplicateTxPolicy::PerClient`, as
+/// transaction IDs are only checked within a shard.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rust_payments_engine::core::{DuplicateTxPolicy, EngineConfig};
+/// use rust_payments_engine::strategy::{ProcessingStrategy, ShardedProcessingStrategy};
+/// use std::path::Path;
+/// use std::io;
+///
+/// let strategy = ShardedProcessingStrategy::new(8).with_engine_config(
+///     EngineConfig::new().with_duplicate_tx_policy(DuplicateTxPolicy::PerClient),
+/// );
+/// strategy.process(Path::new("transactions.csv"), &mut io::stdout())
+///     .expect("Processing failed");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShardedProcessingStrategy {
+    /// Number of shards, each processed on its own thread
+    shards: usize,
+    /// Options for reading the input file
+    input: InputOptions,
+    /// Configuration for the shards' transaction engines
+    engine_config: EngineConfig,
+}
+
+impl ShardedProcessingStrategy {
+    /// Create a strategy processing CSV input in `shards` shards (at least 1)
+    pub fn new(shards: usize) -> Self {
+        Self {
+            shards: shards.max(1),
+            input: InputOptions::default(),
+            engine_config: EngineConfig::default(),
+        }
+    }
+
+    /// Set the input options, or just the format of the input file
+    pub fn with_input(mut self, input: impl Into<InputOptions>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Set the transaction engine configuration of every shard
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Reject the options that cannot be sharded, and duplicate policies
+    /// other than per-client
+    fn check_options(&self) -> Result<(), String> {
+        let unsharded = [
+            (
+                "dispute expiry",
+                self.engine_config.dispute_expiry.is_some(),
+            ),
+            ("quarantine", self.input.quarantine.is_some()),
+            ("sanity warnings", self.input.sanity.is_some()),
+            ("journal", self.input.journal.is_some()),
+            ("trial balance", self.input.trial_balance.is_some()),
+            ("balance changes", self.input.changes.is_some()),
+            ("balance history", self.input.balance_history.is_some()),
+            ("cutoff snapshots", self.input.cutoffs.is_some()),
+            ("standing orders", !self.input.standing_orders.is_empty()),
+            ("dead letters", self.input.dead_letter.is_some()),
+            ("analytics", self.input.analytics.is_some()),
+            ("fees", self.engine_config.fees.is_some()),
+        ];
+        if let Some((stage, _)) = unsharded.iter().find(|(_, enabled)| *enabled) {
+            return Err(format!("Sharded processing does not support the {}", stage));
+        }
+        if self.engine_config.duplicate_tx_policy != DuplicateTxPolicy::PerClient {
+            return Err(
+                "Sharded processing requires per-client transaction IDs (--duplicate-tx per-client)"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Read the input, sending every record to the shard of its client
+    ///
+    /// Stops early if a shard has stopped on a fatal error, which is reported
+    /// when the shard is joined.
+    fn distribute(
+        &self,
+        input_paths: &[PathBuf],
+        shards: &[SyncSender<ShardBatch>],
+    ) -> Result<(), String> {
+        let mut batches: Vec<ShardBatch> = vec![Vec::new(); shards.len()];
+        for input_path in input_paths {
+            for result in open_records(
+                input_path,
+                &self.input,
+                input_source(input_paths, input_path),
+            )? {
+                let shard = match &result {
+                    Ok(record) => shard_of(record.client, shards.len()),
+                    Err(_) => 0,
+                };
+                batches[shard].push(result);
+                if batches[shard].len() == SHARD_BATCH_SIZE {
+                    let batch = std::mem::replace(
+                        &mut batches[shard],
+                        Vec::with_capacity(SHARD_BATCH_SIZE),
+                    );
+                    if shards[shard].send(batch).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        for (shard, batch) in shards.iter().zip(batches) {
+            if !batch.is_empty() && shard.send(batch).is_err() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply the records sent to one shard through its own stages and engine
+fn run_shard(
+    input: &InputOptions,
+    engine_config: EngineConfig,
+    batches: Receiver<ShardBatch>,
+) -> Result<ShardResult, String> {
+    let mut engine = TransactionEngine::with_config(engine_config);
+    let mut stages = RecordStages::open(input)?;
+    for batch in batches {
+        for result in batch {
+            stages.apply(&mut engine, result)?;
+        }
+    }
+    Ok(ShardResult {
+        summary: stages.finish()?,
+        accounts: Engine::get_accounts(&engine),
+        flows: engine.flows(),
+    })
+}
+
+impl ProcessingStrategy for ShardedProcessingStrategy {
+    /// Process transactions from input files in shards and write the merged
+    /// results to output
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RunSummary)` with the counts of all shards added up
+    /// * `Err(EngineError)` if an input cannot be read, a shard failed
+    ///   fatally, or the input options include a stage that is not sharded
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        check_inputs(input_paths)?;
+        self.check_options()?;
+        let engine_config = self.input.engine_config(&self.engine_config);
+
+        let (read, results) = thread::scope(|scope| {
+            let (senders, workers): (Vec<_>, Vec<_>) = (0..self.shards)
+                .map(|shard| {
+                    let (sender, receiver) = sync_channel(SHARD_QUEUE_BATCHES);
+                    let (input, engine_config) = (&self.input, engine_config.clone());
+                    let worker = thread::Builder::new()
+                        .name(format!("shard-{}", shard))
+                        .spawn_scoped(scope, move || run_shard(input, engine_config, receiver))
+                        .map_err(|e| EngineError::io("Failed to start a shard thread", e));
+                    (sender, worker)
+                })
+                .unzip();
+            // Closing the channels ends the shards
+            let read = self.distribute(input_paths, &senders);
+            drop(senders);
+            let results: Vec<_> = workers
+                .into_iter()
+                .map(|worker| {
+                    worker.map(|worker| {
+                        worker
+                            .join()
+                            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    })
+                })
+                .collect();
+            (read, results)
+        });
+
+        let mut summary = RunSummary::default();
+        let mut accounts = Vec::new();
+        let mut flows = MoneyFlows::default();
+        for result in results {
+            let shard = result??;
+            summary.add_counts(&shard.summary);
+            accounts.extend(shard.accounts);
+            flows.add(&shard.flows);
+        }
+        read?;
+
+        // Write the merged account states to the output sink
+        accounts.sort_by_key(|account| account.client);
+        summary.accounts = AccountTotals::of(&accounts);
+        summary.conservation = Some(Conservation::check(flows, &accounts));
+        output.write_accounts(&accounts)?;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::SyncProcessingStrategy;
+    use rstest::rstest;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn per_client() -> EngineConfig {
+        EngineConfig::new().with_duplicate_tx_policy(DuplicateTxPolicy::PerClient)
+    }
+
+    fn create_temp_csv(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_shard_of_spreads_clients() {
+        let mut counts = [0; 4];
+        for client in 0..1000 {
+            counts[shard_of(client, 4)] += 1;
+        }
+        assert!(counts.iter().all(|&count| count > 200), "{:?}", counts);
+        assert_eq!(shard_of(7, 1), 0);
+    }
+
+    #[rstest]
+    #[case::one_shard(1)]
+    #[case::more_shards_than_clients(8)]
+    fn test_sharded_output_matches_sync(#[case] shards: usize) {
+        let file = create_temp_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,10.0\n\
+             deposit,2,2,20.0\n\
+             deposit,3,3,30.0\n\
+             withdrawal,1,4,5.0\n\
+             dispute,2,2,\n\
+             chargeback,2,2,\n\
+             deposit,2,5,1.0\n\
+             withdrawal,3,6,50.0\n\
+             deposit,4,7,not-a-number\n\
+             resolve,3,3,\n\
+             deposit,4,1,7.0\n\
+             deposit,1,1,3.0\n\
+             dispute,4,1,\n\
+             deposit,5,2,2.0\n\
+             chargeback,5,2,\n",
+        );
+
+        let mut expected = Vec::new();
+        let expected_summary = SyncProcessingStrategy::new()
+            .with_engine_config(per_client())
+            .process(file.path(), &mut expected)
+            .unwrap();
+        let mut output = Vec::new();
+        let summary = ShardedProcessingStrategy::new(shards)
+            .with_engine_config(per_client())
+            .process(file.path(), &mut output)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            String::from_utf8(expected).unwrap()
+        );
+        assert_eq!(summary, expected_summary);
+        assert_eq!(summary.records_read, 15);
+        assert_eq!(summary.parse_errors, 1);
+        assert!(summary.conservation.unwrap().is_conserved());
+    }
+
+    #[test]
+    fn test_sharded_rejects_unsharded_stages() {
+        let file = create_temp_csv("type,client,tx,amount\n");
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = InputOptions::default().with_journal(dir.path().join("journal.csv"));
+
+        let err = ShardedProcessingStrategy::new(2)
+            .with_input(input)
+            .with_engine_config(per_client())
+            .process(file.path(), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Sharded processing does not support the journal"
+        );
+    }
+
+    #[rstest]
+    #[case::reject(DuplicateTxPolicy::Reject)]
+    #[case::ignore(DuplicateTxPolicy::Ignore)]
+    fn test_sharded_requires_per_client_ids(#[case] policy: DuplicateTxPolicy) {
+        let file = create_temp_csv("type,client,tx,amount\n");
+
+        let err = ShardedProcessingStrategy::new(2)
+            .with_engine_config(EngineConfig::new().with_duplicate_tx_policy(policy))
+            .process(file.path(), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Sharded processing requires per-client transaction IDs (--duplicate-tx per-client)"
+        );
+    }
+}
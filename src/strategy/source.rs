@@ -0,0 +1,238 @@
+//! Opening the record sources of an input
+//!
+//! Every strategy reads its inputs through the sources opened here: blocking
+//! strategies through `open_records`, the async strategy through
+//! `open_batches`. A format is added by giving it a `RecordSource` in
+//! `open_records`; `open_batches` reads it in batches like any other blocking
+//! source unless it has an `AsyncRecordSource` of its own.
+//!
+//! When a run reads several inputs, each is opened with its name as the
+//! source: its records carry it (`TransactionRecord::source`) and its
+//! failures are prefixed with it, so a rejected record can be traced back to
+//! the file it came from.
+
+use crate::cli::InputFormat;
+use crate::io::{
+    is_object_url, AsyncReader, AsyncRecordSource, BlockingBatches, RecordBatch, RecordSource,
+};
+use crate::strategy::InputOptions;
+use crate::types::TransactionRecord;
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Source to tag the records of `input_path` with: its name if it is one of
+/// several inputs, `None` for a single input
+pub(crate) fn input_source(input_paths: &[PathBuf], input_path: &Path) -> Option<Arc<str>> {
+    (input_paths.len() > 1).then(|| Arc::from(input_path.display().to_string()))
+}
+
+/// Open a blocking record source for the given input options
+///
+/// Records are passed through `InputOptions::check`; rejected ones are yielded
+/// as errors. With a `source`, records are tagged with it and errors prefixed
+/// with it.
+///
+/// # Returns
+///
+/// The input may be a local file or an object store URL.
+///
+/// * `Ok(Box<dyn RecordSource>)` if the input was opened successfully
+/// * `Err(String)` if the input could not be opened, its header is invalid, or
+///   the format (or object store support) is not compiled in
+pub(crate) fn open_records(
+    input_path: &Path,
+    input: &InputOptions,
+    source: Option<Arc<str>>,
+) -> Result<Box<dyn RecordSource>, String> {
+    let file = crate::io::open_input(input_path)?;
+    let records: Box<dyn RecordSource> = match input.format {
+        #[cfg(feature = "fast-csv")]
+        InputFormat::Csv if input.fast_csv => Box::new(crate::io::FastCsvReader::with_dialect(
+            file,
+            input.csv_dialect.clone(),
+        )?),
+        #[cfg(not(feature = "fast-csv"))]
+        InputFormat::Csv if input.fast_csv => {
+            return Err(
+                "The fast CSV reader requires building with the 'fast-csv' feature".to_string(),
+            )
+        }
+        InputFormat::Csv => Box::new(crate::io::SyncReader::with_dialect(
+            file,
+            input.csv_dialect.clone(),
+        )?),
+        #[cfg(feature = "avro")]
+        InputFormat::Avro => Box::new(crate::io::AvroReader::new(std::io::BufReader::new(file))?),
+        #[cfg(not(feature = "avro"))]
+        InputFormat::Avro => {
+            return Err("Avro input requires building with the 'avro' feature".to_string())
+        }
+    };
+
+    let records: Box<dyn RecordSource> = if input.checks_records() {
+        let input = input.clone();
+        Box::new(records.map(move |record| record.and_then(|r| input.check(r))))
+    } else {
+        records
+    };
+
+    match source {
+        Some(source) => Ok(Box::new(
+            records.map(move |record| tag_record(&source, record)),
+        )),
+        None => Ok(records),
+    }
+}
+
+/// Tag a record with its source, or prefix its error with it
+fn tag_record(
+    source: &Arc<str>,
+    record: Result<TransactionRecord, String>,
+) -> Result<TransactionRecord, String> {
+    match record {
+        Ok(record) => Ok(record.with_source(Arc::clone(source))),
+        Err(e) => Err(format!("{}: {}", source, e)),
+    }
+}
+
+/// Open a source of record batches for the given input options
+///
+/// Local CSV files are streamed by the `AsyncReader`. Objects, CSV for the
+/// fast reader and the other formats are read from their blocking source.
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn AsyncRecordSource>)` if the input was opened successfully
+/// * `Err(String)` as for `open_records`
+pub(crate) async fn open_batches(
+    input_path: &Path,
+    input: &InputOptions,
+    source: Option<Arc<str>>,
+) -> Result<Box<dyn AsyncRecordSource>, String> {
+    match input.format {
+        InputFormat::Csv if !input.fast_csv && !is_object_url(&input_path.to_string_lossy()) => {
+            let file = tokio::fs::File::open(input_path)
+                .await
+                .map_err(|e| format!("Failed to open file '{}': {}", input_path.display(), e))?;
+
+            // Wrap tokio file in a compatibility layer for csv-async
+            let compat_file = tokio_util::compat::TokioAsyncReadCompatExt::compat(file);
+
+            let mut reader = AsyncReader::with_dialect(compat_file, input.csv_dialect.clone());
+            reader.read_header().await?;
+
+            if input.checks_records() || source.is_some() {
+                Ok(Box::new(CheckedBatches {
+                    batches: Box::new(reader),
+                    input: input.checks_records().then(|| input.clone()),
+                    source,
+                }))
+            } else {
+                Ok(Box::new(reader))
+            }
+        }
+        _ => Ok(Box::new(BlockingBatches::new(open_records(
+            input_path, input, source,
+        )?))),
+    }
+}
+
+/// Passes the batches of an async source through `InputOptions::check`,
+/// turning rejected records into failures, and tags them with their source
+struct CheckedBatches {
+    batches: Box<dyn AsyncRecordSource>,
+    /// Options to check records with, `None` to keep them all
+    input: Option<InputOptions>,
+    source: Option<Arc<str>>,
+}
+
+impl AsyncRecordSource for CheckedBatches {
+    fn read_batch(&mut self, batch_size: usize) -> BoxFuture<'_, RecordBatch> {
+        Box::pin(async move {
+            let (batch, mut failures) = self.batches.read_batch(batch_size).await;
+            let mut records = Vec::with_capacity(batch.len());
+            for record in batch {
+                let checked = match &self.input {
+                    Some(input) => input.check(record),
+                    None => Ok(record),
+                };
+                match checked {
+                    Ok(record) => records.push(record),
+                    Err(e) => failures.push(e),
+                }
+            }
+
+            if let Some(source) = &self.source {
+                for record in &mut records {
+                    record.source = Some(Arc::clone(source));
+                }
+                for e in &mut failures {
+                    *e = format!("{}: {}", source, e);
+                }
+            }
+            (records, failures)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const INPUT: &str = "type,client,tx,amount\n\
+                         deposit,1,1,1.0\n\
+                         deposit,x,2,1.0\n";
+
+    fn input_file() -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(INPUT.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_input_source_names_one_of_several_inputs() {
+        let inputs = [PathBuf::from("day-1.csv"), PathBuf::from("day-2.csv")];
+        assert_eq!(
+            input_source(&inputs, &inputs[1]).as_deref(),
+            Some("day-2.csv")
+        );
+        assert_eq!(input_source(&inputs[..1], &inputs[0]), None);
+    }
+
+    #[test]
+    fn test_open_records_tags_source() {
+        let file = input_file();
+        let source = Some(Arc::from("day-2.csv"));
+        let mut records = open_records(file.path(), &InputOptions::default(), source).unwrap();
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.source.as_deref(), Some("day-2.csv"));
+        assert_eq!(record.line, Some(2));
+        let err = records.next().unwrap().unwrap_err();
+        assert!(err.starts_with("day-2.csv: Line 3: "), "{}", err);
+
+        let mut records = open_records(file.path(), &InputOptions::default(), None).unwrap();
+        assert_eq!(records.next().unwrap().unwrap().source, None);
+    }
+
+    #[tokio::test]
+    async fn test_open_batches_tags_source() {
+        let file = input_file();
+        let source = Some(Arc::from("day-2.csv"));
+        let mut batches = open_batches(file.path(), &InputOptions::default(), source)
+            .await
+            .unwrap();
+
+        let (records, failures) = batches.read_batch(10).await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].source.as_deref(), Some("day-2.csv"));
+        assert_eq!(failures.len(), 1);
+        assert!(
+            failures[0].starts_with("day-2.csv: Line 3: "),
+            "{}",
+            failures[0]
+        );
+    }
+}
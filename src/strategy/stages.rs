@@ -0,0 +1,507 @@
+//! Record stages shared by the sequential strategies
+//!
+//! Every record read from the input goes through the same steps before and
+//! after the engine: it is counted, skipped if its client was not selected or
+//! it duplicates a recent record,
+//! diverted if it matches a quarantine rule, and otherwise flagged by the
+//! sanity checks and applied, with
+//! parse and processing errors and expired disputes logged to stderr and
+//! counted, accounts locked by a chargeback logged to stderr with their lock
+//! reason, and rejected records sent to a dead-letter sink. The postings of applied transactions can be written to a journal
+//! and totalled in a trial balance, sent as balance changes and counted in the analytics, sampled balances to a balance history, and after every N records a cutoff
+//! snapshot of the accounts. `RecordStages`
+//! implements these steps once for any `Engine`, or for backends such as the
+//! write-ahead log and the SQLite ledger whose writes can fail fatally.
+
+use crate::cli::InputFormat;
+use crate::core::{BalanceChanges, BalancePoint, Engine, ExpiredDispute, Posting, TrialBalance};
+use crate::io::{
+    create_change_sink, create_dead_letter_sink, log, BalanceHistoryWriter, ChangeSink,
+    ClientPseudonymizer, DeadLetter, DeadLetterSink, JournalWriter, LogLine, TrialBalanceWriter,
+};
+use crate::strategy::{
+    Analytics, Cutoffs, DedupFilter, InputOptions, Quarantine, RunSummary, SanityWarnings,
+};
+use crate::types::{
+    Account, ClientId, ClientSet, LockReason, PaymentError, TransactionId, TransactionRecord,
+    TransactionType,
+};
+use std::fs::File;
+use std::sync::Arc;
+
+/// The line logged for a transaction the engine rejected
+///
+/// Names the input file and line of the record where known, so a rejected
+/// transaction is reported like a record that failed to parse.
+pub(crate) fn transaction_error(
+    error: &PaymentError,
+    tx: TransactionId,
+    client: ClientId,
+    source: Option<&str>,
+    line: Option<u64>,
+) -> LogLine {
+    let mut message = String::from("Transaction processing error: ");
+    if let Some(source) = source {
+        message.push_str(&format!("{}: ", source));
+    }
+    if let Some(line) = line {
+        message.push_str(&format!("Line {}: ", line));
+    }
+    message.push_str(&error.with_code());
+    LogLine::error(message)
+        .with_error_code(error)
+        .with_record(tx, client)
+        .with_input(source, line)
+}
+
+/// Dedup, quarantine and sanity stages, and the summary of the records they
+/// handled
+pub(crate) struct RecordStages {
+    /// Input format, for error messages
+    format: InputFormat,
+    /// Clients selected for processing, `None` for all
+    clients: Option<ClientSet>,
+    dedup: DedupFilter,
+    quarantine: Quarantine,
+    sanity: SanityWarnings,
+    cutoffs: Cutoffs,
+    journal: Option<JournalWriter<File>>,
+    trial_balance: Option<(TrialBalanceWriter<File>, TrialBalance)>,
+    changes: Option<(Box<dyn ChangeSink>, BalanceChanges)>,
+    history: Option<BalanceHistoryWriter<File>>,
+    analytics: Option<Analytics>,
+    /// Pseudonymizer of the clients named in logged errors and events
+    pseudonymizer: Option<Arc<ClientPseudonymizer>>,
+    /// Sink of the records the engine rejects
+    dead_letters: Option<Box<dyn DeadLetterSink>>,
+    summary: RunSummary,
+}
+
+impl RecordStages {
+    /// Set up the stages configured by the input options
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RecordStages)` - With an empty summary
+    /// * `Err(String)` - If the quarantine, warnings, journal, trial balance,
+    ///   change, balance history or dead letter file, or the snapshot
+    ///   directory, cannot be created
+    pub(crate) fn open(input: &InputOptions) -> Result<Self, String> {
+        Ok(Self {
+            format: input.format,
+            clients: input.clients.clone(),
+            dedup: DedupFilter::new(input.dedup_window),
+            quarantine: Quarantine::open(input.quarantine.as_ref())?,
+            sanity: SanityWarnings::open(input.sanity.as_ref())?,
+            cutoffs: Cutoffs::open(input.cutoffs.as_ref())?,
+            journal: input
+                .journal
+                .as_deref()
+                .map(JournalWriter::create)
+                .transpose()?,
+            trial_balance: match input.trial_balance.as_deref() {
+                Some(path) => Some((TrialBalanceWriter::create(path)?, TrialBalance::new())),
+                None => None,
+            },
+            changes: match input.changes.as_deref() {
+                Some(target) => Some((create_change_sink(target)?, BalanceChanges::new())),
+                None => None,
+            },
+            history: input
+                .balance_history
+                .as_ref()
+                .map(|history| BalanceHistoryWriter::create(&history.path))
+                .transpose()?,
+            analytics: input.analytics.map(Analytics::new),
+            pseudonymizer: input.pseudonymizer.clone(),
+            dead_letters: input
+                .dead_letter
+                .as_ref()
+                .map(create_dead_letter_sink)
+                .transpose()?,
+            summary: RunSummary::default(),
+        })
+    }
+
+    /// Run one record read from the input through the stages and the engine
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the record was handled, including when it was rejected
+    /// * `Err(String)` - If the quarantine file, the warnings file, the
+    ///   journal, a balance change, the balance history, a cutoff snapshot or
+    ///   a dead letter cannot be written
+    pub(crate) fn apply<E: Engine + ?Sized>(
+        &mut self,
+        engine: &mut E,
+        result: Result<TransactionRecord, String>,
+    ) -> Result<(), String> {
+        self.apply_with(result, |record| Ok(engine.process_transaction(record)))?;
+        self.record_expired(engine.take_expired_disputes());
+        self.write_postings(engine.take_postings())?;
+        self.write_balance_history(engine.take_balance_history())?;
+        self.cutoff(&*engine)
+    }
+
+    /// Start the balance changes, if enabled, at the balances of accounts
+    /// that existed before the run
+    pub(crate) fn seed_changes(&mut self, accounts: &[Account]) {
+        if let Some((_, changes)) = self.changes.as_mut() {
+            changes.seed(accounts);
+        }
+    }
+
+    /// Write the postings of applied transactions to the journal, total them
+    /// in the trial balance, send the balance changes they make and count
+    /// them in the analytics, if enabled
+    pub(crate) fn write_postings(&mut self, postings: Vec<Posting>) -> Result<(), String> {
+        for posting in &postings {
+            if let Some(journal) = self.journal.as_mut() {
+                journal.write(posting)?;
+            }
+            if let Some((_, trial_balance)) = self.trial_balance.as_mut() {
+                trial_balance.record(posting);
+            }
+            if let Some((sink, changes)) = self.changes.as_mut() {
+                for change in changes.record(posting) {
+                    sink.send(change)?;
+                }
+            }
+            if let Some(analytics) = self.analytics.as_mut() {
+                analytics.record(posting);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write balance samples to the balance history, if any
+    pub(crate) fn write_balance_history(
+        &mut self,
+        points: Vec<BalancePoint>,
+    ) -> Result<(), String> {
+        let Some(history) = self.history.as_mut() else {
+            return Ok(());
+        };
+        for point in &points {
+            history.write(point)?;
+        }
+        Ok(())
+    }
+
+    /// Write a cutoff snapshot of the engine's accounts, if one is due
+    ///
+    /// Called after every record; `apply` does so itself.
+    pub(crate) fn cutoff<E: Engine + ?Sized>(&mut self, engine: &E) -> Result<(), String> {
+        if self.cutoffs.is_due(self.summary.records_read) {
+            self.cutoffs.write(&engine.get_accounts())?;
+        }
+        Ok(())
+    }
+
+    /// Log and count the audit events of disputes the engine expired
+    pub(crate) fn record_expired(&mut self, expired: Vec<ExpiredDispute>) {
+        for event in expired {
+            self.log(LogLine::info(event.to_string()).with_record(event.tx, event.client));
+            self.summary.expired_disputes += 1;
+        }
+    }
+
+    /// Run one record through the stages and a backend that can fail fatally
+    ///
+    /// `process` applies a record that passed the stages. It returns the
+    /// engine's verdict, or `Err(String)` for a fatal error (such as a failed
+    /// log or database write) that aborts the run.
+    pub(crate) fn apply_with<F>(
+        &mut self,
+        result: Result<TransactionRecord, String>,
+        process: F,
+    ) -> Result<(), String>
+    where
+        F: FnOnce(TransactionRecord) -> Result<Result<(), PaymentError>, String>,
+    {
+        self.summary.records_read += 1;
+        if let Ok(transaction_record) = &result {
+            self.summary.record_parsed(transaction_record);
+        }
+        match result {
+            Ok(transaction_record)
+                if self
+                    .clients
+                    .as_ref()
+                    .is_some_and(|clients| !clients.contains(transaction_record.client)) =>
+            {
+                self.summary.filtered += 1;
+            }
+            Ok(transaction_record) if self.dedup.is_duplicate(&transaction_record) => {
+                // Identical to a recent record, most likely an upstream retry
+                self.summary.duplicates += 1;
+            }
+            Ok(transaction_record) if self.quarantine.divert(&transaction_record)? => {
+                self.summary.quarantined += 1;
+            }
+            Ok(transaction_record) => {
+                self.summary.warnings += self.sanity.check(&transaction_record)?;
+                // Individual transaction errors are logged and processing continues
+                let dead_letter = self
+                    .dead_letters
+                    .is_some()
+                    .then(|| transaction_record.clone());
+                let (tx, client, line) = (
+                    transaction_record.tx,
+                    transaction_record.client,
+                    transaction_record.line,
+                );
+                let source = transaction_record.source.clone();
+                // Only unlocked accounts accept a chargeback, which locks them
+                let lock = (transaction_record.tx_type == TransactionType::Chargeback)
+                    .then_some(LockReason::ChargebackTx(tx));
+                match process(transaction_record)? {
+                    Ok(()) => {
+                        if let Some(reason) = lock {
+                            let event = format!("Account {} locked: {}", client, reason);
+                            self.log(
+                                LogLine::info(event)
+                                    .with_record(tx, client)
+                                    .with_input(source.as_deref(), line),
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        self.log(transaction_error(&e, tx, client, source.as_deref(), line));
+                        self.summary.record_transaction_error(&e);
+                        if let (Some(sink), Some(record)) =
+                            (self.dead_letters.as_mut(), dead_letter)
+                        {
+                            sink.send(DeadLetter::new(record, &e))?;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                self.log(
+                    LogLine::error(format!("{} parsing error: {}", self.format, e))
+                        .with_class("ParseError"),
+                );
+                self.summary.parse_errors += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Log a line, with clients pseudonymized if configured
+    fn log(&self, line: LogLine) {
+        log(line.masked(self.pseudonymizer.as_deref()));
+    }
+
+    /// Flush the records quarantined, flagged, journaled, sampled and
+    /// dead-lettered, and the balance changes, so far
+    pub(crate) fn flush(&mut self) -> Result<(), String> {
+        self.quarantine.flush()?;
+        self.sanity.flush()?;
+        if let Some(dead_letters) = self.dead_letters.as_mut() {
+            dead_letters.flush()?;
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.flush()?;
+        }
+        if let Some((sink, _)) = self.changes.as_mut() {
+            sink.flush()?;
+        }
+        match self.history.as_mut() {
+            Some(history) => history.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush the quarantine, warnings, journal, balance changes, balance
+    /// history and dead letters, write the trial balance and return the
+    /// summary of the run
+    pub(crate) fn finish(mut self) -> Result<RunSummary, String> {
+        self.flush()?;
+        self.quarantine.finish()?;
+        self.sanity.finish()?;
+        if let Some((writer, trial_balance)) = self.trial_balance.as_mut() {
+            writer.write(trial_balance)?;
+        }
+        self.summary.analytics = self.analytics.as_ref().map(Analytics::report);
+        Ok(self.summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::TransactionEngine;
+    use crate::io::DeadLetterOptions;
+    use crate::strategy::{SanityCheck, SanityOptions};
+    use crate::types::{ClientId, TransactionType};
+    use rust_decimal::Decimal;
+
+    fn deposit(tx: u16) -> Result<TransactionRecord, String> {
+        deposit_for(1, tx)
+    }
+
+    fn deposit_for(client: ClientId, tx: u16) -> Result<TransactionRecord, String> {
+        Ok(TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client,
+            tx: tx.into(),
+            amount: Some(Decimal::ONE),
+            line: None,
+            source: None,
+        })
+    }
+
+    #[test]
+    fn test_transaction_error_names_line() {
+        let error = PaymentError::account_locked(7);
+        let line = transaction_error(&error, 3, 7, None, Some(12));
+        assert_eq!(
+            line.message,
+            format!(
+                "Transaction processing error: Line 12: {}",
+                error.with_code()
+            )
+        );
+        assert_eq!(
+            (line.tx, line.client, line.line),
+            (Some(3), Some(7), Some(12))
+        );
+
+        let line = transaction_error(&error, 3, 7, None, None);
+        assert_eq!(
+            line.message,
+            format!("Transaction processing error: {}", error.with_code())
+        );
+        assert_eq!(line.line, None);
+    }
+
+    #[test]
+    fn test_transaction_error_names_source() {
+        let error = PaymentError::account_locked(7);
+        let line = transaction_error(&error, 3, 7, Some("day-2.csv"), Some(12));
+        assert_eq!(
+            line.message,
+            format!(
+                "Transaction processing error: day-2.csv: Line 12: {}",
+                error.with_code()
+            )
+        );
+        assert_eq!(
+            (line.source.as_deref(), line.line),
+            (Some("day-2.csv"), Some(12))
+        );
+
+        let line = transaction_error(&error, 3, 7, Some("day-2.avro"), None);
+        assert_eq!(
+            line.message,
+            format!(
+                "Transaction processing error: day-2.avro: {}",
+                error.with_code()
+            )
+        );
+    }
+
+    #[test]
+    fn test_apply_counts_each_outcome() {
+        let mut engine = TransactionEngine::new();
+        let mut stages =
+            RecordStages::open(&InputOptions::default().with_dedup_window(10)).unwrap();
+
+        for result in [
+            deposit(1),
+            deposit(1),
+            Err("Line 4: bad row".to_string()),
+            deposit(2),
+        ] {
+            stages.apply(&mut engine, result).unwrap();
+        }
+        let summary = stages.finish().unwrap();
+
+        assert_eq!(summary.records_read, 4);
+        assert_eq!(summary.duplicates, 1);
+        assert_eq!(summary.parse_errors, 1);
+        assert_eq!(summary.transaction_errors, 0);
+        assert_eq!(summary.transaction_types.deposit, 3);
+        assert_eq!(engine.get_accounts()[0].available, Decimal::from(2));
+    }
+
+    #[test]
+    fn test_apply_skips_unselected_clients() {
+        let mut engine = TransactionEngine::new();
+        let input = InputOptions::default().with_clients("2-3".parse().unwrap());
+        let mut stages = RecordStages::open(&input).unwrap();
+
+        for result in [deposit_for(1, 1), deposit_for(2, 2), deposit_for(4, 3)] {
+            stages.apply(&mut engine, result).unwrap();
+        }
+        let summary = stages.finish().unwrap();
+
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.filtered, 2);
+        let clients: Vec<ClientId> = engine.get_accounts().iter().map(|a| a.client).collect();
+        assert_eq!(clients, vec![2]);
+    }
+
+    #[test]
+    fn test_apply_dead_letters_rejected_records() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("dead.csv");
+        let input = InputOptions::default()
+            .with_dead_letter(DeadLetterOptions::new(path.to_str().unwrap()));
+        let mut engine = TransactionEngine::new();
+        let mut stages = RecordStages::open(&input).unwrap();
+
+        let withdrawal = TransactionRecord {
+            tx_type: TransactionType::Withdrawal,
+            client: 1,
+            tx: 2,
+            amount: Some(Decimal::TEN),
+            line: None,
+            source: None,
+        };
+        for result in [
+            deposit(1),
+            Ok(withdrawal),
+            Err("Line 4: bad row".to_string()),
+        ] {
+            stages.apply(&mut engine, result).unwrap();
+        }
+        let summary = stages.finish().unwrap();
+
+        assert_eq!(summary.transaction_errors, 1);
+        let dead_letters = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = dead_letters.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", dead_letters);
+        assert!(lines[1].starts_with("withdrawal,1,2,10,301,InsufficientFunds,"));
+    }
+
+    #[test]
+    fn test_apply_warns_and_still_processes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("warnings.csv");
+        let input = InputOptions::default()
+            .with_sanity(SanityOptions::new(&path).with_check(SanityCheck::NewClientWithdrawal));
+        let mut engine = TransactionEngine::new();
+        let mut stages = RecordStages::open(&input).unwrap();
+
+        let withdrawal = TransactionRecord {
+            tx_type: TransactionType::Withdrawal,
+            client: 2,
+            tx: 2,
+            amount: Some(Decimal::ONE),
+            line: None,
+            source: None,
+        };
+        for result in [deposit(1), Ok(withdrawal)] {
+            stages.apply(&mut engine, result).unwrap();
+        }
+        let summary = stages.finish().unwrap();
+
+        // The withdrawal is flagged, and rejected by the engine as usual
+        assert_eq!(summary.warnings, 1);
+        assert_eq!(summary.transaction_errors, 1);
+        let warnings = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(warnings.lines().count(), 2, "{}", warnings);
+    }
+}
@@ -0,0 +1,163 @@
+//! Standing orders expanded into the record stream
+//!
+//! A standing order is a recurring deposit or withdrawal, such as a
+//! subscription payment, that is not in the input itself. The sequential
+//! strategies expand each order into a concrete record after every N input
+//! records, merged into the stream right after the record that completes the
+//! interval, so the generated records are filtered, deduplicated and applied
+//! like any other. Records carry no timestamps, so intervals are counted in
+//! records.
+//!
+//! Generated records take transaction IDs counting down from the largest
+//! `TransactionId`, so they do not collide with the input's IDs, which
+//! normally count up.
+
+use crate::types::{ClientId, TransactionId, TransactionRecord, TransactionType};
+use rust_decimal::Decimal;
+
+/// A recurring deposit or withdrawal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandingOrder {
+    /// Client the records are generated for
+    pub client: ClientId,
+    /// `Deposit` or `Withdrawal`
+    pub tx_type: TransactionType,
+    /// Amount of every generated record
+    pub amount: Decimal,
+    /// Number of input records between generated records (at least 1)
+    pub every: u64,
+}
+
+impl StandingOrder {
+    /// Create an order generating a record after every `every` input records
+    pub fn new(client: ClientId, tx_type: TransactionType, amount: Decimal, every: u64) -> Self {
+        Self {
+            client,
+            tx_type,
+            amount,
+            every: every.max(1),
+        }
+    }
+}
+
+/// Standing order stage of a processing run
+#[derive(Debug)]
+pub(crate) struct StandingOrders {
+    orders: Vec<StandingOrder>,
+    /// Number of input records seen so far
+    records: u64,
+    /// Transaction ID of the next generated record
+    next_tx: TransactionId,
+}
+
+impl StandingOrders {
+    /// Create the stage for the given orders, with no input records seen
+    pub(crate) fn new(orders: &[StandingOrder]) -> Self {
+        Self {
+            orders: orders.to_vec(),
+            records: 0,
+            next_tx: TransactionId::MAX,
+        }
+    }
+
+    /// Count an input record and generate the records of the orders due after it
+    fn next_record(&mut self) -> Vec<TransactionRecord> {
+        self.records += 1;
+        let records = self.records;
+        self.orders
+            .iter()
+            .filter(|order| records.is_multiple_of(order.every))
+            .map(|order| {
+                let tx = self.next_tx;
+                self.next_tx = self.next_tx.saturating_sub(1);
+                TransactionRecord {
+                    tx_type: order.tx_type,
+                    client: order.client,
+                    tx,
+                    amount: Some(order.amount),
+                    line: None,
+                    source: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Merge the generated records into a stream of input records
+    ///
+    /// Every input record, including one that failed to parse, counts towards
+    /// the orders' intervals. The stage keeps counting across calls, so the
+    /// files of a run are expanded as one stream.
+    pub(crate) fn expand<'a>(
+        &'a mut self,
+        records: impl IntoIterator<Item = Result<TransactionRecord, String>> + 'a,
+    ) -> impl Iterator<Item = Result<TransactionRecord, String>> + 'a {
+        records.into_iter().flat_map(move |result| {
+            let generated = if self.orders.is_empty() {
+                Vec::new()
+            } else {
+                self.next_record()
+            };
+            std::iter::once(result).chain(generated.into_iter().map(Ok))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deposit(tx: TransactionId) -> Result<TransactionRecord, String> {
+        Ok(TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            tx,
+            amount: Some(Decimal::ONE),
+            line: None,
+            source: None,
+        })
+    }
+
+    #[test]
+    fn test_expand_merges_due_orders() {
+        let mut stage = StandingOrders::new(&[
+            StandingOrder::new(7, TransactionType::Withdrawal, Decimal::TEN, 2),
+            StandingOrder::new(8, TransactionType::Deposit, Decimal::ONE, 3),
+        ]);
+
+        let first: Vec<_> = stage
+            .expand([deposit(1), Err("bad row".to_string()), deposit(2)])
+            .collect();
+        let second: Vec<_> = stage.expand([deposit(3)]).collect();
+
+        let ids = |records: &[Result<TransactionRecord, String>]| -> Vec<Option<TransactionId>> {
+            records
+                .iter()
+                .map(|result| result.as_ref().ok().map(|record| record.tx))
+                .collect()
+        };
+        let max = TransactionId::MAX;
+        assert_eq!(
+            ids(&first),
+            vec![Some(1), None, Some(max), Some(2), Some(max - 1)]
+        );
+        assert_eq!(ids(&second), vec![Some(3), Some(max - 2)]);
+        assert_eq!(
+            first[2],
+            Ok(TransactionRecord {
+                tx_type: TransactionType::Withdrawal,
+                client: 7,
+                tx: max,
+                amount: Some(Decimal::TEN),
+                line: None,
+                source: None,
+            })
+        );
+        assert_eq!(first[4].as_ref().unwrap().client, 8);
+    }
+
+    #[test]
+    fn test_expand_without_orders() {
+        let mut stage = StandingOrders::new(&[]);
+        assert_eq!(stage.expand([deposit(1)]).count(), 1);
+    }
+}
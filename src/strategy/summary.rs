@@ -0,0 +1,653 @@
+//! Run summary for processing strategies
+//!
+//! This module defines the `RunSummary` returned by every processing strategy.
+//! It records how many input records were read and how many of them failed,
+//! either while parsing the CSV row or while being applied by the engine.
+//!
+//! The CLI uses the summary to enforce its exit-code policy
+//! (`--fail-on-error`, `--max-error-rate`), and can write it as JSON
+//! (`--summary`) together with per-type and per-error counts and totals over
+//! the final accounts, for reconciliation jobs.
+//!
+//! Strategies that track money flows also attach a `Conservation` report,
+//! comparing the final balances against the money moved by the run, which
+//! `--check-conservation` turns into a failing exit code.
+
+use crate::core::MoneyFlows;
+use crate::strategy::AnalyticsReport;
+use crate::types::{Account, ClientId, PaymentError, TransactionRecord, TransactionType};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+
+/// Number of parsed records of each transaction type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TransactionTypeCounts {
+    pub deposit: u64,
+    pub withdrawal: u64,
+    pub dispute: u64,
+    pub resolve: u64,
+    pub chargeback: u64,
+    pub approve: u64,
+    pub reject: u64,
+}
+
+impl TransactionTypeCounts {
+    /// Count one record of the given type
+    pub fn count(&mut self, tx_type: TransactionType) {
+        match tx_type {
+            TransactionType::Deposit => self.deposit += 1,
+            TransactionType::Withdrawal => self.withdrawal += 1,
+            TransactionType::Dispute => self.dispute += 1,
+            TransactionType::Resolve => self.resolve += 1,
+            TransactionType::Chargeback => self.chargeback += 1,
+            TransactionType::Approve => self.approve += 1,
+            TransactionType::Reject => self.reject += 1,
+        }
+    }
+}
+
+/// Retries of transactions that failed with a transient error (async
+/// strategy, `--retry-attempts`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RetryCounts {
+    /// Number of transactions processed more than once
+    pub retried: u64,
+    /// Number of attempts beyond the first, over all transactions
+    pub retries: u64,
+    /// Number of transactions still failing with a transient error after
+    /// their last attempt, included in `transaction_errors`
+    pub exhausted: u64,
+}
+
+/// Totals over the final account states of a run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct AccountTotals {
+    /// Number of accounts in the output
+    pub accounts: u64,
+    /// Number of locked (frozen) accounts
+    pub locked: u64,
+    /// Sum of available funds across all accounts
+    pub available: Decimal,
+    /// Sum of held funds across all accounts
+    pub held: Decimal,
+    /// Sum of total funds across all accounts
+    pub total: Decimal,
+}
+
+impl AccountTotals {
+    /// Compute the totals of a set of accounts
+    ///
+    /// The sums saturate rather than overflow, which can only happen with
+    /// balances near the limits of `Decimal`.
+    pub fn of(accounts: &[Account]) -> Self {
+        accounts
+            .iter()
+            .fold(Self::default(), |totals, account| Self {
+                accounts: totals.accounts + 1,
+                locked: totals.locked + u64::from(account.locked),
+                available: totals.available.saturating_add(account.available),
+                held: totals.held.saturating_add(account.held),
+                total: totals.total.saturating_add(account.total),
+            })
+    }
+}
+
+/// Maximum number of unbalanced clients listed in the diagnostics
+const MAX_LISTED_CLIENTS: usize = 10;
+
+/// Comparison of the final balances with the money moved during a run
+///
+/// Money is conserved when the sum of all account totals equals deposits
+/// minus withdrawals minus chargebacks, and every account's available and
+/// held funds add up to its total. Any difference points at an accounting
+/// bug in the engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Conservation {
+    /// Money moved by the applied transactions
+    pub flows: MoneyFlows,
+    /// Sum of account totals implied by the flows
+    pub expected_total: Decimal,
+    /// Actual sum of account totals
+    pub actual_total: Decimal,
+    /// `actual_total - expected_total`
+    pub drift: Decimal,
+    /// Clients whose available and held funds do not add up to their total
+    pub unbalanced_clients: Vec<ClientId>,
+}
+
+impl Conservation {
+    /// Compare the final accounts of a run with the money flows of its engine
+    pub fn check(flows: MoneyFlows, accounts: &[Account]) -> Self {
+        let expected_total = flows.expected_total();
+        let actual_total = AccountTotals::of(accounts).total;
+        Self {
+            flows,
+            expected_total,
+            actual_total,
+            drift: actual_total.saturating_sub(expected_total),
+            unbalanced_clients: accounts
+                .iter()
+                .filter(|account| {
+                    account.available.checked_add(account.held) != Some(account.total)
+                })
+                .map(|account| account.client)
+                .collect(),
+        }
+    }
+
+    /// Whether no money appeared or disappeared
+    pub fn is_conserved(&self) -> bool {
+        self.drift.is_zero() && self.unbalanced_clients.is_empty()
+    }
+}
+
+impl fmt::Display for Conservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "accounts total {} but deposits {}",
+            self.actual_total, self.flows.deposited
+        )?;
+        if !self.flows.interest.is_zero() {
+            write!(f, " + interest {}", self.flows.interest)?;
+        }
+        write!(
+            f,
+            " - withdrawals {} - chargebacks {} = {} (drift {})",
+            self.flows.withdrawn, self.flows.charged_back, self.expected_total, self.drift,
+        )?;
+        if !self.unbalanced_clients.is_empty() {
+            let listed: Vec<String> = self
+                .unbalanced_clients
+                .iter()
+                .take(MAX_LISTED_CLIENTS)
+                .map(ToString::to_string)
+                .collect();
+            write!(
+                f,
+                "; available + held != total for clients {}",
+                listed.join(", ")
+            )?;
+            if self.unbalanced_clients.len() > MAX_LISTED_CLIENTS {
+                write!(
+                    f,
+                    " and {} more",
+                    self.unbalanced_clients.len() - MAX_LISTED_CLIENTS
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts collected over a complete processing run
+///
+/// Every record read from the input is counted exactly once: either it failed
+/// to parse, it was skipped because its client was not selected, it was skipped
+/// as a duplicate, it was quarantined, it was rejected by the engine, or it was
+/// applied successfully.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RunSummary {
+    /// Number of input records read, including records that failed to parse
+    pub records_read: u64,
+
+    /// Number of records rejected while parsing or converting CSV rows
+    pub parse_errors: u64,
+
+    /// Number of transactions rejected by the engine
+    pub transaction_errors: u64,
+
+    /// Number of records skipped as duplicates of a recent record (`--dedup-window`)
+    pub duplicates: u64,
+
+    /// Number of records diverted to the quarantine file (`--quarantine`)
+    pub quarantined: u64,
+
+    /// Number of warnings written about suspicious records (`--warnings`);
+    /// the records are processed as usual, so they are counted elsewhere too
+    pub warnings: u64,
+
+    /// Number of records skipped because their client was not selected
+    /// (`--clients` with `--filter-input`)
+    pub filtered: u64,
+
+    /// Number of successfully parsed records of each transaction type
+    pub transaction_types: TransactionTypeCounts,
+
+    /// Number of transactions rejected by the engine for exceeding an amount
+    /// or velocity limit (`--max-deposit`, `--max-withdrawal`, `--max-total`,
+    /// `--velocity-window`), included in `transaction_errors`
+    pub limit_rejections: u64,
+
+    /// Number of disputes the engine resolved because they were left open for
+    /// longer than `--dispute-expiry` records
+    pub expired_disputes: u64,
+
+    /// Number of transactions rejected by the engine, by `PaymentError` variant
+    pub transaction_error_kinds: BTreeMap<&'static str, u64>,
+
+    /// Retries of transactions that failed with a transient error
+    pub retry: RetryCounts,
+
+    /// Totals over the final account states
+    pub accounts: AccountTotals,
+
+    /// Conservation check of the final balances, or `None` if the strategy
+    /// does not track money flows (the SQLite ledger, whose balances include
+    /// earlier runs)
+    pub conservation: Option<Conservation>,
+
+    /// Whether the run was cancelled before reading all of its input; the
+    /// counts then cover only the records processed until then
+    pub cancelled: bool,
+
+    /// Aggregate statistics of the applied transactions, if requested
+    /// (`--analytics`); written separately, so left out of the JSON summary
+    #[serde(skip)]
+    pub analytics: Option<AnalyticsReport>,
+}
+
+impl RunSummary {
+    /// Count a successfully parsed record by its transaction type
+    pub fn record_parsed(&mut self, record: &TransactionRecord) {
+        self.transaction_types.count(record.tx_type);
+    }
+
+    /// Count a transaction rejected by the engine
+    pub fn record_transaction_error(&mut self, error: &PaymentError) {
+        self.transaction_errors += 1;
+        if error.is_limit_exceeded() {
+            self.limit_rejections += 1;
+        }
+        *self
+            .transaction_error_kinds
+            .entry(error.kind())
+            .or_default() += 1;
+    }
+
+    /// Count the retries of a processed transaction
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts` - Number of times processing it was attempted
+    /// * `error` - The error of its last attempt, if it failed
+    pub fn record_attempts(&mut self, attempts: u32, error: Option<&PaymentError>) {
+        if attempts > 1 {
+            self.retry.retried += 1;
+            self.retry.retries += u64::from(attempts - 1);
+        }
+        if error.is_some_and(PaymentError::is_transient) {
+            self.retry.exhausted += 1;
+        }
+    }
+
+    /// Add the counts of another run over disjoint records, such as a shard
+    ///
+    /// The account totals, conservation and analytics are left as they are;
+    /// they depend on the merged accounts, not on the counts.
+    pub fn add_counts(&mut self, other: &RunSummary) {
+        self.records_read += other.records_read;
+        self.parse_errors += other.parse_errors;
+        self.transaction_errors += other.transaction_errors;
+        self.duplicates += other.duplicates;
+        self.quarantined += other.quarantined;
+        self.warnings += other.warnings;
+        self.filtered += other.filtered;
+        self.transaction_types.deposit += other.transaction_types.deposit;
+        self.transaction_types.withdrawal += other.transaction_types.withdrawal;
+        self.transaction_types.dispute += other.transaction_types.dispute;
+        self.transaction_types.resolve += other.transaction_types.resolve;
+        self.transaction_types.chargeback += other.transaction_types.chargeback;
+        self.limit_rejections += other.limit_rejections;
+        self.expired_disputes += other.expired_disputes;
+        for (kind, count) in &other.transaction_error_kinds {
+            *self.transaction_error_kinds.entry(kind).or_default() += count;
+        }
+        self.retry.retried += other.retry.retried;
+        self.retry.retries += other.retry.retries;
+        self.retry.exhausted += other.retry.exhausted;
+    }
+
+    /// Write the summary as JSON
+    pub fn write_json(&self, mut output: impl Write) -> Result<(), String> {
+        serde_json::to_writer_pretty(&mut output, self)
+            .map_err(|e| format!("Failed to write run summary: {}", e))?;
+        writeln!(output).map_err(|e| format!("Failed to write run summary: {}", e))
+    }
+
+    /// Write the summary as JSON to a file, or to stderr if `target` is `-`
+    pub fn write_json_to(&self, target: &str) -> Result<(), String> {
+        if target == "-" {
+            return self.write_json(std::io::stderr());
+        }
+        let file = File::create(target)
+            .map_err(|e| format!("Failed to create summary file '{}': {}", target, e))?;
+        self.write_json(file)
+    }
+
+    /// Total number of failed records (parse errors + transaction errors)
+    pub fn error_count(&self) -> u64 {
+        self.parse_errors + self.transaction_errors
+    }
+
+    /// Percentage of records that failed (0.0 - 100.0)
+    ///
+    /// Returns 0.0 when no records were read.
+    pub fn error_rate(&self) -> f64 {
+        if self.records_read == 0 {
+            return 0.0;
+        }
+        self.error_count() as f64 * 100.0 / self.records_read as f64
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Processed {} records: {} parse errors, {} transaction errors",
+            self.records_read, self.parse_errors, self.transaction_errors,
+        )?;
+        if self.limit_rejections > 0 {
+            write!(f, " ({} over limits)", self.limit_rejections)?;
+        }
+        if self.duplicates > 0 {
+            write!(f, ", {} duplicates skipped", self.duplicates)?;
+        }
+        if self.quarantined > 0 {
+            write!(f, ", {} quarantined", self.quarantined)?;
+        }
+        if self.warnings > 0 {
+            write!(f, ", {} warnings", self.warnings)?;
+        }
+        if self.filtered > 0 {
+            write!(f, ", {} filtered", self.filtered)?;
+        }
+        if self.expired_disputes > 0 {
+            write!(f, ", {} disputes expired", self.expired_disputes)?;
+        }
+        if self.retry.retried > 0 {
+            write!(f, ", {} retried", self.retry.retried)?;
+        }
+        if self.cancelled {
+            write!(f, ", cancelled")?;
+        }
+        write!(f, " ({:.2}% failed)", self.error_rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty(0, 0, 0, 0.0)]
+    #[case::no_errors(10, 0, 0, 0.0)]
+    #[case::parse_errors_only(10, 2, 0, 20.0)]
+    #[case::transaction_errors_only(4, 0, 1, 25.0)]
+    #[case::mixed(10, 5, 4, 90.0)]
+    fn test_error_rate(
+        #[case] records_read: u64,
+        #[case] parse_errors: u64,
+        #[case] transaction_errors: u64,
+        #[case] expected: f64,
+    ) {
+        let summary = RunSummary {
+            records_read,
+            parse_errors,
+            transaction_errors,
+            duplicates: 0,
+            quarantined: 0,
+            ..RunSummary::default()
+        };
+        assert_eq!(summary.error_rate(), expected);
+        assert_eq!(summary.error_count(), parse_errors + transaction_errors);
+    }
+
+    #[test]
+    fn test_display() {
+        let summary = RunSummary {
+            records_read: 8,
+            parse_errors: 1,
+            transaction_errors: 1,
+            duplicates: 0,
+            quarantined: 0,
+            ..RunSummary::default()
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Processed 8 records: 1 parse errors, 1 transaction errors (25.00% failed)"
+        );
+    }
+
+    #[test]
+    fn test_display_with_skipped_records() {
+        let summary = RunSummary {
+            records_read: 8,
+            parse_errors: 1,
+            transaction_errors: 1,
+            duplicates: 2,
+            quarantined: 3,
+            warnings: 4,
+            ..RunSummary::default()
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Processed 8 records: 1 parse errors, 1 transaction errors, 2 duplicates skipped, 3 quarantined, 4 warnings (25.00% failed)"
+        );
+    }
+
+    #[test]
+    fn test_record_transaction_error_counts_kinds() {
+        let mut summary = RunSummary::default();
+        summary.record_transaction_error(&PaymentError::account_locked(1));
+        summary.record_transaction_error(&PaymentError::account_locked(2));
+        summary.record_transaction_error(&PaymentError::duplicate_transaction(3, 1));
+
+        assert_eq!(summary.transaction_errors, 3);
+        assert_eq!(
+            summary.transaction_error_kinds,
+            BTreeMap::from([("AccountLocked", 2), ("DuplicateTransaction", 1)])
+        );
+    }
+
+    #[test]
+    fn test_record_transaction_error_counts_limit_rejections() {
+        let mut summary = RunSummary::default();
+        summary.record_transaction_error(&PaymentError::amount_limit_exceeded(
+            1,
+            1,
+            "deposit",
+            Decimal::from(2000),
+            Decimal::from(1000),
+        ));
+        summary.record_transaction_error(&PaymentError::account_locked(1));
+
+        assert_eq!(summary.transaction_errors, 2);
+        assert_eq!(summary.limit_rejections, 1);
+        assert_eq!(
+            summary.to_string(),
+            "Processed 0 records: 0 parse errors, 2 transaction errors (1 over limits) (0.00% failed)"
+        );
+    }
+
+    #[test]
+    fn test_account_totals() {
+        let mut first = Account::new(1);
+        first.available = Decimal::new(15, 1);
+        first.total = Decimal::new(15, 1);
+        let mut second = Account::new(2);
+        second.held = Decimal::ONE;
+        second.total = Decimal::ONE;
+        second.locked = true;
+
+        assert_eq!(
+            AccountTotals::of(&[first, second]),
+            AccountTotals {
+                accounts: 2,
+                locked: 1,
+                available: Decimal::new(15, 1),
+                held: Decimal::ONE,
+                total: Decimal::new(25, 1),
+            }
+        );
+    }
+
+    fn flows(deposited: i64, withdrawn: i64, charged_back: i64) -> MoneyFlows {
+        MoneyFlows {
+            deposited: Decimal::from(deposited),
+            withdrawn: Decimal::from(withdrawn),
+            charged_back: Decimal::from(charged_back),
+            ..MoneyFlows::default()
+        }
+    }
+
+    fn account(client: ClientId, available: i64, held: i64, total: i64) -> Account {
+        let mut account = Account::new(client);
+        account.available = Decimal::from(available);
+        account.held = Decimal::from(held);
+        account.total = Decimal::from(total);
+        account
+    }
+
+    #[test]
+    fn test_conservation_holds() {
+        let conservation = Conservation::check(
+            flows(100, 30, 20),
+            &[account(1, 40, 5, 45), account(2, 5, 0, 5)],
+        );
+        assert!(conservation.is_conserved());
+        assert_eq!(conservation.expected_total, Decimal::from(50));
+        assert_eq!(conservation.drift, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_conservation_reports_drift() {
+        let conservation = Conservation::check(
+            flows(100, 30, 20),
+            &[account(1, 40, 10, 60), account(2, 5, 0, 5)],
+        );
+        assert!(!conservation.is_conserved());
+        assert_eq!(conservation.drift, Decimal::from(15));
+        assert_eq!(conservation.unbalanced_clients, vec![1]);
+        assert_eq!(
+            conservation.to_string(),
+            "accounts total 65 but deposits 100 - withdrawals 30 - chargebacks 20 = 50 (drift 15); \
+             available + held != total for clients 1"
+        );
+    }
+
+    #[test]
+    fn test_conservation_counts_interest() {
+        let mut flows = flows(100, 30, 20);
+        flows.record_interest(Decimal::from(2));
+        let conservation = Conservation::check(flows, &[account(1, 50, 0, 50)]);
+        assert!(!conservation.is_conserved());
+        assert_eq!(
+            conservation.to_string(),
+            "accounts total 50 but deposits 100 + interest 2 - withdrawals 30 - chargebacks 20 = 52 (drift -2)"
+        );
+    }
+
+    #[test]
+    fn test_conservation_truncates_client_list() {
+        let accounts: Vec<Account> = (1..=12).map(|client| account(client, 1, 0, 0)).collect();
+        let conservation = Conservation::check(flows(0, 0, 0), &accounts);
+        assert!(!conservation.is_conserved());
+        assert!(conservation
+            .to_string()
+            .ends_with("clients 1, 2, 3, 4, 5, 6, 7, 8, 9, 10 and 2 more"));
+    }
+
+    #[test]
+    fn test_record_attempts() {
+        let transient = PaymentError::IoError {
+            message: "connection reset".to_string(),
+        };
+        let mut summary = RunSummary::default();
+
+        summary.record_attempts(1, None);
+        summary.record_attempts(3, None);
+        summary.record_attempts(2, Some(&transient));
+        summary.record_attempts(1, Some(&PaymentError::account_locked(1)));
+
+        assert_eq!(
+            summary.retry,
+            RetryCounts {
+                retried: 2,
+                retries: 3,
+                exhausted: 1,
+            }
+        );
+        assert!(summary.to_string().contains(", 2 retried"));
+    }
+
+    #[test]
+    fn test_add_counts() {
+        let mut first = RunSummary {
+            records_read: 3,
+            parse_errors: 1,
+            ..RunSummary::default()
+        };
+        first.record_parsed(&TransactionRecord {
+            tx_type: TransactionType::Deposit,
+            client: 1,
+            tx: 1,
+            amount: Some(Decimal::ONE),
+            line: None,
+            source: None,
+        });
+        first.record_transaction_error(&PaymentError::account_locked(1));
+        let mut second = RunSummary {
+            records_read: 2,
+            duplicates: 1,
+            ..RunSummary::default()
+        };
+        second.record_transaction_error(&PaymentError::account_locked(2));
+        second.record_attempts(2, None);
+
+        first.add_counts(&second);
+
+        assert_eq!(first.records_read, 5);
+        assert_eq!(first.parse_errors, 1);
+        assert_eq!(first.duplicates, 1);
+        assert_eq!(first.transaction_types.deposit, 1);
+        assert_eq!(first.transaction_errors, 2);
+        assert_eq!(
+            first.transaction_error_kinds,
+            BTreeMap::from([("AccountLocked", 2)])
+        );
+        assert_eq!(first.retry.retried, 1);
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut summary = RunSummary {
+            records_read: 3,
+            transaction_errors: 1,
+            ..RunSummary::default()
+        };
+        summary.transaction_types.count(TransactionType::Deposit);
+        summary.transaction_types.count(TransactionType::Withdrawal);
+        summary
+            .transaction_error_kinds
+            .insert("InsufficientFunds", 1);
+        summary.accounts = AccountTotals::of(&[Account::new(1)]);
+
+        let mut output = Vec::new();
+        summary.write_json(&mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["records_read"], 3);
+        assert_eq!(json["transaction_types"]["deposit"], 1);
+        assert_eq!(json["transaction_types"]["chargeback"], 0);
+        assert_eq!(json["transaction_error_kinds"]["InsufficientFunds"], 1);
+        assert_eq!(json["accounts"]["accounts"], 1);
+        assert_eq!(json["accounts"]["available"], "0");
+    }
+}
@@ -2,14 +2,15 @@
 //!
 //! This module provides a synchronous, single-threaded implementation of the
 //! ProcessingStrategy trait. It orchestrates transaction processing by coordinating
-//! between the SyncReader (for CSV input) and TransactionEngine (for business logic).
+//! between a record reader (SyncReader for CSV, AvroReader for Avro input) and
+//! TransactionEngine (for business logic).
 //!
 //! # Design
 //!
 //! The SyncProcessingStrategy focuses on orchestration, delegating:
-//! - CSV parsing to `SyncReader` (iterator interface)
+//! - Input parsing to `SyncReader` or `AvroReader` (iterator interface)
 //! - Transaction processing to `TransactionEngine` (business logic)
-//! - CSV output to `csv_format::write_accounts_csv` (format handling)
+//! - Account output to an `AccountSink` (CSV for any writer)
 //!
 //! This separation of concerns makes the code more maintainable and testable.
 //!
@@ -26,13 +27,15 @@
 //! compatible with the ProcessingStrategy trait, allowing it to be used in
 //! multi-threaded contexts if needed.
 
-use crate::core::TransactionEngine;
-use crate::io::csv_format::write_accounts_csv;
-use crate::io::sync_reader::SyncReader;
-use crate::strategy::ProcessingStrategy;
-use crate::types::Account;
-use std::io::Write;
-use std::path::Path;
+use crate::core::{save_state, Engine, EngineConfig, EngineSnapshot, TransactionEngine};
+use crate::io::AccountSink;
+use crate::strategy::{
+    check_inputs, input_source, open_records, AccountTotals, Conservation, InputOptions,
+    ProcessingStrategy, RecordStages, RunSummary, StandingOrders,
+};
+use crate::types::EngineError;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Synchronous processing strategy
 ///
@@ -47,7 +50,7 @@ use std::path::Path;
 /// use std::path::Path;
 /// use std::io;
 ///
-/// let strategy = SyncProcessingStrategy;
+/// let strategy = SyncProcessingStrategy::new();
 /// let mut output = io::stdout();
 ///
 /// strategy.process(Path::new("transactions.csv"), &mut output)
@@ -66,33 +69,81 @@ use std::path::Path;
 /// - Uses the same TransactionEngine for processing
 /// - Produces identical output for the same input
 /// - Has the same error handling behavior
-#[derive(Debug, Clone, Copy)]
-pub struct SyncProcessingStrategy;
+#[derive(Debug, Clone, Default)]
+pub struct SyncProcessingStrategy {
+    /// Options for reading the input file
+    input: InputOptions,
+    /// Configuration for the transaction engine
+    engine_config: EngineConfig,
+    /// State file to write the final engine snapshot to
+    save_state: Option<PathBuf>,
+    /// State of an earlier run to continue from (`apply`)
+    prior_state: Option<Arc<EngineSnapshot>>,
+}
+
+impl SyncProcessingStrategy {
+    /// Create a new SyncProcessingStrategy reading CSV input
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the input options, or just the format of the input file
+    pub fn with_input(mut self, input: impl Into<InputOptions>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Set the transaction engine configuration
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Write the final engine snapshot to a state file (`--save-state`)
+    pub fn with_save_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_state = Some(path.into());
+        self
+    }
+
+    /// Continue from the state of an earlier run instead of an empty engine
+    ///
+    /// The inputs are applied on top of the snapshot's accounts and stored
+    /// transactions, so they only need to hold the records added since. The
+    /// summary then has no conservation check, as the money moved by the
+    /// earlier run is not known.
+    pub fn with_prior_state(mut self, snapshot: EngineSnapshot) -> Self {
+        self.prior_state = Some(Arc::new(snapshot));
+        self
+    }
+}
 
 impl ProcessingStrategy for SyncProcessingStrategy {
-    /// Process transactions from input file and write results to output
+    /// Process transactions from input files and write results to output
     ///
     /// This method orchestrates the complete synchronous processing pipeline:
-    /// 1. Creates a SyncReader to stream transaction records from the CSV file
-    /// 2. Creates a TransactionEngine to process transactions
+    /// 1. Creates a TransactionEngine to process transactions
+    /// 2. For each input file in order, opens a reader (SyncReader or AvroReader)
+    ///    to stream transaction records
     /// 3. Iterates through records, processing each through the engine
-    /// 4. Collects final account states from the engine
-    /// 5. Writes account states to output using csv_format::write_accounts_csv
+    /// 4. Counts records read and records that failed (parse or processing errors)
+    /// 5. Collects final account states from the engine
+    /// 6. Writes account states to the output sink
     ///
     /// # Arguments
     ///
-    /// * `input_path` - Path to the input CSV file
-    /// * `output` - Mutable reference to a writer for outputting account states
+    /// * `input_paths` - Paths to the input files, processed in order
+    /// * `output` - Sink for the account states
     ///
     /// # Returns
     ///
-    /// * `Ok(())` if processing completed successfully
-    /// * `Err(String)` if a fatal error occurred
+    /// * `Ok(RunSummary)` if processing completed successfully
+    /// * `Err(EngineError)` if a fatal error occurred
     ///
     /// # Error Handling
     ///
     /// Fatal errors (file not found, I/O errors) are returned immediately.
-    /// Individual transaction errors are logged to stderr and processing continues.
+    /// Individual transaction errors are logged to stderr, counted in the
+    /// summary, and processing continues.
     ///
     /// # Examples
     ///
@@ -101,57 +152,82 @@ impl ProcessingStrategy for SyncProcessingStrategy {
     /// use std::path::Path;
     /// use std::io;
     ///
-    /// let strategy = SyncProcessingStrategy;
+    /// let strategy = SyncProcessingStrategy::new();
     /// let mut output = io::stdout();
     ///
     /// match strategy.process(Path::new("transactions.csv"), &mut output) {
-    ///     Ok(()) => println!("Processing completed"),
+    ///     Ok(summary) => println!("Processing completed: {}", summary),
     ///     Err(e) => eprintln!("Fatal error: {}", e),
     /// }
     /// ```
-    fn process(&self, input_path: &Path, output: &mut dyn Write) -> Result<(), String> {
-        // Create transaction engine
-        let mut engine = TransactionEngine::new();
-
-        // Create sync reader for streaming CSV input
-        let reader = SyncReader::new(input_path)?;
-
-        // Process each transaction record through the engine
-        // The iterator interface allows us to process one record at a time
-        for result in reader {
-            match result {
-                Ok(transaction_record) => {
-                    // Process the transaction through the engine
-                    // Individual transaction errors are handled by the engine
-                    if let Err(e) = engine.process(transaction_record) {
-                        // Log transaction processing errors to stderr
-                        eprintln!("Transaction processing error: {}", e);
-                    }
-                }
-                Err(e) => {
-                    // Log CSV parsing/conversion errors to stderr
-                    eprintln!("CSV parsing error: {}", e);
-                }
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        check_inputs(input_paths)?;
+
+        // Create transaction engine, shared by all input files
+        let config = self.input.engine_config(&self.engine_config);
+        let mut engine = match &self.prior_state {
+            Some(prior) => TransactionEngine::with_state(
+                config,
+                prior.accounts.iter().cloned(),
+                prior.transactions.iter().cloned(),
+            ),
+            None => TransactionEngine::with_config(config),
+        };
+
+        let mut stages = RecordStages::open(&self.input)?;
+        if let Some(prior) = &self.prior_state {
+            stages.seed_changes(&prior.accounts);
+        }
+        let mut standing_orders = StandingOrders::new(&self.input.standing_orders);
+
+        for input_path in input_paths {
+            // Create reader for streaming input in the configured format
+            let reader = open_records(
+                input_path,
+                &self.input,
+                input_source(input_paths, input_path),
+            )?;
+
+            // Process each transaction record through the stages and the engine
+            // The iterator interface allows us to process one record at a time
+            for result in standing_orders.expand(reader) {
+                stages.apply(&mut engine, result)?;
             }
         }
+        let mut summary = stages.finish()?;
 
-        // Get final account states from the engine
-        let account_refs = engine.get_accounts();
-
-        // Convert references to owned accounts for CSV writing
-        let accounts: Vec<Account> = account_refs.iter().map(|&a| a.clone()).collect();
-
-        // Write account states to output using csv_format module
-        write_accounts_csv(&accounts, output)?;
+        // Write final account states to the output sink
+        let accounts = Engine::get_accounts(&engine);
+        summary.accounts = AccountTotals::of(&accounts);
+        if self.prior_state.is_none() {
+            summary.conservation = Some(Conservation::check(engine.flows(), &accounts));
+        }
+        if let Some(path) = &self.save_state {
+            save_state(path, &engine.snapshot())?;
+        }
+        output.write_accounts(&accounts)?;
 
-        Ok(())
+        Ok(summary)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::{FeeSchedule, MoneyFlows};
+    use crate::strategy::{
+        AmountScale, BalanceHistoryOptions, ClientIdOffset, CutoffOptions, QuarantineOptions,
+        QuarantineRule, RetryCounts, StandingOrder, TransactionTypeCounts,
+    };
+    use crate::types::{TransactionRecord, TransactionType};
+    use rstest::rstest;
+    use rust_decimal::Decimal;
     use std::io::Write;
+    use std::path::Path;
     use tempfile::NamedTempFile;
 
     /// Helper function to create a temporary CSV file for testing
@@ -168,7 +244,7 @@ mod tests {
         let csv_content = "type,client,tx,amount\ndeposit,1,1,100.0\n";
         let file = create_temp_csv(csv_content);
 
-        let strategy = SyncProcessingStrategy;
+        let strategy = SyncProcessingStrategy::new();
         let mut output = Vec::new();
 
         let result = strategy.process(file.path(), &mut output);
@@ -188,7 +264,7 @@ mod tests {
                           deposit,2,3,200.0\n";
         let file = create_temp_csv(csv_content);
 
-        let strategy = SyncProcessingStrategy;
+        let strategy = SyncProcessingStrategy::new();
         let mut output = Vec::new();
 
         let result = strategy.process(file.path(), &mut output);
@@ -202,12 +278,32 @@ mod tests {
 
     #[test]
     fn test_sync_strategy_handles_missing_file() {
-        let strategy = SyncProcessingStrategy;
+        let strategy = SyncProcessingStrategy::new();
         let mut output = Vec::new();
 
         let result = strategy.process(Path::new("nonexistent.csv"), &mut output);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Failed to open file"));
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Failed to open file"));
+        assert!(matches!(
+            err,
+            EngineError::Io { ref source, .. } if source.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_sync_strategy_rejects_invalid_header() {
+        let file = create_temp_csv("type,client,tx,amout\ndeposit,1,1,100.0\n");
+
+        let strategy = SyncProcessingStrategy::new();
+        let mut output = Vec::new();
+
+        let err = strategy
+            .process(file.path(), &mut output)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("did you mean 'amount'?"), "{err}");
+        assert!(output.is_empty());
     }
 
     #[test]
@@ -217,7 +313,7 @@ mod tests {
                           dispute,1,1,\n";
         let file = create_temp_csv(csv_content);
 
-        let strategy = SyncProcessingStrategy;
+        let strategy = SyncProcessingStrategy::new();
         let mut output = Vec::new();
 
         let result = strategy.process(file.path(), &mut output);
@@ -237,8 +333,8 @@ mod tests {
 
     #[test]
     fn test_sync_strategy_can_be_cloned() {
-        let strategy1 = SyncProcessingStrategy;
-        let strategy2 = strategy1;
+        let strategy1 = SyncProcessingStrategy::new();
+        let strategy2 = strategy1.clone();
 
         // Both should work independently
         let csv_content = "type,client,tx,amount\ndeposit,1,1,100.0\n";
@@ -261,7 +357,7 @@ mod tests {
                           deposit,3,3,50.0\n";
         let file = create_temp_csv(csv_content);
 
-        let strategy = SyncProcessingStrategy;
+        let strategy = SyncProcessingStrategy::new();
         let mut output = Vec::new();
 
         let result = strategy.process(file.path(), &mut output);
@@ -272,4 +368,479 @@ mod tests {
         assert!(output_str.contains("1"));
         assert!(output_str.contains("3"));
     }
+
+    #[test]
+    fn test_sync_strategy_saves_state() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,10.0\n\
+                          deposit,2,2,5.0\n\
+                          dispute,2,2,\n";
+        let file = create_temp_csv(csv_content);
+        let state_dir = tempfile::TempDir::new().unwrap();
+        let state_path = state_dir.path().join("state.bin");
+
+        let strategy = SyncProcessingStrategy::new().with_save_state(&state_path);
+        strategy.process(file.path(), &mut Vec::new()).unwrap();
+
+        let snapshot = crate::core::load_state(&state_path).unwrap();
+        assert_eq!(snapshot.accounts.len(), 2);
+        assert_eq!(snapshot.account(2).unwrap().held, Decimal::from(5));
+        assert!(snapshot.transaction(2).unwrap().dispute_state.is_disputed());
+    }
+
+    #[test]
+    fn test_sync_strategy_summarizes_errors() {
+        // One malformed record and one rejected withdrawal out of four
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,100.0\n\
+                          deposit,2,2,invalid\n\
+                          withdrawal,1,3,500.0\n\
+                          deposit,3,4,50.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let strategy = SyncProcessingStrategy::new();
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                records_read: 4,
+                parse_errors: 1,
+                transaction_errors: 1,
+                limit_rejections: 0,
+                duplicates: 0,
+                quarantined: 0,
+                warnings: 0,
+                filtered: 0,
+                expired_disputes: 0,
+                transaction_types: TransactionTypeCounts {
+                    deposit: 2,
+                    withdrawal: 1,
+                    ..TransactionTypeCounts::default()
+                },
+                transaction_error_kinds: [("InsufficientFunds", 1)].into(),
+                retry: RetryCounts::default(),
+                accounts: AccountTotals {
+                    accounts: 2,
+                    locked: 0,
+                    available: Decimal::from(150),
+                    held: Decimal::ZERO,
+                    total: Decimal::from(150),
+                },
+                conservation: Some(Conservation {
+                    flows: MoneyFlows {
+                        deposited: Decimal::from(150),
+                        ..MoneyFlows::default()
+                    },
+                    expected_total: Decimal::from(150),
+                    actual_total: Decimal::from(150),
+                    ..Conservation::default()
+                }),
+                cancelled: false,
+                analytics: None,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::default(false, 0, "1,3.0000")]
+    #[case::legacy(true, 1, "1,1.0000")]
+    fn test_sync_strategy_legacy_tx_ids(
+        #[case] legacy_tx_ids: bool,
+        #[case] parse_errors: u64,
+        #[case] expected_line: &str,
+    ) {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,4294967295,1.0\n\
+                          deposit,1,4294967296,2.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let input = InputOptions::default().with_legacy_tx_ids(legacy_tx_ids);
+        let strategy = SyncProcessingStrategy::new().with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.parse_errors, parse_errors);
+        assert!(String::from_utf8(output).unwrap().contains(expected_line));
+    }
+
+    #[rstest]
+    #[case::disabled(0, 0, 1, "1,3.0000")]
+    #[case::enabled(10, 1, 0, "1,3.0000")]
+    fn test_sync_strategy_dedup_window(
+        #[case] dedup_window: usize,
+        #[case] duplicates: u64,
+        #[case] transaction_errors: u64,
+        #[case] expected_line: &str,
+    ) {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,1.0\n\
+                          deposit,1,1,1.0\n\
+                          deposit,1,2,2.0\n";
+        let file = create_temp_csv(csv_content);
+
+        let input = InputOptions::default().with_dedup_window(dedup_window);
+        let strategy = SyncProcessingStrategy::new().with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.duplicates, duplicates);
+        assert_eq!(summary.transaction_errors, transaction_errors);
+        assert!(String::from_utf8(output).unwrap().contains(expected_line));
+    }
+
+    #[test]
+    fn test_sync_strategy_applies_middleware() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,250\ndeposit,2,2,100\n");
+
+        let input = InputOptions::default()
+            .with_middleware(ClientIdOffset::new(100))
+            .with_middleware(AmountScale::new(Decimal::new(1, 2)));
+        let strategy = SyncProcessingStrategy::new().with_input(input);
+        let mut output = Vec::new();
+
+        strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             101,2.5000,0.0000,2.5000,false\n\
+             102,1.0000,0.0000,1.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_sync_strategy_quarantines_large_amounts() {
+        let csv_content = "type,client,tx,amount\n\
+                          deposit,1,1,100.0\n\
+                          deposit,1,2,5000.0\n\
+                          withdrawal,1,3,30.0\n";
+        let file = create_temp_csv(csv_content);
+        let quarantine_file = NamedTempFile::new().unwrap();
+
+        let input = InputOptions::default().with_quarantine(
+            QuarantineOptions::new(quarantine_file.path())
+                .with_rule(QuarantineRule::AmountAbove(1000.into())),
+        );
+        let strategy = SyncProcessingStrategy::new().with_input(input);
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.quarantined, 1);
+        assert!(String::from_utf8(output).unwrap().contains("1,70.0000"));
+        assert_eq!(
+            std::fs::read_to_string(quarantine_file.path()).unwrap(),
+            "type,client,tx,amount,reason\ndeposit,1,2,5000.0,amount 5000.0 above 1000\n"
+        );
+    }
+
+    #[test]
+    fn test_sync_strategy_processes_files_in_order() {
+        // The dispute in the second file references a deposit from the first
+        let file1 = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+        let file2 = create_temp_csv("type,client,tx,amount\ndeposit,1,2,5.0\ndispute,1,1,\n");
+
+        let strategy = SyncProcessingStrategy::new();
+        let mut output = Vec::new();
+
+        let summary = strategy
+            .process_files(
+                &[file1.path().to_path_buf(), file2.path().to_path_buf()],
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(summary.records_read, 3);
+        assert_eq!(summary.error_count(), 0);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("1,5.0000,100.0000,105.0000,false"));
+    }
+
+    #[test]
+    fn test_sync_strategy_counts_expired_disputes() {
+        let file = create_temp_csv(
+            "type,client,tx,amount\ndeposit,1,1,100.0\ndispute,1,1,\ndeposit,1,2,5.0\ndeposit,1,3,5.0\n",
+        );
+
+        let strategy = SyncProcessingStrategy::new()
+            .with_engine_config(EngineConfig::new().with_dispute_expiry(1));
+        let mut output = Vec::new();
+
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(summary.expired_disputes, 1);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("1,110.0000,0.0000,110.0000,false"));
+    }
+
+    #[test]
+    fn test_sync_strategy_writes_cutoff_snapshots() {
+        // The parse error counts towards the cutoff like any other record
+        let file = create_temp_csv(
+            "type,client,tx,amount\ndeposit,1,1,10.0\nbogus,1,2,1.0\ndeposit,1,3,5.0\ndeposit,2,4,1.0\ndeposit,2,5,1.0\n",
+        );
+        let dir = tempfile::TempDir::new().unwrap();
+        let cutoffs = CutoffOptions::new(dir.path(), 2);
+
+        let strategy = SyncProcessingStrategy::new()
+            .with_input(InputOptions::default().with_cutoffs(cutoffs.clone()));
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(cutoffs.path(1)).unwrap(),
+            "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(cutoffs.path(2)).unwrap(),
+            "client,available,held,total,locked\n1,15.0000,0.0000,15.0000,false\n2,1.0000,0.0000,1.0000,false\n"
+        );
+        assert!(!cutoffs.path(3).exists());
+    }
+
+    #[test]
+    fn test_sync_strategy_expands_standing_orders() {
+        // The withdrawal follows every second record across both files
+        let file1 = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\ndeposit,2,2,1.0\n");
+        let file2 = create_temp_csv("type,client,tx,amount\ndeposit,2,3,1.0\ndeposit,2,4,1.0\n");
+        let order = StandingOrder::new(1, TransactionType::Withdrawal, Decimal::new(999, 2), 2);
+
+        let strategy = SyncProcessingStrategy::new()
+            .with_input(InputOptions::default().with_standing_orders(vec![order]));
+        let mut output = Vec::new();
+
+        let summary = strategy
+            .process_files(
+                &[file1.path().to_path_buf(), file2.path().to_path_buf()],
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(summary.records_read, 6);
+        assert_eq!(summary.transaction_types.withdrawal, 2);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("1,80.0200,0.0000,80.0200,false"));
+    }
+
+    #[test]
+    fn test_sync_strategy_writes_journal() {
+        let file = create_temp_csv(
+            "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,25.0\ndispute,1,1,\n",
+        );
+        let journal = NamedTempFile::new().unwrap();
+
+        let strategy = SyncProcessingStrategy::new()
+            .with_input(InputOptions::default().with_journal(journal.path()));
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        // The rejected withdrawal has no journal lines
+        assert_eq!(
+            std::fs::read_to_string(journal.path()).unwrap(),
+            "tx,type,account,contra_account,debit,credit\n\
+             1,deposit,settlement,client:1:available,10.0000,\n\
+             1,deposit,client:1:available,settlement,,10.0000\n\
+             1,dispute,client:1:available,client:1:held,10.0000,\n\
+             1,dispute,client:1:held,client:1:available,,10.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_sync_strategy_writes_trial_balance() {
+        let file = create_temp_csv(
+            "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,4.0\ndeposit,2,3,5.0\ndispute,2,3,\n",
+        );
+        let trial_balance = NamedTempFile::new().unwrap();
+
+        let strategy = SyncProcessingStrategy::new()
+            .with_engine_config(
+                EngineConfig::new()
+                    .with_fees(FeeSchedule::new(9).with_withdrawal_fee("1".parse().unwrap())),
+            )
+            .with_input(InputOptions::default().with_trial_balance(trial_balance.path()));
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(trial_balance.path()).unwrap(),
+            "account,section,debit,credit,balance\n\
+             settlement,system,15.0000,4.0000,-11.0000\n\
+             client:9:available,system,0.0000,1.0000,1.0000\n\
+             client:1:available,client,5.0000,10.0000,5.0000\n\
+             client:2:available,client,5.0000,5.0000,0.0000\n\
+             client:2:held,client,0.0000,5.0000,5.0000\n\
+             total,,25.0000,25.0000,0.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_sync_strategy_writes_changes_from_prior_state() {
+        let mut prior = TransactionEngine::new();
+        prior
+            .process_transaction(TransactionRecord {
+                tx_type: TransactionType::Deposit,
+                client: 1,
+                tx: 1,
+                amount: Some(Decimal::TEN),
+                line: None,
+                source: None,
+            })
+            .unwrap();
+        let file = create_temp_csv(
+            "type,client,tx,amount\nwithdrawal,1,2,4.0\ndeposit,1,3,1.0\ndispute,1,3,\n",
+        );
+        let changes = NamedTempFile::new().unwrap();
+
+        let strategy = SyncProcessingStrategy::new()
+            .with_prior_state(prior.snapshot())
+            .with_input(InputOptions::default().with_changes(changes.path().to_str().unwrap()));
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        let events: Vec<serde_json::Value> = std::fs::read_to_string(changes.path())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let balances: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event["seq"].as_u64().unwrap(),
+                    event["type"].as_str().unwrap(),
+                    event["before"]["available"].as_str().unwrap(),
+                    event["after"]["available"].as_str().unwrap(),
+                    event["after"]["held"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            balances,
+            [
+                (1, "withdrawal", "10", "6.0", "0"),
+                (2, "deposit", "6.0", "7.0", "0"),
+                (3, "dispute", "7.0", "6.0", "1.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sync_strategy_writes_balance_history() {
+        let file = create_temp_csv(
+            "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,2,2,1.0\nwithdrawal,1,3,4.0\ndeposit,1,4,2.5\n",
+        );
+        let history = NamedTempFile::new().unwrap();
+
+        let strategy = SyncProcessingStrategy::new().with_input(
+            InputOptions::default()
+                .with_balance_history(BalanceHistoryOptions::new(history.path(), 1)),
+        );
+        let mut output = Vec::new();
+        strategy.process(file.path(), &mut output).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(history.path()).unwrap(),
+            "client,transactions,available,held,total,locked\n\
+             1,1,10.0000,0.0000,10.0000,false\n\
+             2,1,1.0000,0.0000,1.0000,false\n\
+             1,2,6.0000,0.0000,6.0000,false\n\
+             1,3,8.5000,0.0000,8.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_sync_strategy_collects_analytics() {
+        let file = create_temp_csv(
+            "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,2,2,250.0\nwithdrawal,1,3,40.0\ndispute,1,1,\nchargeback,1,1,\n",
+        );
+
+        let strategy =
+            SyncProcessingStrategy::new().with_input(InputOptions::default().with_analytics(1));
+        let mut output = Vec::new();
+        let summary = strategy.process(file.path(), &mut output).unwrap();
+
+        // The rejected withdrawal is not counted
+        let analytics = summary.analytics.unwrap();
+        assert_eq!(analytics.clients, 2);
+        assert_eq!(analytics.top_by_volume.len(), 1);
+        assert_eq!(analytics.top_by_volume[0].client, 2);
+        assert_eq!(analytics.top_by_dispute_rate[0].client, 1);
+        assert_eq!(analytics.top_by_dispute_rate[0].dispute_rate, Decimal::ONE);
+        assert_eq!(analytics.chargebacks.amount, Decimal::from(10));
+        assert_eq!(analytics.amounts[2].count, 1);
+        assert_eq!(analytics.amounts[3].count, 1);
+        assert!(SyncProcessingStrategy::new()
+            .process(file.path(), &mut Vec::new())
+            .unwrap()
+            .analytics
+            .is_none());
+    }
+
+    #[test]
+    fn test_sync_strategy_checks_all_files_before_processing() {
+        let file = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\n");
+
+        let strategy = SyncProcessingStrategy::new();
+        let mut output = Vec::new();
+
+        let result = strategy.process_files(
+            &[file.path().to_path_buf(), "nonexistent.csv".into()],
+            &mut output,
+        );
+        assert!(result.unwrap_err().to_string().contains("nonexistent.csv"));
+        assert!(output.is_empty());
+    }
+
+    #[cfg(not(feature = "avro"))]
+    #[test]
+    fn test_sync_strategy_avro_requires_feature() {
+        let file = create_temp_csv("");
+
+        let strategy = SyncProcessingStrategy::new().with_input(crate::cli::InputFormat::Avro);
+        let mut output = Vec::new();
+
+        let result = strategy.process(file.path(), &mut output);
+        assert!(result.unwrap_err().to_string().contains("'avro' feature"));
+    }
+
+    #[cfg(feature = "fast-csv")]
+    #[test]
+    fn test_sync_strategy_fast_csv() {
+        let file = create_temp_csv(
+            "type,client,tx,amount\n\
+             deposit,1,1,100.0\n\
+             \"withdrawal\",1,2,\"40.0\"\n\
+             deposit,2,3,5.0\n",
+        );
+
+        let strategy =
+            SyncProcessingStrategy::new().with_input(InputOptions::default().with_fast_csv(true));
+        let mut output = Vec::new();
+
+        strategy.process(file.path(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,60.0000,0.0000,60.0000,false\n\
+             2,5.0000,0.0000,5.0000,false\n"
+        );
+    }
+
+    #[cfg(not(feature = "fast-csv"))]
+    #[test]
+    fn test_sync_strategy_fast_csv_requires_feature() {
+        let file = create_temp_csv("type,client,tx,amount\n");
+
+        let strategy =
+            SyncProcessingStrategy::new().with_input(InputOptions::default().with_fast_csv(true));
+        let mut output = Vec::new();
+
+        let result = strategy.process(file.path(), &mut output);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("'fast-csv' feature"));
+    }
 }
@@ -0,0 +1,183 @@
+//! Write-ahead logged processing strategy
+//!
+//! This module provides a ProcessingStrategy that runs every record through a
+//! `DurableEngine`: the record is appended to a write-ahead log before it is
+//! applied. On startup the existing log is replayed, so a run interrupted by a
+//! crash can be continued from exactly the records that were logged.
+//!
+//! Records are applied sequentially, in input order, so that replay reproduces
+//! the original outcome of every record. The output contains every account in
+//! the recovered state, not only the accounts touched by this run.
+
+use crate::core::wal::DurableEngine;
+use crate::core::{save_state, Engine, EngineConfig};
+use crate::io::AccountSink;
+use crate::strategy::{
+    check_inputs, input_source, open_records, AccountTotals, Conservation, InputOptions,
+    ProcessingStrategy, RecordStages, RunSummary, StandingOrders,
+};
+use crate::types::EngineError;
+use std::path::PathBuf;
+
+/// Processing strategy backed by a write-ahead log
+#[derive(Debug, Clone)]
+pub struct WalProcessingStrategy {
+    /// Path to the write-ahead log file
+    wal_path: PathBuf,
+    /// Options for reading the input file
+    input: InputOptions,
+    /// Configuration for the transaction engine
+    engine_config: EngineConfig,
+    /// State file to write the final engine snapshot to
+    save_state: Option<PathBuf>,
+}
+
+impl WalProcessingStrategy {
+    /// Create a new WalProcessingStrategy
+    ///
+    /// # Arguments
+    ///
+    /// * `wal_path` - Path to the write-ahead log file (created if missing)
+    pub fn new(wal_path: impl Into<PathBuf>) -> Self {
+        Self {
+            wal_path: wal_path.into(),
+            input: InputOptions::default(),
+            engine_config: EngineConfig::default(),
+            save_state: None,
+        }
+    }
+
+    /// Set the input options, or just the format of the input file
+    pub fn with_input(mut self, input: impl Into<InputOptions>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    /// Set the transaction engine configuration
+    ///
+    /// The configuration should not change between runs on the same log, or
+    /// replay may not reproduce the original outcome of every record.
+    pub fn with_engine_config(mut self, engine_config: EngineConfig) -> Self {
+        self.engine_config = engine_config;
+        self
+    }
+
+    /// Write the final engine snapshot to a state file (`--save-state`)
+    pub fn with_save_state(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_state = Some(path.into());
+        self
+    }
+}
+
+impl ProcessingStrategy for WalProcessingStrategy {
+    /// Recover state from the log, then log and apply transactions from input files
+    ///
+    /// # Arguments
+    ///
+    /// * `input_paths` - Paths to the input files, processed in order
+    /// * `output` - Sink for the account states
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RunSummary)` if processing completed; counts only this run's records
+    /// * `Err(EngineError)` if a fatal error occurred (including a failed log write)
+    fn process_files(
+        &self,
+        input_paths: &[PathBuf],
+        output: &mut dyn AccountSink,
+    ) -> Result<RunSummary, EngineError> {
+        check_inputs(input_paths)?;
+        let mut engine = DurableEngine::open(
+            &self.wal_path,
+            self.input.engine_config(&self.engine_config),
+        )?;
+
+        let mut stages = RecordStages::open(&self.input)?;
+        // Changes continue from the balances recovered from the log
+        stages.seed_changes(&Engine::get_accounts(engine.engine()));
+        let mut standing_orders = StandingOrders::new(&self.input.standing_orders);
+
+        for input_path in input_paths {
+            for result in standing_orders.expand(open_records(
+                input_path,
+                &self.input,
+                input_source(input_paths, input_path),
+            )?) {
+                stages.apply_with(result, |record| engine.process(record))?;
+                stages.record_expired(engine.take_expired_disputes());
+                stages.write_postings(engine.take_postings())?;
+                stages.write_balance_history(engine.take_balance_history())?;
+                stages.cutoff(engine.engine())?;
+            }
+        }
+
+        let mut summary = stages.finish()?;
+        engine.sync()?;
+
+        let accounts = Engine::get_accounts(engine.engine());
+        summary.accounts = AccountTotals::of(&accounts);
+        summary.conservation = Some(Conservation::check(engine.engine().flows(), &accounts));
+        if let Some(path) = &self.save_state {
+            save_state(path, &engine.engine().snapshot())?;
+        }
+        output.write_accounts(&accounts)?;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::{NamedTempFile, TempDir};
+
+    /// Helper function to create a temporary CSV file for testing
+    fn create_temp_csv(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().expect("Failed to create temp file");
+        file.write_all(content.as_bytes())
+            .expect("Failed to write to temp file");
+        file.flush().expect("Failed to flush temp file");
+        file
+    }
+
+    #[test]
+    fn test_wal_strategy_continues_from_log() {
+        let dir = TempDir::new().unwrap();
+        let strategy = WalProcessingStrategy::new(dir.path().join("engine.wal"));
+
+        let first = create_temp_csv("type,client,tx,amount\ndeposit,1,1,100.0\ndeposit,2,2,5.0\n");
+        let mut output = Vec::new();
+        strategy.process(first.path(), &mut output).unwrap();
+
+        let second = create_temp_csv("type,client,tx,amount\ndispute,1,1,\nwithdrawal,2,3,10.0\n");
+        let mut output = Vec::new();
+        let summary = strategy.process(second.path(), &mut output).unwrap();
+
+        assert_eq!(summary.records_read, 2);
+        assert_eq!(summary.transaction_errors, 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,0.0000,100.0000,100.0000,false\n\
+             2,5.0000,0.0000,5.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_wal_strategy_missing_input_leaves_log_untouched() {
+        let dir = TempDir::new().unwrap();
+        let wal_path = dir.path().join("engine.wal");
+        let strategy = WalProcessingStrategy::new(&wal_path);
+
+        let mut output = Vec::new();
+        let result = strategy.process(Path::new("nonexistent.csv"), &mut output);
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to open file"));
+        assert!(!wal_path.exists());
+    }
+}
@@ -3,16 +3,118 @@
 //! This module defines the Account structure and related functionality
 //! for managing client account state.
 
-use super::transaction::ClientId;
+use super::transaction::{ClientId, TransactionId, TransactionType};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Named metadata attached to an account (e.g. `owner`, `kyc_status`)
+///
+/// Keys are sorted so that output columns are deterministic.
+pub type AccountMetadata = BTreeMap<String, String>;
+
+/// Why an account was locked
+///
+/// Written as `chargeback:<tx>`, `admin` or `risk_rule:<name>`, e.g. in the
+/// `lock_reason` output column, and parsed back from the same form.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockReason {
+    /// The chargeback of this transaction locked the account
+    ChargebackTx(TransactionId),
+
+    /// An operator locked the account
+    Admin,
+
+    /// The named risk rule locked the account
+    RiskRule(String),
+}
+
+impl fmt::Display for LockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockReason::ChargebackTx(tx) => write!(f, "chargeback:{}", tx),
+            LockReason::Admin => write!(f, "admin"),
+            LockReason::RiskRule(name) => write!(f, "risk_rule:{}", name),
+        }
+    }
+}
+
+impl FromStr for LockReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid lock reason '{}': expected chargeback:TX, admin or risk_rule:NAME",
+                s
+            )
+        };
+        match s.trim().split_once(':') {
+            Some(("chargeback", tx)) => tx
+                .trim()
+                .parse()
+                .map(LockReason::ChargebackTx)
+                .map_err(|_| invalid()),
+            Some(("risk_rule", name)) if !name.trim().is_empty() => {
+                Ok(LockReason::RiskRule(name.trim().to_string()))
+            }
+            None if s.trim() == "admin" => Ok(LockReason::Admin),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Processing counters of a client, kept by the account managers
+///
+/// Counts the records of the client that find or leave an account: a record
+/// rejected without creating one, e.g. the dispute of an unknown transaction
+/// by a new client, is not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientActivity {
+    /// Records of the client processed, accepted or rejected
+    pub transactions: u64,
+
+    /// Records of the client rejected
+    pub errors: u64,
+
+    /// Disputes of the client's transactions accepted
+    pub disputes: u64,
+
+    /// Transaction ID of the client's last record
+    pub last_tx: Option<TransactionId>,
+}
+
+impl ClientActivity {
+    /// Count a record of the client, accepted if `applied`
+    pub fn record(&mut self, tx_type: TransactionType, tx: TransactionId, applied: bool) {
+        self.transactions += 1;
+        if !applied {
+            self.errors += 1;
+        } else if tx_type == TransactionType::Dispute {
+            self.disputes += 1;
+        }
+        self.last_tx = Some(tx);
+    }
+
+    /// Count the last record, already counted as accepted, as rejected
+    ///
+    /// For a deposit or withdrawal undone after it was counted.
+    pub fn reject_last(&mut self) {
+        self.errors += 1;
+    }
+}
 
 /// Client account state
 ///
 /// Represents the current state of a client's account, including
 /// available funds, held funds (due to disputes), and locked status.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Account {
-    /// The client ID (u16: 0-65,535)
+    /// The client ID (see `ClientId` for the range)
     pub client: ClientId,
 
     /// Funds available for withdrawal or trading
@@ -39,6 +141,24 @@ pub struct Account {
     ///
     /// Once an account is locked, all subsequent transactions are rejected.
     pub locked: bool,
+
+    /// Why the account was locked, if it is locked and the reason is known
+    ///
+    /// Accounts read back from an output file without a `lock_reason`
+    /// column are locked without a reason.
+    pub lock_reason: Option<LockReason>,
+
+    /// Counters of the client's records (see `ClientActivity`)
+    ///
+    /// Accounts read back from an output file or a SQLite ledger start with
+    /// zero counters.
+    pub activity: ClientActivity,
+
+    /// Optional named metadata, loaded from an accounts metadata file
+    ///
+    /// Shared rather than owned, since accounts are cloned frequently and
+    /// metadata never changes during processing.
+    pub metadata: Option<Arc<AccountMetadata>>,
 }
 
 impl Account {
@@ -54,7 +174,9 @@ impl Account {
     /// - available = 0.0000
     /// - held = 0.0000
     /// - total = 0.0000
-    /// - locked = false
+    /// - locked = false, with no lock reason
+    /// - zero activity counters
+    /// - no metadata
     pub fn new(client: ClientId) -> Self {
         Account {
             client,
@@ -62,6 +184,73 @@ impl Account {
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: false,
+            lock_reason: None,
+            activity: ClientActivity::default(),
+            metadata: None,
+        }
+    }
+
+    /// Lock the account for `reason`
+    ///
+    /// An account that is already locked keeps the reason it was first
+    /// locked for.
+    pub fn lock(&mut self, reason: LockReason) {
+        if !self.locked {
+            self.locked = true;
+            self.lock_reason = Some(reason);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::chargeback(LockReason::ChargebackTx(7), "chargeback:7")]
+    #[case::admin(LockReason::Admin, "admin")]
+    #[case::risk_rule(LockReason::RiskRule("velocity".to_string()), "risk_rule:velocity")]
+    fn test_lock_reason_round_trip(#[case] reason: LockReason, #[case] text: &str) {
+        assert_eq!(reason.to_string(), text);
+        assert_eq!(text.parse::<LockReason>(), Ok(reason));
+    }
+
+    #[rstest]
+    #[case("chargeback:x")]
+    #[case("risk_rule:")]
+    #[case("admin:1")]
+    #[case("frozen")]
+    fn test_lock_reason_rejects_invalid(#[case] text: &str) {
+        assert!(text.parse::<LockReason>().is_err());
+    }
+
+    #[test]
+    fn test_lock_keeps_first_reason() {
+        let mut account = Account::new(1);
+        account.lock(LockReason::ChargebackTx(3));
+        account.lock(LockReason::Admin);
+
+        assert!(account.locked);
+        assert_eq!(account.lock_reason, Some(LockReason::ChargebackTx(3)));
+    }
+
+    #[test]
+    fn test_activity_counts_records() {
+        let mut activity = ClientActivity::default();
+        activity.record(TransactionType::Deposit, 1, true);
+        activity.record(TransactionType::Dispute, 1, true);
+        activity.record(TransactionType::Dispute, 1, false);
+        activity.record(TransactionType::Withdrawal, 4, false);
+
+        assert_eq!(
+            activity,
+            ClientActivity {
+                transactions: 4,
+                errors: 2,
+                disputes: 1,
+                last_tx: Some(4),
+            }
+        );
+    }
+}
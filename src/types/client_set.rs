@@ -0,0 +1,98 @@
+//! Sets of client IDs selected on the command line
+//!
+//! A `ClientSet` is parsed from a comma-separated list of client IDs and
+//! inclusive ranges, such as `1,2,7-20`, and is used to restrict the output
+//! (and optionally the processing) to a few clients.
+
+use crate::types::ClientId;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Set of client IDs, stored as inclusive ranges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientSet {
+    ranges: Vec<RangeInclusive<ClientId>>,
+}
+
+impl ClientSet {
+    /// Whether the set contains the client
+    pub fn contains(&self, client: ClientId) -> bool {
+        self.ranges.iter().any(|range| range.contains(&client))
+    }
+}
+
+impl FromStr for ClientSet {
+    type Err = String;
+
+    /// Parse a list such as `1,2,7-20`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ClientSet)` - If every entry is a client ID or an ascending range
+    /// * `Err(String)` - If the list is empty or an entry is invalid
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parse_id = |id: &str| {
+            id.trim()
+                .parse::<ClientId>()
+                .map_err(|_| format!("'{}' is not a valid client ID", id.trim()))
+        };
+
+        let ranges = value
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| match entry.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse_id(start)?, parse_id(end)?);
+                    if start > end {
+                        return Err(format!("client range '{}' is descending", entry.trim()));
+                    }
+                    Ok(start..=end)
+                }
+                None => parse_id(entry).map(|id| id..=id),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if ranges.is_empty() {
+            return Err("no clients given".to_string());
+        }
+        Ok(Self { ranges })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::single("42", &[42], &[41, 43])]
+    #[case::list("1,2,7-20", &[1, 2, 7, 13, 20], &[0, 3, 6, 21])]
+    #[case::whitespace(" 1 , 5 - 6 ", &[1, 5, 6], &[4, 7])]
+    #[case::trailing_comma("3,", &[3], &[4])]
+    fn test_parse_and_contains(
+        #[case] value: &str,
+        #[case] included: &[ClientId],
+        #[case] excluded: &[ClientId],
+    ) {
+        let clients: ClientSet = value.parse().unwrap();
+        for client in included {
+            assert!(clients.contains(*client), "{client} should be selected");
+        }
+        for client in excluded {
+            assert!(
+                !clients.contains(*client),
+                "{client} should not be selected"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::not_a_number("abc")]
+    #[case::negative("-1")]
+    #[case::descending("20-7")]
+    #[case::open_range("5-")]
+    fn test_parse_invalid(#[case] value: &str) {
+        assert!(value.parse::<ClientSet>().is_err());
+    }
+}
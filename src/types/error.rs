@@ -9,8 +9,14 @@
 //! - **CSV Parsing Errors**: Malformed CSV, invalid data types, etc.
 //! - **Transaction Errors**: Insufficient funds, account locked, invalid references, etc.
 //! - **Arithmetic Errors**: Overflow, underflow in balance calculations
+//!
+//! These are `PaymentError`s. Errors that end a whole processing run are
+//! `EngineError`s, which wrap them along with I/O and runtime failures.
 
+use crate::types::{ClientId, TransactionId};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
 /// Main error type for the payments engine
@@ -18,7 +24,12 @@ use thiserror::Error;
 /// This enum represents all possible errors that can occur during
 /// transaction processing. Each variant includes relevant context
 /// to help diagnose and resolve the issue.
-#[derive(Debug, Clone, PartialEq, Error)]
+///
+/// Serializes as an object tagged with the variant name under `"kind"`
+/// (the same name `kind()` returns) next to the variant's fields, e.g.
+/// `{"kind":"AccountLocked","client":42}`.
+#[derive(Debug, Clone, PartialEq, Error, Serialize, Deserialize)]
+#[serde(tag = "kind")]
 pub enum PaymentError {
     /// File not found at the specified path
     ///
@@ -59,7 +70,7 @@ pub enum PaymentError {
         /// The invalid transaction type string
         tx_type: String,
         /// Transaction ID (if available)
-        tx: Option<u32>,
+        tx: Option<TransactionId>,
     },
 
     /// Amount field is missing for a transaction that requires it
@@ -71,9 +82,9 @@ pub enum PaymentError {
         /// Transaction type that requires an amount
         tx_type: String,
         /// Transaction ID
-        tx: u32,
+        tx: TransactionId,
         /// Client ID
-        client: u16,
+        client: ClientId,
     },
 
     /// Invalid amount value (negative or malformed)
@@ -84,7 +95,7 @@ pub enum PaymentError {
         /// The invalid amount string
         amount: String,
         /// Transaction ID
-        tx: u32,
+        tx: TransactionId,
     },
 
     /// Insufficient funds for withdrawal
@@ -96,7 +107,7 @@ pub enum PaymentError {
     )]
     InsufficientFunds {
         /// Client ID
-        client: u16,
+        client: ClientId,
         /// Available balance
         available: Decimal,
         /// Requested withdrawal amount
@@ -109,7 +120,7 @@ pub enum PaymentError {
     #[error("Account {client} is locked")]
     AccountLocked {
         /// Client ID of the locked account
-        client: u16,
+        client: ClientId,
     },
 
     /// Arithmetic overflow would occur
@@ -121,7 +132,7 @@ pub enum PaymentError {
         /// Operation that would overflow
         operation: String,
         /// Client ID
-        client: u16,
+        client: ClientId,
     },
 
     /// Arithmetic underflow would occur
@@ -133,7 +144,7 @@ pub enum PaymentError {
         /// Operation that would underflow
         operation: String,
         /// Client ID
-        client: u16,
+        client: ClientId,
     },
 
     /// Transaction not found for dispute operation
@@ -143,7 +154,7 @@ pub enum PaymentError {
     #[error("Transaction {tx} not found for {operation}")]
     TransactionNotFound {
         /// Transaction ID that was not found
-        tx: u32,
+        tx: TransactionId,
         /// Operation that failed
         operation: String,
     },
@@ -154,9 +165,9 @@ pub enum PaymentError {
     #[error("Transaction {tx} for client {client} is already under dispute")]
     TransactionAlreadyDisputed {
         /// Transaction ID
-        tx: u32,
+        tx: TransactionId,
         /// Client ID
-        client: u16,
+        client: ClientId,
     },
 
     /// Transaction is not under dispute
@@ -165,13 +176,52 @@ pub enum PaymentError {
     #[error("Transaction {tx} for client {client} is not under dispute ({operation})")]
     TransactionNotDisputed {
         /// Transaction ID
-        tx: u32,
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Operation that failed
+        operation: String,
+    },
+
+    /// Transaction has already been charged back
+    ///
+    /// A chargeback is final: the transaction cannot be disputed, resolved or
+    /// charged back again. This is a recoverable error - the operation is rejected.
+    #[error("Transaction {tx} for client {client} has already been charged back ({operation})")]
+    TransactionChargedBack {
+        /// Transaction ID
+        tx: TransactionId,
         /// Client ID
-        client: u16,
+        client: ClientId,
         /// Operation that failed
         operation: String,
     },
 
+    /// Resolved transaction cannot be disputed again
+    ///
+    /// The re-dispute policy does not allow disputing resolved transactions.
+    /// This is a recoverable error - the dispute is rejected.
+    #[error("Transaction {tx} for client {client} was resolved and cannot be disputed again")]
+    RedisputeNotAllowed {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+    },
+
+    /// Resolved transaction has been re-disputed the maximum number of times
+    ///
+    /// This is a recoverable error - the dispute is rejected.
+    #[error("Transaction {tx} for client {client} has reached the limit of {limit} re-dispute(s)")]
+    RedisputeLimitReached {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Maximum number of re-disputes allowed
+        limit: u32,
+    },
+
     /// Client mismatch in dispute operation
     ///
     /// The client ID in the dispute/resolve/chargeback doesn't match
@@ -180,11 +230,11 @@ pub enum PaymentError {
     #[error("Client mismatch for {operation} on transaction {tx}: expected client {expected_client}, got client {actual_client}")]
     ClientMismatch {
         /// Transaction ID
-        tx: u32,
+        tx: TransactionId,
         /// Expected client ID (from original transaction)
-        expected_client: u16,
+        expected_client: ClientId,
         /// Actual client ID (from dispute operation)
-        actual_client: u16,
+        actual_client: ClientId,
         /// Operation that failed
         operation: String,
     },
@@ -195,7 +245,7 @@ pub enum PaymentError {
     #[error("Insufficient held funds for {operation} on client {client}: held {held}, requested {requested}")]
     InsufficientHeldFunds {
         /// Client ID
-        client: u16,
+        client: ClientId,
         /// Held balance
         held: Decimal,
         /// Requested amount
@@ -210,7 +260,7 @@ pub enum PaymentError {
     #[error("Insufficient available funds for {operation} on client {client}: available {available}, requested {requested}")]
     InsufficientAvailableFunds {
         /// Client ID
-        client: u16,
+        client: ClientId,
         /// Available balance
         available: Decimal,
         /// Requested amount
@@ -226,10 +276,250 @@ pub enum PaymentError {
     #[error("Duplicate transaction ID {tx} for client {client}")]
     DuplicateTransaction {
         /// Transaction ID that is duplicated
-        tx: u32,
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+    },
+
+    /// Withdrawal rejected by a risk rule on account metadata
+    ///
+    /// This is a recoverable error - the withdrawal is rejected and the
+    /// account is left unchanged.
+    #[error("Withdrawal blocked for client {client}: {reason}")]
+    WithdrawalBlocked {
+        /// Client ID
+        client: ClientId,
+        /// Rule that blocked the withdrawal
+        reason: String,
+    },
+
+    /// Deposit or withdrawal amount above the configured maximum
+    ///
+    /// This is a recoverable error - the transaction is rejected.
+    #[error("{operation} of {amount} in transaction {tx} for client {client} exceeds the maximum of {limit}")]
+    AmountLimitExceeded {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Operation that was rejected (`deposit` or `withdrawal`)
+        operation: String,
+        /// Requested amount
+        amount: Decimal,
+        /// Maximum amount per transaction
+        limit: Decimal,
+    },
+
+    /// Deposit that would raise a client's total above the configured maximum
+    ///
+    /// This is a recoverable error - the deposit is rejected.
+    #[error("Deposit of {amount} in transaction {tx} would raise the total of client {client} from {total} above the maximum of {limit}")]
+    BalanceLimitExceeded {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Total balance before the deposit
+        total: Decimal,
+        /// Requested amount
+        amount: Decimal,
+        /// Maximum total balance per client
+        limit: Decimal,
+    },
+
+    /// Withdrawal above the configured velocity limit of its client
+    ///
+    /// This is a recoverable error - the withdrawal is rejected.
+    #[error("Withdrawal {tx} for client {client} exceeds the velocity limit of {limit}")]
+    VelocityLimitExceeded {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// The limit, e.g. `3 withdrawals per 10 transactions`
+        limit: String,
+    },
+
+    /// Withdrawal is pending approval
+    ///
+    /// A withdrawal held for approval cannot be disputed, resolved or charged
+    /// back until it is approved. This is a recoverable error - the operation
+    /// is rejected.
+    #[error("Withdrawal {tx} for client {client} is pending approval ({operation})")]
+    WithdrawalPendingApproval {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Operation that failed
+        operation: String,
+    },
+
+    /// Transaction is not a withdrawal pending approval
+    ///
+    /// This is a recoverable error - the approve/reject is ignored.
+    #[error("Transaction {tx} for client {client} is not pending approval ({operation})")]
+    WithdrawalNotPendingApproval {
+        /// Transaction ID
+        tx: TransactionId,
         /// Client ID
-        client: u16,
+        client: ClientId,
+        /// Operation that failed
+        operation: String,
+    },
+
+    /// Transaction type cannot be disputed
+    ///
+    /// The disputable types configured for the scheme do not include the
+    /// referenced transaction's type. This is a recoverable error - the
+    /// dispute is rejected.
+    #[error("Transaction {tx} for client {client} is a {tx_type}, which cannot be disputed")]
+    TransactionNotDisputable {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Type of the referenced transaction
+        tx_type: String,
+    },
+
+    /// Withdrawal approval was rejected
+    ///
+    /// A rejection is final: the withdrawal cannot be approved, rejected or
+    /// disputed afterwards. This is a recoverable error - the operation is rejected.
+    #[error("Withdrawal {tx} for client {client} was rejected ({operation})")]
+    WithdrawalRejected {
+        /// Transaction ID
+        tx: TransactionId,
+        /// Client ID
+        client: ClientId,
+        /// Operation that failed
+        operation: String,
+    },
+}
+
+/// Broad category of a `PaymentError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorCategory {
+    /// The run cannot continue (missing file, I/O failure)
+    Fatal,
+    /// The record is malformed or inconsistent with the transaction it
+    /// references, and is skipped
+    Validation,
+    /// The record is well-formed but rejected by an account rule
+    /// (insufficient funds, locked account, dispute lifecycle)
+    Business,
+    /// Processing the record failed for another reason, such as arithmetic
+    /// overflow, and it is skipped
+    Recoverable,
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorCategory::Fatal => "fatal",
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Business => "business",
+            ErrorCategory::Recoverable => "recoverable",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Fatal error that stops a processing run
+///
+/// Returned by the processing strategies. Unlike the recoverable
+/// `PaymentError`s of individual records, which are counted in the run
+/// summary, an `EngineError` ends the run. I/O and runtime failures keep the
+/// underlying error as their `source()`.
+#[derive(Debug, Error)]
+pub enum EngineError {
+    /// An error of the engine that cannot be skipped
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+
+    /// Reading an input or writing an output failed
+    #[error("{context}: {source}")]
+    Io {
+        /// What was being done, e.g. `Failed to open file 'input.csv'`
+        context: String,
+        /// The underlying I/O error
+        #[source]
+        source: std::io::Error,
     },
+
+    /// The runtime for concurrent processing could not be set up
+    #[error("Failed to create tokio runtime: {0}")]
+    Runtime(#[source] std::io::Error),
+
+    /// Any other fatal error, such as an invalid CSV header or a state file
+    /// that cannot be written, described by its message
+    #[error("{0}")]
+    Other(String),
+}
+
+impl EngineError {
+    /// Create an Io error with a description of the failed operation
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        EngineError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+/// Invalid processing configuration
+///
+/// Returned by `BatchConfigBuilder::build` for settings that are out of range
+/// or inconsistent with each other.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ConfigError {
+    /// A setting that must be positive is zero
+    #[error("{field} must be greater than zero")]
+    Zero {
+        /// Name of the setting
+        field: &'static str,
+    },
+
+    /// A setting exceeds its upper bound
+    #[error("{field} ({value}) exceeds the maximum of {max}")]
+    TooLarge {
+        /// Name of the setting
+        field: &'static str,
+        /// The configured value
+        value: usize,
+        /// The largest allowed value
+        max: usize,
+    },
+
+    /// A rate is not strictly between 0 and 1
+    #[error("{field} ({value}) must be between 0 and 1")]
+    InvalidRate {
+        /// Name of the setting
+        field: &'static str,
+        /// The configured value
+        value: f64,
+    },
+
+    /// Batches are smaller than the expected number of clients, so most
+    /// batches would hold a single transaction per client
+    #[error(
+        "batch_size ({batch_size}) is smaller than the expected number of clients ({clients})"
+    )]
+    BatchSmallerThanClients {
+        /// The configured batch size
+        batch_size: usize,
+        /// The expected number of clients
+        clients: usize,
+    },
+}
+
+// Most fatal errors of the readers and writers are plain messages
+impl From<String> for EngineError {
+    fn from(message: String) -> Self {
+        EngineError::Other(message)
+    }
 }
 
 // Conversion from io::Error to PaymentError
@@ -257,8 +547,99 @@ impl From<csv::Error> for PaymentError {
 // Helper functions for creating common errors
 
 impl PaymentError {
+    /// Name of the error variant, e.g. `"InsufficientFunds"`
+    ///
+    /// Used to count errors by kind in the run summary.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PaymentError::FileNotFound { .. } => "FileNotFound",
+            PaymentError::IoError { .. } => "IoError",
+            PaymentError::ParseError { .. } => "ParseError",
+            PaymentError::InvalidTransactionType { .. } => "InvalidTransactionType",
+            PaymentError::MissingAmount { .. } => "MissingAmount",
+            PaymentError::InvalidAmount { .. } => "InvalidAmount",
+            PaymentError::InsufficientFunds { .. } => "InsufficientFunds",
+            PaymentError::AccountLocked { .. } => "AccountLocked",
+            PaymentError::ArithmeticOverflow { .. } => "ArithmeticOverflow",
+            PaymentError::ArithmeticUnderflow { .. } => "ArithmeticUnderflow",
+            PaymentError::TransactionNotFound { .. } => "TransactionNotFound",
+            PaymentError::TransactionAlreadyDisputed { .. } => "TransactionAlreadyDisputed",
+            PaymentError::TransactionNotDisputed { .. } => "TransactionNotDisputed",
+            PaymentError::TransactionChargedBack { .. } => "TransactionChargedBack",
+            PaymentError::RedisputeNotAllowed { .. } => "RedisputeNotAllowed",
+            PaymentError::RedisputeLimitReached { .. } => "RedisputeLimitReached",
+            PaymentError::ClientMismatch { .. } => "ClientMismatch",
+            PaymentError::InsufficientHeldFunds { .. } => "InsufficientHeldFunds",
+            PaymentError::InsufficientAvailableFunds { .. } => "InsufficientAvailableFunds",
+            PaymentError::DuplicateTransaction { .. } => "DuplicateTransaction",
+            PaymentError::WithdrawalBlocked { .. } => "WithdrawalBlocked",
+            PaymentError::AmountLimitExceeded { .. } => "AmountLimitExceeded",
+            PaymentError::BalanceLimitExceeded { .. } => "BalanceLimitExceeded",
+            PaymentError::VelocityLimitExceeded { .. } => "VelocityLimitExceeded",
+            PaymentError::WithdrawalPendingApproval { .. } => "WithdrawalPendingApproval",
+            PaymentError::WithdrawalNotPendingApproval { .. } => "WithdrawalNotPendingApproval",
+            PaymentError::WithdrawalRejected { .. } => "WithdrawalRejected",
+            PaymentError::TransactionNotDisputable { .. } => "TransactionNotDisputable",
+        }
+    }
+
+    /// Stable numeric code of the error, e.g. `301` for `InsufficientFunds`
+    ///
+    /// The hundreds digit is the category: 1xx fatal, 2xx validation,
+    /// 3xx business and 4xx recoverable. Codes are never reused, so
+    /// downstream systems can branch on them instead of the message.
+    pub fn code(&self) -> u16 {
+        match self {
+            PaymentError::FileNotFound { .. } => 101,
+            PaymentError::IoError { .. } => 102,
+            PaymentError::ParseError { .. } => 201,
+            PaymentError::InvalidTransactionType { .. } => 202,
+            PaymentError::MissingAmount { .. } => 203,
+            PaymentError::InvalidAmount { .. } => 204,
+            PaymentError::TransactionNotFound { .. } => 205,
+            PaymentError::ClientMismatch { .. } => 206,
+            PaymentError::DuplicateTransaction { .. } => 207,
+            PaymentError::InsufficientFunds { .. } => 301,
+            PaymentError::AccountLocked { .. } => 302,
+            PaymentError::TransactionAlreadyDisputed { .. } => 303,
+            PaymentError::TransactionNotDisputed { .. } => 304,
+            PaymentError::TransactionChargedBack { .. } => 305,
+            PaymentError::RedisputeNotAllowed { .. } => 306,
+            PaymentError::RedisputeLimitReached { .. } => 307,
+            PaymentError::InsufficientHeldFunds { .. } => 308,
+            PaymentError::InsufficientAvailableFunds { .. } => 309,
+            PaymentError::WithdrawalBlocked { .. } => 310,
+            PaymentError::AmountLimitExceeded { .. } => 311,
+            PaymentError::BalanceLimitExceeded { .. } => 312,
+            PaymentError::VelocityLimitExceeded { .. } => 313,
+            PaymentError::WithdrawalPendingApproval { .. } => 314,
+            PaymentError::WithdrawalNotPendingApproval { .. } => 315,
+            PaymentError::WithdrawalRejected { .. } => 316,
+            PaymentError::TransactionNotDisputable { .. } => 317,
+            PaymentError::ArithmeticOverflow { .. } => 401,
+            PaymentError::ArithmeticUnderflow { .. } => 402,
+        }
+    }
+
+    /// Category of the error, derived from its code
+    pub fn category(&self) -> ErrorCategory {
+        match self.code() / 100 {
+            1 => ErrorCategory::Fatal,
+            2 => ErrorCategory::Validation,
+            3 => ErrorCategory::Business,
+            _ => ErrorCategory::Recoverable,
+        }
+    }
+
+    /// The message prefixed with the code, e.g. `[E302] Account 42 is locked`
+    ///
+    /// Used wherever errors are reported to stderr or an error sink.
+    pub fn with_code(&self) -> String {
+        format!("[E{}] {}", self.code(), self)
+    }
+
     /// Create an InsufficientFunds error
-    pub fn insufficient_funds(client: u16, available: Decimal, requested: Decimal) -> Self {
+    pub fn insufficient_funds(client: ClientId, available: Decimal, requested: Decimal) -> Self {
         PaymentError::InsufficientFunds {
             client,
             available,
@@ -267,12 +648,12 @@ impl PaymentError {
     }
 
     /// Create an AccountLocked error
-    pub fn account_locked(client: u16) -> Self {
+    pub fn account_locked(client: ClientId) -> Self {
         PaymentError::AccountLocked { client }
     }
 
     /// Create a TransactionNotFound error
-    pub fn transaction_not_found(tx: u32, operation: &str) -> Self {
+    pub fn transaction_not_found(tx: TransactionId, operation: &str) -> Self {
         PaymentError::TransactionNotFound {
             tx,
             operation: operation.to_string(),
@@ -281,9 +662,9 @@ impl PaymentError {
 
     /// Create a ClientMismatch error
     pub fn client_mismatch(
-        tx: u32,
-        expected_client: u16,
-        actual_client: u16,
+        tx: TransactionId,
+        expected_client: ClientId,
+        actual_client: ClientId,
         operation: &str,
     ) -> Self {
         PaymentError::ClientMismatch {
@@ -295,12 +676,12 @@ impl PaymentError {
     }
 
     /// Create a TransactionAlreadyDisputed error
-    pub fn transaction_already_disputed(tx: u32, client: u16) -> Self {
+    pub fn transaction_already_disputed(tx: TransactionId, client: ClientId) -> Self {
         PaymentError::TransactionAlreadyDisputed { tx, client }
     }
 
     /// Create a TransactionNotDisputed error
-    pub fn transaction_not_disputed(tx: u32, client: u16, operation: &str) -> Self {
+    pub fn transaction_not_disputed(tx: TransactionId, client: ClientId, operation: &str) -> Self {
         PaymentError::TransactionNotDisputed {
             tx,
             client,
@@ -308,8 +689,27 @@ impl PaymentError {
         }
     }
 
+    /// Create a TransactionChargedBack error
+    pub fn transaction_charged_back(tx: TransactionId, client: ClientId, operation: &str) -> Self {
+        PaymentError::TransactionChargedBack {
+            tx,
+            client,
+            operation: operation.to_string(),
+        }
+    }
+
+    /// Create a RedisputeNotAllowed error
+    pub fn redispute_not_allowed(tx: TransactionId, client: ClientId) -> Self {
+        PaymentError::RedisputeNotAllowed { tx, client }
+    }
+
+    /// Create a RedisputeLimitReached error
+    pub fn redispute_limit_reached(tx: TransactionId, client: ClientId, limit: u32) -> Self {
+        PaymentError::RedisputeLimitReached { tx, client, limit }
+    }
+
     /// Create an ArithmeticOverflow error
-    pub fn arithmetic_overflow(operation: &str, client: u16) -> Self {
+    pub fn arithmetic_overflow(operation: &str, client: ClientId) -> Self {
         PaymentError::ArithmeticOverflow {
             operation: operation.to_string(),
             client,
@@ -317,7 +717,7 @@ impl PaymentError {
     }
 
     /// Create an ArithmeticUnderflow error
-    pub fn arithmetic_underflow(operation: &str, client: u16) -> Self {
+    pub fn arithmetic_underflow(operation: &str, client: ClientId) -> Self {
         PaymentError::ArithmeticUnderflow {
             operation: operation.to_string(),
             client,
@@ -325,7 +725,7 @@ impl PaymentError {
     }
 
     /// Create a MissingAmount error
-    pub fn missing_amount(tx_type: &str, tx: u32, client: u16) -> Self {
+    pub fn missing_amount(tx_type: &str, tx: TransactionId, client: ClientId) -> Self {
         PaymentError::MissingAmount {
             tx_type: tx_type.to_string(),
             tx,
@@ -334,7 +734,7 @@ impl PaymentError {
     }
 
     /// Create an InvalidAmount error
-    pub fn invalid_amount(amount: &str, tx: u32) -> Self {
+    pub fn invalid_amount(amount: &str, tx: TransactionId) -> Self {
         PaymentError::InvalidAmount {
             amount: amount.to_string(),
             tx,
@@ -342,7 +742,7 @@ impl PaymentError {
     }
 
     /// Create an InvalidTransactionType error
-    pub fn invalid_transaction_type(tx_type: &str, tx: Option<u32>) -> Self {
+    pub fn invalid_transaction_type(tx_type: &str, tx: Option<TransactionId>) -> Self {
         PaymentError::InvalidTransactionType {
             tx_type: tx_type.to_string(),
             tx,
@@ -351,7 +751,7 @@ impl PaymentError {
 
     /// Create an InsufficientHeldFunds error
     pub fn insufficient_held_funds(
-        client: u16,
+        client: ClientId,
         held: Decimal,
         requested: Decimal,
         operation: &str,
@@ -366,7 +766,7 @@ impl PaymentError {
 
     /// Create an InsufficientAvailableFunds error
     pub fn insufficient_available_funds(
-        client: u16,
+        client: ClientId,
         available: Decimal,
         requested: Decimal,
         operation: &str,
@@ -380,9 +780,115 @@ impl PaymentError {
     }
 
     /// Create a DuplicateTransaction error
-    pub fn duplicate_transaction(tx: u32, client: u16) -> Self {
+    pub fn duplicate_transaction(tx: TransactionId, client: ClientId) -> Self {
         PaymentError::DuplicateTransaction { tx, client }
     }
+
+    /// Create a WithdrawalBlocked error
+    pub fn withdrawal_blocked(client: ClientId, reason: &str) -> Self {
+        PaymentError::WithdrawalBlocked {
+            client,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Create an AmountLimitExceeded error
+    pub fn amount_limit_exceeded(
+        tx: TransactionId,
+        client: ClientId,
+        operation: &str,
+        amount: Decimal,
+        limit: Decimal,
+    ) -> Self {
+        PaymentError::AmountLimitExceeded {
+            tx,
+            client,
+            operation: operation.to_string(),
+            amount,
+            limit,
+        }
+    }
+
+    /// Create a BalanceLimitExceeded error
+    pub fn balance_limit_exceeded(
+        tx: TransactionId,
+        client: ClientId,
+        total: Decimal,
+        amount: Decimal,
+        limit: Decimal,
+    ) -> Self {
+        PaymentError::BalanceLimitExceeded {
+            tx,
+            client,
+            total,
+            amount,
+            limit,
+        }
+    }
+
+    /// Create a VelocityLimitExceeded error
+    pub fn velocity_limit_exceeded(tx: TransactionId, client: ClientId, limit: &str) -> Self {
+        PaymentError::VelocityLimitExceeded {
+            tx,
+            client,
+            limit: limit.to_string(),
+        }
+    }
+
+    /// Create a WithdrawalPendingApproval error
+    pub fn withdrawal_pending(tx: TransactionId, client: ClientId, operation: &str) -> Self {
+        PaymentError::WithdrawalPendingApproval {
+            tx,
+            client,
+            operation: operation.to_string(),
+        }
+    }
+
+    /// Create a WithdrawalNotPendingApproval error
+    pub fn withdrawal_not_pending(tx: TransactionId, client: ClientId, operation: &str) -> Self {
+        PaymentError::WithdrawalNotPendingApproval {
+            tx,
+            client,
+            operation: operation.to_string(),
+        }
+    }
+
+    /// Create a WithdrawalRejected error
+    pub fn withdrawal_rejected(tx: TransactionId, client: ClientId, operation: &str) -> Self {
+        PaymentError::WithdrawalRejected {
+            tx,
+            client,
+            operation: operation.to_string(),
+        }
+    }
+
+    /// Create a TransactionNotDisputable error
+    pub fn transaction_not_disputable(tx: TransactionId, client: ClientId, tx_type: &str) -> Self {
+        PaymentError::TransactionNotDisputable {
+            tx,
+            client,
+            tx_type: tx_type.to_string(),
+        }
+    }
+
+    /// Whether the error is a rejection by a configured amount or velocity limit
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self,
+            PaymentError::AmountLimitExceeded { .. }
+                | PaymentError::BalanceLimitExceeded { .. }
+                | PaymentError::VelocityLimitExceeded { .. }
+        )
+    }
+
+    /// Whether the error may go away if the transaction is processed again
+    ///
+    /// The engine's verdicts are deterministic: a transaction it rejects is
+    /// rejected again on every retry, so they are permanent. Only I/O errors,
+    /// such as a store backed by a database failing to answer, are transient.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, PaymentError::IoError { .. })
+    }
 }
 
 #[cfg(test)]
@@ -391,6 +897,29 @@ mod tests {
     use rstest::rstest;
     use rust_decimal::Decimal;
 
+    #[rstest]
+    #[case::insufficient_funds(
+        PaymentError::insufficient_funds(1, Decimal::ZERO, Decimal::ONE),
+        "InsufficientFunds"
+    )]
+    #[case::account_locked(PaymentError::account_locked(1), "AccountLocked")]
+    #[case::duplicate(PaymentError::duplicate_transaction(1, 1), "DuplicateTransaction")]
+    fn test_kind(#[case] error: PaymentError, #[case] expected: &str) {
+        assert_eq!(error.kind(), expected);
+    }
+
+    #[rstest]
+    #[case::io(PaymentError::IoError { message: "connection reset".to_string() }, true)]
+    #[case::insufficient_funds(
+        PaymentError::insufficient_funds(1, Decimal::ZERO, Decimal::ONE),
+        false
+    )]
+    #[case::account_locked(PaymentError::account_locked(1), false)]
+    #[case::overflow(PaymentError::ArithmeticOverflow { client: 1, operation: "deposit".to_string() }, false)]
+    fn test_is_transient(#[case] error: PaymentError, #[case] expected: bool) {
+        assert_eq!(error.is_transient(), expected);
+    }
+
     #[rstest]
     #[case::file_not_found(
         PaymentError::FileNotFound { path: "test.csv".to_string() },
@@ -432,10 +961,56 @@ mod tests {
         PaymentError::TransactionNotFound { tx: 999, operation: "dispute".to_string() },
         "Transaction 999 not found for dispute"
     )]
+    #[case::transaction_charged_back(
+        PaymentError::TransactionChargedBack { tx: 7, client: 1, operation: "dispute".to_string() },
+        "Transaction 7 for client 1 has already been charged back (dispute)"
+    )]
+    #[case::redispute_not_allowed(
+        PaymentError::RedisputeNotAllowed { tx: 7, client: 1 },
+        "Transaction 7 for client 1 was resolved and cannot be disputed again"
+    )]
+    #[case::redispute_limit_reached(
+        PaymentError::RedisputeLimitReached { tx: 7, client: 1, limit: 2 },
+        "Transaction 7 for client 1 has reached the limit of 2 re-dispute(s)"
+    )]
     #[case::client_mismatch(
         PaymentError::ClientMismatch { tx: 123, expected_client: 1, actual_client: 2, operation: "dispute".to_string() },
         "Client mismatch for dispute on transaction 123: expected client 1, got client 2"
     )]
+    #[case::withdrawal_blocked(
+        PaymentError::WithdrawalBlocked { client: 7, reason: "requires kyc_status=verified".to_string() },
+        "Withdrawal blocked for client 7: requires kyc_status=verified"
+    )]
+    #[case::amount_limit_exceeded(
+        PaymentError::amount_limit_exceeded(
+            5,
+            1,
+            "deposit",
+            Decimal::from(1_000_000_000_000i64),
+            Decimal::from(10000)
+        ),
+        "deposit of 1000000000000 in transaction 5 for client 1 exceeds the maximum of 10000"
+    )]
+    #[case::balance_limit_exceeded(
+        PaymentError::balance_limit_exceeded(5, 1, Decimal::from(900), Decimal::from(200), Decimal::from(1000)),
+        "Deposit of 200 in transaction 5 would raise the total of client 1 from 900 above the maximum of 1000"
+    )]
+    #[case::withdrawal_pending_approval(
+        PaymentError::withdrawal_pending(7, 1, "dispute"),
+        "Withdrawal 7 for client 1 is pending approval (dispute)"
+    )]
+    #[case::withdrawal_not_pending_approval(
+        PaymentError::withdrawal_not_pending(7, 1, "approve"),
+        "Transaction 7 for client 1 is not pending approval (approve)"
+    )]
+    #[case::withdrawal_rejected(
+        PaymentError::withdrawal_rejected(7, 1, "reject"),
+        "Withdrawal 7 for client 1 was rejected (reject)"
+    )]
+    #[case::transaction_not_disputable(
+        PaymentError::transaction_not_disputable(7, 1, "withdrawal"),
+        "Transaction 7 for client 1 is a withdrawal, which cannot be disputed"
+    )]
     fn test_error_display(#[case] error: PaymentError, #[case] expected: &str) {
         assert_eq!(error.to_string(), expected);
     }
@@ -469,4 +1044,93 @@ mod tests {
         assert!(matches!(error, PaymentError::IoError { .. }));
         assert_eq!(error.to_string(), "I/O error: Permission denied");
     }
+
+    #[rstest]
+    #[case::account_locked(PaymentError::account_locked(42))]
+    #[case::insufficient_funds(PaymentError::insufficient_funds(1, Decimal::ZERO, Decimal::ONE))]
+    #[case::parse_error(PaymentError::ParseError { line: None, message: "bad".to_string() })]
+    #[case::client_mismatch(PaymentError::client_mismatch(7, 1, 2, "dispute"))]
+    fn test_serde_round_trip(#[case] error: PaymentError) {
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["kind"], error.kind());
+        assert_eq!(serde_json::from_value::<PaymentError>(json).unwrap(), error);
+    }
+
+    #[test]
+    fn test_serde_field_names() {
+        let json = serde_json::to_value(PaymentError::insufficient_funds(
+            1,
+            Decimal::ZERO,
+            Decimal::ONE,
+        ))
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "InsufficientFunds",
+                "client": 1,
+                "available": "0",
+                "requested": "1"
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::fatal(PaymentError::FileNotFound { path: "x.csv".to_string() }, 101, ErrorCategory::Fatal)]
+    #[case::validation(
+        PaymentError::client_mismatch(7, 1, 2, "dispute"),
+        206,
+        ErrorCategory::Validation
+    )]
+    #[case::business(PaymentError::account_locked(42), 302, ErrorCategory::Business)]
+    #[case::withdrawal_rejected(
+        PaymentError::withdrawal_rejected(7, 1, "approve"),
+        316,
+        ErrorCategory::Business
+    )]
+    #[case::not_disputable(
+        PaymentError::transaction_not_disputable(7, 1, "withdrawal"),
+        317,
+        ErrorCategory::Business
+    )]
+    #[case::recoverable(PaymentError::ArithmeticOverflow { operation: "deposit".to_string(), client: 1 }, 401, ErrorCategory::Recoverable)]
+    fn test_code_and_category(
+        #[case] error: PaymentError,
+        #[case] code: u16,
+        #[case] category: ErrorCategory,
+    ) {
+        assert_eq!(error.code(), code);
+        assert_eq!(error.category(), category);
+    }
+
+    #[test]
+    fn test_with_code() {
+        assert_eq!(
+            PaymentError::account_locked(42).with_code(),
+            "[E302] Account 42 is locked"
+        );
+    }
+
+    #[test]
+    fn test_engine_error_source() {
+        use std::error::Error as _;
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "No such file");
+        let error = EngineError::io("Failed to open file 'x.csv'", io);
+        assert_eq!(
+            error.to_string(),
+            "Failed to open file 'x.csv': No such file"
+        );
+        assert_eq!(error.source().unwrap().to_string(), "No such file");
+
+        let error = EngineError::from(PaymentError::account_locked(1));
+        assert!(matches!(
+            error,
+            EngineError::Payment(PaymentError::AccountLocked { client: 1 })
+        ));
+        assert_eq!(error.to_string(), "Account 1 is locked");
+
+        let error = EngineError::from("Invalid CSV header".to_string());
+        assert!(error.source().is_none());
+    }
 }
@@ -3,15 +3,18 @@
 //! Contains core data structures used throughout the application.
 //! This module organizes types into logical submodules:
 //! - `account`: Account-related types
+//! - `client_set`: Sets of client IDs for filtering
 //! - `transaction`: Transaction-related types and identifiers
 //! - `error`: Error types for the payments engine
 
 pub mod account;
+pub mod client_set;
 pub mod error;
 pub mod transaction;
 
-pub use account::Account;
-pub use error::PaymentError;
+pub use account::{Account, AccountMetadata, ClientActivity, LockReason};
+pub use client_set::ClientSet;
+pub use error::{ConfigError, EngineError, ErrorCategory, PaymentError};
 pub use transaction::{
-    ClientId, StoredTransaction, TransactionId, TransactionRecord, TransactionType,
+    ClientId, DisputeState, StoredTransaction, TransactionId, TransactionRecord, TransactionType,
 };
@@ -3,25 +3,41 @@
 //! This module defines transaction types, records, and stored transaction data
 //! used throughout the system for processing payments and disputes.
 
+use crate::types::PaymentError;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 /// Client identifier
 ///
-/// Supports client IDs from 0 to 65,535
+/// `u16` by default, supporting client IDs from 0 to 65,535. Enable the
+/// `client-id-u32` or `client-id-u64` feature for larger ID spaces; if both are
+/// enabled the wider type is used.
+#[cfg(not(any(feature = "client-id-u32", feature = "client-id-u64")))]
 pub type ClientId = u16;
 
+/// Client identifier (`client-id-u32` feature: 0 to 4,294,967,295)
+#[cfg(all(feature = "client-id-u32", not(feature = "client-id-u64")))]
+pub type ClientId = u32;
+
+/// Client identifier (`client-id-u64` feature: 0 to 18,446,744,073,709,551,615)
+#[cfg(feature = "client-id-u64")]
+pub type ClientId = u64;
+
 /// Transaction identifier
 ///
-/// Supports transaction IDs from 0 to 4,294,967,295
-pub type TransactionId = u32;
+/// Supports transaction IDs from 0 to 18,446,744,073,709,551,615. Legacy files
+/// limited to 32-bit IDs can be checked with `strategy::InputOptions::legacy_tx_ids`.
+pub type TransactionId = u64;
 
 /// Transaction types supported by the payments engine
 ///
 /// Each variant represents a different operation that can be performed
 /// on client accounts. Deposits and withdrawals modify balances directly,
-/// while disputes, resolves, and chargebacks manage the dispute lifecycle.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// while disputes, resolves, and chargebacks manage the dispute lifecycle,
+/// and approvals and rejections complete withdrawals pending approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     /// Credit funds to an account
@@ -53,6 +69,19 @@ pub enum TransactionType {
     /// Removes held funds, decreases total, and locks the account.
     /// Can only be applied to transactions currently under dispute.
     Chargeback,
+
+    /// Complete a withdrawal pending approval
+    ///
+    /// Removes the withdrawal's held funds and decreases total. Can only be
+    /// applied to withdrawals pending approval
+    /// (`EngineConfig::withdrawal_approval_above`).
+    Approve,
+
+    /// Cancel a withdrawal pending approval
+    ///
+    /// Moves the withdrawal's held funds back to available, keeping total
+    /// unchanged. Can only be applied to withdrawals pending approval.
+    Reject,
 }
 
 /// Input transaction record from CSV
@@ -60,22 +89,188 @@ pub enum TransactionType {
 /// Represents a single transaction as read from the input CSV file.
 /// The amount field is optional because dispute, resolve, and chargeback
 /// operations reference existing transactions and don't specify amounts.
-#[derive(Debug, Clone)]
+///
+/// Serializes with the CSV column names (`type`, `client`, `tx`, `amount`).
+/// Records are compared and hashed without their `line`, so the same record
+/// read from two lines is a duplicate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRecord {
     /// The type of transaction (deposit, withdrawal, dispute, resolve, or chargeback)
+    #[serde(rename = "type")]
     pub tx_type: TransactionType,
 
-    /// The client ID this transaction applies to (u16: 0-65,535)
+    /// The client ID this transaction applies to (see `ClientId` for the range)
     pub client: ClientId,
 
-    /// Unique transaction identifier (u32: 0-4,294,967,295)
+    /// Unique transaction identifier (u64: 0-18,446,744,073,709,551,615)
     pub tx: TransactionId,
 
     /// Transaction amount with 4 decimal places precision
     ///
     /// Required for deposit and withdrawal transactions.
-    /// Should be None for dispute, resolve, and chargeback operations.
+    /// Should be None for dispute, resolve, chargeback, approve and reject
+    /// operations.
     pub amount: Option<Decimal>,
+
+    /// Line of the input the record was read from, if known
+    ///
+    /// Set by the CSV readers so errors can name the line; not serialized.
+    #[serde(skip)]
+    pub line: Option<u64>,
+
+    /// Input the record was read from, if known
+    ///
+    /// Set by the strategies when they read several inputs, so errors and
+    /// dead letters can name the file; not serialized.
+    #[serde(skip)]
+    pub source: Option<Arc<str>>,
+}
+
+impl TransactionRecord {
+    /// Set the line of the input the record was read from
+    pub fn with_line(mut self, line: u64) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Set the input the record was read from
+    pub fn with_source(mut self, source: Arc<str>) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+impl PartialEq for TransactionRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.tx_type == other.tx_type
+            && self.client == other.client
+            && self.tx == other.tx
+            && self.amount == other.amount
+    }
+}
+
+impl Eq for TransactionRecord {}
+
+impl Hash for TransactionRecord {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tx_type.hash(state);
+        self.client.hash(state);
+        self.tx.hash(state);
+        self.amount.hash(state);
+    }
+}
+
+/// Dispute lifecycle state of a stored transaction
+///
+/// ```text
+/// None ──dispute──> Disputed ──resolve───> Resolved ──dispute──> Disputed
+///                      └─────chargeback──> ChargedBack (final)
+///
+/// Pending ──approve──> None
+///    └─────reject────> Rejected (final)
+/// ```
+///
+/// Withdrawals held for approval start as `Pending` and cannot be disputed
+/// until they are approved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeState {
+    /// Never disputed
+    #[default]
+    None,
+
+    /// Under dispute; the transaction's funds are held
+    Disputed,
+
+    /// A dispute was resolved and the held funds released
+    Resolved,
+
+    /// A dispute ended in a chargeback; no further transitions are allowed
+    ChargedBack,
+
+    /// A withdrawal awaiting approval; its funds are held
+    Pending,
+
+    /// A withdrawal whose approval was rejected and its held funds released;
+    /// no further transitions are allowed
+    Rejected,
+}
+
+impl DisputeState {
+    /// Returns true if the transaction is currently under dispute
+    pub fn is_disputed(self) -> bool {
+        self == DisputeState::Disputed
+    }
+
+    /// Compute the state after applying a dispute, resolve or chargeback
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The dispute operation being applied
+    /// * `tx` - Transaction ID, for error reporting
+    /// * `client` - Client ID, for error reporting
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(DisputeState)` - The new state if the transition is allowed
+    /// * `Err(PaymentError)` - If the transition is not allowed from this state
+    ///
+    /// Deposits and withdrawals are not dispute operations and leave the
+    /// state unchanged.
+    pub fn transition(
+        self,
+        operation: TransactionType,
+        tx: TransactionId,
+        client: ClientId,
+    ) -> Result<DisputeState, PaymentError> {
+        match (operation, self) {
+            (TransactionType::Deposit | TransactionType::Withdrawal, state) => Ok(state),
+            (TransactionType::Approve, DisputeState::Pending) => Ok(DisputeState::None),
+            (TransactionType::Reject, DisputeState::Pending) => Ok(DisputeState::Rejected),
+            (operation, DisputeState::Rejected) => Err(PaymentError::withdrawal_rejected(
+                tx,
+                client,
+                operation_name(operation),
+            )),
+            (operation @ (TransactionType::Approve | TransactionType::Reject), _) => Err(
+                PaymentError::withdrawal_not_pending(tx, client, operation_name(operation)),
+            ),
+            (operation, DisputeState::Pending) => Err(PaymentError::withdrawal_pending(
+                tx,
+                client,
+                operation_name(operation),
+            )),
+            (TransactionType::Dispute, DisputeState::None | DisputeState::Resolved) => {
+                Ok(DisputeState::Disputed)
+            }
+            (TransactionType::Dispute, DisputeState::Disputed) => {
+                Err(PaymentError::transaction_already_disputed(tx, client))
+            }
+            (TransactionType::Resolve, DisputeState::Disputed) => Ok(DisputeState::Resolved),
+            (TransactionType::Chargeback, DisputeState::Disputed) => Ok(DisputeState::ChargedBack),
+            (operation, DisputeState::ChargedBack) => Err(PaymentError::transaction_charged_back(
+                tx,
+                client,
+                operation_name(operation),
+            )),
+            (operation, DisputeState::None | DisputeState::Resolved) => Err(
+                PaymentError::transaction_not_disputed(tx, client, operation_name(operation)),
+            ),
+        }
+    }
+}
+
+/// Lowercase name of a transaction type, as used in error messages
+pub(crate) fn operation_name(tx_type: TransactionType) -> &'static str {
+    match tx_type {
+        TransactionType::Deposit => "deposit",
+        TransactionType::Withdrawal => "withdrawal",
+        TransactionType::Dispute => "dispute",
+        TransactionType::Resolve => "resolve",
+        TransactionType::Chargeback => "chargeback",
+        TransactionType::Approve => "approve",
+        TransactionType::Reject => "reject",
+    }
 }
 
 /// Stored transaction for dispute resolution
@@ -83,7 +278,7 @@ pub struct TransactionRecord {
 /// Only deposits and withdrawals are stored, as these are the only
 /// transaction types that can be disputed. This optimizes memory usage
 /// by not storing dispute/resolve/chargeback operations.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StoredTransaction {
     /// The client ID that owns this transaction
     pub client: ClientId,
@@ -94,9 +289,157 @@ pub struct StoredTransaction {
     /// The transaction type (only Deposit or Withdrawal are stored)
     pub tx_type: TransactionType,
 
-    /// Whether this transaction is currently disputed
+    /// Where this transaction is in the dispute lifecycle
     ///
-    /// Set to true when a dispute is processed, false when resolved.
-    /// Used to prevent duplicate disputes and validate resolve/chargeback operations.
-    pub under_dispute: bool,
+    /// Used to prevent duplicate disputes, validate resolve/chargeback
+    /// operations and reject any operation on a charged-back transaction.
+    pub dispute_state: DisputeState,
+
+    /// Number of times this transaction has been disputed
+    ///
+    /// Used to enforce the re-dispute policy on resolved transactions.
+    pub disputes: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::dispute_new(
+        DisputeState::None,
+        TransactionType::Dispute,
+        Ok(DisputeState::Disputed)
+    )]
+    #[case::dispute_resolved(
+        DisputeState::Resolved,
+        TransactionType::Dispute,
+        Ok(DisputeState::Disputed)
+    )]
+    #[case::resolve_disputed(
+        DisputeState::Disputed,
+        TransactionType::Resolve,
+        Ok(DisputeState::Resolved)
+    )]
+    #[case::chargeback_disputed(
+        DisputeState::Disputed,
+        TransactionType::Chargeback,
+        Ok(DisputeState::ChargedBack)
+    )]
+    #[case::deposit_unchanged(
+        DisputeState::Disputed,
+        TransactionType::Deposit,
+        Ok(DisputeState::Disputed)
+    )]
+    #[case::dispute_disputed(
+        DisputeState::Disputed,
+        TransactionType::Dispute,
+        Err(PaymentError::transaction_already_disputed(1, 2))
+    )]
+    #[case::resolve_new(
+        DisputeState::None,
+        TransactionType::Resolve,
+        Err(PaymentError::transaction_not_disputed(1, 2, "resolve"))
+    )]
+    #[case::chargeback_resolved(
+        DisputeState::Resolved,
+        TransactionType::Chargeback,
+        Err(PaymentError::transaction_not_disputed(1, 2, "chargeback"))
+    )]
+    #[case::dispute_charged_back(
+        DisputeState::ChargedBack,
+        TransactionType::Dispute,
+        Err(PaymentError::transaction_charged_back(1, 2, "dispute"))
+    )]
+    #[case::resolve_charged_back(
+        DisputeState::ChargedBack,
+        TransactionType::Resolve,
+        Err(PaymentError::transaction_charged_back(1, 2, "resolve"))
+    )]
+    #[case::chargeback_charged_back(
+        DisputeState::ChargedBack,
+        TransactionType::Chargeback,
+        Err(PaymentError::transaction_charged_back(1, 2, "chargeback"))
+    )]
+    #[case::approve_pending(
+        DisputeState::Pending,
+        TransactionType::Approve,
+        Ok(DisputeState::None)
+    )]
+    #[case::reject_pending(
+        DisputeState::Pending,
+        TransactionType::Reject,
+        Ok(DisputeState::Rejected)
+    )]
+    #[case::dispute_pending(
+        DisputeState::Pending,
+        TransactionType::Dispute,
+        Err(PaymentError::withdrawal_pending(1, 2, "dispute"))
+    )]
+    #[case::approve_new(
+        DisputeState::None,
+        TransactionType::Approve,
+        Err(PaymentError::withdrawal_not_pending(1, 2, "approve"))
+    )]
+    #[case::reject_charged_back(
+        DisputeState::ChargedBack,
+        TransactionType::Reject,
+        Err(PaymentError::withdrawal_not_pending(1, 2, "reject"))
+    )]
+    #[case::approve_rejected(
+        DisputeState::Rejected,
+        TransactionType::Approve,
+        Err(PaymentError::withdrawal_rejected(1, 2, "approve"))
+    )]
+    fn test_dispute_state_transition(
+        #[case] state: DisputeState,
+        #[case] operation: TransactionType,
+        #[case] expected: Result<DisputeState, PaymentError>,
+    ) {
+        assert_eq!(state.transition(operation, 1, 2), expected);
+    }
+
+    #[test]
+    fn test_transaction_record_serde_uses_csv_column_names() {
+        let record = TransactionRecord {
+            tx_type: TransactionType::Withdrawal,
+            client: 1,
+            tx: 7,
+            amount: Some(Decimal::new(15, 1)),
+            line: None,
+            source: None,
+        }
+        .with_line(3)
+        .with_source(Arc::from("day-1.csv"));
+
+        // The line and source are neither serialized nor compared
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"type": "withdrawal", "client": 1, "tx": 7, "amount": "1.5"})
+        );
+        assert_eq!(
+            serde_json::from_value::<TransactionRecord>(json).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn test_stored_transaction_serde_round_trip() {
+        let stored = StoredTransaction {
+            client: 2,
+            amount: Decimal::new(10000, 4),
+            tx_type: TransactionType::Deposit,
+            dispute_state: DisputeState::ChargedBack,
+            disputes: 1,
+        };
+
+        let json = serde_json::to_string(&stored).unwrap();
+        assert!(json.contains(r#""dispute_state":"charged_back""#));
+        assert_eq!(
+            serde_json::from_str::<StoredTransaction>(&json).unwrap(),
+            stored
+        );
+    }
 }
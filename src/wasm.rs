@@ -0,0 +1,61 @@
+//! Byte-oriented API for embedding the engine (feature `wasm`)
+//!
+//! Browser and other embedded hosts have no file system or threads. Built with
+//! `--no-default-features --features wasm`, the library compiles to
+//! `wasm32-unknown-unknown`, and `process_csv_bytes` runs the sync engine over
+//! CSV input held in memory, e.g. a file picked in a web page.
+
+use crate::core::{Engine, TransactionEngine};
+use crate::io::SyncReader;
+use crate::types::Account;
+
+/// Process a CSV file held in memory and return the final accounts
+///
+/// Records that fail to parse or are rejected by the engine are skipped, as
+/// they are by the CLI. A file whose header does not match the schema has no
+/// records that can be read, so it produces no accounts.
+///
+/// # Returns
+///
+/// The final account states, sorted by client ID
+pub fn process_csv_bytes(input: &[u8]) -> Vec<Account> {
+    let Ok(reader) = SyncReader::from_reader(input) else {
+        return Vec::new();
+    };
+
+    let mut engine = TransactionEngine::new();
+    for record in reader.flatten() {
+        // A rejected transaction leaves the accounts unchanged
+        let _ = engine.process(record);
+    }
+    Engine::get_accounts(&engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_process_csv_bytes() {
+        let accounts = process_csv_bytes(
+            b"type,client,tx,amount\n\
+              deposit,2,1,10.0\n\
+              deposit,1,2,5.0\n\
+              withdrawal,1,3,7.0\n\
+              dispute,2,1,\n\
+              bogus,3,4,1.0\n",
+        );
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].client, 1);
+        assert_eq!(accounts[0].available, Decimal::from(5));
+        assert_eq!(accounts[1].held, Decimal::from(10));
+    }
+
+    #[test]
+    fn test_process_csv_bytes_invalid_header() {
+        assert!(process_csv_bytes(b"kind,client,tx,amount\ndeposit,1,1,1.0\n").is_empty());
+        assert!(process_csv_bytes(b"").is_empty());
+    }
+}
@@ -19,7 +19,8 @@
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
-    use rust_payments_engine::cli::StrategyType;
+    use rust_payments_engine::cli::{InputFormat, StrategyType};
+    use rust_payments_engine::core::EngineConfig;
     use rust_payments_engine::strategy::create_strategy;
     use std::fs;
     use std::io::Write;
@@ -64,7 +65,13 @@ mod tests {
         );
 
         // Create processing strategy
-        let strategy = create_strategy(strategy_type.clone(), None);
+        let strategy = create_strategy(
+            strategy_type.clone(),
+            None,
+            InputFormat::Csv,
+            EngineConfig::default(),
+            None,
+        );
 
         // Create temporary output file
         let mut temp_output = NamedTempFile::new().expect("Failed to create temp file");
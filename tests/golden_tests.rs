@@ -0,0 +1,143 @@
+//! Golden-file tests of the command line
+//!
+//! Every directory in `tests/golden/` is a case, run through the compiled
+//! binary exactly as a user would run it:
+//!
+//! - `input*.csv`: the input files, passed in name order
+//! - `args` (optional): further arguments, separated by whitespace; lines
+//!   starting with `#` are comments. Paths are relative to the case directory,
+//!   which is the working directory of the run
+//! - `expected.csv`: the golden account output
+//!
+//! The output is normalized before comparing: line endings and trailing
+//! whitespace are dropped and the accounts are sorted, keeping the header
+//! first, as their order is not part of the output format. Unless `args`
+//! chooses a strategy, each case runs with both the sync and the async
+//! strategy against the same golden.
+//!
+//! # Blessing
+//!
+//! To add a case, create its directory with its inputs and arguments, and
+//! run the tests with `BLESS=1` to write its golden from the actual output;
+//! the same updates goldens after an intended change of behavior. Review the
+//! goldens' diff before committing them:
+//!
+//! ```bash
+//! BLESS=1 cargo test --test golden_tests
+//! git diff tests/golden
+//! ```
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// Whether to write goldens instead of comparing against them
+    fn blessing() -> bool {
+        std::env::var_os("BLESS").is_some_and(|value| !value.is_empty() && value != "0")
+    }
+
+    /// Arguments of a case, from its `args` file
+    fn case_args(case: &Path) -> Vec<String> {
+        let Ok(args) = fs::read_to_string(case.join("args")) else {
+            return Vec::new();
+        };
+        args.lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .flat_map(str::split_whitespace)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Input files of a case, in name order, relative to the case directory
+    fn case_inputs(case: &Path) -> Vec<String> {
+        let mut inputs: Vec<String> = fs::read_dir(case)
+            .unwrap_or_else(|e| panic!("Failed to list {}: {}", case.display(), e))
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("input") && name.ends_with(".csv"))
+            .collect();
+        inputs.sort();
+        assert!(!inputs.is_empty(), "No input*.csv in {}", case.display());
+        inputs
+    }
+
+    /// Output with line endings and trailing whitespace dropped, and the
+    /// lines after the header sorted
+    fn normalize(output: &str) -> String {
+        let mut lines = output.lines().map(str::trim_end);
+        let header = lines.next().unwrap_or_default();
+        let mut accounts: Vec<&str> = lines.filter(|line| !line.is_empty()).collect();
+        accounts.sort_unstable();
+        std::iter::once(header)
+            .chain(accounts)
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    /// Run the binary on a case, returning its normalized output
+    fn run_case(case: &Path, strategy: Option<&str>) -> String {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rust-payments-engine"));
+        command.current_dir(case).args(case_args(case));
+        if let Some(strategy) = strategy {
+            command.args(["--strategy", strategy]);
+        }
+        let output = command
+            .args(case_inputs(case))
+            .output()
+            .unwrap_or_else(|e| panic!("Failed to run the binary: {}", e));
+        assert!(
+            output.status.success(),
+            "Case {} (strategy: {:?}) failed with {}:\n{}",
+            case.display(),
+            strategy,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        normalize(&String::from_utf8(output.stdout).expect("Output is not UTF-8"))
+    }
+
+    #[rstest]
+    fn golden(
+        #[files("tests/golden/*")]
+        #[dirs]
+        case: PathBuf,
+    ) {
+        let strategies = if case_args(&case).iter().any(|arg| arg == "--strategy") {
+            vec![None]
+        } else {
+            vec![Some("sync"), Some("async")]
+        };
+        let expected_path = case.join("expected.csv");
+        let mut expected = (!blessing()).then(|| {
+            let expected = fs::read_to_string(&expected_path).unwrap_or_else(|_| {
+                panic!(
+                    "No golden {}; run with BLESS=1 to write it",
+                    expected_path.display()
+                )
+            });
+            normalize(&expected)
+        });
+
+        for strategy in strategies {
+            let actual = run_case(&case, strategy);
+            // When blessing, the first strategy writes the golden and the
+            // others must still agree with it
+            let expected = expected.get_or_insert_with(|| {
+                fs::write(&expected_path, &actual).unwrap_or_else(|e| {
+                    panic!("Failed to write {}: {}", expected_path.display(), e)
+                });
+                actual.clone()
+            });
+            assert_eq!(
+                actual,
+                *expected,
+                "\n\nOutput mismatch for case {} (strategy: {:?}); if the change is \
+                 intended, run with BLESS=1 to update the golden\n",
+                case.display(),
+                strategy
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Model checking of the async engine's interleavings with loom
+//!
+//! The async strategy never processes one client's transactions concurrently,
+//! but the engine is shared and must stay consistent if it is. Each test races
+//! two transactions of the same client on an `AsyncTransactionEngine`, and
+//! loom runs it once for every order of their accesses to the account and
+//! transaction maps. Every run must end as one of the two serial orders does,
+//! as processed by the synchronous engine: same results, same account.
+//!
+//! The tests only exist when built with `--cfg payments_loom`:
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg payments_loom" cargo test --release --test loom_tests
+//! ```
+
+#![cfg(payments_loom)]
+
+use rust_decimal::Decimal;
+use rust_payments_engine::core::{
+    AsyncAccountManager, AsyncTransactionEngine, AsyncTransactionStore, Engine, TransactionEngine,
+};
+use rust_payments_engine::types::{Account, ClientId, TransactionRecord, TransactionType};
+use std::sync::Arc;
+
+/// Client of every transaction
+const CLIENT: ClientId = 1;
+
+fn record(tx_type: TransactionType, tx: u64, amount: Option<i64>) -> TransactionRecord {
+    TransactionRecord {
+        tx_type,
+        client: CLIENT,
+        tx,
+        amount: amount.map(Decimal::from),
+        line: None,
+        source: None,
+    }
+}
+
+/// Results of `first` and `second` and the client's account at the end
+type Outcome = (bool, bool, Account);
+
+/// Outcome of processing `setup`, then `first` and `second` in that order,
+/// with the synchronous engine
+fn serial_outcome(
+    setup: &[TransactionRecord],
+    first: &TransactionRecord,
+    second: &TransactionRecord,
+) -> Outcome {
+    let mut engine = TransactionEngine::new();
+    for record in setup {
+        engine.process(record.clone()).unwrap();
+    }
+    let first_ok = engine.process(first.clone()).is_ok();
+    let second_ok = engine.process(second.clone()).is_ok();
+    (first_ok, second_ok, engine.account(CLIENT).unwrap().clone())
+}
+
+/// Race `first` and `second` after `setup` in every interleaving, and check
+/// each ends as one of the serial orders
+fn check_race(setup: Vec<TransactionRecord>, first: TransactionRecord, second: TransactionRecord) {
+    let serial = [serial_outcome(&setup, &first, &second), {
+        let (second_ok, first_ok, account) = serial_outcome(&setup, &second, &first);
+        (first_ok, second_ok, account)
+    }];
+
+    loom::model(move || {
+        let engine = Arc::new(AsyncTransactionEngine::new(
+            Arc::new(AsyncAccountManager::new()),
+            Arc::new(AsyncTransactionStore::new()),
+        ));
+        for record in &setup {
+            engine.process_transaction(record.clone()).unwrap();
+        }
+
+        let racer = {
+            let engine = Arc::clone(&engine);
+            let first = first.clone();
+            loom::thread::spawn(move || engine.process_transaction(first).is_ok())
+        };
+        let second_ok = engine.process_transaction(second.clone()).is_ok();
+        let first_ok = racer.join().unwrap();
+
+        let account = engine.get_accounts().pop().unwrap();
+        assert_eq!(account.total, account.available + account.held);
+        let outcome = (first_ok, second_ok, account);
+        assert!(
+            serial.contains(&outcome),
+            "{:?} is neither serial order: {:?}",
+            outcome,
+            serial
+        );
+    });
+}
+
+#[test]
+fn dispute_races_withdrawal() {
+    // Disputing the deposit holds all of it, so whichever comes second fails
+    check_race(
+        vec![record(TransactionType::Deposit, 1, Some(10))],
+        record(TransactionType::Dispute, 1, None),
+        record(TransactionType::Withdrawal, 2, Some(5)),
+    );
+}
+
+#[test]
+fn chargeback_races_deposit() {
+    // The chargeback locks the account, rejecting a deposit checked after it
+    check_race(
+        vec![
+            record(TransactionType::Deposit, 1, Some(10)),
+            record(TransactionType::Dispute, 1, None),
+        ],
+        record(TransactionType::Chargeback, 1, None),
+        record(TransactionType::Deposit, 2, Some(5)),
+    );
+}
+
+#[test]
+fn chargeback_races_resolve() {
+    // Only one of the two settles the dispute
+    check_race(
+        vec![
+            record(TransactionType::Deposit, 1, Some(10)),
+            record(TransactionType::Dispute, 1, None),
+        ],
+        record(TransactionType::Chargeback, 1, None),
+        record(TransactionType::Resolve, 1, None),
+    );
+}
+
+#[test]
+fn withdrawals_race_for_funds() {
+    // Only one of the two fits in the balance
+    check_race(
+        vec![record(TransactionType::Deposit, 1, Some(10))],
+        record(TransactionType::Withdrawal, 2, Some(6)),
+        record(TransactionType::Withdrawal, 3, Some(6)),
+    );
+}